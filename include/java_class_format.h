@@ -0,0 +1,57 @@
+/* C API for java-class-format, built as a shared library with
+   `cargo rustc --lib --release --crate-type cdylib --features ffi`. */
+
+#ifndef JAVA_CLASS_FORMAT_H
+#define JAVA_CLASS_FORMAT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct ClassFile ClassFile;
+typedef struct Instructions Instructions;
+
+/* The last error on this thread, or NULL. Valid until the next failing call. */
+const char *jcf_last_error(void);
+
+/* Releases a `char *` returned by any function below. */
+void jcf_string_free(char *string);
+
+/* Parses a class file; `data` is copied. Returns NULL on error. */
+ClassFile *jcf_class_open(const uint8_t *data, size_t len);
+void jcf_class_free(ClassFile *class_file);
+
+char *jcf_class_name(const ClassFile *class_file);
+/* NULL for java/lang/Object and module descriptors. */
+char *jcf_class_super_name(const ClassFile *class_file);
+uint16_t jcf_class_access_flags(const ClassFile *class_file);
+uint16_t jcf_class_major_version(const ClassFile *class_file);
+size_t jcf_class_field_count(const ClassFile *class_file);
+size_t jcf_class_method_count(const ClassFile *class_file);
+
+char *jcf_field_name(const ClassFile *class_file, size_t index);
+char *jcf_field_descriptor(const ClassFile *class_file, size_t index);
+uint16_t jcf_field_access_flags(const ClassFile *class_file, size_t index);
+
+char *jcf_method_name(const ClassFile *class_file, size_t index);
+char *jcf_method_descriptor(const ClassFile *class_file, size_t index);
+uint16_t jcf_method_access_flags(const ClassFile *class_file, size_t index);
+
+/* NULL if the method has no code or it fails to decode. */
+Instructions *jcf_method_instructions(const ClassFile *class_file, size_t index);
+size_t jcf_instructions_len(const Instructions *instructions);
+/* -1 if `index` is out of range. */
+int64_t jcf_instructions_pc(const Instructions *instructions, size_t index);
+/* As written by the text assembly format, e.g. `goto L12`. Borrowed from
+   `instructions`. */
+const char *jcf_instructions_text(const Instructions *instructions, size_t index);
+void jcf_instructions_free(Instructions *instructions);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
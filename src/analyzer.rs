@@ -0,0 +1,826 @@
+//! Infers the types of the locals and operand stack before every instruction
+//! by simulating the code over the control-flow graph, as the verifier does.
+//! See [`Method::frames`].
+
+use crate::{
+    attributes::{Code, StackMapFrame, VerificationTypeInfo},
+    cfg::{ControlFlowGraph, Edge, EdgeKind},
+    dataflow::{self, Analysis, Direction, Lattice, Solution},
+    field::TypeDescriptor,
+    instruction::{Instruction, InstructionAt, LoadableConstant, MaybeInterfaceMethodRef},
+    method::Method,
+    raw::MethodAccessFlags,
+    Error, Result,
+};
+
+/// The type of a local or stack slot, after the `verification_type_info` of
+/// stack map frames (§4.10.1.2). `boolean`, `byte`, `char` and `short` are all
+/// `Integer`, and a `long` or `double` takes two slots, the second `Top`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationType<'a> {
+    /// Unusable: never written, the second half of a two slot value, or
+    /// written with types that do not agree.
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    /// `this` in a constructor before the superclass constructor is called.
+    UninitializedThis,
+    /// A class or array type.
+    Object(TypeDescriptor<'a>),
+    /// An object created by the `new` at `offset` whose constructor has not
+    /// been called yet.
+    Uninitialized { offset: u32 },
+}
+
+impl<'a> VerificationType<'a> {
+    pub(crate) fn from_descriptor(descriptor: &TypeDescriptor<'a>) -> Self {
+        match descriptor {
+            TypeDescriptor::Byte
+            | TypeDescriptor::Char
+            | TypeDescriptor::Int
+            | TypeDescriptor::Short
+            | TypeDescriptor::Boolean => Self::Integer,
+            TypeDescriptor::Float => Self::Float,
+            TypeDescriptor::Long => Self::Long,
+            TypeDescriptor::Double => Self::Double,
+            x => Self::Object(x.clone()),
+        }
+    }
+
+    /// Whether the value takes two slots.
+    pub fn is_wide(&self) -> bool {
+        matches!(self, Self::Long | Self::Double)
+    }
+
+    /// The type of a slot reached with `self` along one path and `other`
+    /// along another. Without the class hierarchy, two different reference
+    /// types merge to `java/lang/Object`, a common supertype if not the least.
+    fn merge(&self, other: &Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a.clone(),
+            (Self::Null, Self::Object(x)) | (Self::Object(x), Self::Null) => Self::Object(x.clone()),
+            (Self::Object(_), Self::Object(_)) => Self::Object(TypeDescriptor::ClassName("java/lang/Object")),
+            _ => Self::Top,
+        }
+    }
+}
+
+/// Writes the type as the JVMS names it, with the descriptor of a class or
+/// array type, e.g. `int` or `Ljava/lang/String;`.
+impl std::fmt::Display for VerificationType<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Top => f.write_str("top"),
+            Self::Integer => f.write_str("int"),
+            Self::Float => f.write_str("float"),
+            Self::Long => f.write_str("long"),
+            Self::Double => f.write_str("double"),
+            Self::Null => f.write_str("null"),
+            Self::UninitializedThis => f.write_str("uninitializedThis"),
+            Self::Object(x) => write!(f, "{}", x),
+            Self::Uninitialized { offset } => write!(f, "uninitialized({})", offset),
+        }
+    }
+}
+
+/// The types of the locals and operand stack at an instruction. Both have an
+/// entry per slot, so a `long` on the stack is `Long` followed by `Top`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub locals: Vec<VerificationType<'a>>,
+    pub stack: Vec<VerificationType<'a>>,
+}
+
+/// Writes the frame as its locals and stack, e.g.
+/// `locals [int, Ljava/lang/String;] stack [long, top]`.
+impl std::fmt::Display for Frame<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (name, types)) in [("locals", &self.locals), ("stack", &self.stack)].into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{} [", name)?;
+            for (j, x) in types.iter().enumerate() {
+                if j > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", x)?;
+            }
+            f.write_str("]")?;
+        }
+        Ok(())
+    }
+}
+
+/// The first frame of a `StackMapTable` that does not hold for the code, see
+/// [`Method::stack_map_divergence`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameDivergence<'a> {
+    pub pc: u32,
+    /// The frame the table declares.
+    pub declared: Frame<'a>,
+    /// The frame simulating the code gives, or `None` if no instruction
+    /// starts at `pc`.
+    pub computed: Option<Frame<'a>>,
+}
+
+/// The frame before every instruction, see [`Method::frames`].
+#[derive(Debug)]
+pub struct Frames<'a> {
+    frames: Vec<(u32, Option<Frame<'a>>)>,
+}
+
+impl<'a> Frames<'a> {
+    /// The frame before the instruction at `pc`, or `None` if no instruction
+    /// starts there or it cannot be reached.
+    pub fn at(&self, pc: u32) -> Option<&Frame<'a>> {
+        let i = self.frames.binary_search_by_key(&pc, |x| x.0).ok()?;
+        self.frames[i].1.as_ref()
+    }
+
+    /// Every instruction's pc and frame, in code order.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, Option<&Frame<'a>>)> {
+        self.frames.iter().map(|(pc, x)| (*pc, x.as_ref()))
+    }
+}
+
+/// A frame, or the first problem found on the way to it with its pc if it
+/// has one. Problems are kept as facts, since the solver's transfer functions
+/// cannot fail.
+#[derive(Debug, Clone)]
+pub(crate) struct State<'a> {
+    frame: Option<Frame<'a>>,
+    error: Option<(Option<u32>, String)>,
+}
+
+impl Lattice for State<'_> {
+    fn join(&mut self, other: &Self) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+        if other.error.is_some() {
+            self.error = other.error.clone();
+            return true;
+        }
+        let (a, b) = match (&mut self.frame, &other.frame) {
+            (_, None) => return false,
+            (None, Some(b)) => {
+                self.frame = Some(b.clone());
+                return true;
+            }
+            (Some(a), Some(b)) => (a, b),
+        };
+        if a.stack.len() != b.stack.len() || a.locals.len() != b.locals.len() {
+            self.error = Some((None, format!("frames with stacks of {} and {} slots meet", a.stack.len(), b.stack.len())));
+            return true;
+        }
+        let mut changed = false;
+        for (x, y) in a.locals.iter_mut().chain(a.stack.iter_mut()).zip(b.locals.iter().chain(&b.stack)) {
+            let merged = x.merge(y);
+            if merged != *x {
+                *x = merged;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+pub(crate) struct Analyzer<'a> {
+    this_class: &'a str,
+    entry: Frame<'a>,
+}
+
+impl<'a> Analyzer<'a> {
+    /// An analyzer starting from `method`'s parameters, with the rest of
+    /// its `max_locals` slots `Top`.
+    pub(crate) fn new(method: &Method<'a>, code: &Code<'a>) -> Result<Self> {
+        let this_class = method.class_file.this_class()?;
+
+        let mut locals = Vec::new();
+        if !method.method_inner.access_flags.contains(MethodAccessFlags::STATIC) {
+            locals.push(if method.identifier()? == "<init>" {
+                VerificationType::UninitializedThis
+            } else {
+                VerificationType::Object(TypeDescriptor::ClassName(this_class))
+            });
+        }
+        for ty in method.descriptor()?.parameter_types() {
+            let ty = VerificationType::from_descriptor(ty);
+            let wide = ty.is_wide();
+            locals.push(ty);
+            if wide {
+                locals.push(VerificationType::Top);
+            }
+        }
+        if locals.len() > code.max_locals as usize {
+            return Err(Error::AnalysisError {
+                pc: 0,
+                message: format!("parameters take {} slots, past max_locals", locals.len()),
+            });
+        }
+        locals.resize(code.max_locals as usize, VerificationType::Top);
+
+        Ok(Self {
+            this_class,
+            entry: Frame {
+                locals,
+                stack: Vec::new(),
+            },
+        })
+    }
+
+    /// The frame on entry to the method.
+    pub(crate) fn entry(&self) -> &Frame<'a> {
+        &self.entry
+    }
+
+    /// The type named by a `CONSTANT_Class`, which is an array descriptor for
+    /// array classes.
+    pub(crate) fn class_type(&self, name: &'a str) -> std::result::Result<TypeDescriptor<'a>, String> {
+        if name.starts_with('[') {
+            return TypeDescriptor::parse(name).map(|x| x.1).map_err(|x| crate::Error::from(x).to_string());
+        }
+        Ok(match name {
+            "java/lang/String" => TypeDescriptor::String,
+            "java/lang/Class" => TypeDescriptor::Class,
+            x => TypeDescriptor::ClassName(x),
+        })
+    }
+
+    pub(crate) fn object(&self, name: &'a str) -> std::result::Result<VerificationType<'a>, String> {
+        Ok(VerificationType::Object(self.class_type(name)?))
+    }
+
+    fn constant(&self, constant: &LoadableConstant<'a>) -> VerificationType<'a> {
+        match constant {
+            LoadableConstant::Int(_) => VerificationType::Integer,
+            LoadableConstant::Float(_) => VerificationType::Float,
+            LoadableConstant::Long(_) => VerificationType::Long,
+            LoadableConstant::Double(_) => VerificationType::Double,
+            LoadableConstant::String(_) => VerificationType::Object(TypeDescriptor::String),
+            LoadableConstant::Class(_) => VerificationType::Object(TypeDescriptor::Class),
+            LoadableConstant::MethodType(_) => {
+                VerificationType::Object(TypeDescriptor::ClassName("java/lang/invoke/MethodType"))
+            }
+            LoadableConstant::MethodHandle(_) => {
+                VerificationType::Object(TypeDescriptor::ClassName("java/lang/invoke/MethodHandle"))
+            }
+            LoadableConstant::Dynamic(x) => VerificationType::from_descriptor(&x.descriptor),
+        }
+    }
+
+    /// Simulates `instruction` on `frame`, which holds the types before it.
+    pub(crate) fn execute(&self, frame: &mut Frame<'a>, at: &InstructionAt<'a>) -> std::result::Result<(), String> {
+        let pops = at.instruction.stack_effect().pops as usize;
+        if frame.stack.len() < pops {
+            return Err(format!("{} pops {} slots from a stack of {}", at.instruction.mnemonic(), pops, frame.stack.len()));
+        }
+        if let Some(x) = at.instruction.local_access() {
+            let last = x.index as usize + x.slots as usize - 1;
+            if last >= frame.locals.len() {
+                return Err(format!("local {} is past max_locals", last));
+            }
+        }
+        let popped = frame.stack.split_off(frame.stack.len() - pops);
+        let pushed = match &at.instruction {
+            Instruction::Aload { index } | Instruction::WideAload { index } => Some(local(frame, index.0)?),
+            Instruction::Aload0 => Some(local(frame, 0)?),
+            Instruction::Aload1 => Some(local(frame, 1)?),
+            Instruction::Aload2 => Some(local(frame, 2)?),
+            Instruction::Aload3 => Some(local(frame, 3)?),
+
+            Instruction::Istore { index }
+            | Instruction::Fstore { index }
+            | Instruction::Astore { index }
+            | Instruction::Lstore { index }
+            | Instruction::Dstore { index }
+            | Instruction::WideIstore { index }
+            | Instruction::WideFstore { index }
+            | Instruction::WideAstore { index }
+            | Instruction::WideLstore { index }
+            | Instruction::WideDstore { index } => store(frame, index.0, &popped[0])?,
+            Instruction::Istore0
+            | Instruction::Fstore0
+            | Instruction::Astore0
+            | Instruction::Lstore0
+            | Instruction::Dstore0 => store(frame, 0, &popped[0])?,
+            Instruction::Istore1
+            | Instruction::Fstore1
+            | Instruction::Astore1
+            | Instruction::Lstore1
+            | Instruction::Dstore1 => store(frame, 1, &popped[0])?,
+            Instruction::Istore2
+            | Instruction::Fstore2
+            | Instruction::Astore2
+            | Instruction::Lstore2
+            | Instruction::Dstore2 => store(frame, 2, &popped[0])?,
+            Instruction::Istore3
+            | Instruction::Fstore3
+            | Instruction::Astore3
+            | Instruction::Lstore3
+            | Instruction::Dstore3 => store(frame, 3, &popped[0])?,
+
+            // The stack has a slot per word, so these move slots as they are.
+            Instruction::Dup => {
+                frame.stack.extend([&popped[0], &popped[0]].map(Clone::clone));
+                None
+            }
+            Instruction::DupX1 => {
+                frame.stack.extend([&popped[1], &popped[0], &popped[1]].map(Clone::clone));
+                None
+            }
+            Instruction::DupX2 => {
+                frame.stack.extend([&popped[2], &popped[0], &popped[1], &popped[2]].map(Clone::clone));
+                None
+            }
+            Instruction::Dup2 => {
+                frame.stack.extend([&popped[0], &popped[1], &popped[0], &popped[1]].map(Clone::clone));
+                None
+            }
+            Instruction::Dup2X1 => {
+                frame
+                    .stack
+                    .extend([&popped[1], &popped[2], &popped[0], &popped[1], &popped[2]].map(Clone::clone));
+                None
+            }
+            Instruction::Dup2X2 => {
+                frame.stack.extend(
+                    [&popped[2], &popped[3], &popped[0], &popped[1], &popped[2], &popped[3]].map(Clone::clone),
+                );
+                None
+            }
+            Instruction::Swap => {
+                frame.stack.extend([&popped[1], &popped[0]].map(Clone::clone));
+                None
+            }
+
+            Instruction::AconstNull => Some(VerificationType::Null),
+            Instruction::IconstM1
+            | Instruction::Iconst0
+            | Instruction::Iconst1
+            | Instruction::Iconst2
+            | Instruction::Iconst3
+            | Instruction::Iconst4
+            | Instruction::Iconst5
+            | Instruction::Bipush { .. }
+            | Instruction::Sipush { .. }
+            | Instruction::Iload { .. }
+            | Instruction::WideIload { .. }
+            | Instruction::Iload0
+            | Instruction::Iload1
+            | Instruction::Iload2
+            | Instruction::Iload3
+            | Instruction::Iaload
+            | Instruction::Baload
+            | Instruction::Caload
+            | Instruction::Saload
+            | Instruction::Iadd
+            | Instruction::Isub
+            | Instruction::Imul
+            | Instruction::Idiv
+            | Instruction::Irem
+            | Instruction::Ineg
+            | Instruction::Ishl
+            | Instruction::Ishr
+            | Instruction::Iushr
+            | Instruction::Iand
+            | Instruction::Ior
+            | Instruction::Ixor
+            | Instruction::L2i
+            | Instruction::F2i
+            | Instruction::D2i
+            | Instruction::I2b
+            | Instruction::I2c
+            | Instruction::I2s
+            | Instruction::Lcmp
+            | Instruction::Fcmpl
+            | Instruction::Fcmpg
+            | Instruction::Dcmpl
+            | Instruction::Dcmpg
+            | Instruction::Arraylength
+            | Instruction::Instanceof { .. } => Some(VerificationType::Integer),
+            Instruction::Fconst0
+            | Instruction::Fconst1
+            | Instruction::Fconst2
+            | Instruction::Fload { .. }
+            | Instruction::WideFload { .. }
+            | Instruction::Fload0
+            | Instruction::Fload1
+            | Instruction::Fload2
+            | Instruction::Fload3
+            | Instruction::Faload
+            | Instruction::Fadd
+            | Instruction::Fsub
+            | Instruction::Fmul
+            | Instruction::Fdiv
+            | Instruction::Frem
+            | Instruction::Fneg
+            | Instruction::I2f
+            | Instruction::L2f
+            | Instruction::D2f => Some(VerificationType::Float),
+            Instruction::Lconst0
+            | Instruction::Lconst1
+            | Instruction::Lload { .. }
+            | Instruction::WideLload { .. }
+            | Instruction::Lload0
+            | Instruction::Lload1
+            | Instruction::Lload2
+            | Instruction::Lload3
+            | Instruction::Laload
+            | Instruction::Ladd
+            | Instruction::Lsub
+            | Instruction::Lmul
+            | Instruction::Ldiv
+            | Instruction::Lrem
+            | Instruction::Lneg
+            | Instruction::Lshl
+            | Instruction::Lshr
+            | Instruction::Lushr
+            | Instruction::Land
+            | Instruction::Lor
+            | Instruction::Lxor
+            | Instruction::I2l
+            | Instruction::F2l
+            | Instruction::D2l => Some(VerificationType::Long),
+            Instruction::Dconst0
+            | Instruction::Dconst1
+            | Instruction::Dload { .. }
+            | Instruction::WideDload { .. }
+            | Instruction::Dload0
+            | Instruction::Dload1
+            | Instruction::Dload2
+            | Instruction::Dload3
+            | Instruction::Daload
+            | Instruction::Dadd
+            | Instruction::Dsub
+            | Instruction::Dmul
+            | Instruction::Ddiv
+            | Instruction::Drem
+            | Instruction::Dneg
+            | Instruction::I2d
+            | Instruction::L2d
+            | Instruction::F2d => Some(VerificationType::Double),
+
+            Instruction::Ldc { constant, .. } | Instruction::LdcW { constant, .. } | Instruction::Ldc2W { constant, .. } => {
+                Some(self.constant(constant))
+            }
+            Instruction::Aaload => Some(match &popped[0] {
+                VerificationType::Object(TypeDescriptor::Array(x)) => VerificationType::from_descriptor(x),
+                VerificationType::Null => VerificationType::Null,
+                _ => VerificationType::Object(TypeDescriptor::ClassName("java/lang/Object")),
+            }),
+            // The return address is not tracked, so `ret` reads nothing useful.
+            Instruction::Jsr { .. } | Instruction::JsrW { .. } => Some(VerificationType::Top),
+
+            Instruction::Getstatic { field } | Instruction::Getfield { field } => {
+                Some(VerificationType::from_descriptor(&field.descriptor))
+            }
+            Instruction::Invokevirtual { index } => index.descriptor.return_type().map(VerificationType::from_descriptor),
+            Instruction::Invokeinterface { index, .. } => {
+                index.descriptor.return_type().map(VerificationType::from_descriptor)
+            }
+            Instruction::Invokestatic { index } => index.descriptor().return_type().map(VerificationType::from_descriptor),
+            Instruction::Invokedynamic { index, .. } => {
+                index.descriptor.return_type().map(VerificationType::from_descriptor)
+            }
+            Instruction::Invokespecial { index } => {
+                let (class, name) = match index {
+                    MaybeInterfaceMethodRef::RegularMethod(x) => (x.class, x.name),
+                    MaybeInterfaceMethodRef::InterfaceMethod(x) => (x.class, x.name),
+                };
+                if name == "<init>" {
+                    let initialized = match &popped[0] {
+                        VerificationType::UninitializedThis => self.object(self.this_class)?,
+                        VerificationType::Uninitialized { .. } => self.object(class)?,
+                        x => return Err(format!("<init> called on {:?}", x)),
+                    };
+                    for x in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+                        if *x == popped[0] {
+                            *x = initialized.clone();
+                        }
+                    }
+                }
+                index.descriptor().return_type().map(VerificationType::from_descriptor)
+            }
+
+            Instruction::New { .. } => Some(VerificationType::Uninitialized { offset: at.pc }),
+            Instruction::Checkcast { class } | Instruction::Multianewarray { class, .. } => Some(self.object(class)?),
+            Instruction::Anewarray { class } => Some(VerificationType::Object(TypeDescriptor::Array(Box::new(
+                self.class_type(class)?,
+            )))),
+            Instruction::Newarray { atype } => {
+                Some(VerificationType::Object(TypeDescriptor::Array(Box::new(atype.descriptor()))))
+            }
+
+            // Stores of the other kinds, branches, returns and the rest only pop.
+            _ => None,
+        };
+        if let Some(x) = pushed {
+            let wide = x.is_wide();
+            frame.stack.push(x);
+            if wide {
+                frame.stack.push(VerificationType::Top);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn local<'a>(frame: &Frame<'a>, index: u16) -> std::result::Result<VerificationType<'a>, String> {
+    match frame.locals.get(index as usize) {
+        Some(x) => Ok(x.clone()),
+        None => Err(format!("local {} is past max_locals", index)),
+    }
+}
+
+/// Writes `value` to local `index`, clearing any two slot value it overlaps.
+fn store<'a>(
+    frame: &mut Frame<'a>,
+    index: u16,
+    value: &VerificationType<'a>,
+) -> std::result::Result<Option<VerificationType<'a>>, String> {
+    let index = index as usize;
+    let size = if value.is_wide() { 2 } else { 1 };
+    if index + size > frame.locals.len() {
+        return Err(format!("local {} is past max_locals", index + size - 1));
+    }
+    if index > 0 && frame.locals[index - 1].is_wide() {
+        frame.locals[index - 1] = VerificationType::Top;
+    }
+    frame.locals[index] = value.clone();
+    if size == 2 {
+        frame.locals[index + 1] = VerificationType::Top;
+    }
+    Ok(None)
+}
+
+impl<'a> Analysis<'a> for Analyzer<'a> {
+    type Fact = State<'a>;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self, _: &ControlFlowGraph<'a>) -> Self::Fact {
+        State { frame: None, error: None }
+    }
+
+    fn boundary(&self, _: &ControlFlowGraph<'a>) -> Self::Fact {
+        State {
+            frame: Some(self.entry.clone()),
+            error: None,
+        }
+    }
+
+    fn transfer(&self, fact: &mut Self::Fact, instruction: &InstructionAt<'a>) {
+        if fact.error.is_some() {
+            return;
+        }
+        if let Some(frame) = &mut fact.frame {
+            if let Err(message) = self.execute(frame, instruction) {
+                fact.error = Some((Some(instruction.pc), message));
+            }
+        }
+    }
+
+    fn transfer_edge(&self, fact: &mut Self::Fact, edge: &Edge<'a>) {
+        if let (EdgeKind::Exception { catch_type }, Some(frame)) = (edge.kind, &mut fact.frame) {
+            match self.object(catch_type.unwrap_or("java/lang/Throwable")) {
+                Ok(x) => frame.stack = vec![x],
+                Err(message) => fact.error = Some((None, message)),
+            }
+        }
+    }
+}
+
+/// The problem the analysis ran into, if any. Those found where frames meet
+/// have no pc of their own, so they are given the start of the block they
+/// first reach, the one whose predecessors all end without a problem.
+fn first_error(graph: &ControlFlowGraph, solution: &Solution<State>) -> Option<Error> {
+    let mut merged = None;
+    for (block, x) in graph.blocks().iter().enumerate() {
+        match &solution.after(block).error {
+            Some((Some(pc), message)) => {
+                return Some(Error::AnalysisError {
+                    pc: *pc,
+                    message: message.clone(),
+                })
+            }
+            Some((None, message))
+                if merged.is_none() && graph.predecessors(block).all(|x| solution.after(x.from).error.is_none()) =>
+            {
+                merged = Some(Error::AnalysisError {
+                    pc: x.start_pc,
+                    message: message.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+    merged
+}
+
+impl<'a> Method<'a> {
+    /// Infers the types of the locals and stack before every instruction, or
+    /// returns `None` for a method without code. Fails with
+    /// [`Error::AnalysisError`] if the code underflows the stack, uses a
+    /// local past `max_locals`, or reaches an instruction with different
+    /// stack depths. Unlike the verifier it does not check that values are
+    /// used at the right types, so an `iload` of a local holding a reference
+    /// pushes an `int` all the same, and it ignores any `StackMapTable`.
+    pub fn frames(&self) -> Result<Option<Frames<'a>>> {
+        let code = match self.code()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let graph = code.control_flow_graph()?;
+        let analyzer = Analyzer::new(self, &code)?;
+        let solution = dataflow::solve(&graph, &analyzer);
+        if let Some(x) = first_error(&graph, &solution) {
+            return Err(x);
+        }
+        let mut frames = Vec::with_capacity(graph.instructions().len());
+        for block in 0..graph.blocks().len() {
+            let facts = solution.instruction_facts(&graph, &analyzer, block);
+            for (instruction, fact) in graph.block_instructions(block).iter().zip(facts) {
+                frames.push((instruction.pc, fact.frame));
+            }
+        }
+        Ok(Some(Frames { frames }))
+    }
+
+    /// The frames recorded in the code's `StackMapTable`, each with its pc
+    /// and expanded from its compressed form against the one before, the
+    /// first against the frame on entry. Returns `None` for a method without
+    /// code or without the table. Fails with [`Error::AnalysisError`] if a
+    /// frame removes more locals than there are or has more than
+    /// `max_locals`.
+    pub fn stack_map_frames(&self) -> Result<Option<Vec<(u32, Frame<'a>)>>> {
+        let code = match self.code()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let table = match code.stack_map_table()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let analyzer = Analyzer::new(self, &code)?;
+        let max_locals = code.max_locals as usize;
+        let convert = |x: &VerificationTypeInfo| -> Result<VerificationType<'a>> {
+            Ok(match x {
+                VerificationTypeInfo::Top => VerificationType::Top,
+                VerificationTypeInfo::Integer => VerificationType::Integer,
+                VerificationTypeInfo::Float => VerificationType::Float,
+                VerificationTypeInfo::Long => VerificationType::Long,
+                VerificationTypeInfo::Double => VerificationType::Double,
+                VerificationTypeInfo::Null => VerificationType::Null,
+                VerificationTypeInfo::UninitializedThis => VerificationType::UninitializedThis,
+                VerificationTypeInfo::Object { cpool_index } => {
+                    let name = cpool_index.get_as_string(self.class_file)?;
+                    analyzer.object(name).map_err(Error::ConstantPoolError)?
+                }
+                VerificationTypeInfo::Uninitialized { offset } => VerificationType::Uninitialized { offset: *offset as u32 },
+            })
+        };
+
+        // The table lists a `long` or `double` once, and its locals stop at
+        // the last one in use.
+        let mut locals = Vec::new();
+        let mut entry = analyzer.entry().locals.iter();
+        while let Some(x) = entry.next() {
+            if x.is_wide() {
+                entry.next();
+            }
+            locals.push(x.clone());
+        }
+        while locals.last() == Some(&VerificationType::Top) {
+            locals.pop();
+        }
+
+        let mut res: Vec<(u32, Frame<'a>)> = Vec::new();
+        for x in table.entries() {
+            let (offset_delta, stack) = match x {
+                StackMapFrame::SameFrame { offset_delta } => (offset_delta, Vec::new()),
+                StackMapFrame::SameLocals1StackItemFrame { offset_delta, stack } => {
+                    (offset_delta, vec![convert(&stack[0])?])
+                }
+                StackMapFrame::ChopFrame {
+                    locals_to_remove,
+                    offset_delta,
+                } => {
+                    let count = *locals_to_remove as usize;
+                    if count > locals.len() {
+                        return Err(Error::AnalysisError {
+                            pc: res.last().map_or(0, |x| x.0 + 1) + *offset_delta as u32,
+                            message: format!("stack map frame removes {} of {} locals", count, locals.len()),
+                        });
+                    }
+                    locals.truncate(locals.len() - count);
+                    (offset_delta, Vec::new())
+                }
+                StackMapFrame::AppendFrame { offset_delta, locals: appended } => {
+                    for x in appended {
+                        locals.push(convert(x)?);
+                    }
+                    (offset_delta, Vec::new())
+                }
+                StackMapFrame::FullFrame {
+                    offset_delta,
+                    locals: full,
+                    stack,
+                } => {
+                    locals = full.iter().map(&convert).collect::<Result<_>>()?;
+                    (offset_delta, stack.iter().map(&convert).collect::<Result<_>>()?)
+                }
+            };
+            let pc = res.last().map_or(0, |x| x.0 + 1) + *offset_delta as u32;
+            let mut frame = Frame {
+                locals: expand(&locals),
+                stack: expand(&stack),
+            };
+            if frame.locals.len() > max_locals {
+                return Err(Error::AnalysisError {
+                    pc,
+                    message: format!("stack map frame has {} locals, past max_locals", frame.locals.len()),
+                });
+            }
+            frame.locals.resize(max_locals, VerificationType::Top);
+            res.push((pc, frame));
+        }
+        Ok(Some(res))
+    }
+
+    /// Compares the code's `StackMapTable` against the frames simulating the
+    /// code gives, as [`Self::frames`], returning the first declared frame
+    /// that does not hold there, with both frames. A declared frame holds if
+    /// the stacks are as deep and each slot's computed type may be stored in
+    /// its declared one, references of any class in a reference slot.
+    /// Frames at unreachable code are not checked. Returns `None` for a
+    /// method without code or without the table, or if every frame holds.
+    pub fn stack_map_divergence(&self) -> Result<Option<FrameDivergence<'a>>> {
+        let declared = match self.stack_map_frames()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let frames = match self.frames()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        for (pc, frame) in declared {
+            let computed = match frames.frames.binary_search_by_key(&pc, |x| x.0) {
+                Ok(i) => match &frames.frames[i].1 {
+                    Some(x) => x,
+                    None => continue,
+                },
+                Err(_) => {
+                    return Ok(Some(FrameDivergence {
+                        pc,
+                        declared: frame,
+                        computed: None,
+                    }))
+                }
+            };
+            let consistent = frame.stack.len() == computed.stack.len()
+                && frame.locals.len() == computed.locals.len()
+                && frame
+                    .locals
+                    .iter()
+                    .chain(&frame.stack)
+                    .zip(computed.locals.iter().chain(&computed.stack))
+                    .all(|(a, b)| holds(a, b));
+            if !consistent {
+                return Ok(Some(FrameDivergence {
+                    pc,
+                    declared: frame,
+                    computed: Some(computed.clone()),
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Whether a value of type `computed` may be in a slot declared `declared`.
+/// Classes are not compared, as inference merges them to
+/// `java/lang/Object` and which are assignable takes the class hierarchy.
+fn holds(declared: &VerificationType, computed: &VerificationType) -> bool {
+    match (declared, computed) {
+        (VerificationType::Top, _) => true,
+        (VerificationType::Object(_), VerificationType::Object(_) | VerificationType::Null) => true,
+        (a, b) => a == b,
+    }
+}
+
+/// Gives each `long` and `double` its second slot.
+fn expand<'a>(types: &[VerificationType<'a>]) -> Vec<VerificationType<'a>> {
+    let mut res = Vec::with_capacity(types.len());
+    for x in types {
+        res.push(x.clone());
+        if x.is_wide() {
+            res.push(VerificationType::Top);
+        }
+    }
+    res
+}
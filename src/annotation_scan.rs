@@ -0,0 +1,203 @@
+//! Finding the classes, fields and methods of a class path that carry an
+//! annotation, such as every `@Entity` class, see [`AnnotationScanner`].
+
+use std::{collections::BTreeSet, path::Path};
+
+use crate::{
+    archive::Archive,
+    attributes::Annotation,
+    class_index::class_files,
+    owned::OwnedAnnotation,
+    raw::ConstantPoolItem,
+    resolver::ClassResolver,
+    ClassFile, ParseOptions, Result,
+};
+
+/// What an annotation was found on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AnnotatedElement {
+    Class,
+    Field { name: String, descriptor: String },
+    Method { name: String, descriptor: String },
+}
+
+/// An annotation the scanner looks for, where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationMatch {
+    /// The internal name of the class declaring the element.
+    pub class: String,
+    pub element: AnnotatedElement,
+    pub annotation: OwnedAnnotation,
+    /// Whether the annotation is kept at run time, being in a
+    /// `RuntimeVisibleAnnotations` attribute, rather than only in the class
+    /// file.
+    pub visible: bool,
+}
+
+/// Finds classes, fields and methods carrying given annotations, with the
+/// values the annotations give.
+///
+/// Annotations are matched by the field descriptor of their interface, e.g.
+/// `Ljakarta/persistence/Entity;`. Annotations on annotations are not
+/// followed, so an annotation interface annotated with a wanted one does not
+/// make what it annotates match.
+#[derive(Debug, Clone)]
+pub struct AnnotationScanner {
+    annotations: BTreeSet<String>,
+    invisible: bool,
+    options: ParseOptions,
+}
+
+impl Default for AnnotationScanner {
+    fn default() -> Self {
+        Self {
+            annotations: BTreeSet::new(),
+            invisible: true,
+            options: ParseOptions::default(),
+        }
+    }
+}
+
+impl AnnotationScanner {
+    /// A scanner matching every annotation, until some are added with
+    /// [`with_annotation`](Self::with_annotation).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks for the annotation interface with field descriptor `descriptor`
+    /// too.
+    pub fn with_annotation(mut self, descriptor: &str) -> Self {
+        self.annotations.insert(descriptor.to_string());
+        self
+    }
+
+    /// Whether annotations only kept in the class file, those of `CLASS`
+    /// retention, match. They do by default.
+    pub fn with_invisible(mut self, invisible: bool) -> Self {
+        self.invisible = invisible;
+        self
+    }
+
+    /// Parses classes with `options` rather than the default.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn wanted(&self, annotation: &Annotation) -> bool {
+        self.annotations.is_empty() || self.annotations.contains(annotation.type_name)
+    }
+
+    /// Whether `class_file` could have a wanted annotation, which needs the
+    /// annotation's descriptor in its constant pool. Saves decoding the
+    /// attributes of the many classes that do not.
+    fn may_match(&self, class_file: &ClassFile) -> bool {
+        self.annotations.is_empty()
+            || class_file.constant_pool().any(|(_, x)| match x {
+                ConstantPoolItem::Utf8 { value, .. } => self.annotations.contains(value.as_str()),
+                _ => false,
+            })
+    }
+
+    fn push<'a>(
+        &self,
+        res: &mut Vec<AnnotationMatch>,
+        class: &str,
+        element: &AnnotatedElement,
+        visible: Vec<Annotation<'a>>,
+        invisible: impl FnOnce() -> Result<Vec<Annotation<'a>>>,
+    ) -> Result<()> {
+        let invisible = if self.invisible { invisible()? } else { Vec::new() };
+        let annotations = visible
+            .into_iter()
+            .map(|x| (x, true))
+            .chain(invisible.into_iter().map(|x| (x, false)));
+        for (annotation, visible) in annotations.filter(|(x, _)| self.wanted(x)) {
+            res.push(AnnotationMatch {
+                class: class.to_string(),
+                element: element.clone(),
+                annotation: annotation.into_owned(),
+                visible,
+            });
+        }
+        Ok(())
+    }
+
+    /// The wanted annotations of `class_file`, its fields and its methods,
+    /// in that order, each in file order with the visible annotations first.
+    pub fn scan_class(&self, class_file: &ClassFile) -> Result<Vec<AnnotationMatch>> {
+        let mut res = Vec::new();
+        if !self.may_match(class_file) {
+            return Ok(res);
+        }
+        let class = class_file.this_class()?;
+        self.push(&mut res, class, &AnnotatedElement::Class, class_file.annotations()?, || {
+            class_file.invisible_annotations()
+        })?;
+        for x in class_file.fields() {
+            let element = AnnotatedElement::Field {
+                name: x.identifier()?.to_string(),
+                descriptor: x.field_inner.descriptor_index.get_as_string(class_file)?.to_string(),
+            };
+            self.push(&mut res, class, &element, x.annotations()?, || x.invisible_annotations())?;
+        }
+        for x in class_file.methods() {
+            let element = AnnotatedElement::Method {
+                name: x.identifier()?.to_string(),
+                descriptor: x.method_inner.descriptor_index.get_as_string(class_file)?.to_string(),
+            };
+            self.push(&mut res, class, &element, x.annotations()?, || x.invisible_annotations())?;
+        }
+        Ok(res)
+    }
+
+    /// Scans the classes called `names` that `resolver` has, in order,
+    /// skipping those it does not. A resolver cannot list its classes, so
+    /// for a [`ClassRepository`](crate::repository::ClassRepository) pass
+    /// its [`names`](crate::repository::ClassRepository::names).
+    pub fn scan_resolver<'a>(
+        &self,
+        resolver: &(impl ClassResolver + ?Sized),
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<AnnotationMatch>> {
+        let mut res = Vec::new();
+        for name in names {
+            if let Some(class_file) = resolver.resolve(name)? {
+                res.extend(self.scan_class(&class_file)?);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Scans every class of a JAR or JMOD, see [`Archive::classes`].
+    pub fn scan_archive(&self, archive: &Archive) -> Result<Vec<AnnotationMatch>> {
+        let mut res = Vec::new();
+        for (_, entry) in archive.classes() {
+            let class_file = ClassFile::parse_with_options(archive.read_entry(entry)?, &self.options)?;
+            res.extend(self.scan_class(&class_file)?);
+        }
+        Ok(res)
+    }
+
+    /// Scans every archive in turn, as the JARs of a class path.
+    pub fn scan_archives<'a>(&self, archives: impl IntoIterator<Item = &'a Archive>) -> Result<Vec<AnnotationMatch>> {
+        let mut res = Vec::new();
+        for archive in archives {
+            res.extend(self.scan_archive(archive)?);
+        }
+        Ok(res)
+    }
+
+    /// Scans the `.class` files under `root`, recursively, in path order.
+    pub fn scan_directory(&self, root: impl AsRef<Path>) -> Result<Vec<AnnotationMatch>> {
+        let mut paths = Vec::new();
+        class_files(root.as_ref(), &mut paths)?;
+        let mut res = Vec::new();
+        for path in paths {
+            let class_file = ClassFile::parse_with_options(std::fs::read(path)?, &self.options)?;
+            res.extend(self.scan_class(&class_file)?);
+        }
+        Ok(res)
+    }
+}
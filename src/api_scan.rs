@@ -0,0 +1,199 @@
+use crate::{
+    instruction::{Instruction, MaybeInterfaceMethodRef},
+    ClassFile, Result,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiCategory {
+    JdkInternal,
+    Removed,
+    DeprecatedForRemoval,
+}
+
+/// A rule matching referenced classes or members.
+///
+/// Patterns use internal names: `pkg/` matches a package prefix, `pkg/Class`
+/// matches a class and `pkg/Class.member` matches a single field or method.
+#[derive(Debug, Clone)]
+pub struct ApiRule {
+    pub pattern: String,
+    pub category: ApiCategory,
+}
+
+impl ApiRule {
+    fn matches(&self, class: &str, member: Option<&str>) -> bool {
+        if self.pattern.ends_with('/') {
+            return class.starts_with(&self.pattern);
+        }
+        match self.pattern.split_once('.') {
+            Some((owner, name)) => owner == class && member == Some(name),
+            None => self.pattern == class,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiFinding<'a> {
+    pub method: &'a str,
+    pub method_descriptor: &'a str,
+    pub pc: u32,
+    /// The class referenced, or for an array class such as
+    /// `[Lsun/misc/Unsafe;`, its element class.
+    pub target_class: &'a str,
+    pub target_member: Option<&'a str>,
+    pub rule: String,
+    pub category: ApiCategory,
+}
+
+/// The element class of an array class such as `[[Lpkg/Class;`, or the
+/// class itself. Arrays of primitives are left as they are.
+fn element_class(class: &str) -> &str {
+    let element = class.trim_start_matches('[');
+    match element.strip_prefix('L').and_then(|x| x.strip_suffix(';')) {
+        Some(x) if element.len() != class.len() => x,
+        _ => class,
+    }
+}
+
+const JDK_INTERNAL: &[&str] = &["sun/misc/Unsafe", "jdk/internal/", "sun/", "com/sun/proxy/"];
+
+const REMOVED: &[&str] = &[
+    "javax/xml/bind/",
+    "javax/xml/ws/",
+    "javax/jws/",
+    "javax/activation/",
+    "javax/transaction/",
+    "org/omg/",
+    "javax/rmi/CORBA/",
+    "javax/annotation/PostConstruct",
+    "javax/annotation/PreDestroy",
+    "javax/annotation/Resource",
+    "javax/annotation/Resources",
+    "javax/annotation/Generated",
+    "java/rmi/activation/",
+    "java/lang/Thread.destroy",
+    "java/lang/System.runFinalizersOnExit",
+    "java/lang/Runtime.runFinalizersOnExit",
+];
+
+const DEPRECATED_FOR_REMOVAL: &[&str] = &[
+    "java/applet/",
+    "java/lang/SecurityManager",
+    "java/lang/System.getSecurityManager",
+    "java/lang/System.setSecurityManager",
+    "java/lang/Thread.suspend",
+    "java/lang/Thread.resume",
+    "java/lang/Thread.stop",
+    "java/lang/ThreadGroup.suspend",
+    "java/lang/ThreadGroup.resume",
+    "java/lang/ThreadGroup.stop",
+    "java/lang/Runtime.runFinalization",
+    "java/lang/System.runFinalization",
+    "java/security/AccessController",
+];
+
+/// A jdeprscan-like scanner over the member references of a class.
+#[derive(Debug, Clone)]
+pub struct ApiScanner {
+    rules: Vec<ApiRule>,
+}
+
+impl Default for ApiScanner {
+    fn default() -> Self {
+        let rules = JDK_INTERNAL
+            .iter()
+            .map(|x| (x, ApiCategory::JdkInternal))
+            .chain(REMOVED.iter().map(|x| (x, ApiCategory::Removed)))
+            .chain(
+                DEPRECATED_FOR_REMOVAL
+                    .iter()
+                    .map(|x| (x, ApiCategory::DeprecatedForRemoval)),
+            )
+            .map(|(pattern, category)| ApiRule {
+                pattern: pattern.to_string(),
+                category,
+            })
+            .collect();
+        Self { rules }
+    }
+}
+
+impl ApiScanner {
+    /// A scanner preloaded with JDK-internal packages and known removed APIs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A scanner without any rules.
+    pub fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    pub fn with_rule(mut self, pattern: &str, category: ApiCategory) -> Self {
+        self.rules.push(ApiRule {
+            pattern: pattern.to_string(),
+            category,
+        });
+        self
+    }
+
+    pub fn rules(&self) -> &[ApiRule] {
+        &self.rules
+    }
+
+    fn find_rule(&self, class: &str, member: Option<&str>) -> Option<&ApiRule> {
+        self.rules.iter().find(|x| x.matches(class, member))
+    }
+
+    pub fn scan<'a>(&self, class_file: &'a ClassFile) -> Result<Vec<ApiFinding<'a>>> {
+        let mut findings = Vec::new();
+        for method in class_file.methods() {
+            let code = match method.code()? {
+                Some(x) => x,
+                None => continue,
+            };
+            let name = method.identifier()?;
+            let method_descriptor = method
+                .method_inner
+                .descriptor_index
+                .get_as_string(class_file)?;
+            for (pc, instruction) in code.instructions_with_pcs()? {
+                let (target_class, target_member) = match &instruction {
+                    Instruction::Getfield { field }
+                    | Instruction::Getstatic { field }
+                    | Instruction::Putfield { field }
+                    | Instruction::Putstatic { field } => (field.class, Some(field.name)),
+                    Instruction::Invokevirtual { index } => (index.class, Some(index.name)),
+                    Instruction::Invokeinterface { index, .. } => (index.class, Some(index.name)),
+                    Instruction::Invokespecial { index } | Instruction::Invokestatic { index } => {
+                        match index {
+                            MaybeInterfaceMethodRef::RegularMethod(x) => (x.class, Some(x.name)),
+                            MaybeInterfaceMethodRef::InterfaceMethod(x) => {
+                                (x.class, Some(x.name))
+                            }
+                        }
+                    }
+                    Instruction::New { class }
+                    | Instruction::Anewarray { class }
+                    | Instruction::Checkcast { class }
+                    | Instruction::Instanceof { class }
+                    | Instruction::Multianewarray { class, .. } => (*class, None),
+                    _ => continue,
+                };
+                let target_class = element_class(target_class);
+                if let Some(rule) = self.find_rule(target_class, target_member) {
+                    findings.push(ApiFinding {
+                        method: name,
+                        method_descriptor,
+                        pc,
+                        target_class,
+                        target_member,
+                        rule: rule.pattern.clone(),
+                        category: rule.category,
+                    });
+                }
+            }
+        }
+        Ok(findings)
+    }
+}
@@ -0,0 +1,260 @@
+//! Reading class files out of JARs and JDK `.jmod` files, see [`Archive`].
+
+use std::{collections::HashMap, path::Path};
+
+use crate::{inflate, repository::ClassRepository, ClassFile, Error, ParseOptions, Result};
+
+/// The magic number and version a `.jmod` file starts with, before its ZIP.
+const JMOD_MAGIC: &[u8] = b"JM\x01\x00";
+/// The directory of a `.jmod` file holding its classes and resources.
+const JMOD_CLASSES: &str = "classes/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// A JAR or any other ZIP archive.
+    Jar,
+    /// A JDK module, a ZIP behind a four byte header whose class path is its
+    /// `classes/` directory, alongside `bin/`, `lib/`, `conf/` and others.
+    Jmod,
+}
+
+/// A file in an [`Archive`], as listed by its central directory.
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub name: String,
+    /// The size once decompressed.
+    pub size: u64,
+    pub compressed_size: u64,
+    method: u16,
+    crc32: u32,
+    header_offset: u64,
+}
+
+impl ArchiveEntry {
+    pub fn is_directory(&self) -> bool {
+        self.name.ends_with('/')
+    }
+}
+
+/// A JAR or `.jmod` file, read into memory with its entries listed. Entries
+/// are decompressed on demand; stored and deflated entries are supported,
+/// as are ZIP64 archives.
+pub struct Archive {
+    kind: ArchiveKind,
+    /// The ZIP, without the header of a `.jmod`.
+    zip: Vec<u8>,
+    entries: Vec<ArchiveEntry>,
+    /// Entries by name, the last if there are duplicates.
+    by_name: HashMap<String, usize>,
+}
+
+impl std::fmt::Debug for Archive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Archive")
+            .field("kind", &self.kind)
+            .field("entries", &self.entries.len())
+            .finish()
+    }
+}
+
+fn invalid(message: impl std::fmt::Display) -> Error {
+    Error::ArchiveError(message.to_string())
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16> {
+    data.get(pos..pos + 2)
+        .map(|x| u16::from_le_bytes([x[0], x[1]]))
+        .ok_or_else(|| invalid("unexpected end of archive"))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Result<u32> {
+    data.get(pos..pos + 4)
+        .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+        .ok_or_else(|| invalid("unexpected end of archive"))
+}
+
+fn u64_at(data: &[u8], pos: usize) -> Result<u64> {
+    Ok(u32_at(data, pos)? as u64 | (u32_at(data, pos + 4)? as u64) << 32)
+}
+
+/// The entry count, size and offset of the central directory.
+fn central_directory(zip: &[u8]) -> Result<(u64, u64, u64)> {
+    // The end record is last but for a comment of up to 65535 bytes.
+    let lowest = zip.len().saturating_sub(22 + 0xffff);
+    let end = (lowest..=zip.len().saturating_sub(22))
+        .rev()
+        .find(|&x| zip[x..].starts_with(b"PK\x05\x06"))
+        .ok_or_else(|| invalid("no end of central directory record"))?;
+    let count = u16_at(zip, end + 10)?;
+    let size = u32_at(zip, end + 12)?;
+    let offset = u32_at(zip, end + 16)?;
+    if count != 0xffff && size != 0xffff_ffff && offset != 0xffff_ffff {
+        return Ok((count as u64, size as u64, offset as u64));
+    }
+    // ZIP64 keeps the real values in another record, found by a locator
+    // just before the end record.
+    let locator = end
+        .checked_sub(20)
+        .filter(|&x| zip[x..].starts_with(b"PK\x06\x07"))
+        .ok_or_else(|| invalid("no ZIP64 end of central directory locator"))?;
+    let record = u64_at(zip, locator + 8)? as usize;
+    if !zip.get(record..).is_some_and(|x| x.starts_with(b"PK\x06\x06")) {
+        return Err(invalid("no ZIP64 end of central directory record"));
+    }
+    Ok((u64_at(zip, record + 32)?, u64_at(zip, record + 40)?, u64_at(zip, record + 48)?))
+}
+
+impl Archive {
+    /// Lists the entries of the JAR or `.jmod` file in `data`.
+    pub fn parse(mut zip: Vec<u8>) -> Result<Self> {
+        let kind = if zip.starts_with(JMOD_MAGIC) {
+            zip.drain(..JMOD_MAGIC.len());
+            ArchiveKind::Jmod
+        } else {
+            ArchiveKind::Jar
+        };
+        let (count, _, offset) = central_directory(&zip)?;
+        let mut entries = Vec::with_capacity(count.min(0x10000) as usize);
+        let mut pos = offset as usize;
+        for _ in 0..count {
+            if u32_at(&zip, pos)? != 0x0201_4b50 {
+                return Err(invalid("bad central directory entry"));
+            }
+            let name_length = u16_at(&zip, pos + 28)? as usize;
+            let extra_length = u16_at(&zip, pos + 30)? as usize;
+            let comment_length = u16_at(&zip, pos + 32)? as usize;
+            let name = zip
+                .get(pos + 46..pos + 46 + name_length)
+                .ok_or_else(|| invalid("unexpected end of archive"))?;
+            let mut entry = ArchiveEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                method: u16_at(&zip, pos + 10)?,
+                crc32: u32_at(&zip, pos + 16)?,
+                compressed_size: u32_at(&zip, pos + 20)? as u64,
+                size: u32_at(&zip, pos + 24)? as u64,
+                header_offset: u32_at(&zip, pos + 42)? as u64,
+            };
+
+            // Values too large for their fields are in the ZIP64 extra field,
+            // in this order, for just those that are.
+            let mut extra = pos + 46 + name_length;
+            let extra_end = extra + extra_length;
+            while extra + 4 <= extra_end {
+                let (id, length) = (u16_at(&zip, extra)?, u16_at(&zip, extra + 2)? as usize);
+                if id == 0x0001 {
+                    let mut field = extra + 4;
+                    for x in [&mut entry.size, &mut entry.compressed_size, &mut entry.header_offset] {
+                        if *x == 0xffff_ffff {
+                            *x = u64_at(&zip, field)?;
+                            field += 8;
+                        }
+                    }
+                }
+                extra += 4 + length;
+            }
+
+            entries.push(entry);
+            pos = extra_end + comment_length;
+        }
+        let by_name = entries.iter().enumerate().map(|(i, x)| (x.name.clone(), i)).collect();
+        Ok(Self {
+            kind,
+            zip,
+            entries,
+            by_name,
+        })
+    }
+
+    /// Reads and lists the archive at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(std::fs::read(path)?)
+    }
+
+    pub fn kind(&self) -> ArchiveKind {
+        self.kind
+    }
+
+    /// Every entry, directories included, in central directory order.
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&ArchiveEntry> {
+        self.by_name.get(name).map(|&x| &self.entries[x])
+    }
+
+    /// The decompressed contents of `entry`, checked against its CRC-32.
+    pub fn read_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
+        let header = entry.header_offset as usize;
+        if u32_at(&self.zip, header)? != 0x0403_4b50 {
+            return Err(invalid(format!("bad local header for {}", entry.name)));
+        }
+        let start = header + 30 + u16_at(&self.zip, header + 26)? as usize + u16_at(&self.zip, header + 28)? as usize;
+        let data = self
+            .zip
+            .get(start..start + entry.compressed_size as usize)
+            .ok_or_else(|| invalid(format!("{} runs past the end of the archive", entry.name)))?;
+        let res = match entry.method {
+            0 => data.to_vec(),
+            8 => inflate::inflate(data, entry.size as usize)?,
+            x => return Err(invalid(format!("{} uses unsupported compression method {}", entry.name, x))),
+        };
+        if res.len() as u64 != entry.size || inflate::crc32(&res) != entry.crc32 {
+            return Err(invalid(format!("{} is corrupt", entry.name)));
+        }
+        Ok(res)
+    }
+
+    /// The contents of the entry called `name`, if there is one.
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        self.entry(name).map(|x| self.read_entry(x)).transpose()
+    }
+
+    /// The entries on the class path, with their paths relative to it: every
+    /// file of a JAR, and those under `classes/` of a `.jmod`.
+    pub fn class_path_entries(&self) -> impl Iterator<Item = (&str, &ArchiveEntry)> {
+        self.entries.iter().filter(|x| !x.is_directory()).filter_map(move |x| match self.kind {
+            ArchiveKind::Jar => Some((x.name.as_str(), x)),
+            ArchiveKind::Jmod => Some((x.name.strip_prefix(JMOD_CLASSES)?, x)),
+        })
+    }
+
+    /// The classes on the class path by internal name, e.g.
+    /// `java/lang/Object`, with their entries. Classes under `META-INF`, such
+    /// as the versioned ones of a multi-release JAR, are left out.
+    pub fn classes(&self) -> impl Iterator<Item = (&str, &ArchiveEntry)> {
+        self.class_path_entries().filter_map(|(path, x)| {
+            let name = path.strip_suffix(".class").filter(|x| !x.starts_with("META-INF/"))?;
+            Some((name, x))
+        })
+    }
+
+    /// The bytes of class `name`, if the archive has it.
+    pub fn class_bytes(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        if name.starts_with("META-INF/") {
+            return Ok(None);
+        }
+        let prefix = match self.kind {
+            ArchiveKind::Jar => "",
+            ArchiveKind::Jmod => JMOD_CLASSES,
+        };
+        self.read(&format!("{}{}.class", prefix, name))
+    }
+
+    /// Reads and parses class `name`, if the archive has it.
+    pub fn read_class(&self, name: &str, options: &ParseOptions) -> Result<Option<ClassFile>> {
+        self.class_bytes(name)?
+            .map(|x| ClassFile::parse_with_options(x, options))
+            .transpose()
+    }
+
+    /// Parses every class into `repository`, returning how many there were.
+    pub fn load_into(&self, repository: &ClassRepository, options: &ParseOptions) -> Result<usize> {
+        let mut count = 0;
+        for (_, entry) in self.classes() {
+            repository.parse(self.read_entry(entry)?, options)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
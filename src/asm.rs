@@ -0,0 +1,570 @@
+//! Assembles bytecode with symbolic labels into the `code` array of a `Code`
+//! attribute.
+
+use std::collections::HashMap;
+
+use crate::{builder::ConstantPoolBuilder, method::MethodDescriptor, mutf8, ConstantPoolItem, Error, Result, Utf8Index};
+
+/// A position in the code, bound by [`Op::Label`] and targeted by branches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label(pub u32);
+
+/// A constant loaded by `ldc`, `ldc_w` or `ldc2_w`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Integer(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    String(String),
+    /// A string by its UTF-16 code units, for strings with unpaired
+    /// surrogates, which a `String` cannot hold.
+    Utf16String(Vec<u16>),
+    /// A class by internal name.
+    Class(String),
+    /// A method type by descriptor.
+    MethodType(String),
+    /// A method handle of `kind` (1 to 9, as in §4.4.8) to a field or method.
+    /// `interface` selects an `InterfaceMethodref` for the method kinds.
+    MethodHandle {
+        kind: u8,
+        class: String,
+        name: String,
+        descriptor: String,
+        interface: bool,
+    },
+    /// A dynamic constant produced by entry `bootstrap_method` of the
+    /// `BootstrapMethods` attribute.
+    Dynamic {
+        bootstrap_method: u16,
+        name: String,
+        descriptor: String,
+    },
+}
+
+impl Constant {
+    /// Adds the constant to `pool`, returning its index.
+    pub(crate) fn add_to(&self, pool: &mut ConstantPoolBuilder) -> Result<u16> {
+        Ok(match self {
+            Self::Integer(x) => pool.integer(*x)?.0,
+            Self::Float(x) => pool.float(*x)?.0,
+            Self::Long(x) => pool.long(*x)?.0,
+            Self::Double(x) => pool.double(*x)?.0,
+            Self::String(x) => pool.string(x)?.0,
+            Self::Utf16String(x) => {
+                let string_index = pool.add(ConstantPoolItem::Utf8 {
+                    bytes: mutf8::encode_utf16(x),
+                    value: String::from_utf16_lossy(x),
+                })?;
+                pool.add(ConstantPoolItem::String {
+                    string_index: Utf8Index(string_index),
+                })?
+            }
+            Self::Class(x) => pool.class(x)?.0,
+            Self::MethodType(x) => pool.method_type(x)?.0,
+            Self::MethodHandle {
+                kind,
+                class,
+                name,
+                descriptor,
+                interface,
+            } => {
+                let reference = match (kind, interface) {
+                    (1..=4, _) => pool.field_ref(class, name, descriptor)?.0,
+                    (_, true) => pool.interface_method_ref(class, name, descriptor)?.0,
+                    (_, false) => pool.method_ref(class, name, descriptor)?.0,
+                };
+                pool.method_handle(*kind, reference)?.0
+            }
+            Self::Dynamic {
+                bootstrap_method,
+                name,
+                descriptor,
+            } => pool.dynamic(*bootstrap_method, name, descriptor)?.0,
+        })
+    }
+
+    /// Whether the constant takes two stack slots, and so is loaded by `ldc2_w`.
+    fn is_wide(&self) -> bool {
+        match self {
+            Self::Long(_) | Self::Double(_) => true,
+            Self::Dynamic { descriptor, .. } => descriptor == "J" || descriptor == "D",
+            _ => false,
+        }
+    }
+}
+
+/// An instruction, or a label binding, with pool references given by name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    /// Binds `label` to the pc of the next instruction.
+    Label(Label),
+    /// An instruction without operands, e.g. `0xb1` for `return`.
+    Simple(u8),
+    Bipush(i8),
+    Sipush(i16),
+    Newarray(u8),
+    /// A load, store or `ret` given by the opcode of its general form, e.g.
+    /// `0x15` for `iload`. Uses the one-byte forms such as `iload_0` where
+    /// they exist, and `wide` for indices above 255.
+    Local { opcode: u8, index: u16 },
+    Iinc { index: u16, constant: i16 },
+    /// A `goto`, `jsr`, `if*`, `ifnull` or `ifnonnull`, or their `_w` forms.
+    Branch { opcode: u8, target: Label },
+    Tableswitch {
+        default: Label,
+        low: i32,
+        targets: Vec<Label>,
+    },
+    Lookupswitch {
+        default: Label,
+        pairs: Vec<(i32, Label)>,
+    },
+    Ldc(Constant),
+    /// `getfield`, `putfield`, `getstatic` or `putstatic`.
+    Field {
+        opcode: u8,
+        class: String,
+        name: String,
+        descriptor: String,
+    },
+    /// `invokevirtual`, `invokespecial`, `invokestatic` or `invokeinterface`.
+    /// `interface` selects an `InterfaceMethodref`, and is implied by
+    /// `invokeinterface`.
+    Invoke {
+        opcode: u8,
+        class: String,
+        name: String,
+        descriptor: String,
+        interface: bool,
+    },
+    Invokedynamic {
+        bootstrap_method: u16,
+        name: String,
+        descriptor: String,
+    },
+    /// `new`, `anewarray`, `checkcast` or `instanceof`.
+    Type { opcode: u8, class: String },
+    Multianewarray { class: String, dimensions: u8 },
+}
+
+const GOTO: u8 = 0xa7;
+const JSR: u8 = 0xa8;
+const GOTO_W: u8 = 0xc8;
+const JSR_W: u8 = 0xc9;
+const WIDE: u8 = 0xc4;
+
+/// An [`Op`] with its pool references resolved.
+enum Item {
+    Label(Label),
+    Bytes(Vec<u8>),
+    Branch {
+        opcode: u8,
+        target: Label,
+    },
+    Tableswitch {
+        default: Label,
+        low: i32,
+        targets: Vec<Label>,
+    },
+    Lookupswitch {
+        default: Label,
+        pairs: Vec<(i32, Label)>,
+    },
+}
+
+fn with_index(opcode: u8, index: u16) -> Vec<u8> {
+    let [hi, lo] = index.to_be_bytes();
+    vec![opcode, hi, lo]
+}
+
+/// The stack slots taken by the arguments of a method with `descriptor`.
+fn argument_slots(descriptor: &str) -> Result<u8> {
+    let (_, parsed) = MethodDescriptor::parse(descriptor)?;
+    u8::try_from(parsed.parameter_slots()).map_err(|_| Error::AssemblyError(format!("too many arguments in {}", descriptor)))
+}
+
+fn local(opcode: u8, index: u16) -> Result<Vec<u8>> {
+    // iload..aload and istore..astore have `_0` to `_3` forms, ret does not.
+    let short = match opcode {
+        0x15..=0x19 => Some(0x1a + (opcode - 0x15) * 4),
+        0x36..=0x3a => Some(0x3b + (opcode - 0x36) * 4),
+        0xa9 => None,
+        _ => return Err(Error::AssemblyError(format!("opcode {:#04x} does not take a local", opcode))),
+    };
+    Ok(match (short, u8::try_from(index)) {
+        (Some(short), _) if index < 4 => vec![short + index as u8],
+        (_, Ok(index)) => vec![opcode, index],
+        (_, Err(_)) => {
+            let [hi, lo] = index.to_be_bytes();
+            vec![WIDE, opcode, hi, lo]
+        }
+    })
+}
+
+fn resolve(op: &Op, pool: &mut ConstantPoolBuilder) -> Result<Item> {
+    let bytes = match op {
+        Op::Label(label) => return Ok(Item::Label(*label)),
+        Op::Branch { opcode, target } => {
+            if !matches!(opcode, 0x99..=0xa8 | 0xc6..=0xc9) {
+                return Err(Error::AssemblyError(format!("opcode {:#04x} is not a branch", opcode)));
+            }
+            return Ok(Item::Branch {
+                opcode: *opcode,
+                target: *target,
+            });
+        }
+        Op::Tableswitch { default, low, targets } => {
+            if targets.is_empty() || targets.len() as i64 - 1 > i32::MAX as i64 - *low as i64 {
+                return Err(Error::AssemblyError(format!("tableswitch of {} targets from {}", targets.len(), low)));
+            }
+            return Ok(Item::Tableswitch {
+                default: *default,
+                low: *low,
+                targets: targets.clone(),
+            });
+        }
+        Op::Lookupswitch { default, pairs } => {
+            // The keys must be sorted, and are unique.
+            let mut pairs = pairs.clone();
+            pairs.sort_by_key(|(key, _)| *key);
+            if pairs.windows(2).any(|x| x[0].0 == x[1].0) {
+                return Err(Error::AssemblyError("lookupswitch with a duplicate key".to_string()));
+            }
+            return Ok(Item::Lookupswitch {
+                default: *default,
+                pairs,
+            });
+        }
+        Op::Simple(opcode) => vec![*opcode],
+        Op::Bipush(value) => vec![0x10, *value as u8],
+        Op::Sipush(value) => {
+            let [hi, lo] = value.to_be_bytes();
+            vec![0x11, hi, lo]
+        }
+        Op::Newarray(atype) => vec![0xbc, *atype],
+        Op::Local { opcode, index } => local(*opcode, *index)?,
+        Op::Iinc { index, constant } => match (u8::try_from(*index), i8::try_from(*constant)) {
+            (Ok(index), Ok(constant)) => vec![0x84, index, constant as u8],
+            _ => {
+                let [a, b] = index.to_be_bytes();
+                let [c, d] = constant.to_be_bytes();
+                vec![WIDE, 0x84, a, b, c, d]
+            }
+        },
+        Op::Ldc(constant) => {
+            let index = constant.add_to(pool)?;
+            match u8::try_from(index) {
+                _ if constant.is_wide() => with_index(0x14, index),
+                Ok(index) => vec![0x12, index],
+                Err(_) => with_index(0x13, index),
+            }
+        }
+        Op::Field {
+            opcode,
+            class,
+            name,
+            descriptor,
+        } => with_index(*opcode, pool.field_ref(class, name, descriptor)?.0),
+        Op::Invoke {
+            opcode,
+            class,
+            name,
+            descriptor,
+            interface,
+        } => {
+            if *opcode == 0xb9 {
+                let index = pool.interface_method_ref(class, name, descriptor)?.0;
+                let mut bytes = with_index(*opcode, index);
+                let count = argument_slots(descriptor)?
+                    .checked_add(1)
+                    .ok_or_else(|| Error::AssemblyError(format!("too many arguments in {}", descriptor)))?;
+                bytes.extend_from_slice(&[count, 0]);
+                bytes
+            } else if *interface {
+                with_index(*opcode, pool.interface_method_ref(class, name, descriptor)?.0)
+            } else {
+                with_index(*opcode, pool.method_ref(class, name, descriptor)?.0)
+            }
+        }
+        Op::Invokedynamic {
+            bootstrap_method,
+            name,
+            descriptor,
+        } => {
+            let mut bytes = with_index(0xba, pool.invoke_dynamic(*bootstrap_method, name, descriptor)?.0);
+            bytes.extend_from_slice(&[0, 0]);
+            bytes
+        }
+        Op::Type { opcode, class } => with_index(*opcode, pool.class(class)?.0),
+        Op::Multianewarray { class, dimensions } => {
+            let mut bytes = with_index(0xc5, pool.class(class)?.0);
+            bytes.push(*dimensions);
+            bytes
+        }
+    };
+    Ok(Item::Bytes(bytes))
+}
+
+/// Padding after a switch opcode at `pc`, aligning its operands to four bytes.
+fn padding(pc: u32) -> u32 {
+    (4 - (pc + 1) % 4) % 4
+}
+
+fn size(item: &Item, pc: u32, wide: bool) -> u32 {
+    match item {
+        Item::Label(_) => 0,
+        Item::Bytes(x) => x.len() as u32,
+        Item::Branch { opcode, .. } => match (*opcode, wide) {
+            (GOTO_W | JSR_W, _) | (GOTO | JSR, true) => 5,
+            (_, false) => 3,
+            // An inverted branch over a goto_w.
+            (_, true) => 8,
+        },
+        Item::Tableswitch { targets, .. } => 1 + padding(pc) + 12 + 4 * targets.len() as u32,
+        Item::Lookupswitch { pairs, .. } => 1 + padding(pc) + 8 + 8 * pairs.len() as u32,
+    }
+}
+
+/// The opcode branching when `opcode` does not.
+fn invert(opcode: u8) -> u8 {
+    match opcode {
+        0xc6 => 0xc7,
+        0xc7 => 0xc6,
+        // ifeq/ifne, iflt/ifge, ... if_acmpeq/if_acmpne come in pairs.
+        _ => ((opcode - 0x99) ^ 1) + 0x99,
+    }
+}
+
+/// Bytecode assembled by [`assemble`].
+#[derive(Debug, Clone)]
+pub struct Assembled {
+    pub code: Vec<u8>,
+    labels: HashMap<Label, u32>,
+}
+
+impl Assembled {
+    /// The pc `label` was bound to, for exception tables and stack map frames.
+    pub fn pc(&self, label: Label) -> Option<u32> {
+        self.labels.get(&label).copied()
+    }
+}
+
+/// Assembles `ops`, adding the constants they refer to to `pool`.
+///
+/// Branches whose target is out of reach of a 16-bit offset are widened:
+/// `goto` and `jsr` become `goto_w` and `jsr_w`, and a conditional branch
+/// becomes the opposite condition skipping over a `goto_w`. Fails on unbound
+/// or rebound labels and on code longer than 65535 bytes.
+pub fn assemble(ops: &[Op], pool: &mut ConstantPoolBuilder) -> Result<Assembled> {
+    let items = ops.iter().map(|x| resolve(x, pool)).collect::<Result<Vec<_>>>()?;
+    let mut wide = vec![false; items.len()];
+    // Widening only ever grows the code, so this reaches a fixed point.
+    let (pcs, labels) = loop {
+        let mut pcs = Vec::with_capacity(items.len());
+        let mut labels = HashMap::new();
+        let mut pc = 0u32;
+        for (i, item) in items.iter().enumerate() {
+            if let Item::Label(label) = item {
+                if labels.insert(*label, pc).is_some() {
+                    return Err(Error::AssemblyError(format!("label {} is bound twice", label.0)));
+                }
+            }
+            pcs.push(pc);
+            pc += size(item, pc, wide[i]);
+        }
+        let target = |label: &Label| {
+            labels
+                .get(label)
+                .copied()
+                .ok_or_else(|| Error::AssemblyError(format!("label {} is never bound", label.0)))
+        };
+        let mut changed = false;
+        for (i, item) in items.iter().enumerate() {
+            match item {
+                Item::Branch { target: label, .. } => {
+                    let offset = target(label)? as i64 - pcs[i] as i64;
+                    if !wide[i] && i16::try_from(offset).is_err() {
+                        wide[i] = true;
+                        changed = true;
+                    }
+                }
+                Item::Tableswitch { default, targets, .. } => {
+                    target(default)?;
+                    targets.iter().try_for_each(|x| target(x).map(drop))?;
+                }
+                Item::Lookupswitch { default, pairs } => {
+                    target(default)?;
+                    pairs.iter().try_for_each(|(_, x)| target(x).map(drop))?;
+                }
+                _ => {}
+            }
+        }
+        if !changed {
+            break (pcs, labels);
+        }
+    };
+
+    let offset = |label: &Label, pc: u32| labels[label] as i32 - pc as i32;
+    let mut code = Vec::new();
+    for ((item, pc), wide) in items.iter().zip(pcs).zip(wide) {
+        match item {
+            Item::Label(_) => {}
+            Item::Bytes(x) => code.extend_from_slice(x),
+            Item::Branch { opcode, target } => match (*opcode, wide) {
+                (GOTO_W | JSR_W, _) => {
+                    code.push(*opcode);
+                    code.extend_from_slice(&offset(target, pc).to_be_bytes());
+                }
+                (GOTO | JSR, true) => {
+                    code.push(if *opcode == GOTO { GOTO_W } else { JSR_W });
+                    code.extend_from_slice(&offset(target, pc).to_be_bytes());
+                }
+                (_, false) => {
+                    code.push(*opcode);
+                    code.extend_from_slice(&(offset(target, pc) as i16).to_be_bytes());
+                }
+                (_, true) => {
+                    code.push(invert(*opcode));
+                    code.extend_from_slice(&8i16.to_be_bytes());
+                    code.push(GOTO_W);
+                    code.extend_from_slice(&offset(target, pc + 3).to_be_bytes());
+                }
+            },
+            Item::Tableswitch { default, low, targets } => {
+                code.push(0xaa);
+                code.resize(code.len() + padding(pc) as usize, 0);
+                code.extend_from_slice(&offset(default, pc).to_be_bytes());
+                code.extend_from_slice(&low.to_be_bytes());
+                code.extend_from_slice(&(low + (targets.len() as i32 - 1)).to_be_bytes());
+                for target in targets {
+                    code.extend_from_slice(&offset(target, pc).to_be_bytes());
+                }
+            }
+            Item::Lookupswitch { default, pairs } => {
+                code.push(0xab);
+                code.resize(code.len() + padding(pc) as usize, 0);
+                code.extend_from_slice(&offset(default, pc).to_be_bytes());
+                code.extend_from_slice(&(pairs.len() as u32).to_be_bytes());
+                for (key, target) in pairs {
+                    code.extend_from_slice(&key.to_be_bytes());
+                    code.extend_from_slice(&offset(target, pc).to_be_bytes());
+                }
+            }
+        }
+    }
+    if code.len() > u16::MAX as usize {
+        return Err(Error::AssemblyError(format!("{} bytes of code", code.len())));
+    }
+    Ok(Assembled { code, labels })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::{ClassFileBuilder, MethodBuilder, MethodCode},
+        field::TypeDescriptor,
+        ClassFile, MethodAccessFlags,
+    };
+
+    /// The mnemonic, pc and branch target pcs of an instruction.
+    type Decoded = (&'static str, u32, Vec<u32>);
+
+    /// Assembles `ops` as the body of `static void run(int)`, reads the class
+    /// back and decodes every instruction.
+    fn round_trip(ops: &[Op]) -> (Assembled, Vec<Decoded>) {
+        let mut pool = ConstantPoolBuilder::new();
+        let assembled = assemble(ops, &mut pool).unwrap();
+        let descriptor = MethodDescriptor::new(vec![TypeDescriptor::Int], None);
+        let method = MethodBuilder::new(MethodAccessFlags::STATIC, "run", &descriptor).with_raw_code(MethodCode {
+            max_stack: 1,
+            max_locals: 1,
+            code: assembled.code.clone(),
+        });
+        let bytes = ClassFileBuilder::new("Run")
+            .with_constant_pool(pool)
+            .with_method(method)
+            .build()
+            .unwrap();
+        let class_file = ClassFile::parse(&bytes[..]).unwrap();
+        let code = class_file.methods()[0].code().unwrap().unwrap();
+        let instructions = code.instructions_with_pcs().unwrap();
+        let targets = code.branch_targets().unwrap();
+        let decoded = instructions
+            .iter()
+            .zip(targets)
+            .map(|((pc, instruction), targets)| {
+                (instruction.mnemonic(), *pc, targets.iter().map(|x| x.pc).collect())
+            })
+            .collect();
+        (assembled, decoded)
+    }
+
+    #[test]
+    fn branches_and_switches_land_on_their_labels() {
+        let (a, b, d) = (Label(0), Label(1), Label(2));
+        let ops = vec![
+            Op::Local { opcode: 0x15, index: 0 },
+            Op::Tableswitch { default: d, low: 0, targets: vec![a, b] },
+            Op::Label(a),
+            Op::Local { opcode: 0x15, index: 0 },
+            // Unsorted, to be written in key order.
+            Op::Lookupswitch { default: a, pairs: vec![(5, b), (-1, d)] },
+            Op::Label(b),
+            Op::Local { opcode: 0x15, index: 0 },
+            Op::Branch { opcode: 0x99, target: d },
+            Op::Branch { opcode: 0xa7, target: a },
+            Op::Label(d),
+            Op::Simple(0xb1),
+        ];
+        let (assembled, decoded) = round_trip(&ops);
+        let pc = |label| assembled.pc(label).unwrap();
+        let branches: Vec<_> = decoded
+            .into_iter()
+            .filter(|(_, _, targets)| !targets.is_empty())
+            .map(|(mnemonic, _, targets)| (mnemonic, targets))
+            .collect();
+        assert_eq!(
+            branches,
+            vec![
+                ("tableswitch", vec![pc(d), pc(a), pc(b)]),
+                ("lookupswitch", vec![pc(a), pc(d), pc(b)]),
+                ("ifeq", vec![pc(d)]),
+                ("goto", vec![pc(a)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_reach_branches_are_widened() {
+        let far = Label(0);
+        let mut ops = vec![
+            Op::Local { opcode: 0x15, index: 0 },
+            Op::Branch { opcode: 0x99, target: far },
+            Op::Branch { opcode: 0xa7, target: far },
+        ];
+        ops.resize(ops.len() + 40000, Op::Simple(0x00));
+        ops.extend([Op::Label(far), Op::Simple(0xb1)]);
+        let (assembled, decoded) = round_trip(&ops);
+        let far = assembled.pc(far).unwrap();
+        assert_eq!(far, 14 + 40000);
+        // `ifeq far` becomes `ifne` over a `goto_w far`.
+        assert_eq!(
+            decoded[..4].to_vec(),
+            vec![
+                ("iload_0", 0, vec![]),
+                ("ifne", 1, vec![9]),
+                ("goto_w", 4, vec![far]),
+                ("goto_w", 9, vec![far]),
+            ]
+        );
+        assert_eq!(decoded.last().unwrap().1, far);
+    }
+
+    #[test]
+    fn unbound_labels_fail() {
+        let ops = [Op::Branch { opcode: 0xa7, target: Label(0) }];
+        assert!(assemble(&ops, &mut ConstantPoolBuilder::new()).is_err());
+    }
+}
@@ -1,614 +1,1872 @@
-use std::fmt::Debug;
-
-use binrw::{binread, BinRead, VecArgs};
-
-use crate::{
-    instruction::{Instruction, MethodHandle}, raw::{Attributes, ClassIndex, ConstantPoolItem, MethodHandleIndex, NameAndTypeIndex, Utf8Index}, ClassFile, Error
-};
-
-pub struct ConstantValue<'a> {
-    class_file: &'a ClassFile,
-    constantvalue_index: u16,
-}
-
-impl<'a> BinRead for ConstantValue<'a> {
-    type Args<'b> = (&'a ClassFile,);
-
-    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
-        reader: &mut R,
-        endian: binrw::Endian,
-        (class_file,): Self::Args<'_>,
-    ) -> binrw::prelude::BinResult<Self> {
-        let constantvalue_index = u16::read_options(reader, endian, ())?;
-        Ok(Self {
-            class_file,
-            constantvalue_index,
-        })
-    }
-}
-
-impl<'a> ConstantValue<'a> {
-    pub fn int_value(&self) -> crate::Result<i32> {
-        match &self.class_file.constant_pool.0[self.constantvalue_index as usize - 1] {
-            ConstantPoolItem::Integer { value } => Ok(*value),
-            x => Err(Error::ConstantPoolError(format!(
-                "Expected Integer, instead got {:?}",
-                x
-            ))),
-        }
-    }
-
-    pub fn float_value(&self) -> crate::Result<f32> {
-        match &self.class_file.constant_pool.0[self.constantvalue_index as usize - 1] {
-            ConstantPoolItem::Float { value } => Ok(*value),
-            x => Err(Error::ConstantPoolError(format!(
-                "Expected Float, instead got {:?}",
-                x
-            ))),
-        }
-    }
-
-    pub fn long_value(&self) -> crate::Result<i64> {
-        match &self.class_file.constant_pool.0[self.constantvalue_index as usize - 1] {
-            ConstantPoolItem::Long { value } => Ok(*value),
-            x => Err(Error::ConstantPoolError(format!(
-                "Expected Long, instead got {:?}",
-                x
-            ))),
-        }
-    }
-
-    pub fn double_value(&self) -> crate::Result<f64> {
-        match &self.class_file.constant_pool.0[self.constantvalue_index as usize - 1] {
-            ConstantPoolItem::Double { value } => Ok(*value),
-            x => Err(Error::ConstantPoolError(format!(
-                "Expected Double, instead got {:?}",
-                x
-            ))),
-        }
-    }
-
-    pub fn string_value(&self) -> crate::Result<&'a str> {
-        match &self.class_file.constant_pool.0[self.constantvalue_index as usize - 1] {
-            ConstantPoolItem::String { string_index } => {
-                string_index.get_as_string(self.class_file)
-            }
-            x => Err(Error::ConstantPoolError(format!(
-                "Expected String, instead got {:?}",
-                x
-            ))),
-        }
-    }
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-pub struct Exception<'a> {
-    #[br(calc = cf)]
-    class_file: &'a ClassFile,
-    start_pc: u16,
-    end_pc: u16,
-    handler_pc: u16,
-    #[br(map = |x: ClassIndex| { if x.0 == 0 { None } else { Some(x) } } )]
-    catch_type: Option<ClassIndex>,
-}
-
-impl<'a> Debug for Exception<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Exception")
-            .field("start_pc", &self.start_pc)
-            .field("end_pc", &self.end_pc)
-            .field("handler_pc", &self.handler_pc)
-            .field(
-                "catch_type",
-                &self
-                    .catch_type
-                    .as_ref()
-                    .map(|x| x.get_as_string_impl(&self.class_file.constant_pool)),
-            )
-            .finish()
-    }
-}
-
-pub struct Code<'a> {
-    class_file: &'a ClassFile,
-    max_stack: u16,
-    max_locals: u16,
-    code: Vec<u8>,
-    exception_table: Vec<Exception<'a>>,
-    attributes: Attributes,
-}
-
-macro_rules! attribute {
-    ($strct:ident, $name:ident) => {
-        pub fn $name(&self) -> crate::Result<Option<$strct<'a>>> {
-            match self.attributes.0.get(stringify!($strct)) {
-                Some(x) => {
-                    let mut buf = std::io::Cursor::new(&x[..]);
-                    let value = $strct::read_be_args(&mut buf, (self.class_file,))?;
-                    Ok(Some(value))
-                }
-                None => Ok(None),
-            }
-        }
-    };
-}
-
-impl<'a> Code<'a> {
-    attribute!(StackMapTable, stack_map_table);
-    attribute!(LineNumberTable, line_number_table);
-    attribute!(LocalVariableTable, local_variable_table);
-    attribute!(LocalVariableTypeTable, local_variable_type_table);
-
-    pub fn instructions(&self) -> super::Result<Vec<Instruction>> {
-        let mut cursor = std::io::Cursor::new(&self.code[..]);
-        let mut res = Vec::new();
-        loop {
-            let _p = cursor.position();
-            if cursor.position() >= self.code.len() as u64 {
-                break
-            }
-            let next = match Instruction::read_be_args(&mut cursor, (self.class_file,)) {
-                Ok(x) => x,
-                Err(e) => return Err(super::Error::from(e)),
-            };
-            res.push(next);
-        }
-        Ok(res)
-    }
-}
-
-impl<'a> Debug for Code<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Code")
-            .field("max_stack", &self.max_stack)
-            .field("max_locals", &self.max_locals)
-            .field("code", &"...")
-            .field("exception_table", &self.exception_table)
-            .field("attributes", &self.attributes)
-            .finish()
-    }
-}
-
-impl<'a> BinRead for Code<'a> {
-    type Args<'b> = (&'a ClassFile,);
-
-    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
-        reader: &mut R,
-        endian: binrw::Endian,
-        (cf,): Self::Args<'_>,
-    ) -> binrw::prelude::BinResult<Self> {
-        let class_file = cf;
-        let max_stack = u16::read_options(reader, endian, ())?;
-        let max_locals = u16::read_options(reader, endian, ())?;
-        let code_length = u32::read_options(reader, endian, ())?;
-        let mut code = vec![0u8; code_length as usize];
-        reader.read_exact(&mut code)?;
-        let exception_table_length = u16::read_options(reader, endian, ())?;
-        let mut exception_table = Vec::new();
-        for _ in 0..exception_table_length {
-            let exception = Exception::read_options(reader, endian, (cf,))?;
-            exception_table.push(exception);
-        }
-        let attributes = Attributes::read_options(reader, endian, (&cf.constant_pool,))?;
-        Ok(Self {
-            class_file,
-            max_stack,
-            max_locals,
-            code,
-            exception_table,
-            attributes,
-        })
-    }
-}
-
-#[binread]
-enum VerificationTypeInfo {
-    #[br(magic = 0u8)]
-    Top,
-    #[br(magic = 1u8)]
-    Integer,
-    #[br(magic = 2u8)]
-    Float,
-    #[br(magic = 4u8)]
-    Long,
-    #[br(magic = 3u8)]
-    Double,
-    #[br(magic = 5u8)]
-    Null,
-    #[br(magic = 6u8)]
-    UninitializedThis,
-    #[br(magic = 7u8)]
-    Object { cpool_index: crate::raw::ClassIndex },
-    #[br(magic = 8u8)]
-    Uninitialized { offset: u16 },
-}
-
-enum StackMapFrame {
-    SameFrame {
-        offset_delta: u16,
-    },
-    SameLocals1StackItemFrame {
-        offset_delta: u16,
-        stack: [VerificationTypeInfo; 1],
-    },
-    ChopFrame {
-        locals_to_remove: u8,
-        offset_delta: u16,
-    },
-    AppendFrame {
-        offset_delta: u16,
-        locals: Vec<VerificationTypeInfo>,
-    },
-    FullFrame {
-        offset_delta: u16,
-        locals: Vec<VerificationTypeInfo>,
-        stack: Vec<VerificationTypeInfo>,
-    },
-}
-
-impl BinRead for StackMapFrame {
-    type Args<'a> = ();
-
-    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
-        reader: &mut R,
-        endian: binrw::Endian,
-        _args: Self::Args<'_>,
-    ) -> binrw::prelude::BinResult<Self> {
-        let pos = reader.stream_position()?;
-        let magic = u8::read_options(reader, endian, ())?;
-        match magic {
-            0u8..=63u8 => Ok(Self::SameFrame {
-                offset_delta: magic as u16,
-            }),
-            64u8..=127u8 => {
-                let stack = <[VerificationTypeInfo; 1]>::read_options(reader, endian, ())?;
-                Ok(Self::SameLocals1StackItemFrame {
-                    offset_delta: magic as u16 - 64,
-                    stack,
-                })
-            }
-            128u8..=246u8 => {
-                return Err(binrw::Error::BadMagic {
-                    pos,
-                    found: Box::new(magic),
-                })
-            }
-            247u8 => {
-                let offset_delta = u16::read_options(reader, endian, ())?;
-                let stack = <[VerificationTypeInfo; 1]>::read_options(reader, endian, ())?;
-                Ok(Self::SameLocals1StackItemFrame {
-                    offset_delta,
-                    stack,
-                })
-            }
-            248u8..=250u8 => {
-                let offset_delta = u16::read_options(reader, endian, ())?;
-                Ok(Self::ChopFrame {
-                    locals_to_remove: 251u8 - magic,
-                    offset_delta,
-                })
-            }
-            251u8 => {
-                let offset_delta = u16::read_options(reader, endian, ())?;
-                Ok(Self::SameFrame { offset_delta })
-            }
-            252u8..=254u8 => {
-                let k = magic - 251;
-                let offset_delta = u16::read_options(reader, endian, ())?;
-                let locals = <Vec<VerificationTypeInfo>>::read_options(
-                    reader,
-                    endian,
-                    VecArgs::builder().count(k as usize).finalize(),
-                )?;
-                Ok(Self::AppendFrame {
-                    offset_delta,
-                    locals,
-                })
-            }
-            255u8 => {
-                let offset_delta = u16::read_options(reader, endian, ())?;
-                let num_locals = u16::read_options(reader, endian, ())?;
-                let locals = <Vec<VerificationTypeInfo>>::read_options(
-                    reader,
-                    endian,
-                    VecArgs::builder().count(num_locals as usize).finalize(),
-                )?;
-                let num_stack = u16::read_options(reader, endian, ())?;
-                let stack = <Vec<VerificationTypeInfo>>::read_options(
-                    reader,
-                    endian,
-                    VecArgs::builder().count(num_stack as usize).finalize(),
-                )?;
-                Ok(Self::FullFrame {
-                    offset_delta,
-                    locals,
-                    stack,
-                })
-            }
-        }
-    }
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-pub struct StackMapTable<'a> {
-    #[br(calc = cf)]
-    class_file: &'a ClassFile,
-    #[br(temp)]
-    number_of_entries: u16,
-    #[br(count = number_of_entries)]
-    entries: Vec<StackMapFrame>,
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-pub struct Exceptions<'a> {
-    #[br(calc = cf)]
-    class_file: &'a ClassFile,
-    #[br(temp)]
-    number_of_exceptions: u16,
-    #[br(count = number_of_exceptions)]
-    exception_index_table: Vec<ClassIndex>,
-}
-
-impl<'a> Exceptions<'a> {
-    pub fn class_names(&self) -> crate::Result<Vec<&'a str>> {
-        self.exception_index_table
-            .iter()
-            .map(|x| x.get_as_string(&self.class_file))
-            .collect()
-    }
-}
-
-bitflags::bitflags! {
-    #[derive(Debug)]
-    struct InnerClassAccessFlags: u16 {
-        #[doc = "Marked or implicitly public in source."]
-        const PUBLIC = 0x0001;
-        #[doc = "Marked private in source."]
-        const PRIVATE = 0x0002;
-        #[doc = "Marked protected in source."]
-        const PROTECTED = 0x0004;
-        #[doc = "Marked or implicitly static in source."]
-        const STATIC = 0x0008;
-        #[doc = "Marked or implicitly final in source."]
-        const FINAL = 0x0010;
-        #[doc = "Was an interface in source."]
-        const INTERFACE = 0x0200;
-        #[doc = "Marked or implicitly abstract in source."]
-        const ABSTRACT = 0x0400;
-        #[doc = "Declared synthetic; not present in the source code."]
-        const SYNTHETIC = 0x1000;
-        #[doc = "Declared as an annotation interface."]
-        const ANNOTATION = 0x2000;
-        #[doc = "Declared as an enum class. "]
-        const ENUM = 0x4000;
-    }
-}
-
-#[binread]
-struct InnerClass {
-    inner_class_info: ClassIndex,
-    #[br(map = |x: ClassIndex| { if x.0 == 0 { None } else { Some(x) } } )]
-    outer_class_info: Option<ClassIndex>,
-    #[br(map = |x: Utf8Index| { if x.0 == 0 { None } else { Some(x) } } )]
-    inner_name: Option<Utf8Index>,
-    #[br(map = |x: u16| InnerClassAccessFlags::from_bits_truncate(x))]
-    inner_class_access_flags: InnerClassAccessFlags,
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-pub struct InnerClasses<'a> {
-    #[br(calc = cf)]
-    class_file: &'a ClassFile,
-    #[br(temp)]
-    number_of_classes: u16,
-    #[br(count = number_of_classes)]
-    classes: Vec<InnerClass>,
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-pub struct EnclosingMethod<'a> {
-    #[br(calc = cf)]
-    class_file: &'a ClassFile,
-    class_index: ClassIndex,
-    method_index: NameAndTypeIndex,
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-pub struct Signature<'a> {
-    #[br(calc = cf)]
-    class_file: &'a ClassFile,
-    signature_index: Utf8Index,
-}
-
-impl<'a> Signature<'a> {
-    pub(crate) fn get_class(&self) -> crate::Result<crate::signature::ClassSignature<'a>> {
-        Ok(crate::signature::ClassSignature::parse(
-            self.signature_index.get_as_string(self.class_file)?,
-        )?
-        .1)
-    }
-
-    pub(crate) fn get_method(&self) -> crate::Result<crate::signature::MethodSignature<'a>> {
-        Ok(crate::signature::MethodSignature::parse(
-            self.signature_index.get_as_string(self.class_file)?,
-        )?
-        .1)
-    }
-
-    pub(crate) fn get_field(&self) -> crate::Result<crate::signature::ReferenceType<'a>> {
-        Ok(crate::signature::ReferenceType::parse(
-            self.signature_index.get_as_string(self.class_file)?,
-        )?
-        .1)
-    }
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-pub struct SourceFile<'a> {
-    #[br(calc = cf)]
-    class_file: &'a ClassFile,
-    sourcefile_index: Utf8Index,
-}
-
-impl<'a> SourceFile<'a> {
-    pub fn get(&self) -> crate::Result<&'a str> {
-        self.sourcefile_index.get_as_string(self.class_file)
-    }
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-pub struct LineNumberTable<'a> {
-    #[br(calc = cf)]
-    class_file: &'a ClassFile,
-    #[br(temp)]
-    line_number_table_length: u16,
-    #[br(count = line_number_table_length)]
-    line_number_table: Vec<(u16, u16)>,
-}
-
-impl<'a> LineNumberTable<'a> {
-    // TODO interact with code instructions.
-}
-
-pub struct LocalVariable<'a> {
-    pub start_pc: u16,
-    pub length: u16,
-    pub name: &'a str,
-    pub descriptor: crate::field::TypeDescriptor<'a>,
-    pub index: u16,
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-pub struct LocalVariableTable<'a> {
-    #[br(calc = cf)]
-    class_file: &'a ClassFile,
-    #[br(temp)]
-    local_variable_table_length: u16,
-    #[br(count = local_variable_table_length)]
-    local_variable_table: Vec<(u16, u16, Utf8Index, Utf8Index, u16)>,
-}
-
-impl<'a> LocalVariableTable<'a> {
-    pub fn get_variables(&self) -> crate::Result<Vec<LocalVariable<'a>>> {
-        self.local_variable_table
-            .iter()
-            .map(|(start_pc, length, name, descriptor, index)| {
-                Ok(LocalVariable {
-                    start_pc: *start_pc,
-                    length: *length,
-                    name: name.get_as_string(self.class_file)?,
-                    descriptor: crate::field::TypeDescriptor::parse(
-                        descriptor.get_as_string(self.class_file)?,
-                    )?
-                    .1,
-                    index: *index,
-                })
-            })
-            .collect()
-    }
-}
-
-pub struct LocalVariableType<'a> {
-    pub start_pc: u16,
-    pub length: u16,
-    pub name: &'a str,
-    pub signature: crate::signature::ReferenceType<'a>,
-    pub index: u16,
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-pub struct LocalVariableTypeTable<'a> {
-    #[br(calc = cf)]
-    class_file: &'a ClassFile,
-    #[br(temp)]
-    local_variable_type_table_length: u16,
-    #[br(count = local_variable_type_table_length)]
-    local_variable_type_table: Vec<(u16, u16, Utf8Index, Utf8Index, u16)>,
-}
-
-impl<'a> LocalVariableTypeTable<'a> {
-    pub fn get_variable_types(&self) -> crate::Result<Vec<LocalVariableType<'a>>> {
-        self.local_variable_type_table
-            .iter()
-            .map(|(start_pc, length, name, signature, index)| {
-                Ok(LocalVariableType {
-                    start_pc: *start_pc,
-                    length: *length,
-                    name: name.get_as_string(self.class_file)?,
-                    signature: crate::signature::ReferenceType::parse(
-                        signature.get_as_string(self.class_file)?,
-                    )?
-                    .1,
-                    index: *index,
-                })
-            })
-            .collect()
-    }
-}
-
-// TODO Annotations
-
-#[binread]
-struct BootstrapMethodRaw {
-    bootstrap_method_ref: MethodHandleIndex,
-    #[br(temp)]
-    num_bootstrap_args: u16,
-    #[br(count = num_bootstrap_args)]
-    bootstrap_args: Vec<u16>,
-}
-
-#[derive(Debug)]
-pub struct BootstrapMethod<'a> {
-    pub method: MethodHandle<'a>,
-    // TODO arguments
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-pub struct BootstrapMethods<'a> {
-    #[br(calc = cf)]
-    class_file: &'a ClassFile,
-    #[br(temp)]
-    bootstrap_methods_length: u16,
-    #[br(count = bootstrap_methods_length)]
-    bootstrap_methods: Vec<BootstrapMethodRaw>,
-}
-
-impl<'a> BootstrapMethods<'a> {
-    pub fn get(&self, idx: u16) -> super::Result<Option<BootstrapMethod<'a>>> {
-        if let Some(method) = self.bootstrap_methods.get(idx as usize) {
-            let method = MethodHandle::from_u16(method.bootstrap_method_ref.0, self.class_file)?;
-            Ok(Some(BootstrapMethod { method }))
-        } else {
-            Ok(None)
-        }
-    }
-}
-
-// TODO MethodParameters
-
-// TODO Module
-
-// TODO ModulePackage
-
-// TODO ModuleMainClass
-
-// TODO NestHost
-
-// TODO NestMembers
-
-// TODO Record
-
-// TODO PermittedSubclasses
-
-
+use std::{collections::HashMap, fmt::Debug, time::Instant};
+
+use binrw::{binread, BinRead, VecArgs};
+
+use crate::{
+    field::TypeDescriptor, hexdump::RawAttribute, instruction::{BranchTarget, Instruction, InstructionAt, LAST_OPCODE, LoadableConstant, MethodHandle, OPCODE_TABLE}, metrics::Phase, span::Span, trace, raw::{Attributes, ClassIndex, ConstantPoolItem, MethodHandleIndex, ModuleIndex, NameAndTypeIndex, PackageIndex, Utf8Index}, ClassFile, Error
+};
+
+/// A reader over attribute data that remembers how far the structure read, and
+/// how many bytes it asked for when it ran off the end. Positions are reported
+/// relative to the start of the class file, `base` being where `data` begins.
+struct BoundedReader<'d> {
+    data: &'d [u8],
+    base: u64,
+    pos: u64,
+    furthest: u64,
+    overrun: Option<u64>,
+}
+
+impl<'d> std::io::Read for BoundedReader<'d> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let wanted = self.pos + buf.len() as u64;
+        if wanted > self.data.len() as u64 {
+            self.overrun = self.overrun.max(Some(wanted));
+        }
+        let start = self.pos.min(self.data.len() as u64) as usize;
+        let n = buf.len().min(self.data.len() - start);
+        buf[..n].copy_from_slice(&self.data[start..start + n]);
+        self.pos += n as u64;
+        self.furthest = self.furthest.max(self.pos);
+        Ok(n)
+    }
+}
+
+impl<'d> std::io::Seek for BoundedReader<'d> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let mut cursor = std::io::Cursor::new(self.data);
+        cursor.set_position(self.pos);
+        let pos = match pos {
+            std::io::SeekFrom::Start(x) => std::io::SeekFrom::Start(x.saturating_sub(self.base)),
+            x => x,
+        };
+        self.pos = cursor.seek(pos)?;
+        Ok(self.base + self.pos)
+    }
+}
+
+/// An attribute type decoded from the attribute called [`Self::NAME`], see
+/// [`crate::ClassFile::get_attribute`]. Implement it, along with [`BinRead`]
+/// taking the class file, to read attributes this crate does not know about.
+pub trait KnownAttribute<'a>: for<'b> BinRead<Args<'b> = (&'a ClassFile,)> {
+    const NAME: &'static str;
+}
+
+impl Attributes {
+    /// Decodes the last attribute called `T::NAME`.
+    pub(crate) fn decode<'a, T: KnownAttribute<'a>>(&self, class_file: &'a ClassFile) -> crate::Result<Option<T>> {
+        match self.get(T::NAME) {
+            Some(x) => decode(T::NAME, x, self.data_offset(T::NAME), class_file).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Decodes the attribute `name` from its `data`, found at `offset` in the class
+/// file, checking that the structure uses exactly the declared number of bytes.
+pub(crate) fn decode<'a, T>(
+    name: &str,
+    data: &[u8],
+    offset: u64,
+    class_file: &'a ClassFile,
+) -> crate::Result<T>
+where
+    T: for<'b> BinRead<Args<'b> = (&'a ClassFile,)>,
+{
+    trace::span!(TRACE, "attribute", name, length = data.len());
+    let start = Instant::now();
+    let result = decode_checked(name, data, offset, class_file);
+    if let Some(metrics) = &class_file.metrics {
+        match &result {
+            Ok(_) => {
+                metrics.attribute_decoded(name);
+                metrics.phase_finished(Phase::Attribute, start.elapsed());
+            }
+            Err(_) => metrics.error(Phase::Attribute),
+        }
+    }
+    result
+}
+
+fn decode_checked<'a, T>(
+    name: &str,
+    data: &[u8],
+    offset: u64,
+    class_file: &'a ClassFile,
+) -> crate::Result<T>
+where
+    T: for<'b> BinRead<Args<'b> = (&'a ClassFile,)>,
+{
+    let mut reader = BoundedReader {
+        data,
+        base: offset,
+        pos: 0,
+        furthest: 0,
+        overrun: None,
+    };
+    let length_error = |required| Error::AttributeLength {
+        name: name.to_string(),
+        declared: data.len() as u32,
+        required,
+    };
+    let value = match T::read_be_args(&mut reader, (class_file,)) {
+        Ok(x) => x,
+        Err(e) => {
+            let required = reader.overrun.or_else(|| match e.custom_err::<Error>() {
+                Some(Error::LengthExceedsInput { offset: at, length, .. }) => Some(at - offset + length),
+                _ => None,
+            });
+            return Err(match required {
+                Some(required) => length_error(required),
+                None => e.into(),
+            });
+        }
+    };
+    if reader.furthest < data.len() as u64 && !class_file.leniency.trailing_attribute_bytes {
+        return Err(length_error(reader.furthest));
+    }
+    Ok(value)
+}
+
+pub struct ConstantValue<'a> {
+    class_file: &'a ClassFile,
+    constantvalue_index: u16,
+}
+
+impl<'a> BinRead for ConstantValue<'a> {
+    type Args<'b> = (&'a ClassFile,);
+
+    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        (class_file,): Self::Args<'_>,
+    ) -> binrw::prelude::BinResult<Self> {
+        let constantvalue_index = u16::read_options(reader, endian, ())?;
+        Ok(Self {
+            class_file,
+            constantvalue_index,
+        })
+    }
+}
+
+impl<'a> ConstantValue<'a> {
+    /// The referenced constant, or `None` if the index is out of range.
+    pub fn item(&self) -> Option<&'a ConstantPoolItem> {
+        (self.constantvalue_index as usize)
+            .checked_sub(1)
+            .and_then(|x| self.class_file.constant_pool.0.get(x))
+    }
+
+    pub fn int_value(&self) -> crate::Result<i32> {
+        match self.class_file.constant_pool.get(self.constantvalue_index)? {
+            ConstantPoolItem::Integer { value } => Ok(*value),
+            x => Err(Error::ConstantPoolError(format!(
+                "Expected Integer, instead got {:?}",
+                x
+            ))),
+        }
+    }
+
+    pub fn float_value(&self) -> crate::Result<f32> {
+        match self.class_file.constant_pool.get(self.constantvalue_index)? {
+            ConstantPoolItem::Float { value } => Ok(*value),
+            x => Err(Error::ConstantPoolError(format!(
+                "Expected Float, instead got {:?}",
+                x
+            ))),
+        }
+    }
+
+    pub fn long_value(&self) -> crate::Result<i64> {
+        match self.class_file.constant_pool.get(self.constantvalue_index)? {
+            ConstantPoolItem::Long { value } => Ok(*value),
+            x => Err(Error::ConstantPoolError(format!(
+                "Expected Long, instead got {:?}",
+                x
+            ))),
+        }
+    }
+
+    pub fn double_value(&self) -> crate::Result<f64> {
+        match self.class_file.constant_pool.get(self.constantvalue_index)? {
+            ConstantPoolItem::Double { value } => Ok(*value),
+            x => Err(Error::ConstantPoolError(format!(
+                "Expected Double, instead got {:?}",
+                x
+            ))),
+        }
+    }
+
+    pub fn string_value(&self) -> crate::Result<&'a str> {
+        match self.class_file.constant_pool.get(self.constantvalue_index)? {
+            ConstantPoolItem::String { string_index } => {
+                string_index.get_as_string(self.class_file)
+            }
+            x => Err(Error::ConstantPoolError(format!(
+                "Expected String, instead got {:?}",
+                x
+            ))),
+        }
+    }
+
+    /// Like [`Self::string_value`], but as UTF-16 code units for exact comparisons.
+    pub fn string_value_utf16(&self) -> crate::Result<Vec<u16>> {
+        match self.class_file.constant_pool.get(self.constantvalue_index)? {
+            ConstantPoolItem::String { string_index } => {
+                string_index.get_as_utf16_impl(&self.class_file.constant_pool)
+            }
+            x => Err(Error::ConstantPoolError(format!(
+                "Expected String, instead got {:?}",
+                x
+            ))),
+        }
+    }
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct Exception<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    start_pc: u16,
+    end_pc: u16,
+    handler_pc: u16,
+    #[br(map = |x: ClassIndex| { if x.0 == 0 { None } else { Some(x) } } )]
+    catch_type: Option<ClassIndex>,
+}
+
+impl<'a> Debug for Exception<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Exception")
+            .field("start_pc", &self.start_pc)
+            .field("end_pc", &self.end_pc)
+            .field("handler_pc", &self.handler_pc)
+            .field(
+                "catch_type",
+                &self
+                    .catch_type
+                    .as_ref()
+                    .map(|x| x.get_as_string_impl(&self.class_file.constant_pool)),
+            )
+            .finish()
+    }
+}
+
+/// An entry of the exception table with its catch type resolved and its pcs
+/// located among the decoded instructions, see [`Code::exception_handlers`].
+#[derive(Debug, Clone)]
+pub struct ExceptionHandler<'a> {
+    /// The first instruction covered.
+    pub start: BranchTarget,
+    /// The first instruction after those covered. When the range reaches the
+    /// end of the code this is the code length and the instruction count.
+    pub end: BranchTarget,
+    /// The first instruction of the handler.
+    pub handler: BranchTarget,
+    /// The class caught, e.g. `java/io/IOException`, or `None` for a handler
+    /// that catches everything, as compiled for `finally`.
+    pub catch_type: Option<&'a str>,
+}
+
+impl ExceptionHandler<'_> {
+    /// Whether the instruction at `pc` is covered by this handler.
+    pub fn covers(&self, pc: u32) -> bool {
+        self.start.pc <= pc && pc < self.end.pc
+    }
+}
+
+pub struct Code<'a> {
+    class_file: &'a ClassFile,
+    pub(crate) max_stack: u16,
+    pub(crate) max_locals: u16,
+    code_offset: u64,
+    code: Vec<u8>,
+    exception_table: Vec<Exception<'a>>,
+    pub(crate) attributes: Attributes,
+}
+
+impl<'a> Code<'a> {
+    /// Decodes the last attribute of the code called `T::NAME`.
+    pub fn get_attribute<T: KnownAttribute<'a>>(&self) -> crate::Result<Option<T>> {
+        self.attributes.decode(self.class_file)
+    }
+
+    pub fn stack_map_table(&self) -> crate::Result<Option<StackMapTable<'a>>> {
+        self.get_attribute()
+    }
+
+    pub fn line_number_table(&self) -> crate::Result<Option<LineNumberTable<'a>>> {
+        self.get_attribute()
+    }
+
+    pub fn local_variable_table(&self) -> crate::Result<Option<LocalVariableTable<'a>>> {
+        self.get_attribute()
+    }
+
+    pub fn local_variable_type_table(&self) -> crate::Result<Option<LocalVariableTypeTable<'a>>> {
+        self.get_attribute()
+    }
+
+    /// The `code` array as stored in the file.
+    #[cfg(test)]
+    pub(crate) fn code_bytes(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// The constant pool index among the operands of the instruction at
+    /// `pc`, where [`OPCODE_TABLE`] lays one out. Decoded instructions mostly
+    /// keep only the constant, which is not enough to name its entry.
+    pub(crate) fn constant_index(&self, pc: u32) -> Option<u16> {
+        let opcode = *self.code.get(pc as usize)?;
+        OPCODE_TABLE
+            .get(opcode as usize)?
+            .operands
+            .constant_index(self.code.get(pc as usize + 1..)?)
+    }
+
+    /// The span of the `code` array.
+    pub fn code_span(&self) -> Span {
+        Span::new(self.code_offset, self.code.len() as u64)
+    }
+
+    pub fn attribute_span(&self, name: &str) -> Option<Span> {
+        self.attributes.span(name)
+    }
+
+    pub fn undecoded_attributes(&self) -> Vec<RawAttribute<'_>> {
+        self.attributes.undecoded(self.class_file)
+    }
+
+    /// Every attribute as raw bytes, duplicates included, in the order they
+    /// appear in the file.
+    pub fn raw_attributes(&self) -> Vec<RawAttribute<'_>> {
+        self.attributes.raw(self.class_file)
+    }
+
+    /// Every attribute of the code, decoded, in the order they appear in the file.
+    pub fn attributes(&self) -> crate::Result<Vec<Attribute<'_>>> {
+        self.attributes.typed(self.class_file, AttributeLocation::Code)
+    }
+
+    /// The pc and span of every instruction.
+    pub fn instruction_spans(&self) -> super::Result<Vec<(u32, Span)>> {
+        Ok(self
+            .instructions_at()?
+            .iter()
+            .map(|x| (x.pc, Span::new(self.code_offset + x.pc as u64, x.length as u64)))
+            .collect())
+    }
+
+    /// Every instruction with its pc and encoded length, in code order.
+    pub fn instructions_at(&self) -> super::Result<Vec<InstructionAt<'a>>> {
+        let mut pcs = self.instructions_with_pcs()?.into_iter().peekable();
+        let mut res = Vec::new();
+        while let Some((pc, instruction)) = pcs.next() {
+            let end = pcs.peek().map_or(self.code.len() as u32, |x| x.0);
+            res.push(InstructionAt {
+                pc,
+                length: end - pc,
+                instruction,
+            });
+        }
+        Ok(res)
+    }
+
+    /// The targets of every instruction, in the order of [`Self::instructions`]
+    /// and of [`Instruction::branch_offsets`], empty for those that do not
+    /// branch. Fails with [`Error::InvalidBranchTarget`] if any target is not
+    /// the start of an instruction.
+    pub fn branch_targets(&self) -> super::Result<Vec<Vec<BranchTarget>>> {
+        let instructions = self.instructions_with_pcs()?;
+        let index: HashMap<u32, usize> = instructions
+            .iter()
+            .enumerate()
+            .map(|(i, (pc, _))| (*pc, i))
+            .collect();
+        instructions
+            .iter()
+            .map(|(pc, instruction)| {
+                instruction
+                    .branch_offsets()
+                    .into_iter()
+                    .map(|offset| {
+                        offset
+                            .target(*pc)
+                            .and_then(|target| index.get(&target).map(|&i| BranchTarget { pc: target, index: i }))
+                            .ok_or(Error::InvalidBranchTarget {
+                                pc: *pc,
+                                offset: offset.0,
+                            })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The exception table in the order the JVM searches it. Fails with
+    /// [`Error::InvalidExceptionHandler`] if a pc is not the start of an
+    /// instruction, other than an end pc equal to the code length.
+    pub fn exception_handlers(&self) -> super::Result<Vec<ExceptionHandler<'a>>> {
+        let pcs: Vec<u32> = self.instructions_with_pcs()?.into_iter().map(|(pc, _)| pc).collect();
+        let target = |pc: u16, end: bool| {
+            let found = match pcs.binary_search(&(pc as u32)) {
+                Ok(index) => Some(index),
+                Err(index) if end && pc as usize == self.code.len() => Some(index),
+                Err(_) => None,
+            };
+            found
+                .map(|index| BranchTarget { pc: pc as u32, index })
+                .ok_or(Error::InvalidExceptionHandler { pc })
+        };
+        self.exception_table
+            .iter()
+            .map(|x| {
+                Ok(ExceptionHandler {
+                    start: target(x.start_pc, false)?,
+                    end: target(x.end_pc, true)?,
+                    handler: target(x.handler_pc, false)?,
+                    catch_type: x.catch_type.as_ref().map(|x| x.get_as_string(self.class_file)).transpose()?,
+                })
+            })
+            .collect()
+    }
+
+    /// The handlers covering the instruction at `pc`, in the order the JVM
+    /// tries them when it throws.
+    pub fn exception_handlers_at(&self, pc: u32) -> super::Result<Vec<ExceptionHandler<'a>>> {
+        Ok(self
+            .exception_handlers()?
+            .into_iter()
+            .filter(|x| x.covers(pc))
+            .collect())
+    }
+
+    /// Every instruction in code order, without positions; see
+    /// [`Self::instructions_at`] to correlate them with offsets.
+    pub fn instructions(&self) -> super::Result<Vec<Instruction<'a>>> {
+        Ok(self
+            .instructions_with_pcs()?
+            .into_iter()
+            .map(|(_, x)| x)
+            .collect())
+    }
+
+    pub(crate) fn instructions_with_pcs(&self) -> super::Result<Vec<(u32, Instruction<'a>)>> {
+        let start = Instant::now();
+        let result = self.decode_instructions();
+        if let Some(metrics) = &self.class_file.metrics {
+            match &result {
+                Ok(_) => metrics.phase_finished(Phase::Instructions, start.elapsed()),
+                Err(_) => metrics.error(Phase::Instructions),
+            }
+        }
+        result
+    }
+
+    /// Decodes the one instruction at `pc`, or returns `None` if `pc` is past
+    /// the end of the code. Switch padding is aligned to the start of the
+    /// code, as for [`Self::instructions`], but `pc` is not checked to be the
+    /// start of an instruction.
+    pub fn instruction_at(&self, pc: u32) -> super::Result<Option<InstructionAt<'a>>> {
+        if pc as usize >= self.code.len() {
+            return Ok(None);
+        }
+        let mut cursor = std::io::Cursor::new(&self.code[..]);
+        cursor.set_position(pc as u64);
+        let instruction = self.decode_next(&mut cursor)?;
+        Ok(Some(InstructionAt {
+            pc,
+            length: cursor.position() as u32 - pc,
+            instruction,
+        }))
+    }
+
+    fn decode_instructions(&self) -> super::Result<Vec<(u32, Instruction<'a>)>> {
+        let mut cursor = std::io::Cursor::new(&self.code[..]);
+        let mut res = Vec::new();
+        loop {
+            let pc = cursor.position();
+            if pc >= self.code.len() as u64 {
+                break
+            }
+            res.push((pc as u32, self.decode_next(&mut cursor)?));
+        }
+        Ok(res)
+    }
+
+    /// Decodes the instruction at the position of `cursor`, which must be
+    /// inside the code.
+    fn decode_next(&self, cursor: &mut std::io::Cursor<&[u8]>) -> super::Result<Instruction<'a>> {
+        let pc = cursor.position();
+        let opcode = self.code[pc as usize];
+        if opcode > LAST_OPCODE && self.class_file.leniency.unknown_opcodes {
+            trace::event!(DEBUG, pc, opcode, "unknown opcode");
+            cursor.set_position(pc + 1);
+            return Ok(Instruction::Unknown { opcode, pc: pc as u32 });
+        }
+        match Instruction::read_be_args(cursor, (self.class_file,)) {
+            Ok(x) => {
+                trace::event!(TRACE, pc, instruction = ?x);
+                Ok(x)
+            }
+            Err(e) => {
+                trace::event!(DEBUG, pc, error = %e, "instruction decode failed");
+                Err(super::Error::from(e))
+            }
+        }
+    }
+}
+
+impl<'a> Debug for Code<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Code")
+            .field("max_stack", &self.max_stack)
+            .field("max_locals", &self.max_locals)
+            .field("code", &"...")
+            .field("exception_table", &self.exception_table)
+            .field("attributes", &self.attributes)
+            .finish()
+    }
+}
+
+impl<'a> BinRead for Code<'a> {
+    type Args<'b> = (&'a ClassFile,);
+
+    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        (cf,): Self::Args<'_>,
+    ) -> binrw::prelude::BinResult<Self> {
+        let class_file = cf;
+        let max_stack = u16::read_options(reader, endian, ())?;
+        let max_locals = u16::read_options(reader, endian, ())?;
+        let code_length = u32::read_options(reader, endian, ())?;
+        let code_offset = reader.stream_position()?;
+        let code = crate::raw::read_bytes(reader, code_length as u64)?;
+        let exception_table_length = u16::read_options(reader, endian, ())?;
+        let mut exception_table = Vec::new();
+        for _ in 0..exception_table_length {
+            let exception = Exception::read_options(reader, endian, (cf,))?;
+            exception_table.push(exception);
+        }
+        let attributes = Attributes::read_options(
+            reader,
+            endian,
+            (&cf.constant_pool, cf.leniency, None, cf.attribute_filter.as_deref()),
+        )?;
+        Ok(Self {
+            class_file,
+            max_stack,
+            max_locals,
+            code_offset,
+            code,
+            exception_table,
+            attributes,
+        })
+    }
+}
+
+#[binread]
+pub(crate) enum VerificationTypeInfo {
+    #[br(magic = 0u8)]
+    Top,
+    #[br(magic = 1u8)]
+    Integer,
+    #[br(magic = 2u8)]
+    Float,
+    #[br(magic = 4u8)]
+    Long,
+    #[br(magic = 3u8)]
+    Double,
+    #[br(magic = 5u8)]
+    Null,
+    #[br(magic = 6u8)]
+    UninitializedThis,
+    #[br(magic = 7u8)]
+    Object { cpool_index: crate::raw::ClassIndex },
+    #[br(magic = 8u8)]
+    Uninitialized { offset: u16 },
+}
+
+pub(crate) enum StackMapFrame {
+    SameFrame {
+        offset_delta: u16,
+    },
+    SameLocals1StackItemFrame {
+        offset_delta: u16,
+        stack: [VerificationTypeInfo; 1],
+    },
+    ChopFrame {
+        locals_to_remove: u8,
+        offset_delta: u16,
+    },
+    AppendFrame {
+        offset_delta: u16,
+        locals: Vec<VerificationTypeInfo>,
+    },
+    FullFrame {
+        offset_delta: u16,
+        locals: Vec<VerificationTypeInfo>,
+        stack: Vec<VerificationTypeInfo>,
+    },
+}
+
+impl BinRead for StackMapFrame {
+    type Args<'a> = ();
+
+    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        _args: Self::Args<'_>,
+    ) -> binrw::prelude::BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let magic = u8::read_options(reader, endian, ())?;
+        match magic {
+            0u8..=63u8 => Ok(Self::SameFrame {
+                offset_delta: magic as u16,
+            }),
+            64u8..=127u8 => {
+                let stack = <[VerificationTypeInfo; 1]>::read_options(reader, endian, ())?;
+                Ok(Self::SameLocals1StackItemFrame {
+                    offset_delta: magic as u16 - 64,
+                    stack,
+                })
+            }
+            128u8..=246u8 => {
+                return Err(binrw::Error::BadMagic {
+                    pos,
+                    found: Box::new(magic),
+                })
+            }
+            247u8 => {
+                let offset_delta = u16::read_options(reader, endian, ())?;
+                let stack = <[VerificationTypeInfo; 1]>::read_options(reader, endian, ())?;
+                Ok(Self::SameLocals1StackItemFrame {
+                    offset_delta,
+                    stack,
+                })
+            }
+            248u8..=250u8 => {
+                let offset_delta = u16::read_options(reader, endian, ())?;
+                Ok(Self::ChopFrame {
+                    locals_to_remove: 251u8 - magic,
+                    offset_delta,
+                })
+            }
+            251u8 => {
+                let offset_delta = u16::read_options(reader, endian, ())?;
+                Ok(Self::SameFrame { offset_delta })
+            }
+            252u8..=254u8 => {
+                let k = magic - 251;
+                let offset_delta = u16::read_options(reader, endian, ())?;
+                let locals = <Vec<VerificationTypeInfo>>::read_options(
+                    reader,
+                    endian,
+                    VecArgs::builder().count(k as usize).finalize(),
+                )?;
+                Ok(Self::AppendFrame {
+                    offset_delta,
+                    locals,
+                })
+            }
+            255u8 => {
+                let offset_delta = u16::read_options(reader, endian, ())?;
+                let num_locals = u16::read_options(reader, endian, ())?;
+                let locals = <Vec<VerificationTypeInfo>>::read_options(
+                    reader,
+                    endian,
+                    VecArgs::builder().count(num_locals as usize).finalize(),
+                )?;
+                let num_stack = u16::read_options(reader, endian, ())?;
+                let stack = <Vec<VerificationTypeInfo>>::read_options(
+                    reader,
+                    endian,
+                    VecArgs::builder().count(num_stack as usize).finalize(),
+                )?;
+                Ok(Self::FullFrame {
+                    offset_delta,
+                    locals,
+                    stack,
+                })
+            }
+        }
+    }
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct StackMapTable<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    #[br(temp)]
+    number_of_entries: u16,
+    #[br(count = number_of_entries)]
+    entries: Vec<StackMapFrame>,
+}
+
+impl StackMapTable<'_> {
+    pub(crate) fn entries(&self) -> &[StackMapFrame] {
+        &self.entries
+    }
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct Exceptions<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    #[br(temp)]
+    number_of_exceptions: u16,
+    #[br(count = number_of_exceptions)]
+    exception_index_table: Vec<ClassIndex>,
+}
+
+impl<'a> Exceptions<'a> {
+    pub fn class_names(&self) -> crate::Result<Vec<&'a str>> {
+        self.exception_index_table
+            .iter()
+            .map(|x| x.get_as_string(&self.class_file))
+            .collect()
+    }
+}
+
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InnerClassAccessFlags: u16 {
+        #[doc = "Marked or implicitly public in source."]
+        const PUBLIC = 0x0001;
+        #[doc = "Marked private in source."]
+        const PRIVATE = 0x0002;
+        #[doc = "Marked protected in source."]
+        const PROTECTED = 0x0004;
+        #[doc = "Marked or implicitly static in source."]
+        const STATIC = 0x0008;
+        #[doc = "Marked or implicitly final in source."]
+        const FINAL = 0x0010;
+        #[doc = "Was an interface in source."]
+        const INTERFACE = 0x0200;
+        #[doc = "Marked or implicitly abstract in source."]
+        const ABSTRACT = 0x0400;
+        #[doc = "Declared synthetic; not present in the source code."]
+        const SYNTHETIC = 0x1000;
+        #[doc = "Declared as an annotation interface."]
+        const ANNOTATION = 0x2000;
+        #[doc = "Declared as an enum class. "]
+        const ENUM = 0x4000;
+    }
+}
+
+#[binread]
+struct InnerClass {
+    inner_class_info: ClassIndex,
+    #[br(map = |x: ClassIndex| { if x.0 == 0 { None } else { Some(x) } } )]
+    outer_class_info: Option<ClassIndex>,
+    #[br(map = |x: Utf8Index| { if x.0 == 0 { None } else { Some(x) } } )]
+    inner_name: Option<Utf8Index>,
+    #[br(map = |x: u16| InnerClassAccessFlags::from_bits_retain(x))]
+    inner_class_access_flags: InnerClassAccessFlags,
+}
+
+/// An entry of the `InnerClasses` attribute, with names resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InnerClassInfo<'a> {
+    pub inner_class: &'a str,
+    /// The class declaring `inner_class` as a member, or `None` for local and
+    /// anonymous classes.
+    pub outer_class: Option<&'a str>,
+    /// The simple name in source, or `None` for anonymous classes.
+    pub inner_name: Option<&'a str>,
+    pub access_flags: InnerClassAccessFlags,
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct InnerClasses<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    #[br(temp)]
+    number_of_classes: u16,
+    #[br(count = number_of_classes)]
+    classes: Vec<InnerClass>,
+}
+
+impl<'a> InnerClasses<'a> {
+    pub fn classes(&self) -> crate::Result<Vec<InnerClassInfo<'a>>> {
+        self.classes
+            .iter()
+            .map(|x| {
+                Ok(InnerClassInfo {
+                    inner_class: x.inner_class_info.get_as_string(self.class_file)?,
+                    outer_class: x
+                        .outer_class_info
+                        .map(|x| x.get_as_string(self.class_file))
+                        .transpose()?,
+                    inner_name: x
+                        .inner_name
+                        .map(|x| x.get_as_string(self.class_file))
+                        .transpose()?,
+                    access_flags: x.inner_class_access_flags,
+                })
+            })
+            .collect()
+    }
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct EnclosingMethod<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    class_index: ClassIndex,
+    method_index: NameAndTypeIndex,
+}
+
+impl<'a> EnclosingMethod<'a> {
+    /// The innermost class enclosing the local or anonymous class.
+    pub fn class_name(&self) -> crate::Result<&'a str> {
+        self.class_index.get_as_string(self.class_file)
+    }
+
+    /// The name of the method the class is declared in, or `None` if it is
+    /// declared in an initializer.
+    pub fn method_name(&self) -> crate::Result<Option<&'a str>> {
+        match self.method_index.0 {
+            0 => Ok(None),
+            _ => self.method_index.get_name(self.class_file).map(Some),
+        }
+    }
+
+    pub fn method_descriptor(&self) -> crate::Result<Option<crate::method::MethodDescriptor<'a>>> {
+        match self.method_index.0 {
+            0 => Ok(None),
+            _ => {
+                let descriptor = self.method_index.get_descriptor_index(self.class_file)?;
+                self.class_file.method_descriptor_at(descriptor).map(Some)
+            }
+        }
+    }
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct Signature<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    signature_index: Utf8Index,
+}
+
+impl<'a> Signature<'a> {
+    pub(crate) fn get_class(&self) -> crate::Result<crate::signature::ClassSignature<'a>> {
+        self.class_file.class_signature_at(self.signature_index)
+    }
+
+    pub(crate) fn get_method(&self) -> crate::Result<crate::signature::MethodSignature<'a>> {
+        self.class_file.method_signature_at(self.signature_index)
+    }
+
+    pub(crate) fn get_field(&self) -> crate::Result<crate::signature::ReferenceType<'a>> {
+        self.class_file.field_signature_at(self.signature_index)
+    }
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct SourceFile<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    sourcefile_index: Utf8Index,
+}
+
+impl<'a> SourceFile<'a> {
+    pub fn get(&self) -> crate::Result<&'a str> {
+        self.sourcefile_index.get_as_string(self.class_file)
+    }
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct LineNumberTable<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    #[br(temp)]
+    line_number_table_length: u16,
+    #[br(count = line_number_table_length)]
+    line_number_table: Vec<(u16, u16)>,
+}
+
+impl<'a> LineNumberTable<'a> {
+    // TODO interact with code instructions.
+}
+
+pub struct LocalVariable<'a> {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name: &'a str,
+    pub descriptor: crate::field::TypeDescriptor<'a>,
+    pub index: u16,
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct LocalVariableTable<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    #[br(temp)]
+    local_variable_table_length: u16,
+    #[br(count = local_variable_table_length)]
+    local_variable_table: Vec<(u16, u16, Utf8Index, Utf8Index, u16)>,
+}
+
+impl<'a> LocalVariableTable<'a> {
+    pub fn get_variables(&self) -> crate::Result<Vec<LocalVariable<'a>>> {
+        self.local_variable_table
+            .iter()
+            .map(|(start_pc, length, name, descriptor, index)| {
+                Ok(LocalVariable {
+                    start_pc: *start_pc,
+                    length: *length,
+                    name: name.get_as_string(self.class_file)?,
+                    descriptor: self.class_file.field_descriptor_at(*descriptor)?,
+                    index: *index,
+                })
+            })
+            .collect()
+    }
+}
+
+pub struct LocalVariableType<'a> {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name: &'a str,
+    pub signature: crate::signature::ReferenceType<'a>,
+    pub index: u16,
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct LocalVariableTypeTable<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    #[br(temp)]
+    local_variable_type_table_length: u16,
+    #[br(count = local_variable_type_table_length)]
+    local_variable_type_table: Vec<(u16, u16, Utf8Index, Utf8Index, u16)>,
+}
+
+impl<'a> LocalVariableTypeTable<'a> {
+    pub fn get_variable_types(&self) -> crate::Result<Vec<LocalVariableType<'a>>> {
+        self.local_variable_type_table
+            .iter()
+            .map(|(start_pc, length, name, signature, index)| {
+                Ok(LocalVariableType {
+                    start_pc: *start_pc,
+                    length: *length,
+                    name: name.get_as_string(self.class_file)?,
+                    signature: self.class_file.field_signature_at(*signature)?,
+                    index: *index,
+                })
+            })
+            .collect()
+    }
+}
+
+/// How deeply annotations and arrays may nest in an element value before the
+/// attribute is rejected, which keeps hostile input from exhausting the stack.
+const MAX_ELEMENT_VALUE_DEPTH: u32 = 256;
+
+/// An annotation, as in §4.7.16.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation<'a> {
+    /// The field descriptor of the annotation interface, e.g.
+    /// `Ljava/lang/Deprecated;`.
+    pub type_name: &'a str,
+    /// The explicitly given elements by name, in file order. Elements left to
+    /// their default are absent.
+    pub elements: Vec<(&'a str, ElementValue<'a>)>,
+}
+
+/// The value of an annotation element, as in §4.7.16.1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElementValue<'a> {
+    Byte(i8),
+    /// A UTF-16 code unit, which may be half a surrogate pair.
+    Char(u16),
+    Double(f64),
+    Float(f32),
+    Int(i32),
+    Long(i64),
+    Short(i16),
+    Boolean(bool),
+    String(&'a str),
+    Enum {
+        /// The field descriptor of the enum class.
+        type_name: &'a str,
+        const_name: &'a str,
+    },
+    /// A class literal by return descriptor, e.g. `Ljava/lang/String;`, `I`
+    /// or `V` for `void.class`.
+    Class(&'a str),
+    Annotation(Annotation<'a>),
+    Array(Vec<ElementValue<'a>>),
+}
+
+/// The simple name of the class with internal name `name`, with `.` between
+/// the names of member classes, e.g. `Map.Entry`.
+pub(crate) fn simple_class_name(name: &str) -> String {
+    name.rsplit('/').next().unwrap_or(name).replace('$', ".")
+}
+
+/// The type a descriptor such as `Ljava/lang/Deprecated;` stands for as Java
+/// source writes it, or the descriptor itself if it is malformed.
+fn descriptor_to_java(descriptor: &str, class_name: &mut dyn FnMut(&str) -> String) -> String {
+    match TypeDescriptor::parse(descriptor) {
+        Ok(("", x)) => x.to_java_type_with(class_name),
+        _ => descriptor.to_string(),
+    }
+}
+
+impl Annotation<'_> {
+    /// The annotation as Java source writes it, e.g.
+    /// `@Retention(RetentionPolicy.RUNTIME)`, with each class written as
+    /// `class_name` gives it from its internal name. A lone `value` element
+    /// goes without its name.
+    pub fn to_java_with(&self, class_name: &mut dyn FnMut(&str) -> String) -> String {
+        let mut res = format!("@{}", descriptor_to_java(self.type_name, class_name));
+        match self.elements.as_slice() {
+            [] => {}
+            [("value", x)] => res.push_str(&format!("({})", x.to_java_with(class_name))),
+            elements => {
+                let elements = elements
+                    .iter()
+                    .map(|(name, x)| format!("{} = {}", name, x.to_java_with(class_name)))
+                    .collect::<Vec<_>>();
+                res.push_str(&format!("({})", elements.join(", ")));
+            }
+        }
+        res
+    }
+}
+
+/// Writes the annotation as Java source does, with simple class names, e.g.
+/// `@Target({ElementType.FIELD, ElementType.METHOD})`.
+impl std::fmt::Display for Annotation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_java_with(&mut simple_class_name))
+    }
+}
+
+impl ElementValue<'_> {
+    /// The value as a Java constant expression, e.g. `'a'`, `String[].class`
+    /// or `{1, 2}`, with each class written as `class_name` gives it from its
+    /// internal name. Infinities and NaN, which have no literal, are written
+    /// as divisions.
+    pub fn to_java_with(&self, class_name: &mut dyn FnMut(&str) -> String) -> String {
+        let floating = |value: f64, text: String, suffix: &str| {
+            if value.is_nan() {
+                format!("0.0{0} / 0.0{0}", suffix)
+            } else if value.is_infinite() {
+                format!("{}1.0{1} / 0.0{1}", if value < 0.0 { "-" } else { "" }, suffix)
+            } else {
+                format!("{}{}", text, suffix)
+            }
+        };
+        match self {
+            Self::Byte(x) => x.to_string(),
+            Self::Char(x) => match x {
+                0x08 => "'\\b'".to_string(),
+                0x09 => "'\\t'".to_string(),
+                0x0a => "'\\n'".to_string(),
+                0x0c => "'\\f'".to_string(),
+                0x0d => "'\\r'".to_string(),
+                0x27 => "'\\''".to_string(),
+                0x5c => "'\\\\'".to_string(),
+                0x20..=0x7e => format!("'{}'", *x as u8 as char),
+                _ => format!("'\\u{:04x}'", x),
+            },
+            Self::Double(x) => floating(*x, format!("{:?}", x), ""),
+            Self::Float(x) => floating(*x as f64, format!("{:?}", x), "f"),
+            Self::Int(x) => x.to_string(),
+            Self::Long(x) => format!("{}L", x),
+            Self::Short(x) => x.to_string(),
+            Self::Boolean(x) => x.to_string(),
+            Self::String(x) => crate::text::quote(x),
+            Self::Enum { type_name, const_name } => {
+                format!("{}.{}", descriptor_to_java(type_name, class_name), const_name)
+            }
+            Self::Class("V") => "void.class".to_string(),
+            Self::Class(x) => format!("{}.class", descriptor_to_java(x, class_name)),
+            Self::Annotation(x) => x.to_java_with(class_name),
+            Self::Array(x) => {
+                let values = x.iter().map(|x| x.to_java_with(class_name)).collect::<Vec<_>>();
+                format!("{{{}}}", values.join(", "))
+            }
+        }
+    }
+}
+
+/// Writes the value as a Java constant expression, with simple class names.
+impl std::fmt::Display for ElementValue<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_java_with(&mut simple_class_name))
+    }
+}
+
+fn constant_error(pos: u64, index: u16, expected: &str, cf: &ClassFile) -> binrw::Error {
+    let found = (index as usize)
+        .checked_sub(1)
+        .and_then(|x| cf.constant_pool.0.get(x))
+        .map_or("nothing", |x| x.kind());
+    binrw::Error::Custom {
+        pos,
+        err: Box::new(Error::ConstantPoolError(format!(
+            "expected {} at constant pool index {}. Instead found {}.",
+            expected, index, found
+        ))),
+    }
+}
+
+fn read_utf8<'a, R: std::io::Read + std::io::Seek>(
+    reader: &mut R,
+    endian: binrw::Endian,
+    cf: &'a ClassFile,
+) -> binrw::BinResult<&'a str> {
+    let pos = reader.stream_position()?;
+    let index = Utf8Index::read_options(reader, endian, ())?;
+    index.get_as_string(cf).map_err(|x| binrw::Error::Custom { pos, err: Box::new(x) })
+}
+
+fn read_annotation<'a, R: std::io::Read + std::io::Seek>(
+    reader: &mut R,
+    endian: binrw::Endian,
+    cf: &'a ClassFile,
+    depth: u32,
+) -> binrw::BinResult<Annotation<'a>> {
+    let type_name = read_utf8(reader, endian, cf)?;
+    let num_element_value_pairs = u16::read_options(reader, endian, ())?;
+    let elements = (0..num_element_value_pairs)
+        .map(|_| {
+            let name = read_utf8(reader, endian, cf)?;
+            Ok((name, read_element_value(reader, endian, cf, depth)?))
+        })
+        .collect::<binrw::BinResult<_>>()?;
+    Ok(Annotation { type_name, elements })
+}
+
+fn read_element_value<'a, R: std::io::Read + std::io::Seek>(
+    reader: &mut R,
+    endian: binrw::Endian,
+    cf: &'a ClassFile,
+    depth: u32,
+) -> binrw::BinResult<ElementValue<'a>> {
+    let pos = reader.stream_position()?;
+    if depth >= MAX_ELEMENT_VALUE_DEPTH {
+        return Err(binrw::Error::AssertFail {
+            pos,
+            message: format!("element values nested more than {} deep", MAX_ELEMENT_VALUE_DEPTH),
+        });
+    }
+    let tag = u8::read_options(reader, endian, ())?;
+    let constant = |reader: &mut R| -> binrw::BinResult<(u16, Option<&'a ConstantPoolItem>)> {
+        let index = u16::read_options(reader, endian, ())?;
+        let item = (index as usize).checked_sub(1).and_then(|x| cf.constant_pool.0.get(x));
+        Ok((index, item))
+    };
+    Ok(match tag {
+        b'B' | b'C' | b'I' | b'S' | b'Z' => match constant(reader)? {
+            (_, Some(ConstantPoolItem::Integer { value })) => match tag {
+                b'B' => ElementValue::Byte(*value as i8),
+                b'C' => ElementValue::Char(*value as u16),
+                b'S' => ElementValue::Short(*value as i16),
+                b'Z' => ElementValue::Boolean(*value != 0),
+                _ => ElementValue::Int(*value),
+            },
+            (index, _) => return Err(constant_error(pos + 1, index, "Integer", cf)),
+        },
+        b'D' => match constant(reader)? {
+            (_, Some(ConstantPoolItem::Double { value })) => ElementValue::Double(*value),
+            (index, _) => return Err(constant_error(pos + 1, index, "Double", cf)),
+        },
+        b'F' => match constant(reader)? {
+            (_, Some(ConstantPoolItem::Float { value })) => ElementValue::Float(*value),
+            (index, _) => return Err(constant_error(pos + 1, index, "Float", cf)),
+        },
+        b'J' => match constant(reader)? {
+            (_, Some(ConstantPoolItem::Long { value })) => ElementValue::Long(*value),
+            (index, _) => return Err(constant_error(pos + 1, index, "Long", cf)),
+        },
+        b's' => ElementValue::String(read_utf8(reader, endian, cf)?),
+        b'e' => ElementValue::Enum {
+            type_name: read_utf8(reader, endian, cf)?,
+            const_name: read_utf8(reader, endian, cf)?,
+        },
+        b'c' => ElementValue::Class(read_utf8(reader, endian, cf)?),
+        b'@' => ElementValue::Annotation(read_annotation(reader, endian, cf, depth + 1)?),
+        b'[' => {
+            let num_values = u16::read_options(reader, endian, ())?;
+            ElementValue::Array(
+                (0..num_values)
+                    .map(|_| read_element_value(reader, endian, cf, depth + 1))
+                    .collect::<binrw::BinResult<_>>()?,
+            )
+        }
+        _ => {
+            return Err(binrw::Error::AssertFail {
+                pos,
+                message: format!("unknown element value tag {:?}", tag as char),
+            })
+        }
+    })
+}
+
+macro_rules! annotations_attribute {
+    ($name:ident) => {
+        pub struct $name<'a> {
+            annotations: Vec<Annotation<'a>>,
+        }
+
+        impl<'a> BinRead for $name<'a> {
+            type Args<'b> = (&'a ClassFile,);
+
+            fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
+                reader: &mut R,
+                endian: binrw::Endian,
+                (cf,): Self::Args<'_>,
+            ) -> binrw::prelude::BinResult<Self> {
+                let num_annotations = u16::read_options(reader, endian, ())?;
+                let annotations = (0..num_annotations)
+                    .map(|_| read_annotation(reader, endian, cf, 0))
+                    .collect::<binrw::BinResult<_>>()?;
+                Ok(Self { annotations })
+            }
+        }
+
+        impl<'a> $name<'a> {
+            pub fn annotations(self) -> Vec<Annotation<'a>> {
+                self.annotations
+            }
+        }
+    };
+}
+
+annotations_attribute!(RuntimeVisibleAnnotations);
+annotations_attribute!(RuntimeInvisibleAnnotations);
+
+/// The default value of the element of an annotation interface that the
+/// method declares (§4.7.22).
+pub struct AnnotationDefault<'a> {
+    value: ElementValue<'a>,
+}
+
+impl<'a> BinRead for AnnotationDefault<'a> {
+    type Args<'b> = (&'a ClassFile,);
+
+    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        (cf,): Self::Args<'_>,
+    ) -> binrw::prelude::BinResult<Self> {
+        Ok(Self {
+            value: read_element_value(reader, endian, cf, 0)?,
+        })
+    }
+}
+
+impl<'a> AnnotationDefault<'a> {
+    pub fn value(self) -> ElementValue<'a> {
+        self.value
+    }
+}
+
+#[binread]
+struct BootstrapMethodRaw {
+    bootstrap_method_ref: MethodHandleIndex,
+    #[br(temp)]
+    num_bootstrap_args: u16,
+    #[br(count = num_bootstrap_args)]
+    bootstrap_args: Vec<u16>,
+}
+
+pub struct BootstrapMethod<'a> {
+    class_file: &'a ClassFile,
+    index: u16,
+    pub method: MethodHandle<'a>,
+    argument_indices: Vec<u16>,
+}
+
+impl<'a> BootstrapMethod<'a> {
+    /// The position of the method in the `BootstrapMethods` attribute.
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// The static arguments passed to the method. They are resolved on demand,
+    /// as a `Dynamic` argument may itself name a bootstrap method.
+    pub fn arguments(&self) -> super::Result<Vec<LoadableConstant<'a>>> {
+        self.argument_indices
+            .iter()
+            .map(|&x| LoadableConstant::from_u16(x, self.class_file))
+            .collect()
+    }
+}
+
+impl<'a> Debug for BootstrapMethod<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BootstrapMethod")
+            .field("index", &self.index)
+            .field("method", &self.method)
+            .field("arguments", &self.argument_indices)
+            .finish()
+    }
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct BootstrapMethods<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    #[br(temp)]
+    bootstrap_methods_length: u16,
+    #[br(count = bootstrap_methods_length)]
+    bootstrap_methods: Vec<BootstrapMethodRaw>,
+}
+
+impl<'a> BootstrapMethods<'a> {
+    pub fn get(&self, idx: u16) -> super::Result<Option<BootstrapMethod<'a>>> {
+        if let Some(method) = self.bootstrap_methods.get(idx as usize) {
+            let argument_indices = method.bootstrap_args.clone();
+            let method = MethodHandle::from_u16(method.bootstrap_method_ref.0, self.class_file)?;
+            Ok(Some(BootstrapMethod {
+                class_file: self.class_file,
+                index: idx,
+                method,
+                argument_indices,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// TODO MethodParameters
+
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ModuleFlags: u16 {
+        const OPEN      = 0x0020;
+        const SYNTHETIC = 0x1000;
+        const MANDATED  = 0x8000;
+    }
+}
+
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct RequiresFlags: u16 {
+        const TRANSITIVE   = 0x0020;
+        const STATIC_PHASE = 0x0040;
+        const SYNTHETIC    = 0x1000;
+        const MANDATED     = 0x8000;
+    }
+}
+
+bitflags::bitflags! {
+    /// Flags of an `exports` or `opens` directive.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PackageDirectiveFlags: u16 {
+        const SYNTHETIC = 0x1000;
+        const MANDATED  = 0x8000;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Requires<'a> {
+    pub module: &'a str,
+    pub flags: RequiresFlags,
+    /// The version of the module compiled against, if recorded.
+    pub version: Option<&'a str>,
+}
+
+/// An `exports` or `opens` directive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageDirective<'a> {
+    /// The package by internal name, e.g. `com/example/api`.
+    pub package: &'a str,
+    pub flags: PackageDirectiveFlags,
+    /// The modules the package is exported or opened to, or empty for all.
+    pub to: Vec<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provides<'a> {
+    /// The service interface by internal name.
+    pub service: &'a str,
+    pub with: Vec<&'a str>,
+}
+
+/// A module declaration, as returned by [`ClassFile::module`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleDescriptor<'a> {
+    pub name: &'a str,
+    pub flags: ModuleFlags,
+    pub version: Option<&'a str>,
+    pub requires: Vec<Requires<'a>>,
+    pub exports: Vec<PackageDirective<'a>>,
+    pub opens: Vec<PackageDirective<'a>>,
+    /// The service interfaces used, by internal name.
+    pub uses: Vec<&'a str>,
+    pub provides: Vec<Provides<'a>>,
+    /// Every package of the module, from `ModulePackages`, or empty if the
+    /// attribute is missing.
+    pub packages: Vec<&'a str>,
+    /// The main class by internal name, from `ModuleMainClass`.
+    pub main_class: Option<&'a str>,
+}
+
+/// A `Utf8Index` that may be 0 for "none".
+fn optional_utf8(class_file: &ClassFile, index: u16) -> crate::Result<Option<&str>> {
+    match index {
+        0 => Ok(None),
+        x => Utf8Index(x).get_as_string(class_file).map(Some),
+    }
+}
+
+#[binread]
+struct RequiresRaw {
+    requires_index: ModuleIndex,
+    requires_flags: u16,
+    requires_version_index: u16,
+}
+
+#[binread]
+struct PackageDirectiveRaw {
+    index: PackageIndex,
+    flags: u16,
+    #[br(temp)]
+    to_count: u16,
+    #[br(count = to_count)]
+    to_index: Vec<ModuleIndex>,
+}
+
+#[binread]
+struct ProvidesRaw {
+    provides_index: ClassIndex,
+    #[br(temp)]
+    provides_with_count: u16,
+    #[br(count = provides_with_count)]
+    provides_with_index: Vec<ClassIndex>,
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct Module<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    module_name_index: ModuleIndex,
+    module_flags: u16,
+    module_version_index: u16,
+    #[br(temp)]
+    requires_count: u16,
+    #[br(count = requires_count)]
+    requires: Vec<RequiresRaw>,
+    #[br(temp)]
+    exports_count: u16,
+    #[br(count = exports_count)]
+    exports: Vec<PackageDirectiveRaw>,
+    #[br(temp)]
+    opens_count: u16,
+    #[br(count = opens_count)]
+    opens: Vec<PackageDirectiveRaw>,
+    #[br(temp)]
+    uses_count: u16,
+    #[br(count = uses_count)]
+    uses_index: Vec<ClassIndex>,
+    #[br(temp)]
+    provides_count: u16,
+    #[br(count = provides_count)]
+    provides: Vec<ProvidesRaw>,
+}
+
+impl<'a> Module<'a> {
+    fn package_directives(&self, directives: &[PackageDirectiveRaw]) -> crate::Result<Vec<PackageDirective<'a>>> {
+        directives
+            .iter()
+            .map(|x| {
+                Ok(PackageDirective {
+                    package: x.index.get_as_string(self.class_file)?,
+                    flags: PackageDirectiveFlags::from_bits_retain(x.flags),
+                    to: x
+                        .to_index
+                        .iter()
+                        .map(|x| x.get_as_string(self.class_file))
+                        .collect::<crate::Result<_>>()?,
+                })
+            })
+            .collect()
+    }
+
+    /// The declaration, without the packages and main class kept in other
+    /// attributes.
+    pub fn descriptor(&self) -> crate::Result<ModuleDescriptor<'a>> {
+        let cf = self.class_file;
+        Ok(ModuleDescriptor {
+            name: self.module_name_index.get_as_string(cf)?,
+            flags: ModuleFlags::from_bits_retain(self.module_flags),
+            version: optional_utf8(cf, self.module_version_index)?,
+            requires: self
+                .requires
+                .iter()
+                .map(|x| {
+                    Ok(Requires {
+                        module: x.requires_index.get_as_string(cf)?,
+                        flags: RequiresFlags::from_bits_retain(x.requires_flags),
+                        version: optional_utf8(cf, x.requires_version_index)?,
+                    })
+                })
+                .collect::<crate::Result<_>>()?,
+            exports: self.package_directives(&self.exports)?,
+            opens: self.package_directives(&self.opens)?,
+            uses: self
+                .uses_index
+                .iter()
+                .map(|x| x.get_as_string(cf))
+                .collect::<crate::Result<_>>()?,
+            provides: self
+                .provides
+                .iter()
+                .map(|x| {
+                    Ok(Provides {
+                        service: x.provides_index.get_as_string(cf)?,
+                        with: x
+                            .provides_with_index
+                            .iter()
+                            .map(|x| x.get_as_string(cf))
+                            .collect::<crate::Result<_>>()?,
+                    })
+                })
+                .collect::<crate::Result<_>>()?,
+            packages: Vec::new(),
+            main_class: None,
+        })
+    }
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct ModulePackages<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    #[br(temp)]
+    package_count: u16,
+    #[br(count = package_count)]
+    package_index: Vec<PackageIndex>,
+}
+
+impl<'a> ModulePackages<'a> {
+    pub fn packages(&self) -> crate::Result<Vec<&'a str>> {
+        self.package_index
+            .iter()
+            .map(|x| x.get_as_string(self.class_file))
+            .collect()
+    }
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct ModuleMainClass<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    main_class_index: ClassIndex,
+}
+
+impl<'a> ModuleMainClass<'a> {
+    pub fn get(&self) -> crate::Result<&'a str> {
+        self.main_class_index.get_as_string(self.class_file)
+    }
+}
+
+// TODO NestHost
+
+// TODO NestMembers
+
+/// A component of a record class, as in §4.7.30.
+pub struct RecordComponent<'a> {
+    class_file: &'a ClassFile,
+    name_index: Utf8Index,
+    descriptor_index: Utf8Index,
+    attributes: Attributes,
+}
+
+impl<'a> BinRead for RecordComponent<'a> {
+    type Args<'b> = (&'a ClassFile,);
+
+    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        (cf,): Self::Args<'_>,
+    ) -> binrw::prelude::BinResult<Self> {
+        let name_index = Utf8Index::read_options(reader, endian, ())?;
+        let descriptor_index = Utf8Index::read_options(reader, endian, ())?;
+        let attributes = Attributes::read_options(
+            reader,
+            endian,
+            (&cf.constant_pool, cf.leniency, None, cf.attribute_filter.as_deref()),
+        )?;
+        Ok(Self {
+            class_file: cf,
+            name_index,
+            descriptor_index,
+            attributes,
+        })
+    }
+}
+
+impl<'a> RecordComponent<'a> {
+    pub fn identifier(&self) -> crate::Result<&'a str> {
+        self.name_index.get_as_string(self.class_file)
+    }
+
+    pub fn descriptor(&self) -> crate::Result<TypeDescriptor<'a>> {
+        self.class_file.field_descriptor_at(self.descriptor_index)
+    }
+
+    /// Decodes the last attribute of the component called `T::NAME`.
+    pub fn get_attribute<T: KnownAttribute<'a>>(&self) -> crate::Result<Option<T>> {
+        self.attributes.decode(self.class_file)
+    }
+
+    pub fn signature(&self) -> crate::Result<Option<crate::signature::ReferenceType<'a>>> {
+        match self.get_attribute::<Signature>()? {
+            Some(value) => Ok(Some(value.get_field()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The generic type of the component, from its own `Signature` attribute
+    /// or else the one of its accessor, or `None` if neither has one and the
+    /// descriptor gives the type.
+    pub fn generic_type(&self) -> crate::Result<Option<crate::signature::JavaType<'a>>> {
+        if let Some(x) = self.signature()? {
+            return Ok(Some(crate::signature::JavaType::Reference(x)));
+        }
+        match self.accessor()? {
+            Some(accessor) => Ok(accessor.signature()?.and_then(|x| x.result)),
+            None => Ok(None),
+        }
+    }
+
+    /// The method returning the value of the component, which has its name
+    /// and takes no arguments.
+    pub fn accessor(&self) -> crate::Result<Option<crate::method::Method<'a>>> {
+        let name = self.identifier()?;
+        let descriptor = format!("(){}", self.descriptor_index.get_as_string(self.class_file)?);
+        for method in self.class_file.methods() {
+            if method.identifier()? == name
+                && method.method_inner.descriptor_index.get_as_string(self.class_file)? == descriptor
+            {
+                return Ok(Some(method));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The annotations in the `RuntimeVisibleAnnotations` attribute, if any.
+    pub fn annotations(&self) -> crate::Result<Vec<Annotation<'a>>> {
+        match self.get_attribute::<RuntimeVisibleAnnotations>()? {
+            Some(value) => Ok(value.annotations()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The annotations in the `RuntimeInvisibleAnnotations` attribute, which
+    /// compilers keep for `CLASS` retention, if any.
+    pub fn invisible_annotations(&self) -> crate::Result<Vec<Annotation<'a>>> {
+        match self.get_attribute::<RuntimeInvisibleAnnotations>()? {
+            Some(value) => Ok(value.annotations()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn attribute_span(&self, name: &str) -> Option<Span> {
+        self.attributes.span(name)
+    }
+}
+
+/// The components of a record class, as in §4.7.30.
+pub struct Record<'a> {
+    components: Vec<RecordComponent<'a>>,
+}
+
+impl<'a> BinRead for Record<'a> {
+    type Args<'b> = (&'a ClassFile,);
+
+    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        (cf,): Self::Args<'_>,
+    ) -> binrw::prelude::BinResult<Self> {
+        let components_count = u16::read_options(reader, endian, ())?;
+        let components = (0..components_count)
+            .map(|_| RecordComponent::read_options(reader, endian, (cf,)))
+            .collect::<binrw::BinResult<_>>()?;
+        Ok(Self { components })
+    }
+}
+
+impl<'a> Record<'a> {
+    pub fn components(self) -> Vec<RecordComponent<'a>> {
+        self.components
+    }
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+pub struct PermittedSubclasses<'a> {
+    #[br(calc = cf)]
+    class_file: &'a ClassFile,
+    #[br(temp)]
+    number_of_classes: u16,
+    #[br(count = number_of_classes)]
+    classes: Vec<ClassIndex>,
+}
+
+impl<'a> PermittedSubclasses<'a> {
+    pub fn class_names(&self) -> crate::Result<Vec<&'a str>> {
+        self.classes
+            .iter()
+            .map(|x| x.get_as_string(self.class_file))
+            .collect()
+    }
+}
+
+macro_rules! typed_attributes {
+    ($($strct:ident),* $(,)?) => {
+        /// A decoded attribute, see [`crate::ClassFile::attributes`]. Attributes
+        /// without a decoder, or misplaced ones such as `Code` on a class, are
+        /// [`Attribute::Unknown`].
+        pub enum Attribute<'a> {
+            $($strct($strct<'a>),)*
+            Deprecated,
+            Synthetic,
+            Unknown(RawAttribute<'a>),
+        }
+
+        $(impl<'a> KnownAttribute<'a> for $strct<'a> {
+            const NAME: &'static str = stringify!($strct);
+        })*
+
+        impl<'a> Attribute<'a> {
+            pub fn name(&self) -> &'a str {
+                match self {
+                    $(Self::$strct(_) => stringify!($strct),)*
+                    Self::Deprecated => "Deprecated",
+                    Self::Synthetic => "Synthetic",
+                    Self::Unknown(x) => x.name,
+                }
+            }
+        }
+
+        impl Attributes {
+            /// Decodes every attribute allowed in `location`, in the order they
+            /// appear in the file.
+            pub(crate) fn typed<'a>(
+                &'a self,
+                class_file: &'a ClassFile,
+                location: AttributeLocation,
+            ) -> crate::Result<Vec<Attribute<'a>>> {
+                self.iter()
+                    .map(|x| {
+                        let offset = x.span.start + 6;
+                        Ok(match x.name.as_str() {
+                            $(stringify!($strct) if location.allows(stringify!($strct)) => Attribute::$strct(
+                                decode::<$strct>(&x.name, &x.data, offset, class_file)?,
+                            ),)*
+                            "Deprecated" if location.allows("Deprecated") => Attribute::Deprecated,
+                            "Synthetic" if location.allows("Synthetic") => Attribute::Synthetic,
+                            _ => Attribute::Unknown(x.raw(class_file)),
+                        })
+                    })
+                    .collect()
+            }
+        }
+    };
+}
+
+typed_attributes!(
+    ConstantValue,
+    Code,
+    StackMapTable,
+    Exceptions,
+    InnerClasses,
+    EnclosingMethod,
+    Signature,
+    SourceFile,
+    LineNumberTable,
+    LocalVariableTable,
+    LocalVariableTypeTable,
+    BootstrapMethods,
+    RuntimeVisibleAnnotations,
+    RuntimeInvisibleAnnotations,
+    AnnotationDefault,
+    Module,
+    ModulePackages,
+    ModuleMainClass,
+    Record,
+    PermittedSubclasses,
+);
+
+/// Where a set of attributes was found, which decides the ones that are decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AttributeLocation {
+    Class,
+    Field,
+    Method,
+    Code,
+}
+
+impl AttributeLocation {
+    fn allows(self, name: &str) -> bool {
+        match self {
+            Self::Class => matches!(
+                name,
+                "InnerClasses"
+                    | "EnclosingMethod"
+                    | "Signature"
+                    | "SourceFile"
+                    | "Deprecated"
+                    | "Synthetic"
+                    | "BootstrapMethods"
+                    | "RuntimeVisibleAnnotations"
+                    | "RuntimeInvisibleAnnotations"
+                    | "Module"
+                    | "ModulePackages"
+                    | "ModuleMainClass"
+                    | "Record"
+                    | "PermittedSubclasses"
+            ),
+            Self::Field => matches!(
+                name,
+                "ConstantValue"
+                    | "Signature"
+                    | "Deprecated"
+                    | "Synthetic"
+                    | "RuntimeVisibleAnnotations"
+                    | "RuntimeInvisibleAnnotations"
+            ),
+            Self::Method => matches!(
+                name,
+                "Code"
+                    | "Exceptions"
+                    | "Signature"
+                    | "Deprecated"
+                    | "Synthetic"
+                    | "RuntimeVisibleAnnotations"
+                    | "RuntimeInvisibleAnnotations"
+                    | "AnnotationDefault"
+            ),
+            Self::Code => matches!(
+                name,
+                "StackMapTable" | "LineNumberTable" | "LocalVariableTable" | "LocalVariableTypeTable"
+            ),
+        }
+    }
+}
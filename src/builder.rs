@@ -0,0 +1,753 @@
+//! Assembles class files from scratch, for stubs, proxies and other small
+//! synthesized classes.
+
+use std::{collections::HashMap, io::Write};
+
+use crate::{
+    mutf8,
+    raw::{AttributeEntry, Attributes, BootstrapMethodAttrInfo, FieldRaw, MethodRaw, Reference},
+    asm::{assemble, Constant, Label, Op},
+    field::TypeDescriptor,
+    method::MethodDescriptor,
+    writer::{count, write_u16},
+    ClassAccessFlags, ClassIndex, ConstantPoolItem, DoubleIndex, DynamicIndex, Error, FieldAccessFlags,
+    FieldrefIndex, FloatIndex, IntegerIndex, InterfaceMethodrefIndex, InvokeDynamicIndex, LongIndex,
+    MethodAccessFlags, MethodHandleIndex, MethodTypeIndex, MethodrefIndex, ModuleIndex,
+    NameAndTypeIndex, PackageIndex, Result, Span, StringIndex, Utf8Index,
+};
+
+/// Builds a constant pool, returning the index of each entry and reusing
+/// entries already present.
+///
+/// Entries are identified by their encoding, so `0.0` and `-0.0`, or NaNs
+/// with different bits, stay distinct. Long and Double entries take two
+/// indices, as in a class file.
+#[derive(Debug, Clone, Default)]
+pub struct ConstantPoolBuilder {
+    items: Vec<ConstantPoolItem>,
+    entries: HashMap<Vec<u8>, u16>,
+}
+
+impl ConstantPoolBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder starting from the entries of an existing pool, which keep
+    /// their indices. Of duplicate entries, the first is reused.
+    pub(crate) fn from_items(items: &[ConstantPoolItem]) -> Result<Self> {
+        let mut entries = HashMap::new();
+        for (i, item) in items.iter().enumerate() {
+            if matches!(item, ConstantPoolItem::Skip) {
+                continue;
+            }
+            let mut key = Vec::new();
+            item.write_to(&mut key)?;
+            entries.entry(key).or_insert(i as u16 + 1);
+        }
+        Ok(Self {
+            items: items.to_vec(),
+            entries,
+        })
+    }
+
+    /// The entries so far, with a `Skip` after each Long and Double.
+    pub fn items(&self) -> &[ConstantPoolItem] {
+        &self.items
+    }
+
+    /// The index the next new entry will get, which is also the
+    /// `constant_pool_count` of the class file.
+    pub fn next_index(&self) -> u16 {
+        self.items.len() as u16 + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Adds `item` unless an identical entry exists, returning its index.
+    /// References in `item` are not checked.
+    pub fn add(&mut self, item: ConstantPoolItem) -> Result<u16> {
+        if matches!(item, ConstantPoolItem::Skip) {
+            return Err(Error::WriteError("Skip is not a constant pool entry".to_string()));
+        }
+        let mut key = Vec::new();
+        item.write_to(&mut key)?;
+        if let Some(&index) = self.entries.get(&key) {
+            return Ok(index);
+        }
+        self.push(item)
+    }
+
+    /// Adds `item` even if an identical entry exists, as when rebuilding a
+    /// pool with the indices it had. Later lookups find the first of them.
+    pub(crate) fn push(&mut self, item: ConstantPoolItem) -> Result<u16> {
+        if matches!(item, ConstantPoolItem::Skip) {
+            return Err(Error::WriteError("Skip is not a constant pool entry".to_string()));
+        }
+        let mut key = Vec::new();
+        item.write_to(&mut key)?;
+        let wide = matches!(item, ConstantPoolItem::Long { .. } | ConstantPoolItem::Double { .. });
+        let index = self.items.len() + 1;
+        if index + wide as usize >= u16::MAX as usize {
+            return Err(Error::WriteError("the constant pool is full".to_string()));
+        }
+        self.items.push(item);
+        if wide {
+            self.items.push(ConstantPoolItem::Skip);
+        }
+        self.entries.entry(key).or_insert(index as u16);
+        Ok(index as u16)
+    }
+
+    pub fn utf8(&mut self, value: &str) -> Result<Utf8Index> {
+        self.add(ConstantPoolItem::Utf8 {
+            bytes: mutf8::encode(value),
+            value: value.to_string(),
+        })
+        .map(Utf8Index)
+    }
+
+    /// A class by internal name, e.g. `java/lang/String` or `[I`.
+    pub fn class(&mut self, name: &str) -> Result<ClassIndex> {
+        let name_index = self.utf8(name)?;
+        self.add(ConstantPoolItem::Class { name_index }).map(ClassIndex)
+    }
+
+    pub fn string(&mut self, value: &str) -> Result<StringIndex> {
+        let string_index = self.utf8(value)?;
+        self.add(ConstantPoolItem::String { string_index })
+            .map(StringIndex)
+    }
+
+    pub fn integer(&mut self, value: i32) -> Result<IntegerIndex> {
+        self.add(ConstantPoolItem::Integer { value }).map(IntegerIndex)
+    }
+
+    pub fn float(&mut self, value: f32) -> Result<FloatIndex> {
+        self.add(ConstantPoolItem::Float { value }).map(FloatIndex)
+    }
+
+    pub fn long(&mut self, value: i64) -> Result<LongIndex> {
+        self.add(ConstantPoolItem::Long { value }).map(LongIndex)
+    }
+
+    pub fn double(&mut self, value: f64) -> Result<DoubleIndex> {
+        self.add(ConstantPoolItem::Double { value }).map(DoubleIndex)
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> Result<NameAndTypeIndex> {
+        let name_index = self.utf8(name)?;
+        let descriptor_index = self.utf8(descriptor)?;
+        self.add(ConstantPoolItem::NameAndType {
+            name_index,
+            descriptor_index,
+        })
+        .map(NameAndTypeIndex)
+    }
+
+    fn member(&mut self, class: &str, name: &str, descriptor: &str) -> Result<(ClassIndex, NameAndTypeIndex)> {
+        Ok((self.class(class)?, self.name_and_type(name, descriptor)?))
+    }
+
+    pub fn field_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<FieldrefIndex> {
+        let (class_index, name_and_type_index) = self.member(class, name, descriptor)?;
+        self.add(ConstantPoolItem::Fieldref {
+            class_index,
+            name_and_type_index,
+        })
+        .map(FieldrefIndex)
+    }
+
+    pub fn method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<MethodrefIndex> {
+        let (class_index, name_and_type_index) = self.member(class, name, descriptor)?;
+        self.add(ConstantPoolItem::Methodref {
+            class_index,
+            name_and_type_index,
+        })
+        .map(MethodrefIndex)
+    }
+
+    pub fn interface_method_ref(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<InterfaceMethodrefIndex> {
+        let (class_index, name_and_type_index) = self.member(class, name, descriptor)?;
+        self.add(ConstantPoolItem::InterfaceMethodref {
+            class_index,
+            name_and_type_index,
+        })
+        .map(InterfaceMethodrefIndex)
+    }
+
+    /// A method handle of `kind` (1 to 9, as in §4.4.8) to the field or
+    /// method entry at `reference`.
+    pub fn method_handle(&mut self, kind: u8, reference: u16) -> Result<MethodHandleIndex> {
+        self.add(ConstantPoolItem::MethodHandle {
+            reference: Reference {
+                kind,
+                index: reference,
+            },
+        })
+        .map(MethodHandleIndex)
+    }
+
+    pub fn method_type(&mut self, descriptor: &str) -> Result<MethodTypeIndex> {
+        let descriptor_index = self.utf8(descriptor)?;
+        self.add(ConstantPoolItem::MethodType { descriptor_index })
+            .map(MethodTypeIndex)
+    }
+
+    /// A dynamic constant produced by entry `bootstrap_method` of the
+    /// `BootstrapMethods` attribute.
+    pub fn dynamic(&mut self, bootstrap_method: u16, name: &str, descriptor: &str) -> Result<DynamicIndex> {
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.add(ConstantPoolItem::Dynamic {
+            bootstrap_method_attr_index: BootstrapMethodAttrInfo(bootstrap_method),
+            name_and_type_index,
+        })
+        .map(DynamicIndex)
+    }
+
+    /// A call site produced by entry `bootstrap_method` of the
+    /// `BootstrapMethods` attribute.
+    pub fn invoke_dynamic(
+        &mut self,
+        bootstrap_method: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<InvokeDynamicIndex> {
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.add(ConstantPoolItem::InvokeDynamic {
+            bootstrap_method_attr_index: BootstrapMethodAttrInfo(bootstrap_method),
+            name_and_type_index,
+        })
+        .map(InvokeDynamicIndex)
+    }
+
+    pub fn module(&mut self, name: &str) -> Result<ModuleIndex> {
+        let name_index = self.utf8(name)?;
+        self.add(ConstantPoolItem::Module { name_index }).map(ModuleIndex)
+    }
+
+    pub fn package(&mut self, name: &str) -> Result<PackageIndex> {
+        let name_index = self.utf8(name)?;
+        self.add(ConstantPoolItem::Package { name_index }).map(PackageIndex)
+    }
+
+    /// Writes `constant_pool_count` followed by the entries.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_u16(writer, self.next_index())?;
+        for item in &self.items {
+            item.write_to(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// The contents of a method's `Code` attribute, without exception handlers or
+/// nested attributes.
+#[derive(Debug, Clone)]
+pub struct MethodCode {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub code: Vec<u8>,
+}
+
+impl MethodCode {
+    fn to_attribute(&self, attributes: &Attributes) -> Result<Vec<u8>> {
+        code_attribute(self.max_stack, self.max_locals, &self.code, &[], attributes)
+    }
+}
+
+/// The contents of a `Code` attribute, from an exception table of
+/// `(start_pc, end_pc, handler_pc, catch_type)`.
+pub(crate) fn code_attribute(
+    max_stack: u16,
+    max_locals: u16,
+    code: &[u8],
+    exception_table: &[[u16; 4]],
+    attributes: &Attributes,
+) -> Result<Vec<u8>> {
+    if code.is_empty() || code.len() > u16::MAX as usize {
+        return Err(Error::WriteError(format!("method code of {} bytes", code.len())));
+    }
+    let mut out = Vec::new();
+    write_u16(&mut out, max_stack)?;
+    write_u16(&mut out, max_locals)?;
+    out.extend_from_slice(&(code.len() as u32).to_be_bytes());
+    out.extend_from_slice(code);
+    write_u16(&mut out, count(exception_table, "exception handlers")?)?;
+    for entry in exception_table {
+        for x in entry {
+            write_u16(&mut out, *x)?;
+        }
+    }
+    attributes.write_to(&mut out)?;
+    Ok(out)
+}
+
+/// An exception handler covering the code from `start` up to `end`.
+#[derive(Debug, Clone)]
+pub struct ExceptionHandler {
+    pub start: Label,
+    pub end: Label,
+    pub handler: Label,
+    /// The internal name of the caught class, or `None` to catch everything.
+    pub catch_type: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Body {
+    Code(MethodCode),
+    Assembly {
+        max_stack: u16,
+        max_locals: u16,
+        ops: Vec<Op>,
+    },
+    /// Calls the superclass's `<init>()V`.
+    SuperConstructor,
+}
+
+pub(crate) fn attribute_entries(pool: &mut ConstantPoolBuilder, attributes: Vec<(String, Vec<u8>)>) -> Result<Attributes> {
+    attributes
+        .into_iter()
+        .map(|(name, data)| {
+            Ok(AttributeEntry {
+                name_index: pool.utf8(&name)?.0,
+                name,
+                data,
+                span: Span::default(),
+            })
+        })
+        .collect::<Result<_>>()
+        .map(Attributes)
+}
+
+/// A field for [`ClassFileBuilder::with_field`].
+#[derive(Debug, Clone)]
+pub struct FieldBuilder {
+    access_flags: u16,
+    name: String,
+    descriptor: String,
+    constant_value: Option<Constant>,
+    attributes: Vec<(String, Vec<u8>)>,
+}
+
+impl FieldBuilder {
+    pub fn new(access_flags: FieldAccessFlags, name: &str, descriptor: &TypeDescriptor) -> Self {
+        Self::from_raw(access_flags.bits(), name, descriptor.to_string())
+    }
+
+    /// A field with a descriptor taken as is, e.g. from a class being read.
+    pub(crate) fn from_raw(access_flags: u16, name: &str, descriptor: String) -> Self {
+        Self {
+            access_flags,
+            name: name.to_string(),
+            descriptor,
+            constant_value: None,
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Adds a `ConstantValue` attribute, which the JVM only uses on static
+    /// fields. Class, method type, method handle and dynamic constants are
+    /// rejected when the class is built.
+    pub fn with_constant_value(mut self, value: Constant) -> Self {
+        self.constant_value = Some(value);
+        self
+    }
+
+    /// Adds an attribute with the given contents, e.g. an empty `Synthetic`.
+    pub fn with_attribute(mut self, name: &str, data: Vec<u8>) -> Self {
+        self.attributes.push((name.to_string(), data));
+        self
+    }
+
+    fn build(&self, pool: &mut ConstantPoolBuilder) -> Result<FieldRaw> {
+        let mut attributes = Vec::new();
+        if let Some(value) = &self.constant_value {
+            let index = match value {
+                Constant::Integer(x) => pool.integer(*x)?.0,
+                Constant::Float(x) => pool.float(*x)?.0,
+                Constant::Long(x) => pool.long(*x)?.0,
+                Constant::Double(x) => pool.double(*x)?.0,
+                Constant::String(x) => pool.string(x)?.0,
+                Constant::Utf16String(_) => value.add_to(pool)?,
+                Constant::Class(_) | Constant::MethodType(_) | Constant::MethodHandle { .. } | Constant::Dynamic { .. } => {
+                    return Err(Error::WriteError(format!("{:?} is not a field constant", value)))
+                }
+            };
+            attributes.push(("ConstantValue".to_string(), index.to_be_bytes().to_vec()));
+        }
+        attributes.extend(self.attributes.iter().cloned());
+        Ok(FieldRaw {
+            access_flags: FieldAccessFlags::from_bits_retain(self.access_flags),
+            name_index: pool.utf8(&self.name)?,
+            descriptor_index: pool.utf8(&self.descriptor)?,
+            attributes: attribute_entries(pool, attributes)?,
+            span: Span::default(),
+        })
+    }
+}
+
+/// A method for [`ClassFileBuilder::with_method`]. Without code it suits
+/// abstract and native methods.
+#[derive(Debug, Clone)]
+pub struct MethodBuilder {
+    access_flags: u16,
+    name: String,
+    descriptor: String,
+    body: Option<Body>,
+    exception_handlers: Vec<ExceptionHandler>,
+    code_attributes: Vec<(String, Vec<u8>)>,
+    attributes: Vec<(String, Vec<u8>)>,
+}
+
+impl MethodBuilder {
+    pub fn new(access_flags: MethodAccessFlags, name: &str, descriptor: &MethodDescriptor) -> Self {
+        Self::from_raw(access_flags.bits(), name, descriptor.to_string())
+    }
+
+    /// A method with a descriptor taken as is, e.g. from a class being read.
+    pub(crate) fn from_raw(access_flags: u16, name: &str, descriptor: String) -> Self {
+        Self {
+            access_flags,
+            name: name.to_string(),
+            descriptor,
+            body: None,
+            exception_handlers: Vec::new(),
+            code_attributes: Vec::new(),
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Sets the body to `ops`, assembled when the class is built.
+    pub fn with_code(mut self, max_stack: u16, max_locals: u16, ops: Vec<Op>) -> Self {
+        self.body = Some(Body::Assembly {
+            max_stack,
+            max_locals,
+            ops,
+        });
+        self
+    }
+
+    /// Sets the body to already encoded bytecode, whose indices refer to the
+    /// pool given to [`ClassFileBuilder::with_constant_pool`].
+    pub fn with_raw_code(mut self, code: MethodCode) -> Self {
+        self.body = Some(Body::Code(code));
+        self
+    }
+
+    /// Adds an exception handler between labels of the code given to
+    /// [`Self::with_code`]. Handlers are tried in the order they are added.
+    pub fn with_exception_handler(mut self, handler: ExceptionHandler) -> Self {
+        self.exception_handlers.push(handler);
+        self
+    }
+
+    /// Adds an attribute with the given contents, e.g. an empty `Deprecated`.
+    pub fn with_attribute(mut self, name: &str, data: Vec<u8>) -> Self {
+        self.attributes.push((name.to_string(), data));
+        self
+    }
+
+    /// Adds an attribute to the method's `Code` attribute, e.g. the
+    /// `StackMapTable` that methods which branch need from class file version
+    /// 50 on. Pcs and constant pool indices in `data` are taken as they are,
+    /// so pool entries it refers to belong in the pool given to
+    /// [`ClassFileBuilder::with_constant_pool`].
+    pub fn with_code_attribute(mut self, name: &str, data: Vec<u8>) -> Self {
+        self.code_attributes.push((name.to_string(), data));
+        self
+    }
+
+    fn build(&self, pool: &mut ConstantPoolBuilder, super_class: Option<&str>) -> Result<MethodRaw> {
+        if !self.exception_handlers.is_empty() && !matches!(self.body, Some(Body::Assembly { .. })) {
+            return Err(Error::WriteError(format!(
+                "exception handlers in {} without assembled code",
+                self.name
+            )));
+        }
+        if !self.code_attributes.is_empty() && self.body.is_none() {
+            return Err(Error::WriteError(format!("code attributes in {} without code", self.name)));
+        }
+        let code_attributes = attribute_entries(pool, self.code_attributes.clone())?;
+        let code = match &self.body {
+            Some(Body::Code(code)) => Some(code.to_attribute(&code_attributes)?),
+            Some(Body::Assembly {
+                max_stack,
+                max_locals,
+                ops,
+            }) => {
+                let assembled = assemble(ops, pool)?;
+                let pc = |label: Label| {
+                    assembled
+                        .pc(label)
+                        .map(|x| x as u16)
+                        .ok_or_else(|| Error::AssemblyError(format!("label {} is never bound", label.0)))
+                };
+                let exception_table = self
+                    .exception_handlers
+                    .iter()
+                    .map(|x| {
+                        let catch_type = match &x.catch_type {
+                            Some(name) => pool.class(name)?.0,
+                            None => 0,
+                        };
+                        Ok([pc(x.start)?, pc(x.end)?, pc(x.handler)?, catch_type])
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Some(code_attribute(
+                    *max_stack,
+                    *max_locals,
+                    &assembled.code,
+                    &exception_table,
+                    &code_attributes,
+                )?)
+            }
+            Some(Body::SuperConstructor) => {
+                let super_class = super_class.ok_or_else(|| {
+                    Error::WriteError("a default constructor needs a superclass".to_string())
+                })?;
+                let ops = [
+                    Op::Local { opcode: 0x19, index: 0 },
+                    Op::Invoke {
+                        opcode: 0xb7,
+                        class: super_class.to_string(),
+                        name: "<init>".to_string(),
+                        descriptor: "()V".to_string(),
+                        interface: false,
+                    },
+                    Op::Simple(0xb1),
+                ];
+                Some(code_attribute(1, 1, &assemble(&ops, pool)?.code, &[], &code_attributes)?)
+            }
+            None => None,
+        };
+        let attributes = code
+            .map(|x| ("Code".to_string(), x))
+            .into_iter()
+            .chain(self.attributes.iter().cloned())
+            .collect();
+        Ok(MethodRaw {
+            access_flags: MethodAccessFlags::from_bits_retain(self.access_flags),
+            name_index: pool.utf8(&self.name)?,
+            descriptor_index: pool.utf8(&self.descriptor)?,
+            attributes: attribute_entries(pool, attributes)?,
+            span: Span::default(),
+        })
+    }
+}
+
+/// Builds a class file from names and descriptors, adding the constants it
+/// needs to the pool when the class is encoded.
+///
+/// The class starts out `public`, extending `java/lang/Object`, with major
+/// version 50 and no members. Nothing is checked beyond what is needed to
+/// encode the class, so the caller is responsible for producing something a
+/// JVM will accept.
+#[derive(Debug, Clone)]
+pub struct ClassFileBuilder {
+    name: String,
+    major_version: u16,
+    minor_version: u16,
+    access_flags: u16,
+    super_class: Option<String>,
+    interfaces: Vec<String>,
+    fields: Vec<FieldBuilder>,
+    methods: Vec<MethodBuilder>,
+    attributes: Vec<(String, Vec<u8>)>,
+    constant_pool: ConstantPoolBuilder,
+}
+
+impl ClassFileBuilder {
+    /// A class with internal name `name`, e.g. `com/example/Stub`.
+    ///
+    /// Version 50 is the newest a JVM loads without a `StackMapTable` on
+    /// methods that branch, which the builder does not compute. Raising it
+    /// with [`Self::with_version`] requires attaching one to such methods.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            major_version: 50,
+            minor_version: 0,
+            access_flags: (ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER).bits(),
+            super_class: Some("java/lang/Object".to_string()),
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Vec::new(),
+            constant_pool: ConstantPoolBuilder::new(),
+        }
+    }
+
+    /// Starts the class's constant pool from `constant_pool`, so that code and
+    /// attributes can use indices from it. Constants the class needs beyond
+    /// those are added after its entries.
+    pub fn with_constant_pool(mut self, constant_pool: ConstantPoolBuilder) -> Self {
+        self.constant_pool = constant_pool;
+        self
+    }
+
+    pub fn with_version(mut self, major: u16, minor: u16) -> Self {
+        self.major_version = major;
+        self.minor_version = minor;
+        self
+    }
+
+    pub fn with_access_flags(mut self, access_flags: ClassAccessFlags) -> Self {
+        self.access_flags = access_flags.bits();
+        self
+    }
+
+    pub fn with_super_class(mut self, name: &str) -> Self {
+        self.super_class = Some(name.to_string());
+        self
+    }
+
+    /// Leaves out the superclass, as only `java/lang/Object` and module
+    /// descriptors do.
+    pub fn without_super_class(mut self) -> Self {
+        self.super_class = None;
+        self
+    }
+
+    pub fn with_interface(mut self, name: &str) -> Self {
+        self.interfaces.push(name.to_string());
+        self
+    }
+
+    pub fn with_field(mut self, field: FieldBuilder) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    pub fn with_method(mut self, method: MethodBuilder) -> Self {
+        self.methods.push(method);
+        self
+    }
+
+    /// Adds a public `<init>()V` that only calls the superclass constructor.
+    pub fn with_default_constructor(mut self) -> Self {
+        self.methods.push(MethodBuilder {
+            body: Some(Body::SuperConstructor),
+            ..MethodBuilder::new(MethodAccessFlags::PUBLIC, "<init>", &MethodDescriptor::new(Vec::new(), None))
+        });
+        self
+    }
+
+    /// Adds a class attribute with the given contents, e.g. a `SourceFile`
+    /// pointing into the pool given to [`Self::with_constant_pool`].
+    pub fn with_attribute(mut self, name: &str, data: Vec<u8>) -> Self {
+        self.attributes.push((name.to_string(), data));
+        self
+    }
+
+    /// Encodes the class.
+    pub fn build(&self) -> Result<Vec<u8>> {
+        let mut pool = self.constant_pool.clone();
+        let this_class = pool.class(&self.name)?.0;
+        let super_class = match &self.super_class {
+            Some(x) => pool.class(x)?.0,
+            None => 0,
+        };
+        let interfaces = self
+            .interfaces
+            .iter()
+            .map(|x| Ok(pool.class(x)?.0))
+            .collect::<Result<Vec<_>>>()?;
+        let fields = self
+            .fields
+            .iter()
+            .map(|x| x.build(&mut pool))
+            .collect::<Result<Vec<_>>>()?;
+        let methods = self
+            .methods
+            .iter()
+            .map(|x| x.build(&mut pool, self.super_class.as_deref()))
+            .collect::<Result<Vec<_>>>()?;
+        let attributes = attribute_entries(&mut pool, self.attributes.clone())?;
+
+        let mut out = b"\xca\xfe\xba\xbe".to_vec();
+        write_u16(&mut out, self.minor_version)?;
+        write_u16(&mut out, self.major_version)?;
+        pool.write_to(&mut out)?;
+        write_u16(&mut out, self.access_flags)?;
+        write_u16(&mut out, this_class)?;
+        write_u16(&mut out, super_class)?;
+        write_u16(&mut out, count(&interfaces, "interfaces")?)?;
+        for interface in interfaces {
+            write_u16(&mut out, interface)?;
+        }
+        write_u16(&mut out, count(&fields, "fields")?)?;
+        for field in &fields {
+            field.write_to(&mut out)?;
+        }
+        write_u16(&mut out, count(&methods, "methods")?)?;
+        for method in &methods {
+            method.write_to(&mut out)?;
+        }
+        attributes.write_to(&mut out)?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{verifier::Verifier, ClassFile};
+
+    /// `static int sign(int)`, returning 1 for anything but zero.
+    fn branching_method() -> MethodBuilder {
+        let descriptor = MethodDescriptor::new(vec![TypeDescriptor::Int], Some(TypeDescriptor::Int));
+        let zero = Label(0);
+        MethodBuilder::new(MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC, "sign", &descriptor).with_code(
+            1,
+            1,
+            vec![
+                Op::Local { opcode: 0x15, index: 0 },
+                Op::Branch { opcode: 0x99, target: zero },
+                Op::Simple(0x04),
+                Op::Simple(0xac),
+                Op::Label(zero),
+                Op::Simple(0x03),
+                Op::Simple(0xac),
+            ],
+        )
+    }
+
+    fn violations(class: ClassFileBuilder) -> usize {
+        let class_file = ClassFile::parse(&class.build().unwrap()[..]).unwrap();
+        Verifier::new().verify(&class_file).unwrap().len()
+    }
+
+    #[test]
+    fn branching_method_verifies_at_the_default_version() {
+        let class = ClassFileBuilder::new("Sign").with_method(branching_method());
+        let class_file = ClassFile::parse(&class.build().unwrap()[..]).unwrap();
+        assert_eq!(class_file.major_version(), 50);
+        assert_eq!(violations(class), 0);
+    }
+
+    #[test]
+    fn branching_method_needs_a_stack_map_table_from_version_51() {
+        let class = ClassFileBuilder::new("Sign").with_version(52, 0);
+        assert_ne!(violations(class.clone().with_method(branching_method())), 0);
+
+        // One `same_frame` at the `iconst_0` at pc 6.
+        let method = branching_method().with_code_attribute("StackMapTable", vec![0, 1, 6]);
+        assert_eq!(violations(class.with_method(method)), 0);
+    }
+
+    #[test]
+    fn code_attributes_need_code() {
+        let descriptor = MethodDescriptor::new(Vec::new(), None);
+        let method = MethodBuilder::new(MethodAccessFlags::ABSTRACT, "run", &descriptor)
+            .with_code_attribute("StackMapTable", vec![0, 0]);
+        assert!(ClassFileBuilder::new("Run").with_method(method).build().is_err());
+    }
+}
@@ -0,0 +1,118 @@
+//! Tallies of what classes contain, see [`ClassFile::stats`] and
+//! [`ClassStats::add`].
+
+use std::collections::BTreeMap;
+
+use crate::{raw::AttributeEntry, ClassFile, Result};
+
+/// How often an attribute occurs and how much space it takes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AttributeStats {
+    pub count: usize,
+    /// The size of the contents, not counting the six byte name and length
+    /// header of each.
+    pub bytes: usize,
+}
+
+/// Counts over one class or many, see [`ClassFile::stats`]. Start from
+/// [`ClassStats::default`] and [`add`](Self::add) classes, or
+/// [`merge`](Self::merge) counts made separately, to total a codebase.
+#[derive(Debug, Clone, Default)]
+pub struct ClassStats {
+    pub classes: usize,
+    pub fields: usize,
+    pub methods: usize,
+    /// Methods with a `Code` attribute.
+    pub methods_with_code: usize,
+    /// The total length of all bytecode, in bytes.
+    pub code_bytes: usize,
+    /// The number of times each instruction occurs, keyed by mnemonic.
+    pub opcodes: BTreeMap<&'static str, usize>,
+    /// The `invoke*` entries of [`Self::opcodes`] on their own, to compare
+    /// how calls are dispatched.
+    pub invokes: BTreeMap<&'static str, usize>,
+    /// The number of constant pool entries of each kind, keyed by
+    /// [`ConstantPoolItem::kind`](crate::raw::ConstantPoolItem::kind).
+    pub constant_pool: BTreeMap<&'static str, usize>,
+    /// Attributes by name, wherever they are: on the class, its fields and
+    /// methods, or a `Code` attribute.
+    pub attributes: BTreeMap<String, AttributeStats>,
+}
+
+impl ClassStats {
+    /// Adds the counts of `class_file`. Fails if a method's code cannot be
+    /// decoded, leaving counts from the part of the class already read.
+    pub fn add(&mut self, class_file: &ClassFile) -> Result<()> {
+        self.classes += 1;
+        self.fields += class_file.fields.len();
+        self.methods += class_file.methods.len();
+        for (_, x) in class_file.constant_pool() {
+            *self.constant_pool.entry(x.kind()).or_default() += 1;
+        }
+        self.attributes(&class_file.attributes.0);
+        for x in &class_file.fields {
+            self.attributes(&x.attributes.0);
+        }
+        for x in &class_file.methods {
+            self.attributes(&x.attributes.0);
+        }
+        for method in class_file.methods() {
+            let code = match method.code()? {
+                Some(x) => x,
+                None => continue,
+            };
+            self.methods_with_code += 1;
+            self.attributes(&code.attributes.0);
+            for x in code.instructions_at()? {
+                let mnemonic = x.instruction.mnemonic();
+                *self.opcodes.entry(mnemonic).or_default() += 1;
+                if mnemonic.starts_with("invoke") {
+                    *self.invokes.entry(mnemonic).or_default() += 1;
+                }
+                self.code_bytes += x.length as usize;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds counts made separately, such as on another thread.
+    pub fn merge(&mut self, other: &ClassStats) {
+        self.classes += other.classes;
+        self.fields += other.fields;
+        self.methods += other.methods;
+        self.methods_with_code += other.methods_with_code;
+        self.code_bytes += other.code_bytes;
+        for (name, count) in &other.opcodes {
+            *self.opcodes.entry(name).or_default() += count;
+        }
+        for (name, count) in &other.invokes {
+            *self.invokes.entry(name).or_default() += count;
+        }
+        for (kind, count) in &other.constant_pool {
+            *self.constant_pool.entry(kind).or_default() += count;
+        }
+        for (name, x) in &other.attributes {
+            let entry = self.attributes.entry(name.clone()).or_default();
+            entry.count += x.count;
+            entry.bytes += x.bytes;
+        }
+    }
+
+    fn attributes(&mut self, attributes: &[AttributeEntry]) {
+        for x in attributes {
+            let entry = self.attributes.entry(x.name.clone()).or_default();
+            entry.count += 1;
+            entry.bytes += x.data.len();
+        }
+    }
+}
+
+impl ClassFile {
+    /// Tallies the instructions, constant pool entries and attributes of the
+    /// class, see [`ClassStats`].
+    pub fn stats(&self) -> Result<ClassStats> {
+        let mut stats = ClassStats::default();
+        stats.add(self)?;
+        Ok(stats)
+    }
+}
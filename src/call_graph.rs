@@ -0,0 +1,272 @@
+//! Calls between methods, as found in their code, see [`ClassFile::call_edges`]
+//! and [`CallGraph`].
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    instruction::{Instruction, InterfaceMethodRef, MaybeInterfaceMethodRef, MethodHandle, MethodRef},
+    resolver::ClassResolver,
+    ClassFile, Result,
+};
+
+/// A method by class, name and descriptor. For a callee the class is the
+/// one the call names, which may inherit the method rather than declare it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MethodId {
+    pub class: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+impl MethodId {
+    pub fn new(class: &str, name: &str, descriptor: &str) -> Self {
+        Self {
+            class: class.to_string(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+        }
+    }
+
+    /// The method this one resolves to through `resolver`, with the class
+    /// declaring it, see [`ClassResolver::resolve_method`]. `None` if no
+    /// class that resolves declares it.
+    pub fn resolve(&self, resolver: &(impl ClassResolver + ?Sized)) -> Result<Option<MethodId>> {
+        let declaring = match resolver.resolve_method(&self.class, &self.name, &self.descriptor)? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        Ok(Some(Self::new(declaring.this_class()?, &self.name, &self.descriptor)))
+    }
+
+    fn method(x: &MethodRef) -> Self {
+        Self::new(x.class, x.name, &x.descriptor.to_string())
+    }
+
+    fn interface_method(x: &InterfaceMethodRef) -> Self {
+        Self::new(x.class, x.name, &x.descriptor.to_string())
+    }
+
+    fn maybe_interface_method(x: &MaybeInterfaceMethodRef) -> Self {
+        match x {
+            MaybeInterfaceMethodRef::RegularMethod(x) => Self::method(x),
+            MaybeInterfaceMethodRef::InterfaceMethod(x) => Self::interface_method(x),
+        }
+    }
+
+    /// The method a handle invokes, or `None` for a field handle.
+    fn handle(x: &MethodHandle) -> Option<Self> {
+        match x {
+            MethodHandle::GetField(_)
+            | MethodHandle::GetStatic(_)
+            | MethodHandle::PutField(_)
+            | MethodHandle::PutStatic(_) => None,
+            MethodHandle::InvokeVirtual(x) | MethodHandle::NewInvokeSpecial(x) => Some(Self::method(x)),
+            MethodHandle::InvokeStatic(x) | MethodHandle::InvokeSpecial(x) => Some(Self::maybe_interface_method(x)),
+            MethodHandle::InvokeInterface(x) => Some(Self::interface_method(x)),
+        }
+    }
+}
+
+/// Writes the method as `class.name` followed by the descriptor, e.g.
+/// `java/util/List.add(Ljava/lang/Object;)Z`.
+impl std::fmt::Display for MethodId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}{}", self.class, self.name, self.descriptor)
+    }
+}
+
+/// How a [`CallEdge`] calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CallKind {
+    Virtual,
+    Interface,
+    Special,
+    Static,
+    /// An `invokedynamic`, calling its bootstrap method to link the site.
+    Dynamic,
+    /// An `invokedynamic` making a lambda or method reference, to the method
+    /// the lambda calls when invoked. The site also has a
+    /// [`Dynamic`](Self::Dynamic) edge to `LambdaMetafactory`.
+    Lambda,
+}
+
+impl CallKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Virtual => "virtual",
+            Self::Interface => "interface",
+            Self::Special => "special",
+            Self::Static => "static",
+            Self::Dynamic => "dynamic",
+            Self::Lambda => "lambda",
+        }
+    }
+}
+
+/// A call from the instruction at `pc` in `caller`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CallEdge {
+    pub caller: MethodId,
+    pub pc: u32,
+    pub kind: CallKind,
+    pub callee: MethodId,
+}
+
+impl ClassFile {
+    /// Every call made by the code of the class's methods, in method and
+    /// then code order. Callees are as the instructions name them, not
+    /// resolved to the methods a virtual call may reach.
+    pub fn call_edges(&self) -> Result<Vec<CallEdge>> {
+        let this_class = self.this_class()?;
+        let mut res = Vec::new();
+        for method in self.methods() {
+            let code = match method.code()? {
+                Some(x) => x,
+                None => continue,
+            };
+            let caller = MethodId::new(
+                this_class,
+                method.identifier()?,
+                method.method_inner.descriptor_index.get_as_string(self)?,
+            );
+            for (pc, instruction) in code.instructions_with_pcs()? {
+                let mut edge = |kind, callee| {
+                    res.push(CallEdge {
+                        caller: caller.clone(),
+                        pc,
+                        kind,
+                        callee,
+                    })
+                };
+                match &instruction {
+                    Instruction::Invokevirtual { index } => edge(CallKind::Virtual, MethodId::method(index)),
+                    Instruction::Invokeinterface { index, .. } => {
+                        edge(CallKind::Interface, MethodId::interface_method(index))
+                    }
+                    Instruction::Invokespecial { index } => {
+                        edge(CallKind::Special, MethodId::maybe_interface_method(index))
+                    }
+                    Instruction::Invokestatic { index } => {
+                        edge(CallKind::Static, MethodId::maybe_interface_method(index))
+                    }
+                    Instruction::Invokedynamic { index, .. } => {
+                        if let Some(x) = MethodId::handle(&index.bootstrap_method.method) {
+                            edge(CallKind::Dynamic, x);
+                        }
+                        if let Some(x) = index.lambda()?.and_then(|x| MethodId::handle(&x.implementation)) {
+                            edge(CallKind::Lambda, x);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// The calls made by many classes, indexed by caller and by callee.
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    edges: Vec<CallEdge>,
+    defined: BTreeSet<MethodId>,
+    by_caller: BTreeMap<MethodId, Vec<usize>>,
+    by_callee: BTreeMap<MethodId, Vec<usize>>,
+}
+
+impl CallGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_classes<'a>(classes: impl IntoIterator<Item = &'a ClassFile>) -> Result<Self> {
+        let mut graph = Self::new();
+        for class in classes {
+            graph.add_class(class)?;
+        }
+        Ok(graph)
+    }
+
+    /// Adds the methods of `class_file` and the calls they make.
+    pub fn add_class(&mut self, class_file: &ClassFile) -> Result<()> {
+        let this_class = class_file.this_class()?;
+        for method in class_file.methods() {
+            self.defined.insert(MethodId::new(
+                this_class,
+                method.identifier()?,
+                method.method_inner.descriptor_index.get_as_string(class_file)?,
+            ));
+        }
+        for edge in class_file.call_edges()? {
+            let id = self.edges.len();
+            self.by_caller.entry(edge.caller.clone()).or_default().push(id);
+            self.by_callee.entry(edge.callee.clone()).or_default().push(id);
+            self.edges.push(edge);
+        }
+        Ok(())
+    }
+
+    /// Every call, grouped by class in the order classes were added.
+    pub fn edges(&self) -> &[CallEdge] {
+        &self.edges
+    }
+
+    /// The methods declared by the classes added, whether or not they have
+    /// code.
+    pub fn methods(&self) -> impl Iterator<Item = &MethodId> {
+        self.defined.iter()
+    }
+
+    /// Whether `method` is declared by one of the classes added.
+    pub fn contains(&self, method: &MethodId) -> bool {
+        self.defined.contains(method)
+    }
+
+    /// The calls made by `caller`, in code order.
+    pub fn calls_from(&self, caller: &MethodId) -> Vec<&CallEdge> {
+        self.by_caller.get(caller).map_or_else(Vec::new, |x| x.iter().map(|&x| &self.edges[x]).collect())
+    }
+
+    /// The calls naming `callee`, wherever they are.
+    pub fn calls_to(&self, callee: &MethodId) -> Vec<&CallEdge> {
+        self.by_callee.get(callee).map_or_else(Vec::new, |x| x.iter().map(|&x| &self.edges[x]).collect())
+    }
+
+    /// The calls whose callee resolves to `method` through `resolver`, such
+    /// as calls naming a subclass that inherits it. Like [`Self::calls_to`]
+    /// it does not follow overriding, so a call to an overridden method is
+    /// not one to the overriding method.
+    pub fn calls_resolving_to(
+        &self,
+        resolver: &(impl ClassResolver + ?Sized),
+        method: &MethodId,
+    ) -> Result<Vec<&CallEdge>> {
+        let mut ids = Vec::new();
+        for (callee, edges) in &self.by_callee {
+            if callee.name != method.name || callee.descriptor != method.descriptor {
+                continue;
+            }
+            if callee == method || callee.resolve(resolver)?.as_ref() == Some(method) {
+                ids.extend(edges.iter().copied());
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids.into_iter().map(|x| &self.edges[x]).collect())
+    }
+
+    /// The distinct methods `caller` calls.
+    pub fn callees(&self, caller: &MethodId) -> BTreeSet<&MethodId> {
+        self.calls_from(caller).into_iter().map(|x| &x.callee).collect()
+    }
+
+    /// The distinct methods calling `callee`.
+    pub fn callers(&self, callee: &MethodId) -> BTreeSet<&MethodId> {
+        self.calls_to(callee).into_iter().map(|x| &x.caller).collect()
+    }
+
+    /// Methods called but not declared by any class added, such as those of
+    /// libraries and the JDK, or inherited ones named through a subclass.
+    pub fn external_callees(&self) -> impl Iterator<Item = &MethodId> {
+        self.by_callee.keys().filter(|x| !self.defined.contains(*x))
+    }
+}
@@ -0,0 +1,739 @@
+//! Canonical re-encoding, see [`ClassFile::to_canonical_bytes`].
+
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::{
+    instruction::{raw_instructions, OperandFormat},
+    raw::{ConstantPool, ConstantPoolItem, Reference},
+    writer::{count, write_u16},
+    ClassFile, Error, Result,
+};
+
+type Key = Rc<[u8]>;
+
+fn invalid_index(index: u16) -> Error {
+    Error::WriteError(format!("invalid constant pool index {}", index))
+}
+
+pub(crate) fn item(cpool: &ConstantPool, index: u16) -> Result<&ConstantPoolItem> {
+    match (index as usize).checked_sub(1).and_then(|x| cpool.0.get(x)) {
+        Some(ConstantPoolItem::Skip) | None => Err(invalid_index(index)),
+        Some(x) => Ok(x),
+    }
+}
+
+/// The pool indices `item` refers to, in encoding order.
+pub(crate) fn references(item: &ConstantPoolItem) -> Result<Vec<u16>> {
+    Ok(match item {
+        ConstantPoolItem::Class { name_index: x }
+        | ConstantPoolItem::String { string_index: x }
+        | ConstantPoolItem::MethodType {
+            descriptor_index: x,
+        }
+        | ConstantPoolItem::Module { name_index: x }
+        | ConstantPoolItem::Package { name_index: x } => vec![x.0],
+        ConstantPoolItem::Fieldref {
+            class_index,
+            name_and_type_index,
+        }
+        | ConstantPoolItem::Methodref {
+            class_index,
+            name_and_type_index,
+        }
+        | ConstantPoolItem::InterfaceMethodref {
+            class_index,
+            name_and_type_index,
+        } => vec![class_index.0, name_and_type_index.0],
+        ConstantPoolItem::NameAndType {
+            name_index,
+            descriptor_index,
+        } => vec![name_index.0, descriptor_index.0],
+        ConstantPoolItem::MethodHandle { reference } => vec![reference.index],
+        ConstantPoolItem::Dynamic {
+            name_and_type_index,
+            ..
+        }
+        | ConstantPoolItem::InvokeDynamic {
+            name_and_type_index,
+            ..
+        } => vec![name_and_type_index.0],
+        ConstantPoolItem::Integer { .. }
+        | ConstantPoolItem::Float { .. }
+        | ConstantPoolItem::Long { .. }
+        | ConstantPoolItem::Double { .. }
+        | ConstantPoolItem::Utf8 { .. } => Vec::new(),
+        ConstantPoolItem::Custom { tag, .. } => {
+            return Err(Error::WriteError(format!(
+                "cannot canonicalize custom constant with tag {}",
+                tag
+            )))
+        }
+        ConstantPoolItem::Skip => Vec::new(),
+    })
+}
+
+/// `item` with the indices from [`references`] replaced by `new`.
+fn with_references(item: &ConstantPoolItem, new: &[u16]) -> ConstantPoolItem {
+    let mut item = item.clone();
+    match &mut item {
+        ConstantPoolItem::Class { name_index: x }
+        | ConstantPoolItem::String { string_index: x }
+        | ConstantPoolItem::MethodType {
+            descriptor_index: x,
+        }
+        | ConstantPoolItem::Module { name_index: x }
+        | ConstantPoolItem::Package { name_index: x } => x.0 = new[0],
+        ConstantPoolItem::Fieldref {
+            class_index,
+            name_and_type_index,
+        }
+        | ConstantPoolItem::Methodref {
+            class_index,
+            name_and_type_index,
+        }
+        | ConstantPoolItem::InterfaceMethodref {
+            class_index,
+            name_and_type_index,
+        } => {
+            class_index.0 = new[0];
+            name_and_type_index.0 = new[1];
+        }
+        ConstantPoolItem::NameAndType {
+            name_index,
+            descriptor_index,
+        } => {
+            name_index.0 = new[0];
+            descriptor_index.0 = new[1];
+        }
+        ConstantPoolItem::MethodHandle {
+            reference: Reference { index, .. },
+        } => *index = new[0],
+        ConstantPoolItem::Dynamic {
+            name_and_type_index,
+            ..
+        }
+        | ConstantPoolItem::InvokeDynamic {
+            name_and_type_index,
+            ..
+        } => name_and_type_index.0 = new[0],
+        _ => {}
+    }
+    item
+}
+
+/// Assigns each entry an identity independent of where it and the entries it
+/// refers to sit in the pool, and records the order entries are first used in.
+struct Layout<'a> {
+    cpool: &'a ConstantPool,
+    keys: HashMap<u16, Key>,
+    /// Entries loaded by `ldc`, which must get an index below 256.
+    narrow: Vec<Key>,
+    rest: Vec<Key>,
+    seen: HashSet<Key>,
+    /// An old index for each key.
+    indices: HashMap<Key, u16>,
+}
+
+impl<'a> Layout<'a> {
+    fn key(&mut self, index: u16, depth: u8) -> Result<Key> {
+        if let Some(x) = self.keys.get(&index) {
+            return Ok(x.clone());
+        }
+        // MethodHandle -> Methodref -> NameAndType -> Utf8 is the longest chain.
+        if depth > 4 {
+            return Err(Error::WriteError(format!(
+                "constant pool entry {} refers to itself",
+                index
+            )));
+        }
+        let item = item(self.cpool, index)?;
+        let references = references(item)?;
+        let mut key = Vec::new();
+        with_references(item, &vec![0; references.len()]).write_to(&mut key)?;
+        for x in references {
+            let inner = self.key(x, depth + 1)?;
+            key.extend_from_slice(&(inner.len() as u32).to_be_bytes());
+            key.extend_from_slice(&inner);
+        }
+        let key: Key = key.into();
+        self.keys.insert(index, key.clone());
+        Ok(key)
+    }
+
+    fn visit(&mut self, index: u16, narrow: bool) -> Result<u16> {
+        let key = self.key(index, 0)?;
+        if narrow && !self.narrow.contains(&key) {
+            self.narrow.push(key.clone());
+        }
+        if self.seen.insert(key.clone()) {
+            self.rest.push(key.clone());
+            self.indices.insert(key, index);
+            for x in references(item(self.cpool, index)?)? {
+                self.visit(x, false)?;
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// Reads big-endian values from attribute contents.
+pub(crate) struct Reader<'d> {
+    pub(crate) data: &'d [u8],
+    pub(crate) pos: usize,
+    name: &'d str,
+}
+
+impl<'d> Reader<'d> {
+    pub(crate) fn new(data: &'d [u8], name: &'d str) -> Self {
+        Self { data, pos: 0, name }
+    }
+
+    pub(crate) fn bytes(&mut self, n: usize) -> Result<&'d [u8]> {
+        let res = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| Error::WriteError(format!("{} is truncated", self.name)))?;
+        self.pos += n;
+        Ok(res)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16> {
+        let x = self.bytes(2)?;
+        Ok(u16::from_be_bytes([x[0], x[1]]))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        let x = self.bytes(4)?;
+        Ok(u32::from_be_bytes([x[0], x[1], x[2], x[3]]))
+    }
+}
+
+/// Copies class contents in canonical order, passing every pool index
+/// through `map`. The second argument of `map` is set for `ldc` operands.
+pub(crate) struct Walker<'a, F> {
+    pub(crate) cpool: &'a ConstantPool,
+    pub(crate) map: F,
+    /// The names of attributes the walker cannot look into, which are copied
+    /// as is.
+    pub(crate) unknown: Vec<String>,
+}
+
+impl<'a, F: FnMut(u16, bool) -> Result<u16>> Walker<'a, F> {
+    fn index(&mut self, r: &mut Reader, out: &mut Vec<u8>) -> Result<()> {
+        let index = (self.map)(r.u16()?, false)?;
+        write_u16(out, index)
+    }
+
+    /// An index that may be 0 for "none".
+    fn optional_index(&mut self, r: &mut Reader, out: &mut Vec<u8>) -> Result<()> {
+        match r.u16()? {
+            0 => write_u16(out, 0),
+            x => write_u16(out, (self.map)(x, false)?),
+        }
+    }
+
+    fn copy(&mut self, r: &mut Reader, out: &mut Vec<u8>, n: usize) -> Result<()> {
+        out.extend_from_slice(r.bytes(n)?);
+        Ok(())
+    }
+
+    /// A `u16` count followed by that many items.
+    fn table<G>(&mut self, r: &mut Reader, out: &mut Vec<u8>, mut item: G) -> Result<()>
+    where
+        G: FnMut(&mut Self, &mut Reader, &mut Vec<u8>) -> Result<()>,
+    {
+        let count = r.u16()?;
+        write_u16(out, count)?;
+        (0..count).try_for_each(|_| item(self, r, out))
+    }
+
+    /// Writes an attribute table, sorted by name.
+    fn attributes(&mut self, attributes: Vec<(u16, &[u8])>, out: &mut Vec<u8>) -> Result<()> {
+        let cpool = self.cpool;
+        let mut attributes = attributes
+            .into_iter()
+            .map(|(index, data)| match item(cpool, index)? {
+                ConstantPoolItem::Utf8 { value, .. } => Ok((index, value.as_str(), data)),
+                _ => Err(invalid_index(index)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        attributes.sort_by_key(|(_, name, _)| *name);
+        write_u16(out, count(&attributes, "attributes")?)?;
+        for (index, name, data) in attributes {
+            write_u16(out, (self.map)(index, false)?)?;
+            let body = self.attribute(name, data)?;
+            let length = u32::try_from(body.len())
+                .map_err(|_| Error::WriteError(format!("attribute {} is too long", name)))?;
+            out.extend_from_slice(&length.to_be_bytes());
+            out.extend_from_slice(&body);
+        }
+        Ok(())
+    }
+
+    /// Reads an attribute table nested in another attribute.
+    fn attribute_table(&mut self, r: &mut Reader, out: &mut Vec<u8>) -> Result<()> {
+        let mut attributes = Vec::new();
+        for _ in 0..r.u16()? {
+            let index = r.u16()?;
+            let length = r.u32()?;
+            attributes.push((index, r.bytes(length as usize)?));
+        }
+        self.attributes(attributes, out)
+    }
+
+    /// The contents of attribute `name`, dropping any bytes after its structure.
+    fn attribute(&mut self, name: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let r = &mut Reader::new(data, name);
+        let out = &mut Vec::new();
+        match name {
+            "ConstantValue" | "SourceFile" | "Signature" | "NestHost" | "ModuleMainClass" => {
+                self.index(r, out)?
+            }
+            "Exceptions" | "NestMembers" | "PermittedSubclasses" | "ModulePackages" => {
+                self.table(r, out, |w, r, out| w.index(r, out))?
+            }
+            "Synthetic" | "Deprecated" => {}
+            "SourceDebugExtension" => self.copy(r, out, data.len())?,
+            "LineNumberTable" => self.table(r, out, |w, r, out| w.copy(r, out, 4))?,
+            "LocalVariableTable" | "LocalVariableTypeTable" => {
+                self.table(r, out, |w, r, out| {
+                    w.copy(r, out, 4)?;
+                    w.index(r, out)?;
+                    w.index(r, out)?;
+                    w.copy(r, out, 2)
+                })?
+            }
+            "InnerClasses" => self.table(r, out, |w, r, out| {
+                w.index(r, out)?;
+                w.optional_index(r, out)?;
+                w.optional_index(r, out)?;
+                w.copy(r, out, 2)
+            })?,
+            "EnclosingMethod" => {
+                self.index(r, out)?;
+                self.optional_index(r, out)?;
+            }
+            "BootstrapMethods" => self.table(r, out, |w, r, out| {
+                w.index(r, out)?;
+                w.table(r, out, |w, r, out| w.index(r, out))
+            })?,
+            "MethodParameters" => {
+                let count = r.u8()?;
+                out.push(count);
+                for _ in 0..count {
+                    self.optional_index(r, out)?;
+                    self.copy(r, out, 2)?;
+                }
+            }
+            "Code" => self.code(r, out)?,
+            "StackMapTable" => self.stack_map_table(r, out)?,
+            "Record" => self.table(r, out, |w, r, out| {
+                w.index(r, out)?;
+                w.index(r, out)?;
+                w.attribute_table(r, out)
+            })?,
+            "RuntimeVisibleAnnotations" | "RuntimeInvisibleAnnotations" => {
+                self.table(r, out, |w, r, out| w.annotation(r, out))?
+            }
+            "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+                let count = r.u8()?;
+                out.push(count);
+                for _ in 0..count {
+                    self.table(r, out, |w, r, out| w.annotation(r, out))?;
+                }
+            }
+            "RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => {
+                self.table(r, out, |w, r, out| w.type_annotation(r, out))?
+            }
+            "AnnotationDefault" => self.element_value(r, out)?,
+            "Module" => self.module(r, out)?,
+            // Not in the specification, but written by the JDK into module-info.
+            "ModuleHashes" => {
+                self.index(r, out)?;
+                self.table(r, out, |w, r, out| {
+                    w.index(r, out)?;
+                    let length = r.u16()?;
+                    write_u16(out, length)?;
+                    w.copy(r, out, length as usize)
+                })?
+            }
+            "ModuleTarget" => self.index(r, out)?,
+            "ModuleResolution" => self.copy(r, out, 2)?,
+            _ => {
+                self.unknown.push(name.to_string());
+                self.copy(r, out, data.len())?
+            }
+        }
+        Ok(std::mem::take(out))
+    }
+
+    fn code(&mut self, r: &mut Reader, out: &mut Vec<u8>) -> Result<()> {
+        self.copy(r, out, 4)?;
+        let length = r.u32()?;
+        out.extend_from_slice(&length.to_be_bytes());
+        let code = r.bytes(length as usize)?;
+        self.bytecode(code, out)?;
+        self.table(r, out, |w, r, out| {
+            w.copy(r, out, 6)?;
+            w.optional_index(r, out)
+        })?;
+        self.attribute_table(r, out)
+    }
+
+    /// Copies `code` instruction by instruction, mapping the constant pool
+    /// index of each and zeroing switch padding.
+    fn bytecode(&mut self, code: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        for x in raw_instructions(code)? {
+            let start = out.len();
+            out.extend_from_slice(x.bytes);
+            match (x.format, x.constant_index()) {
+                (OperandFormat::ConstantIndexByte, Some(index)) => {
+                    let index = (self.map)(index, true)?;
+                    out[start + 1] = u8::try_from(index).map_err(|_| invalid_index(index))?;
+                }
+                (_, Some(index)) => {
+                    let index = (self.map)(index, false)?;
+                    out[start + 1..start + 3].copy_from_slice(&index.to_be_bytes());
+                }
+                (OperandFormat::Tableswitch | OperandFormat::Lookupswitch, None) => {
+                    let padding = 3 - x.pc as usize % 4;
+                    out[start + 1..start + 1 + padding].fill(0);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn verification_type(&mut self, r: &mut Reader, out: &mut Vec<u8>) -> Result<()> {
+        let tag = r.u8()?;
+        out.push(tag);
+        match tag {
+            0..=6 => Ok(()),
+            7 => self.index(r, out),
+            8 => self.copy(r, out, 2),
+            _ => Err(Error::WriteError(format!(
+                "unknown verification type {}",
+                tag
+            ))),
+        }
+    }
+
+    fn stack_map_table(&mut self, r: &mut Reader, out: &mut Vec<u8>) -> Result<()> {
+        self.table(r, out, |w, r, out| {
+            let frame_type = r.u8()?;
+            out.push(frame_type);
+            match frame_type {
+                0..=63 => Ok(()),
+                64..=127 => w.verification_type(r, out),
+                247 => {
+                    w.copy(r, out, 2)?;
+                    w.verification_type(r, out)
+                }
+                248..=251 => w.copy(r, out, 2),
+                252..=254 => {
+                    w.copy(r, out, 2)?;
+                    (251..frame_type).try_for_each(|_| w.verification_type(r, out))
+                }
+                255 => {
+                    w.copy(r, out, 2)?;
+                    w.table(r, out, |w, r, out| w.verification_type(r, out))?;
+                    w.table(r, out, |w, r, out| w.verification_type(r, out))
+                }
+                _ => Err(Error::WriteError(format!(
+                    "unknown stack map frame type {}",
+                    frame_type
+                ))),
+            }
+        })
+    }
+
+    fn annotation(&mut self, r: &mut Reader, out: &mut Vec<u8>) -> Result<()> {
+        self.index(r, out)?;
+        self.table(r, out, |w, r, out| {
+            w.index(r, out)?;
+            w.element_value(r, out)
+        })
+    }
+
+    fn element_value(&mut self, r: &mut Reader, out: &mut Vec<u8>) -> Result<()> {
+        let tag = r.u8()?;
+        out.push(tag);
+        match tag {
+            b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' | b'c' => {
+                self.index(r, out)
+            }
+            b'e' => {
+                self.index(r, out)?;
+                self.index(r, out)
+            }
+            b'@' => self.annotation(r, out),
+            b'[' => self.table(r, out, |w, r, out| w.element_value(r, out)),
+            _ => Err(Error::WriteError(format!(
+                "unknown element value tag {}",
+                tag
+            ))),
+        }
+    }
+
+    fn type_annotation(&mut self, r: &mut Reader, out: &mut Vec<u8>) -> Result<()> {
+        let target_type = r.u8()?;
+        out.push(target_type);
+        match target_type {
+            0x00 | 0x01 | 0x16 => self.copy(r, out, 1)?,
+            0x10 | 0x17 | 0x42..=0x46 => self.copy(r, out, 2)?,
+            0x11 | 0x12 => self.copy(r, out, 2)?,
+            0x13..=0x15 => {}
+            0x40 | 0x41 => self.table(r, out, |w, r, out| w.copy(r, out, 6))?,
+            0x47..=0x4b => self.copy(r, out, 3)?,
+            _ => {
+                return Err(Error::WriteError(format!(
+                    "unknown type annotation target {:#04x}",
+                    target_type
+                )))
+            }
+        }
+        let path_length = r.u8()?;
+        out.push(path_length);
+        self.copy(r, out, path_length as usize * 2)?;
+        self.annotation(r, out)
+    }
+
+    fn module(&mut self, r: &mut Reader, out: &mut Vec<u8>) -> Result<()> {
+        self.index(r, out)?;
+        self.copy(r, out, 2)?;
+        self.optional_index(r, out)?;
+        // requires
+        self.table(r, out, |w, r, out| {
+            w.index(r, out)?;
+            w.copy(r, out, 2)?;
+            w.optional_index(r, out)
+        })?;
+        // exports and opens
+        for _ in 0..2 {
+            self.table(r, out, |w, r, out| {
+                w.index(r, out)?;
+                w.copy(r, out, 2)?;
+                w.table(r, out, |w, r, out| w.index(r, out))
+            })?;
+        }
+        // uses
+        self.table(r, out, |w, r, out| w.index(r, out))?;
+        // provides
+        self.table(r, out, |w, r, out| {
+            w.index(r, out)?;
+            w.table(r, out, |w, r, out| w.index(r, out))
+        })
+    }
+
+    /// Everything after `constant_pool`.
+    pub(crate) fn class(&mut self, class_file: &ClassFile) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        write_u16(&mut out, class_file.access_flags.bits())?;
+        write_u16(&mut out, (self.map)(class_file.this_class.0, false)?)?;
+        match class_file.super_class.0 {
+            0 => write_u16(&mut out, 0)?,
+            x => write_u16(&mut out, (self.map)(x, false)?)?,
+        }
+        write_u16(&mut out, count(&class_file.interfaces, "interfaces")?)?;
+        for x in &class_file.interfaces {
+            write_u16(&mut out, (self.map)(x.0, false)?)?;
+        }
+        write_u16(&mut out, count(&class_file.fields, "fields")?)?;
+        for x in &class_file.fields {
+            write_u16(&mut out, x.access_flags.bits())?;
+            write_u16(&mut out, (self.map)(x.name_index.0, false)?)?;
+            write_u16(&mut out, (self.map)(x.descriptor_index.0, false)?)?;
+            self.attributes(
+                x.attributes
+                    .iter()
+                    .map(|x| (x.name_index, &x.data[..]))
+                    .collect(),
+                &mut out,
+            )?;
+        }
+        write_u16(&mut out, count(&class_file.methods, "methods")?)?;
+        for x in &class_file.methods {
+            write_u16(&mut out, x.access_flags.bits())?;
+            write_u16(&mut out, (self.map)(x.name_index.0, false)?)?;
+            write_u16(&mut out, (self.map)(x.descriptor_index.0, false)?)?;
+            self.attributes(
+                x.attributes
+                    .iter()
+                    .map(|x| (x.name_index, &x.data[..]))
+                    .collect(),
+                &mut out,
+            )?;
+        }
+        let attributes = class_file
+            .attributes
+            .iter()
+            .map(|x| (x.name_index, &x.data[..]))
+            .collect();
+        self.attributes(attributes, &mut out)?;
+        Ok(out)
+    }
+}
+
+impl ClassFile {
+    /// Serializes the class in a canonical form, so that classes differing
+    /// only in encoding choices come out identical.
+    ///
+    /// The constant pool holds only the entries in use, each once, in the
+    /// order they are first referenced, after the constants loaded by `ldc`.
+    /// Attributes are sorted by name, switch padding is zeroed, and bytes
+    /// trailing an attribute's structure are dropped. Members keep their
+    /// order, since it is visible through reflection.
+    ///
+    /// Attributes the crate does not know are copied as they are, sorted by
+    /// name like the rest, so any pool indices inside them are left pointing
+    /// at the old pool. Fails on constants from a
+    /// [`crate::options::ConstantPoolHook`], whose pool references cannot be
+    /// found.
+    pub fn to_canonical_bytes(&self) -> Result<Vec<u8>> {
+        let cpool = &self.constant_pool;
+        let mut layout = Layout {
+            cpool,
+            keys: HashMap::new(),
+            narrow: Vec::new(),
+            rest: Vec::new(),
+            seen: HashSet::new(),
+            indices: HashMap::new(),
+        };
+        Walker {
+            cpool,
+            map: |index, narrow| layout.visit(index, narrow),
+            unknown: Vec::new(),
+        }
+        .class(self)?;
+
+        let narrow = layout.narrow.iter().cloned().collect::<HashSet<_>>();
+        let order = layout
+            .narrow
+            .iter()
+            .chain(layout.rest.iter().filter(|x| !narrow.contains(*x)))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut new_indices = HashMap::new();
+        let mut next = 1usize;
+        for key in &order {
+            new_indices.insert(key.clone(), next as u16);
+            next += match item(cpool, layout.indices[key])? {
+                ConstantPoolItem::Long { .. } | ConstantPoolItem::Double { .. } => 2,
+                _ => 1,
+            };
+        }
+        // Every index in use got a key during the first pass.
+        let map = |index: u16| -> Result<u16> {
+            let key = layout
+                .keys
+                .get(&index)
+                .ok_or_else(|| invalid_index(index))?;
+            new_indices
+                .get(key)
+                .copied()
+                .ok_or_else(|| invalid_index(index))
+        };
+
+        let mut out = b"\xca\xfe\xba\xbe".to_vec();
+        write_u16(&mut out, self._minor_version)?;
+        write_u16(&mut out, self._major_version)?;
+        write_u16(&mut out, next as u16)?;
+        for key in &order {
+            let old = item(cpool, layout.indices[key])?;
+            let references = references(old)?
+                .into_iter()
+                .map(map)
+                .collect::<Result<Vec<_>>>()?;
+            with_references(old, &references).write_to(&mut out)?;
+        }
+        let body = Walker {
+            cpool,
+            map: |index, _| map(index),
+            unknown: Vec::new(),
+        }
+        .class(self)?;
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        asm::{Label, Op},
+        builder::{ClassFileBuilder, MethodBuilder},
+        field::TypeDescriptor,
+        method::MethodDescriptor,
+        ClassFile, MethodAccessFlags,
+    };
+
+    #[test]
+    fn lookupswitch_round_trips() {
+        let descriptor = MethodDescriptor::new(vec![TypeDescriptor::Int], Some(TypeDescriptor::Int));
+        let (one, ten, default, switch) = (Label(0), Label(1), Label(2), Label(3));
+        // The switch ends the code, so reading past its pairs runs off the end.
+        let method = MethodBuilder::new(MethodAccessFlags::STATIC, "pick", &descriptor).with_code(
+            1,
+            1,
+            vec![
+                Op::Branch { opcode: 0xa7, target: switch },
+                Op::Label(one),
+                Op::Simple(0x04),
+                Op::Simple(0xac),
+                Op::Label(ten),
+                Op::Simple(0x05),
+                Op::Simple(0xac),
+                Op::Label(default),
+                Op::Simple(0x03),
+                Op::Simple(0xac),
+                Op::Label(switch),
+                Op::Local { opcode: 0x15, index: 0 },
+                Op::Lookupswitch {
+                    default,
+                    pairs: vec![(1, one), (10, ten)],
+                },
+            ],
+        );
+        let bytes = ClassFileBuilder::new("Pick").with_method(method).build().unwrap();
+        let class_file = ClassFile::parse(&bytes[..]).unwrap();
+        let canonical = class_file.to_canonical_bytes().unwrap();
+        let reparsed = ClassFile::parse(&canonical[..]).unwrap();
+
+        let instructions = |class_file: &ClassFile| {
+            let code = class_file.methods()[0].code().unwrap().unwrap();
+            format!("{:?}", code.instructions_with_pcs().unwrap())
+        };
+        assert_eq!(instructions(&reparsed), instructions(&class_file));
+        assert_eq!(reparsed.to_canonical_bytes().unwrap(), canonical);
+    }
+
+    #[test]
+    fn unknown_attributes_are_copied() {
+        let bytes = ClassFileBuilder::new("Tagged")
+            .with_attribute("Zeta", vec![1, 2, 3])
+            .with_attribute("Alpha", vec![4])
+            .build()
+            .unwrap();
+        let class_file = ClassFile::parse(&bytes[..]).unwrap();
+        let canonical = class_file.to_canonical_bytes().unwrap();
+        let reparsed = ClassFile::parse(&canonical[..]).unwrap();
+
+        let attributes = reparsed
+            .raw_attributes()
+            .iter()
+            .map(|x| (x.name.to_string(), x.data.to_vec()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            attributes,
+            [("Alpha".to_string(), vec![4]), ("Zeta".to_string(), vec![1, 2, 3])]
+        );
+    }
+}
@@ -0,0 +1,444 @@
+//! Control-flow graphs of method bodies, see [`ControlFlowGraph`].
+
+use crate::{
+    attributes::{Code, ExceptionHandler},
+    instruction::{BranchTarget, Instruction, InstructionAt},
+    Result,
+};
+
+/// How control passes along an [`Edge`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind<'a> {
+    /// Execution continues with the next instruction, including after a
+    /// conditional branch that is not taken and after a `jsr` returns.
+    Fallthrough,
+    /// A `goto`, a taken conditional branch, or a `jsr` into its subroutine.
+    Branch,
+    /// A switch case matching this key.
+    SwitchCase(i32),
+    /// The default of a switch.
+    SwitchDefault,
+    /// An exception thrown inside a handler's range, caught if it is a
+    /// `catch_type`. `None` catches everything.
+    Exception { catch_type: Option<&'a str> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge<'a> {
+    /// The position of the source block in [`ControlFlowGraph::blocks`].
+    pub from: usize,
+    /// The position of the target block in [`ControlFlowGraph::blocks`].
+    pub to: usize,
+    pub kind: EdgeKind<'a>,
+}
+
+/// A run of instructions entered only at its first and left only after its
+/// last. Blocks also split at the bounds of exception handler ranges, so each
+/// is either wholly covered by a handler or not at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// The position of the first instruction in [`ControlFlowGraph::instructions`].
+    pub start: usize,
+    /// The position after the last instruction.
+    pub end: usize,
+    pub start_pc: u32,
+    /// The pc after the last instruction.
+    pub end_pc: u32,
+}
+
+/// The basic blocks of a method body and the edges between them, see
+/// [`Code::control_flow_graph`]. The entry block is the first.
+#[derive(Debug)]
+pub struct ControlFlowGraph<'a> {
+    instructions: Vec<InstructionAt<'a>>,
+    blocks: Vec<BasicBlock>,
+    edges: Vec<Edge<'a>>,
+}
+
+impl<'a> ControlFlowGraph<'a> {
+    /// Every instruction in code order.
+    pub fn instructions(&self) -> &[InstructionAt<'a>] {
+        &self.instructions
+    }
+
+    /// The blocks in code order.
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    /// Every edge, grouped by source block.
+    pub fn edges(&self) -> &[Edge<'a>] {
+        &self.edges
+    }
+
+    /// The instructions of block `block`.
+    pub fn block_instructions(&self, block: usize) -> &[InstructionAt<'a>] {
+        let block = &self.blocks[block];
+        &self.instructions[block.start..block.end]
+    }
+
+    /// The block containing the instruction at or spanning `pc`.
+    pub fn block_at(&self, pc: u32) -> Option<usize> {
+        if pc >= self.blocks.last()?.end_pc {
+            return None;
+        }
+        Some(self.blocks.partition_point(|x| x.end_pc <= pc))
+    }
+
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge<'a>> {
+        self.edges.iter().filter(move |x| x.from == block)
+    }
+
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = &Edge<'a>> {
+        self.edges.iter().filter(move |x| x.to == block)
+    }
+
+    /// The blocks reachable from the entry, in reverse postorder.
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let mut successors = vec![Vec::new(); self.blocks.len()];
+        for edge in &self.edges {
+            successors[edge.from].push(edge.to);
+        }
+        let mut order = Vec::with_capacity(self.blocks.len());
+        let mut visited = vec![false; self.blocks.len()];
+        // Each entry is a block and how many of its successors were visited.
+        let mut stack = Vec::new();
+        if !self.blocks.is_empty() {
+            visited[0] = true;
+            stack.push((0, 0));
+        }
+        while let Some((block, next)) = stack.last_mut() {
+            match successors[*block].get(*next) {
+                Some(&successor) => {
+                    *next += 1;
+                    if !visited[successor] {
+                        visited[successor] = true;
+                        stack.push((successor, 0));
+                    }
+                }
+                None => {
+                    order.push(*block);
+                    stack.pop();
+                }
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// The dominator tree, following exception edges as well as normal ones.
+    pub fn dominators(&self) -> Dominators {
+        let order = self.reverse_postorder();
+        let mut position = vec![usize::MAX; self.blocks.len()];
+        for (i, &block) in order.iter().enumerate() {
+            position[block] = i;
+        }
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); self.blocks.len()];
+        for edge in &self.edges {
+            predecessors[edge.to].push(edge.from);
+        }
+
+        // Cooper, Harvey and Kennedy's "A Simple, Fast Dominance Algorithm",
+        // with the entry as its own dominator until the end.
+        let mut idom: Vec<Option<usize>> = vec![None; self.blocks.len()];
+        if let Some(&entry) = order.first() {
+            idom[entry] = Some(entry);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut new: Option<usize> = None;
+                for &predecessor in &predecessors[block] {
+                    if idom[predecessor].is_none() {
+                        continue;
+                    }
+                    new = Some(match new {
+                        None => predecessor,
+                        Some(mut a) => {
+                            let mut b = predecessor;
+                            while a != b {
+                                while position[a] > position[b] {
+                                    a = idom[a].expect("processed blocks have a dominator");
+                                }
+                                while position[b] > position[a] {
+                                    b = idom[b].expect("processed blocks have a dominator");
+                                }
+                            }
+                            a
+                        }
+                    });
+                }
+                if new.is_some() && idom[block] != new {
+                    idom[block] = new;
+                    changed = true;
+                }
+            }
+        }
+        if let Some(&entry) = order.first() {
+            idom[entry] = None;
+        }
+        Dominators {
+            idom,
+            reachable: position.iter().map(|&x| x != usize::MAX).collect(),
+        }
+    }
+
+    /// The dominance frontier of every block: the blocks it does not strictly
+    /// dominate but has an edge into, directly or from a block it dominates.
+    /// These are where definitions in the block meet others, so where SSA
+    /// form needs phis. Each frontier is sorted.
+    pub fn dominance_frontiers(&self, dominators: &Dominators) -> Vec<Vec<usize>> {
+        let mut frontiers = vec![Vec::new(); self.blocks.len()];
+        for block in 0..self.blocks.len() {
+            if !dominators.is_reachable(block) {
+                continue;
+            }
+            // The entry is also entered from outside, as if from a root
+            // above it that dominates everything.
+            let idom = dominators.immediate_dominator(block);
+            let mut predecessors: Vec<usize> = self
+                .predecessors(block)
+                .map(|x| x.from)
+                .filter(|&x| dominators.is_reachable(x))
+                .collect();
+            predecessors.sort_unstable();
+            predecessors.dedup();
+            if predecessors.len() + usize::from(idom.is_none()) < 2 {
+                continue;
+            }
+            for predecessor in predecessors {
+                let mut runner = Some(predecessor);
+                while let Some(x) = runner.filter(|&x| Some(x) != idom) {
+                    frontiers[x].push(block);
+                    runner = dominators.immediate_dominator(x);
+                }
+            }
+        }
+        for x in &mut frontiers {
+            x.sort_unstable();
+            x.dedup();
+        }
+        frontiers
+    }
+
+    /// The natural loops, one per header with the bodies of all its back
+    /// edges merged, ordered by header. A back edge is one whose target
+    /// dominates its source. Loops formed otherwise, by jumping into the
+    /// middle of a cycle, are not natural and not found.
+    pub fn natural_loops(&self) -> Vec<Loop<'a>> {
+        let dominators = self.dominators();
+        let mut loops: Vec<Loop<'a>> = Vec::new();
+        for edge in &self.edges {
+            if !dominators.dominates(edge.to, edge.from) {
+                continue;
+            }
+            match loops.iter_mut().find(|x| x.header == edge.to) {
+                Some(x) => x.back_edges.push(*edge),
+                None => loops.push(Loop {
+                    header: edge.to,
+                    body: Vec::new(),
+                    back_edges: vec![*edge],
+                }),
+            }
+        }
+        for x in &mut loops {
+            let mut body = vec![false; self.blocks.len()];
+            body[x.header] = true;
+            let mut stack: Vec<usize> = x.back_edges.iter().map(|x| x.from).collect();
+            while let Some(block) = stack.pop() {
+                if body[block] || !dominators.is_reachable(block) {
+                    continue;
+                }
+                body[block] = true;
+                stack.extend(self.predecessors(block).map(|x| x.from));
+            }
+            x.body = (0..self.blocks.len()).filter(|&x| body[x]).collect();
+        }
+        loops.sort_by_key(|x| x.header);
+        loops
+    }
+}
+
+/// The immediate dominators of a [`ControlFlowGraph`]'s blocks, see
+/// [`ControlFlowGraph::dominators`]. Block `a` dominates `b` if every path
+/// from the entry to `b` passes through `a`.
+#[derive(Debug, Clone)]
+pub struct Dominators {
+    idom: Vec<Option<usize>>,
+    reachable: Vec<bool>,
+}
+
+impl Dominators {
+    /// The closest block dominating `block` other than itself, its parent in
+    /// the dominator tree. `None` for the entry and unreachable blocks.
+    pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+        self.idom[block]
+    }
+
+    /// The blocks `block` immediately dominates, its children in the tree.
+    pub fn children(&self, block: usize) -> Vec<usize> {
+        (0..self.idom.len()).filter(|&x| self.idom[x] == Some(block)).collect()
+    }
+
+    /// Whether `a` dominates `b`. Every reachable block dominates itself, and
+    /// unreachable blocks neither dominate nor are dominated.
+    pub fn dominates(&self, a: usize, b: usize) -> bool {
+        if !self.reachable[a] || !self.reachable[b] {
+            return false;
+        }
+        let mut block = Some(b);
+        while let Some(x) = block {
+            if x == a {
+                return true;
+            }
+            block = self.idom[x];
+        }
+        false
+    }
+
+    /// Whether `block` can be reached from the entry.
+    pub fn is_reachable(&self, block: usize) -> bool {
+        self.reachable[block]
+    }
+}
+
+/// A natural loop, see [`ControlFlowGraph::natural_loops`].
+#[derive(Debug, Clone)]
+pub struct Loop<'a> {
+    /// The block every iteration enters through, which dominates the body.
+    pub header: usize,
+    /// The blocks of the loop, the header included, in code order.
+    pub body: Vec<usize>,
+    /// The edges jumping back to the header.
+    pub back_edges: Vec<Edge<'a>>,
+}
+
+impl Loop<'_> {
+    pub fn contains(&self, block: usize) -> bool {
+        self.body.binary_search(&block).is_ok()
+    }
+}
+
+/// A basic block with its instructions, see [`Code::basic_blocks`].
+#[derive(Debug)]
+pub struct InstructionBlock<'a> {
+    pub start_pc: u32,
+    /// The pc after the last instruction.
+    pub end_pc: u32,
+    pub instructions: Vec<InstructionAt<'a>>,
+}
+
+/// Splits `instructions` before each branch target and handler start, after
+/// each branch and instruction that does not fall through, and at the bounds
+/// of handler ranges.
+fn split(instructions: &[InstructionAt], targets: &[Vec<BranchTarget>], handlers: &[ExceptionHandler]) -> Vec<BasicBlock> {
+    let mut leaders = vec![false; instructions.len() + 1];
+    leaders[0] = true;
+    for (i, x) in instructions.iter().enumerate() {
+        if !targets[i].is_empty() || !x.instruction.falls_through() {
+            leaders[i + 1] = true;
+        }
+        for target in &targets[i] {
+            leaders[target.index] = true;
+        }
+    }
+    for handler in handlers {
+        leaders[handler.start.index] = true;
+        leaders[handler.end.index] = true;
+        leaders[handler.handler.index] = true;
+    }
+
+    let mut blocks: Vec<BasicBlock> = Vec::new();
+    for (i, x) in instructions.iter().enumerate() {
+        if leaders[i] {
+            blocks.push(BasicBlock {
+                start: i,
+                end: i,
+                start_pc: x.pc,
+                end_pc: x.pc,
+            });
+        }
+        let block = blocks.last_mut().expect("the first instruction leads a block");
+        block.end = i + 1;
+        block.end_pc = x.pc + x.length;
+    }
+    blocks
+}
+
+impl<'a> Code<'a> {
+    /// The code split into basic blocks, in code order, as in
+    /// [`Self::control_flow_graph`] but without working out the edges.
+    pub fn basic_blocks(&self) -> Result<Vec<InstructionBlock<'a>>> {
+        let instructions = self.instructions_at()?;
+        let blocks = split(&instructions, &self.branch_targets()?, &self.exception_handlers()?);
+        let mut instructions = instructions.into_iter();
+        Ok(blocks
+            .iter()
+            .map(|x| InstructionBlock {
+                start_pc: x.start_pc,
+                end_pc: x.end_pc,
+                instructions: instructions.by_ref().take(x.end - x.start).collect(),
+            })
+            .collect())
+    }
+
+    /// Splits the code into basic blocks and links them by fallthrough,
+    /// branches, switch cases and exception handlers. A `ret` has no edges, as
+    /// where it returns to is not known without data flow analysis.
+    pub fn control_flow_graph(&self) -> Result<ControlFlowGraph<'a>> {
+        let instructions = self.instructions_at()?;
+        let targets = self.branch_targets()?;
+        let handlers = self.exception_handlers()?;
+
+        let blocks = split(&instructions, &targets, &handlers);
+        let mut block_of = Vec::with_capacity(instructions.len());
+        for (i, block) in blocks.iter().enumerate() {
+            block_of.resize(block.end, i);
+        }
+
+        let mut edges = Vec::new();
+        for (from, block) in blocks.iter().enumerate() {
+            let last = block.end - 1;
+            let mut edge = |to: usize, kind| edges.push(Edge { from, to: block_of[to], kind });
+            match &instructions[last].instruction {
+                Instruction::Tableswitch { low, .. } => {
+                    edge(targets[last][0].index, EdgeKind::SwitchDefault);
+                    for (i, target) in targets[last][1..].iter().enumerate() {
+                        edge(target.index, EdgeKind::SwitchCase(low.wrapping_add(i as i32)));
+                    }
+                }
+                Instruction::Lookupswitch { pairs, .. } => {
+                    edge(targets[last][0].index, EdgeKind::SwitchDefault);
+                    for ((key, _), target) in pairs.iter().zip(&targets[last][1..]) {
+                        edge(target.index, EdgeKind::SwitchCase(*key));
+                    }
+                }
+                _ => {
+                    for target in &targets[last] {
+                        edge(target.index, EdgeKind::Branch);
+                    }
+                }
+            }
+            if instructions[last].instruction.falls_through() && block.end < instructions.len() {
+                edge(block.end, EdgeKind::Fallthrough);
+            }
+            for handler in &handlers {
+                if handler.start.index <= block.start && block.start < handler.end.index {
+                    edge(
+                        handler.handler.index,
+                        EdgeKind::Exception {
+                            catch_type: handler.catch_type,
+                        },
+                    );
+                }
+            }
+        }
+
+        Ok(ControlFlowGraph {
+            instructions,
+            blocks,
+            edges,
+        })
+    }
+}
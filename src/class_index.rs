@@ -0,0 +1,354 @@
+//! A searchable index of the classes of a class path, their members and
+//! what they reference, built by [`ClassIndexer`].
+//!
+//! With the `rayon` feature, class files are read and parsed on rayon's
+//! thread pool; otherwise one after another.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    archive::Archive,
+    code_index::{class_symbol, field_symbol, method_symbol, SymbolKind},
+    dependencies::element_class,
+    raw::ConstantPoolItem,
+    ClassFile, Error, ParseOptions, Result,
+};
+
+/// Called with the number of class files done and the total, after each.
+/// With the `rayon` feature it is called from the threads doing the work.
+pub type ProgressCallback = dyn Fn(usize, usize) + Send + Sync;
+
+/// A field or method of an indexed class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberSummary {
+    pub name: String,
+    pub descriptor: String,
+    /// The access flags as stored in the class file.
+    pub access_flags: u16,
+}
+
+/// What the index keeps of a class.
+#[derive(Debug, Clone)]
+pub struct ClassSummary {
+    /// Where the class came from: a path, or the name of an archive entry.
+    pub source: String,
+    pub name: String,
+    pub super_class: Option<String>,
+    pub interfaces: Vec<String>,
+    /// The access flags as stored in the class file.
+    pub access_flags: u16,
+    pub fields: Vec<MemberSummary>,
+    pub methods: Vec<MemberSummary>,
+}
+
+/// A class file that could not be read or parsed.
+#[derive(Debug)]
+pub struct IndexError {
+    pub source: String,
+    pub error: Error,
+}
+
+/// Classes by name, with their members by name and the classes referencing
+/// each class, field and method.
+///
+/// References are those of the constant pool, keyed by the symbols of
+/// [`code_index`](crate::code_index), such as `java/util/List#add(Ljava/lang/Object;)Z.`.
+/// A class's reference to itself is left out, but not references to its own
+/// members.
+#[derive(Debug, Default)]
+pub struct ClassIndex {
+    classes: BTreeMap<String, ClassSummary>,
+    /// Classes declaring a member by the member's name.
+    members: HashMap<String, BTreeSet<(String, SymbolKind)>>,
+    references: HashMap<String, BTreeSet<String>>,
+    errors: Vec<IndexError>,
+}
+
+impl ClassIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a class and the symbols it references, unless a class of the
+    /// same name is already there, as the first on a class path wins. Returns
+    /// whether it was added.
+    fn insert(&mut self, summary: ClassSummary, references: BTreeSet<String>) -> bool {
+        if self.classes.contains_key(&summary.name) {
+            return false;
+        }
+        for (kind, members) in [(SymbolKind::Field, &summary.fields), (SymbolKind::Method, &summary.methods)] {
+            for x in members {
+                self.members
+                    .entry(x.name.clone())
+                    .or_default()
+                    .insert((summary.name.clone(), kind));
+            }
+        }
+        for symbol in references {
+            self.references.entry(symbol).or_default().insert(summary.name.clone());
+        }
+        self.classes.insert(summary.name.clone(), summary);
+        true
+    }
+
+    /// Adds the classes and errors of `other`, keeping this index's class
+    /// where both have one.
+    pub fn merge(&mut self, other: ClassIndex) {
+        let mut references = HashMap::<String, BTreeSet<String>>::new();
+        for (symbol, classes) in other.references {
+            for class in classes {
+                references.entry(class).or_default().insert(symbol.clone());
+            }
+        }
+        for (name, summary) in other.classes {
+            self.insert(summary, references.remove(&name).unwrap_or_default());
+        }
+        self.errors.extend(other.errors);
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    pub fn class(&self, name: &str) -> Option<&ClassSummary> {
+        self.classes.get(name)
+    }
+
+    /// Every class, sorted by name.
+    pub fn classes(&self) -> impl Iterator<Item = &ClassSummary> {
+        self.classes.values()
+    }
+
+    /// The classes whose name contains `query`, sorted by name.
+    pub fn search_classes<'a>(&'a self, query: &'a str) -> impl Iterator<Item = &'a ClassSummary> {
+        self.classes.values().filter(move |x| x.name.contains(query))
+    }
+
+    /// The fields and methods called `name`, with their classes, sorted by
+    /// class.
+    pub fn members_named(&self, name: &str) -> Vec<(&ClassSummary, SymbolKind, &MemberSummary)> {
+        let mut res = Vec::new();
+        for (class, kind) in self.members.get(name).into_iter().flatten() {
+            let summary = &self.classes[class];
+            let members = match kind {
+                SymbolKind::Field => &summary.fields,
+                _ => &summary.methods,
+            };
+            res.extend(members.iter().filter(|x| x.name == name).map(|x| (summary, *kind, x)));
+        }
+        res
+    }
+
+    /// The classes referencing `symbol`, sorted.
+    pub fn references(&self, symbol: &str) -> Vec<&str> {
+        self.references
+            .get(symbol)
+            .map_or_else(Vec::new, |x| x.iter().map(String::as_str).collect())
+    }
+
+    /// The classes referencing class `name`, including through arrays of it.
+    pub fn class_references(&self, name: &str) -> Vec<&str> {
+        self.references(&class_symbol(name))
+    }
+
+    /// The classes referencing field `name` of `class`, as named through
+    /// `class` rather than a subclass.
+    pub fn field_references(&self, class: &str, name: &str) -> Vec<&str> {
+        self.references(&field_symbol(class, name))
+    }
+
+    /// The classes referencing a method of `class`, as named through `class`
+    /// rather than a subclass.
+    pub fn method_references(&self, class: &str, name: &str, descriptor: &str) -> Vec<&str> {
+        self.references(&method_symbol(class, name, descriptor))
+    }
+
+    /// The class files that could not be read or parsed, in input order.
+    pub fn errors(&self) -> &[IndexError] {
+        &self.errors
+    }
+}
+
+/// Builds a [`ClassIndex`] from class files, in parallel with the `rayon`
+/// feature.
+#[derive(Default, Clone)]
+pub struct ClassIndexer {
+    options: ParseOptions,
+    progress: Option<Arc<ProgressCallback>>,
+}
+
+impl std::fmt::Debug for ClassIndexer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClassIndexer")
+            .field("options", &self.options)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// The summary and references of a class file.
+fn summarize(source: String, data: Vec<u8>, options: &ParseOptions) -> Result<(ClassSummary, BTreeSet<String>)> {
+    let class_file = ClassFile::parse_with_options(data, options)?;
+    let name = class_file.this_class()?;
+    let mut references = BTreeSet::new();
+    for (_, item) in class_file.constant_pool() {
+        match item {
+            ConstantPoolItem::Class { name_index } => {
+                if let Some(x) = element_class(name_index.get_as_string(&class_file)?).filter(|x| *x != name) {
+                    references.insert(class_symbol(x));
+                }
+            }
+            ConstantPoolItem::Fieldref {
+                class_index,
+                name_and_type_index,
+            } => {
+                references.insert(field_symbol(
+                    class_index.get_as_string(&class_file)?,
+                    name_and_type_index.get_name(&class_file)?,
+                ));
+            }
+            ConstantPoolItem::Methodref {
+                class_index,
+                name_and_type_index,
+            }
+            | ConstantPoolItem::InterfaceMethodref {
+                class_index,
+                name_and_type_index,
+            } => {
+                references.insert(method_symbol(
+                    class_index.get_as_string(&class_file)?,
+                    name_and_type_index.get_name(&class_file)?,
+                    name_and_type_index.get_descriptor(&class_file)?,
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    let mut fields = Vec::new();
+    for x in class_file.fields() {
+        fields.push(MemberSummary {
+            name: x.identifier()?.to_string(),
+            descriptor: x.field_inner.descriptor_index.get_as_string(&class_file)?.to_string(),
+            access_flags: x.field_inner.access_flags.bits(),
+        });
+    }
+    let mut methods = Vec::new();
+    for x in class_file.methods() {
+        methods.push(MemberSummary {
+            name: x.identifier()?.to_string(),
+            descriptor: x.method_inner.descriptor_index.get_as_string(&class_file)?.to_string(),
+            access_flags: x.method_inner.access_flags.bits(),
+        });
+    }
+    let summary = ClassSummary {
+        source,
+        name: name.to_string(),
+        super_class: match class_file.super_class.0 {
+            0 => None,
+            _ => Some(class_file.super_class()?.to_string()),
+        },
+        interfaces: class_file.interfaces()?.into_iter().map(str::to_string).collect(),
+        access_flags: class_file.access_flags.bits(),
+        fields,
+        methods,
+    };
+    Ok((summary, references))
+}
+
+/// The `.class` files under `dir`, recursively, sorted.
+pub(crate) fn class_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|x| x.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            class_files(&path, out)?;
+        } else if path.extension().is_some_and(|x| x == "class") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+impl ClassIndexer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses classes with `options` rather than the default.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Reports progress to `callback`, see [`ProgressCallback`].
+    pub fn with_progress(mut self, callback: impl Fn(usize, usize) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Reads, parses and summarizes each of `items` with `load`, giving
+    /// where it came from and its bytes, then indexes them in input order.
+    fn run<T: Send>(&self, items: Vec<T>, load: impl Fn(T) -> (String, Result<Vec<u8>>) + Sync) -> ClassIndex {
+        let total = items.len();
+        let done = AtomicUsize::new(0);
+        let work = |item: T| {
+            let (source, data) = load(item);
+            let res = data.and_then(|x| summarize(source.clone(), x, &self.options));
+            if let Some(progress) = &self.progress {
+                progress(done.fetch_add(1, Ordering::Relaxed) + 1, total);
+            }
+            res.map_err(|error| IndexError { source, error })
+        };
+        #[cfg(feature = "rayon")]
+        let results: Vec<_> = {
+            use rayon::prelude::*;
+            items.into_par_iter().map(work).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let results: Vec<_> = items.into_iter().map(work).collect();
+
+        let mut index = ClassIndex::new();
+        for x in results {
+            match x {
+                Ok((summary, references)) => {
+                    index.insert(summary, references);
+                }
+                Err(e) => index.errors.push(e),
+            }
+        }
+        index
+    }
+
+    /// Indexes class files already in memory, each with where it came from.
+    pub fn index_bytes(&self, classes: Vec<(String, Vec<u8>)>) -> ClassIndex {
+        self.run(classes, |(source, data)| (source, Ok(data)))
+    }
+
+    /// Indexes the classes of a JAR or JMOD, decompressing them in parallel
+    /// too, see [`Archive::classes`].
+    pub fn index_archive(&self, archive: &Archive) -> ClassIndex {
+        let entries = archive.classes().map(|(_, x)| x).collect::<Vec<_>>();
+        self.run(entries, |x| (x.name.clone(), archive.read_entry(x)))
+    }
+
+    /// Indexes the `.class` files under `root`, recursively. Fails only if a
+    /// directory cannot be listed.
+    pub fn index_directory(&self, root: impl AsRef<Path>) -> Result<ClassIndex> {
+        let mut paths = Vec::new();
+        class_files(root.as_ref(), &mut paths)?;
+        Ok(self.run(paths, |x| (x.display().to_string(), std::fs::read(&x).map_err(Error::from))))
+    }
+}
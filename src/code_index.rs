@@ -0,0 +1,302 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    dependencies::{element_class, json_string},
+    field::TypeDescriptor,
+    instruction::{Instruction, MaybeInterfaceMethodRef},
+    method::MethodDescriptor,
+    span::Span,
+    ClassFile, Result,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SymbolKind {
+    Class,
+    Field,
+    Method,
+}
+
+impl SymbolKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Class => "class",
+            Self::Field => "field",
+            Self::Method => "method",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolRole {
+    Definition,
+    Reference,
+}
+
+impl SymbolRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Definition => "definition",
+            Self::Reference => "reference",
+        }
+    }
+}
+
+/// A symbol mentioned at a byte range of a class file.
+#[derive(Debug, Clone)]
+pub struct Occurrence {
+    pub span: Span,
+    pub symbol: String,
+    pub role: SymbolRole,
+}
+
+/// The occurrences found in one class file.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub path: String,
+    pub occurrences: Vec<Occurrence>,
+}
+
+/// `pkg/Class#`, the symbol naming a class.
+pub fn class_symbol(class: &str) -> String {
+    format!("{}#", class)
+}
+
+/// `pkg/Class#name.`, the symbol naming a field.
+pub fn field_symbol(class: &str, name: &str) -> String {
+    format!("{}#{}.", class, name)
+}
+
+/// `pkg/Class#name(descriptor).`, the symbol naming a method overload.
+pub fn method_symbol(class: &str, name: &str, descriptor: &str) -> String {
+    format!("{}#{}{}.", class, name, descriptor)
+}
+
+fn write_type(out: &mut String, ty: &TypeDescriptor) {
+    match ty {
+        TypeDescriptor::Byte => out.push('B'),
+        TypeDescriptor::Char => out.push('C'),
+        TypeDescriptor::Double => out.push('D'),
+        TypeDescriptor::Float => out.push('F'),
+        TypeDescriptor::Int => out.push('I'),
+        TypeDescriptor::Long => out.push('J'),
+        TypeDescriptor::Short => out.push('S'),
+        TypeDescriptor::Boolean => out.push('Z'),
+        TypeDescriptor::String => out.push_str("Ljava/lang/String;"),
+        TypeDescriptor::Class => out.push_str("Ljava/lang/Class;"),
+        TypeDescriptor::Array(x) => {
+            out.push('[');
+            write_type(out, x);
+        }
+        TypeDescriptor::ClassName(x) => {
+            let _ = write!(out, "L{};", x);
+        }
+    }
+}
+
+fn method_descriptor(descriptor: &MethodDescriptor) -> String {
+    let mut out = String::from("(");
+    for ty in descriptor.parameter_types() {
+        write_type(&mut out, ty);
+    }
+    out.push(')');
+    match descriptor.return_type() {
+        Some(ty) => write_type(&mut out, ty),
+        None => out.push('V'),
+    }
+    out
+}
+
+/// Definitions and references of classes, fields and methods across a set of
+/// class files, in the spirit of LSIF and SCIP indexes.
+///
+/// Ranges are byte [`Span`]s into each class file: definitions cover the whole
+/// class or member, references cover the instruction naming the symbol.
+#[derive(Debug, Default)]
+pub struct CodeIndex {
+    documents: Vec<Document>,
+    symbols: BTreeMap<String, SymbolKind>,
+}
+
+impl CodeIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indexes `(path, class file)` pairs, the path naming the document in the output.
+    pub fn from_classes<'a>(
+        classes: impl IntoIterator<Item = (&'a str, &'a ClassFile)>,
+    ) -> Result<Self> {
+        let mut index = Self::new();
+        for (path, class) in classes {
+            index.add_class(path, class)?;
+        }
+        Ok(index)
+    }
+
+    pub fn add_class(&mut self, path: &str, class_file: &ClassFile) -> Result<()> {
+        let this_class = class_file.this_class()?;
+        let mut occurrences = Vec::new();
+        let mut add = |symbol: String, kind, span, role| {
+            self.symbols.entry(symbol.clone()).or_insert(kind);
+            occurrences.push(Occurrence { span, symbol, role });
+        };
+
+        add(
+            class_symbol(this_class),
+            SymbolKind::Class,
+            class_file.span(),
+            SymbolRole::Definition,
+        );
+        for field in class_file.fields() {
+            add(
+                field_symbol(this_class, field.identifier()?),
+                SymbolKind::Field,
+                field.span(),
+                SymbolRole::Definition,
+            );
+        }
+        for method in class_file.methods() {
+            let descriptor = method
+                .method_inner
+                .descriptor_index
+                .get_as_string(class_file)?;
+            add(
+                method_symbol(this_class, method.identifier()?, descriptor),
+                SymbolKind::Method,
+                method.span(),
+                SymbolRole::Definition,
+            );
+            let code = match method.code()? {
+                Some(x) => x,
+                None => continue,
+            };
+            let spans = code.instruction_spans()?;
+            for ((_, instruction), (_, span)) in code.instructions_with_pcs()?.iter().zip(spans) {
+                let reference = match instruction {
+                    Instruction::Getfield { field }
+                    | Instruction::Getstatic { field }
+                    | Instruction::Putfield { field }
+                    | Instruction::Putstatic { field } => {
+                        Some((field_symbol(field.class, field.name), SymbolKind::Field))
+                    }
+                    Instruction::Invokevirtual { index } => Some((
+                        method_symbol(index.class, index.name, &method_descriptor(&index.descriptor)),
+                        SymbolKind::Method,
+                    )),
+                    Instruction::Invokeinterface { index, .. } => Some((
+                        method_symbol(index.class, index.name, &method_descriptor(&index.descriptor)),
+                        SymbolKind::Method,
+                    )),
+                    Instruction::Invokespecial { index } | Instruction::Invokestatic { index } => {
+                        let (class, name, descriptor) = match index {
+                            MaybeInterfaceMethodRef::RegularMethod(x) => {
+                                (x.class, x.name, &x.descriptor)
+                            }
+                            MaybeInterfaceMethodRef::InterfaceMethod(x) => {
+                                (x.class, x.name, &x.descriptor)
+                            }
+                        };
+                        Some((
+                            method_symbol(class, name, &method_descriptor(descriptor)),
+                            SymbolKind::Method,
+                        ))
+                    }
+                    Instruction::New { class }
+                    | Instruction::Anewarray { class }
+                    | Instruction::Checkcast { class }
+                    | Instruction::Instanceof { class }
+                    | Instruction::Multianewarray { class, .. } => {
+                        element_class(class).map(|x| (class_symbol(x), SymbolKind::Class))
+                    }
+                    _ => None,
+                };
+                if let Some((symbol, kind)) = reference {
+                    add(symbol, kind, span, SymbolRole::Reference);
+                }
+            }
+        }
+
+        self.documents.push(Document {
+            path: path.to_string(),
+            occurrences,
+        });
+        Ok(())
+    }
+
+    pub fn documents(&self) -> &[Document] {
+        &self.documents
+    }
+
+    /// Every symbol seen so far, defined or only referenced.
+    pub fn symbols(&self) -> impl Iterator<Item = (&str, SymbolKind)> {
+        self.symbols.iter().map(|(symbol, kind)| (symbol.as_str(), *kind))
+    }
+
+    fn occurrences_of<'a>(
+        &'a self,
+        symbol: &'a str,
+        role: SymbolRole,
+    ) -> impl Iterator<Item = (&'a str, Span)> {
+        self.documents.iter().flat_map(move |document| {
+            document
+                .occurrences
+                .iter()
+                .filter(move |x| x.role == role && x.symbol == symbol)
+                .map(move |x| (document.path.as_str(), x.span))
+        })
+    }
+
+    /// The document and span defining `symbol`, if it is part of the index.
+    pub fn definition<'a>(&'a self, symbol: &'a str) -> Option<(&'a str, Span)> {
+        self.occurrences_of(symbol, SymbolRole::Definition).next()
+    }
+
+    pub fn references<'a>(&'a self, symbol: &'a str) -> Vec<(&'a str, Span)> {
+        self.occurrences_of(symbol, SymbolRole::Reference).collect()
+    }
+
+    /// Renders the index as SCIP-like JSON: documents with their occurrences,
+    /// then every symbol with its kind and whether the index defines it.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"documents\":[");
+        for (i, document) in self.documents.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"relative_path\":{},\"occurrences\":[",
+                json_string(&document.path)
+            );
+            for (j, x) in document.occurrences.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                let _ = write!(
+                    out,
+                    "{{\"range\":[{},{}],\"symbol\":{},\"role\":\"{}\"}}",
+                    x.span.start,
+                    x.span.end(),
+                    json_string(&x.symbol),
+                    x.role.as_str()
+                );
+            }
+            out.push_str("]}");
+        }
+        out.push_str("],\"symbols\":[");
+        for (i, (symbol, kind)) in self.symbols().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"symbol\":{},\"kind\":\"{}\",\"defined\":{}}}",
+                json_string(symbol),
+                kind.as_str(),
+                self.definition(symbol).is_some()
+            );
+        }
+        out.push_str("]}");
+        out
+    }
+}
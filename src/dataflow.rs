@@ -0,0 +1,278 @@
+//! A worklist solver for dataflow analyses over a [`ControlFlowGraph`], such
+//! as liveness, reaching definitions or constant propagation. An analysis
+//! implements [`Analysis`] over facts that implement [`Lattice`], and
+//! [`solve`] iterates its transfer functions to a fixed point.
+
+use std::collections::{BTreeSet, VecDeque};
+
+use crate::{
+    cfg::{ControlFlowGraph, Edge, EdgeKind},
+    instruction::InstructionAt,
+};
+
+/// The values of a join semilattice. Facts only grow by joining, so an
+/// analysis whose lattice has finite height always terminates.
+pub trait Lattice: Clone {
+    /// Merges `other` into `self`, returning whether `self` changed.
+    fn join(&mut self, other: &Self) -> bool;
+}
+
+/// Sets joined by union, as for liveness or reaching definitions.
+impl<T: Ord + Clone> Lattice for BTreeSet<T> {
+    fn join(&mut self, other: &Self) -> bool {
+        let len = self.len();
+        self.extend(other.iter().cloned());
+        self.len() != len
+    }
+}
+
+/// Joined element by element, as for a fact per local variable. A longer
+/// `other` contributes its extra elements as they are.
+impl<L: Lattice> Lattice for Vec<L> {
+    fn join(&mut self, other: &Self) -> bool {
+        let mut changed = false;
+        for (a, b) in self.iter_mut().zip(other) {
+            changed |= a.join(b);
+        }
+        if other.len() > self.len() {
+            self.extend_from_slice(&other[self.len()..]);
+            changed = true;
+        }
+        changed
+    }
+}
+
+/// `None` is below every other fact, as for a block not yet reached.
+impl<L: Lattice> Lattice for Option<L> {
+    fn join(&mut self, other: &Self) -> bool {
+        match (self.as_mut(), other) {
+            (_, None) => false,
+            (Some(a), Some(b)) => a.join(b),
+            (None, Some(b)) => {
+                *self = Some(b.clone());
+                true
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Facts flow from the entry along edges, as for reaching definitions.
+    Forward,
+    /// Facts flow from the exits against edges, as for liveness.
+    Backward,
+}
+
+/// A dataflow problem, see [`solve`].
+pub trait Analysis<'a> {
+    type Fact: Lattice;
+
+    const DIRECTION: Direction;
+
+    /// The fact every block starts from, the least element of the lattice.
+    fn bottom(&self, graph: &ControlFlowGraph<'a>) -> Self::Fact;
+
+    /// The fact at the start of the entry block for a forward analysis, or at
+    /// the end of each block without successors for a backward one.
+    fn boundary(&self, graph: &ControlFlowGraph<'a>) -> Self::Fact;
+
+    /// Updates `fact` across `instruction`: from before it to after it for a
+    /// forward analysis, and from after it to before it for a backward one.
+    fn transfer(&self, fact: &mut Self::Fact, instruction: &InstructionAt<'a>);
+
+    /// Updates `fact` as it flows along `edge`, e.g. to account for the
+    /// exception pushed on entering a handler. Does nothing by default.
+    fn transfer_edge(&self, fact: &mut Self::Fact, edge: &Edge<'a>) {
+        let _ = (fact, edge);
+    }
+}
+
+/// The facts at the start and end of every block, see [`solve`]. Both are in
+/// code order whatever the direction: `before` holds at the first instruction
+/// of a block, and `after` once the last instruction has run.
+#[derive(Debug, Clone)]
+pub struct Solution<F> {
+    before: Vec<F>,
+    after: Vec<F>,
+}
+
+impl<F: Lattice> Solution<F> {
+    pub fn before(&self, block: usize) -> &F {
+        &self.before[block]
+    }
+
+    pub fn after(&self, block: usize) -> &F {
+        &self.after[block]
+    }
+
+    /// The facts between the instructions of `block`, one more than there
+    /// are instructions: the first is [`Self::before`], each next one follows
+    /// another instruction, and the last is [`Self::after`].
+    pub fn instruction_facts<'a, A: Analysis<'a, Fact = F>>(
+        &self,
+        graph: &ControlFlowGraph<'a>,
+        analysis: &A,
+        block: usize,
+    ) -> Vec<F> {
+        let instructions = graph.block_instructions(block);
+        match A::DIRECTION {
+            Direction::Forward => {
+                let mut fact = self.before[block].clone();
+                let mut res = vec![fact.clone()];
+                for instruction in instructions {
+                    analysis.transfer(&mut fact, instruction);
+                    res.push(fact.clone());
+                }
+                res
+            }
+            Direction::Backward => {
+                let exceptional = handler_facts(graph, analysis, block, &self.before);
+                let mut fact = self.after[block].clone();
+                let mut res = vec![fact.clone()];
+                for instruction in instructions.iter().rev() {
+                    if let Some(x) = &exceptional {
+                        fact.join(x);
+                    }
+                    analysis.transfer(&mut fact, instruction);
+                    res.push(fact.clone());
+                }
+                res.reverse();
+                res
+            }
+        }
+    }
+}
+
+/// The join of the facts at the start of the handlers covering `block`, for a
+/// backward analysis, or `None` if there are none.
+fn handler_facts<'a, A: Analysis<'a>>(
+    graph: &ControlFlowGraph<'a>,
+    analysis: &A,
+    block: usize,
+    before: &[A::Fact],
+) -> Option<A::Fact> {
+    let mut res: Option<A::Fact> = None;
+    for edge in graph.successors(block) {
+        if let EdgeKind::Exception { .. } = edge.kind {
+            let mut fact = before[edge.to].clone();
+            analysis.transfer_edge(&mut fact, edge);
+            match &mut res {
+                Some(x) => {
+                    x.join(&fact);
+                }
+                None => res = Some(fact),
+            }
+        }
+    }
+    res
+}
+
+/// Runs `analysis` over `graph` until no fact changes.
+///
+/// An exception may be thrown by any instruction of a block covered by a
+/// handler, not only the last. Going forward, the fact before each
+/// instruction of the block flows along its exception edges; going backward,
+/// the fact at the start of a handler is joined in after every instruction.
+/// Unreachable blocks take part like any other.
+pub fn solve<'a, A: Analysis<'a>>(graph: &ControlFlowGraph<'a>, analysis: &A) -> Solution<A::Fact> {
+    let count = graph.blocks().len();
+    let bottom = analysis.bottom(graph);
+    let mut before = vec![bottom.clone(); count];
+    let mut after = vec![bottom; count];
+
+    let mut order = graph.reverse_postorder();
+    let mut reached = vec![false; count];
+    for &x in &order {
+        reached[x] = true;
+    }
+    order.extend((0..count).filter(|&x| !reached[x]));
+    if A::DIRECTION == Direction::Backward {
+        order.reverse();
+    }
+    let mut queued = vec![true; count];
+    let mut worklist: VecDeque<usize> = order.into_iter().collect();
+
+    match A::DIRECTION {
+        Direction::Forward => {
+            if count > 0 {
+                before[0].join(&analysis.boundary(graph));
+            }
+        }
+        Direction::Backward => {
+            let boundary = analysis.boundary(graph);
+            for (block, fact) in after.iter_mut().enumerate() {
+                if graph.successors(block).next().is_none() {
+                    fact.join(&boundary);
+                }
+            }
+        }
+    }
+
+    while let Some(block) = worklist.pop_front() {
+        queued[block] = false;
+        let instructions = graph.block_instructions(block);
+        match A::DIRECTION {
+            Direction::Forward => {
+                let throws = graph
+                    .successors(block)
+                    .any(|x| matches!(x.kind, EdgeKind::Exception { .. }));
+                let mut fact = before[block].clone();
+                let mut thrown = Vec::new();
+                for instruction in instructions {
+                    if throws {
+                        thrown.push(fact.clone());
+                    }
+                    analysis.transfer(&mut fact, instruction);
+                }
+                after[block] = fact;
+                for edge in graph.successors(block) {
+                    let facts = match edge.kind {
+                        EdgeKind::Exception { .. } => &thrown[..],
+                        _ => std::slice::from_ref(&after[block]),
+                    };
+                    let mut changed = false;
+                    for fact in facts {
+                        let mut fact = fact.clone();
+                        analysis.transfer_edge(&mut fact, edge);
+                        changed |= before[edge.to].join(&fact);
+                    }
+                    if changed && !queued[edge.to] {
+                        queued[edge.to] = true;
+                        worklist.push_back(edge.to);
+                    }
+                }
+            }
+            Direction::Backward => {
+                let exceptional = handler_facts(graph, analysis, block, &before);
+                let mut fact = after[block].clone();
+                for instruction in instructions.iter().rev() {
+                    if let Some(x) = &exceptional {
+                        fact.join(x);
+                    }
+                    analysis.transfer(&mut fact, instruction);
+                }
+                if !before[block].join(&fact) {
+                    continue;
+                }
+                for edge in graph.predecessors(block) {
+                    let changed = match edge.kind {
+                        // Picked up through `handler_facts` when it is processed.
+                        EdgeKind::Exception { .. } => true,
+                        _ => {
+                            let mut fact = before[block].clone();
+                            analysis.transfer_edge(&mut fact, edge);
+                            after[edge.from].join(&fact)
+                        }
+                    };
+                    if changed && !queued[edge.from] {
+                        queued[edge.from] = true;
+                        worklist.push_back(edge.from);
+                    }
+                }
+            }
+        }
+    }
+
+    Solution { before, after }
+}
@@ -0,0 +1,202 @@
+//! Def-use and use-def chains for local variables, see [`Method::def_use`].
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    cfg::ControlFlowGraph,
+    dataflow::{self, Analysis, Direction},
+    instruction::InstructionAt,
+    method::Method,
+    raw::MethodAccessFlags,
+    Result,
+};
+
+/// A write to local variable slots: a store or `iinc`, or on entry to the
+/// method, a parameter or `this`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Definition {
+    /// The pc of the instruction, or `None` for a parameter.
+    pub pc: Option<u32>,
+    pub index: u16,
+    /// Two for a `long` or `double`, one otherwise.
+    pub slots: u16,
+}
+
+/// A read of local variable slots: a load, `iinc` or `ret`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Use {
+    pub pc: u32,
+    pub index: u16,
+    pub slots: u16,
+}
+
+/// Whether slots `index` onwards for `slots` share any slot with `other`.
+fn overlaps(index: u16, slots: u16, other: &Definition) -> bool {
+    index < other.index + other.slots && other.index < index + slots
+}
+
+/// The definitions and uses of a method's locals and which reach which. Ids
+/// are positions in [`Self::definitions`] and [`Self::uses`], both in code
+/// order with parameters first.
+#[derive(Debug, Clone)]
+pub struct DefUse {
+    definitions: Vec<Definition>,
+    uses: Vec<Use>,
+    reaching: Vec<Vec<usize>>,
+    reached: Vec<Vec<usize>>,
+}
+
+impl DefUse {
+    pub fn definitions(&self) -> &[Definition] {
+        &self.definitions
+    }
+
+    pub fn uses(&self) -> &[Use] {
+        &self.uses
+    }
+
+    /// The use-def chain of use `id`: the definitions whose value it may read,
+    /// several where paths with different writes meet.
+    pub fn definitions_of(&self, id: usize) -> &[usize] {
+        &self.reaching[id]
+    }
+
+    /// The def-use chain of definition `id`: the uses that may read it. Empty
+    /// for a value that is never read, such as a dead store.
+    pub fn uses_of(&self, id: usize) -> &[usize] {
+        &self.reached[id]
+    }
+
+    /// The definition made by the instruction at `pc`, if it writes a local.
+    pub fn definition_at(&self, pc: u32) -> Option<usize> {
+        self.definitions.binary_search_by_key(&Some(pc), |x| x.pc).ok()
+    }
+
+    /// The use made by the instruction at `pc`, if it reads a local.
+    pub fn use_at(&self, pc: u32) -> Option<usize> {
+        self.uses.binary_search_by_key(&pc, |x| x.pc).ok()
+    }
+}
+
+/// Reaching definitions, each fact being the ids of the definitions that may
+/// still hold.
+struct ReachingDefinitions<'d> {
+    definitions: &'d [Definition],
+    parameters: usize,
+    by_pc: HashMap<u32, usize>,
+}
+
+impl<'a> Analysis<'a> for ReachingDefinitions<'_> {
+    type Fact = BTreeSet<usize>;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self, _: &ControlFlowGraph<'a>) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn boundary(&self, _: &ControlFlowGraph<'a>) -> Self::Fact {
+        (0..self.parameters).collect()
+    }
+
+    fn transfer(&self, fact: &mut Self::Fact, instruction: &InstructionAt<'a>) {
+        if let Some(&id) = self.by_pc.get(&instruction.pc) {
+            let definition = &self.definitions[id];
+            fact.retain(|&x| !overlaps(definition.index, definition.slots, &self.definitions[x]));
+            fact.insert(id);
+        }
+    }
+}
+
+impl Method<'_> {
+    /// Links every read of a local variable to the writes it may see, over
+    /// all paths through the code including into exception handlers. Returns
+    /// `None` for a method without code.
+    ///
+    /// Slots are tracked separately, so a write to either half of a `long`
+    /// or `double` ends it, and a read of either half sees writes to it.
+    pub fn def_use(&self) -> Result<Option<DefUse>> {
+        let code = match self.code()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let graph = code.control_flow_graph()?;
+
+        let mut definitions = Vec::new();
+        let mut index = 0;
+        if !self.method_inner.access_flags.contains(MethodAccessFlags::STATIC) {
+            definitions.push(Definition {
+                pc: None,
+                index,
+                slots: 1,
+            });
+            index += 1;
+        }
+        for ty in self.descriptor()?.parameter_types() {
+            definitions.push(Definition {
+                pc: None,
+                index,
+                slots: ty.slots(),
+            });
+            index += ty.slots();
+        }
+        let parameters = definitions.len();
+
+        let mut by_pc = HashMap::new();
+        let mut uses = Vec::new();
+        for instruction in graph.instructions() {
+            let access = match instruction.instruction.local_access() {
+                Some(x) => x,
+                None => continue,
+            };
+            if access.reads {
+                uses.push(Use {
+                    pc: instruction.pc,
+                    index: access.index,
+                    slots: access.slots,
+                });
+            }
+            if access.writes {
+                by_pc.insert(instruction.pc, definitions.len());
+                definitions.push(Definition {
+                    pc: Some(instruction.pc),
+                    index: access.index,
+                    slots: access.slots,
+                });
+            }
+        }
+
+        let analysis = ReachingDefinitions {
+            definitions: &definitions,
+            parameters,
+            by_pc,
+        };
+        let solution = dataflow::solve(&graph, &analysis);
+        let mut reaching = vec![Vec::new(); uses.len()];
+        let mut reached = vec![Vec::new(); definitions.len()];
+        let mut next_use = 0;
+        for block in 0..graph.blocks().len() {
+            let facts = solution.instruction_facts(&graph, &analysis, block);
+            for (instruction, fact) in graph.block_instructions(block).iter().zip(facts) {
+                let used = match uses.get(next_use) {
+                    Some(x) if x.pc == instruction.pc => x,
+                    _ => continue,
+                };
+                for &id in &fact {
+                    if overlaps(used.index, used.slots, &definitions[id]) {
+                        reaching[next_use].push(id);
+                        reached[id].push(next_use);
+                    }
+                }
+                next_use += 1;
+            }
+        }
+
+        Ok(Some(DefUse {
+            definitions,
+            uses,
+            reaching,
+            reached,
+        }))
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use crate::{
+    field::TypeDescriptor,
+    instruction::{Instruction, MaybeInterfaceMethodRef},
+    method::MethodDescriptor,
+    ClassFile, Result,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EvidenceKind {
+    SuperClass,
+    Interface,
+    FieldType,
+    MethodSignature,
+    ThrowsClause,
+    FieldAccess,
+    MethodCall,
+    TypeInstruction,
+}
+
+impl EvidenceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SuperClass => "super_class",
+            Self::Interface => "interface",
+            Self::FieldType => "field_type",
+            Self::MethodSignature => "method_signature",
+            Self::ThrowsClause => "throws_clause",
+            Self::FieldAccess => "field_access",
+            Self::MethodCall => "method_call",
+            Self::TypeInstruction => "type_instruction",
+        }
+    }
+}
+
+/// Where in the artifact a dependency was observed.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Evidence {
+    pub class: String,
+    pub member: Option<String>,
+    pub pc: Option<u32>,
+    pub kind: EvidenceKind,
+}
+
+/// Inventory of the classes an artifact references but does not define.
+#[derive(Debug, Default)]
+pub struct DependencyInventory {
+    defined: BTreeSet<String>,
+    referenced: BTreeMap<String, BTreeSet<Evidence>>,
+}
+
+fn package_of(class: &str) -> &str {
+    class.rsplit_once('/').map_or("", |(package, _)| package)
+}
+
+fn is_jdk_class(class: &str) -> bool {
+    ["java/", "javax/", "jdk/", "sun/", "com/sun/"]
+        .iter()
+        .any(|x| class.starts_with(x))
+}
+
+/// Strips array dimensions and `L...;` wrapping from a class constant, which may
+/// be an array descriptor for `anewarray`, `checkcast` and friends.
+pub(crate) fn element_class(name: &str) -> Option<&str> {
+    let element = name.trim_start_matches('[');
+    if element.len() == name.len() {
+        return Some(name);
+    }
+    element.strip_prefix('L').and_then(|x| x.strip_suffix(';'))
+}
+
+fn type_class<'a>(ty: &TypeDescriptor<'a>) -> Option<&'a str> {
+    match ty {
+        TypeDescriptor::String => Some("java/lang/String"),
+        TypeDescriptor::Class => Some("java/lang/Class"),
+        TypeDescriptor::ClassName(x) => Some(x),
+        TypeDescriptor::Array(x) => type_class(x),
+        _ => None,
+    }
+}
+
+fn method_classes<'a>(descriptor: &MethodDescriptor<'a>) -> Vec<&'a str> {
+    descriptor
+        .parameter_types()
+        .iter()
+        .chain(descriptor.return_type())
+        .filter_map(type_class)
+        .collect()
+}
+
+impl DependencyInventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_classes<'a>(classes: impl IntoIterator<Item = &'a ClassFile>) -> Result<Self> {
+        let mut inventory = Self::new();
+        for class in classes {
+            inventory.add_class(class)?;
+        }
+        Ok(inventory)
+    }
+
+    fn record(&mut self, class: &str, evidence: Evidence) {
+        if let Some(class) = element_class(class) {
+            self.referenced
+                .entry(class.to_string())
+                .or_default()
+                .insert(evidence);
+        }
+    }
+
+    pub fn add_class(&mut self, class_file: &ClassFile) -> Result<()> {
+        let this_class = class_file.this_class()?;
+        self.defined.insert(this_class.to_string());
+        let evidence = |member: Option<String>, pc: Option<u32>, kind| Evidence {
+            class: this_class.to_string(),
+            member,
+            pc,
+            kind,
+        };
+
+        if class_file.super_class.0 != 0 {
+            let super_class = class_file.super_class()?;
+            self.record(super_class, evidence(None, None, EvidenceKind::SuperClass));
+        }
+        for interface in class_file.interfaces()? {
+            self.record(interface, evidence(None, None, EvidenceKind::Interface));
+        }
+        for field in class_file.fields() {
+            let member = field.identifier()?.to_string();
+            if let Some(class) = type_class(&field.descriptor()?) {
+                self.record(class, evidence(Some(member), None, EvidenceKind::FieldType));
+            }
+        }
+        for method in class_file.methods() {
+            let descriptor = method.descriptor()?;
+            let member = format!(
+                "{}{}",
+                method.identifier()?,
+                method
+                    .method_inner
+                    .descriptor_index
+                    .get_as_string(class_file)?
+            );
+            for class in method_classes(&descriptor) {
+                self.record(
+                    class,
+                    evidence(Some(member.clone()), None, EvidenceKind::MethodSignature),
+                );
+            }
+            if let Some(exceptions) = method.exceptions()? {
+                for class in exceptions.class_names()? {
+                    self.record(
+                        class,
+                        evidence(Some(member.clone()), None, EvidenceKind::ThrowsClause),
+                    );
+                }
+            }
+            let code = match method.code()? {
+                Some(x) => x,
+                None => continue,
+            };
+            for (pc, instruction) in code.instructions_with_pcs()? {
+                let at = |kind| evidence(Some(member.clone()), Some(pc), kind);
+                match &instruction {
+                    Instruction::Getfield { field }
+                    | Instruction::Getstatic { field }
+                    | Instruction::Putfield { field }
+                    | Instruction::Putstatic { field } => {
+                        self.record(field.class, at(EvidenceKind::FieldAccess));
+                        if let Some(class) = type_class(&field.descriptor) {
+                            self.record(class, at(EvidenceKind::FieldAccess));
+                        }
+                    }
+                    Instruction::Invokevirtual { index } => {
+                        self.record(index.class, at(EvidenceKind::MethodCall));
+                        for class in method_classes(&index.descriptor) {
+                            self.record(class, at(EvidenceKind::MethodCall));
+                        }
+                    }
+                    Instruction::Invokeinterface { index, .. } => {
+                        self.record(index.class, at(EvidenceKind::MethodCall));
+                        for class in method_classes(&index.descriptor) {
+                            self.record(class, at(EvidenceKind::MethodCall));
+                        }
+                    }
+                    Instruction::Invokespecial { index } | Instruction::Invokestatic { index } => {
+                        let (class, descriptor) = match index {
+                            MaybeInterfaceMethodRef::RegularMethod(x) => (x.class, &x.descriptor),
+                            MaybeInterfaceMethodRef::InterfaceMethod(x) => {
+                                (x.class, &x.descriptor)
+                            }
+                        };
+                        self.record(class, at(EvidenceKind::MethodCall));
+                        for class in method_classes(descriptor) {
+                            self.record(class, at(EvidenceKind::MethodCall));
+                        }
+                    }
+                    Instruction::Invokedynamic { index, .. } => {
+                        for class in method_classes(&index.descriptor) {
+                            self.record(class, at(EvidenceKind::MethodCall));
+                        }
+                    }
+                    Instruction::New { class }
+                    | Instruction::Anewarray { class }
+                    | Instruction::Checkcast { class }
+                    | Instruction::Instanceof { class }
+                    | Instruction::Multianewarray { class, .. } => {
+                        self.record(class, at(EvidenceKind::TypeInstruction));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// External classes (referenced but not defined in the artifact) with their evidence.
+    pub fn external_classes(&self) -> impl Iterator<Item = (&str, &BTreeSet<Evidence>)> {
+        self.referenced
+            .iter()
+            .filter(|(class, _)| !self.defined.contains(*class))
+            .map(|(class, evidence)| (class.as_str(), evidence))
+    }
+
+    pub fn external_packages(&self) -> BTreeSet<&str> {
+        self.external_classes()
+            .map(|(class, _)| package_of(class))
+            .collect()
+    }
+
+    /// Renders the inventory as JSON, grouped by package then class.
+    pub fn to_json(&self) -> String {
+        let mut packages: BTreeMap<&str, Vec<(&str, &BTreeSet<Evidence>)>> = BTreeMap::new();
+        for (class, evidence) in self.external_classes() {
+            packages
+                .entry(package_of(class))
+                .or_default()
+                .push((class, evidence));
+        }
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"defined_classes\":{},\"packages\":[",
+            self.defined.len()
+        );
+        for (i, (package, classes)) in packages.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{{\"package\":{},\"classes\":[", json_string(package));
+            for (j, (class, evidence)) in classes.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                let _ = write!(
+                    out,
+                    "{{\"class\":{},\"jdk\":{},\"evidence\":[",
+                    json_string(class),
+                    is_jdk_class(class)
+                );
+                for (k, x) in evidence.iter().enumerate() {
+                    if k > 0 {
+                        out.push(',');
+                    }
+                    let _ = write!(
+                        out,
+                        "{{\"class\":{},\"member\":{},\"pc\":{},\"kind\":\"{}\"}}",
+                        json_string(&x.class),
+                        x.member.as_deref().map_or("null".to_string(), json_string),
+                        x.pc.map_or("null".to_string(), |x| x.to_string()),
+                        x.kind.as_str()
+                    );
+                }
+                out.push_str("]}");
+            }
+            out.push_str("]}");
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,315 @@
+use std::{ops::Range, sync::OnceLock};
+
+use crate::{
+    field::TypeDescriptor,
+    method::MethodDescriptor,
+    raw::Utf8Index,
+    signature::{
+        ClassSignature, ClassType, JavaType, MethodSignature, ReferenceType, SimpleClassType, ThrowsSignature,
+        TypeArgument, TypeParameter,
+    },
+    ClassFile, Result,
+};
+
+/// Types parsed from descriptor or signature text that borrow nothing from it
+/// but names.
+trait MapNames<'a> {
+    type Output<'b>;
+
+    /// The same value with every name replaced by what `f` returns for it.
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> Self::Output<'b>;
+}
+
+impl<'a> MapNames<'a> for TypeDescriptor<'a> {
+    type Output<'b> = TypeDescriptor<'b>;
+
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> TypeDescriptor<'b> {
+        match self {
+            Self::Byte => TypeDescriptor::Byte,
+            Self::Char => TypeDescriptor::Char,
+            Self::Double => TypeDescriptor::Double,
+            Self::Float => TypeDescriptor::Float,
+            Self::Int => TypeDescriptor::Int,
+            Self::Long => TypeDescriptor::Long,
+            Self::Short => TypeDescriptor::Short,
+            Self::Boolean => TypeDescriptor::Boolean,
+            Self::String => TypeDescriptor::String,
+            Self::Class => TypeDescriptor::Class,
+            Self::Array(x) => TypeDescriptor::Array(Box::new(x.map_names(f))),
+            Self::ClassName(x) => TypeDescriptor::ClassName(f(x)),
+        }
+    }
+}
+
+impl<'a> MapNames<'a> for MethodDescriptor<'a> {
+    type Output<'b> = MethodDescriptor<'b>;
+
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> MethodDescriptor<'b> {
+        MethodDescriptor {
+            param_tys: self.param_tys.iter().map(|x| x.map_names(f)).collect(),
+            return_ty: self.return_ty.as_ref().map(|x| x.map_names(f)),
+        }
+    }
+}
+
+impl<'a> MapNames<'a> for JavaType<'a> {
+    type Output<'b> = JavaType<'b>;
+
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> JavaType<'b> {
+        match self {
+            Self::Base(x) => JavaType::Base(x.clone()),
+            Self::Reference(x) => JavaType::Reference(x.map_names(f)),
+        }
+    }
+}
+
+impl<'a> MapNames<'a> for ReferenceType<'a> {
+    type Output<'b> = ReferenceType<'b>;
+
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> ReferenceType<'b> {
+        match self {
+            Self::JavaString => ReferenceType::JavaString,
+            Self::JavaClass => ReferenceType::JavaClass,
+            Self::ClassType(x) => ReferenceType::ClassType(x.map_names(f)),
+            Self::TypeVariable(x) => ReferenceType::TypeVariable(f(x)),
+            Self::ArrayType(x) => ReferenceType::ArrayType(Box::new(x.map_names(f))),
+        }
+    }
+}
+
+impl<'a> MapNames<'a> for TypeArgument<'a> {
+    type Output<'b> = TypeArgument<'b>;
+
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> TypeArgument<'b> {
+        match self {
+            Self::Exact(x) => TypeArgument::Exact(x.map_names(f)),
+            Self::Plus(x) => TypeArgument::Plus(x.map_names(f)),
+            Self::Minus(x) => TypeArgument::Minus(x.map_names(f)),
+            Self::Star => TypeArgument::Star,
+        }
+    }
+}
+
+impl<'a> MapNames<'a> for SimpleClassType<'a> {
+    type Output<'b> = SimpleClassType<'b>;
+
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> SimpleClassType<'b> {
+        SimpleClassType {
+            name: f(self.name),
+            type_arguments: self.type_arguments.iter().map(|x| x.map_names(f)).collect(),
+        }
+    }
+}
+
+impl<'a> MapNames<'a> for ClassType<'a> {
+    type Output<'b> = ClassType<'b>;
+
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> ClassType<'b> {
+        ClassType {
+            package: self.package.iter().map(|x| f(x)).collect(),
+            base: self.base.map_names(f),
+            sub: self.sub.iter().map(|x| x.map_names(f)).collect(),
+        }
+    }
+}
+
+impl<'a> MapNames<'a> for TypeParameter<'a> {
+    type Output<'b> = TypeParameter<'b>;
+
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> TypeParameter<'b> {
+        TypeParameter {
+            name: f(self.name),
+            class_bound: self.class_bound.as_ref().map(|x| x.map_names(f)),
+            interface_bounds: self.interface_bounds.iter().map(|x| x.map_names(f)).collect(),
+        }
+    }
+}
+
+impl<'a> MapNames<'a> for ClassSignature<'a> {
+    type Output<'b> = ClassSignature<'b>;
+
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> ClassSignature<'b> {
+        ClassSignature {
+            type_parameters: self.type_parameters.iter().map(|x| x.map_names(f)).collect(),
+            superclass_signature: self.superclass_signature.map_names(f),
+            superinterface_signatures: self.superinterface_signatures.iter().map(|x| x.map_names(f)).collect(),
+        }
+    }
+}
+
+impl<'a> MapNames<'a> for ThrowsSignature<'a> {
+    type Output<'b> = ThrowsSignature<'b>;
+
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> ThrowsSignature<'b> {
+        match self {
+            Self::ClassType(x) => ThrowsSignature::ClassType(x.map_names(f)),
+            Self::TypeVariable(x) => ThrowsSignature::TypeVariable(f(x)),
+        }
+    }
+}
+
+impl<'a> MapNames<'a> for MethodSignature<'a> {
+    type Output<'b> = MethodSignature<'b>;
+
+    fn map_names<'b>(&self, f: &mut dyn FnMut(&'a str) -> &'b str) -> MethodSignature<'b> {
+        MethodSignature {
+            type_parameters: self.type_parameters.iter().map(|x| x.map_names(f)).collect(),
+            parameters: self.parameters.iter().map(|x| x.map_names(f)).collect(),
+            result: self.result.as_ref().map(|x| x.map_names(f)),
+            throws: self.throws.iter().map(|x| x.map_names(f)).collect(),
+        }
+    }
+}
+
+/// A parsed value with its names blanked out, and the ranges of the text they
+/// were taken from, so that it can live on the [`ClassFile`] whose constant
+/// pool holds the text.
+struct Template<T> {
+    value: T,
+    names: Vec<Range<usize>>,
+}
+
+impl<T: MapNames<'static>> Template<T> {
+    fn new<'a, U>(text: &'a str, value: &U) -> Self
+    where
+        U: MapNames<'a, Output<'static> = T>,
+    {
+        let mut names = Vec::new();
+        let value = value.map_names(&mut |x| {
+            // The parsers only ever return slices of their input.
+            let start = x.as_ptr() as usize - text.as_ptr() as usize;
+            names.push(start..start + x.len());
+            ""
+        });
+        Self { value, names }
+    }
+
+    /// The value parsed from `text`, which must be the text the template was
+    /// made from. Names are visited in the same order as by [`Self::new`].
+    fn fill<'a>(&self, text: &'a str) -> T::Output<'a> {
+        let mut names = self.names.iter();
+        self.value.map_names(&mut |_| names.next().map_or("", |x| &text[x.clone()]))
+    }
+}
+
+enum Cached {
+    Field(Box<Template<TypeDescriptor<'static>>>),
+    Method(Box<Template<MethodDescriptor<'static>>>),
+    ClassSignature(Box<Template<ClassSignature<'static>>>),
+    MethodSignature(Box<Template<MethodSignature<'static>>>),
+    FieldSignature(Box<Template<ReferenceType<'static>>>),
+}
+
+/// Parsed descriptors and signatures of one class, one slot per constant pool
+/// entry, filled the first time the `CONSTANT_Utf8` there is parsed.
+///
+/// Hits take no lock, but still build a fresh value from the template: values
+/// borrow names from the constant pool, and the cache lives on the same
+/// [`ClassFile`], so it cannot hold them as they are handed out.
+pub(crate) struct DescriptorCache {
+    slots: Box<[OnceLock<Cached>]>,
+}
+
+impl DescriptorCache {
+    pub(crate) fn new(constant_pool_count: usize) -> Self {
+        Self {
+            slots: (0..constant_pool_count).map(|_| OnceLock::new()).collect(),
+        }
+    }
+
+    /// The value cached for the `CONSTANT_Utf8` at `index`, if `hit` accepts
+    /// its kind, or else `parse` of its text, cached through `store` if the
+    /// slot is still empty. A slot keeps the first kind its text was parsed
+    /// as; text read as two kinds is parsed every time as the second.
+    fn get<'a, T>(
+        &self,
+        class_file: &'a ClassFile,
+        index: Utf8Index,
+        hit: impl FnOnce(&Cached, &'a str) -> Option<T>,
+        parse: impl FnOnce(&'a str) -> Result<T>,
+        store: impl FnOnce(&'a str, &T) -> Cached,
+    ) -> Result<T> {
+        let text = index.get_as_string(class_file)?;
+        let slot = (index.0 as usize).checked_sub(1).and_then(|x| self.slots.get(x));
+        if let Some(x) = slot.and_then(OnceLock::get).and_then(|x| hit(x, text)) {
+            return Ok(x);
+        }
+        let value = parse(text)?;
+        if let Some(slot) = slot {
+            slot.get_or_init(|| store(text, &value));
+        }
+        Ok(value)
+    }
+}
+
+impl ClassFile {
+    /// The field descriptor in the `CONSTANT_Utf8` at `index`.
+    pub(crate) fn field_descriptor_at(&self, index: Utf8Index) -> Result<TypeDescriptor<'_>> {
+        self.descriptors.get(
+            self,
+            index,
+            |x, text| match x {
+                Cached::Field(x) => Some(x.fill(text)),
+                _ => None,
+            },
+            |text| Ok(TypeDescriptor::parse(text)?.1),
+            |text, x| Cached::Field(Box::new(Template::new(text, x))),
+        )
+    }
+
+    /// The method descriptor in the `CONSTANT_Utf8` at `index`.
+    pub(crate) fn method_descriptor_at(&self, index: Utf8Index) -> Result<MethodDescriptor<'_>> {
+        self.descriptors.get(
+            self,
+            index,
+            |x, text| match x {
+                Cached::Method(x) => Some(x.fill(text)),
+                _ => None,
+            },
+            |text| Ok(MethodDescriptor::parse(text)?.1),
+            |text, x| Cached::Method(Box::new(Template::new(text, x))),
+        )
+    }
+
+    /// The class signature in the `CONSTANT_Utf8` at `index`.
+    pub(crate) fn class_signature_at(&self, index: Utf8Index) -> Result<ClassSignature<'_>> {
+        self.descriptors.get(
+            self,
+            index,
+            |x, text| match x {
+                Cached::ClassSignature(x) => Some(x.fill(text)),
+                _ => None,
+            },
+            |text| Ok(ClassSignature::parse(text)?.1),
+            |text, x| Cached::ClassSignature(Box::new(Template::new(text, x))),
+        )
+    }
+
+    /// The method signature in the `CONSTANT_Utf8` at `index`.
+    pub(crate) fn method_signature_at(&self, index: Utf8Index) -> Result<MethodSignature<'_>> {
+        self.descriptors.get(
+            self,
+            index,
+            |x, text| match x {
+                Cached::MethodSignature(x) => Some(x.fill(text)),
+                _ => None,
+            },
+            |text| Ok(MethodSignature::parse(text)?.1),
+            |text, x| Cached::MethodSignature(Box::new(Template::new(text, x))),
+        )
+    }
+
+    /// The field signature in the `CONSTANT_Utf8` at `index`.
+    pub(crate) fn field_signature_at(&self, index: Utf8Index) -> Result<ReferenceType<'_>> {
+        self.descriptors.get(
+            self,
+            index,
+            |x, text| match x {
+                Cached::FieldSignature(x) => Some(x.fill(text)),
+                _ => None,
+            },
+            |text| Ok(ReferenceType::parse(text)?.1),
+            |text, x| Cached::FieldSignature(Box::new(Template::new(text, x))),
+        )
+    }
+}
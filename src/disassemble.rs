@@ -0,0 +1,550 @@
+//! Human readable listings of classes, laid out as `javap -c -p` lays them
+//! out, see [`ClassFile::disassemble`].
+//!
+//! ```text
+//! Compiled from "Hello.java"
+//! public class Hello {
+//!   public Hello();
+//!     Code:
+//!        0: aload_0
+//!        1: invokespecial #1                  // Method java/lang/Object."<init>":()V
+//!        4: return
+//! }
+//! ```
+
+use std::fmt::Write;
+
+use bitflags::Flags;
+
+use crate::{
+    attributes::{Code, ModuleFlags, RequiresFlags},
+    canonical::Reader,
+    field::Field,
+    instruction::{BranchOffset, Instruction, OperandFormat},
+    method::Method,
+    raw::ConstantPoolItem,
+    text::ARRAY_TYPES,
+    ClassAccessFlags, ClassFile, ClassIndex, FieldAccessFlags, MethodAccessFlags, Result,
+};
+
+/// Method handle kinds, from `REF_getField` = 1.
+const HANDLE_KINDS: [&str; 9] = [
+    "REF_getField",
+    "REF_getStatic",
+    "REF_putField",
+    "REF_putStatic",
+    "REF_invokeVirtual",
+    "REF_invokeStatic",
+    "REF_invokeSpecial",
+    "REF_newInvokeSpecial",
+    "REF_invokeInterface",
+];
+
+pub(crate) const CLASS_MODIFIERS: [(ClassAccessFlags, &str); 3] = [
+    (ClassAccessFlags::PUBLIC, "public"),
+    (ClassAccessFlags::FINAL, "final"),
+    (ClassAccessFlags::ABSTRACT, "abstract"),
+];
+
+pub(crate) const FIELD_MODIFIERS: [(FieldAccessFlags, &str); 7] = [
+    (FieldAccessFlags::PUBLIC, "public"),
+    (FieldAccessFlags::PRIVATE, "private"),
+    (FieldAccessFlags::PROTECTED, "protected"),
+    (FieldAccessFlags::STATIC, "static"),
+    (FieldAccessFlags::FINAL, "final"),
+    (FieldAccessFlags::VOLATILE, "volatile"),
+    (FieldAccessFlags::TRANSIENT, "transient"),
+];
+
+pub(crate) const METHOD_MODIFIERS: [(MethodAccessFlags, &str); 9] = [
+    (MethodAccessFlags::PUBLIC, "public"),
+    (MethodAccessFlags::PRIVATE, "private"),
+    (MethodAccessFlags::PROTECTED, "protected"),
+    (MethodAccessFlags::STATIC, "static"),
+    (MethodAccessFlags::FINAL, "final"),
+    (MethodAccessFlags::SYNCHRONIZED, "synchronized"),
+    (MethodAccessFlags::NATIVE, "native"),
+    (MethodAccessFlags::ABSTRACT, "abstract"),
+    (MethodAccessFlags::STRICT, "strictfp"),
+];
+
+/// The column the comments naming constants start at.
+const COMMENT_COLUMN: usize = 44;
+
+/// The indentation of the cases of a switch and its closing brace.
+const SWITCH_INDENT: &str = "          ";
+
+/// The keywords of the `flags` set in `modifiers`, each followed by a space.
+pub(crate) fn modifier_words<F: Flags<Bits = u16>>(flags: &F, modifiers: &[(F, &str)]) -> String {
+    let mut res = String::new();
+    for (flag, word) in modifiers {
+        if flags.contains(F::from_bits_retain(flag.bits())) {
+            res.push_str(word);
+            res.push(' ');
+        }
+    }
+    res
+}
+
+fn java_name(internal_name: &str) -> String {
+    internal_name.replace('/', ".")
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '$' || c == '_'
+}
+
+/// `name` as `javap` writes names in comments: in quotes, with `\`, `"`, new
+/// lines and tabs escaped, unless it is a sequence of Java identifiers
+/// separated by `/`, e.g. `"<init>"` or `"[Ljava/lang/Object;"`.
+fn checked_name(name: &str) -> String {
+    let mut previous = '/';
+    let plain = !name.is_empty()
+        && name.chars().all(|c| {
+            let valid = match (previous, c) {
+                ('/', c) => is_identifier_start(c),
+                (_, '/') => true,
+                (_, c) => is_identifier_start(c) || c.is_alphanumeric(),
+            };
+            previous = c;
+            valid
+        });
+    if plain {
+        return name.to_string();
+    }
+    let mut res = String::from("\"");
+    for c in name.chars() {
+        match c {
+            '\\' | '"' => {
+                res.push('\\');
+                res.push(c);
+            }
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// A string constant with the escapes of a Java literal, unquoted.
+fn escaped(value: &str) -> String {
+    let mut res = String::new();
+    for c in value.chars() {
+        match c {
+            '\t' => res.push_str("\\t"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\u{8}' => res.push_str("\\b"),
+            '\u{c}' => res.push_str("\\f"),
+            '"' | '\'' | '\\' => {
+                res.push('\\');
+                res.push(c);
+            }
+            c if c.is_control() => {
+                let _ = write!(res, "\\u{:04x}", c as u32);
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// A floating point number as Java's `toString` writes it: in plain decimal
+/// if its magnitude is from 10^-3 up to 10^7, and as e.g. `1.0E10` otherwise,
+/// with the fewest digits that read back to it, but at least two in the
+/// latter form.
+fn java_float_text<T: std::fmt::LowerExp>(value: T) -> String {
+    let scientific = format!("{:e}", value);
+    if scientific == "NaN" {
+        return scientific;
+    }
+    let (sign, unsigned) = match scientific.strip_prefix('-') {
+        Some(x) => ("-", x),
+        None => ("", scientific.as_str()),
+    };
+    let (mantissa, exponent) = match unsigned.split_once('e') {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().unwrap_or(0)),
+        None => return format!("{}Infinity", sign),
+    };
+    let digits = mantissa.replace('.', "");
+    let point = exponent + 1;
+    if digits == "0" {
+        format!("{}0.0", sign)
+    } else if !(-2..=7).contains(&point) {
+        // Of the two digit numbers, the nearest, which need not be the
+        // shortest padded with a zero, e.g. `4.9E-324` rather than `5.0E-324`.
+        let two_digits = format!("{:.1e}", value).replace('e', "E");
+        match digits.len() {
+            1 => two_digits,
+            _ => format!("{}{}.{}E{}", sign, &digits[..1], &digits[1..], exponent),
+        }
+    } else if point <= 0 {
+        format!("{}0.{}{}", sign, "0".repeat(-point as usize), digits)
+    } else if point as usize >= digits.len() {
+        format!("{}{}{}.0", sign, digits, "0".repeat(point as usize - digits.len()))
+    } else {
+        format!("{}{}.{}", sign, &digits[..point as usize], &digits[point as usize..])
+    }
+}
+
+impl ClassFile {
+    /// A constant as `javap` writes it after the kind of constant, e.g.
+    /// `java/lang/String.length:()I`.
+    fn javap_value(&self, index: u16) -> Result<String> {
+        Ok(match self.constant_pool.get(index)? {
+            ConstantPoolItem::Class { name_index } => checked_name(name_index.get_as_string(self)?),
+            ConstantPoolItem::Fieldref {
+                class_index,
+                name_and_type_index,
+            }
+            | ConstantPoolItem::Methodref {
+                class_index,
+                name_and_type_index,
+            }
+            | ConstantPoolItem::InterfaceMethodref {
+                class_index,
+                name_and_type_index,
+            } => format!(
+                "{}.{}",
+                checked_name(class_index.get_as_string(self)?),
+                self.javap_value(name_and_type_index.0)?
+            ),
+            ConstantPoolItem::NameAndType {
+                name_index,
+                descriptor_index,
+            } => format!(
+                "{}:{}",
+                checked_name(name_index.get_as_string(self)?),
+                descriptor_index.get_as_string(self)?
+            ),
+            ConstantPoolItem::String { string_index } => escaped(string_index.get_as_string(self)?),
+            ConstantPoolItem::Utf8 { value, .. } => escaped(value),
+            ConstantPoolItem::Integer { value } => value.to_string(),
+            ConstantPoolItem::Float { value } => {
+                format!("{}f", java_float_text(*value))
+            }
+            ConstantPoolItem::Long { value } => format!("{}l", value),
+            ConstantPoolItem::Double { value } => {
+                format!("{}d", java_float_text(*value))
+            }
+            ConstantPoolItem::MethodHandle { reference } => {
+                let kind = (reference.kind as usize)
+                    .checked_sub(1)
+                    .and_then(|x| HANDLE_KINDS.get(x))
+                    .map_or_else(|| format!("kind {}", reference.kind), |x| x.to_string());
+                format!("{} {}", kind, self.javap_value(reference.index)?)
+            }
+            ConstantPoolItem::MethodType { descriptor_index } => {
+                escaped(descriptor_index.get_as_string(self)?)
+            }
+            ConstantPoolItem::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            }
+            | ConstantPoolItem::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => format!(
+                "#{}:{}",
+                bootstrap_method_attr_index.0,
+                self.javap_value(name_and_type_index.0)?
+            ),
+            ConstantPoolItem::Module { name_index } | ConstantPoolItem::Package { name_index } => {
+                checked_name(name_index.get_as_string(self)?)
+            }
+            x => format!("{:?}", x),
+        })
+    }
+
+    /// The comment naming the constant an instruction refers to, e.g.
+    /// `// Method java/lang/Object."<init>":()V`, leaving out the class of
+    /// members of this class.
+    fn javap_comment(&self, index: u16) -> Result<String> {
+        let item = self.constant_pool.get(index)?;
+        let kind = match item {
+            ConstantPoolItem::Class { .. } => "class",
+            ConstantPoolItem::Fieldref { .. } => "Field",
+            ConstantPoolItem::Methodref { .. } => "Method",
+            ConstantPoolItem::InterfaceMethodref { .. } => "InterfaceMethod",
+            ConstantPoolItem::String { .. } => "String",
+            ConstantPoolItem::Integer { .. } => "int",
+            ConstantPoolItem::Float { .. } => "float",
+            ConstantPoolItem::Long { .. } => "long",
+            ConstantPoolItem::Double { .. } => "double",
+            ConstantPoolItem::NameAndType { .. } => "NameAndType",
+            ConstantPoolItem::Utf8 { .. } => "Utf8",
+            ConstantPoolItem::MethodHandle { .. } => "MethodHandle",
+            ConstantPoolItem::MethodType { .. } => "MethodType",
+            ConstantPoolItem::Dynamic { .. } => "Dynamic",
+            ConstantPoolItem::InvokeDynamic { .. } => "InvokeDynamic",
+            ConstantPoolItem::Module { .. } => "Module",
+            ConstantPoolItem::Package { .. } => "Package",
+            _ => "unknown",
+        };
+        let value = match item {
+            ConstantPoolItem::Fieldref {
+                class_index,
+                name_and_type_index,
+            }
+            | ConstantPoolItem::Methodref {
+                class_index,
+                name_and_type_index,
+            }
+            | ConstantPoolItem::InterfaceMethodref {
+                class_index,
+                name_and_type_index,
+            } if class_index.0 == self.this_class.0 => self.javap_value(name_and_type_index.0)?,
+            _ => self.javap_value(index)?,
+        };
+        Ok(format!("// {} {}", kind, value))
+    }
+
+    /// The instructions of `code`, a line each, or several for a switch.
+    fn javap_instructions(&self, code: &Code, out: &mut String) -> Result<()> {
+        for (pc, instruction) in code.instructions_with_pcs()? {
+            let target = |offset: &BranchOffset| pc as i64 + offset.0 as i64;
+            let pool_index = code.constant_index(pc);
+            let comment = match pool_index {
+                Some(x) => Some(self.javap_comment(x)?),
+                None => None,
+            };
+            let operands = match (&instruction, pool_index) {
+                (Instruction::Invokeinterface { count, .. }, Some(x)) => format!("#{},  {}", x, count),
+                (Instruction::Invokedynamic { .. }, Some(x)) => format!("#{},  0", x),
+                (Instruction::Multianewarray { dimensions, .. }, Some(x)) => format!("#{},  {}", x, dimensions),
+                (_, Some(x)) => format!("#{}", x),
+                (Instruction::Bipush { value }, _) => value.to_string(),
+                (Instruction::Sipush { value }, _) => value.to_string(),
+                (Instruction::Iinc { index, constant } | Instruction::WideIinc { index, constant }, _) => {
+                    format!("{}, {}", index.0, constant)
+                }
+                (
+                    Instruction::Tableswitch {
+                        default,
+                        low,
+                        high,
+                        jump_offsets,
+                        ..
+                    },
+                    _,
+                ) => {
+                    let mut res = format!("{{ // {} to {}", low, high);
+                    for (key, offset) in (*low as i64..).zip(jump_offsets) {
+                        let _ = write!(res, "\n{}{:12}: {}", SWITCH_INDENT, key, target(offset));
+                    }
+                    let default = target(default);
+                    let _ = write!(res, "\n{}     default: {}\n{}}}", SWITCH_INDENT, default, SWITCH_INDENT);
+                    res
+                }
+                (Instruction::Lookupswitch { default, pairs, .. }, _) => {
+                    let mut res = format!("{{ // {}", pairs.len());
+                    for (key, offset) in pairs {
+                        let _ = write!(res, "\n{}{:12}: {}", SWITCH_INDENT, key, target(offset));
+                    }
+                    let default = target(default);
+                    let _ = write!(res, "\n{}     default: {}\n{}}}", SWITCH_INDENT, default, SWITCH_INDENT);
+                    res
+                }
+                (Instruction::Newarray { atype }, _) => format!(" {}", ARRAY_TYPES[*atype as usize - 4]),
+                (x, _) => match (x.local_access(), x.branch_offsets().first()) {
+                    // Not the forms such as `iload_0` that imply their index.
+                    (Some(access), _) if x.operand_format() == Some(OperandFormat::LocalIndex) => {
+                        access.index.to_string()
+                    }
+                    (_, Some(offset)) => target(offset).to_string(),
+                    _ => String::new(),
+                },
+            };
+            let mnemonic = match &instruction {
+                Instruction::Unknown { opcode, .. } => format!("bytecode {}", opcode),
+                x if x.is_wide() => format!("{}_w", x.mnemonic()),
+                x => x.mnemonic().to_string(),
+            };
+            let mut line = format!("    {:4}: {:<13} {}", pc, mnemonic, operands);
+            if let Some(comment) = comment {
+                let width = COMMENT_COLUMN.saturating_sub(line.len()).max(1);
+                let _ = write!(line, "{:width$}{}", "", comment, width = width);
+            }
+            writeln!(out, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+
+    /// The listing of a `Code` attribute, with its exception table.
+    fn javap_code(&self, data: &[u8], out: &mut String) -> Result<()> {
+        let r = &mut Reader::new(data, "Code");
+        r.bytes(4)?;
+        let length = r.u32()?;
+        r.bytes(length as usize)?;
+        writeln!(out, "    Code:")?;
+        let code = crate::attributes::decode::<Code>("Code", data, 0, self)?;
+        self.javap_instructions(&code, out)?;
+        let count = r.u16()?;
+        if count > 0 {
+            writeln!(out, "    Exception table:")?;
+            writeln!(out, "       from    to  target type")?;
+        }
+        for _ in 0..count {
+            let (start, end, handler) = (r.u16()?, r.u16()?, r.u16()?);
+            let catch_type = match r.u16()? {
+                0 => "any".to_string(),
+                x => format!("Class {}", checked_name(ClassIndex(x).get_as_string(self)?)),
+            };
+            writeln!(out, "       {:5} {:5} {:5}   {}", start, end, handler, catch_type)?;
+        }
+        Ok(())
+    }
+
+    fn javap_field(&self, field: &Field, out: &mut String) -> Result<()> {
+        writeln!(
+            out,
+            "  {}{} {};",
+            modifier_words(&field.field_inner.access_flags, &FIELD_MODIFIERS),
+            field.descriptor()?.to_java_type(),
+            field.identifier()?
+        )?;
+        Ok(())
+    }
+
+    fn javap_method(&self, method: &Method, out: &mut String) -> Result<()> {
+        let flags = &method.method_inner.access_flags;
+        let name = method.identifier()?;
+        let descriptor = method.descriptor()?;
+        let mut modifiers = modifier_words(flags, &METHOD_MODIFIERS);
+        if self.access_flags.contains(ClassAccessFlags::INTERFACE)
+            && !flags.intersects(MethodAccessFlags::ABSTRACT | MethodAccessFlags::STATIC | MethodAccessFlags::PRIVATE)
+            && self._major_version >= 52
+        {
+            modifiers.push_str("default ");
+        }
+        let mut parameters = descriptor
+            .parameter_types()
+            .iter()
+            .map(|x| x.to_java_type())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if flags.contains(MethodAccessFlags::VARARGS) && parameters.ends_with("[]") {
+            parameters.truncate(parameters.len() - 2);
+            parameters.push_str("...");
+        }
+        write!(out, "  {}", modifiers)?;
+        match name {
+            "<init>" => write!(out, "{}({})", java_name(self.this_class()?), parameters)?,
+            "<clinit>" => write!(out, "{{}}")?,
+            _ => {
+                let return_type = descriptor.return_type().map_or_else(|| "void".to_string(), |x| x.to_java_type());
+                write!(out, "{} {}({})", return_type, name, parameters)?;
+            }
+        }
+        if let Some(exceptions) = method.exceptions()? {
+            let names = exceptions.class_names()?;
+            if !names.is_empty() {
+                let names = names.into_iter().map(java_name).collect::<Vec<_>>();
+                write!(out, " throws {}", names.join(", "))?;
+            }
+        }
+        writeln!(out, ";")?;
+        if let Some(code) = method.method_inner.attributes.get("Code") {
+            self.javap_code(code, out)?;
+        }
+        Ok(())
+    }
+
+    /// The declaration and directives of a `module-info` class.
+    fn javap_module(&self, out: &mut String) -> Result<bool> {
+        let module = match self.module()? {
+            Some(x) => x,
+            None => return Ok(false),
+        };
+        if module.flags.contains(ModuleFlags::OPEN) {
+            write!(out, "open ")?;
+        }
+        write!(out, "module {}", module.name)?;
+        if let Some(version) = module.version {
+            write!(out, "@{}", version)?;
+        }
+        writeln!(out, " {{")?;
+        for x in &module.requires {
+            write!(out, "  requires ")?;
+            if x.flags.contains(RequiresFlags::STATIC_PHASE) {
+                write!(out, "static ")?;
+            }
+            if x.flags.contains(RequiresFlags::TRANSITIVE) {
+                write!(out, "transitive ")?;
+            }
+            writeln!(out, "{};", x.module)?;
+        }
+        for (keyword, directives) in [("exports", &module.exports), ("opens", &module.opens)] {
+            for x in directives {
+                write!(out, "  {} {}", keyword, java_name(x.package))?;
+                if !x.to.is_empty() {
+                    write!(out, " to\n    {}", x.to.join(",\n    "))?;
+                }
+                writeln!(out, ";")?;
+            }
+        }
+        for x in &module.uses {
+            writeln!(out, "  uses {};", java_name(x))?;
+        }
+        for x in &module.provides {
+            let with = x.with.iter().map(|x| java_name(x)).collect::<Vec<_>>();
+            writeln!(out, "  provides  {} with\n    {};", java_name(x.service), with.join(",\n    "))?;
+        }
+        Ok(true)
+    }
+
+    /// A listing of the class as `javap -c -p` writes it: its declaration,
+    /// then every field and method, with the instructions of each method by
+    /// pc and the constants they refer to in comments.
+    ///
+    /// Types are written erased, as descriptors give them, rather than with
+    /// the type arguments of `Signature` attributes.
+    pub fn disassemble(&self) -> Result<String> {
+        let mut out = String::new();
+        if let Some(source_file) = self.source_file()? {
+            writeln!(out, "Compiled from \"{}\"", source_file.get()?)?;
+        }
+        if self.access_flags.contains(ClassAccessFlags::MODULE) && self.javap_module(&mut out)? {
+            writeln!(out, "}}")?;
+            return Ok(out);
+        }
+
+        let interface = self.access_flags.contains(ClassAccessFlags::INTERFACE);
+        let mut flags = ClassAccessFlags::from_bits_retain(self.access_flags.bits());
+        if interface {
+            flags.remove(ClassAccessFlags::ABSTRACT);
+        }
+        write!(out, "{}", modifier_words(&flags, &CLASS_MODIFIERS))?;
+        write!(out, "{} {}", if interface { "interface" } else { "class" }, java_name(self.this_class()?))?;
+        if !interface && self.super_class.0 != 0 && self.super_class()? != "java/lang/Object" {
+            write!(out, " extends {}", java_name(self.super_class()?))?;
+        }
+        let interfaces = self.interfaces()?;
+        if !interfaces.is_empty() {
+            let separator = match self.attributes.get("Signature") {
+                Some(_) => ", ",
+                None => ",",
+            };
+            let names = interfaces.into_iter().map(java_name).collect::<Vec<_>>();
+            let keyword = if interface { "extends" } else { "implements" };
+            write!(out, " {} {}", keyword, names.join(separator))?;
+        }
+        writeln!(out, " {{")?;
+
+        for field in self.fields() {
+            self.javap_field(&field, &mut out)?;
+            writeln!(out)?;
+        }
+        let mut methods = Vec::new();
+        for method in self.methods() {
+            let mut text = String::new();
+            self.javap_method(&method, &mut text)?;
+            methods.push(text);
+        }
+        out.push_str(&methods.join("\n"));
+        writeln!(out, "}}")?;
+        Ok(out)
+    }
+}
@@ -0,0 +1,334 @@
+//! Encodes instructions back into a `code` array, the inverse of
+//! [`crate::attributes::Code::instructions`].
+
+use crate::{
+    builder::ConstantPoolBuilder,
+    owned::{
+        OwnedDynamicConstant, OwnedFieldRef, OwnedInstruction, OwnedLoadableConstant, OwnedMethodHandle,
+        OwnedMethodRef,
+    },
+    ClassFile, Error, Result, Span,
+};
+
+const WIDE: u8 = 0xc4;
+
+fn error(pc: u32, message: impl std::fmt::Display) -> Error {
+    Error::WriteError(format!("{} at pc {}", message, pc))
+}
+
+struct Encoder {
+    pool: ConstantPoolBuilder,
+    code: Vec<u8>,
+}
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.code.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.code.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.code.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn field(&mut self, field: &OwnedFieldRef) -> Result<u16> {
+        Ok(self.pool.field_ref(&field.class, &field.name, &field.descriptor)?.0)
+    }
+
+    fn method(&mut self, method: &OwnedMethodRef) -> Result<u16> {
+        Ok(if method.interface {
+            self.pool
+                .interface_method_ref(&method.class, &method.name, &method.descriptor)?
+                .0
+        } else {
+            self.pool.method_ref(&method.class, &method.name, &method.descriptor)?.0
+        })
+    }
+
+    fn method_handle(&mut self, handle: &OwnedMethodHandle) -> Result<u16> {
+        let (kind, reference) = match handle {
+            OwnedMethodHandle::GetField(x) => (1, self.field(x)?),
+            OwnedMethodHandle::GetStatic(x) => (2, self.field(x)?),
+            OwnedMethodHandle::PutField(x) => (3, self.field(x)?),
+            OwnedMethodHandle::PutStatic(x) => (4, self.field(x)?),
+            OwnedMethodHandle::InvokeVirtual(x) => (5, self.method(x)?),
+            OwnedMethodHandle::InvokeStatic(x) => (6, self.method(x)?),
+            OwnedMethodHandle::InvokeSpecial(x) => (7, self.method(x)?),
+            OwnedMethodHandle::NewInvokeSpecial(x) => (8, self.method(x)?),
+            OwnedMethodHandle::InvokeInterface(x) => (9, self.method(x)?),
+        };
+        Ok(self.pool.method_handle(kind, reference)?.0)
+    }
+
+    fn dynamic(&mut self, constant: &OwnedDynamicConstant) -> Result<u16> {
+        Ok(self
+            .pool
+            .dynamic(constant.bootstrap_method.index, &constant.name, &constant.descriptor)?
+            .0)
+    }
+
+    fn loadable(&mut self, constant: &OwnedLoadableConstant) -> Result<u16> {
+        Ok(match constant {
+            OwnedLoadableConstant::Int(x) => self.pool.integer(*x)?.0,
+            OwnedLoadableConstant::Float(x) => self.pool.float(*x)?.0,
+            OwnedLoadableConstant::Long(x) => self.pool.long(*x)?.0,
+            OwnedLoadableConstant::Double(x) => self.pool.double(*x)?.0,
+            OwnedLoadableConstant::String(x) => self.pool.string(x)?.0,
+            OwnedLoadableConstant::Class(x) => self.pool.class(x)?.0,
+            OwnedLoadableConstant::MethodType(x) => self.pool.method_type(x)?.0,
+            OwnedLoadableConstant::MethodHandle(x) => self.method_handle(x)?,
+            OwnedLoadableConstant::Dynamic(x) => self.dynamic(x)?,
+        })
+    }
+
+    fn narrow(&mut self, pc: u32, index: u16) -> Result<()> {
+        let index = u8::try_from(index).map_err(|_| error(pc, format_args!("local {} needs the wide form", index)))?;
+        self.u8(index);
+        Ok(())
+    }
+
+    fn branch(&mut self, pc: u32, offset: i32) -> Result<()> {
+        let offset = i16::try_from(offset)
+            .map_err(|_| error(pc, format_args!("branch offset {} needs the _w form", offset)))?;
+        self.u16(offset as u16);
+        Ok(())
+    }
+
+    /// Pads with zeros up to the four byte boundary after the opcode at `pc`.
+    fn pad(&mut self, pc: u32) {
+        let padding = (4 - (pc as usize + 1) % 4) % 4;
+        self.code.resize(self.code.len() + padding, 0);
+    }
+
+    fn instruction(&mut self, instruction: &OwnedInstruction) -> Result<()> {
+        let pc = self.code.len() as u32;
+        if instruction.is_wide() {
+            self.u8(WIDE);
+        }
+        self.u8(instruction.opcode());
+        match instruction {
+            OwnedInstruction::Bipush { value } => self.u8(*value as u8),
+            OwnedInstruction::Sipush { value } => self.u16(*value as u16),
+            OwnedInstruction::Ldc { constant, .. } => {
+                let index = self.loadable(constant)?;
+                let index =
+                    u8::try_from(index).map_err(|_| error(pc, format_args!("constant {} needs ldc_w", index)))?;
+                self.u8(index);
+            }
+            OwnedInstruction::LdcW { constant, .. } | OwnedInstruction::Ldc2W { constant, .. } => {
+                let index = self.loadable(constant)?;
+                self.u16(index);
+            }
+            OwnedInstruction::Iload { index }
+            | OwnedInstruction::Lload { index }
+            | OwnedInstruction::Fload { index }
+            | OwnedInstruction::Dload { index }
+            | OwnedInstruction::Aload { index }
+            | OwnedInstruction::Istore { index }
+            | OwnedInstruction::Lstore { index }
+            | OwnedInstruction::Fstore { index }
+            | OwnedInstruction::Dstore { index }
+            | OwnedInstruction::Astore { index }
+            | OwnedInstruction::Ret { index } => self.narrow(pc, index.0)?,
+            OwnedInstruction::Iinc { index, constant } => {
+                self.narrow(pc, index.0)?;
+                let constant = i8::try_from(*constant)
+                    .map_err(|_| error(pc, format_args!("iinc constant {} needs the wide form", constant)))?;
+                self.u8(constant as u8);
+            }
+            OwnedInstruction::WideIload { index }
+            | OwnedInstruction::WideLload { index }
+            | OwnedInstruction::WideFload { index }
+            | OwnedInstruction::WideDload { index }
+            | OwnedInstruction::WideAload { index }
+            | OwnedInstruction::WideIstore { index }
+            | OwnedInstruction::WideLstore { index }
+            | OwnedInstruction::WideFstore { index }
+            | OwnedInstruction::WideDstore { index }
+            | OwnedInstruction::WideAstore { index }
+            | OwnedInstruction::WideRet { index } => self.u16(index.0),
+            OwnedInstruction::WideIinc { index, constant } => {
+                self.u16(index.0);
+                self.u16(*constant as u16);
+            }
+            OwnedInstruction::IfAcmpeq { offset }
+            | OwnedInstruction::IfAcmpne { offset }
+            | OwnedInstruction::IfIcmpeq { offset }
+            | OwnedInstruction::IfIcmpne { offset }
+            | OwnedInstruction::IfIcmplt { offset }
+            | OwnedInstruction::IfIcmpge { offset }
+            | OwnedInstruction::IfIcmpgt { offset }
+            | OwnedInstruction::IfIcmple { offset }
+            | OwnedInstruction::Ifeq { offset }
+            | OwnedInstruction::Ifne { offset }
+            | OwnedInstruction::Iflt { offset }
+            | OwnedInstruction::Ifge { offset }
+            | OwnedInstruction::Ifgt { offset }
+            | OwnedInstruction::Ifle { offset }
+            | OwnedInstruction::Ifnonnull { offset }
+            | OwnedInstruction::Ifnull { offset }
+            | OwnedInstruction::Goto { offset }
+            | OwnedInstruction::Jsr { offset } => self.branch(pc, offset.0)?,
+            OwnedInstruction::GotoW { offset } | OwnedInstruction::JsrW { offset } => self.i32(offset.0),
+            OwnedInstruction::Tableswitch {
+                default,
+                low,
+                high,
+                jump_offsets,
+            } => {
+                if *high as i64 - *low as i64 + 1 != jump_offsets.len() as i64 {
+                    return Err(error(
+                        pc,
+                        format_args!("tableswitch from {} to {} with {} offsets", low, high, jump_offsets.len()),
+                    ));
+                }
+                self.pad(pc);
+                self.i32(default.0);
+                self.i32(*low);
+                self.i32(*high);
+                for offset in jump_offsets {
+                    self.i32(offset.0);
+                }
+            }
+            OwnedInstruction::Lookupswitch { default, pairs } => {
+                self.pad(pc);
+                self.i32(default.0);
+                self.i32(pairs.len() as i32);
+                for (key, offset) in pairs {
+                    self.i32(*key);
+                    self.i32(offset.0);
+                }
+            }
+            OwnedInstruction::Getstatic { field }
+            | OwnedInstruction::Putstatic { field }
+            | OwnedInstruction::Getfield { field }
+            | OwnedInstruction::Putfield { field } => {
+                let index = self.field(field)?;
+                self.u16(index);
+            }
+            OwnedInstruction::Invokevirtual { index }
+            | OwnedInstruction::Invokespecial { index }
+            | OwnedInstruction::Invokestatic { index } => {
+                let index = self.method(index)?;
+                self.u16(index);
+            }
+            OwnedInstruction::Invokeinterface { index, count } => {
+                let index = self.method(index)?;
+                self.u16(index);
+                self.u8(*count);
+                self.u8(0);
+            }
+            OwnedInstruction::Invokedynamic { index } => {
+                let index = self
+                    .pool
+                    .invoke_dynamic(index.bootstrap_method.index, &index.name, &index.descriptor)?
+                    .0;
+                self.u16(index);
+                self.u16(0);
+            }
+            OwnedInstruction::New { class }
+            | OwnedInstruction::Anewarray { class }
+            | OwnedInstruction::Checkcast { class }
+            | OwnedInstruction::Instanceof { class } => {
+                let index = self.pool.class(class)?.0;
+                self.u16(index);
+            }
+            OwnedInstruction::Multianewarray { class, dimensions } => {
+                let index = self.pool.class(class)?.0;
+                self.u16(index);
+                self.u8(*dimensions);
+            }
+            OwnedInstruction::Newarray { atype } => self.u8(*atype as u8),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl ClassFile {
+    /// Encodes `instructions` into the bytes of a `code` array, padding
+    /// switches for their position. Constants are looked up in the pool and
+    /// added if missing, and nothing is added if encoding fails. The `index`
+    /// kept by `ldc` and its variants is ignored in favour of the constant.
+    ///
+    /// Branch offsets are written as given, so they must already match the
+    /// encoded positions. An operand too large for its form, such as a local
+    /// above 255 without `wide`, is an error rather than widened.
+    pub fn encode_instructions(&mut self, instructions: &[OwnedInstruction]) -> Result<Vec<u8>> {
+        let mut encoder = Encoder {
+            pool: ConstantPoolBuilder::from_items(&self.constant_pool.0)?,
+            code: Vec::new(),
+        };
+        for instruction in instructions {
+            encoder.instruction(instruction)?;
+        }
+        let added = &encoder.pool.items()[self.constant_pool.0.len()..];
+        self.constant_pool.1.extend(added.iter().map(|_| Span::default()));
+        self.constant_pool.0.extend_from_slice(added);
+        Ok(encoder.code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        instruction::LocalIndex,
+        owned::{OwnedInstruction, OwnedLoadableConstant},
+        ClassFile,
+    };
+
+    const EXAMPLE: &[u8] = include_bytes!("../example.class");
+
+    /// An `ldc_w`, as the example's pool is too large for a new constant to
+    /// fit `ldc`.
+    fn ldc_w(text: &str) -> OwnedInstruction {
+        OwnedInstruction::LdcW {
+            index: 0,
+            constant: OwnedLoadableConstant::String(text.to_string()),
+        }
+    }
+
+    #[test]
+    fn decoded_instructions_encode_to_the_same_bytes() {
+        let mut class_file = ClassFile::parse(EXAMPLE).unwrap();
+        let before = class_file.constant_pool.0.len();
+        for i in 0..class_file.methods().len() {
+            let Some(code) = class_file.methods()[i].code().unwrap() else {
+                continue;
+            };
+            let expected = code.code_bytes().to_vec();
+            let owned = code
+                .instructions()
+                .unwrap()
+                .into_iter()
+                .map(|x| x.into_owned())
+                .collect::<crate::Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(class_file.encode_instructions(&owned).unwrap(), expected);
+        }
+        assert_eq!(class_file.constant_pool.0.len(), before);
+    }
+
+    #[test]
+    fn missing_constants_are_added_once() {
+        let mut class_file = ClassFile::parse(EXAMPLE).unwrap();
+        let before = class_file.constant_pool.0.len();
+        let code = class_file.encode_instructions(&[ldc_w("not in the pool")]).unwrap();
+        // A `String` and its `Utf8`.
+        assert_eq!(class_file.constant_pool.0.len(), before + 2);
+        assert_eq!(class_file.encode_instructions(&[ldc_w("not in the pool")]).unwrap(), code);
+        assert_eq!(class_file.constant_pool.0.len(), before + 2);
+    }
+
+    #[test]
+    fn failed_encoding_adds_nothing_to_the_pool() {
+        let mut class_file = ClassFile::parse(EXAMPLE).unwrap();
+        let before = class_file.constant_pool.0.len();
+        let instructions = [ldc_w("not in the pool"), OwnedInstruction::Iload { index: LocalIndex(300) }];
+        assert!(class_file.encode_instructions(&instructions).is_err());
+        assert_eq!(class_file.constant_pool.0.len(), before);
+    }
+}
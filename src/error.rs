@@ -1,33 +1,95 @@
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-    #[error("Invalid Constant Pool {0}")]
-    ConstantPoolError(String),
-    #[error("Binary Parsing Error")]
-    BinrwError(#[from] binrw::Error),
-    #[error("Text Processing Error")]
-    NomError(nom::Err<nom::error::Error<String>>),
-    #[error("IO Error")]
-    IoError(#[from] std::io::Error),
-    #[error("InvokeDynamic instruction found without a class BootstrapMethods attribute")]
-    NoBootstrapMethods,
-    #[error("Invalid bootstrap method index `{0}`.")]
-    InvalidBootstrapIndex(u16), 
-}
-
-impl<'a> From<nom::Err<nom::error::Error<&'a str>>> for Error {
-    fn from(value: nom::Err<nom::error::Error<&'a str>>) -> Self {
-        match value {
-            nom::Err::Incomplete(x) => Self::NomError(nom::Err::Incomplete(x)),
-            nom::Err::Error(x) => Self::NomError(nom::Err::Error(nom::error::Error::new(
-                x.input.to_string(),
-                x.code,
-            ))),
-            nom::Err::Failure(x) => Self::NomError(nom::Err::Failure(nom::error::Error::new(
-                x.input.to_string(),
-                x.code,
-            ))),
-        }
-    }
-}
-
-pub type Result<T> = std::result::Result<T, Error>;
\ No newline at end of file
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid Constant Pool {0}")]
+    ConstantPoolError(String),
+    #[error("Binary Parsing Error")]
+    BinrwError(binrw::Error),
+    #[error("Text Processing Error")]
+    NomError(nom::Err<nom::error::Error<String>>),
+    #[error("IO Error")]
+    IoError(#[from] std::io::Error),
+    #[error("InvokeDynamic instruction found without a class BootstrapMethods attribute")]
+    NoBootstrapMethods,
+    #[error("Invalid bootstrap method index `{0}`.")]
+    InvalidBootstrapIndex(u16), 
+    #[error("Utf8 constant {index} is not valid modified UTF-8 at offset {offset}")]
+    InvalidUtf8 { index: u16, offset: u64 },
+    #[error("Unsupported class file version {major}.{minor}")]
+    UnsupportedVersion { major: u16, minor: u16 },
+    #[error("Length {length} at offset {offset} exceeds the {remaining} bytes remaining")]
+    LengthExceedsInput {
+        offset: u64,
+        length: u64,
+        remaining: u64,
+    },
+    #[error("Attribute {name} declares {declared} bytes but its contents need {required}")]
+    AttributeLength {
+        name: String,
+        declared: u32,
+        required: u64,
+    },
+    #[error("Invalid ConstantValue attribute {0}")]
+    InvalidConstantValue(String),
+    #[error("Branch at pc {pc} with offset {offset} does not land on an instruction")]
+    InvalidBranchTarget { pc: u32, offset: i32 },
+    #[error("Exception handler pc {pc} is not the start of an instruction")]
+    InvalidExceptionHandler { pc: u16 },
+    #[error("Analysis Error at pc {pc}: {message}")]
+    AnalysisError { pc: u32, message: String },
+    #[error("Evaluation Error {0}")]
+    EvaluationError(String),
+    #[error("Write Error {0}")]
+    WriteError(String),
+    #[error("Assembly Error {0}")]
+    AssemblyError(String),
+    #[error("Archive Error {0}")]
+    ArchiveError(String),
+    #[error("Class {0} could not be resolved")]
+    ClassNotFound(String),
+    #[error("No member {class}.{name} with descriptor {descriptor}")]
+    NoSuchMember {
+        class: String,
+        name: String,
+        descriptor: String,
+    },
+    #[error("Incompatible Class Change {0}")]
+    IncompatibleClassChange(String),
+}
+
+impl<'a> From<nom::Err<nom::error::Error<&'a str>>> for Error {
+    fn from(value: nom::Err<nom::error::Error<&'a str>>) -> Self {
+        match value {
+            nom::Err::Incomplete(x) => Self::NomError(nom::Err::Incomplete(x)),
+            nom::Err::Error(x) => Self::NomError(nom::Err::Error(nom::error::Error::new(
+                x.input.to_string(),
+                x.code,
+            ))),
+            nom::Err::Failure(x) => Self::NomError(nom::Err::Failure(nom::error::Error::new(
+                x.input.to_string(),
+                x.code,
+            ))),
+        }
+    }
+}
+
+/// Errors this crate raises while reading come out as themselves rather than
+/// wrapped in [`Error::BinrwError`].
+impl From<binrw::Error> for Error {
+    fn from(value: binrw::Error) -> Self {
+        match value {
+            binrw::Error::Backtrace(x) if x.error.custom_err::<Error>().is_some() => Self::from(*x.error),
+            binrw::Error::Custom { pos, err } => match err.downcast::<Error>() {
+                Ok(x) => *x,
+                Err(err) => Self::BinrwError(binrw::Error::Custom { pos, err }),
+            },
+            x => Self::BinrwError(x),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+impl From<std::fmt::Error> for Error {
+    fn from(value: std::fmt::Error) -> Self {
+        Self::WriteError(value.to_string())
+    }
+}
@@ -0,0 +1,306 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use binrw::BinRead;
+
+use crate::{
+    attributes::Code,
+    descriptor_cache::DescriptorCache,
+    instruction::Instruction,
+    raw::{
+        read_attribute_info, AttributeEntry, Attributes, ClassAccessFlags, ClassIndex, ConstantPool,
+        ConstantPoolItem, FieldAccessFlags, MethodAccessFlags, Utf8Index,
+    },
+    options, ClassFile, Error, Leniency, ParseOptions, Result, Span, VersionPolicy,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeOwner {
+    Class,
+    Field,
+    Method,
+    Code,
+}
+
+/// A single step of a streaming parse, see [`EventParser`].
+#[derive(Debug)]
+pub enum Event<'a, 'b> {
+    Header {
+        minor_version: u16,
+        major_version: u16,
+    },
+    Constant {
+        index: u16,
+        item: &'a ConstantPoolItem,
+    },
+    Class {
+        access_flags: &'a ClassAccessFlags,
+        this_class: &'a str,
+        super_class: Option<&'a str>,
+        interfaces: Vec<&'a str>,
+    },
+    Field {
+        access_flags: FieldAccessFlags,
+        name: &'a str,
+        descriptor: &'a str,
+    },
+    Method {
+        access_flags: MethodAccessFlags,
+        name: &'a str,
+        descriptor: &'a str,
+    },
+    /// An attribute of the most recent class, field, method or Code item.
+    Attribute {
+        owner: AttributeOwner,
+        name: &'a str,
+        data: &'b [u8],
+    },
+    Instruction {
+        pc: u32,
+        instruction: &'b Instruction<'a>,
+    },
+    End,
+}
+
+/// A push parser that reports a class file as a sequence of [`Event`]s.
+///
+/// Only the constant pool and the class's `BootstrapMethods` attribute are kept
+/// in memory; members, attributes and instructions are dropped as soon as the
+/// callback returns. Returning an error from the callback stops the parse.
+#[derive(Debug, Clone)]
+pub struct EventParser {
+    decode_instructions: bool,
+    options: ParseOptions,
+}
+
+impl Default for EventParser {
+    fn default() -> Self {
+        Self {
+            decode_instructions: true,
+            options: ParseOptions::default(),
+        }
+    }
+}
+
+fn skip_attributes<R: Read + Seek>(reader: &mut R) -> Result<()> {
+    let count = u16::read_be(reader)?;
+    for _ in 0..count {
+        reader.seek(SeekFrom::Current(2))?;
+        let len = u32::read_be(reader)?;
+        reader.seek(SeekFrom::Current(len as i64))?;
+    }
+    Ok(())
+}
+
+fn skip_members<R: Read + Seek>(reader: &mut R) -> Result<()> {
+    let count = u16::read_be(reader)?;
+    for _ in 0..count {
+        reader.seek(SeekFrom::Current(6))?;
+        skip_attributes(reader)?;
+    }
+    Ok(())
+}
+
+fn read_attribute<R: Read + Seek>(
+    reader: &mut R,
+    cpool: &ConstantPool,
+    leniency: Leniency,
+) -> Result<AttributeEntry> {
+    let name_index = Utf8Index::read_be(reader)?;
+    let name = name_index.get_as_string_impl(cpool).unwrap_or("").to_string();
+    let len = u32::read_be(reader)?;
+    let (data, _) = read_attribute_info(reader, len, leniency)?;
+    Ok(AttributeEntry {
+        name_index: name_index.0,
+        name,
+        data,
+        span: Span::default(),
+    })
+}
+
+impl EventParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `Code` attributes are decoded into [`Event::Instruction`]s.
+    pub fn with_instructions(mut self, decode_instructions: bool) -> Self {
+        self.decode_instructions = decode_instructions;
+        self
+    }
+
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn parse<R, F>(&self, reader: &mut R, mut callback: F) -> Result<()>
+    where
+        R: Read + Seek,
+        F: FnMut(Event<'_, '_>) -> Result<()>,
+    {
+        let pos = reader.stream_position()?;
+        let magic = u32::read_be(reader)?;
+        if magic != 0xcafebabe {
+            return Err(binrw::Error::BadMagic {
+                pos,
+                found: Box::new(magic),
+            }
+            .into());
+        }
+        let minor_version = u16::read_be(reader)?;
+        let major_version = u16::read_be(reader)?;
+        let known = options::MIN_MAJOR_VERSION..=options::MAX_KNOWN_MAJOR_VERSION;
+        if !known.contains(&major_version) && self.options.version_policy == VersionPolicy::Reject {
+            return Err(Error::UnsupportedVersion {
+                major: major_version,
+                minor: minor_version,
+            });
+        }
+        callback(Event::Header {
+            minor_version,
+            major_version,
+        })?;
+
+        let constant_pool = ConstantPool::read_be_args(reader, (&self.options, None))?;
+        let access_flags = ClassAccessFlags::from_bits_retain(u16::read_be(reader)?);
+        let this_class = ClassIndex::read_be(reader)?;
+        let super_class = ClassIndex::read_be(reader)?;
+        let interfaces_count = u16::read_be(reader)?;
+        let interfaces = (0..interfaces_count)
+            .map(|_| ClassIndex::read_be(reader))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        // Instruction decoding needs the class's BootstrapMethods, which is stored
+        // after all members, so skip ahead to fetch it and come back.
+        let members_start = reader.stream_position()?;
+        skip_members(reader)?;
+        skip_members(reader)?;
+        let mut class_attributes = Vec::new();
+        let count = u16::read_be(reader)?;
+        for _ in 0..count {
+            let attribute = read_attribute(reader, &constant_pool, self.options.leniency)?;
+            if attribute.name == "BootstrapMethods" && self.options.keeps_attribute(&attribute.name) {
+                class_attributes.push(attribute);
+            }
+        }
+        reader.seek(SeekFrom::Start(members_start))?;
+
+        let constant_pool_count = constant_pool.0.len();
+        let skeleton = ClassFile {
+            _minor_version: minor_version,
+            _major_version: major_version,
+            constant_pool,
+            access_flags,
+            this_class,
+            super_class,
+            interfaces,
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Attributes(class_attributes),
+            span: Span::default(),
+            leniency: self.options.leniency,
+            metrics: self.options.metrics.clone(),
+            attribute_filter: self.options.attribute_filter.clone(),
+            warnings: Vec::new(),
+            descriptors: DescriptorCache::new(constant_pool_count),
+        };
+        let cpool = &skeleton.constant_pool;
+
+        for (i, item) in cpool.0.iter().enumerate() {
+            if !matches!(item, ConstantPoolItem::Skip) {
+                callback(Event::Constant {
+                    index: i as u16 + 1,
+                    item,
+                })?;
+            }
+        }
+        callback(Event::Class {
+            access_flags: &skeleton.access_flags,
+            this_class: skeleton.this_class()?,
+            super_class: match skeleton.super_class.0 {
+                0 => None,
+                _ => Some(skeleton.super_class()?),
+            },
+            interfaces: skeleton.interfaces()?,
+        })?;
+
+        let fields_count = u16::read_be(reader)?;
+        for _ in 0..fields_count {
+            let access_flags = FieldAccessFlags::from_bits_retain(u16::read_be(reader)?);
+            let name = Utf8Index::read_be(reader)?.get_as_string_impl(cpool)?;
+            let descriptor = Utf8Index::read_be(reader)?.get_as_string_impl(cpool)?;
+            callback(Event::Field {
+                access_flags,
+                name,
+                descriptor,
+            })?;
+            self.attributes(reader, &skeleton, AttributeOwner::Field, &mut callback)?;
+        }
+
+        let methods_count = u16::read_be(reader)?;
+        for _ in 0..methods_count {
+            let access_flags = MethodAccessFlags::from_bits_retain(u16::read_be(reader)?);
+            let name = Utf8Index::read_be(reader)?.get_as_string_impl(cpool)?;
+            let descriptor = Utf8Index::read_be(reader)?.get_as_string_impl(cpool)?;
+            callback(Event::Method {
+                access_flags,
+                name,
+                descriptor,
+            })?;
+            self.attributes(reader, &skeleton, AttributeOwner::Method, &mut callback)?;
+        }
+
+        self.attributes(reader, &skeleton, AttributeOwner::Class, &mut callback)?;
+        callback(Event::End)
+    }
+
+    fn attributes<R, F>(
+        &self,
+        reader: &mut R,
+        skeleton: &ClassFile,
+        owner: AttributeOwner,
+        callback: &mut F,
+    ) -> Result<()>
+    where
+        R: Read + Seek,
+        F: FnMut(Event<'_, '_>) -> Result<()>,
+    {
+        let count = u16::read_be(reader)?;
+        for _ in 0..count {
+            let name_index = Utf8Index::read_be(reader)?;
+            let name = name_index
+                .get_as_string_impl(&skeleton.constant_pool)
+                .unwrap_or("");
+            let len = u32::read_be(reader)?;
+            let offset = reader.stream_position()?;
+            let (data, _) = read_attribute_info(reader, len, self.options.leniency)?;
+            if !self.options.keeps_attribute(name) {
+                continue;
+            }
+            callback(Event::Attribute {
+                owner,
+                name,
+                data: &data,
+            })?;
+            if owner == AttributeOwner::Method && name == "Code" && self.decode_instructions {
+                let code = crate::attributes::decode::<Code>("Code", &data, offset, skeleton)?;
+                let mut pc = 0;
+                while let Some(x) = code.instruction_at(pc)? {
+                    callback(Event::Instruction {
+                        pc,
+                        instruction: &x.instruction,
+                    })?;
+                    pc += x.length;
+                }
+                for x in code.attributes.iter() {
+                    callback(Event::Attribute {
+                        owner: AttributeOwner::Code,
+                        name: &x.name,
+                        data: &x.data,
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
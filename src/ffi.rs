@@ -0,0 +1,243 @@
+//! C bindings, enabled by the `ffi` feature. See `include/java_class_format.h`.
+//!
+//! The crate builds as an rlib only; a shared library for C is built with
+//! `cargo rustc --lib --release --crate-type cdylib --features ffi`.
+//!
+//! Handles returned by `jcf_*_open` and `jcf_method_instructions` are owned by
+//! the caller and must be released with the matching `*_free` function. Strings
+//! returned as `char *` are owned by the caller and released with
+//! [`jcf_string_free`]; strings returned as `const char *` are borrowed from a
+//! handle and live as long as it does. Functions that fail return `NULL` or a
+//! negative value and leave a message for [`jcf_last_error`].
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CString},
+    ptr,
+};
+
+use crate::{field::Field, method::Method, ClassFile, Result};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: String) {
+    let message = CString::new(message.replace('\0', "\\0")).unwrap_or_default();
+    LAST_ERROR.with(|x| *x.borrow_mut() = Some(message));
+}
+
+fn to_c_string(value: Result<&str>) -> *mut c_char {
+    match value.map(CString::new) {
+        Ok(Ok(x)) => x.into_raw(),
+        Ok(Err(_)) => {
+            set_error("string contains a NUL character".to_string());
+            ptr::null_mut()
+        }
+        Err(e) => {
+            set_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+unsafe fn field<'a>(class: *const ClassFile, index: usize) -> Option<Field<'a>> {
+    let class = class.as_ref()?;
+    let field = class.fields().into_iter().nth(index);
+    if field.is_none() {
+        set_error(format!("no field at index {}", index));
+    }
+    field
+}
+
+unsafe fn method<'a>(class: *const ClassFile, index: usize) -> Option<Method<'a>> {
+    let class = class.as_ref()?;
+    let method = class.methods().into_iter().nth(index);
+    if method.is_none() {
+        set_error(format!("no method at index {}", index));
+    }
+    method
+}
+
+/// The message of the last error on this thread, or `NULL`. Borrowed until the
+/// next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn jcf_last_error() -> *const c_char {
+    LAST_ERROR.with(|x| x.borrow().as_ref().map_or(ptr::null(), |x| x.as_ptr()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Parses `len` bytes at `data`, which are copied and need not outlive the call.
+#[no_mangle]
+pub unsafe extern "C" fn jcf_class_open(data: *const u8, len: usize) -> *mut ClassFile {
+    if data.is_null() {
+        set_error("data is NULL".to_string());
+        return ptr::null_mut();
+    }
+    let data = std::slice::from_raw_parts(data, len).to_vec();
+    match ClassFile::parse(data) {
+        Ok(x) => Box::into_raw(Box::new(x)),
+        Err(e) => {
+            set_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_class_free(class: *mut ClassFile) {
+    if !class.is_null() {
+        drop(Box::from_raw(class));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_class_name(class: *const ClassFile) -> *mut c_char {
+    match class.as_ref() {
+        Some(x) => to_c_string(x.this_class()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// `NULL` without an error for `java/lang/Object` and module descriptors.
+#[no_mangle]
+pub unsafe extern "C" fn jcf_class_super_name(class: *const ClassFile) -> *mut c_char {
+    match class.as_ref() {
+        Some(x) if x.super_class.0 != 0 => to_c_string(x.super_class()),
+        _ => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_class_access_flags(class: *const ClassFile) -> u16 {
+    class.as_ref().map_or(0, |x| x.access_flags.bits())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_class_major_version(class: *const ClassFile) -> u16 {
+    class.as_ref().map_or(0, |x| x.major_version())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_class_field_count(class: *const ClassFile) -> usize {
+    class.as_ref().map_or(0, |x| x.fields.len())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_class_method_count(class: *const ClassFile) -> usize {
+    class.as_ref().map_or(0, |x| x.methods.len())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_field_name(class: *const ClassFile, index: usize) -> *mut c_char {
+    field(class, index).map_or(ptr::null_mut(), |x| to_c_string(x.identifier()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_field_descriptor(class: *const ClassFile, index: usize) -> *mut c_char {
+    field(class, index).map_or(ptr::null_mut(), |x| {
+        to_c_string(x.field_inner.descriptor_index.get_as_string(x.class_file))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_field_access_flags(class: *const ClassFile, index: usize) -> u16 {
+    field(class, index).map_or(0, |x| x.field_inner.access_flags.bits())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_method_name(class: *const ClassFile, index: usize) -> *mut c_char {
+    method(class, index).map_or(ptr::null_mut(), |x| to_c_string(x.identifier()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_method_descriptor(class: *const ClassFile, index: usize) -> *mut c_char {
+    method(class, index).map_or(ptr::null_mut(), |x| {
+        to_c_string(x.method_inner.descriptor_index.get_as_string(x.class_file))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_method_access_flags(class: *const ClassFile, index: usize) -> u16 {
+    method(class, index).map_or(0, |x| x.method_inner.access_flags.bits())
+}
+
+/// The decoded instructions of a method, see [`jcf_method_instructions`].
+pub struct Instructions(Vec<(u32, CString)>);
+
+/// Decodes the instructions of a method. Returns `NULL` with an error if the
+/// method has no `Code` attribute or it fails to decode.
+#[no_mangle]
+pub unsafe extern "C" fn jcf_method_instructions(
+    class: *const ClassFile,
+    index: usize,
+) -> *mut Instructions {
+    let method = match method(class, index) {
+        Some(x) => x,
+        None => return ptr::null_mut(),
+    };
+    let instructions = match method.code() {
+        Ok(Some(code)) => method
+            .class_file
+            .instructions_text(&code, &mut Default::default()),
+        Ok(None) => {
+            set_error("method has no Code attribute".to_string());
+            return ptr::null_mut();
+        }
+        Err(e) => Err(e),
+    };
+    match instructions {
+        Ok(x) => Box::into_raw(Box::new(Instructions(
+            x.into_iter()
+                .map(|(pc, text)| {
+                    let text = text.replace('\0', "\\0");
+                    (pc, CString::new(text).unwrap_or_default())
+                })
+                .collect(),
+        ))),
+        Err(e) => {
+            set_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_instructions_len(instructions: *const Instructions) -> usize {
+    instructions.as_ref().map_or(0, |x| x.0.len())
+}
+
+/// The pc of instruction `index`, or -1 if out of range.
+#[no_mangle]
+pub unsafe extern "C" fn jcf_instructions_pc(instructions: *const Instructions, index: usize) -> i64 {
+    instructions
+        .as_ref()
+        .and_then(|x| x.0.get(index))
+        .map_or(-1, |x| x.0 as i64)
+}
+
+/// A textual rendering of instruction `index`, borrowed from `instructions`.
+#[no_mangle]
+pub unsafe extern "C" fn jcf_instructions_text(
+    instructions: *const Instructions,
+    index: usize,
+) -> *const c_char {
+    instructions
+        .as_ref()
+        .and_then(|x| x.0.get(index))
+        .map_or(ptr::null(), |x| x.1.as_ptr())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jcf_instructions_free(instructions: *mut Instructions) {
+    if !instructions.is_null() {
+        drop(Box::from_raw(instructions));
+    }
+}
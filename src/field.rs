@@ -1,106 +1,288 @@
-use std::fmt::Debug;
-
-use binrw::BinRead;
-use nom::{branch::alt, bytes::complete::{is_not, tag}, character::complete::char, combinator::{map, value}, sequence::{delimited, preceded}, IResult};
-
-use crate::{attributes::{ConstantValue, Signature}, raw::FieldRaw, signature::ReferenceType, ClassFile};
-
-#[derive(Clone, Debug)]
-pub enum TypeDescriptor<'a> {
-    Byte,
-    Char,
-    Double,
-    Float,
-    Int,
-    Long,
-    Short,
-    Boolean,
-    String,
-    Class,
-    Array(Box<TypeDescriptor<'a>>),
-    ClassName(&'a str),
-}
-
-impl<'a> TypeDescriptor<'a> {
-    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        alt((
-            value(Self::Byte, char('B')),
-            value(Self::Char, char('C')),
-            value(Self::Double, char('D')),
-            value(Self::Float, char('F')),
-            value(Self::Int, char('I')),
-            value(Self::Long, char('J')),
-            value(Self::Short, char('S')),
-            value(Self::Boolean, char('Z')),
-            value(Self::String, tag("Ljava/lang/String;")),
-            value(Self::Class, tag("Ljava/lang/Class;")),
-            map(delimited(char('L'), is_not(";"), char(';')), |x| {
-                Self::ClassName(x)
-            }),
-            map(preceded(char('['), Self::parse), |x| {
-                Self::Array(Box::new(x))
-            }),
-        ))(input)
-    }
-}
-
-pub struct Field<'a> {
-    pub(crate) class_file: &'a ClassFile,
-    pub(crate) field_inner: &'a FieldRaw,
-}
-
-macro_rules! attribute {
-    ($strct:ident, $name:ident) => {
-        pub fn $name(&self) -> crate::Result<Option<$strct<'a>>> {
-            match self.field_inner.attributes.0.get(stringify!($strct)) {
-                Some(x) => {
-                    let mut buf = std::io::Cursor::new(&x[..]);
-                    let value = $strct::read_be_args(&mut buf, (self.class_file,))?;
-                    Ok(Some(value))
-                }
-                None => Ok(None),
-            }
-        }
-    };
-}
-
-impl<'a> Field<'a> {
-    pub fn identifier(&self) -> crate::Result<&'a str> {
-        self.field_inner.name_index.get_as_string(self.class_file)
-    }
-
-    pub fn descriptor(&self) -> crate::Result<TypeDescriptor<'a>> {
-        let raw_descriptor = self
-            .field_inner
-            .descriptor_index
-            .get_as_string(self.class_file)?;
-        let (_input, ty) = TypeDescriptor::parse(raw_descriptor)?;
-        Ok(ty)
-    }
-
-    attribute!(ConstantValue, constant_value);
-
-    pub fn signature(&self) -> crate::Result<Option<ReferenceType<'a>>> {
-        match self.field_inner.attributes.0.get("Signature") {
-            Some(x) => {
-                let mut buf = std::io::Cursor::new(&x[..]);
-                let value = Signature::read_be_args(&mut buf, (self.class_file,))?;
-                Ok(Some(value.get_field()?))
-            }
-            None => Ok(None),
-        }
-    }
-
-    pub fn is_deprecated(&self) -> bool {
-        self.field_inner.attributes.0.get("Deprecated").is_some()
-    }
-}
-
-impl<'a> Debug for Field<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Method")
-            .field("identifier", &self.identifier())
-            .field("desciptor", &self.descriptor())
-            .finish()
-    }
+use std::fmt::Debug;
+
+use nom::{branch::alt, bytes::complete::{is_not, tag}, character::complete::char, combinator::{map, value}, sequence::{delimited, preceded}, IResult};
+
+use crate::{hexdump::RawAttribute, attributes::{Annotation, Attribute, AttributeLocation, KnownAttribute, ConstantValue, RuntimeInvisibleAnnotations, RuntimeVisibleAnnotations, Signature}, raw::{FieldAccessFlags, FieldRaw}, signature::ReferenceType, span::Span, ClassFile, Error};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypeDescriptor<'a> {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    String,
+    Class,
+    Array(Box<TypeDescriptor<'a>>),
+    ClassName(&'a str),
+}
+
+impl<'a> TypeDescriptor<'a> {
+    /// The operand stack or local variable slots a value of this type takes,
+    /// two for `long` and `double` and one otherwise.
+    pub fn slots(&self) -> u16 {
+        match self {
+            Self::Long | Self::Double => 2,
+            _ => 1,
+        }
+    }
+
+    /// The type `jni.h` declares a value of this type with, e.g. `jint` or
+    /// `jobjectArray`. Only `java/lang/Throwable` itself is `jthrowable`, as
+    /// telling its subclasses takes the class hierarchy.
+    pub fn to_jni_type(&self) -> &'static str {
+        match self {
+            Self::Boolean => "jboolean",
+            Self::Byte => "jbyte",
+            Self::Char => "jchar",
+            Self::Short => "jshort",
+            Self::Int => "jint",
+            Self::Long => "jlong",
+            Self::Float => "jfloat",
+            Self::Double => "jdouble",
+            Self::String => "jstring",
+            Self::Class => "jclass",
+            Self::Array(element) => match **element {
+                Self::Boolean => "jbooleanArray",
+                Self::Byte => "jbyteArray",
+                Self::Char => "jcharArray",
+                Self::Short => "jshortArray",
+                Self::Int => "jintArray",
+                Self::Long => "jlongArray",
+                Self::Float => "jfloatArray",
+                Self::Double => "jdoubleArray",
+                _ => "jobjectArray",
+            },
+            Self::ClassName("java/lang/Throwable") => "jthrowable",
+            Self::ClassName(_) => "jobject",
+        }
+    }
+
+    /// The descriptor as it is stored in a class file, e.g.
+    /// `[Ljava/lang/String;`. The same as the [`Display`](std::fmt::Display)
+    /// output.
+    pub fn to_descriptor_string(&self) -> String {
+        self.to_string()
+    }
+
+    /// The type as Java source writes it, with the binary name of a class,
+    /// e.g. `java.lang.String[]` or `java.util.Map$Entry`.
+    pub fn to_java_type(&self) -> String {
+        self.to_java_type_with(&mut |x| x.replace('/', "."))
+    }
+
+    /// The type as Java source writes it, with each class written as
+    /// `class_name` gives it from its internal name.
+    pub fn to_java_type_with(&self, class_name: &mut dyn FnMut(&str) -> String) -> String {
+        match self {
+            Self::Byte => "byte".to_string(),
+            Self::Char => "char".to_string(),
+            Self::Double => "double".to_string(),
+            Self::Float => "float".to_string(),
+            Self::Int => "int".to_string(),
+            Self::Long => "long".to_string(),
+            Self::Short => "short".to_string(),
+            Self::Boolean => "boolean".to_string(),
+            Self::String => class_name("java/lang/String"),
+            Self::Class => class_name("java/lang/Class"),
+            Self::Array(x) => format!("{}[]", x.to_java_type_with(class_name)),
+            Self::ClassName(x) => class_name(x),
+        }
+    }
+
+    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        alt((
+            value(Self::Byte, char('B')),
+            value(Self::Char, char('C')),
+            value(Self::Double, char('D')),
+            value(Self::Float, char('F')),
+            value(Self::Int, char('I')),
+            value(Self::Long, char('J')),
+            value(Self::Short, char('S')),
+            value(Self::Boolean, char('Z')),
+            value(Self::String, tag("Ljava/lang/String;")),
+            value(Self::Class, tag("Ljava/lang/Class;")),
+            map(delimited(char('L'), is_not(";"), char(';')), |x| {
+                Self::ClassName(x)
+            }),
+            map(preceded(char('['), Self::parse), |x| {
+                Self::Array(Box::new(x))
+            }),
+        ))(input)
+    }
+}
+
+/// Writes the descriptor, e.g. `[Ljava/lang/String;`.
+impl std::fmt::Display for TypeDescriptor<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Byte => f.write_str("B"),
+            Self::Char => f.write_str("C"),
+            Self::Double => f.write_str("D"),
+            Self::Float => f.write_str("F"),
+            Self::Int => f.write_str("I"),
+            Self::Long => f.write_str("J"),
+            Self::Short => f.write_str("S"),
+            Self::Boolean => f.write_str("Z"),
+            Self::String => f.write_str("Ljava/lang/String;"),
+            Self::Class => f.write_str("Ljava/lang/Class;"),
+            Self::Array(x) => write!(f, "[{}", x),
+            Self::ClassName(x) => write!(f, "L{};", x),
+        }
+    }
+}
+
+/// Why the JVM would not use a field's ConstantValue attribute (§4.7.2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConstantValueIssue {
+    /// The field is not static, so the attribute is silently ignored.
+    NotStatic,
+    /// The constant's kind does not match the field descriptor, so the class is rejected.
+    /// `expected` is `None` for descriptors that cannot have a ConstantValue at all.
+    TypeMismatch {
+        expected: Option<&'static str>,
+        found: &'static str,
+    },
+}
+
+pub struct Field<'a> {
+    pub(crate) class_file: &'a ClassFile,
+    pub(crate) field_inner: &'a FieldRaw,
+}
+
+impl<'a> Field<'a> {
+    pub fn identifier(&self) -> crate::Result<&'a str> {
+        self.field_inner.name_index.get_as_string(self.class_file)
+    }
+
+    pub fn descriptor(&self) -> crate::Result<TypeDescriptor<'a>> {
+        self.class_file.field_descriptor_at(self.field_inner.descriptor_index)
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.field_inner
+            .access_flags
+            .contains(FieldAccessFlags::STATIC)
+    }
+
+    /// Decodes the last field attribute called `T::NAME`.
+    pub fn get_attribute<T: KnownAttribute<'a>>(&self) -> crate::Result<Option<T>> {
+        self.field_inner.attributes.decode(self.class_file)
+    }
+
+    pub fn constant_value(&self) -> crate::Result<Option<ConstantValue<'a>>> {
+        self.get_attribute()
+    }
+
+    /// Checks the ConstantValue attribute, if any, the way the JVM would.
+    pub fn constant_value_issue(&self) -> crate::Result<Option<ConstantValueIssue>> {
+        let constant = match self.constant_value()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        if !self.is_static() {
+            return Ok(Some(ConstantValueIssue::NotStatic));
+        }
+        let expected = match self.descriptor()? {
+            TypeDescriptor::Int
+            | TypeDescriptor::Short
+            | TypeDescriptor::Char
+            | TypeDescriptor::Byte
+            | TypeDescriptor::Boolean => Some("Integer"),
+            TypeDescriptor::Float => Some("Float"),
+            TypeDescriptor::Long => Some("Long"),
+            TypeDescriptor::Double => Some("Double"),
+            TypeDescriptor::String => Some("String"),
+            _ => None,
+        };
+        let found = constant.item().map_or("invalid index", |x| x.kind());
+        if expected == Some(found) {
+            Ok(None)
+        } else {
+            Ok(Some(ConstantValueIssue::TypeMismatch { expected, found }))
+        }
+    }
+
+    /// The ConstantValue the JVM initializes this field with. Non-static fields
+    /// have none, and a constant of the wrong type is an error.
+    pub fn initial_value(&self) -> crate::Result<Option<ConstantValue<'a>>> {
+        match self.constant_value_issue()? {
+            None => self.constant_value(),
+            Some(ConstantValueIssue::NotStatic) => Ok(None),
+            Some(x) => Err(Error::InvalidConstantValue(format!(
+                "on field {}: {:?}",
+                self.identifier()?,
+                x
+            ))),
+        }
+    }
+
+    pub fn signature(&self) -> crate::Result<Option<ReferenceType<'a>>> {
+        match self.get_attribute::<Signature>()? {
+            Some(value) => Ok(Some(value.get_field()?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.field_inner.span
+    }
+
+    pub fn attribute_span(&self, name: &str) -> Option<Span> {
+        self.field_inner.attributes.span(name)
+    }
+
+    pub fn undecoded_attributes(&self) -> Vec<RawAttribute<'a>> {
+        self.field_inner.attributes.undecoded(self.class_file)
+    }
+
+    /// Every attribute as raw bytes, duplicates included, in the order they
+    /// appear in the file.
+    pub fn raw_attributes(&self) -> Vec<RawAttribute<'a>> {
+        self.field_inner.attributes.raw(self.class_file)
+    }
+
+    /// Every field attribute, decoded, in the order they appear in the file.
+    pub fn attributes(&self) -> crate::Result<Vec<Attribute<'a>>> {
+        self.field_inner.attributes.typed(self.class_file, AttributeLocation::Field)
+    }
+
+    /// The annotations in the `RuntimeVisibleAnnotations` attribute, if any.
+    pub fn annotations(&self) -> crate::Result<Vec<Annotation<'a>>> {
+        match self.get_attribute::<RuntimeVisibleAnnotations>()? {
+            Some(value) => Ok(value.annotations()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The annotations in the `RuntimeInvisibleAnnotations` attribute, which
+    /// compilers keep for `CLASS` retention, if any.
+    pub fn invisible_annotations(&self) -> crate::Result<Vec<Annotation<'a>>> {
+        match self.get_attribute::<RuntimeInvisibleAnnotations>()? {
+            Some(value) => Ok(value.annotations()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.field_inner.attributes.get("Deprecated").is_some()
+    }
+
+    /// Whether the compiler marked this as not present in the source, by the
+    /// `SYNTHETIC` flag or, as older compilers did, a `Synthetic` attribute.
+    pub fn is_synthetic(&self) -> bool {
+        self.field_inner.access_flags.contains(FieldAccessFlags::SYNTHETIC) || self.field_inner.attributes.get("Synthetic").is_some()
+    }
+}
+
+impl<'a> Debug for Field<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Method")
+            .field("identifier", &self.identifier())
+            .field("desciptor", &self.descriptor())
+            .finish()
+    }
 }
\ No newline at end of file
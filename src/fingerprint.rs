@@ -0,0 +1,202 @@
+use std::collections::BTreeSet;
+
+use crate::{field::TypeDescriptor, instruction::Instruction, method::MethodDescriptor, ClassFile, Result};
+
+const DEFAULT_SHINGLE_SIZE: usize = 4;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |h, b| (h ^ *b as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Maps an instruction onto a rename-insensitive token. Local slots, constant
+/// values and symbolic references are erased; only the operation family remains.
+fn token(instruction: &Instruction) -> &'static str {
+    use Instruction::*;
+    match instruction {
+        Nop => "nop",
+        AconstNull => "null",
+        IconstM1 | Iconst0 | Iconst1 | Iconst2 | Iconst3 | Iconst4 | Iconst5 | Bipush { .. }
+        | Sipush { .. } => "iconst",
+        Lconst0 | Lconst1 => "lconst",
+        Fconst0 | Fconst1 | Fconst2 => "fconst",
+        Dconst0 | Dconst1 => "dconst",
+        Ldc { .. } | LdcW { .. } | Ldc2W { .. } => "ldc",
+        Iload { .. } | Iload0 | Iload1 | Iload2 | Iload3 | WideIload { .. } => "iload",
+        Lload { .. } | Lload0 | Lload1 | Lload2 | Lload3 | WideLload { .. } => "lload",
+        Fload { .. } | Fload0 | Fload1 | Fload2 | Fload3 | WideFload { .. } => "fload",
+        Dload { .. } | Dload0 | Dload1 | Dload2 | Dload3 | WideDload { .. } => "dload",
+        Aload { .. } | Aload0 | Aload1 | Aload2 | Aload3 | WideAload { .. } => "aload",
+        Istore { .. } | Istore0 | Istore1 | Istore2 | Istore3 | WideIstore { .. } => "istore",
+        Lstore { .. } | Lstore0 | Lstore1 | Lstore2 | Lstore3 | WideLstore { .. } => "lstore",
+        Fstore { .. } | Fstore0 | Fstore1 | Fstore2 | Fstore3 | WideFstore { .. } => "fstore",
+        Dstore { .. } | Dstore0 | Dstore1 | Dstore2 | Dstore3 | WideDstore { .. } => "dstore",
+        Astore { .. } | Astore0 | Astore1 | Astore2 | Astore3 | WideAstore { .. } => "astore",
+        Iaload | Laload | Faload | Daload | Aaload | Baload | Caload | Saload => "xaload",
+        Iastore | Lastore | Fastore | Dastore | Aastore | Bastore | Castore | Sastore => {
+            "xastore"
+        }
+        Pop | Pop2 => "pop",
+        Dup | DupX1 | DupX2 | Dup2 | Dup2X1 | Dup2X2 | Swap => "dup",
+        Iadd | Ladd | Fadd | Dadd => "add",
+        Isub | Lsub | Fsub | Dsub => "sub",
+        Imul | Lmul | Fmul | Dmul => "mul",
+        Idiv | Ldiv | Fdiv | Ddiv => "div",
+        Irem | Lrem | Frem | Drem => "rem",
+        Ineg | Lneg | Fneg | Dneg => "neg",
+        Ishl | Lshl | Ishr | Lshr | Iushr | Lushr => "shift",
+        Iand | Land | Ior | Lor | Ixor | Lxor => "bitwise",
+        Iinc { .. } | WideIinc { .. } => "iinc",
+        I2l | I2f | I2d | L2i | L2f | L2d | F2i | F2l | F2d | D2i | D2l | D2f | I2b | I2c
+        | I2s => "convert",
+        Lcmp | Fcmpl | Fcmpg | Dcmpl | Dcmpg => "cmp",
+        Ifeq { .. } | Ifne { .. } | Iflt { .. } | Ifge { .. } | Ifgt { .. } | Ifle { .. } => "if",
+        IfIcmpeq { .. } | IfIcmpne { .. } | IfIcmplt { .. } | IfIcmpge { .. }
+        | IfIcmpgt { .. } | IfIcmple { .. } => "ificmp",
+        IfAcmpeq { .. } | IfAcmpne { .. } => "ifacmp",
+        Ifnull { .. } | Ifnonnull { .. } => "ifnull",
+        Goto { .. } | GotoW { .. } => "goto",
+        Jsr { .. } | JsrW { .. } | Ret { .. } | WideRet { .. } => "jsr",
+        Tableswitch { .. } | Lookupswitch { .. } => "switch",
+        Ireturn | Lreturn | Freturn | Dreturn | Areturn => "xreturn",
+        Return => "return",
+        Getstatic { .. } => "getstatic",
+        Putstatic { .. } => "putstatic",
+        Getfield { .. } => "getfield",
+        Putfield { .. } => "putfield",
+        Invokevirtual { .. } => "invokevirtual",
+        Invokespecial { .. } => "invokespecial",
+        Invokestatic { .. } => "invokestatic",
+        Invokeinterface { .. } => "invokeinterface",
+        Invokedynamic { .. } => "invokedynamic",
+        New { .. } => "new",
+        Newarray { .. } | Anewarray { .. } | Multianewarray { .. } => "newarray",
+        Arraylength => "arraylength",
+        Athrow => "athrow",
+        Checkcast { .. } => "checkcast",
+        Instanceof { .. } => "instanceof",
+        Monitorenter | Monitorexit => "monitor",
+        Unknown { .. } => "unknown",
+    }
+}
+
+fn shape_char(ty: &TypeDescriptor) -> char {
+    match ty {
+        TypeDescriptor::Byte => 'B',
+        TypeDescriptor::Char => 'C',
+        TypeDescriptor::Double => 'D',
+        TypeDescriptor::Float => 'F',
+        TypeDescriptor::Int => 'I',
+        TypeDescriptor::Long => 'J',
+        TypeDescriptor::Short => 'S',
+        TypeDescriptor::Boolean => 'Z',
+        TypeDescriptor::Array(_) => '[',
+        TypeDescriptor::String | TypeDescriptor::Class | TypeDescriptor::ClassName(_) => 'L',
+    }
+}
+
+/// The descriptor with every class name erased, e.g. `(LI)L` for `(Lcom/a/Foo;I)Lcom/a/Bar;`.
+fn descriptor_shape(descriptor: &MethodDescriptor) -> String {
+    let mut shape = String::from("(");
+    shape.extend(descriptor.parameter_types().iter().map(shape_char));
+    shape.push(')');
+    shape.push(descriptor.return_type().map_or('V', shape_char));
+    shape
+}
+
+#[derive(Debug, Clone)]
+pub struct MethodFingerprint {
+    pub shape: String,
+    pub instruction_count: usize,
+    pub shingles: BTreeSet<u64>,
+}
+
+impl MethodFingerprint {
+    pub fn similarity(&self, other: &Self) -> f64 {
+        jaccard(&self.shingles, &other.shingles)
+    }
+}
+
+/// A structural fingerprint of a class, stable across runs and insensitive to
+/// renaming of classes, members and locals.
+#[derive(Debug, Clone)]
+pub struct ClassFingerprint {
+    pub methods: Vec<MethodFingerprint>,
+    pub shingles: BTreeSet<u64>,
+}
+
+impl ClassFingerprint {
+    pub fn new(class_file: &ClassFile) -> Result<Self> {
+        Self::with_shingle_size(class_file, DEFAULT_SHINGLE_SIZE)
+    }
+
+    pub fn with_shingle_size(class_file: &ClassFile, shingle_size: usize) -> Result<Self> {
+        let shingle_size = shingle_size.max(1);
+        let mut methods = Vec::new();
+        for method in class_file.methods() {
+            let shape = descriptor_shape(&method.descriptor()?);
+            let tokens = match method.code()? {
+                Some(code) => code
+                    .instructions()?
+                    .iter()
+                    .map(token)
+                    .collect::<Vec<_>>(),
+                None => Vec::new(),
+            };
+            let seed = fnv1a(FNV_OFFSET, shape.as_bytes());
+            let mut shingles = BTreeSet::new();
+            shingles.insert(seed);
+            for window in tokens.windows(shingle_size.min(tokens.len()).max(1)) {
+                let hash = window
+                    .iter()
+                    .fold(FNV_OFFSET, |h, t| fnv1a(fnv1a(h, t.as_bytes()), b";"));
+                shingles.insert(hash);
+            }
+            methods.push(MethodFingerprint {
+                shape,
+                instruction_count: tokens.len(),
+                shingles,
+            });
+        }
+        let shingles = methods
+            .iter()
+            .flat_map(|x| x.shingles.iter().copied())
+            .collect();
+        Ok(Self { methods, shingles })
+    }
+
+    /// Jaccard similarity of the two classes' shingle sets, from 0.0 to 1.0.
+    pub fn similarity(&self, other: &Self) -> f64 {
+        jaccard(&self.shingles, &other.shingles)
+    }
+
+    /// Pairs every method of `self` with its most similar method in `other`.
+    pub fn method_matches(&self, other: &Self) -> Vec<(usize, Option<usize>, f64)> {
+        self.methods
+            .iter()
+            .enumerate()
+            .map(|(i, method)| {
+                other
+                    .methods
+                    .iter()
+                    .enumerate()
+                    .map(|(j, x)| (j, method.similarity(x)))
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .map_or((i, None, 0.0), |(j, score)| (i, Some(j), score))
+            })
+            .collect()
+    }
+}
+
+fn jaccard(a: &BTreeSet<u64>, b: &BTreeSet<u64>) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let intersection = a.intersection(b).count();
+    let union = a.len() + b.len() - intersection;
+    intersection as f64 / union as f64
+}
@@ -0,0 +1,435 @@
+//! Synthesizes small, valid class files for testing tools built on this crate.
+//! Enabled by the `generator` feature.
+
+use std::collections::HashMap;
+
+use crate::{mutf8, options::MAX_KNOWN_MAJOR_VERSION, ClassFile, Result};
+
+/// Deduplicating constant pool writer.
+#[derive(Default)]
+struct Pool {
+    bytes: Vec<u8>,
+    /// The index the next entry will get.
+    next: u16,
+    entries: HashMap<Vec<u8>, u16>,
+}
+
+impl Pool {
+    fn new() -> Self {
+        Self {
+            next: 1,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, entry: Vec<u8>) -> u16 {
+        if let Some(&index) = self.entries.get(&entry) {
+            return index;
+        }
+        let index = self.next;
+        // Long and Double take two slots.
+        self.next += if matches!(entry[0], 5 | 6) { 2 } else { 1 };
+        self.bytes.extend_from_slice(&entry);
+        self.entries.insert(entry, index);
+        index
+    }
+
+    fn utf8(&mut self, value: &str) -> u16 {
+        let value = mutf8::encode(value);
+        let mut entry = vec![1];
+        entry.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        entry.extend_from_slice(&value);
+        self.add(entry)
+    }
+
+    fn with_index(&mut self, tag: u8, index: u16) -> u16 {
+        let mut entry = vec![tag];
+        entry.extend_from_slice(&index.to_be_bytes());
+        self.add(entry)
+    }
+
+    fn with_indices(&mut self, tag: u8, a: u16, b: u16) -> u16 {
+        let mut entry = vec![tag];
+        entry.extend_from_slice(&a.to_be_bytes());
+        entry.extend_from_slice(&b.to_be_bytes());
+        self.add(entry)
+    }
+
+    fn class(&mut self, name: &str) -> u16 {
+        let name = self.utf8(name);
+        self.with_index(7, name)
+    }
+
+    fn module(&mut self, name: &str) -> u16 {
+        let name = self.utf8(name);
+        self.with_index(19, name)
+    }
+
+    fn long(&mut self, value: i64) -> u16 {
+        let mut entry = vec![5];
+        entry.extend_from_slice(&value.to_be_bytes());
+        self.add(entry)
+    }
+
+    fn member_ref(&mut self, tag: u8, class: &str, name: &str, descriptor: &str) -> u16 {
+        let class = self.class(class);
+        let name = self.utf8(name);
+        let descriptor = self.utf8(descriptor);
+        let name_and_type = self.with_indices(12, name, descriptor);
+        self.with_indices(tag, class, name_and_type)
+    }
+
+    fn method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+        self.member_ref(10, class, name, descriptor)
+    }
+
+    fn field_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+        self.member_ref(9, class, name, descriptor)
+    }
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_attribute(out: &mut Vec<u8>, name: u16, data: &[u8]) {
+    push_u16(out, name);
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+struct MethodDef {
+    access_flags: u16,
+    name: u16,
+    descriptor: u16,
+    /// The `Code` attribute's data.
+    code: Vec<u8>,
+}
+
+fn code_attribute(max_stack: u16, max_locals: u16, code: &[u8], attributes: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    push_u16(&mut out, max_stack);
+    push_u16(&mut out, max_locals);
+    out.extend_from_slice(&(code.len() as u32).to_be_bytes());
+    out.extend_from_slice(code);
+    push_u16(&mut out, 0);
+    push_u16(&mut out, attributes.len() as u16);
+    for (name, data) in attributes {
+        push_attribute(&mut out, *name, data);
+    }
+    out
+}
+
+/// The load and return opcodes for a field descriptor, and its slot count.
+fn type_ops(descriptor: &str) -> (u8, u8, u8) {
+    match descriptor.as_bytes().first() {
+        Some(b'J') => (0x16, 0xad, 2),
+        Some(b'F') => (0x17, 0xae, 1),
+        Some(b'D') => (0x18, 0xaf, 2),
+        Some(b'L' | b'[') => (0x19, 0xb0, 1),
+        _ => (0x15, 0xac, 1),
+    }
+}
+
+/// xorshift64*, enough to spread generator options from a seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed ^ 0x9e37_79b9_7f4a_7c15 | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+const COMPONENT_TYPES: &[&str] = &["I", "J", "F", "D", "Z", "B", "C", "S", "Ljava/lang/String;", "[I"];
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Class,
+    Record(Vec<(String, String)>),
+    Module,
+}
+
+/// Builds a minimal class file that parses here and loads in a JVM.
+///
+/// A plain class gets a public no-argument constructor. Optional features add
+/// a branching method with a `StackMapTable`, turn the class into a record, or
+/// pad the constant pool. The major version is clamped to what the crate knows,
+/// and raised to 60 for records and 53 for modules.
+#[derive(Debug, Clone)]
+pub struct ClassGenerator {
+    name: String,
+    kind: Kind,
+    major_version: u16,
+    stack_map_table: bool,
+    extra_constants: u16,
+}
+
+impl ClassGenerator {
+    /// A class with internal name `name`, e.g. `pkg/Generated`.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: Kind::Class,
+            major_version: 52,
+            stack_map_table: false,
+            extra_constants: 0,
+        }
+    }
+
+    /// A `module-info` class for `module`, requiring only `java.base`.
+    pub fn module_info(module: &str) -> Self {
+        Self {
+            kind: Kind::Module,
+            major_version: 53,
+            ..Self::new(module)
+        }
+    }
+
+    pub fn with_major_version(mut self, major_version: u16) -> Self {
+        self.major_version = major_version;
+        self
+    }
+
+    /// Adds `static int abs(int)`, whose branch needs a `StackMapTable` from
+    /// version 50 on. Ignored for module descriptors.
+    pub fn with_stack_map_table(mut self, stack_map_table: bool) -> Self {
+        self.stack_map_table = stack_map_table;
+        self
+    }
+
+    /// Makes the class a record with the given `(name, field descriptor)` components.
+    pub fn with_record_components(mut self, components: &[(&str, &str)]) -> Self {
+        self.kind = Kind::Record(
+            components
+                .iter()
+                .map(|(name, descriptor)| (name.to_string(), descriptor.to_string()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Pads the constant pool with `count` unused entries, alternating `Utf8`
+    /// and two-slot `Long` constants, stopping short of the pool size limit.
+    pub fn with_extra_constants(mut self, count: u16) -> Self {
+        self.extra_constants = count;
+        self
+    }
+
+    /// A generator with every option picked from `seed`.
+    pub fn random(seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut generator = match rng.below(6) {
+            0 => Self::module_info(&format!("gen.m{}", seed)),
+            1 | 2 => {
+                let mut generator = Self::new(&format!("gen/R{}", seed));
+                let components = (0..rng.below(6))
+                    .map(|i| {
+                        let ty = COMPONENT_TYPES[rng.below(COMPONENT_TYPES.len() as u64) as usize];
+                        (format!("c{}", i), ty.to_string())
+                    })
+                    .collect();
+                generator.kind = Kind::Record(components);
+                generator
+            }
+            _ => Self::new(&format!("gen/C{}", seed)),
+        };
+        generator.major_version = 45 + rng.below((MAX_KNOWN_MAJOR_VERSION - 44) as u64) as u16;
+        generator.stack_map_table = rng.below(2) == 0;
+        generator.extra_constants = match rng.below(8) {
+            0 => rng.below(u16::MAX as u64 + 1) as u16,
+            _ => rng.below(50) as u16,
+        };
+        generator
+    }
+
+    /// Describes how `class_file`, parsed from [`Self::build`], differs from
+    /// what this generator asked for.
+    pub(crate) fn mismatch(&self, class_file: &ClassFile) -> Result<Option<String>> {
+        let (this_class, fields, methods, attribute) = match &self.kind {
+            Kind::Class => (self.name.as_str(), 0, 1, None),
+            Kind::Record(components) => (
+                self.name.as_str(),
+                components.len(),
+                components.len() + 1,
+                Some("Record"),
+            ),
+            Kind::Module => ("module-info", 0, 0, Some("Module")),
+        };
+        let methods = methods + (self.stack_map_table && !matches!(self.kind, Kind::Module)) as usize;
+        let problem = if class_file.major_version() != self.major_version() {
+            format!("major version {} instead of {}", class_file.major_version(), self.major_version())
+        } else if class_file.this_class()? != this_class {
+            format!("this class {} instead of {}", class_file.this_class()?, this_class)
+        } else if class_file.fields().len() != fields {
+            format!("{} fields instead of {}", class_file.fields().len(), fields)
+        } else if class_file.methods().len() != methods {
+            format!("{} methods instead of {}", class_file.methods().len(), methods)
+        } else if attribute.is_some_and(|x| class_file.attribute_span(x).is_none()) {
+            format!("missing {} attribute", attribute.unwrap_or_default())
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(problem))
+    }
+
+    fn major_version(&self) -> u16 {
+        let min = match self.kind {
+            Kind::Class => 45,
+            Kind::Record(_) => 60,
+            Kind::Module => 53,
+        };
+        self.major_version.clamp(min, MAX_KNOWN_MAJOR_VERSION)
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let major_version = self.major_version();
+        let mut pool = Pool::new();
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+        let mut attributes = Vec::new();
+        let code_name = pool.utf8("Code");
+
+        let (access_flags, this_class, super_class) = match &self.kind {
+            Kind::Module => {
+                let module_name = pool.utf8("Module");
+                let module = pool.module(&self.name);
+                let java_base = pool.module("java.base");
+                let mut data = Vec::new();
+                push_u16(&mut data, module);
+                push_u16(&mut data, 0);
+                push_u16(&mut data, 0);
+                // requires java.base, ACC_MANDATED
+                push_u16(&mut data, 1);
+                push_u16(&mut data, java_base);
+                push_u16(&mut data, 0x8000);
+                push_u16(&mut data, 0);
+                // exports, opens, uses, provides
+                data.extend_from_slice(&[0; 8]);
+                attributes.push((module_name, data));
+                (0x8000, pool.class("module-info"), 0)
+            }
+            Kind::Class => {
+                let super_init = pool.method_ref("java/lang/Object", "<init>", "()V");
+                let [hi, lo] = super_init.to_be_bytes();
+                methods.push(MethodDef {
+                    access_flags: 0x0001,
+                    name: pool.utf8("<init>"),
+                    descriptor: pool.utf8("()V"),
+                    code: code_attribute(1, 1, &[0x2a, 0xb7, hi, lo, 0xb1], &[]),
+                });
+                (0x0021, pool.class(&self.name), pool.class("java/lang/Object"))
+            }
+            Kind::Record(components) => {
+                let mut record = Vec::new();
+                push_u16(&mut record, components.len() as u16);
+                let [hi, lo] = pool.method_ref("java/lang/Record", "<init>", "()V").to_be_bytes();
+                let mut init = vec![0x2a, 0xb7, hi, lo];
+                let mut slot = 1u16;
+                let mut init_descriptor = String::from("(");
+                for (name, descriptor) in components {
+                    let name_index = pool.utf8(name);
+                    let descriptor_index = pool.utf8(descriptor);
+                    push_u16(&mut record, name_index);
+                    push_u16(&mut record, descriptor_index);
+                    push_u16(&mut record, 0);
+                    // private final
+                    fields.push((0x0012, name_index, descriptor_index));
+
+                    let field = pool.field_ref(&self.name, name, descriptor);
+                    let [hi, lo] = field.to_be_bytes();
+                    let (load, ret, slots) = type_ops(descriptor);
+                    init.extend_from_slice(&[0x2a, load, slot as u8, 0xb5, hi, lo]);
+                    slot += slots as u16;
+                    init_descriptor.push_str(descriptor);
+
+                    let accessor = format!("(){}", descriptor);
+                    methods.push(MethodDef {
+                        access_flags: 0x0001,
+                        name: name_index,
+                        descriptor: pool.utf8(&accessor),
+                        code: code_attribute(slots as u16, 1, &[0x2a, 0xb4, hi, lo, ret], &[]),
+                    });
+                }
+                init.push(0xb1);
+                init_descriptor.push_str(")V");
+                methods.push(MethodDef {
+                    access_flags: 0x0001,
+                    name: pool.utf8("<init>"),
+                    descriptor: pool.utf8(&init_descriptor),
+                    code: code_attribute(3, slot, &init, &[]),
+                });
+                attributes.push((pool.utf8("Record"), record));
+                // public final super
+                (0x0031, pool.class(&self.name), pool.class("java/lang/Record"))
+            }
+        };
+
+        if self.stack_map_table && !matches!(self.kind, Kind::Module) {
+            // iload_0; ifge 7; iload_0; ineg; ireturn; 7: iload_0; ireturn
+            let code = [0x1a, 0x9c, 0x00, 0x06, 0x1a, 0x74, 0xac, 0x1a, 0xac];
+            let mut code_attributes = Vec::new();
+            if major_version >= 50 {
+                // One same_frame at offset 7.
+                code_attributes.push((pool.utf8("StackMapTable"), vec![0x00, 0x01, 7]));
+            }
+            methods.push(MethodDef {
+                access_flags: 0x0009,
+                name: pool.utf8("abs"),
+                descriptor: pool.utf8("(I)I"),
+                code: code_attribute(1, 1, &code, &code_attributes),
+            });
+        }
+
+        for i in 0..self.extra_constants {
+            if pool.next >= u16::MAX - 1 {
+                break;
+            }
+            if i % 2 == 0 {
+                pool.utf8(&format!("extra{}", i));
+            } else {
+                pool.long(i as i64);
+            }
+        }
+
+        let mut out = vec![0xca, 0xfe, 0xba, 0xbe];
+        push_u16(&mut out, 0);
+        push_u16(&mut out, major_version);
+        push_u16(&mut out, pool.next);
+        out.extend_from_slice(&pool.bytes);
+        push_u16(&mut out, access_flags);
+        push_u16(&mut out, this_class);
+        push_u16(&mut out, super_class);
+        push_u16(&mut out, 0);
+        push_u16(&mut out, fields.len() as u16);
+        for (access_flags, name, descriptor) in fields {
+            push_u16(&mut out, access_flags);
+            push_u16(&mut out, name);
+            push_u16(&mut out, descriptor);
+            push_u16(&mut out, 0);
+        }
+        push_u16(&mut out, methods.len() as u16);
+        for method in methods {
+            push_u16(&mut out, method.access_flags);
+            push_u16(&mut out, method.name);
+            push_u16(&mut out, method.descriptor);
+            push_u16(&mut out, 1);
+            push_attribute(&mut out, code_name, &method.code);
+        }
+        push_u16(&mut out, attributes.len() as u16);
+        for (name, data) in attributes {
+            push_attribute(&mut out, name, &data);
+        }
+        out
+    }
+}
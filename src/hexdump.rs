@@ -0,0 +1,133 @@
+use std::fmt::{Debug, Write};
+
+use crate::{raw::ConstantPoolItem, span::Span, ClassFile};
+
+/// Attributes with a typed accessor somewhere in the crate.
+pub const DECODED_ATTRIBUTES: &[&str] = &[
+    "ConstantValue",
+    "Code",
+    "StackMapTable",
+    "Exceptions",
+    "InnerClasses",
+    "EnclosingMethod",
+    "Signature",
+    "SourceFile",
+    "LineNumberTable",
+    "LocalVariableTable",
+    "LocalVariableTypeTable",
+    "Deprecated",
+    "Synthetic",
+    "BootstrapMethods",
+    "RuntimeVisibleAnnotations",
+    "RuntimeInvisibleAnnotations",
+    "AnnotationDefault",
+    "Module",
+    "ModulePackages",
+    "ModuleMainClass",
+    "Record",
+    "PermittedSubclasses",
+];
+
+/// Formats `data` as `offset  hex  |ascii|` lines of 16 bytes, numbering from `offset`.
+pub fn hexdump(data: &[u8], offset: u64) -> String {
+    let mut out = String::new();
+    for (i, line) in data.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x}  ", offset + i as u64 * 16);
+        for j in 0..16 {
+            match line.get(j) {
+                Some(x) => {
+                    let _ = write!(out, "{:02x} ", x);
+                }
+                None => out.push_str("   "),
+            }
+            if j == 7 {
+                out.push(' ');
+            }
+        }
+        out.push_str(" |");
+        out.extend(line.iter().map(|&x| match x {
+            0x20..=0x7e => x as char,
+            _ => '.',
+        }));
+        out.push_str("|\n");
+    }
+    out
+}
+
+/// Two bytes that could be a constant pool index, see [`pool_references`].
+#[derive(Debug, Clone)]
+pub struct PoolReference<'a> {
+    pub offset: u64,
+    pub index: u16,
+    pub kind: &'static str,
+    /// The text of Utf8 entries, and the name of Class, String and similar entries.
+    pub text: Option<&'a str>,
+}
+
+/// Best-effort guesses at the pool indices embedded in `data`: every big-endian
+/// `u16` at any offset that names an existing pool entry. Expect false positives.
+pub fn pool_references<'a>(class_file: &'a ClassFile, data: &[u8], offset: u64) -> Vec<PoolReference<'a>> {
+    let cpool = &class_file.constant_pool;
+    data.windows(2)
+        .enumerate()
+        .filter_map(|(i, x)| {
+            let index = u16::from_be_bytes([x[0], x[1]]);
+            let item = cpool.0.get((index as usize).checked_sub(1)?)?;
+            let text = match item {
+                ConstantPoolItem::Skip => return None,
+                ConstantPoolItem::Utf8 { value, .. } => Some(value.as_str()),
+                ConstantPoolItem::Class { name_index }
+                | ConstantPoolItem::Module { name_index }
+                | ConstantPoolItem::Package { name_index } => name_index.get_as_string_impl(cpool).ok(),
+                ConstantPoolItem::String { string_index } => string_index.get_as_string_impl(cpool).ok(),
+                _ => None,
+            };
+            Some(PoolReference {
+                offset: offset + i as u64,
+                index,
+                kind: item.kind(),
+                text,
+            })
+        })
+        .collect()
+}
+
+/// An attribute's raw bytes, for attributes the crate has no decoder for.
+pub struct RawAttribute<'a> {
+    pub(crate) class_file: &'a ClassFile,
+    /// The constant pool index of the name, kept when the class is written.
+    pub name_index: u16,
+    pub name: &'a str,
+    pub data: &'a [u8],
+    /// The span of the whole attribute, including its six byte header.
+    pub span: Span,
+}
+
+impl<'a> RawAttribute<'a> {
+    fn data_offset(&self) -> u64 {
+        self.span.start + 6
+    }
+
+    pub fn hexdump(&self) -> String {
+        hexdump(self.data, self.data_offset())
+    }
+
+    pub fn pool_references(&self) -> Vec<PoolReference<'a>> {
+        pool_references(self.class_file, self.data, self.data_offset())
+    }
+}
+
+impl<'a> Debug for RawAttribute<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} at {:#x} ({} bytes)", self.name, self.span.start, self.data.len())?;
+        f.write_str(&self.hexdump())?;
+        for x in self.pool_references() {
+            write!(f, "  {:#010x}: #{} {}", x.offset, x.index, x.kind)?;
+            match x.text {
+                Some(text) => writeln!(f, " {:?}", text)?,
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
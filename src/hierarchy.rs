@@ -0,0 +1,245 @@
+//! The class hierarchy of a class path, see [`ClassHierarchy`].
+
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    sync::{Arc, RwLock},
+};
+
+use crate::{raw::ClassAccessFlags, resolver::ClassResolver, ClassFile, Error, Result};
+
+const OBJECT: &str = "java/lang/Object";
+
+/// What the index keeps of a class.
+#[derive(Debug, Clone)]
+struct Node {
+    super_class: Option<String>,
+    interfaces: Vec<String>,
+    is_interface: bool,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    /// Classes looked up so far, `None` for those that did not resolve.
+    nodes: HashMap<String, Option<Node>>,
+    /// Direct subclasses and implementors of the classes looked up.
+    subtypes: HashMap<String, BTreeSet<String>>,
+}
+
+/// An index of superclasses, interfaces and subtypes over the classes of a
+/// [`ClassResolver`].
+///
+/// Classes are resolved the first time a query needs them and remembered,
+/// along with those that do not resolve. A resolver cannot list its classes,
+/// so the subtypes known are those of the classes looked up so far, and
+/// [`add_class`](Self::add_class) brings in the rest of a class path, for
+/// example every name in a [`ClassRepository`](crate::repository::ClassRepository).
+///
+/// Only class and interface names are taken, not array types.
+pub struct ClassHierarchy<R> {
+    resolver: R,
+    index: RwLock<Index>,
+}
+
+impl<R: std::fmt::Debug> std::fmt::Debug for ClassHierarchy<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClassHierarchy")
+            .field("resolver", &self.resolver)
+            .field("classes", &self.index.read().unwrap_or_else(|e| e.into_inner()).nodes.len())
+            .finish()
+    }
+}
+
+impl<R: ClassResolver> ClassHierarchy<R> {
+    pub fn new(resolver: R) -> Self {
+        Self {
+            resolver,
+            index: RwLock::default(),
+        }
+    }
+
+    /// A hierarchy with `names` and their supertypes already looked up.
+    pub fn from_classes<'a>(resolver: R, names: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let hierarchy = Self::new(resolver);
+        for name in names {
+            hierarchy.add_class(name)?;
+        }
+        Ok(hierarchy)
+    }
+
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+
+    // A panic while holding the lock cannot leave the index half-updated, so
+    // poisoning is ignored.
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Index> {
+        self.index.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Looks class `name` up, resolving it the first time.
+    fn node(&self, name: &str) -> Result<Option<Node>> {
+        if let Some(x) = self.read().nodes.get(name) {
+            return Ok(x.clone());
+        }
+        let node = match self.resolver.resolve(name)? {
+            Some(class_file) => Some(Node {
+                super_class: match class_file.super_class.0 {
+                    0 => None,
+                    _ => Some(class_file.super_class()?.to_string()),
+                },
+                interfaces: class_file.interfaces()?.into_iter().map(str::to_string).collect(),
+                is_interface: class_file.access_flags.contains(ClassAccessFlags::INTERFACE),
+            }),
+            None => None,
+        };
+        let mut index = self.index.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(node) = &node {
+            for supertype in node.super_class.iter().chain(&node.interfaces) {
+                index.subtypes.entry(supertype.clone()).or_default().insert(name.to_string());
+            }
+        }
+        index.nodes.insert(name.to_string(), node.clone());
+        Ok(node)
+    }
+
+    fn expect(&self, name: &str) -> Result<Node> {
+        self.node(name)?.ok_or_else(|| Error::ClassNotFound(name.to_string()))
+    }
+
+    /// Looks class `name` up along with all of its supertypes, so that it is
+    /// among their subtypes. Returns whether `name` resolved.
+    pub fn add_class(&self, name: &str) -> Result<bool> {
+        let found = self.node(name)?.is_some();
+        self.all_supertypes(name)?;
+        Ok(found)
+    }
+
+    /// Whether `name` resolves.
+    pub fn contains(&self, name: &str) -> Result<bool> {
+        Ok(self.node(name)?.is_some())
+    }
+
+    /// Whether `name` resolves to an interface.
+    pub fn is_interface(&self, name: &str) -> Result<bool> {
+        Ok(self.node(name)?.is_some_and(|x| x.is_interface))
+    }
+
+    /// `name`'s superclass, its superclass and so on, stopping at the first
+    /// class that does not resolve.
+    pub fn super_classes(&self, name: &str) -> Result<Vec<String>> {
+        let mut res = Vec::new();
+        let mut current = self.node(name)?;
+        while let Some(super_class) = current.and_then(|x| x.super_class) {
+            // Guard against cycles in malformed input.
+            if res.contains(&super_class) {
+                break;
+            }
+            current = self.node(&super_class)?;
+            res.push(super_class);
+        }
+        Ok(res)
+    }
+
+    /// Every superclass and interface of `name`, breadth first, each once.
+    /// Supertypes of classes that do not resolve are missing.
+    pub fn all_supertypes(&self, name: &str) -> Result<Vec<String>> {
+        let mut res = Vec::new();
+        let mut seen = BTreeSet::from([name.to_string()]);
+        let mut queue = VecDeque::from([name.to_string()]);
+        while let Some(current) = queue.pop_front() {
+            let node = match self.node(&current)? {
+                Some(x) => x,
+                None => continue,
+            };
+            for supertype in node.super_class.into_iter().chain(node.interfaces) {
+                if seen.insert(supertype.clone()) {
+                    res.push(supertype.clone());
+                    queue.push_back(supertype);
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Whether `sub` is `sup` or reaches it through superclasses and
+    /// interfaces that resolve. Every class is a subtype of
+    /// `java/lang/Object`.
+    pub fn is_subtype_of(&self, sub: &str, sup: &str) -> Result<bool> {
+        if sub == sup || sup == OBJECT {
+            return Ok(true);
+        }
+        Ok(self.all_supertypes(sub)?.iter().any(|x| x == sup))
+    }
+
+    /// The nearest superclass `a` and `b` share, as a frame merging the two
+    /// takes: one of them if it is a superclass of the other, and
+    /// `java/lang/Object` if either is an interface. Fails with
+    /// [`Error::ClassNotFound`] if a class on the way to the answer does not
+    /// resolve.
+    pub fn common_superclass(&self, a: &str, b: &str) -> Result<String> {
+        if a == b {
+            return Ok(a.to_string());
+        }
+        if self.expect(a)?.is_interface || self.expect(b)?.is_interface {
+            return Ok(OBJECT.to_string());
+        }
+        let chain = |name: &str| -> Result<Vec<String>> {
+            let mut res = vec![name.to_string()];
+            res.extend(self.super_classes(name)?);
+            let last = res.last().map_or(name, |x| x);
+            if last != OBJECT {
+                // Either the chain stopped at a class that does not resolve,
+                // or at a root other than java/lang/Object.
+                self.expect(last)?;
+            }
+            Ok(res)
+        };
+        let b = chain(b)?;
+        Ok(chain(a)?
+            .into_iter()
+            .find(|x| b.contains(x))
+            .unwrap_or_else(|| OBJECT.to_string()))
+    }
+
+    /// Classes directly extending or implementing `name`, among those looked
+    /// up so far, sorted.
+    pub fn direct_subtypes(&self, name: &str) -> Vec<String> {
+        self.read()
+            .subtypes
+            .get(name)
+            .map(|x| x.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Every class reaching `name` through superclasses and interfaces,
+    /// among those looked up so far, sorted.
+    pub fn all_subtypes(&self, name: &str) -> Vec<String> {
+        let index = self.read();
+        let mut res = BTreeSet::new();
+        let mut pending = vec![name];
+        while let Some(current) = pending.pop() {
+            for x in index.subtypes.get(current).into_iter().flatten() {
+                if res.insert(x.clone()) {
+                    pending.push(x.as_str());
+                }
+            }
+        }
+        res.into_iter().collect()
+    }
+}
+
+/// Resolves classes through the hierarchy's resolver, so a hierarchy can be
+/// handed to anything taking a [`ClassResolver`].
+impl<R: ClassResolver> ClassResolver for ClassHierarchy<R> {
+    fn resolve(&self, internal_name: &str) -> Result<Option<Arc<ClassFile>>> {
+        self.resolver.resolve(internal_name)
+    }
+
+    fn super_classes(&self, name: &str) -> Result<Vec<String>> {
+        ClassHierarchy::super_classes(self, name)
+    }
+
+    fn is_subtype_of(&self, sub: &str, sup: &str) -> Result<bool> {
+        ClassHierarchy::is_subtype_of(self, sub, sup)
+    }
+}
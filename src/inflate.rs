@@ -0,0 +1,225 @@
+//! Decompression of DEFLATE streams (RFC 1951), as ZIP entries are stored,
+//! and their CRC-32 checksums.
+
+use crate::{Error, Result};
+
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+/// The order code length code lengths are listed in.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn invalid(message: &str) -> Error {
+    Error::ArchiveError(format!("invalid deflate data: {}", message))
+}
+
+/// Reads bits least significant first, as DEFLATE packs them.
+struct Bits<'d> {
+    data: &'d [u8],
+    pos: usize,
+    buffer: u64,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn get(&mut self, n: u32) -> Result<u32> {
+        while self.count < n {
+            let byte = *self.data.get(self.pos).ok_or_else(|| invalid("unexpected end"))?;
+            self.buffer |= (byte as u64) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let res = (self.buffer & ((1 << n) - 1)) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(res)
+    }
+
+    /// Drops the bits left in the current byte.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the
+/// symbols in code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &x in lengths {
+            counts[x as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    /// Reads one symbol a bit at a time, walking the codes of each length.
+    fn decode(&self, bits: &mut Bits) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_BITS {
+            code |= bits.get(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("bad code"))
+    }
+}
+
+/// Reads the code length and distance codes of a dynamic block.
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman)> {
+    let literals = bits.get(5)? as usize + 257;
+    let distances = bits.get(5)? as usize + 1;
+    let code_lengths = bits.get(4)? as usize + 4;
+    let mut lengths = [0u8; 19];
+    for &x in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[x] = bits.get(3)? as u8;
+    }
+    let code = Huffman::new(&lengths);
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code.decode(bits)? {
+            x @ 0..=15 => (x as u8, 1),
+            16 => (*lengths.last().ok_or_else(|| invalid("repeat with no previous length"))?, 3 + bits.get(2)?),
+            17 => (0, 3 + bits.get(3)?),
+            _ => (0, 11 + bits.get(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literals + distances {
+        return Err(invalid("too many code lengths"));
+    }
+    Ok((Huffman::new(&lengths[..literals]), Huffman::new(&lengths[literals..])))
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+/// Decompresses raw DEFLATE data, expecting `size` bytes out. Failing once the
+/// output would grow past `size`, as that comes from the same untrusted input.
+pub(crate) fn inflate(data: &[u8], size: usize) -> Result<Vec<u8>> {
+    // DEFLATE rarely does better than 4:1 on class files, so don't reserve
+    // much more than that up front whatever `size` claims.
+    let mut out = Vec::with_capacity(size.min(data.len().saturating_mul(4).max(64 << 10)));
+    let too_long = || invalid("more data than the declared size");
+    let bits = &mut Bits {
+        data,
+        pos: 0,
+        buffer: 0,
+        count: 0,
+    };
+    loop {
+        let last = bits.get(1)? == 1;
+        let (literals, distances) = match bits.get(2)? {
+            0 => {
+                bits.align();
+                let header = data.get(bits.pos..bits.pos + 4).ok_or_else(|| invalid("unexpected end"))?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if length != !u16::from_le_bytes([header[2], header[3]]) as usize {
+                    return Err(invalid("stored block length mismatch"));
+                }
+                let start = bits.pos + 4;
+                if out.len() + length > size {
+                    return Err(too_long());
+                }
+                out.extend_from_slice(data.get(start..start + length).ok_or_else(|| invalid("unexpected end"))?);
+                bits.pos = start + length;
+                if last {
+                    break;
+                }
+                continue;
+            }
+            1 => fixed_codes(),
+            2 => dynamic_codes(bits)?,
+            _ => return Err(invalid("reserved block type")),
+        };
+        loop {
+            let symbol = literals.decode(bits)? as usize;
+            match symbol {
+                0..=255 if out.len() == size => return Err(too_long()),
+                0..=255 => out.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let i = symbol - 257;
+                    if i >= LENGTH_BASE.len() {
+                        return Err(invalid("bad length code"));
+                    }
+                    let length = LENGTH_BASE[i] as usize + bits.get(LENGTH_EXTRA[i] as u32)? as usize;
+                    let i = distances.decode(bits)? as usize;
+                    if i >= DISTANCE_BASE.len() {
+                        return Err(invalid("bad distance code"));
+                    }
+                    let distance = DISTANCE_BASE[i] as usize + bits.get(DISTANCE_EXTRA[i] as u32)? as usize;
+                    if distance > out.len() {
+                        return Err(invalid("distance before the start"));
+                    }
+                    if out.len() + length > size {
+                        return Err(too_long());
+                    }
+                    // Copies may overlap what they write, so go byte by byte.
+                    let start = out.len() - distance;
+                    for i in 0..length {
+                        out.push(out[start + i]);
+                    }
+                }
+            }
+        }
+        if last {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+/// The CRC-32 ZIP stores with each entry.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (i, x) in table.iter_mut().enumerate() {
+        let mut c = i as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *x = c;
+    }
+    !data
+        .iter()
+        .fold(!0u32, |c, &x| table[((c ^ x as u32) & 0xff) as usize] ^ (c >> 8))
+}
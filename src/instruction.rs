@@ -1,720 +1,2117 @@
-use binrw::{binread, BinRead};
-
-use crate::{
-    attributes::BootstrapMethod, field::TypeDescriptor, method::MethodDescriptor, raw::ConstantPoolItem, ClassFile, ClassIndex
-};
-
-#[derive(Debug)]
-pub struct FieldRef<'a> {
-    pub class: &'a str,
-    pub name: &'a str,
-    pub descriptor: TypeDescriptor<'a>,
-}
-
-#[derive(Debug)]
-pub struct MethodRef<'a> {
-    pub class: &'a str,
-    pub name: &'a str,
-    pub descriptor: MethodDescriptor<'a>,
-}
-
-#[derive(Debug)]
-pub struct InterfaceMethodRef<'a> {
-    pub class: &'a str,
-    pub name: &'a str,
-    pub descriptor: MethodDescriptor<'a>,
-}
-
-impl<'a> FieldRef<'a> {
-    fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
-        match &cf.constant_pool.0[index as usize - 1] {
-            ConstantPoolItem::Fieldref {
-                class_index,
-                name_and_type_index,
-            } => {
-                let class = class_index.get_as_string(cf)?;
-                let name = name_and_type_index.get_name(cf)?;
-                let descriptor = name_and_type_index.get_descriptor(cf)?;
-                let descriptor = TypeDescriptor::parse(descriptor)?.1;
-                Ok(Self {
-                    class,
-                    name,
-                    descriptor,
-                })
-            }
-            x => Err(super::Error::ConstantPoolError(format!(
-                "expected FieldRef at constant pool index {}. Instead found {:?}.",
-                index, x
-            ))),
-        }
-    }
-}
-
-impl<'a> MethodRef<'a> {
-    fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
-        match &cf.constant_pool.0[index as usize - 1] {
-            ConstantPoolItem::Methodref {
-                class_index,
-                name_and_type_index,
-            } => {
-                let class = class_index.get_as_string(cf)?;
-                let name = name_and_type_index.get_name(cf)?;
-                let descriptor = name_and_type_index.get_descriptor(cf)?;
-                let descriptor = MethodDescriptor::parse(descriptor)?.1;
-                Ok(Self {
-                    class,
-                    name,
-                    descriptor,
-                })
-            }
-            x => Err(super::Error::ConstantPoolError(format!(
-                "expected MethodRef at constant pool index {}. Instead found {:?}.",
-                index, x
-            ))),
-        }
-    }
-}
-
-impl<'a> InterfaceMethodRef<'a> {
-    fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
-        match &cf.constant_pool.0[index as usize - 1] {
-            ConstantPoolItem::InterfaceMethodref {
-                class_index,
-                name_and_type_index,
-            } => {
-                let class = class_index.get_as_string(cf)?;
-                let name = name_and_type_index.get_name(cf)?;
-                let descriptor = name_and_type_index.get_descriptor(cf)?;
-                let descriptor = MethodDescriptor::parse(descriptor)?.1;
-                Ok(Self {
-                    class,
-                    name,
-                    descriptor,
-                })
-            }
-            x => Err(super::Error::ConstantPoolError(format!(
-                "expected InterfaceMethodRef at constant pool index {}. Instead found {:?}.",
-                index, x
-            ))),
-        }
-    }
-}
-
-macro_rules! from_u16_binread {
-    ($class:ident) => {
-        impl<'a> BinRead for $class<'a> {
-            type Args<'b> = (&'a ClassFile,);
-
-            fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
-                reader: &mut R,
-                endian: binrw::Endian,
-                (cf,): Self::Args<'_>,
-            ) -> binrw::prelude::BinResult<Self> {
-                let pos = reader.stream_position()?;
-                let index = u16::read_options(reader, endian, ())?;
-                Self::from_u16(index, cf).map_err(|x| binrw::Error::Custom {
-                    pos,
-                    err: Box::new(x),
-                })
-            }
-        }
-    };
-}
-
-#[derive(Debug)]
-pub enum MaybeInterfaceMethodRef<'a> {
-    RegularMethod(MethodRef<'a>),
-    InterfaceMethod(InterfaceMethodRef<'a>),
-}
-
-impl<'a> MaybeInterfaceMethodRef<'a> {
-    fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
-        match &cf.constant_pool.0[index as usize - 1] {
-            ConstantPoolItem::InterfaceMethodref { .. } => {
-                Ok(Self::InterfaceMethod(InterfaceMethodRef::from_u16(index, cf)?))
-            }
-            ConstantPoolItem::Methodref { .. } => {
-                Ok(Self::RegularMethod(MethodRef::from_u16(index, cf)?))
-            }
-            x => Err(super::Error::ConstantPoolError(format!(
-                "expected MethodRef or InterfaceMethodRef at constant pool index {}. Instead found {:?}.",
-                index, x
-            ))),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub enum MethodHandle<'a> {
-    GetField(FieldRef<'a>),
-    GetStatic(FieldRef<'a>),
-    PutField(FieldRef<'a>),
-    PutStatic(FieldRef<'a>),
-    InvokeVirtual(MethodRef<'a>),
-    NewInvokeSpecial(MethodRef<'a>),
-    InvokeStatic(MaybeInterfaceMethodRef<'a>),
-    InvokeSpecial(MaybeInterfaceMethodRef<'a>),
-    InvokeInterface(InterfaceMethodRef<'a>),
-}
-
-impl<'a> MethodHandle<'a> {
-    pub(crate) fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
-        match &cf.constant_pool.0[index as usize - 1] {
-            ConstantPoolItem::MethodHandle { reference } => {
-                match reference.kind {
-                    1 => Ok(Self::GetField(FieldRef::from_u16(reference.index, cf)?)),
-                    2 => Ok(Self::GetStatic(FieldRef::from_u16(reference.index, cf)?)),
-                    3 => Ok(Self::PutField(FieldRef::from_u16(reference.index, cf)?)),
-                    4 => Ok(Self::PutStatic(FieldRef::from_u16(reference.index, cf)?)),
-                    5 => Ok(Self::InvokeVirtual(MethodRef::from_u16(reference.index, cf)?)),
-                    8 => Ok(Self::NewInvokeSpecial(MethodRef::from_u16(reference.index, cf)?)),
-                    6 => Ok(Self::InvokeStatic(MaybeInterfaceMethodRef::from_u16(reference.index, cf)?)),
-                    7 => Ok(Self::InvokeSpecial(MaybeInterfaceMethodRef::from_u16(reference.index, cf)?)),
-                    9 => Ok(Self::InvokeInterface(InterfaceMethodRef::from_u16(reference.index, cf)?)),
-                    x => Err(super::Error::ConstantPoolError(format!("invalid reference_kind {}.", x)))
-                }
-            }
-            x => Err(super::Error::ConstantPoolError(format!(
-                "expected MethodRef or InterfaceMethodRef at constant pool index {}. Instead found {:?}.",
-                index, x
-            ))),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct DynamicInfo<'a> {
-    pub bootstrap_method: BootstrapMethod<'a>,
-    pub name: &'a str,
-    pub descriptor: MethodDescriptor<'a>,
-}
-
-impl<'a> DynamicInfo<'a> {
-    fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
-        match &cf.constant_pool.0[index as usize - 1] {
-            ConstantPoolItem::InvokeDynamic {
-                bootstrap_method_attr_index,
-                name_and_type_index,
-            } => {
-                let bootstrap_methods = cf.bootstrap_methods()?.ok_or_else(|| super::Error::NoBootstrapMethods)?;
-                let bootstrap_method = bootstrap_methods.get(bootstrap_method_attr_index.0)?.ok_or_else(|| super::Error::InvalidBootstrapIndex(bootstrap_method_attr_index.0))?;
-
-                let name = name_and_type_index.get_name(cf)?;
-                let descriptor =
-                    MethodDescriptor::parse(name_and_type_index.get_descriptor(cf)?)?.1;
-                Ok(Self { bootstrap_method, name, descriptor })
-            }
-            x => Err(super::Error::ConstantPoolError(format!(
-                "expected InvokeDynamic at constant pool index {}. Instead found {:?}.",
-                index, x
-            ))),
-        }
-    }
-}
-
-from_u16_binread!(FieldRef);
-from_u16_binread!(MethodRef);
-from_u16_binread!(InterfaceMethodRef);
-from_u16_binread!(MaybeInterfaceMethodRef);
-from_u16_binread!(MethodHandle);
-from_u16_binread!(DynamicInfo);
-
-#[derive(Debug)]
-pub struct BytePad;
-
-impl BinRead for BytePad {
-    type Args<'a> = ();
-
-    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
-        reader: &mut R,
-        _endian: binrw::Endian,
-        _args: Self::Args<'_>,
-    ) -> binrw::prelude::BinResult<Self> {
-        let pos = reader.stream_position()?;
-        let d4 = pos % 4;
-        if d4 == 0 {
-            return Ok(Self);
-        }
-        let skip = 4 - d4;
-        reader.seek(std::io::SeekFrom::Current(skip as i64))?;
-        Ok(Self)
-    }
-}
-
-#[binread]
-#[br(import(cf: &'a ClassFile,))]
-#[derive(Debug)]
-pub enum Instruction<'a> {
-    #[br(magic = 0x32u8)]
-    Aaload,
-    #[br(magic = 0x53u8)]
-    Aastore,
-    #[br(magic = 0x1u8)]
-    AconstNull,
-    #[br(magic = 0x19u8)]
-    Aload { index: u8 },
-    #[br(magic = 0x2au8)]
-    Aload0,
-    #[br(magic = 0x2bu8)]
-    Aload1,
-    #[br(magic = 0x2cu8)]
-    Aload2,
-    #[br(magic = 0x2du8)]
-    Aload3,
-    #[br(magic = 0xbdu8)]
-    Anewarray {
-        #[br(try_map = |x: ClassIndex| x.get_as_string(cf))]
-        class: &'a str,
-    },
-    #[br(magic = 0xb0u8)]
-    Areturn,
-    #[br(magic = 0xbeu8)]
-    Arraylength,
-    #[br(magic = 0x3au8)]
-    Astore { index: u8 },
-    #[br(magic = 0x4bu8)]
-    Astore0,
-    #[br(magic = 0x4cu8)]
-    Astore1,
-    #[br(magic = 0x4du8)]
-    Astore2,
-    #[br(magic = 0x4eu8)]
-    Astore3,
-    #[br(magic = 0xbfu8)]
-    Athrow,
-    #[br(magic = 0x33u8)]
-    Baload,
-    #[br(magic = 0x54u8)]
-    Bastore,
-    #[br(magic = 0x10u8)]
-    Bipush { byte: i8 },
-    #[br(magic = 0x34u8)]
-    Caload,
-    #[br(magic = 0x55u8)]
-    Castore,
-    #[br(magic = 0xc0u8)]
-    Checkcast {
-        #[br(try_map = |x: ClassIndex| x.get_as_string(cf))]
-        class: &'a str,
-    },
-    #[br(magic = 0x90u8)]
-    D2f,
-    #[br(magic = 0x8eu8)]
-    D2i,
-    #[br(magic = 0x8fu8)]
-    D2l,
-    #[br(magic = 0x63u8)]
-    Dadd,
-    #[br(magic = 0x31u8)]
-    Daload,
-    #[br(magic = 0x52u8)]
-    Dastore,
-    #[br(magic = 0x98u8)]
-    Dcmpg,
-    #[br(magic = 0x97u8)]
-    Dcmpl,
-    #[br(magic = 0xeu8)]
-    Dconst0,
-    #[br(magic = 0xfu8)]
-    Dconst1,
-    #[br(magic = 0x6fu8)]
-    Ddiv,
-    #[br(magic = 0x18u8)]
-    Dload { index: u8 },
-    #[br(magic = 0x26u8)]
-    Dload0,
-    #[br(magic = 0x27u8)]
-    Dload1,
-    #[br(magic = 0x28u8)]
-    Dload2,
-    #[br(magic = 0x29u8)]
-    Dload3,
-    #[br(magic = 0x6bu8)]
-    Dmul,
-    #[br(magic = 0x77u8)]
-    Dneg,
-    #[br(magic = 0x73u8)]
-    Drem,
-    #[br(magic = 0xafu8)]
-    Dreturn,
-    #[br(magic = 0x39u8)]
-    Dstore { index: u8 },
-    #[br(magic = 0x47u8)]
-    Dstore0,
-    #[br(magic = 0x48u8)]
-    Dstore1,
-    #[br(magic = 0x49u8)]
-    Dstore2,
-    #[br(magic = 0x4au8)]
-    Dstore3,
-    #[br(magic = 0x67u8)]
-    Dsub,
-    #[br(magic = 0x59u8)]
-    Dup,
-    #[br(magic = 0x5au8)]
-    DupX1,
-    #[br(magic = 0x5bu8)]
-    DupX2,
-    #[br(magic = 0x5cu8)]
-    Dup2,
-    #[br(magic = 0x5du8)]
-    Dup2X1,
-    #[br(magic = 0x5eu8)]
-    Dup2X2,
-    #[br(magic = 0x8du8)]
-    F2d,
-    #[br(magic = 0x8bu8)]
-    F2i,
-    #[br(magic = 0x8cu8)]
-    F2l,
-    #[br(magic = 0x62u8)]
-    Fadd,
-    #[br(magic = 0x30u8)]
-    Faload,
-    #[br(magic = 0x51u8)]
-    Fastore,
-    #[br(magic = 0x96u8)]
-    Fcmpg,
-    #[br(magic = 0x95u8)]
-    Fcmpl,
-    #[br(magic = 0xbu8)]
-    Fconst0,
-    #[br(magic = 0xcu8)]
-    Fconst1,
-    #[br(magic = 0xdu8)]
-    Fconst2,
-    #[br(magic = 0x6eu8)]
-    Fdiv,
-    #[br(magic = 0x17u8)]
-    Fload { index: u8 },
-    #[br(magic = 0x22u8)]
-    Fload0,
-    #[br(magic = 0x23u8)]
-    Fload1,
-    #[br(magic = 0x24u8)]
-    Fload2,
-    #[br(magic = 0x25u8)]
-    Fload3,
-    #[br(magic = 0x6au8)]
-    Fmul,
-    #[br(magic = 0x76u8)]
-    Fneg,
-    #[br(magic = 0x72u8)]
-    Frem,
-    #[br(magic = 0xaeu8)]
-    Freturn,
-    #[br(magic = 0x38u8)]
-    Fstore { index: u8 },
-    #[br(magic = 0x43u8)]
-    Fstore0,
-    #[br(magic = 0x44u8)]
-    Fstore1,
-    #[br(magic = 0x45u8)]
-    Fstore2,
-    #[br(magic = 0x46u8)]
-    Fstore3,
-    #[br(magic = 0x66u8)]
-    Fsub,
-    #[br(magic = 0xb4u8)]
-    Getfield {
-        #[br(args(cf,))]
-        field: FieldRef<'a>,
-    },
-    #[br(magic = 0xb2u8)]
-    Getstatic {
-        #[br(args(cf,))]
-        field: FieldRef<'a>,
-    },
-    #[br(magic = 0xa7u8)]
-    Goto { offset: i16 },
-    #[br(magic = 0xc8u8)]
-    GotoW { offset: i32 },
-    #[br(magic = 0x91u8)]
-    I2b,
-    #[br(magic = 0x92u8)]
-    I2c,
-    #[br(magic = 0x87u8)]
-    I2d,
-    #[br(magic = 0x86u8)]
-    I2f,
-    #[br(magic = 0x85u8)]
-    I2l,
-    #[br(magic = 0x93u8)]
-    I2s,
-    #[br(magic = 0x60u8)]
-    Iadd,
-    #[br(magic = 0x2eu8)]
-    Iaload,
-    #[br(magic = 0x7eu8)]
-    Iand,
-    #[br(magic = 0x4fu8)]
-    Iastore,
-    #[br(magic = 0x2u8)]
-    IconstM1,
-    #[br(magic = 0x3u8)]
-    Iconst0,
-    #[br(magic = 0x4u8)]
-    Iconst1,
-    #[br(magic = 0x5u8)]
-    Iconst2,
-    #[br(magic = 0x6u8)]
-    Iconst3,
-    #[br(magic = 0x7u8)]
-    Iconst4,
-    #[br(magic = 0x8u8)]
-    Iconst5,
-    #[br(magic = 0x6cu8)]
-    Idiv,
-    #[br(magic = 0xa5u8)]
-    IfAcmpeq { offset: i16 },
-    #[br(magic = 0xa6u8)]
-    IfAcmpne { offset: i16 },
-    #[br(magic = 0x9fu8)]
-    IfIcmpeq { offset: i16 },
-    #[br(magic = 0xa0u8)]
-    IfIcmpne { offset: i16 },
-    #[br(magic = 0xa1u8)]
-    IfIcmplt { offset: i16 },
-    #[br(magic = 0xa2u8)]
-    IfIcmpge { offset: i16 },
-    #[br(magic = 0xa3u8)]
-    IfIcmpgt { offset: i16 },
-    #[br(magic = 0xa4u8)]
-    IfIcmple { offset: i16 },
-    #[br(magic = 0x99u8)]
-    Ifeq { offset: i16 },
-    #[br(magic = 0x9au8)]
-    Ifne { offset: i16 },
-    #[br(magic = 0x9bu8)]
-    Iflt { offset: i16 },
-    #[br(magic = 0x9cu8)]
-    Ifge { offset: i16 },
-    #[br(magic = 0x9du8)]
-    Ifgt { offset: i16 },
-    #[br(magic = 0x9eu8)]
-    Ifle { offset: i16 },
-    #[br(magic = 0xc7u8)]
-    Ifnonnull { offset: i16 },
-    #[br(magic = 0xc6u8)]
-    Ifnull { offset: i16 },
-    #[br(magic = 0x84u8)]
-    Iinc { index: u8, constant: i8 },
-    #[br(magic = 0x15u8)]
-    Iload { index: u8 },
-    #[br(magic = 0x1au8)]
-    Iload0,
-    #[br(magic = 0x1bu8)]
-    Iload1,
-    #[br(magic = 0x1cu8)]
-    Iload2,
-    #[br(magic = 0x1du8)]
-    Iload3,
-    #[br(magic = 0x68u8)]
-    Imul,
-    #[br(magic = 0x74u8)]
-    Ineg,
-    #[br(magic = 0xc1u8)]
-    Instanceof {
-        #[br(try_map = |x: ClassIndex| x.get_as_string(cf))]
-        class: &'a str,
-    },
-    #[br(magic = 0xbau8)]
-    Invokedynamic {
-        #[br(args(cf,))]
-        index: DynamicInfo<'a>,
-        _never_used: u16, // IDK why, but the spec says this is followed by two 0x00 bytes.
-    },
-    #[br(magic = 0xb9u8)]
-    Invokeinterface {
-        #[br(args(cf,))]
-        index: InterfaceMethodRef<'a>,
-        count: u8,
-        _never_used: u16, // IDK why, but the spec says this is followed by one 0x00 byte.
-    },
-    #[br(magic = 0xb7u8)]
-    Invokespecial {
-        #[br(args(cf,))]
-        index: MaybeInterfaceMethodRef<'a>,
-    },
-    #[br(magic = 0xb8u8)]
-    Invokestatic {
-        #[br(args(cf,))]
-        index: MaybeInterfaceMethodRef<'a>,
-    },
-    #[br(magic = 0xb6u8)]
-    Invokevirtual {
-        #[br(args(cf,))]
-        index: MethodRef<'a>,
-    },
-    #[br(magic = 0x80u8)]
-    Ior,
-    #[br(magic = 0x70u8)]
-    Irem,
-    #[br(magic = 0xacu8)]
-    Ireturn,
-    #[br(magic = 0x78u8)]
-    Ishl,
-    #[br(magic = 0x7au8)]
-    Ishr,
-    #[br(magic = 0x36u8)]
-    Istore { index: u8 },
-    #[br(magic = 0x3bu8)]
-    Istore0,
-    #[br(magic = 0x3cu8)]
-    Istore1,
-    #[br(magic = 0x3du8)]
-    Istore2,
-    #[br(magic = 0x3eu8)]
-    Istore3,
-    #[br(magic = 0x64u8)]
-    Isub,
-    #[br(magic = 0x7cu8)]
-    Iushr,
-    #[br(magic = 0x82u8)]
-    Ixor,
-    #[br(magic = 0xa8u8)]
-    Jsr { offset: i16 },
-    #[br(magic = 0xc9u8)]
-    JsrW { offset: i32 },
-    #[br(magic = 0x8au8)]
-    L2d,
-    #[br(magic = 0x89u8)]
-    L2f,
-    #[br(magic = 0x88u8)]
-    L2i,
-    #[br(magic = 0x61u8)]
-    Ladd,
-    #[br(magic = 0x2fu8)]
-    Laload,
-    #[br(magic = 0x7fu8)]
-    Land,
-    #[br(magic = 0x50u8)]
-    Lastore,
-    #[br(magic = 0x94u8)]
-    Lcmp,
-    #[br(magic = 0x9u8)]
-    Lconst0,
-    #[br(magic = 0xau8)]
-    Lconst1,
-    #[br(magic = 0x12u8)]
-    Ldc { index: u8 },
-    #[br(magic = 0x13u8)]
-    LdcW { index: u16 },
-    #[br(magic = 0x14u8)]
-    Ldc2W { index: u16 },
-    #[br(magic = 0x6du8)]
-    Ldiv,
-    #[br(magic = 0x16u8)]
-    Lload { index: u8 },
-    #[br(magic = 0x1eu8)]
-    Lload0,
-    #[br(magic = 0x1fu8)]
-    Lload1,
-    #[br(magic = 0x20u8)]
-    Lload2,
-    #[br(magic = 0x21u8)]
-    Lload3,
-    #[br(magic = 0x69u8)]
-    Lmul,
-    #[br(magic = 0x75u8)]
-    Lneg,
-    #[br(magic = 0xabu8)]
-    Lookupswitch {
-        _padding: BytePad,
-        default: i32,
-        #[br(temp)]
-        npairs: u32,
-        #[br(count = npairs)]
-        pairs: Vec<(i32, i32)>,
-    },
-    #[br(magic = 0x81u8)]
-    Lor,
-    #[br(magic = 0x71u8)]
-    Lrem,
-    #[br(magic = 0xadu8)]
-    Lreturn,
-    #[br(magic = 0x79u8)]
-    Lshl,
-    #[br(magic = 0x7bu8)]
-    Lshr,
-    #[br(magic = 0x37u8)]
-    Lstore { index: u8 },
-    #[br(magic = 0x3fu8)]
-    Lstore0,
-    #[br(magic = 0x40u8)]
-    Lstore1,
-    #[br(magic = 0x41u8)]
-    Lstore2,
-    #[br(magic = 0x42u8)]
-    Lstore3,
-    #[br(magic = 0x65u8)]
-    Lsub,
-    #[br(magic = 0x7du8)]
-    Lushr,
-    #[br(magic = 0x83u8)]
-    Lxor,
-    #[br(magic = 0xc2u8)]
-    Monitorenter,
-    #[br(magic = 0xc3u8)]
-    Monitorexit,
-    #[br(magic = 0xc5u8)]
-    Multianewarray {
-        #[br(try_map = |x: ClassIndex| x.get_as_string(cf))]
-        class: &'a str,
-        dimensions: u8,
-    },
-    #[br(magic = 0xbbu8)]
-    New {
-        #[br(try_map = |x: ClassIndex| x.get_as_string(cf))]
-        class: &'a str,
-    },
-    #[br(magic = 0xbcu8)]
-    Newarray { atype: u8 },
-    #[br(magic = 0x0u8)]
-    Nop,
-    #[br(magic = 0x57u8)]
-    Pop,
-    #[br(magic = 0x58u8)]
-    Pop2,
-    #[br(magic = 0xb5u8)]
-    Putfield {
-        #[br(args(cf,))]
-        field: FieldRef<'a>,
-    },
-    #[br(magic = 0xb3u8)]
-    Putstatic {
-        #[br(args(cf,))]
-        field: FieldRef<'a>,
-    },
-    #[br(magic = 0xa9u8)]
-    Ret { index: u8 },
-    #[br(magic = 0xb1u8)]
-    Return,
-    #[br(magic = 0x35u8)]
-    Saload,
-    #[br(magic = 0x56u8)]
-    Sastore,
-    #[br(magic = 0x11u8)]
-    Sipush {
-        #[br(map = |x: u16| x as i32)]
-        value: i32,
-    },
-    #[br(magic = 0x5fu8)]
-    Swap,
-    #[br(magic = 0xaau8)]
-    Tableswitch {
-        _padding: BytePad,
-        default: i32,
-        low: i32,
-        high: i32,
-        #[br(count = high - low + 1)]
-        jump_offsets: Vec<i32>,
-    },
-    #[br(magic = 0xc4u8)]
-    Wide {
-        opcode: u8,
-        index: u16,
-        #[br(if(opcode == 0x84u8))]
-        constant: u16,
-    },
-}
+use binrw::{binread, BinRead};
+
+use crate::{
+    attributes::BootstrapMethod, field::TypeDescriptor, method::MethodDescriptor, raw::{BootstrapMethodAttrInfo, ConstantPoolItem}, ClassFile, ClassIndex, DynamicIndex, MethodTypeIndex
+};
+
+#[derive(Debug)]
+pub struct FieldRef<'a> {
+    pub class: &'a str,
+    pub name: &'a str,
+    pub descriptor: TypeDescriptor<'a>,
+}
+
+#[derive(Debug)]
+pub struct MethodRef<'a> {
+    pub class: &'a str,
+    pub name: &'a str,
+    pub descriptor: MethodDescriptor<'a>,
+}
+
+#[derive(Debug)]
+pub struct InterfaceMethodRef<'a> {
+    pub class: &'a str,
+    pub name: &'a str,
+    pub descriptor: MethodDescriptor<'a>,
+}
+
+impl<'a> FieldRef<'a> {
+    fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
+        match cf.constant_pool.get(index)? {
+            ConstantPoolItem::Fieldref {
+                class_index,
+                name_and_type_index,
+            } => {
+                let class = class_index.get_as_string(cf)?;
+                let name = name_and_type_index.get_name(cf)?;
+                let descriptor = cf.field_descriptor_at(name_and_type_index.get_descriptor_index(cf)?)?;
+                Ok(Self {
+                    class,
+                    name,
+                    descriptor,
+                })
+            }
+            x => Err(super::Error::ConstantPoolError(format!(
+                "expected FieldRef at constant pool index {}. Instead found {:?}.",
+                index, x
+            ))),
+        }
+    }
+}
+
+impl<'a> MethodRef<'a> {
+    fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
+        match cf.constant_pool.get(index)? {
+            ConstantPoolItem::Methodref {
+                class_index,
+                name_and_type_index,
+            } => {
+                let class = class_index.get_as_string(cf)?;
+                let name = name_and_type_index.get_name(cf)?;
+                let descriptor = cf.method_descriptor_at(name_and_type_index.get_descriptor_index(cf)?)?;
+                Ok(Self {
+                    class,
+                    name,
+                    descriptor,
+                })
+            }
+            x => Err(super::Error::ConstantPoolError(format!(
+                "expected MethodRef at constant pool index {}. Instead found {:?}.",
+                index, x
+            ))),
+        }
+    }
+}
+
+impl<'a> InterfaceMethodRef<'a> {
+    fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
+        match cf.constant_pool.get(index)? {
+            ConstantPoolItem::InterfaceMethodref {
+                class_index,
+                name_and_type_index,
+            } => {
+                let class = class_index.get_as_string(cf)?;
+                let name = name_and_type_index.get_name(cf)?;
+                let descriptor = cf.method_descriptor_at(name_and_type_index.get_descriptor_index(cf)?)?;
+                Ok(Self {
+                    class,
+                    name,
+                    descriptor,
+                })
+            }
+            x => Err(super::Error::ConstantPoolError(format!(
+                "expected InterfaceMethodRef at constant pool index {}. Instead found {:?}.",
+                index, x
+            ))),
+        }
+    }
+}
+
+macro_rules! from_u16_binread {
+    ($class:ident) => {
+        impl<'a> BinRead for $class<'a> {
+            type Args<'b> = (&'a ClassFile,);
+
+            fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
+                reader: &mut R,
+                endian: binrw::Endian,
+                (cf,): Self::Args<'_>,
+            ) -> binrw::prelude::BinResult<Self> {
+                let pos = reader.stream_position()?;
+                let index = u16::read_options(reader, endian, ())?;
+                Self::from_u16(index, cf).map_err(|x| binrw::Error::Custom {
+                    pos,
+                    err: Box::new(x),
+                })
+            }
+        }
+    };
+}
+
+#[derive(Debug)]
+pub enum MaybeInterfaceMethodRef<'a> {
+    RegularMethod(MethodRef<'a>),
+    InterfaceMethod(InterfaceMethodRef<'a>),
+}
+
+impl<'a> MaybeInterfaceMethodRef<'a> {
+    fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
+        match cf.constant_pool.get(index)? {
+            ConstantPoolItem::InterfaceMethodref { .. } => {
+                Ok(Self::InterfaceMethod(InterfaceMethodRef::from_u16(index, cf)?))
+            }
+            ConstantPoolItem::Methodref { .. } => {
+                Ok(Self::RegularMethod(MethodRef::from_u16(index, cf)?))
+            }
+            x => Err(super::Error::ConstantPoolError(format!(
+                "expected MethodRef or InterfaceMethodRef at constant pool index {}. Instead found {:?}.",
+                index, x
+            ))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MethodHandle<'a> {
+    GetField(FieldRef<'a>),
+    GetStatic(FieldRef<'a>),
+    PutField(FieldRef<'a>),
+    PutStatic(FieldRef<'a>),
+    InvokeVirtual(MethodRef<'a>),
+    NewInvokeSpecial(MethodRef<'a>),
+    InvokeStatic(MaybeInterfaceMethodRef<'a>),
+    InvokeSpecial(MaybeInterfaceMethodRef<'a>),
+    InvokeInterface(InterfaceMethodRef<'a>),
+}
+
+impl<'a> MethodHandle<'a> {
+    pub(crate) fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
+        match cf.constant_pool.get(index)? {
+            ConstantPoolItem::MethodHandle { reference } => {
+                match reference.kind {
+                    1 => Ok(Self::GetField(FieldRef::from_u16(reference.index, cf)?)),
+                    2 => Ok(Self::GetStatic(FieldRef::from_u16(reference.index, cf)?)),
+                    3 => Ok(Self::PutField(FieldRef::from_u16(reference.index, cf)?)),
+                    4 => Ok(Self::PutStatic(FieldRef::from_u16(reference.index, cf)?)),
+                    5 => Ok(Self::InvokeVirtual(MethodRef::from_u16(reference.index, cf)?)),
+                    8 => Ok(Self::NewInvokeSpecial(MethodRef::from_u16(reference.index, cf)?)),
+                    6 => Ok(Self::InvokeStatic(MaybeInterfaceMethodRef::from_u16(reference.index, cf)?)),
+                    7 => Ok(Self::InvokeSpecial(MaybeInterfaceMethodRef::from_u16(reference.index, cf)?)),
+                    9 => Ok(Self::InvokeInterface(InterfaceMethodRef::from_u16(reference.index, cf)?)),
+                    x => Err(super::Error::ConstantPoolError(format!("invalid reference_kind {}.", x)))
+                }
+            }
+            x => Err(super::Error::ConstantPoolError(format!(
+                "expected MethodRef or InterfaceMethodRef at constant pool index {}. Instead found {:?}.",
+                index, x
+            ))),
+        }
+    }
+
+    /// The class declaring the referenced field or method.
+    pub fn class(&self) -> &'a str {
+        match self {
+            Self::GetField(x) | Self::GetStatic(x) | Self::PutField(x) | Self::PutStatic(x) => x.class,
+            Self::InvokeVirtual(x) | Self::NewInvokeSpecial(x) => x.class,
+            Self::InvokeStatic(x) | Self::InvokeSpecial(x) => match x {
+                MaybeInterfaceMethodRef::RegularMethod(x) => x.class,
+                MaybeInterfaceMethodRef::InterfaceMethod(x) => x.class,
+            },
+            Self::InvokeInterface(x) => x.class,
+        }
+    }
+
+    /// The name of the referenced field or method.
+    pub fn name(&self) -> &'a str {
+        match self {
+            Self::GetField(x) | Self::GetStatic(x) | Self::PutField(x) | Self::PutStatic(x) => x.name,
+            Self::InvokeVirtual(x) | Self::NewInvokeSpecial(x) => x.name,
+            Self::InvokeStatic(x) | Self::InvokeSpecial(x) => match x {
+                MaybeInterfaceMethodRef::RegularMethod(x) => x.name,
+                MaybeInterfaceMethodRef::InterfaceMethod(x) => x.name,
+            },
+            Self::InvokeInterface(x) => x.name,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DynamicInfo<'a> {
+    pub bootstrap_method: BootstrapMethod<'a>,
+    pub name: &'a str,
+    pub descriptor: MethodDescriptor<'a>,
+}
+
+impl<'a> DynamicInfo<'a> {
+    fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
+        match cf.constant_pool.get(index)? {
+            ConstantPoolItem::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                let bootstrap_method = bootstrap_method(bootstrap_method_attr_index, cf)?;
+                let name = name_and_type_index.get_name(cf)?;
+                let descriptor = cf.method_descriptor_at(name_and_type_index.get_descriptor_index(cf)?)?;
+                Ok(Self { bootstrap_method, name, descriptor })
+            }
+            x => Err(super::Error::ConstantPoolError(format!(
+                "expected InvokeDynamic at constant pool index {}. Instead found {:?}.",
+                index, x
+            ))),
+        }
+    }
+
+    /// Whether the bootstrap method is one of the static methods `names` of `class`.
+    fn bootstraps_with(&self, class: &str, names: &[&str]) -> bool {
+        let method = &self.bootstrap_method.method;
+        matches!(method, MethodHandle::InvokeStatic(_)) && method.class() == class && names.contains(&method.name())
+    }
+
+    /// The lambda or method reference created by this call site, if it is
+    /// bootstrapped by `LambdaMetafactory` with the arguments javac passes.
+    pub fn lambda(&self) -> super::Result<Option<Lambda<'a>>> {
+        if !self.bootstraps_with("java/lang/invoke/LambdaMetafactory", &["metafactory", "altMetafactory"]) {
+            return Ok(None);
+        }
+        let interface = match self.descriptor.return_type() {
+            Some(TypeDescriptor::ClassName(x)) => *x,
+            _ => return Ok(None),
+        };
+        let mut arguments = self.bootstrap_method.arguments()?.into_iter();
+        match (arguments.next(), arguments.next(), arguments.next()) {
+            (
+                Some(LoadableConstant::MethodType(sam_type)),
+                Some(LoadableConstant::MethodHandle(implementation)),
+                Some(LoadableConstant::MethodType(instantiated_type)),
+            ) => Ok(Some(Lambda {
+                interface,
+                method_name: self.name,
+                sam_type,
+                instantiated_type,
+                implementation,
+                captured: self.descriptor.parameter_types().to_vec(),
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    /// The string concatenation done by this call site, if it is bootstrapped
+    /// by `StringConcatFactory`.
+    pub fn string_concat(&self) -> super::Result<Option<StringConcat<'a>>> {
+        if !self.bootstraps_with("java/lang/invoke/StringConcatFactory", &["makeConcat", "makeConcatWithConstants"]) {
+            return Ok(None);
+        }
+        let mut recipe = None;
+        let mut constants = Vec::new();
+        if self.bootstrap_method.method.name() == "makeConcatWithConstants" {
+            let mut arguments = self.bootstrap_method.arguments()?.into_iter();
+            match arguments.next() {
+                Some(LoadableConstant::String(x)) => recipe = Some(x),
+                _ => return Ok(None),
+            }
+            constants.extend(arguments);
+        }
+        Ok(Some(StringConcat {
+            recipe,
+            constants,
+            arguments: self.descriptor.parameter_types().to_vec(),
+        }))
+    }
+}
+
+/// A lambda or method reference, an `invokedynamic` bootstrapped by
+/// `LambdaMetafactory`. See [`DynamicInfo::lambda`].
+#[derive(Debug)]
+pub struct Lambda<'a> {
+    /// The functional interface implemented, e.g. `java/util/function/Function`.
+    pub interface: &'a str,
+    /// The name of the interface method, e.g. `apply`.
+    pub method_name: &'a str,
+    /// The erased descriptor of the interface method.
+    pub sam_type: MethodDescriptor<'a>,
+    /// The descriptor of the interface method with its generics filled in.
+    pub instantiated_type: MethodDescriptor<'a>,
+    /// The method called by the lambda: a synthetic `lambda$` method for a
+    /// lambda expression, or the referenced method for a method reference.
+    pub implementation: MethodHandle<'a>,
+    /// The types of the captured values, taken from the stack by `invokedynamic`.
+    pub captured: Vec<TypeDescriptor<'a>>,
+}
+
+/// A string concatenation, an `invokedynamic` bootstrapped by
+/// `StringConcatFactory`. See [`DynamicInfo::string_concat`].
+#[derive(Debug)]
+pub struct StringConcat<'a> {
+    /// The recipe given to `makeConcatWithConstants`, in which `\u{1}` stands
+    /// for the next argument and `\u{2}` for the next constant. `None` for
+    /// `makeConcat`, which joins the arguments as they are.
+    pub recipe: Option<&'a str>,
+    /// The constants substituted for `\u{2}` in the recipe.
+    pub constants: Vec<LoadableConstant<'a>>,
+    /// The types of the values concatenated, taken from the stack by `invokedynamic`.
+    pub arguments: Vec<TypeDescriptor<'a>>,
+}
+
+fn bootstrap_method<'a>(index: &BootstrapMethodAttrInfo, cf: &'a ClassFile) -> super::Result<BootstrapMethod<'a>> {
+    let bootstrap_methods = cf.bootstrap_methods()?.ok_or_else(|| super::Error::NoBootstrapMethods)?;
+    bootstrap_methods.get(index.0)?.ok_or_else(|| super::Error::InvalidBootstrapIndex(index.0))
+}
+
+/// A `CONSTANT_Dynamic`, whose value is computed by its bootstrap method. Unlike
+/// [`DynamicInfo`], it is described by a field descriptor.
+#[derive(Debug)]
+pub struct DynamicConstant<'a> {
+    pub bootstrap_method: BootstrapMethod<'a>,
+    pub name: &'a str,
+    pub descriptor: TypeDescriptor<'a>,
+}
+
+impl<'a> DynamicConstant<'a> {
+    fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
+        match cf.constant(index) {
+            Some(ConstantPoolItem::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            }) => {
+                let bootstrap_method = bootstrap_method(bootstrap_method_attr_index, cf)?;
+                let name = name_and_type_index.get_name(cf)?;
+                let descriptor = cf.field_descriptor_at(name_and_type_index.get_descriptor_index(cf)?)?;
+                Ok(Self { bootstrap_method, name, descriptor })
+            }
+            x => Err(super::Error::ConstantPoolError(format!(
+                "expected Dynamic at constant pool index {}. Instead found {:?}.",
+                index, x
+            ))),
+        }
+    }
+}
+
+impl DynamicIndex {
+    pub fn resolve<'a>(&self, class: &'a ClassFile) -> super::Result<DynamicConstant<'a>> {
+        DynamicConstant::from_u16(self.0, class)
+    }
+}
+
+/// The operand of `ldc`, `ldc_w` and `ldc2_w`, one of the loadable constants of §4.4.
+#[derive(Debug)]
+pub enum LoadableConstant<'a> {
+    Int(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    String(&'a str),
+    Class(&'a str),
+    MethodType(MethodDescriptor<'a>),
+    MethodHandle(MethodHandle<'a>),
+    Dynamic(DynamicConstant<'a>),
+}
+
+impl<'a> LoadableConstant<'a> {
+    pub(crate) fn from_u16(index: u16, cf: &'a ClassFile) -> super::Result<Self> {
+        match cf.constant(index) {
+            Some(ConstantPoolItem::Integer { value }) => Ok(Self::Int(*value)),
+            Some(ConstantPoolItem::Float { value }) => Ok(Self::Float(*value)),
+            Some(ConstantPoolItem::Long { value }) => Ok(Self::Long(*value)),
+            Some(ConstantPoolItem::Double { value }) => Ok(Self::Double(*value)),
+            Some(ConstantPoolItem::String { string_index }) => Ok(Self::String(string_index.get_as_string(cf)?)),
+            Some(ConstantPoolItem::Class { name_index }) => Ok(Self::Class(name_index.get_as_string(cf)?)),
+            Some(ConstantPoolItem::MethodType { .. }) => Ok(Self::MethodType(MethodTypeIndex(index).get_descriptor(cf)?)),
+            Some(ConstantPoolItem::MethodHandle { .. }) => Ok(Self::MethodHandle(MethodHandle::from_u16(index, cf)?)),
+            Some(ConstantPoolItem::Dynamic { .. }) => Ok(Self::Dynamic(DynamicConstant::from_u16(index, cf)?)),
+            x => Err(super::Error::ConstantPoolError(format!(
+                "expected a loadable constant at constant pool index {}. Instead found {:?}.",
+                index, x
+            ))),
+        }
+    }
+}
+
+from_u16_binread!(FieldRef);
+from_u16_binread!(MethodRef);
+from_u16_binread!(InterfaceMethodRef);
+from_u16_binread!(MaybeInterfaceMethodRef);
+from_u16_binread!(MethodHandle);
+from_u16_binread!(DynamicInfo);
+from_u16_binread!(DynamicConstant);
+
+/// The highest opcode defined by the JVM specification, `jsr_w`. Every opcode
+/// up to it is a variant of [`Instruction`]; those above are reserved.
+pub(crate) const LAST_OPCODE: u8 = 0xc9;
+
+/// How the operands following an opcode are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperandFormat {
+    None,
+    /// A signed byte, for `bipush`.
+    Byte,
+    /// A signed two-byte value, for `sipush`.
+    Short,
+    /// A one-byte local variable index.
+    LocalIndex,
+    /// A one-byte constant pool index, for `ldc`.
+    ConstantIndexByte,
+    /// A two-byte constant pool index.
+    ConstantIndex,
+    /// A one-byte local variable index and a signed byte.
+    Iinc,
+    /// A signed two-byte branch offset.
+    Branch,
+    /// A signed four-byte branch offset, for `goto_w` and `jsr_w`.
+    BranchWide,
+    Tableswitch,
+    Lookupswitch,
+    /// A two-byte constant pool index, a count and a zero byte.
+    InvokeInterface,
+    /// A two-byte constant pool index and two zero bytes.
+    InvokeDynamic,
+    /// An `atype` byte, see [`ArrayPrimitiveType`].
+    ArrayType,
+    /// A two-byte constant pool index and a dimension count.
+    Multianewarray,
+    /// Another opcode with widened operands.
+    Wide,
+}
+
+impl OperandFormat {
+    /// The number of operand bytes, or `None` for the switches and `wide`,
+    /// whose length depends on what follows.
+    pub fn size(&self) -> Option<u32> {
+        match self {
+            Self::None => Some(0),
+            Self::Byte | Self::LocalIndex | Self::ConstantIndexByte | Self::ArrayType => Some(1),
+            Self::Short | Self::ConstantIndex | Self::Iinc | Self::Branch => Some(2),
+            Self::Multianewarray => Some(3),
+            Self::BranchWide | Self::InvokeInterface | Self::InvokeDynamic => Some(4),
+            Self::Tableswitch | Self::Lookupswitch | Self::Wide => None,
+        }
+    }
+
+    /// The constant pool index that `operands` in this format start with, if
+    /// the format has one.
+    pub(crate) fn constant_index(&self, operands: &[u8]) -> Option<u16> {
+        match self {
+            Self::ConstantIndexByte => operands.first().map(|x| *x as u16),
+            Self::ConstantIndex | Self::InvokeInterface | Self::InvokeDynamic | Self::Multianewarray => {
+                operands.get(..2).map(|x| u16::from_be_bytes([x[0], x[1]]))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An entry of [`OPCODE_TABLE`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpcodeInfo {
+    pub mnemonic: &'static str,
+    pub operands: OperandFormat,
+}
+
+const fn op(mnemonic: &'static str, operands: OperandFormat) -> OpcodeInfo {
+    OpcodeInfo { mnemonic, operands }
+}
+
+/// Every opcode defined by the JVM specification, indexed by opcode.
+pub static OPCODE_TABLE: [OpcodeInfo; LAST_OPCODE as usize + 1] = [
+    op("nop", OperandFormat::None),
+    op("aconst_null", OperandFormat::None),
+    op("iconst_m1", OperandFormat::None),
+    op("iconst_0", OperandFormat::None),
+    op("iconst_1", OperandFormat::None),
+    op("iconst_2", OperandFormat::None),
+    op("iconst_3", OperandFormat::None),
+    op("iconst_4", OperandFormat::None),
+    op("iconst_5", OperandFormat::None),
+    op("lconst_0", OperandFormat::None),
+    op("lconst_1", OperandFormat::None),
+    op("fconst_0", OperandFormat::None),
+    op("fconst_1", OperandFormat::None),
+    op("fconst_2", OperandFormat::None),
+    op("dconst_0", OperandFormat::None),
+    op("dconst_1", OperandFormat::None),
+    op("bipush", OperandFormat::Byte),
+    op("sipush", OperandFormat::Short),
+    op("ldc", OperandFormat::ConstantIndexByte),
+    op("ldc_w", OperandFormat::ConstantIndex),
+    op("ldc2_w", OperandFormat::ConstantIndex),
+    op("iload", OperandFormat::LocalIndex),
+    op("lload", OperandFormat::LocalIndex),
+    op("fload", OperandFormat::LocalIndex),
+    op("dload", OperandFormat::LocalIndex),
+    op("aload", OperandFormat::LocalIndex),
+    op("iload_0", OperandFormat::None),
+    op("iload_1", OperandFormat::None),
+    op("iload_2", OperandFormat::None),
+    op("iload_3", OperandFormat::None),
+    op("lload_0", OperandFormat::None),
+    op("lload_1", OperandFormat::None),
+    op("lload_2", OperandFormat::None),
+    op("lload_3", OperandFormat::None),
+    op("fload_0", OperandFormat::None),
+    op("fload_1", OperandFormat::None),
+    op("fload_2", OperandFormat::None),
+    op("fload_3", OperandFormat::None),
+    op("dload_0", OperandFormat::None),
+    op("dload_1", OperandFormat::None),
+    op("dload_2", OperandFormat::None),
+    op("dload_3", OperandFormat::None),
+    op("aload_0", OperandFormat::None),
+    op("aload_1", OperandFormat::None),
+    op("aload_2", OperandFormat::None),
+    op("aload_3", OperandFormat::None),
+    op("iaload", OperandFormat::None),
+    op("laload", OperandFormat::None),
+    op("faload", OperandFormat::None),
+    op("daload", OperandFormat::None),
+    op("aaload", OperandFormat::None),
+    op("baload", OperandFormat::None),
+    op("caload", OperandFormat::None),
+    op("saload", OperandFormat::None),
+    op("istore", OperandFormat::LocalIndex),
+    op("lstore", OperandFormat::LocalIndex),
+    op("fstore", OperandFormat::LocalIndex),
+    op("dstore", OperandFormat::LocalIndex),
+    op("astore", OperandFormat::LocalIndex),
+    op("istore_0", OperandFormat::None),
+    op("istore_1", OperandFormat::None),
+    op("istore_2", OperandFormat::None),
+    op("istore_3", OperandFormat::None),
+    op("lstore_0", OperandFormat::None),
+    op("lstore_1", OperandFormat::None),
+    op("lstore_2", OperandFormat::None),
+    op("lstore_3", OperandFormat::None),
+    op("fstore_0", OperandFormat::None),
+    op("fstore_1", OperandFormat::None),
+    op("fstore_2", OperandFormat::None),
+    op("fstore_3", OperandFormat::None),
+    op("dstore_0", OperandFormat::None),
+    op("dstore_1", OperandFormat::None),
+    op("dstore_2", OperandFormat::None),
+    op("dstore_3", OperandFormat::None),
+    op("astore_0", OperandFormat::None),
+    op("astore_1", OperandFormat::None),
+    op("astore_2", OperandFormat::None),
+    op("astore_3", OperandFormat::None),
+    op("iastore", OperandFormat::None),
+    op("lastore", OperandFormat::None),
+    op("fastore", OperandFormat::None),
+    op("dastore", OperandFormat::None),
+    op("aastore", OperandFormat::None),
+    op("bastore", OperandFormat::None),
+    op("castore", OperandFormat::None),
+    op("sastore", OperandFormat::None),
+    op("pop", OperandFormat::None),
+    op("pop2", OperandFormat::None),
+    op("dup", OperandFormat::None),
+    op("dup_x1", OperandFormat::None),
+    op("dup_x2", OperandFormat::None),
+    op("dup2", OperandFormat::None),
+    op("dup2_x1", OperandFormat::None),
+    op("dup2_x2", OperandFormat::None),
+    op("swap", OperandFormat::None),
+    op("iadd", OperandFormat::None),
+    op("ladd", OperandFormat::None),
+    op("fadd", OperandFormat::None),
+    op("dadd", OperandFormat::None),
+    op("isub", OperandFormat::None),
+    op("lsub", OperandFormat::None),
+    op("fsub", OperandFormat::None),
+    op("dsub", OperandFormat::None),
+    op("imul", OperandFormat::None),
+    op("lmul", OperandFormat::None),
+    op("fmul", OperandFormat::None),
+    op("dmul", OperandFormat::None),
+    op("idiv", OperandFormat::None),
+    op("ldiv", OperandFormat::None),
+    op("fdiv", OperandFormat::None),
+    op("ddiv", OperandFormat::None),
+    op("irem", OperandFormat::None),
+    op("lrem", OperandFormat::None),
+    op("frem", OperandFormat::None),
+    op("drem", OperandFormat::None),
+    op("ineg", OperandFormat::None),
+    op("lneg", OperandFormat::None),
+    op("fneg", OperandFormat::None),
+    op("dneg", OperandFormat::None),
+    op("ishl", OperandFormat::None),
+    op("lshl", OperandFormat::None),
+    op("ishr", OperandFormat::None),
+    op("lshr", OperandFormat::None),
+    op("iushr", OperandFormat::None),
+    op("lushr", OperandFormat::None),
+    op("iand", OperandFormat::None),
+    op("land", OperandFormat::None),
+    op("ior", OperandFormat::None),
+    op("lor", OperandFormat::None),
+    op("ixor", OperandFormat::None),
+    op("lxor", OperandFormat::None),
+    op("iinc", OperandFormat::Iinc),
+    op("i2l", OperandFormat::None),
+    op("i2f", OperandFormat::None),
+    op("i2d", OperandFormat::None),
+    op("l2i", OperandFormat::None),
+    op("l2f", OperandFormat::None),
+    op("l2d", OperandFormat::None),
+    op("f2i", OperandFormat::None),
+    op("f2l", OperandFormat::None),
+    op("f2d", OperandFormat::None),
+    op("d2i", OperandFormat::None),
+    op("d2l", OperandFormat::None),
+    op("d2f", OperandFormat::None),
+    op("i2b", OperandFormat::None),
+    op("i2c", OperandFormat::None),
+    op("i2s", OperandFormat::None),
+    op("lcmp", OperandFormat::None),
+    op("fcmpl", OperandFormat::None),
+    op("fcmpg", OperandFormat::None),
+    op("dcmpl", OperandFormat::None),
+    op("dcmpg", OperandFormat::None),
+    op("ifeq", OperandFormat::Branch),
+    op("ifne", OperandFormat::Branch),
+    op("iflt", OperandFormat::Branch),
+    op("ifge", OperandFormat::Branch),
+    op("ifgt", OperandFormat::Branch),
+    op("ifle", OperandFormat::Branch),
+    op("if_icmpeq", OperandFormat::Branch),
+    op("if_icmpne", OperandFormat::Branch),
+    op("if_icmplt", OperandFormat::Branch),
+    op("if_icmpge", OperandFormat::Branch),
+    op("if_icmpgt", OperandFormat::Branch),
+    op("if_icmple", OperandFormat::Branch),
+    op("if_acmpeq", OperandFormat::Branch),
+    op("if_acmpne", OperandFormat::Branch),
+    op("goto", OperandFormat::Branch),
+    op("jsr", OperandFormat::Branch),
+    op("ret", OperandFormat::LocalIndex),
+    op("tableswitch", OperandFormat::Tableswitch),
+    op("lookupswitch", OperandFormat::Lookupswitch),
+    op("ireturn", OperandFormat::None),
+    op("lreturn", OperandFormat::None),
+    op("freturn", OperandFormat::None),
+    op("dreturn", OperandFormat::None),
+    op("areturn", OperandFormat::None),
+    op("return", OperandFormat::None),
+    op("getstatic", OperandFormat::ConstantIndex),
+    op("putstatic", OperandFormat::ConstantIndex),
+    op("getfield", OperandFormat::ConstantIndex),
+    op("putfield", OperandFormat::ConstantIndex),
+    op("invokevirtual", OperandFormat::ConstantIndex),
+    op("invokespecial", OperandFormat::ConstantIndex),
+    op("invokestatic", OperandFormat::ConstantIndex),
+    op("invokeinterface", OperandFormat::InvokeInterface),
+    op("invokedynamic", OperandFormat::InvokeDynamic),
+    op("new", OperandFormat::ConstantIndex),
+    op("newarray", OperandFormat::ArrayType),
+    op("anewarray", OperandFormat::ConstantIndex),
+    op("arraylength", OperandFormat::None),
+    op("athrow", OperandFormat::None),
+    op("checkcast", OperandFormat::ConstantIndex),
+    op("instanceof", OperandFormat::ConstantIndex),
+    op("monitorenter", OperandFormat::None),
+    op("monitorexit", OperandFormat::None),
+    op("wide", OperandFormat::Wide),
+    op("multianewarray", OperandFormat::Multianewarray),
+    op("ifnull", OperandFormat::Branch),
+    op("ifnonnull", OperandFormat::Branch),
+    op("goto_w", OperandFormat::BranchWide),
+    op("jsr_w", OperandFormat::BranchWide),
+];
+
+/// An instruction as laid out in a `code` array, see [`raw_instructions`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RawInstruction<'c> {
+    pub(crate) pc: u32,
+    /// The layout of the operands, [`OperandFormat::Wide`] for the `wide` forms.
+    pub(crate) format: OperandFormat,
+    /// The encoded instruction, from the opcode to the last operand, switch
+    /// padding included.
+    pub(crate) bytes: &'c [u8],
+}
+
+impl RawInstruction<'_> {
+    /// The constant pool index among the operands, if there is one.
+    pub(crate) fn constant_index(&self) -> Option<u16> {
+        self.format.constant_index(&self.bytes[1..])
+    }
+}
+
+/// Splits `code` into instructions by the operand formats of
+/// [`OPCODE_TABLE`], without decoding their operands. This is the layout
+/// [`Instruction`] reads, for callers that copy or rewrite instructions as
+/// bytes. Switch padding is aligned to the start of `code`.
+pub(crate) fn raw_instructions(code: &[u8]) -> crate::Result<Vec<RawInstruction<'_>>> {
+    let mut res = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let (format, length) = instruction_length(code, pc)?;
+        let bytes = code.get(pc..pc + length).ok_or(crate::Error::LengthExceedsInput {
+            offset: pc as u64,
+            length: length as u64,
+            remaining: (code.len() - pc) as u64,
+        })?;
+        res.push(RawInstruction {
+            pc: pc as u32,
+            format,
+            bytes,
+        });
+        pc += length;
+    }
+    Ok(res)
+}
+
+/// The operand format and encoded length of the instruction at `pc`, which
+/// may run past the end of `code`.
+fn instruction_length(code: &[u8], pc: usize) -> crate::Result<(OperandFormat, usize)> {
+    let invalid = |message: String| {
+        crate::Error::from(binrw::Error::AssertFail {
+            pos: pc as u64,
+            message,
+        })
+    };
+    let opcode = code[pc];
+    let format = match OPCODE_TABLE.get(opcode as usize) {
+        Some(x) => x.operands,
+        None => return Err(invalid(format!("unknown opcode {:#04x}", opcode))),
+    };
+    if let Some(size) = format.size() {
+        return Ok((format, 1 + size as usize));
+    }
+    // Switch operands start at the next multiple of four.
+    let start = pc + 1 + (3 - pc % 4);
+    let int = |at: usize| {
+        code.get(start + at..start + at + 4)
+            .map(|x| i32::from_be_bytes([x[0], x[1], x[2], x[3]]) as i64)
+    };
+    let (header, count, entry_size) = match format {
+        OperandFormat::Tableswitch => (12, int(8).zip(int(4)).map(|(high, low)| high - low + 1), 4),
+        OperandFormat::Lookupswitch => (8, int(4), 8),
+        _ => {
+            let length = match code.get(pc + 1) {
+                None => 2,
+                Some(0x84) => 6,
+                Some(x) if OPCODE_TABLE.get(*x as usize).map(|x| x.operands) == Some(OperandFormat::LocalIndex) => 4,
+                Some(x) => return Err(invalid(format!("wide {:#04x}", x))),
+            };
+            return Ok((format, length));
+        }
+    };
+    match count {
+        Some(x) if x < 0 => Err(invalid(format!("negative switch table length {}", x))),
+        Some(x) => Ok((format, start - pc + header + x as usize * entry_size)),
+        // A truncated header, reported by the caller.
+        None => Ok((format, start - pc + header)),
+    }
+}
+
+/// A signed jump offset, relative to the pc of the branching instruction.
+#[binread]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BranchOffset(pub i32);
+
+impl BranchOffset {
+    fn short(offset: i16) -> Self {
+        Self(offset as i32)
+    }
+
+    /// The absolute pc this offset jumps to from `pc`, if it is representable.
+    pub fn target(&self, pc: u32) -> Option<u32> {
+        u32::try_from(pc as i64 + self.0 as i64).ok()
+    }
+}
+
+/// An index into the local variable array, widened from `u8` for the narrow forms.
+#[binread]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalIndex(pub u16);
+
+/// The element type of a `newarray`, by its `atype` code from §6.5.
+#[binread]
+#[br(repr = u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArrayPrimitiveType {
+    Boolean = 4,
+    Char = 5,
+    Float = 6,
+    Double = 7,
+    Byte = 8,
+    Short = 9,
+    Int = 10,
+    Long = 11,
+}
+
+impl ArrayPrimitiveType {
+    pub fn descriptor(&self) -> TypeDescriptor<'static> {
+        match self {
+            Self::Boolean => TypeDescriptor::Boolean,
+            Self::Char => TypeDescriptor::Char,
+            Self::Float => TypeDescriptor::Float,
+            Self::Double => TypeDescriptor::Double,
+            Self::Byte => TypeDescriptor::Byte,
+            Self::Short => TypeDescriptor::Short,
+            Self::Int => TypeDescriptor::Int,
+            Self::Long => TypeDescriptor::Long,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BytePad;
+
+impl BinRead for BytePad {
+    type Args<'a> = ();
+
+    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
+        reader: &mut R,
+        _endian: binrw::Endian,
+        _args: Self::Args<'_>,
+    ) -> binrw::prelude::BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let d4 = pos % 4;
+        if d4 == 0 {
+            return Ok(Self);
+        }
+        let skip = 4 - d4;
+        reader.seek(std::io::SeekFrom::Current(skip as i64))?;
+        Ok(Self)
+    }
+}
+
+/// Reads the `count` entries of a switch table after checking that the rest of
+/// the code can hold `count * entry_size` bytes.
+fn read_table<R, T>(
+    reader: &mut R,
+    endian: binrw::Endian,
+    (count, entry_size): (i64, u64),
+) -> binrw::BinResult<Vec<T>>
+where
+    R: std::io::Read + std::io::Seek,
+    T: for<'b> BinRead<Args<'b> = ()>,
+{
+    if count < 0 {
+        return Err(binrw::Error::AssertFail {
+            pos: reader.stream_position()?,
+            message: format!("negative switch table length {}", count),
+        });
+    }
+    crate::raw::ensure_remaining(reader, count as u64 * entry_size)?;
+    (0..count).map(|_| T::read_options(reader, endian, ())).collect()
+}
+
+#[binread]
+#[br(import(cf: &'a ClassFile,))]
+#[derive(Debug)]
+pub enum Instruction<'a> {
+    #[br(magic = 0x32u8)]
+    Aaload,
+    #[br(magic = 0x53u8)]
+    Aastore,
+    #[br(magic = 0x1u8)]
+    AconstNull,
+    #[br(magic = 0x19u8)]
+    Aload {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+    },
+    #[br(magic = 0x2au8)]
+    Aload0,
+    #[br(magic = 0x2bu8)]
+    Aload1,
+    #[br(magic = 0x2cu8)]
+    Aload2,
+    #[br(magic = 0x2du8)]
+    Aload3,
+    #[br(magic = 0xbdu8)]
+    Anewarray {
+        #[br(try_map = |x: ClassIndex| x.get_as_string(cf))]
+        class: &'a str,
+    },
+    #[br(magic = 0xb0u8)]
+    Areturn,
+    #[br(magic = 0xbeu8)]
+    Arraylength,
+    #[br(magic = 0x3au8)]
+    Astore {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+    },
+    #[br(magic = 0x4bu8)]
+    Astore0,
+    #[br(magic = 0x4cu8)]
+    Astore1,
+    #[br(magic = 0x4du8)]
+    Astore2,
+    #[br(magic = 0x4eu8)]
+    Astore3,
+    #[br(magic = 0xbfu8)]
+    Athrow,
+    #[br(magic = 0x33u8)]
+    Baload,
+    #[br(magic = 0x54u8)]
+    Bastore,
+    #[br(magic = 0x10u8)]
+    Bipush { value: i8 },
+    #[br(magic = 0x34u8)]
+    Caload,
+    #[br(magic = 0x55u8)]
+    Castore,
+    #[br(magic = 0xc0u8)]
+    Checkcast {
+        #[br(try_map = |x: ClassIndex| x.get_as_string(cf))]
+        class: &'a str,
+    },
+    #[br(magic = 0x90u8)]
+    D2f,
+    #[br(magic = 0x8eu8)]
+    D2i,
+    #[br(magic = 0x8fu8)]
+    D2l,
+    #[br(magic = 0x63u8)]
+    Dadd,
+    #[br(magic = 0x31u8)]
+    Daload,
+    #[br(magic = 0x52u8)]
+    Dastore,
+    #[br(magic = 0x98u8)]
+    Dcmpg,
+    #[br(magic = 0x97u8)]
+    Dcmpl,
+    #[br(magic = 0xeu8)]
+    Dconst0,
+    #[br(magic = 0xfu8)]
+    Dconst1,
+    #[br(magic = 0x6fu8)]
+    Ddiv,
+    #[br(magic = 0x18u8)]
+    Dload {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+    },
+    #[br(magic = 0x26u8)]
+    Dload0,
+    #[br(magic = 0x27u8)]
+    Dload1,
+    #[br(magic = 0x28u8)]
+    Dload2,
+    #[br(magic = 0x29u8)]
+    Dload3,
+    #[br(magic = 0x6bu8)]
+    Dmul,
+    #[br(magic = 0x77u8)]
+    Dneg,
+    #[br(magic = 0x73u8)]
+    Drem,
+    #[br(magic = 0xafu8)]
+    Dreturn,
+    #[br(magic = 0x39u8)]
+    Dstore {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+    },
+    #[br(magic = 0x47u8)]
+    Dstore0,
+    #[br(magic = 0x48u8)]
+    Dstore1,
+    #[br(magic = 0x49u8)]
+    Dstore2,
+    #[br(magic = 0x4au8)]
+    Dstore3,
+    #[br(magic = 0x67u8)]
+    Dsub,
+    #[br(magic = 0x59u8)]
+    Dup,
+    #[br(magic = 0x5au8)]
+    DupX1,
+    #[br(magic = 0x5bu8)]
+    DupX2,
+    #[br(magic = 0x5cu8)]
+    Dup2,
+    #[br(magic = 0x5du8)]
+    Dup2X1,
+    #[br(magic = 0x5eu8)]
+    Dup2X2,
+    #[br(magic = 0x8du8)]
+    F2d,
+    #[br(magic = 0x8bu8)]
+    F2i,
+    #[br(magic = 0x8cu8)]
+    F2l,
+    #[br(magic = 0x62u8)]
+    Fadd,
+    #[br(magic = 0x30u8)]
+    Faload,
+    #[br(magic = 0x51u8)]
+    Fastore,
+    #[br(magic = 0x96u8)]
+    Fcmpg,
+    #[br(magic = 0x95u8)]
+    Fcmpl,
+    #[br(magic = 0xbu8)]
+    Fconst0,
+    #[br(magic = 0xcu8)]
+    Fconst1,
+    #[br(magic = 0xdu8)]
+    Fconst2,
+    #[br(magic = 0x6eu8)]
+    Fdiv,
+    #[br(magic = 0x17u8)]
+    Fload {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+    },
+    #[br(magic = 0x22u8)]
+    Fload0,
+    #[br(magic = 0x23u8)]
+    Fload1,
+    #[br(magic = 0x24u8)]
+    Fload2,
+    #[br(magic = 0x25u8)]
+    Fload3,
+    #[br(magic = 0x6au8)]
+    Fmul,
+    #[br(magic = 0x76u8)]
+    Fneg,
+    #[br(magic = 0x72u8)]
+    Frem,
+    #[br(magic = 0xaeu8)]
+    Freturn,
+    #[br(magic = 0x38u8)]
+    Fstore {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+    },
+    #[br(magic = 0x43u8)]
+    Fstore0,
+    #[br(magic = 0x44u8)]
+    Fstore1,
+    #[br(magic = 0x45u8)]
+    Fstore2,
+    #[br(magic = 0x46u8)]
+    Fstore3,
+    #[br(magic = 0x66u8)]
+    Fsub,
+    #[br(magic = 0xb4u8)]
+    Getfield {
+        #[br(args(cf,))]
+        field: FieldRef<'a>,
+    },
+    #[br(magic = 0xb2u8)]
+    Getstatic {
+        #[br(args(cf,))]
+        field: FieldRef<'a>,
+    },
+    #[br(magic = 0xa7u8)]
+    Goto {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0xc8u8)]
+    GotoW {
+        #[br(map = BranchOffset)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0x91u8)]
+    I2b,
+    #[br(magic = 0x92u8)]
+    I2c,
+    #[br(magic = 0x87u8)]
+    I2d,
+    #[br(magic = 0x86u8)]
+    I2f,
+    #[br(magic = 0x85u8)]
+    I2l,
+    #[br(magic = 0x93u8)]
+    I2s,
+    #[br(magic = 0x60u8)]
+    Iadd,
+    #[br(magic = 0x2eu8)]
+    Iaload,
+    #[br(magic = 0x7eu8)]
+    Iand,
+    #[br(magic = 0x4fu8)]
+    Iastore,
+    #[br(magic = 0x2u8)]
+    IconstM1,
+    #[br(magic = 0x3u8)]
+    Iconst0,
+    #[br(magic = 0x4u8)]
+    Iconst1,
+    #[br(magic = 0x5u8)]
+    Iconst2,
+    #[br(magic = 0x6u8)]
+    Iconst3,
+    #[br(magic = 0x7u8)]
+    Iconst4,
+    #[br(magic = 0x8u8)]
+    Iconst5,
+    #[br(magic = 0x6cu8)]
+    Idiv,
+    #[br(magic = 0xa5u8)]
+    IfAcmpeq {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0xa6u8)]
+    IfAcmpne {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0x9fu8)]
+    IfIcmpeq {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0xa0u8)]
+    IfIcmpne {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0xa1u8)]
+    IfIcmplt {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0xa2u8)]
+    IfIcmpge {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0xa3u8)]
+    IfIcmpgt {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0xa4u8)]
+    IfIcmple {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0x99u8)]
+    Ifeq {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0x9au8)]
+    Ifne {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0x9bu8)]
+    Iflt {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0x9cu8)]
+    Ifge {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0x9du8)]
+    Ifgt {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0x9eu8)]
+    Ifle {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0xc7u8)]
+    Ifnonnull {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0xc6u8)]
+    Ifnull {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0x84u8)]
+    Iinc {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+        #[br(map = |x: i8| x as i16)]
+        constant: i16,
+    },
+    #[br(magic = 0x15u8)]
+    Iload {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+    },
+    #[br(magic = 0x1au8)]
+    Iload0,
+    #[br(magic = 0x1bu8)]
+    Iload1,
+    #[br(magic = 0x1cu8)]
+    Iload2,
+    #[br(magic = 0x1du8)]
+    Iload3,
+    #[br(magic = 0x68u8)]
+    Imul,
+    #[br(magic = 0x74u8)]
+    Ineg,
+    #[br(magic = 0xc1u8)]
+    Instanceof {
+        #[br(try_map = |x: ClassIndex| x.get_as_string(cf))]
+        class: &'a str,
+    },
+    #[br(magic = 0xbau8)]
+    Invokedynamic {
+        #[br(args(cf,))]
+        index: DynamicInfo<'a>,
+        _never_used: u16, // IDK why, but the spec says this is followed by two 0x00 bytes.
+    },
+    #[br(magic = 0xb9u8)]
+    Invokeinterface {
+        #[br(args(cf,))]
+        index: InterfaceMethodRef<'a>,
+        count: u8,
+        _never_used: u8, // IDK why, but the spec says this is followed by one 0x00 byte.
+    },
+    #[br(magic = 0xb7u8)]
+    Invokespecial {
+        #[br(args(cf,))]
+        index: MaybeInterfaceMethodRef<'a>,
+    },
+    #[br(magic = 0xb8u8)]
+    Invokestatic {
+        #[br(args(cf,))]
+        index: MaybeInterfaceMethodRef<'a>,
+    },
+    #[br(magic = 0xb6u8)]
+    Invokevirtual {
+        #[br(args(cf,))]
+        index: MethodRef<'a>,
+    },
+    #[br(magic = 0x80u8)]
+    Ior,
+    #[br(magic = 0x70u8)]
+    Irem,
+    #[br(magic = 0xacu8)]
+    Ireturn,
+    #[br(magic = 0x78u8)]
+    Ishl,
+    #[br(magic = 0x7au8)]
+    Ishr,
+    #[br(magic = 0x36u8)]
+    Istore {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+    },
+    #[br(magic = 0x3bu8)]
+    Istore0,
+    #[br(magic = 0x3cu8)]
+    Istore1,
+    #[br(magic = 0x3du8)]
+    Istore2,
+    #[br(magic = 0x3eu8)]
+    Istore3,
+    #[br(magic = 0x64u8)]
+    Isub,
+    #[br(magic = 0x7cu8)]
+    Iushr,
+    #[br(magic = 0x82u8)]
+    Ixor,
+    #[br(magic = 0xa8u8)]
+    Jsr {
+        #[br(map = BranchOffset::short)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0xc9u8)]
+    JsrW {
+        #[br(map = BranchOffset)]
+        offset: BranchOffset,
+    },
+    #[br(magic = 0x8au8)]
+    L2d,
+    #[br(magic = 0x89u8)]
+    L2f,
+    #[br(magic = 0x88u8)]
+    L2i,
+    #[br(magic = 0x61u8)]
+    Ladd,
+    #[br(magic = 0x2fu8)]
+    Laload,
+    #[br(magic = 0x7fu8)]
+    Land,
+    #[br(magic = 0x50u8)]
+    Lastore,
+    #[br(magic = 0x94u8)]
+    Lcmp,
+    #[br(magic = 0x9u8)]
+    Lconst0,
+    #[br(magic = 0xau8)]
+    Lconst1,
+    #[br(magic = 0x12u8)]
+    Ldc {
+        index: u8,
+        #[br(try_calc = LoadableConstant::from_u16(index as u16, cf))]
+        constant: LoadableConstant<'a>,
+    },
+    #[br(magic = 0x13u8)]
+    LdcW {
+        index: u16,
+        #[br(try_calc = LoadableConstant::from_u16(index, cf))]
+        constant: LoadableConstant<'a>,
+    },
+    #[br(magic = 0x14u8)]
+    Ldc2W {
+        index: u16,
+        #[br(try_calc = LoadableConstant::from_u16(index, cf))]
+        constant: LoadableConstant<'a>,
+    },
+    #[br(magic = 0x6du8)]
+    Ldiv,
+    #[br(magic = 0x16u8)]
+    Lload {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+    },
+    #[br(magic = 0x1eu8)]
+    Lload0,
+    #[br(magic = 0x1fu8)]
+    Lload1,
+    #[br(magic = 0x20u8)]
+    Lload2,
+    #[br(magic = 0x21u8)]
+    Lload3,
+    #[br(magic = 0x69u8)]
+    Lmul,
+    #[br(magic = 0x75u8)]
+    Lneg,
+    #[br(magic = 0xabu8)]
+    Lookupswitch {
+        _padding: BytePad,
+        #[br(map = BranchOffset)]
+        default: BranchOffset,
+        #[br(temp)]
+        npairs: u32,
+        #[br(parse_with = read_table, args(npairs as i64, 8))]
+        pairs: Vec<(i32, BranchOffset)>,
+    },
+    #[br(magic = 0x81u8)]
+    Lor,
+    #[br(magic = 0x71u8)]
+    Lrem,
+    #[br(magic = 0xadu8)]
+    Lreturn,
+    #[br(magic = 0x79u8)]
+    Lshl,
+    #[br(magic = 0x7bu8)]
+    Lshr,
+    #[br(magic = 0x37u8)]
+    Lstore {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+    },
+    #[br(magic = 0x3fu8)]
+    Lstore0,
+    #[br(magic = 0x40u8)]
+    Lstore1,
+    #[br(magic = 0x41u8)]
+    Lstore2,
+    #[br(magic = 0x42u8)]
+    Lstore3,
+    #[br(magic = 0x65u8)]
+    Lsub,
+    #[br(magic = 0x7du8)]
+    Lushr,
+    #[br(magic = 0x83u8)]
+    Lxor,
+    #[br(magic = 0xc2u8)]
+    Monitorenter,
+    #[br(magic = 0xc3u8)]
+    Monitorexit,
+    #[br(magic = 0xc5u8)]
+    Multianewarray {
+        #[br(try_map = |x: ClassIndex| x.get_as_string(cf))]
+        class: &'a str,
+        dimensions: u8,
+    },
+    #[br(magic = 0xbbu8)]
+    New {
+        #[br(try_map = |x: ClassIndex| x.get_as_string(cf))]
+        class: &'a str,
+    },
+    #[br(magic = 0xbcu8)]
+    Newarray { atype: ArrayPrimitiveType },
+    #[br(magic = 0x0u8)]
+    Nop,
+    #[br(magic = 0x57u8)]
+    Pop,
+    #[br(magic = 0x58u8)]
+    Pop2,
+    #[br(magic = 0xb5u8)]
+    Putfield {
+        #[br(args(cf,))]
+        field: FieldRef<'a>,
+    },
+    #[br(magic = 0xb3u8)]
+    Putstatic {
+        #[br(args(cf,))]
+        field: FieldRef<'a>,
+    },
+    #[br(magic = 0xa9u8)]
+    Ret {
+        #[br(map = |x: u8| LocalIndex(x as u16))]
+        index: LocalIndex,
+    },
+    #[br(magic = 0xb1u8)]
+    Return,
+    #[br(magic = 0x35u8)]
+    Saload,
+    #[br(magic = 0x56u8)]
+    Sastore,
+    #[br(magic = 0x11u8)]
+    Sipush { value: i16 },
+    #[br(magic = 0x5fu8)]
+    Swap,
+    #[br(magic = 0xaau8)]
+    Tableswitch {
+        _padding: BytePad,
+        #[br(map = BranchOffset)]
+        default: BranchOffset,
+        low: i32,
+        high: i32,
+        #[br(parse_with = read_table, args(high as i64 - low as i64 + 1, 4))]
+        jump_offsets: Vec<BranchOffset>,
+    },
+    // The `wide` forms, with a two-byte index and, for `iinc`, a two-byte constant.
+    #[br(magic = 0xc419u16)]
+    WideAload { index: LocalIndex },
+    #[br(magic = 0xc43au16)]
+    WideAstore { index: LocalIndex },
+    #[br(magic = 0xc418u16)]
+    WideDload { index: LocalIndex },
+    #[br(magic = 0xc439u16)]
+    WideDstore { index: LocalIndex },
+    #[br(magic = 0xc417u16)]
+    WideFload { index: LocalIndex },
+    #[br(magic = 0xc438u16)]
+    WideFstore { index: LocalIndex },
+    #[br(magic = 0xc484u16)]
+    WideIinc { index: LocalIndex, constant: i16 },
+    #[br(magic = 0xc415u16)]
+    WideIload { index: LocalIndex },
+    #[br(magic = 0xc436u16)]
+    WideIstore { index: LocalIndex },
+    #[br(magic = 0xc416u16)]
+    WideLload { index: LocalIndex },
+    #[br(magic = 0xc437u16)]
+    WideLstore { index: LocalIndex },
+    #[br(magic = 0xc4a9u16)]
+    WideRet { index: LocalIndex },
+    /// A reserved or undefined opcode, such as `breakpoint`, taken to be one
+    /// byte long. Only produced with [`crate::Leniency::unknown_opcodes`].
+    #[br(pre_assert(false))]
+    Unknown { opcode: u8, pc: u32 },
+}
+
+impl Instruction<'_> {
+    /// The opcode byte. For the `wide` forms this is the opcode they widen,
+    /// so that e.g. `iload` gives 0x15 either way; see [`Self::is_wide`].
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Self::Nop => 0x00,
+            Self::AconstNull => 0x01,
+            Self::IconstM1 => 0x02,
+            Self::Iconst0 => 0x03,
+            Self::Iconst1 => 0x04,
+            Self::Iconst2 => 0x05,
+            Self::Iconst3 => 0x06,
+            Self::Iconst4 => 0x07,
+            Self::Iconst5 => 0x08,
+            Self::Lconst0 => 0x09,
+            Self::Lconst1 => 0x0a,
+            Self::Fconst0 => 0x0b,
+            Self::Fconst1 => 0x0c,
+            Self::Fconst2 => 0x0d,
+            Self::Dconst0 => 0x0e,
+            Self::Dconst1 => 0x0f,
+            Self::Bipush { .. } => 0x10,
+            Self::Sipush { .. } => 0x11,
+            Self::Ldc { .. } => 0x12,
+            Self::LdcW { .. } => 0x13,
+            Self::Ldc2W { .. } => 0x14,
+            Self::Iload { .. } | Self::WideIload { .. } => 0x15,
+            Self::Lload { .. } | Self::WideLload { .. } => 0x16,
+            Self::Fload { .. } | Self::WideFload { .. } => 0x17,
+            Self::Dload { .. } | Self::WideDload { .. } => 0x18,
+            Self::Aload { .. } | Self::WideAload { .. } => 0x19,
+            Self::Iload0 => 0x1a,
+            Self::Iload1 => 0x1b,
+            Self::Iload2 => 0x1c,
+            Self::Iload3 => 0x1d,
+            Self::Lload0 => 0x1e,
+            Self::Lload1 => 0x1f,
+            Self::Lload2 => 0x20,
+            Self::Lload3 => 0x21,
+            Self::Fload0 => 0x22,
+            Self::Fload1 => 0x23,
+            Self::Fload2 => 0x24,
+            Self::Fload3 => 0x25,
+            Self::Dload0 => 0x26,
+            Self::Dload1 => 0x27,
+            Self::Dload2 => 0x28,
+            Self::Dload3 => 0x29,
+            Self::Aload0 => 0x2a,
+            Self::Aload1 => 0x2b,
+            Self::Aload2 => 0x2c,
+            Self::Aload3 => 0x2d,
+            Self::Iaload => 0x2e,
+            Self::Laload => 0x2f,
+            Self::Faload => 0x30,
+            Self::Daload => 0x31,
+            Self::Aaload => 0x32,
+            Self::Baload => 0x33,
+            Self::Caload => 0x34,
+            Self::Saload => 0x35,
+            Self::Istore { .. } | Self::WideIstore { .. } => 0x36,
+            Self::Lstore { .. } | Self::WideLstore { .. } => 0x37,
+            Self::Fstore { .. } | Self::WideFstore { .. } => 0x38,
+            Self::Dstore { .. } | Self::WideDstore { .. } => 0x39,
+            Self::Astore { .. } | Self::WideAstore { .. } => 0x3a,
+            Self::Istore0 => 0x3b,
+            Self::Istore1 => 0x3c,
+            Self::Istore2 => 0x3d,
+            Self::Istore3 => 0x3e,
+            Self::Lstore0 => 0x3f,
+            Self::Lstore1 => 0x40,
+            Self::Lstore2 => 0x41,
+            Self::Lstore3 => 0x42,
+            Self::Fstore0 => 0x43,
+            Self::Fstore1 => 0x44,
+            Self::Fstore2 => 0x45,
+            Self::Fstore3 => 0x46,
+            Self::Dstore0 => 0x47,
+            Self::Dstore1 => 0x48,
+            Self::Dstore2 => 0x49,
+            Self::Dstore3 => 0x4a,
+            Self::Astore0 => 0x4b,
+            Self::Astore1 => 0x4c,
+            Self::Astore2 => 0x4d,
+            Self::Astore3 => 0x4e,
+            Self::Iastore => 0x4f,
+            Self::Lastore => 0x50,
+            Self::Fastore => 0x51,
+            Self::Dastore => 0x52,
+            Self::Aastore => 0x53,
+            Self::Bastore => 0x54,
+            Self::Castore => 0x55,
+            Self::Sastore => 0x56,
+            Self::Pop => 0x57,
+            Self::Pop2 => 0x58,
+            Self::Dup => 0x59,
+            Self::DupX1 => 0x5a,
+            Self::DupX2 => 0x5b,
+            Self::Dup2 => 0x5c,
+            Self::Dup2X1 => 0x5d,
+            Self::Dup2X2 => 0x5e,
+            Self::Swap => 0x5f,
+            Self::Iadd => 0x60,
+            Self::Ladd => 0x61,
+            Self::Fadd => 0x62,
+            Self::Dadd => 0x63,
+            Self::Isub => 0x64,
+            Self::Lsub => 0x65,
+            Self::Fsub => 0x66,
+            Self::Dsub => 0x67,
+            Self::Imul => 0x68,
+            Self::Lmul => 0x69,
+            Self::Fmul => 0x6a,
+            Self::Dmul => 0x6b,
+            Self::Idiv => 0x6c,
+            Self::Ldiv => 0x6d,
+            Self::Fdiv => 0x6e,
+            Self::Ddiv => 0x6f,
+            Self::Irem => 0x70,
+            Self::Lrem => 0x71,
+            Self::Frem => 0x72,
+            Self::Drem => 0x73,
+            Self::Ineg => 0x74,
+            Self::Lneg => 0x75,
+            Self::Fneg => 0x76,
+            Self::Dneg => 0x77,
+            Self::Ishl => 0x78,
+            Self::Lshl => 0x79,
+            Self::Ishr => 0x7a,
+            Self::Lshr => 0x7b,
+            Self::Iushr => 0x7c,
+            Self::Lushr => 0x7d,
+            Self::Iand => 0x7e,
+            Self::Land => 0x7f,
+            Self::Ior => 0x80,
+            Self::Lor => 0x81,
+            Self::Ixor => 0x82,
+            Self::Lxor => 0x83,
+            Self::Iinc { .. } | Self::WideIinc { .. } => 0x84,
+            Self::I2l => 0x85,
+            Self::I2f => 0x86,
+            Self::I2d => 0x87,
+            Self::L2i => 0x88,
+            Self::L2f => 0x89,
+            Self::L2d => 0x8a,
+            Self::F2i => 0x8b,
+            Self::F2l => 0x8c,
+            Self::F2d => 0x8d,
+            Self::D2i => 0x8e,
+            Self::D2l => 0x8f,
+            Self::D2f => 0x90,
+            Self::I2b => 0x91,
+            Self::I2c => 0x92,
+            Self::I2s => 0x93,
+            Self::Lcmp => 0x94,
+            Self::Fcmpl => 0x95,
+            Self::Fcmpg => 0x96,
+            Self::Dcmpl => 0x97,
+            Self::Dcmpg => 0x98,
+            Self::Ifeq { .. } => 0x99,
+            Self::Ifne { .. } => 0x9a,
+            Self::Iflt { .. } => 0x9b,
+            Self::Ifge { .. } => 0x9c,
+            Self::Ifgt { .. } => 0x9d,
+            Self::Ifle { .. } => 0x9e,
+            Self::IfIcmpeq { .. } => 0x9f,
+            Self::IfIcmpne { .. } => 0xa0,
+            Self::IfIcmplt { .. } => 0xa1,
+            Self::IfIcmpge { .. } => 0xa2,
+            Self::IfIcmpgt { .. } => 0xa3,
+            Self::IfIcmple { .. } => 0xa4,
+            Self::IfAcmpeq { .. } => 0xa5,
+            Self::IfAcmpne { .. } => 0xa6,
+            Self::Goto { .. } => 0xa7,
+            Self::Jsr { .. } => 0xa8,
+            Self::Ret { .. } | Self::WideRet { .. } => 0xa9,
+            Self::Tableswitch { .. } => 0xaa,
+            Self::Lookupswitch { .. } => 0xab,
+            Self::Ireturn => 0xac,
+            Self::Lreturn => 0xad,
+            Self::Freturn => 0xae,
+            Self::Dreturn => 0xaf,
+            Self::Areturn => 0xb0,
+            Self::Return => 0xb1,
+            Self::Getstatic { .. } => 0xb2,
+            Self::Putstatic { .. } => 0xb3,
+            Self::Getfield { .. } => 0xb4,
+            Self::Putfield { .. } => 0xb5,
+            Self::Invokevirtual { .. } => 0xb6,
+            Self::Invokespecial { .. } => 0xb7,
+            Self::Invokestatic { .. } => 0xb8,
+            Self::Invokeinterface { .. } => 0xb9,
+            Self::Invokedynamic { .. } => 0xba,
+            Self::New { .. } => 0xbb,
+            Self::Newarray { .. } => 0xbc,
+            Self::Anewarray { .. } => 0xbd,
+            Self::Arraylength => 0xbe,
+            Self::Athrow => 0xbf,
+            Self::Checkcast { .. } => 0xc0,
+            Self::Instanceof { .. } => 0xc1,
+            Self::Monitorenter => 0xc2,
+            Self::Monitorexit => 0xc3,
+            Self::Multianewarray { .. } => 0xc5,
+            Self::Ifnull { .. } => 0xc6,
+            Self::Ifnonnull { .. } => 0xc7,
+            Self::GotoW { .. } => 0xc8,
+            Self::JsrW { .. } => 0xc9,
+            Self::Unknown { opcode, .. } => *opcode,
+        }
+    }
+
+    /// The mnemonic from §6.5, e.g. `invokevirtual`. The `wide` forms share the
+    /// mnemonic of the opcode they widen, and reserved opcodes are named
+    /// `breakpoint`, `impdep1` and `impdep2`, or `unknown` if undefined.
+    pub fn mnemonic(&self) -> &'static str {
+        match OPCODE_TABLE.get(self.opcode() as usize) {
+            Some(x) => x.mnemonic,
+            None => match self.opcode() {
+                0xca => "breakpoint",
+                0xfe => "impdep1",
+                0xff => "impdep2",
+                _ => "unknown",
+            },
+        }
+    }
+
+    /// The operand format of the opcode in [`OPCODE_TABLE`], the one widened
+    /// for the `wide` forms, or `None` for reserved opcodes.
+    pub(crate) fn operand_format(&self) -> Option<OperandFormat> {
+        OPCODE_TABLE.get(self.opcode() as usize).map(|x| x.operands)
+    }
+
+    /// Whether this is one of the forms prefixed by `wide`.
+    pub fn is_wide(&self) -> bool {
+        matches!(
+            self,
+            Self::WideAload { .. }
+                | Self::WideAstore { .. }
+                | Self::WideDload { .. }
+                | Self::WideDstore { .. }
+                | Self::WideFload { .. }
+                | Self::WideFstore { .. }
+                | Self::WideIinc { .. }
+                | Self::WideIload { .. }
+                | Self::WideIstore { .. }
+                | Self::WideLload { .. }
+                | Self::WideLstore { .. }
+                | Self::WideRet { .. }
+        )
+    }
+
+    /// How many operand stack slots the instruction pops and pushes. `dup`
+    /// and its variants are counted as popping what they copy and pushing it
+    /// back with the copies. [`Self::Unknown`] has no known effect and counts
+    /// as neither.
+    pub fn stack_effect(&self) -> StackEffect {
+        let (pops, pushes) = match self {
+            Self::Nop | Self::Iinc { .. } | Self::WideIinc { .. } | Self::Goto { .. } | Self::GotoW { .. } => (0, 0),
+            Self::Ret { .. } | Self::WideRet { .. } | Self::Return | Self::Unknown { .. } => (0, 0),
+            Self::AconstNull
+            | Self::IconstM1
+            | Self::Iconst0
+            | Self::Iconst1
+            | Self::Iconst2
+            | Self::Iconst3
+            | Self::Iconst4
+            | Self::Iconst5
+            | Self::Fconst0
+            | Self::Fconst1
+            | Self::Fconst2
+            | Self::Bipush { .. }
+            | Self::Sipush { .. }
+            | Self::Jsr { .. }
+            | Self::JsrW { .. }
+            | Self::New { .. } => (0, 1),
+            Self::Lconst0 | Self::Lconst1 | Self::Dconst0 | Self::Dconst1 => (0, 2),
+            Self::Ldc { constant, .. } | Self::LdcW { constant, .. } | Self::Ldc2W { constant, .. } => {
+                (0, constant.slots())
+            }
+
+            Self::Iload { .. }
+            | Self::Fload { .. }
+            | Self::Aload { .. }
+            | Self::WideIload { .. }
+            | Self::WideFload { .. }
+            | Self::WideAload { .. }
+            | Self::Iload0
+            | Self::Iload1
+            | Self::Iload2
+            | Self::Iload3
+            | Self::Fload0
+            | Self::Fload1
+            | Self::Fload2
+            | Self::Fload3
+            | Self::Aload0
+            | Self::Aload1
+            | Self::Aload2
+            | Self::Aload3 => (0, 1),
+            Self::Lload { .. }
+            | Self::Dload { .. }
+            | Self::WideLload { .. }
+            | Self::WideDload { .. }
+            | Self::Lload0
+            | Self::Lload1
+            | Self::Lload2
+            | Self::Lload3
+            | Self::Dload0
+            | Self::Dload1
+            | Self::Dload2
+            | Self::Dload3 => (0, 2),
+            Self::Istore { .. }
+            | Self::Fstore { .. }
+            | Self::Astore { .. }
+            | Self::WideIstore { .. }
+            | Self::WideFstore { .. }
+            | Self::WideAstore { .. }
+            | Self::Istore0
+            | Self::Istore1
+            | Self::Istore2
+            | Self::Istore3
+            | Self::Fstore0
+            | Self::Fstore1
+            | Self::Fstore2
+            | Self::Fstore3
+            | Self::Astore0
+            | Self::Astore1
+            | Self::Astore2
+            | Self::Astore3 => (1, 0),
+            Self::Lstore { .. }
+            | Self::Dstore { .. }
+            | Self::WideLstore { .. }
+            | Self::WideDstore { .. }
+            | Self::Lstore0
+            | Self::Lstore1
+            | Self::Lstore2
+            | Self::Lstore3
+            | Self::Dstore0
+            | Self::Dstore1
+            | Self::Dstore2
+            | Self::Dstore3 => (2, 0),
+
+            Self::Iaload | Self::Faload | Self::Aaload | Self::Baload | Self::Caload | Self::Saload => (2, 1),
+            Self::Laload | Self::Daload => (2, 2),
+            Self::Iastore | Self::Fastore | Self::Aastore | Self::Bastore | Self::Castore | Self::Sastore => (3, 0),
+            Self::Lastore | Self::Dastore => (4, 0),
+
+            Self::Pop => (1, 0),
+            Self::Pop2 => (2, 0),
+            Self::Dup => (1, 2),
+            Self::DupX1 => (2, 3),
+            Self::DupX2 => (3, 4),
+            Self::Dup2 => (2, 4),
+            Self::Dup2X1 => (3, 5),
+            Self::Dup2X2 => (4, 6),
+            Self::Swap => (2, 2),
+
+            Self::Iadd
+            | Self::Isub
+            | Self::Imul
+            | Self::Idiv
+            | Self::Irem
+            | Self::Iand
+            | Self::Ior
+            | Self::Ixor
+            | Self::Ishl
+            | Self::Ishr
+            | Self::Iushr
+            | Self::Fadd
+            | Self::Fsub
+            | Self::Fmul
+            | Self::Fdiv
+            | Self::Frem => (2, 1),
+            Self::Ladd
+            | Self::Lsub
+            | Self::Lmul
+            | Self::Ldiv
+            | Self::Lrem
+            | Self::Land
+            | Self::Lor
+            | Self::Lxor
+            | Self::Dadd
+            | Self::Dsub
+            | Self::Dmul
+            | Self::Ddiv
+            | Self::Drem => (4, 2),
+            Self::Lshl | Self::Lshr | Self::Lushr => (3, 2),
+            Self::Ineg | Self::Fneg => (1, 1),
+            Self::Lneg | Self::Dneg => (2, 2),
+
+            Self::I2f | Self::F2i | Self::I2b | Self::I2c | Self::I2s => (1, 1),
+            Self::I2l | Self::I2d | Self::F2l | Self::F2d => (1, 2),
+            Self::L2i | Self::L2f | Self::D2i | Self::D2f => (2, 1),
+            Self::L2d | Self::D2l => (2, 2),
+            Self::Fcmpl | Self::Fcmpg => (2, 1),
+            Self::Lcmp | Self::Dcmpl | Self::Dcmpg => (4, 1),
+
+            Self::Ifeq { .. }
+            | Self::Ifne { .. }
+            | Self::Iflt { .. }
+            | Self::Ifge { .. }
+            | Self::Ifgt { .. }
+            | Self::Ifle { .. }
+            | Self::Ifnull { .. }
+            | Self::Ifnonnull { .. }
+            | Self::Tableswitch { .. }
+            | Self::Lookupswitch { .. } => (1, 0),
+            Self::IfIcmpeq { .. }
+            | Self::IfIcmpne { .. }
+            | Self::IfIcmplt { .. }
+            | Self::IfIcmpge { .. }
+            | Self::IfIcmpgt { .. }
+            | Self::IfIcmple { .. }
+            | Self::IfAcmpeq { .. }
+            | Self::IfAcmpne { .. } => (2, 0),
+
+            Self::Ireturn | Self::Freturn | Self::Areturn | Self::Athrow => (1, 0),
+            Self::Lreturn | Self::Dreturn => (2, 0),
+            Self::Monitorenter | Self::Monitorexit => (1, 0),
+
+            Self::Getstatic { field } => (0, field.descriptor.slots()),
+            Self::Putstatic { field } => (field.descriptor.slots(), 0),
+            Self::Getfield { field } => (1, field.descriptor.slots()),
+            Self::Putfield { field } => (1 + field.descriptor.slots(), 0),
+            Self::Invokevirtual { index } => (1 + index.descriptor.parameter_slots(), index.descriptor.return_slots()),
+            Self::Invokeinterface { index, .. } => {
+                (1 + index.descriptor.parameter_slots(), index.descriptor.return_slots())
+            }
+            Self::Invokespecial { index } => {
+                (1 + index.descriptor().parameter_slots(), index.descriptor().return_slots())
+            }
+            Self::Invokestatic { index } => (index.descriptor().parameter_slots(), index.descriptor().return_slots()),
+            Self::Invokedynamic { index, .. } => {
+                (index.descriptor.parameter_slots(), index.descriptor.return_slots())
+            }
+
+            Self::Newarray { .. }
+            | Self::Anewarray { .. }
+            | Self::Arraylength
+            | Self::Checkcast { .. }
+            | Self::Instanceof { .. } => (1, 1),
+            Self::Multianewarray { dimensions, .. } => (*dimensions as u16, 1),
+        };
+        StackEffect::new(pops, pushes)
+    }
+
+    /// The jump offsets of a branch, `jsr` or switch instruction, empty for
+    /// any other. For switches the default comes first, then the table in
+    /// file order.
+    pub fn branch_offsets(&self) -> Vec<BranchOffset> {
+        match self {
+            Self::Goto { offset }
+            | Self::GotoW { offset }
+            | Self::Jsr { offset }
+            | Self::JsrW { offset }
+            | Self::IfAcmpeq { offset }
+            | Self::IfAcmpne { offset }
+            | Self::IfIcmpeq { offset }
+            | Self::IfIcmpne { offset }
+            | Self::IfIcmplt { offset }
+            | Self::IfIcmpge { offset }
+            | Self::IfIcmpgt { offset }
+            | Self::IfIcmple { offset }
+            | Self::Ifeq { offset }
+            | Self::Ifne { offset }
+            | Self::Iflt { offset }
+            | Self::Ifge { offset }
+            | Self::Ifgt { offset }
+            | Self::Ifle { offset }
+            | Self::Ifnonnull { offset }
+            | Self::Ifnull { offset } => vec![*offset],
+            Self::Tableswitch {
+                default,
+                jump_offsets,
+                ..
+            } => std::iter::once(*default).chain(jump_offsets.iter().copied()).collect(),
+            Self::Lookupswitch { default, pairs, .. } => {
+                std::iter::once(*default).chain(pairs.iter().map(|(_, x)| *x)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether execution can continue with the next instruction. `jsr` counts
+    /// as falling through, since its subroutine returns there.
+    pub fn falls_through(&self) -> bool {
+        !matches!(
+            self,
+            Self::Goto { .. }
+                | Self::GotoW { .. }
+                | Self::Tableswitch { .. }
+                | Self::Lookupswitch { .. }
+                | Self::Ireturn
+                | Self::Lreturn
+                | Self::Freturn
+                | Self::Dreturn
+                | Self::Areturn
+                | Self::Return
+                | Self::Athrow
+                | Self::Ret { .. }
+                | Self::WideRet { .. }
+        )
+    }
+
+    /// The local variable slots the instruction reads or writes, `None` if it
+    /// does neither. `iinc` does both.
+    pub fn local_access(&self) -> Option<LocalAccess> {
+        let read = |index, slots| (index, slots, true, false);
+        let write = |index, slots| (index, slots, false, true);
+        let (index, slots, reads, writes) = match self {
+            Self::Iload { index } | Self::WideIload { index } => read(index.0, 1),
+            Self::Iload0 => read(0, 1),
+            Self::Iload1 => read(1, 1),
+            Self::Iload2 => read(2, 1),
+            Self::Iload3 => read(3, 1),
+            Self::Fload { index } | Self::WideFload { index } => read(index.0, 1),
+            Self::Fload0 => read(0, 1),
+            Self::Fload1 => read(1, 1),
+            Self::Fload2 => read(2, 1),
+            Self::Fload3 => read(3, 1),
+            Self::Aload { index } | Self::WideAload { index } => read(index.0, 1),
+            Self::Aload0 => read(0, 1),
+            Self::Aload1 => read(1, 1),
+            Self::Aload2 => read(2, 1),
+            Self::Aload3 => read(3, 1),
+            Self::Lload { index } | Self::WideLload { index } => read(index.0, 2),
+            Self::Lload0 => read(0, 2),
+            Self::Lload1 => read(1, 2),
+            Self::Lload2 => read(2, 2),
+            Self::Lload3 => read(3, 2),
+            Self::Dload { index } | Self::WideDload { index } => read(index.0, 2),
+            Self::Dload0 => read(0, 2),
+            Self::Dload1 => read(1, 2),
+            Self::Dload2 => read(2, 2),
+            Self::Dload3 => read(3, 2),
+            Self::Istore { index } | Self::WideIstore { index } => write(index.0, 1),
+            Self::Istore0 => write(0, 1),
+            Self::Istore1 => write(1, 1),
+            Self::Istore2 => write(2, 1),
+            Self::Istore3 => write(3, 1),
+            Self::Fstore { index } | Self::WideFstore { index } => write(index.0, 1),
+            Self::Fstore0 => write(0, 1),
+            Self::Fstore1 => write(1, 1),
+            Self::Fstore2 => write(2, 1),
+            Self::Fstore3 => write(3, 1),
+            Self::Astore { index } | Self::WideAstore { index } => write(index.0, 1),
+            Self::Astore0 => write(0, 1),
+            Self::Astore1 => write(1, 1),
+            Self::Astore2 => write(2, 1),
+            Self::Astore3 => write(3, 1),
+            Self::Lstore { index } | Self::WideLstore { index } => write(index.0, 2),
+            Self::Lstore0 => write(0, 2),
+            Self::Lstore1 => write(1, 2),
+            Self::Lstore2 => write(2, 2),
+            Self::Lstore3 => write(3, 2),
+            Self::Dstore { index } | Self::WideDstore { index } => write(index.0, 2),
+            Self::Dstore0 => write(0, 2),
+            Self::Dstore1 => write(1, 2),
+            Self::Dstore2 => write(2, 2),
+            Self::Dstore3 => write(3, 2),
+            Self::Ret { index } | Self::WideRet { index } => read(index.0, 1),
+            Self::Iinc { index, .. } | Self::WideIinc { index, .. } => (index.0, 1, true, true),
+            _ => return None,
+        };
+        Some(LocalAccess {
+            index,
+            slots,
+            reads,
+            writes,
+        })
+    }
+}
+
+/// The local variable slots an instruction touches, see
+/// [`Instruction::local_access`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalAccess {
+    pub index: u16,
+    /// Two for a `long` or `double`, one otherwise.
+    pub slots: u16,
+    pub reads: bool,
+    pub writes: bool,
+}
+
+/// Where a branch lands, see [`crate::attributes::Code::branch_targets`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BranchTarget {
+    /// The absolute offset in the code array.
+    pub pc: u32,
+    /// The position of the target in [`crate::attributes::Code::instructions`].
+    pub index: usize,
+}
+
+/// The operand stack slots an instruction pops and then pushes, counting two
+/// for each `long` and `double`, see [`Instruction::stack_effect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StackEffect {
+    pub pops: u16,
+    pub pushes: u16,
+}
+
+impl StackEffect {
+    fn new(pops: u16, pushes: u16) -> Self {
+        Self { pops, pushes }
+    }
+
+    /// The change in stack depth.
+    pub fn delta(&self) -> i32 {
+        self.pushes as i32 - self.pops as i32
+    }
+}
+
+impl LoadableConstant<'_> {
+    /// The slots the constant takes on the operand stack.
+    pub fn slots(&self) -> u16 {
+        match self {
+            Self::Long(_) | Self::Double(_) => 2,
+            Self::Dynamic(x) => x.descriptor.slots(),
+            _ => 1,
+        }
+    }
+}
+
+impl<'a> MaybeInterfaceMethodRef<'a> {
+    pub fn descriptor(&self) -> &MethodDescriptor<'a> {
+        match self {
+            Self::RegularMethod(x) => &x.descriptor,
+            Self::InterfaceMethod(x) => &x.descriptor,
+        }
+    }
+}
+
+/// An instruction with its position in the code array, see
+/// [`crate::attributes::Code::instructions_at`].
+#[derive(Debug)]
+pub struct InstructionAt<'a> {
+    /// The offset of the opcode from the start of the code array.
+    pub pc: u32,
+    /// The encoded size in bytes, including operands and switch padding.
+    pub length: u32,
+    pub instruction: Instruction<'a>,
+}
@@ -0,0 +1,1182 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    attributes::Code,
+    field::TypeDescriptor,
+    instruction::{BranchOffset, Instruction, MaybeInterfaceMethodRef},
+    method::Method,
+    raw::{ConstantPoolItem, MethodAccessFlags},
+    ClassFile, Error, Result,
+};
+
+const DEFAULT_FUEL: u64 = 1_000_000;
+const DEFAULT_MAX_DEPTH: usize = 64;
+const MAX_ARRAY_LENGTH: i32 = 1 << 20;
+
+/// A value on the operand stack or in a local variable slot.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Null,
+    Reference(Rc<RefCell<Object>>),
+}
+
+/// The heap objects the sandbox knows how to model.
+#[derive(Debug)]
+pub enum Object {
+    String(Vec<u16>),
+    StringBuilder(Vec<u16>),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn string(s: &str) -> Self {
+        Self::object(Object::String(s.encode_utf16().collect()))
+    }
+
+    pub fn as_string(&self) -> Option<String> {
+        match self {
+            Self::Reference(x) => match &*x.borrow() {
+                Object::String(s) => Some(String::from_utf16_lossy(s)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn object(object: Object) -> Self {
+        Self::Reference(Rc::new(RefCell::new(object)))
+    }
+
+    fn is_wide(&self) -> bool {
+        matches!(self, Self::Long(_) | Self::Double(_))
+    }
+}
+
+fn err<T>(message: impl Into<String>) -> Result<T> {
+    Err(Error::EvaluationError(message.into()))
+}
+
+fn check_string_length(len: usize) -> Result<()> {
+    if len > MAX_ARRAY_LENGTH as usize {
+        return err(format!("string of length {} exceeds the sandbox limit", len));
+    }
+    Ok(())
+}
+
+fn string_of(value: &Value) -> Result<Vec<u16>> {
+    match value {
+        Value::Reference(x) => match &*x.borrow() {
+            Object::String(s) | Object::StringBuilder(s) => Ok(s.clone()),
+            Object::Array(_) => err("expected a string, found an array"),
+        },
+        Value::Null => err("java/lang/NullPointerException"),
+        x => err(format!("expected a string, found {:?}", x)),
+    }
+}
+
+fn chars_of(value: &Value) -> Result<Vec<u16>> {
+    match value {
+        Value::Reference(x) => match &*x.borrow() {
+            Object::Array(values) => values
+                .iter()
+                .map(|x| match x {
+                    Value::Int(c) => Ok(*c as u16),
+                    x => err(format!("expected a char, found {:?}", x)),
+                })
+                .collect(),
+            _ => err("expected a char array"),
+        },
+        Value::Null => err("java/lang/NullPointerException"),
+        x => err(format!("expected a char array, found {:?}", x)),
+    }
+}
+
+fn char_array(chars: &[u16]) -> Value {
+    Value::object(Object::Array(
+        chars.iter().map(|x| Value::Int(*x as i32)).collect(),
+    ))
+}
+
+fn java_hash_code(chars: &[u16]) -> i32 {
+    chars
+        .iter()
+        .fold(0i32, |h, c| h.wrapping_mul(31).wrapping_add(*c as i32))
+}
+
+fn default_value(ty: &TypeDescriptor) -> Value {
+    match ty {
+        TypeDescriptor::Long => Value::Long(0),
+        TypeDescriptor::Float => Value::Float(0.0),
+        TypeDescriptor::Double => Value::Double(0.0),
+        TypeDescriptor::Byte
+        | TypeDescriptor::Char
+        | TypeDescriptor::Int
+        | TypeDescriptor::Short
+        | TypeDescriptor::Boolean => Value::Int(0),
+        _ => Value::Null,
+    }
+}
+
+struct Frame {
+    locals: Vec<Value>,
+    stack: Vec<Value>,
+}
+
+impl Frame {
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Result<Value> {
+        match self.stack.pop() {
+            Some(x) => Ok(x),
+            None => err("operand stack underflow"),
+        }
+    }
+
+    fn pop_args(&mut self, count: usize) -> Result<Vec<Value>> {
+        if self.stack.len() < count {
+            return err("operand stack underflow");
+        }
+        Ok(self.stack.split_off(self.stack.len() - count))
+    }
+
+    fn pop_int(&mut self) -> Result<i32> {
+        match self.pop()? {
+            Value::Int(x) => Ok(x),
+            x => err(format!("expected int, found {:?}", x)),
+        }
+    }
+
+    fn pop_long(&mut self) -> Result<i64> {
+        match self.pop()? {
+            Value::Long(x) => Ok(x),
+            x => err(format!("expected long, found {:?}", x)),
+        }
+    }
+
+    fn pop_float(&mut self) -> Result<f32> {
+        match self.pop()? {
+            Value::Float(x) => Ok(x),
+            x => err(format!("expected float, found {:?}", x)),
+        }
+    }
+
+    fn pop_double(&mut self) -> Result<f64> {
+        match self.pop()? {
+            Value::Double(x) => Ok(x),
+            x => err(format!("expected double, found {:?}", x)),
+        }
+    }
+
+    fn pop_reference(&mut self) -> Result<Option<Rc<RefCell<Object>>>> {
+        match self.pop()? {
+            Value::Reference(x) => Ok(Some(x)),
+            Value::Null => Ok(None),
+            x => err(format!("expected reference, found {:?}", x)),
+        }
+    }
+
+    fn pop_array(&mut self) -> Result<Rc<RefCell<Object>>> {
+        match self.pop_reference()? {
+            Some(x) => Ok(x),
+            None => err("java/lang/NullPointerException"),
+        }
+    }
+
+    fn load(&self, index: usize) -> Result<Value> {
+        match self.locals.get(index) {
+            Some(x) => Ok(x.clone()),
+            None => err(format!("invalid local variable index {}", index)),
+        }
+    }
+
+    fn store(&mut self, index: usize, value: Value) -> Result<()> {
+        let width = if value.is_wide() { 2 } else { 1 };
+        if index + width > self.locals.len() {
+            return err(format!("invalid local variable index {}", index));
+        }
+        self.locals[index] = value;
+        Ok(())
+    }
+
+    fn array_load(&mut self) -> Result<Value> {
+        let index = self.pop_int()?;
+        let array = self.pop_array()?;
+        let array = array.borrow();
+        match &*array {
+            Object::Array(values) => match usize::try_from(index).ok().and_then(|i| values.get(i)) {
+                Some(x) => Ok(x.clone()),
+                None => err("java/lang/ArrayIndexOutOfBoundsException"),
+            },
+            _ => err("expected an array"),
+        }
+    }
+
+    fn array_store(&mut self, map: fn(Value) -> Value) -> Result<()> {
+        let value = map(self.pop()?);
+        let index = self.pop_int()?;
+        let array = self.pop_array()?;
+        let mut array = array.borrow_mut();
+        match &mut *array {
+            Object::Array(values) => {
+                match usize::try_from(index).ok().and_then(|i| values.get_mut(i)) {
+                    Some(x) => *x = value,
+                    None => return err("java/lang/ArrayIndexOutOfBoundsException"),
+                }
+                Ok(())
+            }
+            _ => err("expected an array"),
+        }
+    }
+}
+
+fn truncate_int(value: Value, map: fn(i32) -> i32) -> Value {
+    match value {
+        Value::Int(x) => Value::Int(map(x)),
+        x => x,
+    }
+}
+
+/// A string recovered by evaluating a decryption helper on a constant argument.
+#[derive(Debug)]
+pub struct RecoveredString<'a> {
+    pub method: &'a str,
+    pub pc: u32,
+    pub ciphertext: String,
+    pub plaintext: String,
+}
+
+/// A sandboxed interpreter for pure static helper methods of a single class.
+///
+/// Only primitive arithmetic, arrays, strings and string builders are modelled.
+/// Calls are limited to static methods of the class itself plus a small set of
+/// side-effect free `java/lang` intrinsics; anything else aborts evaluation
+/// with [`Error::EvaluationError`].
+pub struct Interpreter<'a> {
+    class_file: &'a ClassFile,
+    fuel: u64,
+    max_depth: usize,
+    depth: usize,
+    statics: HashMap<String, Value>,
+    initialized: bool,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(class_file: &'a ClassFile) -> Self {
+        Self {
+            class_file,
+            fuel: DEFAULT_FUEL,
+            max_depth: DEFAULT_MAX_DEPTH,
+            depth: 0,
+            statics: HashMap::new(),
+            initialized: false,
+        }
+    }
+
+    /// Limits the total number of instructions executed by this interpreter.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn invoke_static(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        let method = self.find_method(name, descriptor)?;
+        self.invoke(&method, args)
+    }
+
+    /// Evaluates a `static String name(String)` helper on a constant input.
+    pub fn decrypt_string(&mut self, name: &str, ciphertext: &str) -> Result<String> {
+        let result = self.invoke_static(
+            name,
+            "(Ljava/lang/String;)Ljava/lang/String;",
+            vec![Value::string(ciphertext)],
+        )?;
+        match result.as_ref().and_then(Value::as_string) {
+            Some(x) => Ok(x),
+            None => err(format!("{} did not return a string", name)),
+        }
+    }
+
+    /// Finds every `ldc "..."; invokestatic helper(String)String` sequence in the
+    /// class and evaluates the helper on the loaded constant. Sites where that
+    /// fails, e.g. by running out of fuel, are left out.
+    pub fn recover_strings(&mut self, helper: &str) -> Result<Vec<RecoveredString<'a>>> {
+        let this_class = self.class_file.this_class()?;
+        let mut res = Vec::new();
+        for method in self.class_file.methods() {
+            let code = match method.code()? {
+                Some(x) => x,
+                None => continue,
+            };
+            let instructions = code.instructions_with_pcs()?;
+            for pair in instructions.windows(2) {
+                let index = match &pair[0].1 {
+                    Instruction::Ldc { index, .. } => *index as u16,
+                    Instruction::LdcW { index, .. } => *index,
+                    _ => continue,
+                };
+                let is_helper = match &pair[1].1 {
+                    Instruction::Invokestatic {
+                        index: MaybeInterfaceMethodRef::RegularMethod(x),
+                    } => {
+                        x.class == this_class
+                            && x.name == helper
+                            && x.descriptor.to_descriptor_string()
+                                == "(Ljava/lang/String;)Ljava/lang/String;"
+                    }
+                    _ => false,
+                };
+                if !is_helper {
+                    continue;
+                }
+                let ciphertext = match self.constant(index).map(|x| x.as_string()) {
+                    Ok(Some(x)) => x,
+                    _ => continue,
+                };
+                let plaintext = match self.decrypt_string(helper, &ciphertext) {
+                    Ok(x) => x,
+                    Err(_) => continue,
+                };
+                res.push(RecoveredString {
+                    method: method.identifier()?,
+                    pc: pair[1].0,
+                    ciphertext,
+                    plaintext,
+                });
+            }
+        }
+        Ok(res)
+    }
+
+    fn find_method(&self, name: &str, descriptor: &str) -> Result<Method<'a>> {
+        for method in self.class_file.methods() {
+            if method.identifier()? == name
+                && method
+                    .method_inner
+                    .descriptor_index
+                    .get_as_string(self.class_file)?
+                    == descriptor
+            {
+                return Ok(method);
+            }
+        }
+        err(format!("method {}{} not found", name, descriptor))
+    }
+
+    fn invoke(&mut self, method: &Method<'a>, args: Vec<Value>) -> Result<Option<Value>> {
+        if !method
+            .method_inner
+            .access_flags
+            .contains(MethodAccessFlags::STATIC)
+        {
+            return err(format!(
+                "{} is not static and cannot be evaluated",
+                method.identifier()?
+            ));
+        }
+        if self.depth >= self.max_depth {
+            return err("maximum call depth exceeded");
+        }
+        let code = match method.code()? {
+            Some(x) => x,
+            None => return err(format!("{} has no Code attribute", method.identifier()?)),
+        };
+        let mut frame = Frame {
+            locals: vec![Value::Null; code.max_locals as usize],
+            stack: Vec::new(),
+        };
+        let mut slot = 0;
+        for arg in args {
+            let width = if arg.is_wide() { 2 } else { 1 };
+            frame.store(slot, arg)?;
+            slot += width;
+        }
+        self.depth += 1;
+        let result = self.execute(&code, frame);
+        self.depth -= 1;
+        result
+    }
+
+    fn constant(&self, index: u16) -> Result<Value> {
+        let item = (index as usize)
+            .checked_sub(1)
+            .and_then(|x| self.class_file.constant_pool.0.get(x));
+        match item {
+            Some(ConstantPoolItem::Integer { value }) => Ok(Value::Int(*value)),
+            Some(ConstantPoolItem::Float { value }) => Ok(Value::Float(*value)),
+            Some(ConstantPoolItem::Long { value }) => Ok(Value::Long(*value)),
+            Some(ConstantPoolItem::Double { value }) => Ok(Value::Double(*value)),
+            Some(ConstantPoolItem::String { string_index }) => Ok(Value::object(Object::String(
+                string_index.get_as_utf16_impl(&self.class_file.constant_pool)?,
+            ))),
+            Some(x) => err(format!("unsupported loadable constant {:?}", x)),
+            None => err(format!("invalid constant pool index {}", index)),
+        }
+    }
+
+    fn ensure_initialized(&mut self) -> Result<()> {
+        if self.initialized {
+            return Ok(());
+        }
+        self.initialized = true;
+        if let Ok(clinit) = self.find_method("<clinit>", "()V") {
+            self.invoke(&clinit, Vec::new())?;
+        }
+        Ok(())
+    }
+
+    fn get_static(&mut self, name: &str, ty: &TypeDescriptor) -> Result<Value> {
+        self.ensure_initialized()?;
+        if let Some(x) = self.statics.get(name) {
+            return Ok(x.clone());
+        }
+        for field in self.class_file.fields() {
+            if field.identifier()? != name {
+                continue;
+            }
+            if let Some(constant) = field.constant_value()? {
+                return match ty {
+                    TypeDescriptor::Long => Ok(Value::Long(constant.long_value()?)),
+                    TypeDescriptor::Float => Ok(Value::Float(constant.float_value()?)),
+                    TypeDescriptor::Double => Ok(Value::Double(constant.double_value()?)),
+                    TypeDescriptor::String => Ok(Value::string(constant.string_value()?)),
+                    _ => Ok(Value::Int(constant.int_value()?)),
+                };
+            }
+        }
+        Ok(default_value(ty))
+    }
+
+    fn execute(&mut self, code: &Code<'a>, mut frame: Frame) -> Result<Option<Value>> {
+        let this_class = self.class_file.this_class()?;
+        let instructions = code.instructions_with_pcs()?;
+        let index: HashMap<u32, usize> = instructions
+            .iter()
+            .enumerate()
+            .map(|(i, (pc, _))| (*pc, i))
+            .collect();
+        let jump = |pc: u32, offset: BranchOffset| -> Result<usize> {
+            match offset.target(pc).and_then(|x| index.get(&x)) {
+                Some(x) => Ok(*x),
+                None => err(format!("invalid branch offset {} at pc {}", offset.0, pc)),
+            }
+        };
+
+        macro_rules! binary {
+            ($pop:ident, $ctor:ident, |$a:ident, $b:ident| $e:expr) => {{
+                let $b = frame.$pop()?;
+                let $a = frame.$pop()?;
+                frame.push(Value::$ctor($e));
+            }};
+        }
+        macro_rules! shift {
+            ($pop:ident, $ctor:ident, |$a:ident, $b:ident| $e:expr) => {{
+                let $b = frame.pop_int()?;
+                let $a = frame.$pop()?;
+                frame.push(Value::$ctor($e));
+            }};
+        }
+        macro_rules! unary {
+            ($pop:ident, $ctor:ident, |$a:ident| $e:expr) => {{
+                let $a = frame.$pop()?;
+                frame.push(Value::$ctor($e));
+            }};
+        }
+
+        let mut ip = 0;
+        loop {
+            let (pc, instruction) = match instructions.get(ip) {
+                Some((pc, x)) => (*pc, x),
+                None => return err("execution fell off the end of the code"),
+            };
+            if self.fuel == 0 {
+                return err("instruction budget exhausted");
+            }
+            self.fuel -= 1;
+            ip += 1;
+
+            let mut branch = |taken: bool, offset: BranchOffset| -> Result<()> {
+                if taken {
+                    ip = jump(pc, offset)?;
+                }
+                Ok(())
+            };
+
+            match instruction {
+                Instruction::Nop => {}
+                Instruction::AconstNull => frame.push(Value::Null),
+                Instruction::IconstM1 => frame.push(Value::Int(-1)),
+                Instruction::Iconst0 => frame.push(Value::Int(0)),
+                Instruction::Iconst1 => frame.push(Value::Int(1)),
+                Instruction::Iconst2 => frame.push(Value::Int(2)),
+                Instruction::Iconst3 => frame.push(Value::Int(3)),
+                Instruction::Iconst4 => frame.push(Value::Int(4)),
+                Instruction::Iconst5 => frame.push(Value::Int(5)),
+                Instruction::Lconst0 => frame.push(Value::Long(0)),
+                Instruction::Lconst1 => frame.push(Value::Long(1)),
+                Instruction::Fconst0 => frame.push(Value::Float(0.0)),
+                Instruction::Fconst1 => frame.push(Value::Float(1.0)),
+                Instruction::Fconst2 => frame.push(Value::Float(2.0)),
+                Instruction::Dconst0 => frame.push(Value::Double(0.0)),
+                Instruction::Dconst1 => frame.push(Value::Double(1.0)),
+                Instruction::Bipush { value } => frame.push(Value::Int(*value as i32)),
+                Instruction::Sipush { value } => frame.push(Value::Int(*value as i32)),
+                Instruction::Ldc { index, .. } => frame.push(self.constant(*index as u16)?),
+                Instruction::LdcW { index, .. } | Instruction::Ldc2W { index, .. } => {
+                    frame.push(self.constant(*index)?)
+                }
+
+                Instruction::Iload { index }
+                | Instruction::Lload { index }
+                | Instruction::Fload { index }
+                | Instruction::Dload { index }
+                | Instruction::Aload { index }
+                | Instruction::WideIload { index }
+                | Instruction::WideLload { index }
+                | Instruction::WideFload { index }
+                | Instruction::WideDload { index }
+                | Instruction::WideAload { index } => frame.push(frame.load(index.0 as usize)?),
+                Instruction::Iload0
+                | Instruction::Lload0
+                | Instruction::Fload0
+                | Instruction::Dload0
+                | Instruction::Aload0 => frame.push(frame.load(0)?),
+                Instruction::Iload1
+                | Instruction::Lload1
+                | Instruction::Fload1
+                | Instruction::Dload1
+                | Instruction::Aload1 => frame.push(frame.load(1)?),
+                Instruction::Iload2
+                | Instruction::Lload2
+                | Instruction::Fload2
+                | Instruction::Dload2
+                | Instruction::Aload2 => frame.push(frame.load(2)?),
+                Instruction::Iload3
+                | Instruction::Lload3
+                | Instruction::Fload3
+                | Instruction::Dload3
+                | Instruction::Aload3 => frame.push(frame.load(3)?),
+
+                Instruction::Istore { index }
+                | Instruction::Lstore { index }
+                | Instruction::Fstore { index }
+                | Instruction::Dstore { index }
+                | Instruction::Astore { index }
+                | Instruction::WideIstore { index }
+                | Instruction::WideLstore { index }
+                | Instruction::WideFstore { index }
+                | Instruction::WideDstore { index }
+                | Instruction::WideAstore { index } => {
+                    let value = frame.pop()?;
+                    frame.store(index.0 as usize, value)?
+                }
+                Instruction::Istore0
+                | Instruction::Lstore0
+                | Instruction::Fstore0
+                | Instruction::Dstore0
+                | Instruction::Astore0 => {
+                    let value = frame.pop()?;
+                    frame.store(0, value)?
+                }
+                Instruction::Istore1
+                | Instruction::Lstore1
+                | Instruction::Fstore1
+                | Instruction::Dstore1
+                | Instruction::Astore1 => {
+                    let value = frame.pop()?;
+                    frame.store(1, value)?
+                }
+                Instruction::Istore2
+                | Instruction::Lstore2
+                | Instruction::Fstore2
+                | Instruction::Dstore2
+                | Instruction::Astore2 => {
+                    let value = frame.pop()?;
+                    frame.store(2, value)?
+                }
+                Instruction::Istore3
+                | Instruction::Lstore3
+                | Instruction::Fstore3
+                | Instruction::Dstore3
+                | Instruction::Astore3 => {
+                    let value = frame.pop()?;
+                    frame.store(3, value)?
+                }
+                Instruction::Iinc { index, constant } | Instruction::WideIinc { index, constant } => {
+                    let index = index.0 as usize;
+                    match frame.load(index)? {
+                        Value::Int(x) => {
+                            frame.store(index, Value::Int(x.wrapping_add(*constant as i32)))?
+                        }
+                        x => return err(format!("iinc on non-int local {:?}", x)),
+                    }
+                }
+
+                Instruction::Iaload
+                | Instruction::Laload
+                | Instruction::Faload
+                | Instruction::Daload
+                | Instruction::Aaload
+                | Instruction::Baload
+                | Instruction::Caload
+                | Instruction::Saload => {
+                    let value = frame.array_load()?;
+                    frame.push(value)
+                }
+                Instruction::Iastore
+                | Instruction::Lastore
+                | Instruction::Fastore
+                | Instruction::Dastore
+                | Instruction::Aastore => frame.array_store(|x| x)?,
+                Instruction::Bastore => {
+                    frame.array_store(|x| truncate_int(x, |x| x as i8 as i32))?
+                }
+                Instruction::Castore => {
+                    frame.array_store(|x| truncate_int(x, |x| x as u16 as i32))?
+                }
+                Instruction::Sastore => {
+                    frame.array_store(|x| truncate_int(x, |x| x as i16 as i32))?
+                }
+                Instruction::Arraylength => {
+                    let array = frame.pop_array()?;
+                    let length = match &*array.borrow() {
+                        Object::Array(values) => values.len() as i32,
+                        _ => return err("arraylength on a non-array"),
+                    };
+                    frame.push(Value::Int(length))
+                }
+                Instruction::Newarray { atype } => {
+                    let count = frame.pop_int()?;
+                    let element = default_value(&atype.descriptor());
+                    frame.push(self.new_array(count, element)?)
+                }
+                Instruction::Anewarray { .. } => {
+                    let count = frame.pop_int()?;
+                    frame.push(self.new_array(count, Value::Null)?)
+                }
+
+                Instruction::Pop => {
+                    frame.pop()?;
+                }
+                Instruction::Pop2 => {
+                    if !frame.pop()?.is_wide() {
+                        frame.pop()?;
+                    }
+                }
+                Instruction::Dup => {
+                    let v1 = frame.pop()?;
+                    frame.push(v1.clone());
+                    frame.push(v1);
+                }
+                Instruction::DupX1 => {
+                    let v1 = frame.pop()?;
+                    let v2 = frame.pop()?;
+                    frame.push(v1.clone());
+                    frame.push(v2);
+                    frame.push(v1);
+                }
+                Instruction::DupX2 => {
+                    let v1 = frame.pop()?;
+                    let v2 = frame.pop()?;
+                    if v2.is_wide() {
+                        frame.push(v1.clone());
+                        frame.push(v2);
+                        frame.push(v1);
+                    } else {
+                        let v3 = frame.pop()?;
+                        frame.push(v1.clone());
+                        frame.push(v3);
+                        frame.push(v2);
+                        frame.push(v1);
+                    }
+                }
+                Instruction::Dup2 => {
+                    let v1 = frame.pop()?;
+                    if v1.is_wide() {
+                        frame.push(v1.clone());
+                        frame.push(v1);
+                    } else {
+                        let v2 = frame.pop()?;
+                        frame.push(v2.clone());
+                        frame.push(v1.clone());
+                        frame.push(v2);
+                        frame.push(v1);
+                    }
+                }
+                Instruction::Dup2X1 => {
+                    let v1 = frame.pop()?;
+                    if v1.is_wide() {
+                        let v2 = frame.pop()?;
+                        frame.push(v1.clone());
+                        frame.push(v2);
+                        frame.push(v1);
+                    } else {
+                        let v2 = frame.pop()?;
+                        let v3 = frame.pop()?;
+                        frame.push(v2.clone());
+                        frame.push(v1.clone());
+                        frame.push(v3);
+                        frame.push(v2);
+                        frame.push(v1);
+                    }
+                }
+                Instruction::Dup2X2 => {
+                    let v1 = frame.pop()?;
+                    let v2 = frame.pop()?;
+                    match (v1.is_wide(), v2.is_wide()) {
+                        (true, true) => {
+                            frame.push(v1.clone());
+                            frame.push(v2);
+                            frame.push(v1);
+                        }
+                        (true, false) => {
+                            let v3 = frame.pop()?;
+                            frame.push(v1.clone());
+                            frame.push(v3);
+                            frame.push(v2);
+                            frame.push(v1);
+                        }
+                        _ => {
+                            let v3 = frame.pop()?;
+                            if v3.is_wide() {
+                                frame.push(v2.clone());
+                                frame.push(v1.clone());
+                                frame.push(v3);
+                                frame.push(v2);
+                                frame.push(v1);
+                            } else {
+                                let v4 = frame.pop()?;
+                                frame.push(v2.clone());
+                                frame.push(v1.clone());
+                                frame.push(v4);
+                                frame.push(v3);
+                                frame.push(v2);
+                                frame.push(v1);
+                            }
+                        }
+                    }
+                }
+                Instruction::Swap => {
+                    let v1 = frame.pop()?;
+                    let v2 = frame.pop()?;
+                    frame.push(v1);
+                    frame.push(v2);
+                }
+
+                Instruction::Iadd => binary!(pop_int, Int, |a, b| a.wrapping_add(b)),
+                Instruction::Isub => binary!(pop_int, Int, |a, b| a.wrapping_sub(b)),
+                Instruction::Imul => binary!(pop_int, Int, |a, b| a.wrapping_mul(b)),
+                Instruction::Idiv | Instruction::Irem => {
+                    let b = frame.pop_int()?;
+                    let a = frame.pop_int()?;
+                    if b == 0 {
+                        return err("java/lang/ArithmeticException");
+                    }
+                    frame.push(Value::Int(match instruction {
+                        Instruction::Idiv => a.wrapping_div(b),
+                        _ => a.wrapping_rem(b),
+                    }))
+                }
+                Instruction::Ineg => unary!(pop_int, Int, |a| a.wrapping_neg()),
+                Instruction::Iand => binary!(pop_int, Int, |a, b| a & b),
+                Instruction::Ior => binary!(pop_int, Int, |a, b| a | b),
+                Instruction::Ixor => binary!(pop_int, Int, |a, b| a ^ b),
+                Instruction::Ishl => shift!(pop_int, Int, |a, b| a.wrapping_shl(b as u32)),
+                Instruction::Ishr => shift!(pop_int, Int, |a, b| a.wrapping_shr(b as u32)),
+                Instruction::Iushr => {
+                    shift!(pop_int, Int, |a, b| (a as u32).wrapping_shr(b as u32) as i32)
+                }
+                Instruction::Ladd => binary!(pop_long, Long, |a, b| a.wrapping_add(b)),
+                Instruction::Lsub => binary!(pop_long, Long, |a, b| a.wrapping_sub(b)),
+                Instruction::Lmul => binary!(pop_long, Long, |a, b| a.wrapping_mul(b)),
+                Instruction::Ldiv | Instruction::Lrem => {
+                    let b = frame.pop_long()?;
+                    let a = frame.pop_long()?;
+                    if b == 0 {
+                        return err("java/lang/ArithmeticException");
+                    }
+                    frame.push(Value::Long(match instruction {
+                        Instruction::Ldiv => a.wrapping_div(b),
+                        _ => a.wrapping_rem(b),
+                    }))
+                }
+                Instruction::Lneg => unary!(pop_long, Long, |a| a.wrapping_neg()),
+                Instruction::Land => binary!(pop_long, Long, |a, b| a & b),
+                Instruction::Lor => binary!(pop_long, Long, |a, b| a | b),
+                Instruction::Lxor => binary!(pop_long, Long, |a, b| a ^ b),
+                Instruction::Lshl => shift!(pop_long, Long, |a, b| a.wrapping_shl(b as u32)),
+                Instruction::Lshr => shift!(pop_long, Long, |a, b| a.wrapping_shr(b as u32)),
+                Instruction::Lushr => {
+                    shift!(pop_long, Long, |a, b| (a as u64).wrapping_shr(b as u32) as i64)
+                }
+                Instruction::Fadd => binary!(pop_float, Float, |a, b| a + b),
+                Instruction::Fsub => binary!(pop_float, Float, |a, b| a - b),
+                Instruction::Fmul => binary!(pop_float, Float, |a, b| a * b),
+                Instruction::Fdiv => binary!(pop_float, Float, |a, b| a / b),
+                Instruction::Frem => binary!(pop_float, Float, |a, b| a % b),
+                Instruction::Fneg => unary!(pop_float, Float, |a| -a),
+                Instruction::Dadd => binary!(pop_double, Double, |a, b| a + b),
+                Instruction::Dsub => binary!(pop_double, Double, |a, b| a - b),
+                Instruction::Dmul => binary!(pop_double, Double, |a, b| a * b),
+                Instruction::Ddiv => binary!(pop_double, Double, |a, b| a / b),
+                Instruction::Drem => binary!(pop_double, Double, |a, b| a % b),
+                Instruction::Dneg => unary!(pop_double, Double, |a| -a),
+
+                Instruction::I2l => unary!(pop_int, Long, |a| a as i64),
+                Instruction::I2f => unary!(pop_int, Float, |a| a as f32),
+                Instruction::I2d => unary!(pop_int, Double, |a| a as f64),
+                Instruction::I2b => unary!(pop_int, Int, |a| a as i8 as i32),
+                Instruction::I2c => unary!(pop_int, Int, |a| a as u16 as i32),
+                Instruction::I2s => unary!(pop_int, Int, |a| a as i16 as i32),
+                Instruction::L2i => unary!(pop_long, Int, |a| a as i32),
+                Instruction::L2f => unary!(pop_long, Float, |a| a as f32),
+                Instruction::L2d => unary!(pop_long, Double, |a| a as f64),
+                Instruction::F2i => unary!(pop_float, Int, |a| a as i32),
+                Instruction::F2l => unary!(pop_float, Long, |a| a as i64),
+                Instruction::F2d => unary!(pop_float, Double, |a| a as f64),
+                Instruction::D2i => unary!(pop_double, Int, |a| a as i32),
+                Instruction::D2l => unary!(pop_double, Long, |a| a as i64),
+                Instruction::D2f => unary!(pop_double, Float, |a| a as f32),
+
+                Instruction::Lcmp => binary!(pop_long, Int, |a, b| a.cmp(&b) as i32),
+                Instruction::Fcmpl | Instruction::Fcmpg => {
+                    let nan = if matches!(instruction, Instruction::Fcmpg) { 1 } else { -1 };
+                    binary!(pop_float, Int, |a, b| a.partial_cmp(&b).map_or(nan, |x| x as i32))
+                }
+                Instruction::Dcmpl | Instruction::Dcmpg => {
+                    let nan = if matches!(instruction, Instruction::Dcmpg) { 1 } else { -1 };
+                    binary!(pop_double, Int, |a, b| a.partial_cmp(&b).map_or(nan, |x| x as i32))
+                }
+
+                Instruction::Ifeq { offset } => branch(frame.pop_int()? == 0, *offset)?,
+                Instruction::Ifne { offset } => branch(frame.pop_int()? != 0, *offset)?,
+                Instruction::Iflt { offset } => branch(frame.pop_int()? < 0, *offset)?,
+                Instruction::Ifge { offset } => branch(frame.pop_int()? >= 0, *offset)?,
+                Instruction::Ifgt { offset } => branch(frame.pop_int()? > 0, *offset)?,
+                Instruction::Ifle { offset } => branch(frame.pop_int()? <= 0, *offset)?,
+                Instruction::IfIcmpeq { offset }
+                | Instruction::IfIcmpne { offset }
+                | Instruction::IfIcmplt { offset }
+                | Instruction::IfIcmpge { offset }
+                | Instruction::IfIcmpgt { offset }
+                | Instruction::IfIcmple { offset } => {
+                    let b = frame.pop_int()?;
+                    let a = frame.pop_int()?;
+                    let taken = match instruction {
+                        Instruction::IfIcmpeq { .. } => a == b,
+                        Instruction::IfIcmpne { .. } => a != b,
+                        Instruction::IfIcmplt { .. } => a < b,
+                        Instruction::IfIcmpge { .. } => a >= b,
+                        Instruction::IfIcmpgt { .. } => a > b,
+                        _ => a <= b,
+                    };
+                    branch(taken, *offset)?
+                }
+                Instruction::IfAcmpeq { offset } | Instruction::IfAcmpne { offset } => {
+                    let b = frame.pop_reference()?;
+                    let a = frame.pop_reference()?;
+                    let same = match (a, b) {
+                        (Some(a), Some(b)) => Rc::ptr_eq(&a, &b),
+                        (None, None) => true,
+                        _ => false,
+                    };
+                    branch(
+                        same == matches!(instruction, Instruction::IfAcmpeq { .. }),
+                        *offset,
+                    )?
+                }
+                Instruction::Ifnull { offset } => {
+                    branch(frame.pop_reference()?.is_none(), *offset)?
+                }
+                Instruction::Ifnonnull { offset } => {
+                    branch(frame.pop_reference()?.is_some(), *offset)?
+                }
+                Instruction::Goto { offset } => branch(true, *offset)?,
+                Instruction::GotoW { offset } => branch(true, *offset)?,
+                Instruction::Tableswitch {
+                    default,
+                    low,
+                    high,
+                    jump_offsets,
+                    ..
+                } => {
+                    let key = frame.pop_int()?;
+                    let offset = if key < *low || key > *high {
+                        *default
+                    } else {
+                        jump_offsets
+                            .get((key - low) as usize)
+                            .copied()
+                            .unwrap_or(*default)
+                    };
+                    branch(true, offset)?
+                }
+                Instruction::Lookupswitch { default, pairs, .. } => {
+                    let key = frame.pop_int()?;
+                    let offset = pairs
+                        .iter()
+                        .find(|(k, _)| *k == key)
+                        .map_or(*default, |(_, x)| *x);
+                    branch(true, offset)?
+                }
+
+                Instruction::Ireturn
+                | Instruction::Lreturn
+                | Instruction::Freturn
+                | Instruction::Dreturn
+                | Instruction::Areturn => return Ok(Some(frame.pop()?)),
+                Instruction::Return => return Ok(None),
+
+                Instruction::Getstatic { field } => {
+                    if field.class != this_class {
+                        return err(format!("access to foreign static field {}.{}", field.class, field.name));
+                    }
+                    let value = self.get_static(field.name, &field.descriptor)?;
+                    frame.push(value)
+                }
+                Instruction::Putstatic { field } => {
+                    if field.class != this_class {
+                        return err(format!("access to foreign static field {}.{}", field.class, field.name));
+                    }
+                    self.ensure_initialized()?;
+                    let value = frame.pop()?;
+                    self.statics.insert(field.name.to_string(), value);
+                }
+
+                Instruction::Invokestatic { index } => {
+                    let (class, name, descriptor) = match index {
+                        MaybeInterfaceMethodRef::RegularMethod(x) => (x.class, x.name, &x.descriptor),
+                        MaybeInterfaceMethodRef::InterfaceMethod(x) => (x.class, x.name, &x.descriptor),
+                    };
+                    let args = frame.pop_args(descriptor.parameter_types().len())?;
+                    let descriptor = descriptor.to_descriptor_string();
+                    let result = if class == this_class {
+                        let method = self.find_method(name, &descriptor)?;
+                        self.invoke(&method, args)?
+                    } else {
+                        self.intrinsic(class, name, &descriptor, None, args)?
+                    };
+                    if let Some(x) = result {
+                        frame.push(x);
+                    }
+                }
+                Instruction::Invokevirtual { index } => {
+                    let args = frame.pop_args(index.descriptor.parameter_types().len())?;
+                    let receiver = frame.pop()?;
+                    let descriptor = index.descriptor.to_descriptor_string();
+                    if let Some(x) =
+                        self.intrinsic(index.class, index.name, &descriptor, Some(receiver), args)?
+                    {
+                        frame.push(x);
+                    }
+                }
+                Instruction::Invokespecial { index } => {
+                    let (class, name, descriptor) = match index {
+                        MaybeInterfaceMethodRef::RegularMethod(x) => (x.class, x.name, &x.descriptor),
+                        MaybeInterfaceMethodRef::InterfaceMethod(x) => (x.class, x.name, &x.descriptor),
+                    };
+                    let args = frame.pop_args(descriptor.parameter_types().len())?;
+                    let receiver = frame.pop()?;
+                    let descriptor = descriptor.to_descriptor_string();
+                    if let Some(x) = self.intrinsic(class, name, &descriptor, Some(receiver), args)? {
+                        frame.push(x);
+                    }
+                }
+                Instruction::New { class } => match *class {
+                    "java/lang/String" => frame.push(Value::object(Object::String(Vec::new()))),
+                    "java/lang/StringBuilder" | "java/lang/StringBuffer" => {
+                        frame.push(Value::object(Object::StringBuilder(Vec::new())))
+                    }
+                    x => return err(format!("cannot instantiate {} in the sandbox", x)),
+                },
+                Instruction::Checkcast { .. } => {}
+                Instruction::Monitorenter | Instruction::Monitorexit => {
+                    frame.pop_reference()?;
+                }
+                Instruction::Athrow => return err(format!("exception thrown at pc {}", pc)),
+                x => return err(format!("unsupported instruction {:?} at pc {}", x, pc)),
+            }
+        }
+    }
+
+    fn new_array(&self, count: i32, element: Value) -> Result<Value> {
+        if count < 0 {
+            return err("java/lang/NegativeArraySizeException");
+        }
+        if count > MAX_ARRAY_LENGTH {
+            return err(format!("array of length {} exceeds the sandbox limit", count));
+        }
+        Ok(Value::object(Object::Array(vec![element; count as usize])))
+    }
+
+    fn intrinsic(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+        receiver: Option<Value>,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        let int_arg = |i: usize| match args.get(i) {
+            Some(Value::Int(x)) => Ok(*x),
+            x => err(format!("expected int argument, found {:?}", x)),
+        };
+        let this = || match &receiver {
+            Some(x) => string_of(x),
+            None => err("missing receiver"),
+        };
+        let set_this = |chars: Vec<u16>| -> Result<()> {
+            match &receiver {
+                Some(Value::Reference(x)) => {
+                    match &mut *x.borrow_mut() {
+                        Object::String(s) | Object::StringBuilder(s) => *s = chars,
+                        Object::Array(_) => return err("expected a string receiver"),
+                    }
+                    Ok(())
+                }
+                _ => err("java/lang/NullPointerException"),
+            }
+        };
+        let grow_this = |extra: &[u16]| -> Result<()> {
+            match &receiver {
+                Some(Value::Reference(x)) => match &mut *x.borrow_mut() {
+                    Object::String(s) | Object::StringBuilder(s) => {
+                        check_string_length(s.len() + extra.len())?;
+                        s.extend_from_slice(extra);
+                        Ok(())
+                    }
+                    Object::Array(_) => err("expected a string receiver"),
+                },
+                _ => err("java/lang/NullPointerException"),
+            }
+        };
+        let char_at = |chars: &[u16], index: i32| match usize::try_from(index)
+            .ok()
+            .and_then(|i| chars.get(i))
+        {
+            Some(x) => Ok(Value::Int(*x as i32)),
+            None => err("java/lang/StringIndexOutOfBoundsException"),
+        };
+        let substring = |chars: &[u16], begin: i32, end: i32| {
+            if begin < 0 || end < begin || end as usize > chars.len() {
+                return err("java/lang/StringIndexOutOfBoundsException");
+            }
+            Ok(Some(Value::object(Object::String(
+                chars[begin as usize..end as usize].to_vec(),
+            ))))
+        };
+        let is_builder = matches!(class, "java/lang/StringBuilder" | "java/lang/StringBuffer");
+
+        match (class, name, descriptor) {
+            ("java/lang/Object", "<init>", "()V") => Ok(None),
+            ("java/lang/String", "<init>", "()V") => set_this(Vec::new()).map(|_| None),
+            ("java/lang/String", "<init>", "([C)V") => set_this(chars_of(&args[0])?).map(|_| None),
+            ("java/lang/String", "<init>", "([CII)V") => {
+                let chars = chars_of(&args[0])?;
+                let (offset, count) = (int_arg(1)?, int_arg(2)?);
+                if offset < 0 || count < 0 || (offset as usize + count as usize) > chars.len() {
+                    return err("java/lang/StringIndexOutOfBoundsException");
+                }
+                set_this(chars[offset as usize..(offset + count) as usize].to_vec()).map(|_| None)
+            }
+            ("java/lang/String", "<init>", "(Ljava/lang/String;)V") => {
+                set_this(string_of(&args[0])?).map(|_| None)
+            }
+            ("java/lang/String", "length", "()I") => Ok(Some(Value::Int(this()?.len() as i32))),
+            ("java/lang/String", "charAt", "(I)C") => char_at(&this()?, int_arg(0)?).map(Some),
+            ("java/lang/String", "toCharArray", "()[C") => Ok(Some(char_array(&this()?))),
+            ("java/lang/String", "intern", "()Ljava/lang/String;")
+            | ("java/lang/String", "toString", "()Ljava/lang/String;") => Ok(receiver),
+            ("java/lang/String", "hashCode", "()I") => {
+                Ok(Some(Value::Int(java_hash_code(&this()?))))
+            }
+            ("java/lang/String", "substring", "(I)Ljava/lang/String;") => {
+                let chars = this()?;
+                substring(&chars, int_arg(0)?, chars.len() as i32)
+            }
+            ("java/lang/String", "substring", "(II)Ljava/lang/String;") => {
+                substring(&this()?, int_arg(0)?, int_arg(1)?)
+            }
+            ("java/lang/String", "concat", "(Ljava/lang/String;)Ljava/lang/String;") => {
+                let mut chars = this()?;
+                let other = string_of(&args[0])?;
+                check_string_length(chars.len() + other.len())?;
+                chars.extend(other);
+                Ok(Some(Value::object(Object::String(chars))))
+            }
+            ("java/lang/String", "valueOf", "([C)Ljava/lang/String;") => {
+                Ok(Some(Value::object(Object::String(chars_of(&args[0])?))))
+            }
+            ("java/lang/String", "valueOf", "(C)Ljava/lang/String;") => {
+                Ok(Some(Value::object(Object::String(vec![int_arg(0)? as u16]))))
+            }
+            ("java/lang/String", "valueOf", "(I)Ljava/lang/String;") => {
+                Ok(Some(Value::string(&int_arg(0)?.to_string())))
+            }
+            (_, "<init>", "()V") | (_, "<init>", "(I)V") if is_builder => {
+                set_this(Vec::new()).map(|_| None)
+            }
+            (_, "<init>", "(Ljava/lang/String;)V") if is_builder => {
+                set_this(string_of(&args[0])?).map(|_| None)
+            }
+            (_, "append", _) if is_builder => {
+                // The argument may be the builder itself, so it is read out
+                // before the receiver is borrowed for writing.
+                let extra = match (descriptor, args.first()) {
+                    (x, Some(Value::Int(c))) if x.starts_with("(C)") => vec![*c as u16],
+                    (x, Some(Value::Int(i))) if x.starts_with("(I)") => i.to_string().encode_utf16().collect(),
+                    (x, Some(Value::Long(i))) if x.starts_with("(J)") => i.to_string().encode_utf16().collect(),
+                    (x, Some(value)) if x.starts_with("(Ljava/lang/String;)") => match value {
+                        Value::Null => "null".encode_utf16().collect(),
+                        x => string_of(x)?,
+                    },
+                    (x, Some(value)) if x.starts_with("([C)") => chars_of(value)?,
+                    _ => return err(format!("unsupported intrinsic {}.append{}", class, descriptor)),
+                };
+                grow_this(&extra)?;
+                Ok(receiver.clone())
+            }
+            (_, "toString", "()Ljava/lang/String;") if is_builder => {
+                Ok(Some(Value::object(Object::String(this()?))))
+            }
+            (_, "length", "()I") if is_builder => Ok(Some(Value::Int(this()?.len() as i32))),
+            (_, "charAt", "(I)C") if is_builder => char_at(&this()?, int_arg(0)?).map(Some),
+            (_, "reverse", _) if is_builder => {
+                let chars = String::from_utf16_lossy(&this()?)
+                    .chars()
+                    .rev()
+                    .collect::<String>();
+                set_this(chars.encode_utf16().collect())?;
+                Ok(receiver.clone())
+            }
+            (_, "setCharAt", "(IC)V") if is_builder => {
+                let mut chars = this()?;
+                let index = int_arg(0)?;
+                match usize::try_from(index).ok().and_then(|i| chars.get_mut(i)) {
+                    Some(x) => *x = int_arg(1)? as u16,
+                    None => return err("java/lang/StringIndexOutOfBoundsException"),
+                }
+                set_this(chars).map(|_| None)
+            }
+            ("java/lang/Math", "abs", "(I)I") => Ok(Some(Value::Int(int_arg(0)?.wrapping_abs()))),
+            ("java/lang/Math", "max", "(II)I") => Ok(Some(Value::Int(int_arg(0)?.max(int_arg(1)?)))),
+            ("java/lang/Math", "min", "(II)I") => Ok(Some(Value::Int(int_arg(0)?.min(int_arg(1)?)))),
+            _ => err(format!("call to {}.{}{} is not allowed in the sandbox", class, name, descriptor)),
+        }
+    }
+}
@@ -0,0 +1,901 @@
+//! Java sources declaring the API of compiled classes, with bodies that
+//! throw, to compile against a library only its binaries are at hand for,
+//! see [`JavaStubGenerator`].
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+    sync::Arc,
+};
+
+use crate::{
+    attributes::{Annotation, ConstantValue, ElementValue, InnerClassAccessFlags},
+    field::{Field, TypeDescriptor},
+    method::Method,
+    raw::ConstantPoolItem,
+    resolver::ClassResolver,
+    signature::{ClassType, JavaType, ReferenceType, ThrowsSignature, TypeArgument, TypeParameter},
+    ClassAccessFlags, ClassFile, FieldAccessFlags, MethodAccessFlags, Result,
+};
+
+const INDENT: &str = "    ";
+
+const THROW: &str = "throw new UnsupportedOperationException();";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Class,
+    Interface,
+    Annotation,
+    Enum,
+    Record,
+}
+
+/// How a stub writes the classes it refers to: by simple name where an
+/// import or its package lets it, and by canonical name otherwise.
+#[derive(Default)]
+struct Names {
+    /// The package of the stub, e.g. `com/example`.
+    package: String,
+    /// The declaring class and simple name of every member class known.
+    members: HashMap<String, (String, String)>,
+    /// The class each simple name written stands for.
+    simple: HashMap<String, String>,
+    imports: BTreeSet<String>,
+}
+
+impl Names {
+    fn add_members(&mut self, class_file: &ClassFile) -> Result<()> {
+        if let Some(inner_classes) = class_file.inner_classes()? {
+            for x in inner_classes.classes()? {
+                if let (Some(outer), Some(name)) = (x.outer_class, x.inner_name) {
+                    self.members
+                        .entry(x.inner_class.to_string())
+                        .or_insert_with(|| (outer.to_string(), name.to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The top level class declaring `name`, and the simple names of the
+    /// member classes from there down to it.
+    fn split(&self, name: &str) -> (String, Vec<String>) {
+        let mut top = name;
+        let mut path = Vec::new();
+        // Guard against cycles in malformed input.
+        while let Some((outer, simple)) = self.members.get(top).filter(|_| path.len() <= self.members.len()) {
+            path.push(simple.clone());
+            top = outer;
+        }
+        path.reverse();
+        (top.to_string(), path)
+    }
+
+    /// Has `simple` stand for `name`, unless it already stands for another
+    /// class.
+    fn claim(&mut self, simple: &str, name: &str) -> bool {
+        self.simple.entry(simple.to_string()).or_insert_with(|| name.to_string()) == name
+    }
+
+    /// The class with internal name `name` as the source writes it, e.g.
+    /// `Map.Entry`, importing it if need be.
+    fn class(&mut self, name: &str) -> String {
+        let (top, path) = self.split(name);
+        let (package, simple) = top.rsplit_once('/').unwrap_or(("", &top));
+        let mut res = if self.claim(simple, &top) {
+            if package != self.package && package != "java/lang" {
+                self.imports.insert(top.replace('/', "."));
+            }
+            simple.to_string()
+        } else {
+            top.replace('/', ".")
+        };
+        for x in path {
+            res.push('.');
+            res.push_str(&x);
+        }
+        res
+    }
+
+    fn descriptor(&mut self, ty: &TypeDescriptor) -> String {
+        ty.to_java_type_with(&mut |x| self.class(x))
+    }
+
+    fn java_type(&mut self, ty: &JavaType) -> String {
+        match ty {
+            JavaType::Base(x) => x.to_string(),
+            JavaType::Reference(x) => self.reference(x),
+        }
+    }
+
+    fn reference(&mut self, ty: &ReferenceType) -> String {
+        match ty {
+            ReferenceType::JavaString => self.class("java/lang/String"),
+            ReferenceType::JavaClass => self.class("java/lang/Class"),
+            ReferenceType::ClassType(x) => self.class_type(x),
+            ReferenceType::TypeVariable(x) => x.to_string(),
+            ReferenceType::ArrayType(x) => format!("{}[]", self.java_type(x)),
+        }
+    }
+
+    fn class_type(&mut self, ty: &ClassType) -> String {
+        let mut name = ty.package.join("/");
+        if !name.is_empty() {
+            name.push('/');
+        }
+        name.push_str(ty.base.name);
+        let mut res = self.class(&name);
+        res.push_str(&self.type_arguments(&ty.base.type_arguments));
+        for x in &ty.sub {
+            res.push('.');
+            res.push_str(x.name);
+            res.push_str(&self.type_arguments(&x.type_arguments));
+        }
+        res
+    }
+
+    fn type_arguments(&mut self, arguments: &[TypeArgument]) -> String {
+        if arguments.is_empty() {
+            return String::new();
+        }
+        let arguments = arguments
+            .iter()
+            .map(|x| match x {
+                TypeArgument::Exact(x) => self.reference(x),
+                TypeArgument::Plus(x) => format!("? extends {}", self.reference(x)),
+                TypeArgument::Minus(x) => format!("? super {}", self.reference(x)),
+                TypeArgument::Star => "?".to_string(),
+            })
+            .collect::<Vec<_>>();
+        format!("<{}>", arguments.join(", "))
+    }
+
+    /// Type parameters followed by a space, e.g. `<K, V extends Comparable<V>> `,
+    /// or nothing if there are none. A lone `Object` bound is left out, but
+    /// not one before interface bounds, which would change the erasure.
+    fn type_parameters(&mut self, parameters: &[TypeParameter]) -> String {
+        if parameters.is_empty() {
+            return String::new();
+        }
+        let mut res = Vec::new();
+        for x in parameters {
+            let bounds = x
+                .class_bound
+                .iter()
+                .chain(&x.interface_bounds)
+                .map(|x| self.reference(x))
+                .collect::<Vec<_>>();
+            let object = matches!(&x.class_bound, Some(ReferenceType::ClassType(x)) if x.package == ["java", "lang"] && x.base.name == "Object");
+            match bounds.len() {
+                0 => res.push(x.name.to_string()),
+                1 if object => res.push(x.name.to_string()),
+                _ => res.push(format!("{} extends {}", x.name, bounds.join(" & "))),
+            }
+        }
+        format!("<{}> ", res.join(", "))
+    }
+
+    /// An initializer for a field of type `ty` that is not a constant
+    /// expression, so code compiled against the stub reads the field rather
+    /// than a value copied from it, e.g. `Integer.valueOf(0)`.
+    fn default_value(&mut self, ty: &TypeDescriptor) -> String {
+        let (wrapper, zero) = match ty {
+            TypeDescriptor::Boolean => ("java/lang/Boolean", "false"),
+            TypeDescriptor::Byte => ("java/lang/Byte", "(byte) 0"),
+            TypeDescriptor::Char => ("java/lang/Character", "(char) 0"),
+            TypeDescriptor::Short => ("java/lang/Short", "(short) 0"),
+            TypeDescriptor::Int => ("java/lang/Integer", "0"),
+            TypeDescriptor::Long => ("java/lang/Long", "0L"),
+            TypeDescriptor::Float => ("java/lang/Float", "0.0f"),
+            TypeDescriptor::Double => ("java/lang/Double", "0.0"),
+            _ => return "null".to_string(),
+        };
+        format!("{}.valueOf({})", self.class(wrapper), zero)
+    }
+
+    /// An argument of type `ty` for a call that only has to pick the right
+    /// overload, e.g. `(String) null`.
+    fn placeholder(&mut self, ty: &TypeDescriptor) -> String {
+        match ty {
+            TypeDescriptor::Boolean => "false".to_string(),
+            TypeDescriptor::Byte => "(byte) 0".to_string(),
+            TypeDescriptor::Char => "(char) 0".to_string(),
+            TypeDescriptor::Short => "(short) 0".to_string(),
+            TypeDescriptor::Int => "0".to_string(),
+            TypeDescriptor::Long => "0L".to_string(),
+            TypeDescriptor::Float => "0.0f".to_string(),
+            TypeDescriptor::Double => "0.0".to_string(),
+            x => format!("({}) null", self.descriptor(x)),
+        }
+    }
+}
+
+/// The `ConstantValue` of a field of type `ty` as an element value, which
+/// writes it as a literal.
+pub(crate) fn constant_value<'a>(ty: &TypeDescriptor, value: &ConstantValue<'a>) -> Result<Option<ElementValue<'a>>> {
+    Ok(Some(match (ty, value.item()) {
+        (TypeDescriptor::Boolean, Some(ConstantPoolItem::Integer { value })) => ElementValue::Boolean(*value != 0),
+        (TypeDescriptor::Char, Some(ConstantPoolItem::Integer { value })) => ElementValue::Char(*value as u16),
+        (
+            TypeDescriptor::Byte | TypeDescriptor::Short | TypeDescriptor::Int,
+            Some(ConstantPoolItem::Integer { value }),
+        ) => ElementValue::Int(*value),
+        (TypeDescriptor::Long, Some(ConstantPoolItem::Long { value })) => ElementValue::Long(*value),
+        (TypeDescriptor::Float, Some(ConstantPoolItem::Float { value })) => ElementValue::Float(*value),
+        (TypeDescriptor::Double, Some(ConstantPoolItem::Double { value })) => ElementValue::Double(*value),
+        (TypeDescriptor::String, Some(ConstantPoolItem::String { .. })) => ElementValue::String(value.string_value()?),
+        _ => return Ok(None),
+    }))
+}
+
+/// The class whose members are being written.
+struct Owner<'c> {
+    class_file: &'c ClassFile,
+    kind: Kind,
+    /// The simple name, which constructors take.
+    name: String,
+    /// Whether it is an inner class, whose constructors take an instance of
+    /// the class declaring it first.
+    inner: bool,
+    /// The names of the components of a record, which the parameters of its
+    /// canonical constructor must have.
+    components: Vec<String>,
+}
+
+/// Writes Java sources declaring the classes, fields and methods of compiled
+/// classes, with generic types from their `Signature` attributes, so that
+/// code compiled against them links to the originals.
+///
+/// Constants keep their `ConstantValue`. Other final fields are initialized
+/// with expressions that are not constant, and methods and constructors
+/// throw `UnsupportedOperationException`. Private fields and methods,
+/// and synthetic and bridge members, are left out.
+///
+/// Declarations keep their annotations, visible and invisible, and elements
+/// of annotation interfaces their defaults. Parameter annotations are left
+/// out.
+///
+/// Member classes are declared inside the class declaring them, which takes
+/// a [`ClassResolver`] to find them; without one they are left out. The
+/// resolver also finds superclasses, whose constructors a stub's
+/// constructors then call when there is no constructor without parameters to
+/// call implicitly.
+#[derive(Default, Clone, Copy)]
+pub struct JavaStubGenerator<'r> {
+    resolver: Option<&'r dyn ClassResolver>,
+    private: bool,
+}
+
+impl std::fmt::Debug for JavaStubGenerator<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JavaStubGenerator")
+            .field("resolver", &self.resolver.is_some())
+            .field("private", &self.private)
+            .finish()
+    }
+}
+
+/// A stub being written.
+struct Stub<'g, 'r> {
+    generator: &'g JavaStubGenerator<'r>,
+    names: Names,
+    /// The member classes found, by internal name.
+    members: HashMap<String, Arc<ClassFile>>,
+}
+
+impl<'r> JavaStubGenerator<'r> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks up member classes and superclasses through `resolver`.
+    pub fn with_resolver(mut self, resolver: &'r dyn ClassResolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Whether to declare private fields and methods too. They are left out
+    /// by default, as code compiled against a stub cannot use them.
+    pub fn with_private(mut self, private: bool) -> Self {
+        self.private = private;
+        self
+    }
+
+    /// Whether `class_file` gets a source file of its own: whether it is a
+    /// top level class or interface, rather than a member, local or
+    /// anonymous class, a `module-info` or a `package-info`.
+    pub fn needs_stub(class_file: &ClassFile) -> Result<bool> {
+        let name = class_file.this_class()?;
+        if class_file.access_flags().intersects(ClassAccessFlags::MODULE | ClassAccessFlags::SYNTHETIC)
+            || name.rsplit('/').next() == Some("package-info")
+        {
+            return Ok(false);
+        }
+        Ok(match class_file.inner_classes()? {
+            Some(x) => x.classes()?.iter().all(|x| x.inner_class != name),
+            None => true,
+        })
+    }
+
+    /// The path of the source file of `class_file` within a source tree,
+    /// e.g. `com/example/Foo.java`.
+    pub fn file_name(class_file: &ClassFile) -> Result<String> {
+        Ok(format!("{}.java", class_file.this_class()?))
+    }
+
+    /// The source file declaring `class_file`, a top level class, with its
+    /// package and the imports it needs.
+    pub fn stub(&self, class_file: &ClassFile) -> Result<String> {
+        let this_class = class_file.this_class()?;
+        let (package, simple) = this_class.rsplit_once('/').unwrap_or(("", this_class));
+        let mut stub = Stub {
+            generator: self,
+            names: Names {
+                package: package.to_string(),
+                ..Names::default()
+            },
+            members: HashMap::new(),
+        };
+        let mut pending = vec![this_class.to_string()];
+        while let Some(name) = pending.pop() {
+            let found = stub.members.get(&name).cloned();
+            let current = found.as_deref().unwrap_or(class_file);
+            stub.names.add_members(current)?;
+            if let Some(resolver) = self.resolver {
+                for (member, _) in Self::member_classes(current)? {
+                    if let Some(x) = resolver.resolve(&member)? {
+                        stub.members.insert(member.clone(), x);
+                        pending.push(member);
+                    }
+                }
+            }
+        }
+        // Names declared in the class hide imported ones inside it.
+        stub.names.claim(simple, this_class);
+        let members = stub.names.members.clone();
+        for (member, (_, simple)) in &members {
+            if stub.names.split(member).0 == this_class {
+                stub.names.claim(simple, member);
+            }
+        }
+
+        let mut body = String::new();
+        stub.declaration(class_file, simple, None, "", &mut body)?;
+        let mut out = String::new();
+        if !package.is_empty() {
+            writeln!(out, "package {};", package.replace('/', "."))?;
+            writeln!(out)?;
+        }
+        for x in &stub.names.imports {
+            writeln!(out, "import {};", x)?;
+        }
+        if !stub.names.imports.is_empty() {
+            writeln!(out)?;
+        }
+        out.push_str(&body);
+        Ok(out)
+    }
+
+    /// The member classes `class_file` declares, with their simple names and
+    /// flags. Private ones are among them, as the types of other members may
+    /// name them.
+    fn member_classes(class_file: &ClassFile) -> Result<Vec<(String, (String, InnerClassAccessFlags))>> {
+        let this_class = class_file.this_class()?;
+        let mut res = Vec::new();
+        if let Some(inner_classes) = class_file.inner_classes()? {
+            for x in inner_classes.classes()? {
+                let name = match x.inner_name {
+                    Some(name) if x.outer_class == Some(this_class) => name,
+                    _ => continue,
+                };
+                if x.access_flags.contains(InnerClassAccessFlags::SYNTHETIC) {
+                    continue;
+                }
+                res.push((x.inner_class.to_string(), (name.to_string(), x.access_flags)));
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl Stub<'_, '_> {
+    /// Writes the declaration of `class_file`, called `name`, with the flags
+    /// of its `InnerClasses` entry if it is a member class.
+    fn declaration(
+        &mut self,
+        class_file: &ClassFile,
+        name: &str,
+        member: Option<InnerClassAccessFlags>,
+        indent: &str,
+        out: &mut String,
+    ) -> Result<()> {
+        let flags = class_file.access_flags();
+        let super_class = match class_file.super_class.0 {
+            0 => None,
+            _ => Some(class_file.super_class()?),
+        };
+        let kind = if flags.contains(ClassAccessFlags::ANNOTATION) {
+            Kind::Annotation
+        } else if flags.contains(ClassAccessFlags::INTERFACE) {
+            Kind::Interface
+        } else if flags.contains(ClassAccessFlags::ENUM) && super_class == Some("java/lang/Enum") {
+            Kind::Enum
+        } else if super_class == Some("java/lang/Record") && class_file.record_components()?.is_some() {
+            Kind::Record
+        } else {
+            Kind::Class
+        };
+        let (public, protected, private, is_static, is_abstract, is_final) = match member {
+            Some(x) => (
+                x.contains(InnerClassAccessFlags::PUBLIC),
+                x.contains(InnerClassAccessFlags::PROTECTED),
+                x.contains(InnerClassAccessFlags::PRIVATE),
+                x.contains(InnerClassAccessFlags::STATIC),
+                x.contains(InnerClassAccessFlags::ABSTRACT),
+                x.contains(InnerClassAccessFlags::FINAL),
+            ),
+            None => (
+                flags.contains(ClassAccessFlags::PUBLIC),
+                false,
+                false,
+                false,
+                flags.contains(ClassAccessFlags::ABSTRACT),
+                flags.contains(ClassAccessFlags::FINAL),
+            ),
+        };
+
+        let mut header = String::new();
+        for (set, word) in [(public, "public "), (protected, "protected "), (private, "private ")] {
+            if set {
+                header.push_str(word);
+            }
+        }
+        if kind == Kind::Class {
+            for (set, word) in [(is_static, "static "), (is_abstract, "abstract "), (is_final, "final ")] {
+                if set {
+                    header.push_str(word);
+                }
+            }
+        }
+        header.push_str(match kind {
+            Kind::Class => "class ",
+            Kind::Interface => "interface ",
+            Kind::Annotation => "@interface ",
+            Kind::Enum => "enum ",
+            Kind::Record => "record ",
+        });
+        header.push_str(name);
+
+        let is_object = |x: &str| x == "java/lang/Object";
+        let (superclass, interfaces) = match class_file.signature()? {
+            Some(signature) => {
+                header.push_str(self.names.type_parameters(&signature.type_parameters).trim_end());
+                let superclass = match kind {
+                    Kind::Class if !(signature.superclass_signature.package == ["java", "lang"]
+                        && signature.superclass_signature.base.name == "Object") =>
+                    {
+                        Some(self.names.class_type(&signature.superclass_signature))
+                    }
+                    _ => None,
+                };
+                let interfaces = signature
+                    .superinterface_signatures
+                    .iter()
+                    .map(|x| self.names.class_type(x))
+                    .collect::<Vec<_>>();
+                (superclass, interfaces)
+            }
+            None => {
+                let superclass = match (kind, super_class) {
+                    (Kind::Class, Some(x)) if !is_object(x) => Some(self.names.class(x)),
+                    _ => None,
+                };
+                let interfaces = class_file
+                    .interfaces()?
+                    .into_iter()
+                    .filter(|x| kind != Kind::Annotation || *x != "java/lang/annotation/Annotation")
+                    .map(|x| self.names.class(x))
+                    .collect::<Vec<_>>();
+                (superclass, interfaces)
+            }
+        };
+        let mut components = Vec::new();
+        if kind == Kind::Record {
+            let (parameters, names) = self.record_components(class_file)?;
+            write!(header, "({})", parameters.join(", "))?;
+            components = names;
+        }
+        if let Some(x) = superclass {
+            write!(header, " extends {}", x)?;
+        }
+        if !interfaces.is_empty() && kind != Kind::Annotation {
+            let keyword = if kind == Kind::Interface { "extends" } else { "implements" };
+            write!(header, " {} {}", keyword, interfaces.join(", "))?;
+        }
+        out.push_str(&self.annotations(class_file.annotations()?, class_file.invisible_annotations()?, indent));
+        writeln!(out, "{}{} {{", indent, header)?;
+
+        let owner = Owner {
+            class_file,
+            kind,
+            name: name.to_string(),
+            inner: member.is_some_and(|x| !x.contains(InnerClassAccessFlags::STATIC)) && kind == Kind::Class,
+            components,
+        };
+        let inner_indent = format!("{}{}", indent, INDENT);
+        let mut items = Vec::new();
+        let fields = class_file.fields();
+        if kind == Kind::Enum {
+            let mut constants = Vec::new();
+            for x in fields.iter().filter(|x| x.field_inner.access_flags.contains(FieldAccessFlags::ENUM)) {
+                let annotations = self.annotations(x.annotations()?, x.invisible_annotations()?, &inner_indent);
+                constants.push(format!("{}{}{}", annotations, inner_indent, x.identifier()?));
+            }
+            if constants.is_empty() {
+                constants.push(inner_indent.clone());
+            }
+            items.push(format!("{};\n", constants.join(",\n")));
+        }
+        for field in &fields {
+            if let Some(x) = self.field(field, &owner, &inner_indent)? {
+                items.push(x);
+            }
+        }
+        let mut constructors = 0;
+        for method in class_file.methods() {
+            if let Some(x) = self.method(&method, &owner, &inner_indent)? {
+                constructors += (method.identifier()? == "<init>") as usize;
+                items.push(x);
+            }
+        }
+        if kind == Kind::Class && constructors == 0 {
+            // Without a constructor the class would get a public one, where
+            // the constructors left out are private.
+            let (call, throws) = match self.super_call(class_file)? {
+                Some((call, throws)) => (
+                    format!("{}{}{}\n", inner_indent, INDENT, call),
+                    match throws.is_empty() {
+                        true => String::new(),
+                        false => format!(" throws {}", throws.join(", ")),
+                    },
+                ),
+                None => (String::new(), String::new()),
+            };
+            items.push(format!(
+                "{0}private {1}(){2} {{\n{3}{0}{4}{5}\n{0}}}\n",
+                inner_indent, name, throws, call, INDENT, THROW
+            ));
+        }
+        for (member, (name, flags)) in JavaStubGenerator::member_classes(class_file)? {
+            if let Some(x) = self.members.get(&member).cloned() {
+                let mut text = String::new();
+                self.declaration(&x, &name, Some(flags), &inner_indent, &mut text)?;
+                items.push(text);
+            }
+        }
+        out.push_str(&items.join("\n"));
+        writeln!(out, "{}}}", indent)?;
+        Ok(())
+    }
+
+    fn field(&mut self, field: &Field, owner: &Owner, indent: &str) -> Result<Option<String>> {
+        let flags = &field.field_inner.access_flags;
+        // The fields of record components are implicit.
+        if field.is_synthetic()
+            || flags.contains(FieldAccessFlags::ENUM)
+            || (owner.kind == Kind::Record && !flags.contains(FieldAccessFlags::STATIC))
+            || (flags.contains(FieldAccessFlags::PRIVATE) && !self.generator.private)
+        {
+            return Ok(None);
+        }
+        let interface = matches!(owner.kind, Kind::Interface | Kind::Annotation);
+        let mut res = self.annotations(field.annotations()?, field.invisible_annotations()?, indent);
+        res.push_str(indent);
+        if !interface {
+            for (flag, word) in [
+                (FieldAccessFlags::PUBLIC, "public "),
+                (FieldAccessFlags::PROTECTED, "protected "),
+                (FieldAccessFlags::PRIVATE, "private "),
+                (FieldAccessFlags::STATIC, "static "),
+                (FieldAccessFlags::FINAL, "final "),
+                (FieldAccessFlags::TRANSIENT, "transient "),
+                (FieldAccessFlags::VOLATILE, "volatile "),
+            ] {
+                if flags.contains(flag) {
+                    res.push_str(word);
+                }
+            }
+        }
+        let descriptor = field.descriptor()?;
+        let ty = match field.signature()? {
+            Some(x) => self.names.reference(&x),
+            None => self.names.descriptor(&descriptor),
+        };
+        write!(res, "{} {}", ty, field.identifier()?)?;
+        let constant = match field.constant_value()? {
+            Some(x) if interface || flags.contains(FieldAccessFlags::FINAL) => {
+                constant_value(&descriptor, &x)?
+            }
+            _ => None,
+        };
+        match constant {
+            Some(x) => write!(res, " = {}", x.to_java_with(&mut |x| self.names.class(x)))?,
+            None if interface || flags.contains(FieldAccessFlags::FINAL) => {
+                write!(res, " = {}", self.names.default_value(&descriptor))?
+            }
+            None => {}
+        }
+        res.push_str(";\n");
+        Ok(Some(res))
+    }
+
+    fn method(&mut self, method: &Method, owner: &Owner, indent: &str) -> Result<Option<String>> {
+        let flags = &method.method_inner.access_flags;
+        let name = method.identifier()?;
+        let raw_descriptor = method.method_inner.descriptor_index.get_as_string(owner.class_file)?;
+        let this_class = owner.class_file.this_class()?;
+        let implicit = owner.kind == Kind::Enum
+            && (name == "<init>"
+                || (name == "values" && raw_descriptor == format!("()[L{};", this_class))
+                || (name == "valueOf" && raw_descriptor == format!("(Ljava/lang/String;)L{};", this_class)));
+        if name == "<clinit>"
+            || implicit
+            || method.is_synthetic()
+            || flags.contains(MethodAccessFlags::BRIDGE)
+            || (flags.contains(MethodAccessFlags::PRIVATE) && !self.generator.private)
+        {
+            return Ok(None);
+        }
+        let interface = matches!(owner.kind, Kind::Interface | Kind::Annotation);
+        let is_abstract = flags.contains(MethodAccessFlags::ABSTRACT) && owner.kind != Kind::Enum;
+        let mut res = self.annotations(method.annotations()?, method.invisible_annotations()?, indent);
+        res.push_str(indent);
+        let modifiers: &[(MethodAccessFlags, &str)] = match interface {
+            true => &[(MethodAccessFlags::PRIVATE, "private "), (MethodAccessFlags::STATIC, "static ")],
+            false => &[
+                (MethodAccessFlags::PUBLIC, "public "),
+                (MethodAccessFlags::PROTECTED, "protected "),
+                (MethodAccessFlags::PRIVATE, "private "),
+                (MethodAccessFlags::STATIC, "static "),
+                (MethodAccessFlags::FINAL, "final "),
+                (MethodAccessFlags::SYNCHRONIZED, "synchronized "),
+                (MethodAccessFlags::NATIVE, "native "),
+                (MethodAccessFlags::ABSTRACT, "abstract "),
+            ],
+        };
+        for (flag, word) in modifiers {
+            if flags.contains(MethodAccessFlags::from_bits_retain(flag.bits())) && (*word != "abstract " || is_abstract) {
+                res.push_str(word);
+            }
+        }
+        if interface && !flags.intersects(MethodAccessFlags::ABSTRACT | MethodAccessFlags::STATIC | MethodAccessFlags::PRIVATE) {
+            res.push_str("default ");
+        }
+
+        let descriptor = method.descriptor()?;
+        let signature = method.signature()?;
+        let (mut parameters, return_type) = match &signature {
+            Some(x) => {
+                res.push_str(&self.names.type_parameters(&x.type_parameters));
+                let parameters = x.parameters.iter().map(|x| self.names.java_type(x)).collect::<Vec<_>>();
+                let return_type = x.result.as_ref().map(|x| self.names.java_type(x));
+                (parameters, return_type)
+            }
+            None => {
+                let mut types = descriptor.parameter_types();
+                if name == "<init>" && owner.inner {
+                    if let Some(TypeDescriptor::ClassName(_)) = types.first() {
+                        types = &types[1..];
+                    }
+                }
+                let parameters = types.iter().map(|x| self.names.descriptor(x)).collect::<Vec<_>>();
+                let return_type = descriptor.return_type().map(|x| self.names.descriptor(x));
+                (parameters, return_type)
+            }
+        };
+        if flags.contains(MethodAccessFlags::VARARGS) {
+            if let Some(x) = parameters.last_mut().and_then(|x| x.strip_suffix("[]").map(|y| (x.len(), y.len()))) {
+                let last = parameters.last_mut().unwrap();
+                last.truncate(x.1);
+                last.push_str("...");
+            }
+        }
+        match name {
+            "<init>" => res.push_str(&owner.name),
+            _ => write!(res, "{} {}", return_type.as_deref().unwrap_or("void"), name)?,
+        }
+        let canonical = match owner.kind {
+            Kind::Record if name == "<init>" => match owner.class_file.record_canonical_constructor()? {
+                Some(x) => x.method_inner.descriptor_index.get_as_string(owner.class_file)? == raw_descriptor,
+                None => false,
+            },
+            _ => false,
+        };
+        let parameters = parameters
+            .iter()
+            .enumerate()
+            .map(|(i, x)| match owner.components.get(i).filter(|_| canonical) {
+                Some(name) => format!("{} {}", x, name),
+                None => format!("{} arg{}", x, i),
+            })
+            .collect::<Vec<_>>();
+        write!(res, "({})", parameters.join(", "))?;
+        let mut throws = self.throws(&method.throws()?);
+        let call = match (name, owner.kind) {
+            ("<init>", Kind::Record) if !canonical => self.canonical_call(owner.class_file)?,
+            ("<init>", Kind::Record) => None,
+            ("<init>", _) if !is_abstract => self.super_call(owner.class_file)?,
+            _ => None,
+        };
+        if let Some((_, exceptions)) = &call {
+            for x in exceptions {
+                if !throws.contains(x) {
+                    throws.push(x.clone());
+                }
+            }
+        }
+        if !throws.is_empty() {
+            write!(res, " throws {}", throws.join(", "))?;
+        }
+
+        if let Some(x) = method.annotation_default()? {
+            write!(res, " default {}", x.to_java_with(&mut |x| self.names.class(x)))?;
+        }
+        if is_abstract || flags.contains(MethodAccessFlags::NATIVE) {
+            res.push_str(";\n");
+        } else {
+            writeln!(res, " {{")?;
+            if let Some((call, _)) = call {
+                writeln!(res, "{}{}{}", indent, INDENT, call)?;
+            }
+            writeln!(res, "{}{}{}", indent, INDENT, THROW)?;
+            writeln!(res, "{}}}", indent)?;
+        }
+        Ok(Some(res))
+    }
+
+    /// The components of a record as its header declares them, e.g.
+    /// `List<T> items`, and their names.
+    fn record_components(&mut self, class_file: &ClassFile) -> Result<(Vec<String>, Vec<String>)> {
+        let components = class_file.record_components()?.unwrap_or_default();
+        let varargs = match class_file.record_canonical_constructor()? {
+            Some(x) => x.method_inner.access_flags.contains(MethodAccessFlags::VARARGS),
+            None => false,
+        };
+        let mut parameters = Vec::new();
+        let mut names = Vec::new();
+        for (i, x) in components.iter().enumerate() {
+            let mut parameter = String::new();
+            for annotation in x.annotations()?.iter().chain(&x.invisible_annotations()?) {
+                parameter.push_str(&annotation.to_java_with(&mut |x| self.names.class(x)));
+                parameter.push(' ');
+            }
+            let mut ty = match x.generic_type()? {
+                Some(x) => self.names.java_type(&x),
+                None => self.names.descriptor(&x.descriptor()?),
+            };
+            if varargs && i + 1 == components.len() && ty.ends_with("[]") {
+                ty.truncate(ty.len() - 2);
+                ty.push_str("...");
+            }
+            write!(parameter, "{} {}", ty, x.identifier()?)?;
+            parameters.push(parameter);
+            names.push(x.identifier()?.to_string());
+        }
+        Ok((parameters, names))
+    }
+
+    /// `visible` and `invisible` annotations of a declaration, each on a line
+    /// of its own.
+    fn annotations(&mut self, visible: Vec<Annotation>, invisible: Vec<Annotation>, indent: &str) -> String {
+        let mut res = String::new();
+        for x in visible.iter().chain(&invisible) {
+            res.push_str(indent);
+            res.push_str(&x.to_java_with(&mut |x| self.names.class(x)));
+            res.push('\n');
+        }
+        res
+    }
+
+    /// A call of a constructor of the superclass of `class_file`, such as
+    /// `super((String) null, 0);`, with the exceptions it declares, or `None`
+    /// if one without parameters or exceptions can be called implicitly or
+    /// the superclass cannot be found.
+    fn super_call(&mut self, class_file: &ClassFile) -> Result<Option<(String, Vec<String>)>> {
+        let resolver = match self.generator.resolver {
+            Some(x) if class_file.super_class.0 != 0 => x,
+            _ => return Ok(None),
+        };
+        let super_class = class_file.super_class()?;
+        let super_file = match resolver.resolve(super_class)? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        // The constructors of an inner superclass take its enclosing instance
+        // first, which `super(...)` passes implicitly.
+        let inner = match super_file.inner_classes()? {
+            Some(x) => x.classes()?.iter().any(|x| {
+                x.inner_class == super_class
+                    && x.outer_class.is_some()
+                    && !x.access_flags.contains(InnerClassAccessFlags::STATIC)
+            }),
+            None => false,
+        };
+        let package = |x: &str| x.rsplit_once('/').map_or("", |x| x.0).to_string();
+        let same_package = package(super_class) == package(class_file.this_class()?);
+        // Of the constructors to call, the first that throws no checked
+        // exceptions is preferred, as the stub's constructors may not.
+        let mut candidate: Option<(Vec<String>, Vec<String>)> = None;
+        for x in super_file.methods() {
+            let flags = &x.method_inner.access_flags;
+            if x.identifier()? != "<init>"
+                || x.is_synthetic()
+                || flags.contains(MethodAccessFlags::PRIVATE)
+                || (!flags.intersects(MethodAccessFlags::PUBLIC | MethodAccessFlags::PROTECTED) && !same_package)
+            {
+                continue;
+            }
+            let descriptor = x.descriptor()?;
+            let types = &descriptor.parameter_types()[(inner as usize).min(descriptor.parameter_types().len())..];
+            let throws = x.throws()?;
+            if types.is_empty() && throws.is_empty() {
+                return Ok(None);
+            }
+            if candidate.is_some() && (!throws.is_empty() || candidate.as_ref().is_some_and(|x| x.1.is_empty())) {
+                continue;
+            }
+            candidate = Some((self.arguments(&x, types)?, self.throws(&throws)));
+        }
+        let (arguments, throws) = match candidate {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        Ok(Some((format!("super({});", arguments.join(", ")), throws)))
+    }
+
+    /// A call of the canonical constructor of a record, which its other
+    /// constructors must begin with, and the exceptions it declares.
+    fn canonical_call(&mut self, class_file: &ClassFile) -> Result<Option<(String, Vec<String>)>> {
+        let canonical = match class_file.record_canonical_constructor()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let arguments = self.arguments(&canonical, canonical.descriptor()?.parameter_types())?;
+        let throws = self.throws(&canonical.throws()?);
+        Ok(Some((format!("this({});", arguments.join(", ")), throws)))
+    }
+
+    /// Arguments for the parameters of `types` of the constructor `method`,
+    /// for a call that only has to pick it among the others.
+    fn arguments(&mut self, method: &Method, types: &[TypeDescriptor]) -> Result<Vec<String>> {
+        // A parameter of a type variable's type takes a bare `null`, as a cast
+        // to its erasure would not convert to it.
+        let variables = match method.signature()? {
+            Some(x) if x.parameters.len() == types.len() => x
+                .parameters
+                .iter()
+                .map(|x| matches!(x, JavaType::Reference(ReferenceType::TypeVariable(_))))
+                .collect(),
+            _ => vec![false; types.len()],
+        };
+        Ok(types
+            .iter()
+            .zip(variables)
+            .map(|(x, variable)| match variable {
+                true => "null".to_string(),
+                false => self.names.placeholder(x),
+            })
+            .collect())
+    }
+
+    fn throws(&mut self, throws: &[ThrowsSignature]) -> Vec<String> {
+        throws
+            .iter()
+            .map(|x| match x {
+                ThrowsSignature::ClassType(x) => self.names.class_type(x),
+                ThrowsSignature::TypeVariable(x) => x.to_string(),
+            })
+            .collect()
+    }
+}
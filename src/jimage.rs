@@ -0,0 +1,361 @@
+//! Reading classes out of a JDK's `lib/modules` jimage, the format `jrt:/`
+//! URLs are served from, see [`JImage`].
+
+use std::{borrow::Cow, path::Path};
+
+use crate::{inflate, mutf8, repository::ClassRepository, ClassFile, Error, ParseOptions, Result};
+
+const MAGIC: u32 = 0xcafe_dada;
+const HEADER_SIZE: usize = 28;
+/// The magic number of a compressed resource's header.
+const COMPRESSED_MAGIC: u32 = 0xcafe_fafa;
+const COMPRESSED_HEADER_SIZE: usize = 29;
+/// The multiplier of the FNV-1a style hash, also the seed of the first
+/// lookup.
+const HASH_MULTIPLIER: u32 = 0x0100_0193;
+
+const ATTRIBUTE_END: u8 = 0;
+const ATTRIBUTE_MODULE: u8 = 1;
+const ATTRIBUTE_PARENT: u8 = 2;
+const ATTRIBUTE_BASE: u8 = 3;
+const ATTRIBUTE_EXTENSION: u8 = 4;
+const ATTRIBUTE_OFFSET: u8 = 5;
+const ATTRIBUTE_COMPRESSED: u8 = 6;
+const ATTRIBUTE_UNCOMPRESSED: u8 = 7;
+
+fn invalid(message: impl std::fmt::Display) -> Error {
+    Error::ArchiveError(format!("invalid jimage: {}", message))
+}
+
+fn hash(name: &str, seed: u32) -> u32 {
+    name.bytes()
+        .fold(seed, |hash, x| hash.wrapping_mul(HASH_MULTIPLIER) ^ x as u32)
+        & 0x7fff_ffff
+}
+
+/// A resource as the location table describes it. The name is split into
+/// offsets of strings, any of which may be empty.
+#[derive(Debug, Default)]
+struct Location {
+    module: u32,
+    parent: u32,
+    base: u32,
+    extension: u32,
+    offset: u64,
+    /// 0 if the resource is stored as is.
+    compressed: u64,
+    uncompressed: u64,
+}
+
+/// The image of the modules of a JDK, by default at `$JAVA_HOME/lib/modules`.
+///
+/// Resources are named `/module/path`, as `/java.base/java/lang/Object.class`.
+/// They are found through the image's own hash table and only read when
+/// asked for, so loading a few classes does not cost reading the rest.
+/// Resources compressed by jlink's `zip` plugin are supported; those of the
+/// `compact-cp` plugin fail to read.
+pub struct JImage {
+    data: Vec<u8>,
+    big_endian: bool,
+    table_length: usize,
+    redirect: usize,
+    offsets: usize,
+    locations: usize,
+    strings: usize,
+    /// The start of the resources, which location offsets are relative to.
+    resources: usize,
+}
+
+impl std::fmt::Debug for JImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JImage")
+            .field("resources", &self.table_length)
+            .finish()
+    }
+}
+
+impl JImage {
+    pub fn parse(data: Vec<u8>) -> Result<Self> {
+        let magic = data.get(..4).ok_or_else(|| invalid("too short"))?;
+        let big_endian = match [magic[0], magic[1], magic[2], magic[3]] {
+            x if u32::from_le_bytes(x) == MAGIC => false,
+            x if u32::from_be_bytes(x) == MAGIC => true,
+            _ => return Err(invalid("bad magic number")),
+        };
+        let mut image = Self {
+            data,
+            big_endian,
+            table_length: 0,
+            redirect: HEADER_SIZE,
+            offsets: 0,
+            locations: 0,
+            strings: 0,
+            resources: 0,
+        };
+        let version = image.u32(4)?;
+        if version >> 16 != 1 {
+            return Err(invalid(format!("unsupported version {}.{}", version >> 16, version & 0xffff)));
+        }
+        image.table_length = image.u32(16)? as usize;
+        image.offsets = image.redirect + image.table_length * 4;
+        image.locations = image.offsets + image.table_length * 4;
+        image.strings = image.locations + image.u32(20)? as usize;
+        image.resources = image.strings + image.u32(24)? as usize;
+        if image.resources > image.data.len() {
+            return Err(invalid("index runs past the end of the image"));
+        }
+        Ok(image)
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(std::fs::read(path)?)
+    }
+
+    /// Opens the image of the JDK at `java_home`.
+    pub fn open_java_home(java_home: impl AsRef<Path>) -> Result<Self> {
+        Self::open(java_home.as_ref().join("lib").join("modules"))
+    }
+
+    fn u32(&self, pos: usize) -> Result<u32> {
+        let x = self
+            .data
+            .get(pos..pos + 4)
+            .ok_or_else(|| invalid("unexpected end of image"))?;
+        Ok(self.endian_u32(x))
+    }
+
+    fn string(&self, offset: u32) -> Result<Cow<'_, str>> {
+        let start = self.strings + offset as usize;
+        let len = self.data[..self.resources]
+            .get(start..)
+            .and_then(|x| x.iter().position(|&x| x == 0))
+            .ok_or_else(|| invalid("unterminated string"))?;
+        let bytes = &self.data[start..start + len];
+        match std::str::from_utf8(bytes) {
+            Ok(x) => Ok(Cow::Borrowed(x)),
+            Err(_) => mutf8::decode(bytes).map(Cow::Owned).ok_or_else(|| invalid("bad string")),
+        }
+    }
+
+    /// Decodes the location at `offset` in the location table, a list of
+    /// attributes each with a byte of kind and length before a big endian
+    /// value.
+    fn location(&self, offset: u32) -> Result<Location> {
+        let mut res = Location::default();
+        let mut pos = self.locations + offset as usize;
+        loop {
+            let byte = *self.data[..self.strings]
+                .get(pos)
+                .ok_or_else(|| invalid("unterminated location"))?;
+            let (kind, length) = (byte >> 3, (byte & 7) as usize + 1);
+            if kind == ATTRIBUTE_END {
+                return Ok(res);
+            }
+            let value = self.data[..self.strings]
+                .get(pos + 1..pos + 1 + length)
+                .ok_or_else(|| invalid("unterminated location"))?
+                .iter()
+                .fold(0u64, |value, &x| value << 8 | x as u64);
+            match kind {
+                ATTRIBUTE_MODULE => res.module = value as u32,
+                ATTRIBUTE_PARENT => res.parent = value as u32,
+                ATTRIBUTE_BASE => res.base = value as u32,
+                ATTRIBUTE_EXTENSION => res.extension = value as u32,
+                ATTRIBUTE_OFFSET => res.offset = value,
+                ATTRIBUTE_COMPRESSED => res.compressed = value,
+                ATTRIBUTE_UNCOMPRESSED => res.uncompressed = value,
+                x => return Err(invalid(format!("unknown location attribute {}", x))),
+            }
+            pos += 1 + length;
+        }
+    }
+
+    /// The full name of `location`, `/module/parent/base.extension`.
+    fn name(&self, location: &Location) -> Result<String> {
+        let mut res = String::new();
+        let module = self.string(location.module)?;
+        if !module.is_empty() {
+            res.push('/');
+            res.push_str(&module);
+            res.push('/');
+        }
+        let parent = self.string(location.parent)?;
+        if !parent.is_empty() {
+            res.push_str(&parent);
+            res.push('/');
+        }
+        res.push_str(&self.string(location.base)?);
+        let extension = self.string(location.extension)?;
+        if !extension.is_empty() {
+            res.push('.');
+            res.push_str(&extension);
+        }
+        Ok(res)
+    }
+
+    /// Looks `name` up in the hash table. A negative redirect is the slot of
+    /// the only name with that hash, a positive one the seed to hash the
+    /// names that share it with again.
+    fn find(&self, name: &str) -> Result<Option<Location>> {
+        if self.table_length == 0 {
+            return Ok(None);
+        }
+        let slot = hash(name, HASH_MULTIPLIER) as usize % self.table_length;
+        let redirect = self.u32(self.redirect + slot * 4)? as i32;
+        let index = match redirect {
+            0 => return Ok(None),
+            x if x < 0 => (-1 - x) as usize,
+            x => hash(name, x as u32) as usize % self.table_length,
+        };
+        if index >= self.table_length {
+            return Err(invalid("redirect out of range"));
+        }
+        let location = self.location(self.u32(self.offsets + index * 4)?)?;
+        // Names missing from the table hash to some other name's slot.
+        Ok(self.name(&location).is_ok_and(|x| x == name).then_some(location))
+    }
+
+    fn read_location(&self, location: &Location) -> Result<Vec<u8>> {
+        let start = self.resources.checked_add(location.offset as usize);
+        let size = match location.compressed {
+            0 => location.uncompressed,
+            x => x,
+        } as usize;
+        let mut res = start
+            .and_then(|x| Some(x..x.checked_add(size)?))
+            .and_then(|x| self.data.get(x))
+            .ok_or_else(|| invalid("resource runs past the end of the image"))?
+            .to_vec();
+        if location.compressed == 0 {
+            return Ok(res);
+        }
+        // Plugins may compress one after another, each adding its header.
+        while res.len() >= COMPRESSED_HEADER_SIZE && self.endian_u32(&res[..4]) == COMPRESSED_MAGIC {
+            let size = self.endian_u64(&res[4..12]) as usize;
+            let uncompressed = self.endian_u64(&res[12..20]);
+            // Every stage decompresses to at most the final size.
+            if uncompressed > location.uncompressed {
+                return Err(invalid("compressed resource is larger than its location says"));
+            }
+            let uncompressed = uncompressed as usize;
+            let decompressor = self.string(self.endian_u32(&res[20..24]))?;
+            let content = COMPRESSED_HEADER_SIZE
+                .checked_add(size)
+                .and_then(|x| res.get(COMPRESSED_HEADER_SIZE..x))
+                .ok_or_else(|| invalid("compressed resource runs past its end"))?;
+            res = match &*decompressor {
+                // A zlib stream, two bytes of header before the deflate data.
+                "zip" => inflate::inflate(content.get(2..).unwrap_or_default(), uncompressed)?,
+                x => return Err(invalid(format!("unsupported decompressor {}", x))),
+            };
+        }
+        if res.len() as u64 != location.uncompressed {
+            return Err(invalid("resource decompressed to the wrong size"));
+        }
+        Ok(res)
+    }
+
+    fn endian_u32(&self, x: &[u8]) -> u32 {
+        let x = [x[0], x[1], x[2], x[3]];
+        if self.big_endian { u32::from_be_bytes(x) } else { u32::from_le_bytes(x) }
+    }
+
+    fn endian_u64(&self, x: &[u8]) -> u64 {
+        let (a, b) = (self.endian_u32(&x[..4]) as u64, self.endian_u32(&x[4..]) as u64);
+        if self.big_endian { a << 32 | b } else { b << 32 | a }
+    }
+
+    /// The contents of the resource called `name`, such as
+    /// `/java.base/java/lang/Object.class`.
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        self.find(name)?.map(|x| self.read_location(&x)).transpose()
+    }
+
+    /// The name of every resource, in table order. Besides the resources of
+    /// each module there are the `/packages/` and `/modules/` entries jrt
+    /// builds its directories from.
+    pub fn names(&self) -> Result<Vec<String>> {
+        (0..self.table_length)
+            .map(|i| self.name(&self.location(self.u32(self.offsets + i * 4)?)?))
+            .collect()
+    }
+
+    /// The modules with a non-empty package holding class `name`, e.g.
+    /// `java/lang/Object`. There is at most one in a JDK's image.
+    pub fn modules_of(&self, name: &str) -> Result<Vec<String>> {
+        let package = name.rsplit_once('/').map_or("", |(x, _)| x).replace('/', ".");
+        let content = match self.read(&format!("/packages/{}", package))? {
+            Some(x) => x,
+            None => return Ok(Vec::new()),
+        };
+        // Pairs of whether the module's package is empty and its name.
+        let mut res = Vec::new();
+        for x in content.chunks_exact(8) {
+            if self.endian_u32(&x[..4]) == 0 {
+                res.push(self.string(self.endian_u32(&x[4..]))?.into_owned());
+            }
+        }
+        Ok(res)
+    }
+
+    /// The bytes of class `name`, from whichever module has its package.
+    pub fn class_bytes(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        for module in self.modules_of(name)? {
+            if let Some(x) = self.read(&format!("/{}/{}.class", module, name))? {
+                return Ok(Some(x));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads and parses class `name`, if the image has it.
+    pub fn read_class(&self, name: &str, options: &ParseOptions) -> Result<Option<ClassFile>> {
+        self.class_bytes(name)?
+            .map(|x| ClassFile::parse_with_options(x, options))
+            .transpose()
+    }
+
+    /// Adds class `name` to `repository` with its superclasses and
+    /// interfaces, all the way up, skipping those `repository` already has
+    /// and those the image lacks. Returns how many classes were added.
+    pub fn load_hierarchy(&self, repository: &ClassRepository, name: &str, options: &ParseOptions) -> Result<usize> {
+        let mut pending = vec![name.to_string()];
+        let mut count = 0;
+        while let Some(name) = pending.pop() {
+            if repository.contains(&name) {
+                continue;
+            }
+            let data = match self.class_bytes(&name)? {
+                Some(x) => x,
+                None => continue,
+            };
+            let class_file = repository.parse(data, options)?;
+            count += 1;
+            if class_file.super_class.0 != 0 {
+                pending.push(class_file.super_class()?.to_string());
+            }
+            pending.extend(class_file.interfaces()?.into_iter().map(str::to_string));
+        }
+        Ok(count)
+    }
+
+    /// Parses every class of every module into `repository`, returning how
+    /// many there were. The modules' `module-info` classes are left out.
+    pub fn load_into(&self, repository: &ClassRepository, options: &ParseOptions) -> Result<usize> {
+        let mut count = 0;
+        for i in 0..self.table_length {
+            let location = self.location(self.u32(self.offsets + i * 4)?)?;
+            let module = self.string(location.module)?;
+            if &*self.string(location.extension)? != "class"
+                || &*self.string(location.base)? == "module-info"
+                || module == "modules"
+                || module == "packages"
+            {
+                continue;
+            }
+            repository.parse(self.read_location(&location)?, options)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
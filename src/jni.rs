@@ -0,0 +1,221 @@
+use std::{fmt::Write, sync::Arc};
+
+use crate::{field::TypeDescriptor, resolver::ClassResolver, ClassFile, Result};
+
+const THROWABLE: &str = "java/lang/Throwable";
+
+/// A JNI native method symbol split back into its Java parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JniSymbol {
+    /// The internal name of the class, e.g. `com/example/Foo`.
+    pub class: String,
+    pub method: String,
+    /// The parameter descriptors without parentheses, e.g. `Ljava/lang/String;I`,
+    /// present in the long form used for overloaded native methods.
+    pub parameters: Option<String>,
+}
+
+impl JniSymbol {
+    /// The short form of the symbol for method `method` of class `class`,
+    /// which the JVM tries first.
+    pub fn short(class: &str, method: &str) -> Self {
+        Self {
+            class: class.to_string(),
+            method: method.to_string(),
+            parameters: None,
+        }
+    }
+
+    /// The long form of the symbol for the method with `descriptor`, which
+    /// tells overloaded native methods apart.
+    pub fn long(class: &str, method: &str, descriptor: &str) -> Self {
+        let parameters = descriptor
+            .strip_prefix('(')
+            .and_then(|x| x.split_once(')'))
+            .map_or(descriptor, |(x, _)| x);
+        Self {
+            parameters: Some(parameters.to_string()),
+            ..Self::short(class, method)
+        }
+    }
+
+    /// Whether `descriptor` is a method descriptor this symbol can name.
+    pub fn matches_descriptor(&self, descriptor: &str) -> bool {
+        match &self.parameters {
+            Some(parameters) => descriptor
+                .strip_prefix('(')
+                .and_then(|x| x.strip_prefix(parameters.as_str()))
+                .is_some_and(|x| x.starts_with(')')),
+            None => true,
+        }
+    }
+}
+
+/// Writes the symbol, e.g. `Java_com_example_Foo_bar__Ljava_lang_String_2`.
+impl std::fmt::Display for JniSymbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Java_{}_{}", mangle(&self.class), mangle(&self.method))?;
+        if let Some(parameters) = &self.parameters {
+            write!(f, "__{}", mangle(parameters))?;
+        }
+        Ok(())
+    }
+}
+
+/// Escapes `name` as one part of a JNI symbol: `/` becomes `_`, `_` `_1`,
+/// `;` `_2` and `[` `_3`, ASCII letters and digits stay, and anything else
+/// becomes `_0` and the UTF-16 code unit in lower case hex.
+pub fn mangle(name: &str) -> String {
+    let mut res = String::new();
+    for c in name.chars() {
+        match c {
+            '/' => res.push('_'),
+            '_' => res.push_str("_1"),
+            ';' => res.push_str("_2"),
+            '[' => res.push_str("_3"),
+            c if c.is_ascii_alphanumeric() => res.push(c),
+            c => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    write!(res, "_0{:04x}", unit).unwrap();
+                }
+            }
+        }
+    }
+    res
+}
+
+/// Undoes the JNI escapes in one component. In a class or method name an
+/// unescaped `_` separates components, so only escapes are expected here;
+/// inside the parameter list it stands for `/`.
+fn unescape(mangled: &str, in_signature: bool) -> Option<String> {
+    // Escapes are UTF-16 code units, so supplementary characters arrive as
+    // two `_0` escapes.
+    let mut res = Vec::new();
+    let mut chars = mangled.chars();
+    while let Some(c) = chars.next() {
+        if c != '_' {
+            res.extend(c.encode_utf16(&mut [0; 2]).iter());
+            continue;
+        }
+        let unit = match chars.next() {
+            Some('0') => {
+                let hex = chars.by_ref().take(4).collect::<String>();
+                u16::from_str_radix(&hex, 16).ok()?
+            }
+            Some('1') => b'_' as u16,
+            Some('2') => b';' as u16,
+            Some('3') => b'[' as u16,
+            Some(c) if in_signature => {
+                res.push(b'/' as u16);
+                res.extend(c.encode_utf16(&mut [0; 2]).iter());
+                continue;
+            }
+            _ => return None,
+        };
+        res.push(unit);
+    }
+    String::from_utf16(&res).ok()
+}
+
+/// Splits `mangled` at every `_` that is not the start of an escape.
+fn split_components(mangled: &str) -> Vec<&str> {
+    let bytes = mangled.as_bytes();
+    let mut res = Vec::new();
+    let mut start = 0;
+    for i in 0..bytes.len() {
+        if bytes[i] == b'_' && !matches!(bytes.get(i + 1), Some(b'0'..=b'3')) {
+            res.push(&mangled[start..i]);
+            start = i + 1;
+        }
+    }
+    res.push(&mangled[start..]);
+    res
+}
+
+/// Demangles a JNI symbol such as `Java_com_example_Foo_bar__Ljava_lang_String_2`.
+///
+/// Leading underscores and the `@N` suffix added by some platforms and
+/// calling conventions are ignored. Returns `None` if `symbol` is not a
+/// well-formed JNI name.
+pub fn demangle(symbol: &str) -> Option<JniSymbol> {
+    let symbol = symbol.trim_start_matches('_');
+    let symbol = symbol.split_once('@').map_or(symbol, |(x, _)| x);
+    let mangled = symbol.strip_prefix("Java_")?;
+    // A method name starting with an escape also follows a double underscore,
+    // but parameters never start with `_0`, `_1` or `_2`.
+    let signature = mangled
+        .match_indices("__")
+        .map(|(i, _)| i)
+        .find(|&i| !matches!(mangled.as_bytes().get(i + 2), Some(b'0'..=b'2')));
+    let (name, parameters) = match signature {
+        Some(i) => (&mangled[..i], Some(unescape(&mangled[i + 2..], true)?)),
+        None => (mangled, None),
+    };
+    let mut components = split_components(name);
+    let method = unescape(components.pop()?, false)?;
+    if components.is_empty() || method.is_empty() {
+        return None;
+    }
+    let class = components
+        .into_iter()
+        .map(|x| unescape(x, false))
+        .collect::<Option<Vec<_>>>()?
+        .join("/");
+    Some(JniSymbol {
+        class,
+        method,
+        parameters,
+    })
+}
+
+/// The JNI type of a parameter or return value of type `descriptor`, see
+/// [`TypeDescriptor::to_jni_type`], with subclasses of `java/lang/Throwable`
+/// found through `resolver` as `jthrowable` too.
+pub(crate) fn jni_type(descriptor: &TypeDescriptor, resolver: Option<&dyn ClassResolver>) -> Result<&'static str> {
+    if let (TypeDescriptor::ClassName(name), Some(resolver)) = (descriptor, resolver) {
+        if resolver.is_subtype_of(name, THROWABLE)? {
+            return Ok("jthrowable");
+        }
+    }
+    Ok(descriptor.to_jni_type())
+}
+
+/// A method a JNI symbol resolved to.
+#[derive(Debug, Clone)]
+pub struct JniTarget {
+    pub class_file: Arc<ClassFile>,
+    pub method: String,
+    pub descriptor: String,
+    pub is_native: bool,
+}
+
+/// Demangles `symbol` and finds the methods of its class, as `resolver` has
+/// it, the symbol can name: every overload for the short form, a single
+/// method for the long form. Returns an empty list if the symbol or its class
+/// is unknown.
+pub fn resolve(resolver: &(impl ClassResolver + ?Sized), symbol: &str) -> Result<Vec<JniTarget>> {
+    let symbol = match demangle(symbol) {
+        Some(x) => x,
+        None => return Ok(Vec::new()),
+    };
+    let class_file = match resolver.resolve(&symbol.class)? {
+        Some(x) => x,
+        None => return Ok(Vec::new()),
+    };
+    let mut res = Vec::new();
+    for method in class_file.methods() {
+        let descriptor = method
+            .method_inner
+            .descriptor_index
+            .get_as_string(&class_file)?;
+        if method.identifier()? == symbol.method && symbol.matches_descriptor(descriptor) {
+            res.push(JniTarget {
+                class_file: class_file.clone(),
+                method: symbol.method.clone(),
+                descriptor: descriptor.to_string(),
+                is_native: method.is_native(),
+            });
+        }
+    }
+    Ok(res)
+}
@@ -0,0 +1,197 @@
+//! C headers declaring the native methods of a class, as `javac -h` (and
+//! `javah` before it) writes them, see [`HeaderGenerator`].
+
+use std::fmt::Write;
+
+use crate::{
+    field::TypeDescriptor,
+    jni::{jni_type, mangle},
+    method::Method,
+    raw::{ConstantPoolItem, FieldAccessFlags},
+    resolver::ClassResolver,
+    ClassFile, Result,
+};
+
+/// Writes JNI headers: a `JNIEXPORT` prototype for each native method and a
+/// `#define` for each primitive constant, in an include guard and an
+/// `extern "C"` block for C++.
+///
+/// Parameters and return values of a subclass of `java/lang/Throwable` are
+/// `jthrowable`, which takes the class hierarchy, so without a
+/// [`ClassResolver`] only `java/lang/Throwable` itself is.
+#[derive(Default, Clone, Copy)]
+pub struct HeaderGenerator<'r> {
+    resolver: Option<&'r dyn ClassResolver>,
+}
+
+impl std::fmt::Debug for HeaderGenerator<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeaderGenerator")
+            .field("resolver", &self.resolver.is_some())
+            .finish()
+    }
+}
+
+/// `name` as C identifiers built from class and field names take it: `/`,
+/// `.` and `_` become `_`, `$` becomes `__`, and other characters that are
+/// not ASCII letters or digits an `_0` escape of each UTF-16 code unit.
+pub(crate) fn c_name(name: &str) -> String {
+    let mut res = String::new();
+    for c in name.chars() {
+        match c {
+            '/' | '.' | '_' => res.push('_'),
+            '$' => res.push_str("__"),
+            c if c.is_ascii_alphanumeric() => res.push(c),
+            c => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    write!(res, "_0{:04x}", unit).unwrap();
+                }
+            }
+        }
+    }
+    res
+}
+
+/// The name of `class_file` with `.` rather than `$` between a member class
+/// and the class declaring it, as the `InnerClasses` attribute tells them
+/// apart from a `$` in a name, e.g. `com/example/Outer.Inner`.
+fn nested_name(class_file: &ClassFile) -> Result<String> {
+    let mut name = class_file.this_class()?.to_string();
+    let inner_classes = match class_file.inner_classes()? {
+        Some(x) => x.classes()?,
+        None => return Ok(name),
+    };
+    let mut suffix = String::new();
+    // Guard against cycles in malformed input.
+    for _ in 0..inner_classes.len() {
+        let entry = inner_classes.iter().find(|x| x.inner_class == name);
+        let (outer, inner_name) = match entry.and_then(|x| x.outer_class.zip(x.inner_name)) {
+            Some(x) => x,
+            None => break,
+        };
+        if name.strip_prefix(outer).and_then(|x| x.strip_prefix('$')) != Some(inner_name) {
+            break;
+        }
+        suffix = format!(".{}{}", inner_name, suffix);
+        name = outer.to_string();
+    }
+    Ok(name + &suffix)
+}
+
+/// A C literal for a constant of type `descriptor`, formatted as `javac -h`
+/// does: integral types with an `L` suffix, `long` with `LL` and `float` with
+/// `f`. Infinities and NaN, which have no literal, are written as divisions.
+fn constant_literal(descriptor: &TypeDescriptor, item: &ConstantPoolItem) -> Option<String> {
+    let floating = |value: f64, suffix: &str| {
+        if value.is_nan() {
+            format!("(0.0{0}/0.0{0})", suffix)
+        } else if value.is_infinite() {
+            format!("({}1.0{1}/0.0{1})", if value < 0.0 { "-" } else { "" }, suffix)
+        } else {
+            format!("{:?}{}", value, suffix)
+        }
+    };
+    Some(match (descriptor, item) {
+        (
+            TypeDescriptor::Boolean
+            | TypeDescriptor::Byte
+            | TypeDescriptor::Char
+            | TypeDescriptor::Short
+            | TypeDescriptor::Int,
+            ConstantPoolItem::Integer { value },
+        ) => format!("{}L", value),
+        (TypeDescriptor::Long, ConstantPoolItem::Long { value }) => format!("{}LL", value),
+        (TypeDescriptor::Float, ConstantPoolItem::Float { value }) => match value.is_finite() {
+            true => format!("{:?}f", value),
+            false => floating(*value as f64, "f"),
+        },
+        (TypeDescriptor::Double, ConstantPoolItem::Double { value }) => floating(*value, ""),
+        _ => return None,
+    })
+}
+
+impl<'r> HeaderGenerator<'r> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks up classes through `resolver` to tell which are throwable.
+    pub fn with_resolver(mut self, resolver: &'r dyn ClassResolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Whether `class_file` has native methods, which is when `javac -h`
+    /// writes a header for it.
+    pub fn needs_header(class_file: &ClassFile) -> bool {
+        class_file.methods().iter().any(|x| x.is_native())
+    }
+
+    /// The name `javac -h` gives the header of `class_file`, e.g.
+    /// `com_example_Foo.h`. Unlike the names in the header, every `$` becomes
+    /// a single `_`.
+    pub fn file_name(class_file: &ClassFile) -> Result<String> {
+        Ok(format!("{}.h", c_name(&class_file.this_class()?.replace('$', "."))))
+    }
+
+    /// The declaration of native `method`, with the comment naming it.
+    fn prototype(&self, out: &mut String, class: &str, method: &Method) -> Result<()> {
+        let descriptor = method.descriptor()?;
+        let raw_descriptor = method.method_inner.descriptor_index.get_as_string(method.class_file)?;
+        let return_type = match descriptor.return_type() {
+            Some(x) => jni_type(x, self.resolver)?,
+            None => "void",
+        };
+        let mut parameters = vec!["JNIEnv *", if method.is_static() { "jclass" } else { "jobject" }];
+        for x in descriptor.parameter_types() {
+            parameters.push(jni_type(x, self.resolver)?);
+        }
+        writeln!(out, "/*").unwrap();
+        writeln!(out, " * Class:     {}", class).unwrap();
+        writeln!(out, " * Method:    {}", mangle(method.identifier()?)).unwrap();
+        writeln!(out, " * Signature: {}", raw_descriptor).unwrap();
+        writeln!(out, " */").unwrap();
+        writeln!(out, "JNIEXPORT {} JNICALL {}", return_type, method.jni_symbol()?).unwrap();
+        writeln!(out, "  ({});", parameters.join(", ")).unwrap();
+        writeln!(out).unwrap();
+        Ok(())
+    }
+
+    /// The header for `class_file`, laid out as `javac -h` lays it out. A
+    /// class without native methods gets one with only its constants.
+    pub fn header(&self, class_file: &ClassFile) -> Result<String> {
+        let class = c_name(&nested_name(class_file)?);
+        let mut out = String::new();
+        writeln!(out, "/* DO NOT EDIT THIS FILE - it is machine generated */").unwrap();
+        writeln!(out, "#include <jni.h>").unwrap();
+        writeln!(out, "/* Header for class {} */", class).unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "#ifndef _Included_{}", class).unwrap();
+        writeln!(out, "#define _Included_{}", class).unwrap();
+        writeln!(out, "#ifdef __cplusplus").unwrap();
+        writeln!(out, "extern \"C\" {{").unwrap();
+        writeln!(out, "#endif").unwrap();
+        for field in class_file.fields() {
+            if !field.field_inner.access_flags.contains(FieldAccessFlags::STATIC | FieldAccessFlags::FINAL) {
+                continue;
+            }
+            let item = match field.constant_value()?.and_then(|x| x.item()) {
+                Some(x) => x,
+                None => continue,
+            };
+            if let Some(literal) = constant_literal(&field.descriptor()?, item) {
+                let name = format!("{}_{}", class, c_name(field.identifier()?));
+                writeln!(out, "#undef {}", name).unwrap();
+                writeln!(out, "#define {} {}", name, literal).unwrap();
+            }
+        }
+        for method in class_file.methods().into_iter().filter(|x| x.is_native()) {
+            self.prototype(&mut out, &class, &method)?;
+        }
+        writeln!(out, "#ifdef __cplusplus").unwrap();
+        writeln!(out, "}}").unwrap();
+        writeln!(out, "#endif").unwrap();
+        writeln!(out, "#endif").unwrap();
+        Ok(out)
+    }
+}
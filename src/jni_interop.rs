@@ -0,0 +1,144 @@
+//! Conversions into the types of the [`jni`] crate, enabled by the `jni`
+//! feature, so code calling into a JVM can take its signatures from parsed
+//! class files rather than strings written by hand.
+//!
+//! [`JniMethod`] and [`JniField`] carry what `GetMethodID`, `GetFieldID` and
+//! their static forms take, and look the IDs up through a [`JNIEnv`].
+
+use jni::{
+    objects::{JFieldID, JMethodID, JStaticFieldID, JStaticMethodID},
+    signature::{JavaType, Primitive, ReturnType, TypeSignature},
+    JNIEnv,
+};
+
+use crate::{
+    field::{Field, TypeDescriptor},
+    method::{Method, MethodDescriptor},
+    Result,
+};
+
+impl From<&TypeDescriptor<'_>> for JavaType {
+    fn from(value: &TypeDescriptor<'_>) -> Self {
+        match value {
+            TypeDescriptor::Boolean => JavaType::Primitive(Primitive::Boolean),
+            TypeDescriptor::Byte => JavaType::Primitive(Primitive::Byte),
+            TypeDescriptor::Char => JavaType::Primitive(Primitive::Char),
+            TypeDescriptor::Short => JavaType::Primitive(Primitive::Short),
+            TypeDescriptor::Int => JavaType::Primitive(Primitive::Int),
+            TypeDescriptor::Long => JavaType::Primitive(Primitive::Long),
+            TypeDescriptor::Float => JavaType::Primitive(Primitive::Float),
+            TypeDescriptor::Double => JavaType::Primitive(Primitive::Double),
+            TypeDescriptor::String => JavaType::Object("java/lang/String".to_string()),
+            TypeDescriptor::Class => JavaType::Object("java/lang/Class".to_string()),
+            TypeDescriptor::Array(x) => JavaType::Array(Box::new(x.as_ref().into())),
+            TypeDescriptor::ClassName(x) => JavaType::Object(x.to_string()),
+        }
+    }
+}
+
+impl From<&MethodDescriptor<'_>> for TypeSignature {
+    fn from(value: &MethodDescriptor<'_>) -> Self {
+        TypeSignature {
+            args: value.parameter_types().iter().map(JavaType::from).collect(),
+            ret: value.jni_return_type(),
+        }
+    }
+}
+
+impl TypeDescriptor<'_> {
+    /// The kind of value a method returning this type gives, as the `jni`
+    /// crate's unchecked calls take it.
+    pub fn jni_return_type(&self) -> ReturnType {
+        match JavaType::from(self) {
+            JavaType::Primitive(x) => ReturnType::Primitive(x),
+            JavaType::Array(_) => ReturnType::Array,
+            _ => ReturnType::Object,
+        }
+    }
+}
+
+impl MethodDescriptor<'_> {
+    /// The kind of value the method returns, as the `jni` crate's unchecked
+    /// calls take it.
+    pub fn jni_return_type(&self) -> ReturnType {
+        match self.return_type() {
+            Some(x) => x.jni_return_type(),
+            None => ReturnType::Primitive(Primitive::Void),
+        }
+    }
+}
+
+/// A method as JNI names it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JniMethod {
+    /// The internal name of the declaring class, as `FindClass` takes it.
+    pub class: String,
+    pub name: String,
+    /// The method descriptor, as `GetMethodID` takes it, e.g.
+    /// `(Ljava/lang/String;)V`.
+    pub descriptor: String,
+    /// The descriptor parsed, as the `jni` crate's checked calls take it. Its
+    /// return type does not keep the class of an object.
+    pub signature: TypeSignature,
+    pub is_static: bool,
+}
+
+impl JniMethod {
+    pub fn from_method(method: &Method) -> Result<Self> {
+        let descriptor = method.descriptor()?;
+        Ok(Self {
+            class: method.class_file.this_class()?.to_string(),
+            name: method.identifier()?.to_string(),
+            descriptor: descriptor.to_descriptor_string(),
+            signature: TypeSignature::from(&descriptor),
+            is_static: method.is_static(),
+        })
+    }
+
+    /// Looks up the ID of the method, which must not be static.
+    pub fn method_id(&self, env: &mut JNIEnv) -> jni::errors::Result<JMethodID> {
+        env.get_method_id(&self.class, &self.name, &self.descriptor)
+    }
+
+    /// Looks up the ID of the method, which must be static.
+    pub fn static_method_id(&self, env: &mut JNIEnv) -> jni::errors::Result<JStaticMethodID> {
+        env.get_static_method_id(&self.class, &self.name, &self.descriptor)
+    }
+}
+
+/// A field as JNI names it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JniField {
+    /// The internal name of the declaring class, as `FindClass` takes it.
+    pub class: String,
+    pub name: String,
+    pub ty: JavaType,
+    pub is_static: bool,
+}
+
+impl JniField {
+    pub fn from_field(field: &Field) -> Result<Self> {
+        Ok(Self {
+            class: field.class_file.this_class()?.to_string(),
+            name: field.identifier()?.to_string(),
+            ty: JavaType::from(&field.descriptor()?),
+            is_static: field.is_static(),
+        })
+    }
+
+    /// The field descriptor, as `GetFieldID` takes it, e.g.
+    /// `Ljava/lang/String;`.
+    pub fn descriptor(&self) -> String {
+        self.ty.to_string()
+    }
+
+    /// Looks up the ID of the field, which must not be static.
+    pub fn field_id(&self, env: &mut JNIEnv) -> jni::errors::Result<JFieldID> {
+        env.get_field_id(&self.class, &self.name, self.descriptor())
+    }
+
+    /// Looks up the ID of the field, which must be static.
+    pub fn static_field_id(&self, env: &mut JNIEnv) -> jni::errors::Result<JStaticFieldID> {
+        env.get_static_field_id(&self.class, &self.name, self.descriptor())
+    }
+}
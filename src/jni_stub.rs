@@ -0,0 +1,139 @@
+//! Rust functions implementing the native methods of a class, to fill in and
+//! build into a `cdylib`, see [`RustStubGenerator`].
+
+use std::fmt::Write;
+
+use crate::{jni::jni_type, method::Method, resolver::ClassResolver, ClassFile, Result};
+
+/// The width past which a signature is broken onto a line per parameter, as
+/// rustfmt does.
+const MAX_WIDTH: usize = 100;
+
+/// Writes a `#[no_mangle] pub extern "system" fn` for each native method,
+/// named with its JNI symbol as [`Method::jni_symbol`] gives it, taking and
+/// returning the raw JNI types of the `jni-sys` crate, with a `todo!()` body.
+///
+/// Parameters and return values of a subclass of `java/lang/Throwable` are
+/// `jthrowable`, which takes the class hierarchy, so without a
+/// [`ClassResolver`] only `java/lang/Throwable` itself is.
+#[derive(Clone)]
+pub struct RustStubGenerator<'r> {
+    resolver: Option<&'r dyn ClassResolver>,
+    sys_crate: String,
+    unsafe_attributes: bool,
+}
+
+impl Default for RustStubGenerator<'_> {
+    fn default() -> Self {
+        Self {
+            resolver: None,
+            sys_crate: "jni_sys".to_string(),
+            unsafe_attributes: false,
+        }
+    }
+}
+
+impl std::fmt::Debug for RustStubGenerator<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RustStubGenerator")
+            .field("resolver", &self.resolver.is_some())
+            .field("sys_crate", &self.sys_crate)
+            .field("unsafe_attributes", &self.unsafe_attributes)
+            .finish()
+    }
+}
+
+impl<'r> RustStubGenerator<'r> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks up classes through `resolver` to tell which are throwable.
+    pub fn with_resolver(mut self, resolver: &'r dyn ClassResolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Imports the JNI types from `path` rather than `jni_sys`, e.g.
+    /// `jni::sys` for the `jni` crate's re-export.
+    pub fn with_sys_crate(mut self, path: &str) -> Self {
+        self.sys_crate = path.to_string();
+        self
+    }
+
+    /// Whether to write `#[unsafe(no_mangle)]`, which edition 2024 requires
+    /// and Rust 1.82 and later take in any edition, rather than
+    /// `#[no_mangle]`.
+    pub fn with_unsafe_attributes(mut self, unsafe_attributes: bool) -> Self {
+        self.unsafe_attributes = unsafe_attributes;
+        self
+    }
+
+    /// The function for native `method`, with a doc comment naming it.
+    pub fn stub(&self, method: &Method) -> Result<String> {
+        let descriptor = method.descriptor()?;
+        let raw_descriptor = method.method_inner.descriptor_index.get_as_string(method.class_file)?;
+        let mut parameters = vec![
+            "env: *mut JNIEnv".to_string(),
+            match method.is_static() {
+                true => "class: jclass".to_string(),
+                false => "this: jobject".to_string(),
+            },
+        ];
+        for (i, x) in descriptor.parameter_types().iter().enumerate() {
+            parameters.push(format!("arg{}: {}", i, jni_type(x, self.resolver)?));
+        }
+        let return_type = match descriptor.return_type() {
+            Some(x) => format!(" -> {}", jni_type(x, self.resolver)?),
+            None => String::new(),
+        };
+        let name = method.jni_symbol()?;
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "/// `{}.{}{}`",
+            method.class_file.this_class()?,
+            method.identifier()?,
+            raw_descriptor
+        )
+        .unwrap();
+        match self.unsafe_attributes {
+            true => writeln!(out, "#[unsafe(no_mangle)]").unwrap(),
+            false => writeln!(out, "#[no_mangle]").unwrap(),
+        }
+        writeln!(out, "#[allow(non_snake_case, unused_variables)]").unwrap();
+        let line = format!(
+            "pub extern \"system\" fn {}({}){} {{",
+            name,
+            parameters.join(", "),
+            return_type
+        );
+        if line.len() <= MAX_WIDTH {
+            writeln!(out, "{}", line).unwrap();
+        } else {
+            writeln!(out, "pub extern \"system\" fn {}(", name).unwrap();
+            for x in &parameters {
+                writeln!(out, "    {},", x).unwrap();
+            }
+            writeln!(out, "){} {{", return_type).unwrap();
+        }
+        writeln!(out, "    todo!()").unwrap();
+        writeln!(out, "}}").unwrap();
+        Ok(out)
+    }
+
+    /// A source file with the functions for every native method of
+    /// `class_file`, in file order, after a `use` of the JNI types.
+    pub fn stubs(&self, class_file: &ClassFile) -> Result<String> {
+        let mut out = String::new();
+        writeln!(out, "//! Native methods of `{}`.", class_file.this_class()?).unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "use {}::*;", self.sys_crate).unwrap();
+        for method in class_file.methods().into_iter().filter(|x| x.is_native()) {
+            writeln!(out).unwrap();
+            out.push_str(&self.stub(&method)?);
+        }
+        Ok(out)
+    }
+}
@@ -1,29 +1,105 @@
-use std::fmt::Debug;
+use std::{cell::RefCell, fmt::Debug, sync::Arc, time::Instant};
 
-use attributes::{BootstrapMethods, EnclosingMethod, InnerClasses, Signature, SourceFile};
+use attributes::{
+    Annotation, Attribute, AttributeLocation, KnownAttribute, BootstrapMethods, EnclosingMethod, InnerClassInfo, InnerClasses, Module, ModuleDescriptor, ModuleMainClass, ModulePackages,
+    PermittedSubclasses, Record, RecordComponent, RuntimeInvisibleAnnotations, RuntimeVisibleAnnotations, Signature, SourceFile,
+};
 use binrw::{binread, BinRead};
 
 pub mod error;
+pub mod options;
 pub(crate) mod raw;
 pub mod field;
 pub mod method;
 pub mod attributes;
 pub mod signature;
 pub mod instruction;
+pub mod interpreter;
+pub mod fingerprint;
+pub mod dependencies;
+pub mod call_graph;
+pub mod api_scan;
+pub mod annotation_scan;
+pub mod code_index;
+pub mod class_index;
+pub mod method_metrics;
+pub mod cfg;
+pub mod dataflow;
+pub mod analyzer;
+pub mod def_use;
+pub mod switches;
+pub mod verifier;
+pub mod validate;
+pub mod repository;
+pub mod resolver;
+pub mod resolution;
+pub mod hierarchy;
+pub mod multi_release;
+pub mod archive;
+pub mod jimage;
+pub mod jni;
+pub mod jni_header;
+pub mod jni_stub;
+pub mod java_stub;
+pub mod events;
+pub mod mutf8;
+pub mod metrics;
+pub mod span;
+pub mod hexdump;
+pub mod builder;
+pub mod asm;
+pub mod visitor;
+pub mod text;
+pub mod disassemble;
+pub mod pool_stats;
+pub mod bytecode_stats;
+pub mod remap;
+pub mod owned;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "jni")]
+pub mod jni_interop;
+#[cfg(feature = "generator")]
+pub mod generator;
+#[cfg(feature = "generator")]
+pub mod round_trip;
+#[cfg(feature = "ssa")]
+pub mod ssa;
+#[cfg(feature = "report")]
+pub mod report;
+mod trace;
+mod descriptor_cache;
+mod writer;
+mod encode;
+mod canonical;
+mod inflate;
 
 pub use error::{Result, Error};
+pub use span::Span;
+pub use text::parse_assembly;
+pub use options::{AttributeFilter, CustomConstant, Leniency, ParseOptions, ParseWarning, VersionPolicy};
+pub use raw::{BootstrapMethodAttrInfo, ClassAccessFlags, ConstantPoolItem, FieldAccessFlags, MethodAccessFlags, Reference};
+pub use raw::{
+    ClassIndex, DoubleIndex, DynamicIndex, FieldrefIndex, FloatIndex, IntegerIndex,
+    InterfaceMethodrefIndex, InvokeDynamicIndex, LongIndex, MethodHandleIndex, MethodTypeIndex,
+    MethodrefIndex, ModuleIndex, NameAndTypeIndex, PackageIndex, StringIndex, Utf8Index,
+};
 use field::Field;
 use method::Method;
-use raw::{Attributes, ClassAccessFlags, ClassIndex, FieldRaw, MethodRaw};
+use raw::{Attributes, FieldRaw, MethodRaw};
+use hexdump::RawAttribute;
+use descriptor_cache::DescriptorCache;
+use metrics::{Metrics, Phase};
 use signature::ClassSignature;
 
 #[binread]
-#[br(big, magic = b"\xca\xfe\xba\xbe")]
+#[br(big, magic = b"\xca\xfe\xba\xbe", import(options: &ParseOptions, warnings: &RefCell<Vec<ParseWarning>>))]
 pub struct ClassFile {
     _minor_version: u16,
     _major_version: u16,
+    #[br(args(options, Some(warnings)))]
     constant_pool: raw::ConstantPool,
-    #[br(map = |x: u16| ClassAccessFlags::from_bits_truncate(x))]
+    #[br(map = |x: u16| ClassAccessFlags::from_bits_retain(x))]
     access_flags: ClassAccessFlags,
     this_class: ClassIndex,
     super_class: ClassIndex,
@@ -33,29 +109,28 @@ pub struct ClassFile {
     interfaces: Vec<ClassIndex>,
     #[br(temp)]
     fields_count: u16,
-    #[br(args { count: fields_count.into(), inner: (&constant_pool,) })]
+    #[br(parse_with = raw::read_members, args(fields_count, &constant_pool, options.leniency, Some(warnings), options.attribute_filter.as_deref()))]
     fields: Vec<FieldRaw>,
     #[br(temp)]
     methods_count: u16,
-    #[br(args { count: methods_count.into(), inner: (&constant_pool,) })]
+    #[br(parse_with = raw::read_members, args(methods_count, &constant_pool, options.leniency, Some(warnings), options.attribute_filter.as_deref()))]
     methods: Vec<MethodRaw>,
-    #[br(args(&constant_pool,))]
+    #[br(args(&constant_pool, options.leniency, Some(warnings), options.attribute_filter.as_deref()))]
     attributes: Attributes,
-}
-
-macro_rules! attribute {
-    ($strct:ident, $name:ident) => {
-        pub fn $name<'a>(&'a self) -> Result<Option<$strct<'a>>> {
-            match self.attributes.0.get(stringify!($strct)) {
-                Some(x) => {
-                    let mut buf = std::io::Cursor::new(&x[..]);
-                    let value = $strct::read_be_args(&mut buf, (self,))?;
-                    Ok(Some(value))
-                }
-                None => Ok(None),
-            }
-        }
-    };
+    #[br(temp, parse_with = raw::stream_position)]
+    end: u64,
+    #[br(calc = Span::new(0, end))]
+    span: Span,
+    #[br(calc = options.leniency)]
+    leniency: Leniency,
+    #[br(calc = options.metrics.clone())]
+    metrics: Option<Arc<dyn Metrics>>,
+    #[br(calc = options.attribute_filter.clone())]
+    attribute_filter: Option<Arc<AttributeFilter>>,
+    #[br(calc = Vec::new())]
+    warnings: Vec<ParseWarning>,
+    #[br(calc = DescriptorCache::new(constant_pool.0.len()))]
+    descriptors: DescriptorCache,
 }
 
 impl ClassFile {
@@ -63,7 +138,147 @@ impl ClassFile {
     where
         std::io::Cursor<T>: std::io::Read + std::io::Seek,
     {
-        Ok(Self::read_be(&mut std::io::Cursor::new(t))?)
+        Self::parse_with_options(t, &ParseOptions::default())
+    }
+
+    pub fn parse_with_options<T>(t: T, options: &ParseOptions) -> Result<Self>
+    where
+        std::io::Cursor<T>: std::io::Read + std::io::Seek,
+    {
+        let warnings = RefCell::new(Vec::new());
+        let mut reader = std::io::Cursor::new(t);
+        trace::span!(DEBUG, "class");
+        let start = Instant::now();
+        let mut class_file = match Self::read_be_args(&mut reader, (options, &warnings)) {
+            Ok(x) => x,
+            Err(e) => {
+                trace::event!(DEBUG, offset = reader.position(), error = %e, "class parse failed");
+                if let Some(metrics) = &options.metrics {
+                    metrics.error(Phase::Parse);
+                }
+                return Err(e.into());
+            }
+        };
+        trace::event!(DEBUG, this_class = ?class_file.this_class(), bytes = reader.position(), "parsed class");
+        class_file.warnings = warnings.into_inner();
+        let (major, minor) = (class_file._major_version, class_file._minor_version);
+        if !(options::MIN_MAJOR_VERSION..=options::MAX_KNOWN_MAJOR_VERSION).contains(&major) {
+            match options.version_policy {
+                VersionPolicy::Warn => class_file
+                    .warnings
+                    .push(ParseWarning::UnknownVersion { major, minor }),
+                VersionPolicy::Reject => {
+                    if let Some(metrics) = &options.metrics {
+                        metrics.error(Phase::Parse);
+                    }
+                    return Err(Error::UnsupportedVersion { major, minor });
+                }
+            }
+        }
+        if options.validate_attributes {
+            if let Err(e) = class_file.validate_attributes() {
+                if let Some(metrics) = &options.metrics {
+                    metrics.error(Phase::Parse);
+                }
+                return Err(e);
+            }
+        }
+        if let Some(metrics) = &options.metrics {
+            metrics.class_parsed(reader.position());
+            metrics.phase_finished(Phase::Parse, start.elapsed());
+        }
+        Ok(class_file)
+    }
+
+    pub fn major_version(&self) -> u16 {
+        self._major_version
+    }
+
+    pub fn minor_version(&self) -> u16 {
+        self._minor_version
+    }
+
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// The span of the whole class, from the magic number to the last attribute.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Every constant pool entry with its index, starting at 1. The unusable
+    /// slot after each `Long` and `Double` is left out.
+    pub fn constant_pool(&self) -> impl Iterator<Item = (u16, &ConstantPoolItem)> {
+        self.constant_pool
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| !matches!(x, ConstantPoolItem::Skip))
+            .map(|(i, x)| (i as u16 + 1, x))
+    }
+
+    /// The constant pool entry at `index`, or `None` if no entry starts there.
+    pub fn constant(&self, index: u16) -> Option<&ConstantPoolItem> {
+        (index as usize)
+            .checked_sub(1)
+            .and_then(|x| self.constant_pool.0.get(x))
+            .filter(|x| !matches!(x, ConstantPoolItem::Skip))
+    }
+
+    /// The span of the constant pool entry at `index`.
+    pub fn constant_span(&self, index: u16) -> Option<Span> {
+        (index as usize)
+            .checked_sub(1)
+            .and_then(|x| self.constant_pool.1.get(x))
+            .copied()
+    }
+
+    pub fn attribute_span(&self, name: &str) -> Option<Span> {
+        self.attributes.span(name)
+    }
+
+    pub fn undecoded_attributes<'a>(&'a self) -> Vec<RawAttribute<'a>> {
+        self.attributes.undecoded(self)
+    }
+
+    /// Every attribute as raw bytes, duplicates included, in the order they
+    /// appear in the file.
+    pub fn raw_attributes<'a>(&'a self) -> Vec<RawAttribute<'a>> {
+        self.attributes.raw(self)
+    }
+
+    /// Every class attribute, decoded, in the order they appear in the file.
+    pub fn attributes<'a>(&'a self) -> Result<Vec<Attribute<'a>>> {
+        self.attributes.typed(self, AttributeLocation::Class)
+    }
+
+    /// Decodes the attributes of the class, its members and their `Code`,
+    /// failing on the first that is malformed.
+    fn validate_attributes(&self) -> Result<()> {
+        self.attributes()?;
+        for field in self.fields() {
+            field.attributes()?;
+        }
+        for method in self.methods() {
+            for attribute in method.attributes()? {
+                if let Attribute::Code(code) = attribute {
+                    code.attributes()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The `CONSTANT_Utf8` at `index`, with surrogate pairs combined.
+    pub fn utf8_constant(&self, index: u16) -> Result<&str> {
+        Utf8Index(index).get_as_string(self)
+    }
+
+    /// The `CONSTANT_Utf8` at `index` as the UTF-16 code units Java sees,
+    /// including any unpaired surrogates.
+    pub fn utf16_constant(&self, index: u16) -> Result<Vec<u16>> {
+        Utf8Index(index).get_as_utf16_impl(&self.constant_pool)
     }
 
     pub fn this_class<'a>(&'a self) -> Result<&'a str> {
@@ -101,24 +316,205 @@ impl ClassFile {
             .collect()
     }
 
-    attribute!(InnerClasses, inner_classes);
-    attribute!(EnclosingMethod, enclosing_method);
-    attribute!(SourceFile, source_file);
-    attribute!(BootstrapMethods, bootstrap_methods);
+    /// Decodes the last class attribute called `T::NAME`.
+    pub fn get_attribute<'a, T: KnownAttribute<'a>>(&'a self) -> Result<Option<T>> {
+        self.attributes.decode(self)
+    }
 
-    pub fn signature<'a>(&'a self) -> crate::Result<Option<ClassSignature<'a>>> {
-        match self.attributes.0.get("Signature") {
-            Some(x) => {
-                let mut buf = std::io::Cursor::new(&x[..]);
-                let value = Signature::read_be_args(&mut buf, (self,))?;
-                Ok(Some(value.get_class()?))
+    pub fn inner_classes<'a>(&'a self) -> Result<Option<InnerClasses<'a>>> {
+        self.get_attribute()
+    }
+
+    pub fn enclosing_method<'a>(&'a self) -> Result<Option<EnclosingMethod<'a>>> {
+        self.get_attribute()
+    }
+
+    pub fn source_file<'a>(&'a self) -> Result<Option<SourceFile<'a>>> {
+        self.get_attribute()
+    }
+
+    pub fn bootstrap_methods<'a>(&'a self) -> Result<Option<BootstrapMethods<'a>>> {
+        self.get_attribute()
+    }
+
+    pub fn module_packages<'a>(&'a self) -> Result<Option<ModulePackages<'a>>> {
+        self.get_attribute()
+    }
+
+    pub fn module_main_class<'a>(&'a self) -> Result<Option<ModuleMainClass<'a>>> {
+        self.get_attribute()
+    }
+
+    /// The class this one is declared in as a member, from its own
+    /// `InnerClasses` entry. `None` for top level, local and anonymous classes,
+    /// whose origin is given by [`Self::enclosing_method`] instead.
+    pub fn enclosing_class(&self) -> Result<Option<&str>> {
+        let this_class = self.this_class()?;
+        Ok(match self.inner_classes()? {
+            Some(x) => x
+                .classes()?
+                .into_iter()
+                .find(|x| x.inner_class == this_class)
+                .and_then(|x| x.outer_class),
+            None => None,
+        })
+    }
+
+    /// The member classes declared by this class, as listed in its
+    /// `InnerClasses` attribute.
+    pub fn declared_inner_classes<'a>(&'a self) -> Result<Vec<InnerClassInfo<'a>>> {
+        let this_class = self.this_class()?;
+        Ok(match self.inner_classes()? {
+            Some(x) => x
+                .classes()?
+                .into_iter()
+                .filter(|x| x.outer_class == Some(this_class))
+                .collect(),
+            None => Vec::new(),
+        })
+    }
+
+    /// The classes allowed to extend or implement this one, or `None` if it is
+    /// not sealed.
+    pub fn permitted_subclasses(&self) -> Result<Option<Vec<&str>>> {
+        match self.get_attribute::<PermittedSubclasses>()? {
+            Some(value) => Ok(Some(value.class_names()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The components of a record class, from its `Record` attribute, or
+    /// `None` if it is not a record.
+    pub fn record_components<'a>(&'a self) -> Result<Option<Vec<RecordComponent<'a>>>> {
+        Ok(self.get_attribute::<Record>()?.map(Record::components))
+    }
+
+    /// The canonical constructor of a record class, which takes the
+    /// components in order, or `None` if it is not a record or has none.
+    pub fn record_canonical_constructor<'a>(&'a self) -> Result<Option<Method<'a>>> {
+        let components = match self.record_components()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let mut descriptor = String::from("(");
+        for x in &components {
+            descriptor.push_str(&x.descriptor()?.to_descriptor_string());
+        }
+        descriptor.push_str(")V");
+        for method in self.methods() {
+            if method.identifier()? == "<init>"
+                && method.method_inner.descriptor_index.get_as_string(self)? == descriptor
+            {
+                return Ok(Some(method));
             }
+        }
+        Ok(None)
+    }
+
+    /// The head of the declaration of a record class as Java source writes
+    /// it, with simple class names, e.g.
+    /// `record Pair<A, B>(A first, B... rest) implements Serializable`, or
+    /// `None` if it is not a record. Component types are generic where the
+    /// components or their accessors have signatures, and the last is
+    /// variable arity if the canonical constructor is.
+    pub fn record_declaration(&self) -> Result<Option<String>> {
+        let components = match self.record_components()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let this_class = self.this_class()?;
+        let name = match self.inner_classes()? {
+            Some(x) => x.classes()?.iter().find(|x| x.inner_class == this_class).and_then(|x| x.inner_name),
+            None => None,
+        };
+        let name = name.unwrap_or_else(|| this_class.rsplit('/').next().unwrap_or(this_class));
+        let signature = self.signature()?;
+        let mut res = format!("record {}", name);
+        if let Some(x) = &signature {
+            res.push_str(&signature::type_parameters_to_java(&x.type_parameters));
+        }
+
+        let varargs = match self.record_canonical_constructor()? {
+            Some(x) => x.method_inner.access_flags.contains(MethodAccessFlags::VARARGS),
+            None => false,
+        };
+        let mut parameters = Vec::new();
+        for (i, x) in components.iter().enumerate() {
+            let mut parameter = String::new();
+            for annotation in x.annotations()?.iter().chain(&x.invisible_annotations()?) {
+                parameter.push_str(&format!("{} ", annotation));
+            }
+            let mut ty = match x.generic_type()? {
+                Some(x) => x.to_string(),
+                None => x.descriptor()?.to_java_type_with(&mut attributes::simple_class_name),
+            };
+            if varargs && i + 1 == components.len() && ty.ends_with("[]") {
+                ty.truncate(ty.len() - 2);
+                ty.push_str("...");
+            }
+            parameter.push_str(&format!("{} {}", ty, x.identifier()?));
+            parameters.push(parameter);
+        }
+        res.push_str(&format!("({})", parameters.join(", ")));
+
+        let interfaces = match &signature {
+            Some(x) => x.superinterface_signatures.iter().map(ToString::to_string).collect(),
+            None => self.interfaces()?.into_iter().map(attributes::simple_class_name).collect::<Vec<_>>(),
+        };
+        if !interfaces.is_empty() {
+            res.push_str(&format!(" implements {}", interfaces.join(", ")));
+        }
+        Ok(Some(res))
+    }
+
+    /// The module declared by a `module-info.class`, combining its `Module`,
+    /// `ModulePackages` and `ModuleMainClass` attributes.
+    pub fn module<'a>(&'a self) -> Result<Option<ModuleDescriptor<'a>>> {
+        let mut descriptor = match self.get_attribute::<Module>()? {
+            Some(x) => x.descriptor()?,
+            None => return Ok(None),
+        };
+        if let Some(x) = self.module_packages()? {
+            descriptor.packages = x.packages()?;
+        }
+        if let Some(x) = self.module_main_class()? {
+            descriptor.main_class = Some(x.get()?);
+        }
+        Ok(Some(descriptor))
+    }
+
+    pub fn signature<'a>(&'a self) -> crate::Result<Option<ClassSignature<'a>>> {
+        match self.get_attribute::<Signature>()? {
+            Some(value) => Ok(Some(value.get_class()?)),
             None => Ok(None),
         }
     }
 
+    /// The annotations in the `RuntimeVisibleAnnotations` attribute, if any.
+    pub fn annotations<'a>(&'a self) -> crate::Result<Vec<Annotation<'a>>> {
+        match self.get_attribute::<RuntimeVisibleAnnotations>()? {
+            Some(value) => Ok(value.annotations()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The annotations in the `RuntimeInvisibleAnnotations` attribute, which
+    /// compilers keep for `CLASS` retention, if any.
+    pub fn invisible_annotations<'a>(&'a self) -> crate::Result<Vec<Annotation<'a>>> {
+        match self.get_attribute::<RuntimeInvisibleAnnotations>()? {
+            Some(value) => Ok(value.annotations()),
+            None => Ok(Vec::new()),
+        }
+    }
+
     pub fn is_deprecated(&self) -> bool {
-        self.attributes.0.get("Deprecated").is_some()
+        self.attributes.get("Deprecated").is_some()
+    }
+
+    /// Whether the compiler marked this as not present in the source, by the
+    /// `SYNTHETIC` flag or, as older compilers did, a `Synthetic` attribute.
+    pub fn is_synthetic(&self) -> bool {
+        self.access_flags.contains(ClassAccessFlags::SYNTHETIC) || self.attributes.get("Synthetic").is_some()
     }
 }
 
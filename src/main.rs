@@ -1,11 +1,285 @@
-
-fn main() {
-    let example = std::fs::read("example.class").unwrap();
-    let example = java_class_format::ClassFile::parse(example).unwrap();
-    for method in example.methods() {
-        println!("{}", method.identifier().unwrap());
-        if let Some(code) = method.code().unwrap() {
-            println!("{:#?}", code.instructions().unwrap());
-        }
-    }
-}
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use java_class_format::{
+    archive::Archive,
+    jimage::JImage,
+    jni_header::HeaderGenerator,
+    repository::ClassRepository,
+    resolver::{CachingResolver, ChainResolver, JImageResolver},
+    ClassFile, ParseOptions,
+};
+#[cfg(feature = "report")]
+use java_class_format::report::{ReportFormat, ReportGenerator};
+
+const HEADERS_USAGE: &str = "\
+usage: java-class-format headers [-o DIR] INPUT...
+
+Writes a JNI header for every class with native methods, as `javac -h` does.
+Each INPUT is a .class file, a directory searched recursively for them, or a
+JAR or JMOD. Headers go to DIR, the current directory by default.
+
+Classes are looked up among the inputs and, if JAVA_HOME is set, the JDK
+there, to tell which parameters are throwable.";
+
+const DISASSEMBLE_USAGE: &str = "\
+usage: java-class-format disassemble INPUT...
+
+Prints every class laid out as `javap -c -p` prints it. Each INPUT is a
+.class file, a directory searched recursively for them, or a JAR or JMOD.";
+
+#[cfg(feature = "report")]
+const REPORT_USAGE: &str = "\
+usage: java-class-format report [--markdown] [--disassembly] [-o FILE] INPUT...
+
+Writes a document describing every class, linking each class name to the
+class where it is among the inputs, as HTML or, with --markdown, Markdown.
+Each INPUT is a .class file, a directory searched recursively for them, or a
+JAR or JMOD. The document goes to FILE, standard output by default, and
+--disassembly adds the listing of each class.";
+
+fn dump() {
+    let example = std::fs::read("example.class").unwrap();
+    let example = ClassFile::parse(example).unwrap();
+    print!("{}", example.disassemble().unwrap());
+}
+
+/// Adds the `.class` files under `dir`, recursively, to `out`, sorted.
+fn class_files(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|x| x.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            class_files(&path, out)?;
+        } else if path.extension().is_some_and(|x| x == "class") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The class files of `input`, each with where it came from, or what could
+/// not be read.
+fn read_input(input: &Path) -> Vec<(String, java_class_format::Result<Vec<u8>>)> {
+    let mut res = Vec::new();
+    if input.is_dir() {
+        let mut paths = Vec::new();
+        if let Err(e) = class_files(input, &mut paths) {
+            res.push((input.display().to_string(), Err(e.into())));
+        }
+        for path in paths {
+            let data = std::fs::read(&path).map_err(Into::into);
+            res.push((path.display().to_string(), data));
+        }
+    } else if input.extension().is_some_and(|x| x == "class") {
+        res.push((input.display().to_string(), std::fs::read(input).map_err(Into::into)));
+    } else {
+        match Archive::open(input) {
+            Ok(archive) => {
+                for (_, entry) in archive.classes() {
+                    let source = format!("{}!{}", input.display(), entry.name);
+                    res.push((source, archive.read_entry(entry)));
+                }
+            }
+            Err(e) => res.push((input.display().to_string(), Err(e))),
+        }
+    }
+    res
+}
+
+fn headers(args: &[String]) -> ExitCode {
+    let mut output = PathBuf::from(".");
+    let mut inputs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match args.next() {
+                Some(x) => output = PathBuf::from(x),
+                None => {
+                    eprintln!("{} needs a directory\n\n{}", arg, HEADERS_USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", HEADERS_USAGE);
+                return ExitCode::SUCCESS;
+            }
+            x if x.starts_with('-') => {
+                eprintln!("unknown option {}\n\n{}", x, HEADERS_USAGE);
+                return ExitCode::from(2);
+            }
+            x => inputs.push(PathBuf::from(x)),
+        }
+    }
+    if inputs.is_empty() {
+        eprintln!("{}", HEADERS_USAGE);
+        return ExitCode::from(2);
+    }
+
+    let mut failed = false;
+    let options = ParseOptions::default();
+    let repository = ClassRepository::new();
+    let mut classes = Vec::new();
+    for (source, data) in inputs.iter().flat_map(|x| read_input(x)) {
+        match data.and_then(|x| repository.parse(x, &options)) {
+            Ok(x) => classes.push(x),
+            Err(e) => {
+                eprintln!("{}: {}", source, e);
+                failed = true;
+            }
+        }
+    }
+    let mut resolver = ChainResolver::new().with_resolver(repository);
+    if let Some(java_home) = std::env::var_os("JAVA_HOME") {
+        match JImage::open_java_home(&java_home) {
+            Ok(image) => resolver.push(JImageResolver::new(image)),
+            Err(e) => eprintln!("warning: no classes from JAVA_HOME: {}", e),
+        }
+    }
+    let resolver = CachingResolver::new(resolver);
+    let generator = HeaderGenerator::new().with_resolver(&resolver);
+
+    if let Err(e) = std::fs::create_dir_all(&output) {
+        eprintln!("{}: {}", output.display(), e);
+        return ExitCode::FAILURE;
+    }
+    for class_file in classes.iter().filter(|x| HeaderGenerator::needs_header(x)) {
+        let written = HeaderGenerator::file_name(class_file).and_then(|name| {
+            let header = generator.header(class_file)?;
+            std::fs::write(output.join(name), header)?;
+            Ok(())
+        });
+        if let Err(e) = written {
+            eprintln!("{}: {}", class_file.this_class().unwrap_or("?"), e);
+            failed = true;
+        }
+    }
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+fn disassemble(args: &[String]) -> ExitCode {
+    let mut inputs = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", DISASSEMBLE_USAGE);
+                return ExitCode::SUCCESS;
+            }
+            x if x.starts_with('-') => {
+                eprintln!("unknown option {}\n\n{}", x, DISASSEMBLE_USAGE);
+                return ExitCode::from(2);
+            }
+            x => inputs.push(PathBuf::from(x)),
+        }
+    }
+    if inputs.is_empty() {
+        eprintln!("{}", DISASSEMBLE_USAGE);
+        return ExitCode::from(2);
+    }
+
+    let mut failed = false;
+    let options = ParseOptions::default();
+    for (source, data) in inputs.iter().flat_map(|x| read_input(x)) {
+        let text = data
+            .and_then(|x| ClassFile::parse_with_options(x, &options))
+            .and_then(|x| x.disassemble());
+        match text {
+            Ok(x) => print!("{}", x),
+            Err(e) => {
+                eprintln!("{}: {}", source, e);
+                failed = true;
+            }
+        }
+    }
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+#[cfg(feature = "report")]
+fn report(args: &[String]) -> ExitCode {
+    let mut output = None;
+    let mut generator = ReportGenerator::new();
+    let mut inputs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match args.next() {
+                Some(x) => output = Some(PathBuf::from(x)),
+                None => {
+                    eprintln!("{} needs a file\n\n{}", arg, REPORT_USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            "--markdown" => generator = generator.with_format(ReportFormat::Markdown),
+            "--disassembly" => generator = generator.with_disassembly(true),
+            "-h" | "--help" => {
+                println!("{}", REPORT_USAGE);
+                return ExitCode::SUCCESS;
+            }
+            x if x.starts_with('-') => {
+                eprintln!("unknown option {}\n\n{}", x, REPORT_USAGE);
+                return ExitCode::from(2);
+            }
+            x => inputs.push(PathBuf::from(x)),
+        }
+    }
+    if inputs.is_empty() {
+        eprintln!("{}", REPORT_USAGE);
+        return ExitCode::from(2);
+    }
+
+    let mut failed = false;
+    let options = ParseOptions::default();
+    let mut classes = Vec::new();
+    for (source, data) in inputs.iter().flat_map(|x| read_input(x)) {
+        match data.and_then(|x| ClassFile::parse_with_options(x, &options)) {
+            Ok(x) => classes.push(x),
+            Err(e) => {
+                eprintln!("{}: {}", source, e);
+                failed = true;
+            }
+        }
+    }
+    let title = inputs
+        .iter()
+        .map(|x| x.file_name().unwrap_or(x.as_os_str()).to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let written = generator.report(&title, &classes).and_then(|text| {
+        match &output {
+            Some(path) => std::fs::write(path, text)?,
+            None => print!("{}", text),
+        }
+        Ok(())
+    });
+    if let Err(e) = written {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("headers") => headers(&args[1..]),
+        Some("disassemble") => disassemble(&args[1..]),
+        #[cfg(feature = "report")]
+        Some("report") => report(&args[1..]),
+        _ => {
+            dump();
+            ExitCode::SUCCESS
+        }
+    }
+}
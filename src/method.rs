@@ -1,129 +1,251 @@
-use std::fmt::Debug;
-
-use binrw::BinRead;
-use nom::{branch::alt, character::complete::char, combinator::{map, value}, multi::many0, sequence::tuple, IResult};
-
-use crate::{attributes::{Code, Exceptions, Signature}, field::TypeDescriptor, raw::{MethodAccessFlags, MethodRaw}, signature::MethodSignature, ClassFile};
-
-pub struct MethodDescriptor<'a> {
-    param_tys: Vec<TypeDescriptor<'a>>,
-    return_ty: Option<TypeDescriptor<'a>>,
-}
-
-impl<'a> MethodDescriptor<'a> {
-    pub fn parameter_types<'b>(&'b self) -> &'b [TypeDescriptor<'a>] {
-        &self.param_tys
-    }
-
-    pub fn return_type(&self) -> Option<&TypeDescriptor<'a>> {
-        self.return_ty.as_ref()
-    }
-
-    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        let (input, (_, param_tys, _, return_ty)) = tuple((
-            char('('),
-            many0(TypeDescriptor::parse),
-            char(')'),
-            alt((
-                value(None, char('V')),
-                map(TypeDescriptor::parse, |x| Some(x)),
-            )),
-        ))(input)?;
-        Ok((
-            input,
-            Self {
-                param_tys,
-                return_ty,
-            },
-        ))
-    }
-}
-
-impl<'a> Debug for MethodDescriptor<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("MethodDescriptor")
-            .field(&self.param_tys)
-            .field(&self.return_ty)
-            .finish()
-    }
-}
-
-pub struct Method<'a> {
-    pub(crate) class_file: &'a ClassFile,
-    pub(crate) method_inner: &'a MethodRaw,
-}
-
-macro_rules! attribute {
-    ($strct:ident, $name:ident) => {
-        pub fn $name(&self) -> crate::Result<Option<$strct<'a>>> {
-            match self.method_inner.attributes.0.get(stringify!($strct)) {
-                Some(x) => {
-                    let mut buf = std::io::Cursor::new(&x[..]);
-                    let value = $strct::read_be_args(&mut buf, (self.class_file,))?;
-                    Ok(Some(value))
-                }
-                None => Ok(None),
-            }
-        }
-    };
-}
-
-impl<'a> Method<'a> {
-    pub fn identifier(&self) -> crate::Result<&'a str> {
-        self.method_inner.name_index.get_as_string(self.class_file)
-    }
-
-    pub fn descriptor(&self) -> crate::Result<MethodDescriptor<'a>> {
-        let raw_descriptor = self
-            .method_inner
-            .descriptor_index
-            .get_as_string(self.class_file)?;
-        let (_input, ty) = MethodDescriptor::parse(raw_descriptor)?;
-        Ok(ty)
-    }
-
-    pub fn jni_identifier(&self) -> crate::Result<String> {
-        Ok(format!(
-            "Java_{}_{}",
-            self.class_file
-                .this_class()?
-                .replace('/', "_")
-                .replace('$', "_"),
-            self.identifier()?
-        ))
-    }
-
-    pub fn is_native(&self) -> bool {
-        self.method_inner
-            .access_flags
-            .contains(MethodAccessFlags::NATIVE)
-    }
-
-    attribute!(Code, code);
-    attribute!(Exceptions, exceptions);
-
-    pub fn signature(&self) -> crate::Result<Option<MethodSignature<'a>>> {
-        match self.method_inner.attributes.0.get("Signature") {
-            Some(x) => {
-                let mut buf = std::io::Cursor::new(&x[..]);
-                let value = Signature::read_be_args(&mut buf, (self.class_file,))?;
-                Ok(Some(value.get_method()?))
-            }
-            None => Ok(None),
-        }
-    }
-
-    pub fn is_deprecated(&self) -> bool {
-        self.method_inner.attributes.0.get("Deprecated").is_some()
-    }
-}
-
-impl<'a> Debug for Method<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Method")
-            .field("identifier", &self.identifier())
-            .field("desciptor", &self.descriptor())
-            .field("code", &self.code().unwrap())
-            .finish()
-    }
+use std::fmt::Debug;
+
+use nom::{branch::alt, character::complete::char, combinator::{map, value}, multi::many0, sequence::tuple, IResult};
+
+use crate::{hexdump::RawAttribute, jni::JniSymbol, attributes::{Annotation, AnnotationDefault, Attribute, AttributeLocation, KnownAttribute, Code, ElementValue, Exceptions, RuntimeInvisibleAnnotations, RuntimeVisibleAnnotations, Signature}, field::TypeDescriptor, raw::{MethodAccessFlags, MethodRaw}, signature::{ClassType, MethodSignature, ThrowsSignature}, span::Span, ClassFile};
+
+pub struct MethodDescriptor<'a> {
+    pub(crate) param_tys: Vec<TypeDescriptor<'a>>,
+    pub(crate) return_ty: Option<TypeDescriptor<'a>>,
+}
+
+impl<'a> MethodDescriptor<'a> {
+    /// A descriptor for `parameter_types`, returning `return_type` or `void`.
+    pub fn new(parameter_types: Vec<TypeDescriptor<'a>>, return_type: Option<TypeDescriptor<'a>>) -> Self {
+        Self {
+            param_tys: parameter_types,
+            return_ty: return_type,
+        }
+    }
+
+    pub fn parameter_types<'b>(&'b self) -> &'b [TypeDescriptor<'a>] {
+        &self.param_tys
+    }
+
+    pub fn return_type(&self) -> Option<&TypeDescriptor<'a>> {
+        self.return_ty.as_ref()
+    }
+
+    /// The slots taken by the parameters, not counting `this`.
+    pub fn parameter_slots(&self) -> u16 {
+        self.param_tys.iter().map(|x| x.slots()).sum()
+    }
+
+    /// The slots taken by the return value, zero for `void`.
+    pub fn return_slots(&self) -> u16 {
+        self.return_ty.as_ref().map_or(0, |x| x.slots())
+    }
+
+    /// The signature JNI functions such as `GetMethodID` take, e.g.
+    /// `(Ljava/lang/String;I)V`, which is the method descriptor.
+    pub fn to_jni_signature(&self) -> String {
+        self.to_string()
+    }
+
+    /// The descriptor as it is stored in a class file, e.g. `(I[J)V`. The
+    /// same as the [`Display`](std::fmt::Display) output.
+    pub fn to_descriptor_string(&self) -> String {
+        self.to_string()
+    }
+
+    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let (input, (_, param_tys, _, return_ty)) = tuple((
+            char('('),
+            many0(TypeDescriptor::parse),
+            char(')'),
+            alt((
+                value(None, char('V')),
+                map(TypeDescriptor::parse, |x| Some(x)),
+            )),
+        ))(input)?;
+        Ok((
+            input,
+            Self {
+                param_tys,
+                return_ty,
+            },
+        ))
+    }
+}
+
+/// Writes the descriptor, e.g. `(I[J)V`.
+impl std::fmt::Display for MethodDescriptor<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("(")?;
+        for ty in &self.param_tys {
+            write!(f, "{}", ty)?;
+        }
+        f.write_str(")")?;
+        match &self.return_ty {
+            Some(ty) => write!(f, "{}", ty),
+            None => f.write_str("V"),
+        }
+    }
+}
+
+impl<'a> Debug for MethodDescriptor<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("MethodDescriptor")
+            .field(&self.param_tys)
+            .field(&self.return_ty)
+            .finish()
+    }
+}
+
+pub struct Method<'a> {
+    pub(crate) class_file: &'a ClassFile,
+    pub(crate) method_inner: &'a MethodRaw,
+}
+
+impl<'a> Method<'a> {
+    pub fn identifier(&self) -> crate::Result<&'a str> {
+        self.method_inner.name_index.get_as_string(self.class_file)
+    }
+
+    pub fn descriptor(&self) -> crate::Result<MethodDescriptor<'a>> {
+        self.class_file.method_descriptor_at(self.method_inner.descriptor_index)
+    }
+
+    /// The short form of the JNI symbol for this method, e.g.
+    /// `Java_com_example_Foo_bar`, see [`JniSymbol::short`].
+    pub fn jni_identifier(&self) -> crate::Result<String> {
+        Ok(JniSymbol::short(self.class_file.this_class()?, self.identifier()?).to_string())
+    }
+
+    /// The long form of the JNI symbol for this method, with its parameter
+    /// types, e.g. `Java_com_example_Foo_bar__Ljava_lang_String_2I`.
+    pub fn jni_long_identifier(&self) -> crate::Result<String> {
+        let descriptor = self.method_inner.descriptor_index.get_as_string(self.class_file)?;
+        Ok(JniSymbol::long(self.class_file.this_class()?, self.identifier()?, descriptor).to_string())
+    }
+
+    /// The JNI symbol `javac -h` declares this method with: the long form if
+    /// another native method of the class has the same name, and the short
+    /// form otherwise.
+    pub fn jni_symbol(&self) -> crate::Result<String> {
+        let name = self.identifier()?;
+        for x in self.class_file.methods() {
+            if x.is_native() && !std::ptr::eq(x.method_inner, self.method_inner) && x.identifier()? == name {
+                return self.jni_long_identifier();
+            }
+        }
+        self.jni_identifier()
+    }
+
+    pub fn is_native(&self) -> bool {
+        self.method_inner
+            .access_flags
+            .contains(MethodAccessFlags::NATIVE)
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.method_inner
+            .access_flags
+            .contains(MethodAccessFlags::STATIC)
+    }
+
+    /// Decodes the last method attribute called `T::NAME`.
+    pub fn get_attribute<T: KnownAttribute<'a>>(&self) -> crate::Result<Option<T>> {
+        self.method_inner.attributes.decode(self.class_file)
+    }
+
+    pub fn code(&self) -> crate::Result<Option<Code<'a>>> {
+        self.get_attribute()
+    }
+
+    pub fn exceptions(&self) -> crate::Result<Option<Exceptions<'a>>> {
+        self.get_attribute()
+    }
+
+    pub fn signature(&self) -> crate::Result<Option<MethodSignature<'a>>> {
+        match self.get_attribute::<Signature>()? {
+            Some(value) => Ok(Some(value.get_method()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The declared exceptions, generic-aware: taken from the `Signature`
+    /// attribute when it has a throws clause, since javac only writes one if
+    /// some exception is generic, and from `Exceptions` otherwise.
+    pub fn throws(&self) -> crate::Result<Vec<ThrowsSignature<'a>>> {
+        if let Some(signature) = self.signature()? {
+            if !signature.throws.is_empty() {
+                return Ok(signature.throws);
+            }
+        }
+        match self.exceptions()? {
+            Some(exceptions) => Ok(exceptions
+                .class_names()?
+                .into_iter()
+                .map(|x| ThrowsSignature::ClassType(ClassType::from_internal_name(x)))
+                .collect()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.method_inner.span
+    }
+
+    pub fn attribute_span(&self, name: &str) -> Option<Span> {
+        self.method_inner.attributes.span(name)
+    }
+
+    pub fn undecoded_attributes(&self) -> Vec<RawAttribute<'a>> {
+        self.method_inner.attributes.undecoded(self.class_file)
+    }
+
+    /// Every attribute as raw bytes, duplicates included, in the order they
+    /// appear in the file.
+    pub fn raw_attributes(&self) -> Vec<RawAttribute<'a>> {
+        self.method_inner.attributes.raw(self.class_file)
+    }
+
+    /// Every method attribute, decoded, in the order they appear in the file.
+    pub fn attributes(&self) -> crate::Result<Vec<Attribute<'a>>> {
+        self.method_inner.attributes.typed(self.class_file, AttributeLocation::Method)
+    }
+
+    /// The annotations in the `RuntimeVisibleAnnotations` attribute, if any.
+    pub fn annotations(&self) -> crate::Result<Vec<Annotation<'a>>> {
+        match self.get_attribute::<RuntimeVisibleAnnotations>()? {
+            Some(value) => Ok(value.annotations()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The annotations in the `RuntimeInvisibleAnnotations` attribute, which
+    /// compilers keep for `CLASS` retention, if any.
+    pub fn invisible_annotations(&self) -> crate::Result<Vec<Annotation<'a>>> {
+        match self.get_attribute::<RuntimeInvisibleAnnotations>()? {
+            Some(value) => Ok(value.annotations()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The default value of the annotation element this method declares,
+    /// from its `AnnotationDefault` attribute, if any.
+    pub fn annotation_default(&self) -> crate::Result<Option<ElementValue<'a>>> {
+        Ok(self.get_attribute::<AnnotationDefault>()?.map(AnnotationDefault::value))
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.method_inner.attributes.get("Deprecated").is_some()
+    }
+
+    /// Whether the compiler marked this as not present in the source, by the
+    /// `SYNTHETIC` flag or, as older compilers did, a `Synthetic` attribute.
+    pub fn is_synthetic(&self) -> bool {
+        self.method_inner.access_flags.contains(MethodAccessFlags::SYNTHETIC) || self.method_inner.attributes.get("Synthetic").is_some()
+    }
+}
+
+impl<'a> Debug for Method<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Method")
+            .field("identifier", &self.identifier())
+            .field("desciptor", &self.descriptor())
+            .field("code", &self.code().unwrap())
+            .finish()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,68 @@
+//! Size and complexity figures for method bodies, see [`Method::metrics`].
+
+use std::collections::HashSet;
+
+use crate::{cfg::EdgeKind, method::Method, Result};
+
+/// Figures describing one method's code, see [`Method::metrics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MethodMetrics {
+    pub instructions: usize,
+    /// The length of the bytecode in bytes.
+    pub code_size: u32,
+    /// One more than the number of decisions in the code: every extra target
+    /// a block can go on to, and every exception handler.
+    pub cyclomatic_complexity: usize,
+    /// The most nested exception handler ranges any instruction is in, 0 for
+    /// code outside every `try`. Handlers sharing a range count once.
+    pub try_depth: usize,
+    /// The deepest the operand stack gets, in slots, as declared by the code.
+    pub max_stack: u16,
+    /// The local variable slots used, parameters included, as declared.
+    pub max_locals: u16,
+}
+
+impl Method<'_> {
+    /// Measures the method's code, or returns `None` for a method without
+    /// code. The complexity is counted on the control-flow graph, so a
+    /// `switch` adds one for each distinct target but its default, and
+    /// branches of a condition that go to the same place add nothing.
+    pub fn metrics(&self) -> Result<Option<MethodMetrics>> {
+        let code = match self.code()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let graph = code.control_flow_graph()?;
+
+        let mut decisions = 0;
+        for block in 0..graph.blocks().len() {
+            let mut targets: Vec<usize> = graph
+                .successors(block)
+                .filter(|x| !matches!(x.kind, EdgeKind::Exception { .. }))
+                .map(|x| x.to)
+                .collect();
+            targets.sort_unstable();
+            targets.dedup();
+            decisions += targets.len().saturating_sub(1);
+        }
+        let handlers = code.exception_handlers()?;
+        decisions += handlers.iter().map(|x| x.handler.pc).collect::<HashSet<_>>().len();
+
+        let ranges: HashSet<(u32, u32)> = handlers.iter().map(|x| (x.start.pc, x.end.pc)).collect();
+        let try_depth = graph
+            .instructions()
+            .iter()
+            .map(|x| ranges.iter().filter(|(start, end)| *start <= x.pc && x.pc < *end).count())
+            .max()
+            .unwrap_or(0);
+
+        Ok(Some(MethodMetrics {
+            instructions: graph.instructions().len(),
+            code_size: graph.blocks().last().map_or(0, |x| x.end_pc),
+            cyclomatic_complexity: decisions + 1,
+            try_depth,
+            max_stack: code.max_stack,
+            max_locals: code.max_locals,
+        }))
+    }
+}
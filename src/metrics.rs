@@ -0,0 +1,93 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Reading a whole class file, including the constant pool.
+    Parse,
+    ConstantPool,
+    /// Decoding a single attribute from its raw bytes.
+    Attribute,
+    /// Decoding the instructions of a `Code` attribute.
+    Instructions,
+}
+
+impl Phase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Parse => "parse",
+            Self::ConstantPool => "constant_pool",
+            Self::Attribute => "attribute",
+            Self::Instructions => "instructions",
+        }
+    }
+}
+
+/// Receives parse counters and timings, see [`crate::ParseOptions::with_metrics`].
+///
+/// Every method has an empty default, so implementations only override what
+/// they export. Methods are called from whichever thread is parsing.
+pub trait Metrics: Send + Sync {
+    fn class_parsed(&self, _bytes: u64) {}
+
+    fn attribute_decoded(&self, _name: &str) {}
+
+    fn error(&self, _phase: Phase) {}
+
+    fn phase_finished(&self, _phase: Phase, _elapsed: Duration) {}
+}
+
+/// A [`Metrics`] implementation keeping running totals in atomics.
+#[derive(Debug, Default)]
+pub struct Counters {
+    pub classes: AtomicU64,
+    pub bytes: AtomicU64,
+    pub attributes: AtomicU64,
+    pub errors: AtomicU64,
+    pub parse_nanos: AtomicU64,
+    pub constant_pool_nanos: AtomicU64,
+    pub attribute_nanos: AtomicU64,
+    pub instruction_nanos: AtomicU64,
+}
+
+impl Counters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn nanos(&self, phase: Phase) -> &AtomicU64 {
+        match phase {
+            Phase::Parse => &self.parse_nanos,
+            Phase::ConstantPool => &self.constant_pool_nanos,
+            Phase::Attribute => &self.attribute_nanos,
+            Phase::Instructions => &self.instruction_nanos,
+        }
+    }
+
+    /// Total time spent in `phase` so far.
+    pub fn elapsed(&self, phase: Phase) -> Duration {
+        Duration::from_nanos(self.nanos(phase).load(Ordering::Relaxed))
+    }
+}
+
+impl Metrics for Counters {
+    fn class_parsed(&self, bytes: u64) {
+        self.classes.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn attribute_decoded(&self, _name: &str) {
+        self.attributes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn error(&self, _phase: Phase) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn phase_finished(&self, phase: Phase, elapsed: Duration) {
+        self.nanos(phase)
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,119 @@
+//! Multi-release JARs (JEP 238), whose `META-INF/versions/N/` directories
+//! override entries for Java release `N` and later, see [`MultiReleaseJar`].
+
+use std::collections::BTreeMap;
+
+use crate::{archive::Archive, repository::ClassRepository, ParseOptions, Result};
+
+const VERSIONS: &str = "META-INF/versions/";
+const MANIFEST: &str = "META-INF/MANIFEST.MF";
+
+/// The entries of a JAR, each with the variants its releases give it.
+///
+/// Entries come from an [`Archive`] with [`from_archive`](Self::from_archive),
+/// or are added with [`add_entry`](Self::add_entry) in any order.
+/// As for the JVM, versioned entries only count when the manifest's main
+/// section has `Multi-Release: true`, and only those for release 9 onwards.
+/// Otherwise every release sees the base entries.
+#[derive(Debug, Default, Clone)]
+pub struct MultiReleaseJar {
+    multi_release: bool,
+    /// Variants by path within the release, then release, 0 for the base.
+    entries: BTreeMap<String, BTreeMap<u16, Vec<u8>>>,
+}
+
+/// Whether the main section of a manifest has `Multi-Release: true`.
+fn is_multi_release(manifest: &[u8]) -> bool {
+    String::from_utf8_lossy(manifest)
+        .lines()
+        .take_while(|x| !x.trim().is_empty())
+        .filter_map(|x| x.split_once(':'))
+        .any(|(key, value)| key.eq_ignore_ascii_case("Multi-Release") && value.trim().eq_ignore_ascii_case("true"))
+}
+
+impl MultiReleaseJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads every file of the class path of `archive`.
+    pub fn from_archive(archive: &Archive) -> Result<Self> {
+        let mut jar = Self::new();
+        for (path, entry) in archive.class_path_entries() {
+            jar.add_entry(path, archive.read_entry(entry)?);
+        }
+        Ok(jar)
+    }
+
+    /// Adds the JAR entry at `path`, e.g. `com/example/A.class` or
+    /// `META-INF/versions/11/com/example/A.class`. Entries under a directory
+    /// for a release before 9, or not named by a number, are kept as
+    /// resources of the base like any other path.
+    pub fn add_entry(&mut self, path: &str, data: Vec<u8>) {
+        if path == MANIFEST {
+            self.multi_release = is_multi_release(&data);
+        }
+        let versioned = path.strip_prefix(VERSIONS).and_then(|x| {
+            let (release, rest) = x.split_once('/')?;
+            let release = release.parse::<u16>().ok().filter(|&x| x >= 9)?;
+            Some((release, rest))
+        });
+        let (release, path) = versioned.unwrap_or((0, path));
+        self.entries.entry(path.to_string()).or_default().insert(release, data);
+    }
+
+    /// Whether the manifest marks the JAR as multi-release.
+    pub fn is_multi_release(&self) -> bool {
+        self.multi_release
+    }
+
+    /// The releases with their own variant of the entry at `path`, in order,
+    /// with 0 for the base entry. Versioned variants are listed even if the
+    /// manifest does not make the JAR multi-release.
+    pub fn versions(&self, path: &str) -> Vec<u16> {
+        self.entries.get(path).map_or_else(Vec::new, |x| x.keys().copied().collect())
+    }
+
+    /// The releases with their own variant of class `name`, e.g.
+    /// `com/example/A`, as [`Self::versions`].
+    pub fn class_versions(&self, name: &str) -> Vec<u16> {
+        self.versions(&format!("{}.class", name))
+    }
+
+    /// The release whose variant of the entry at `path` a JVM of `release`
+    /// sees, 0 for the base entry, or `None` if it sees none.
+    pub fn resolve(&self, path: &str, release: u16) -> Option<u16> {
+        let variants = self.entries.get(path)?;
+        let limit = if self.multi_release { release } else { 0 };
+        variants.range(..=limit).next_back().map(|(&x, _)| x)
+    }
+
+    /// The contents of the entry at `path` a JVM of `release` sees.
+    pub fn entry_at(&self, path: &str, release: u16) -> Option<&[u8]> {
+        let version = self.resolve(path, release)?;
+        Some(&self.entries[path][&version])
+    }
+
+    /// The bytes of class `name` a JVM of `release` sees.
+    pub fn class_at(&self, name: &str, release: u16) -> Option<&[u8]> {
+        self.entry_at(&format!("{}.class", name), release)
+    }
+
+    /// Every class a JVM of `release` sees, by name, with its bytes. A class
+    /// only in a later release's directory is not visible, and nor are files
+    /// under `META-INF`, which are not on the class path.
+    pub fn classes_at(&self, release: u16) -> impl Iterator<Item = (&str, &[u8])> {
+        self.entries.keys().filter_map(move |path| {
+            let name = path.strip_suffix(".class").filter(|x| !x.starts_with("META-INF/"))?;
+            Some((name, self.entry_at(path, release)?))
+        })
+    }
+
+    /// Parses the classes a JVM of `release` sees into `repository`.
+    pub fn load_into(&self, repository: &ClassRepository, release: u16, options: &ParseOptions) -> Result<()> {
+        for (_, data) in self.classes_at(release) {
+            repository.parse(data.to_vec(), options)?;
+        }
+        Ok(())
+    }
+}
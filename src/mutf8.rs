@@ -0,0 +1,94 @@
+//! Modified UTF-8 as used by `CONSTANT_Utf8` (§4.4.7).
+//!
+//! It differs from standard UTF-8 in that NUL is written as `C0 80` and
+//! supplementary characters are written as two separately encoded surrogates
+//! (six bytes) rather than a single four byte sequence.
+
+/// Decodes modified UTF-8 into UTF-16 code units, exactly as the JVM would
+/// see the string. Unpaired surrogates are kept as is.
+pub fn to_utf16(bytes: &[u8]) -> Option<Vec<u16>> {
+    decode_units(bytes).ok()
+}
+
+/// The offset of the first byte that does not start a valid modified UTF-8
+/// sequence, or `None` if `bytes` decode.
+pub fn invalid_at(bytes: &[u8]) -> Option<usize> {
+    decode_units(bytes).err()
+}
+
+fn decode_units(bytes: &[u8]) -> Result<Vec<u16>, usize> {
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let x = bytes[i] as u16;
+        let continuation = |n: usize| match bytes.get(i + n) {
+            Some(y) if y & 0xc0 == 0x80 => Ok((y & 0x3f) as u16),
+            _ => Err(i),
+        };
+        match x {
+            0x01..=0x7f => {
+                res.push(x);
+                i += 1;
+            }
+            0xc0..=0xdf => {
+                res.push(((x & 0x1f) << 6) | continuation(1)?);
+                i += 2;
+            }
+            0xe0..=0xef => {
+                res.push(((x & 0x0f) << 12) | (continuation(1)? << 6) | continuation(2)?);
+                i += 3;
+            }
+            _ => return Err(i),
+        }
+    }
+    Ok(res)
+}
+
+/// Decodes modified UTF-8 into a [`String`], combining surrogate pairs into
+/// supplementary characters. Unpaired surrogates become U+FFFD.
+pub fn decode(bytes: &[u8]) -> Option<String> {
+    to_utf16(bytes).map(|x| String::from_utf16_lossy(&x))
+}
+
+/// Like [`decode`], but never fails. Undecodable bytes, including standard
+/// four byte UTF-8 sequences, become U+FFFD.
+pub fn decode_lossy(bytes: &[u8]) -> String {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut start = 0;
+    for (i, x) in bytes.iter().enumerate() {
+        // Split before every byte that can start a sequence, and decode each part on its own.
+        if i > start && x & 0xc0 != 0x80 {
+            units.extend(to_utf16(&bytes[start..i]).unwrap_or_else(|| vec![0xfffd]));
+            start = i;
+        }
+    }
+    if start < bytes.len() {
+        units.extend(to_utf16(&bytes[start..]).unwrap_or_else(|| vec![0xfffd]));
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// Encodes a string as modified UTF-8.
+pub fn encode(s: &str) -> Vec<u8> {
+    encode_utf16(&s.encode_utf16().collect::<Vec<_>>())
+}
+
+/// Encodes UTF-16 code units as modified UTF-8, the inverse of [`to_utf16`].
+pub fn encode_utf16(units: &[u16]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(units.len());
+    for &x in units {
+        match x {
+            0x01..=0x7f => res.push(x as u8),
+            0x00 | 0x80..=0x7ff => {
+                res.push(0xc0 | (x >> 6) as u8);
+                res.push(0x80 | (x & 0x3f) as u8);
+            }
+            _ => {
+                res.push(0xe0 | (x >> 12) as u8);
+                res.push(0x80 | ((x >> 6) & 0x3f) as u8);
+                res.push(0x80 | (x & 0x3f) as u8);
+            }
+        }
+    }
+    res
+}
@@ -0,0 +1,164 @@
+use crate::metrics::Metrics;
+
+/// The newest class file major version this crate was written against (Java 25).
+pub const MAX_KNOWN_MAJOR_VERSION: u16 = 69;
+/// The oldest class file major version defined by the JVM specification (JDK 1.0.2).
+pub const MIN_MAJOR_VERSION: u16 = 45;
+
+/// What to do with a class file whose version this crate does not know about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VersionPolicy {
+    /// Parse the class anyway and record a [`ParseWarning::UnknownVersion`].
+    /// Attributes introduced by the newer version are kept as raw bytes.
+    #[default]
+    Warn,
+    /// Fail with [`crate::Error::UnsupportedVersion`].
+    Reject,
+}
+
+/// Non-fatal problems noticed while parsing, see [`crate::ClassFile::warnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+    UnknownVersion { major: u16, minor: u16 },
+    /// A Utf8 constant was not valid modified UTF-8 and was decoded lossily.
+    InvalidUtf8 { index: u16 },
+    /// An attribute claimed more bytes than were left; the rest of its table was dropped.
+    TruncatedAttribute { name: String, length: u32, available: u32 },
+}
+
+/// HotSpot-compatible relaxations for malformed, usually obfuscated, classes.
+///
+/// Constant pool entries are only resolved when used, so dead entries pointing at
+/// the wrong kind of constant never cause an error even in the strict default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Leniency {
+    /// Decode Utf8 constants that are not valid modified UTF-8 lossily instead of failing.
+    pub lossy_utf8: bool,
+    /// Clamp attribute lengths that run past the end of the data instead of failing.
+    /// This only recovers a class cut short inside its last attribute: a length
+    /// that is merely too large takes the bytes of whatever follows, and reading
+    /// those fails or goes wrong instead.
+    pub truncated_attributes: bool,
+    /// Ignore bytes left over after decoding a known attribute instead of failing
+    /// with [`crate::Error::AttributeLength`].
+    pub trailing_attribute_bytes: bool,
+    /// Decode opcodes the JVM does not define as [`crate::instruction::Instruction::Unknown`]
+    /// and carry on with the next byte instead of failing the whole method.
+    pub unknown_opcodes: bool,
+}
+
+impl Leniency {
+    /// Every relaxation enabled.
+    pub fn permissive() -> Self {
+        Self {
+            lossy_utf8: true,
+            truncated_attributes: true,
+            trailing_attribute_bytes: true,
+            unknown_opcodes: true,
+        }
+    }
+}
+
+/// The payload of a non-standard constant pool entry, as decoded by a
+/// [`ConstantPoolHook`].
+#[derive(Debug, Clone)]
+pub struct CustomConstant {
+    pub data: Vec<u8>,
+    /// How many pool indices the entry occupies, like the two used by Long and Double.
+    pub slots: u8,
+}
+
+/// Called with the tag byte of an unrecognized constant pool entry and a reader
+/// positioned just after it. The hook must consume exactly the entry's payload.
+pub type ConstantPoolHook =
+    dyn Fn(u8, &mut dyn std::io::Read) -> crate::Result<CustomConstant> + Send + Sync;
+
+/// Decides from its name whether an attribute is kept, see
+/// [`ParseOptions::with_attribute_filter`].
+pub type AttributeFilter = dyn Fn(&str) -> bool + Send + Sync;
+
+#[derive(Clone, Default)]
+pub struct ParseOptions {
+    pub version_policy: VersionPolicy,
+    pub leniency: Leniency,
+    pub constant_pool_hook: Option<std::sync::Arc<ConstantPoolHook>>,
+    pub metrics: Option<std::sync::Arc<dyn Metrics>>,
+    pub attribute_filter: Option<std::sync::Arc<AttributeFilter>>,
+    pub validate_attributes: bool,
+}
+
+impl std::fmt::Debug for ParseOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParseOptions")
+            .field("version_policy", &self.version_policy)
+            .field("leniency", &self.leniency)
+            .field("constant_pool_hook", &self.constant_pool_hook.is_some())
+            .field("metrics", &self.metrics.is_some())
+            .field("attribute_filter", &self.attribute_filter.is_some())
+            .field("validate_attributes", &self.validate_attributes)
+            .finish()
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options accepting everything HotSpot would load, see [`Leniency::permissive`].
+    pub fn permissive() -> Self {
+        Self::default().with_leniency(Leniency::permissive())
+    }
+
+    pub fn with_leniency(mut self, leniency: Leniency) -> Self {
+        self.leniency = leniency;
+        self
+    }
+
+    pub fn with_version_policy(mut self, version_policy: VersionPolicy) -> Self {
+        self.version_policy = version_policy;
+        self
+    }
+
+    /// Reports counters and timings to `metrics`, both while parsing and when
+    /// attributes of the resulting [`crate::ClassFile`] are decoded later.
+    pub fn with_metrics(mut self, metrics: std::sync::Arc<dyn Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Only stores attributes for which `filter` returns true, on the class, its
+    /// members and inside `Code`. Dropped attributes are still bounds-checked,
+    /// and read as absent afterwards: dropping `Code` hides method bodies, and
+    /// dropping `BootstrapMethods` makes `invokedynamic` fail to decode.
+    pub fn with_attribute_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.attribute_filter = Some(std::sync::Arc::new(filter));
+        self
+    }
+
+    /// Decodes every attribute known to the crate while parsing, so that a
+    /// malformed one fails [`crate::ClassFile::parse_with_options`] instead of
+    /// the accessor that reads it later. This is only a check: decoded values
+    /// borrow the class, so they cannot be kept in it, and accessors decode
+    /// again when called. [`crate::ClassFile::attributes`] and its counterparts
+    /// on members and `Code` give every attribute of one location at once.
+    pub fn with_attribute_validation(mut self, validate_attributes: bool) -> Self {
+        self.validate_attributes = validate_attributes;
+        self
+    }
+
+    pub(crate) fn keeps_attribute(&self, name: &str) -> bool {
+        self.attribute_filter.as_ref().is_none_or(|x| x(name))
+    }
+
+    pub fn with_constant_pool_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(u8, &mut dyn std::io::Read) -> crate::Result<CustomConstant> + Send + Sync + 'static,
+    {
+        self.constant_pool_hook = Some(std::sync::Arc::new(hook));
+        self
+    }
+}
@@ -0,0 +1,1046 @@
+//! Decoded instructions and annotations that own their data instead of
+//! borrowing the [`crate::ClassFile`], so they can outlive it or be sent to
+//! other threads. See [`Instruction::into_owned`].
+
+use crate::{
+    attributes::{Annotation, BootstrapMethod, ElementValue},
+    field::TypeDescriptor,
+    instruction::{
+        ArrayPrimitiveType, BranchOffset, DynamicConstant, DynamicInfo, FieldRef, Instruction, InterfaceMethodRef, LoadableConstant,
+        LocalIndex, MaybeInterfaceMethodRef, MethodHandle, MethodRef,
+    },
+    method::MethodDescriptor,
+    Error, Result,
+};
+
+/// How deep `Dynamic` constants may nest through bootstrap method arguments.
+/// The JVM rejects cycles, which would otherwise recurse forever here.
+const MAX_DYNAMIC_DEPTH: u32 = 32;
+
+/// A [`FieldRef`] with its descriptor kept as text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnedFieldRef {
+    pub class: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+impl OwnedFieldRef {
+    pub fn parsed_descriptor(&self) -> Result<TypeDescriptor<'_>> {
+        let (_, descriptor) = TypeDescriptor::parse(&self.descriptor)?;
+        Ok(descriptor)
+    }
+}
+
+/// A [`MethodRef`] or [`InterfaceMethodRef`], told apart by `interface`, with
+/// its descriptor kept as text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnedMethodRef {
+    pub class: String,
+    pub name: String,
+    pub descriptor: String,
+    pub interface: bool,
+}
+
+impl OwnedMethodRef {
+    pub fn parsed_descriptor(&self) -> Result<MethodDescriptor<'_>> {
+        let (_, descriptor) = MethodDescriptor::parse(&self.descriptor)?;
+        Ok(descriptor)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OwnedMethodHandle {
+    GetField(OwnedFieldRef),
+    GetStatic(OwnedFieldRef),
+    PutField(OwnedFieldRef),
+    PutStatic(OwnedFieldRef),
+    InvokeVirtual(OwnedMethodRef),
+    NewInvokeSpecial(OwnedMethodRef),
+    InvokeStatic(OwnedMethodRef),
+    InvokeSpecial(OwnedMethodRef),
+    InvokeInterface(OwnedMethodRef),
+}
+
+/// A [`BootstrapMethod`] with its arguments resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedBootstrapMethod {
+    /// The position of the method in the `BootstrapMethods` attribute.
+    pub index: u16,
+    pub method: OwnedMethodHandle,
+    pub arguments: Vec<OwnedLoadableConstant>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedDynamicInfo {
+    pub bootstrap_method: OwnedBootstrapMethod,
+    pub name: String,
+    /// A method descriptor.
+    pub descriptor: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedDynamicConstant {
+    pub bootstrap_method: OwnedBootstrapMethod,
+    pub name: String,
+    /// A field descriptor.
+    pub descriptor: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedLoadableConstant {
+    Int(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    String(String),
+    Class(String),
+    /// A method descriptor.
+    MethodType(String),
+    MethodHandle(OwnedMethodHandle),
+    Dynamic(OwnedDynamicConstant),
+}
+
+/// An [`Annotation`] with its strings copied out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedAnnotation {
+    /// The field descriptor of the annotation interface, e.g.
+    /// `Ljava/lang/Deprecated;`.
+    pub type_name: String,
+    /// The explicitly given elements by name, in file order. Elements left to
+    /// their default are absent.
+    pub elements: Vec<(String, OwnedElementValue)>,
+}
+
+impl OwnedAnnotation {
+    /// The value given for element `name`, if it was not left to its default.
+    pub fn element(&self, name: &str) -> Option<&OwnedElementValue> {
+        self.elements.iter().find(|(x, _)| x == name).map(|(_, x)| x)
+    }
+}
+
+/// An [`ElementValue`] with its strings copied out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedElementValue {
+    Byte(i8),
+    /// A UTF-16 code unit, which may be half a surrogate pair.
+    Char(u16),
+    Double(f64),
+    Float(f32),
+    Int(i32),
+    Long(i64),
+    Short(i16),
+    Boolean(bool),
+    String(String),
+    Enum {
+        /// The field descriptor of the enum class.
+        type_name: String,
+        const_name: String,
+    },
+    /// A class literal by return descriptor.
+    Class(String),
+    Annotation(OwnedAnnotation),
+    Array(Vec<OwnedElementValue>),
+}
+
+/// An [`Instruction`] with every constant pool operand owned. Switch padding
+/// and the zero bytes after `invokeinterface` and `invokedynamic` are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedInstruction {
+    Aaload,
+    Aastore,
+    AconstNull,
+    Aload { index: LocalIndex },
+    Aload0,
+    Aload1,
+    Aload2,
+    Aload3,
+    Anewarray { class: String },
+    Areturn,
+    Arraylength,
+    Astore { index: LocalIndex },
+    Astore0,
+    Astore1,
+    Astore2,
+    Astore3,
+    Athrow,
+    Baload,
+    Bastore,
+    Bipush { value: i8 },
+    Caload,
+    Castore,
+    Checkcast { class: String },
+    D2f,
+    D2i,
+    D2l,
+    Dadd,
+    Daload,
+    Dastore,
+    Dcmpg,
+    Dcmpl,
+    Dconst0,
+    Dconst1,
+    Ddiv,
+    Dload { index: LocalIndex },
+    Dload0,
+    Dload1,
+    Dload2,
+    Dload3,
+    Dmul,
+    Dneg,
+    Drem,
+    Dreturn,
+    Dstore { index: LocalIndex },
+    Dstore0,
+    Dstore1,
+    Dstore2,
+    Dstore3,
+    Dsub,
+    Dup,
+    DupX1,
+    DupX2,
+    Dup2,
+    Dup2X1,
+    Dup2X2,
+    F2d,
+    F2i,
+    F2l,
+    Fadd,
+    Faload,
+    Fastore,
+    Fcmpg,
+    Fcmpl,
+    Fconst0,
+    Fconst1,
+    Fconst2,
+    Fdiv,
+    Fload { index: LocalIndex },
+    Fload0,
+    Fload1,
+    Fload2,
+    Fload3,
+    Fmul,
+    Fneg,
+    Frem,
+    Freturn,
+    Fstore { index: LocalIndex },
+    Fstore0,
+    Fstore1,
+    Fstore2,
+    Fstore3,
+    Fsub,
+    Getfield { field: OwnedFieldRef },
+    Getstatic { field: OwnedFieldRef },
+    Goto { offset: BranchOffset },
+    GotoW { offset: BranchOffset },
+    I2b,
+    I2c,
+    I2d,
+    I2f,
+    I2l,
+    I2s,
+    Iadd,
+    Iaload,
+    Iand,
+    Iastore,
+    IconstM1,
+    Iconst0,
+    Iconst1,
+    Iconst2,
+    Iconst3,
+    Iconst4,
+    Iconst5,
+    Idiv,
+    IfAcmpeq { offset: BranchOffset },
+    IfAcmpne { offset: BranchOffset },
+    IfIcmpeq { offset: BranchOffset },
+    IfIcmpne { offset: BranchOffset },
+    IfIcmplt { offset: BranchOffset },
+    IfIcmpge { offset: BranchOffset },
+    IfIcmpgt { offset: BranchOffset },
+    IfIcmple { offset: BranchOffset },
+    Ifeq { offset: BranchOffset },
+    Ifne { offset: BranchOffset },
+    Iflt { offset: BranchOffset },
+    Ifge { offset: BranchOffset },
+    Ifgt { offset: BranchOffset },
+    Ifle { offset: BranchOffset },
+    Ifnonnull { offset: BranchOffset },
+    Ifnull { offset: BranchOffset },
+    Iinc {
+        index: LocalIndex,
+        constant: i16,
+    },
+    Iload { index: LocalIndex },
+    Iload0,
+    Iload1,
+    Iload2,
+    Iload3,
+    Imul,
+    Ineg,
+    Instanceof { class: String },
+    Invokedynamic { index: OwnedDynamicInfo },
+    Invokeinterface {
+        index: OwnedMethodRef,
+        count: u8,
+    },
+    Invokespecial { index: OwnedMethodRef },
+    Invokestatic { index: OwnedMethodRef },
+    Invokevirtual { index: OwnedMethodRef },
+    Ior,
+    Irem,
+    Ireturn,
+    Ishl,
+    Ishr,
+    Istore { index: LocalIndex },
+    Istore0,
+    Istore1,
+    Istore2,
+    Istore3,
+    Isub,
+    Iushr,
+    Ixor,
+    Jsr { offset: BranchOffset },
+    JsrW { offset: BranchOffset },
+    L2d,
+    L2f,
+    L2i,
+    Ladd,
+    Laload,
+    Land,
+    Lastore,
+    Lcmp,
+    Lconst0,
+    Lconst1,
+    Ldc {
+        index: u8,
+        constant: OwnedLoadableConstant,
+    },
+    LdcW {
+        index: u16,
+        constant: OwnedLoadableConstant,
+    },
+    Ldc2W {
+        index: u16,
+        constant: OwnedLoadableConstant,
+    },
+    Ldiv,
+    Lload { index: LocalIndex },
+    Lload0,
+    Lload1,
+    Lload2,
+    Lload3,
+    Lmul,
+    Lneg,
+    Lookupswitch {
+        default: BranchOffset,
+        pairs: Vec<(i32, BranchOffset)>,
+    },
+    Lor,
+    Lrem,
+    Lreturn,
+    Lshl,
+    Lshr,
+    Lstore { index: LocalIndex },
+    Lstore0,
+    Lstore1,
+    Lstore2,
+    Lstore3,
+    Lsub,
+    Lushr,
+    Lxor,
+    Monitorenter,
+    Monitorexit,
+    Multianewarray {
+        class: String,
+        dimensions: u8,
+    },
+    New { class: String },
+    Newarray { atype: ArrayPrimitiveType },
+    Nop,
+    Pop,
+    Pop2,
+    Putfield { field: OwnedFieldRef },
+    Putstatic { field: OwnedFieldRef },
+    Ret { index: LocalIndex },
+    Return,
+    Saload,
+    Sastore,
+    Sipush { value: i16 },
+    Swap,
+    Tableswitch {
+        default: BranchOffset,
+        low: i32,
+        high: i32,
+        jump_offsets: Vec<BranchOffset>,
+    },
+    WideAload { index: LocalIndex },
+    WideAstore { index: LocalIndex },
+    WideDload { index: LocalIndex },
+    WideDstore { index: LocalIndex },
+    WideFload { index: LocalIndex },
+    WideFstore { index: LocalIndex },
+    WideIinc {
+        index: LocalIndex,
+        constant: i16,
+    },
+    WideIload { index: LocalIndex },
+    WideIstore { index: LocalIndex },
+    WideLload { index: LocalIndex },
+    WideLstore { index: LocalIndex },
+    WideRet { index: LocalIndex },
+    Unknown { opcode: u8, pc: u32 },
+}
+
+impl OwnedInstruction {
+    /// The opcode byte, see [`Instruction::opcode`].
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Self::Nop => 0x00,
+            Self::AconstNull => 0x01,
+            Self::IconstM1 => 0x02,
+            Self::Iconst0 => 0x03,
+            Self::Iconst1 => 0x04,
+            Self::Iconst2 => 0x05,
+            Self::Iconst3 => 0x06,
+            Self::Iconst4 => 0x07,
+            Self::Iconst5 => 0x08,
+            Self::Lconst0 => 0x09,
+            Self::Lconst1 => 0x0a,
+            Self::Fconst0 => 0x0b,
+            Self::Fconst1 => 0x0c,
+            Self::Fconst2 => 0x0d,
+            Self::Dconst0 => 0x0e,
+            Self::Dconst1 => 0x0f,
+            Self::Bipush { .. } => 0x10,
+            Self::Sipush { .. } => 0x11,
+            Self::Ldc { .. } => 0x12,
+            Self::LdcW { .. } => 0x13,
+            Self::Ldc2W { .. } => 0x14,
+            Self::Iload { .. } | Self::WideIload { .. } => 0x15,
+            Self::Lload { .. } | Self::WideLload { .. } => 0x16,
+            Self::Fload { .. } | Self::WideFload { .. } => 0x17,
+            Self::Dload { .. } | Self::WideDload { .. } => 0x18,
+            Self::Aload { .. } | Self::WideAload { .. } => 0x19,
+            Self::Iload0 => 0x1a,
+            Self::Iload1 => 0x1b,
+            Self::Iload2 => 0x1c,
+            Self::Iload3 => 0x1d,
+            Self::Lload0 => 0x1e,
+            Self::Lload1 => 0x1f,
+            Self::Lload2 => 0x20,
+            Self::Lload3 => 0x21,
+            Self::Fload0 => 0x22,
+            Self::Fload1 => 0x23,
+            Self::Fload2 => 0x24,
+            Self::Fload3 => 0x25,
+            Self::Dload0 => 0x26,
+            Self::Dload1 => 0x27,
+            Self::Dload2 => 0x28,
+            Self::Dload3 => 0x29,
+            Self::Aload0 => 0x2a,
+            Self::Aload1 => 0x2b,
+            Self::Aload2 => 0x2c,
+            Self::Aload3 => 0x2d,
+            Self::Iaload => 0x2e,
+            Self::Laload => 0x2f,
+            Self::Faload => 0x30,
+            Self::Daload => 0x31,
+            Self::Aaload => 0x32,
+            Self::Baload => 0x33,
+            Self::Caload => 0x34,
+            Self::Saload => 0x35,
+            Self::Istore { .. } | Self::WideIstore { .. } => 0x36,
+            Self::Lstore { .. } | Self::WideLstore { .. } => 0x37,
+            Self::Fstore { .. } | Self::WideFstore { .. } => 0x38,
+            Self::Dstore { .. } | Self::WideDstore { .. } => 0x39,
+            Self::Astore { .. } | Self::WideAstore { .. } => 0x3a,
+            Self::Istore0 => 0x3b,
+            Self::Istore1 => 0x3c,
+            Self::Istore2 => 0x3d,
+            Self::Istore3 => 0x3e,
+            Self::Lstore0 => 0x3f,
+            Self::Lstore1 => 0x40,
+            Self::Lstore2 => 0x41,
+            Self::Lstore3 => 0x42,
+            Self::Fstore0 => 0x43,
+            Self::Fstore1 => 0x44,
+            Self::Fstore2 => 0x45,
+            Self::Fstore3 => 0x46,
+            Self::Dstore0 => 0x47,
+            Self::Dstore1 => 0x48,
+            Self::Dstore2 => 0x49,
+            Self::Dstore3 => 0x4a,
+            Self::Astore0 => 0x4b,
+            Self::Astore1 => 0x4c,
+            Self::Astore2 => 0x4d,
+            Self::Astore3 => 0x4e,
+            Self::Iastore => 0x4f,
+            Self::Lastore => 0x50,
+            Self::Fastore => 0x51,
+            Self::Dastore => 0x52,
+            Self::Aastore => 0x53,
+            Self::Bastore => 0x54,
+            Self::Castore => 0x55,
+            Self::Sastore => 0x56,
+            Self::Pop => 0x57,
+            Self::Pop2 => 0x58,
+            Self::Dup => 0x59,
+            Self::DupX1 => 0x5a,
+            Self::DupX2 => 0x5b,
+            Self::Dup2 => 0x5c,
+            Self::Dup2X1 => 0x5d,
+            Self::Dup2X2 => 0x5e,
+            Self::Swap => 0x5f,
+            Self::Iadd => 0x60,
+            Self::Ladd => 0x61,
+            Self::Fadd => 0x62,
+            Self::Dadd => 0x63,
+            Self::Isub => 0x64,
+            Self::Lsub => 0x65,
+            Self::Fsub => 0x66,
+            Self::Dsub => 0x67,
+            Self::Imul => 0x68,
+            Self::Lmul => 0x69,
+            Self::Fmul => 0x6a,
+            Self::Dmul => 0x6b,
+            Self::Idiv => 0x6c,
+            Self::Ldiv => 0x6d,
+            Self::Fdiv => 0x6e,
+            Self::Ddiv => 0x6f,
+            Self::Irem => 0x70,
+            Self::Lrem => 0x71,
+            Self::Frem => 0x72,
+            Self::Drem => 0x73,
+            Self::Ineg => 0x74,
+            Self::Lneg => 0x75,
+            Self::Fneg => 0x76,
+            Self::Dneg => 0x77,
+            Self::Ishl => 0x78,
+            Self::Lshl => 0x79,
+            Self::Ishr => 0x7a,
+            Self::Lshr => 0x7b,
+            Self::Iushr => 0x7c,
+            Self::Lushr => 0x7d,
+            Self::Iand => 0x7e,
+            Self::Land => 0x7f,
+            Self::Ior => 0x80,
+            Self::Lor => 0x81,
+            Self::Ixor => 0x82,
+            Self::Lxor => 0x83,
+            Self::Iinc { .. } | Self::WideIinc { .. } => 0x84,
+            Self::I2l => 0x85,
+            Self::I2f => 0x86,
+            Self::I2d => 0x87,
+            Self::L2i => 0x88,
+            Self::L2f => 0x89,
+            Self::L2d => 0x8a,
+            Self::F2i => 0x8b,
+            Self::F2l => 0x8c,
+            Self::F2d => 0x8d,
+            Self::D2i => 0x8e,
+            Self::D2l => 0x8f,
+            Self::D2f => 0x90,
+            Self::I2b => 0x91,
+            Self::I2c => 0x92,
+            Self::I2s => 0x93,
+            Self::Lcmp => 0x94,
+            Self::Fcmpl => 0x95,
+            Self::Fcmpg => 0x96,
+            Self::Dcmpl => 0x97,
+            Self::Dcmpg => 0x98,
+            Self::Ifeq { .. } => 0x99,
+            Self::Ifne { .. } => 0x9a,
+            Self::Iflt { .. } => 0x9b,
+            Self::Ifge { .. } => 0x9c,
+            Self::Ifgt { .. } => 0x9d,
+            Self::Ifle { .. } => 0x9e,
+            Self::IfIcmpeq { .. } => 0x9f,
+            Self::IfIcmpne { .. } => 0xa0,
+            Self::IfIcmplt { .. } => 0xa1,
+            Self::IfIcmpge { .. } => 0xa2,
+            Self::IfIcmpgt { .. } => 0xa3,
+            Self::IfIcmple { .. } => 0xa4,
+            Self::IfAcmpeq { .. } => 0xa5,
+            Self::IfAcmpne { .. } => 0xa6,
+            Self::Goto { .. } => 0xa7,
+            Self::Jsr { .. } => 0xa8,
+            Self::Ret { .. } | Self::WideRet { .. } => 0xa9,
+            Self::Tableswitch { .. } => 0xaa,
+            Self::Lookupswitch { .. } => 0xab,
+            Self::Ireturn => 0xac,
+            Self::Lreturn => 0xad,
+            Self::Freturn => 0xae,
+            Self::Dreturn => 0xaf,
+            Self::Areturn => 0xb0,
+            Self::Return => 0xb1,
+            Self::Getstatic { .. } => 0xb2,
+            Self::Putstatic { .. } => 0xb3,
+            Self::Getfield { .. } => 0xb4,
+            Self::Putfield { .. } => 0xb5,
+            Self::Invokevirtual { .. } => 0xb6,
+            Self::Invokespecial { .. } => 0xb7,
+            Self::Invokestatic { .. } => 0xb8,
+            Self::Invokeinterface { .. } => 0xb9,
+            Self::Invokedynamic { .. } => 0xba,
+            Self::New { .. } => 0xbb,
+            Self::Newarray { .. } => 0xbc,
+            Self::Anewarray { .. } => 0xbd,
+            Self::Arraylength => 0xbe,
+            Self::Athrow => 0xbf,
+            Self::Checkcast { .. } => 0xc0,
+            Self::Instanceof { .. } => 0xc1,
+            Self::Monitorenter => 0xc2,
+            Self::Monitorexit => 0xc3,
+            Self::Multianewarray { .. } => 0xc5,
+            Self::Ifnull { .. } => 0xc6,
+            Self::Ifnonnull { .. } => 0xc7,
+            Self::GotoW { .. } => 0xc8,
+            Self::JsrW { .. } => 0xc9,
+            Self::Unknown { opcode, .. } => *opcode,
+        }
+    }
+
+    /// Whether this is one of the forms prefixed by `wide`.
+    pub fn is_wide(&self) -> bool {
+        matches!(
+            self,
+            Self::WideAload { .. }
+                | Self::WideAstore { .. }
+                | Self::WideDload { .. }
+                | Self::WideDstore { .. }
+                | Self::WideFload { .. }
+                | Self::WideFstore { .. }
+                | Self::WideIinc { .. }
+                | Self::WideIload { .. }
+                | Self::WideIstore { .. }
+                | Self::WideLload { .. }
+                | Self::WideLstore { .. }
+                | Self::WideRet { .. }
+        )
+    }
+}
+
+impl FieldRef<'_> {
+    pub fn into_owned(self) -> OwnedFieldRef {
+        OwnedFieldRef {
+            class: self.class.to_string(),
+            name: self.name.to_string(),
+            descriptor: self.descriptor.to_string(),
+        }
+    }
+}
+
+impl MethodRef<'_> {
+    pub fn into_owned(self) -> OwnedMethodRef {
+        OwnedMethodRef {
+            class: self.class.to_string(),
+            name: self.name.to_string(),
+            descriptor: self.descriptor.to_string(),
+            interface: false,
+        }
+    }
+}
+
+impl InterfaceMethodRef<'_> {
+    pub fn into_owned(self) -> OwnedMethodRef {
+        OwnedMethodRef {
+            class: self.class.to_string(),
+            name: self.name.to_string(),
+            descriptor: self.descriptor.to_string(),
+            interface: true,
+        }
+    }
+}
+
+impl MaybeInterfaceMethodRef<'_> {
+    pub fn into_owned(self) -> OwnedMethodRef {
+        match self {
+            Self::RegularMethod(x) => x.into_owned(),
+            Self::InterfaceMethod(x) => x.into_owned(),
+        }
+    }
+}
+
+impl MethodHandle<'_> {
+    pub fn into_owned(self) -> OwnedMethodHandle {
+        match self {
+            Self::GetField(x) => OwnedMethodHandle::GetField(x.into_owned()),
+            Self::GetStatic(x) => OwnedMethodHandle::GetStatic(x.into_owned()),
+            Self::PutField(x) => OwnedMethodHandle::PutField(x.into_owned()),
+            Self::PutStatic(x) => OwnedMethodHandle::PutStatic(x.into_owned()),
+            Self::InvokeVirtual(x) => OwnedMethodHandle::InvokeVirtual(x.into_owned()),
+            Self::NewInvokeSpecial(x) => OwnedMethodHandle::NewInvokeSpecial(x.into_owned()),
+            Self::InvokeStatic(x) => OwnedMethodHandle::InvokeStatic(x.into_owned()),
+            Self::InvokeSpecial(x) => OwnedMethodHandle::InvokeSpecial(x.into_owned()),
+            Self::InvokeInterface(x) => OwnedMethodHandle::InvokeInterface(x.into_owned()),
+        }
+    }
+}
+
+impl BootstrapMethod<'_> {
+    /// Resolves the arguments, which fails where [`Self::arguments`] would.
+    pub fn into_owned(self) -> Result<OwnedBootstrapMethod> {
+        self.owned(0)
+    }
+
+    fn owned(self, depth: u32) -> Result<OwnedBootstrapMethod> {
+        if depth > MAX_DYNAMIC_DEPTH {
+            return Err(Error::ConstantPoolError(format!(
+                "Dynamic constants nested more than {} deep",
+                MAX_DYNAMIC_DEPTH
+            )));
+        }
+        Ok(OwnedBootstrapMethod {
+            index: self.index(),
+            arguments: self
+                .arguments()?
+                .into_iter()
+                .map(|x| x.owned(depth + 1))
+                .collect::<Result<_>>()?,
+            method: self.method.into_owned(),
+        })
+    }
+}
+
+impl DynamicInfo<'_> {
+    pub fn into_owned(self) -> Result<OwnedDynamicInfo> {
+        Ok(OwnedDynamicInfo {
+            bootstrap_method: self.bootstrap_method.into_owned()?,
+            name: self.name.to_string(),
+            descriptor: self.descriptor.to_string(),
+        })
+    }
+}
+
+impl DynamicConstant<'_> {
+    pub fn into_owned(self) -> Result<OwnedDynamicConstant> {
+        self.owned(0)
+    }
+
+    fn owned(self, depth: u32) -> Result<OwnedDynamicConstant> {
+        Ok(OwnedDynamicConstant {
+            bootstrap_method: self.bootstrap_method.owned(depth)?,
+            name: self.name.to_string(),
+            descriptor: self.descriptor.to_string(),
+        })
+    }
+}
+
+impl LoadableConstant<'_> {
+    pub fn into_owned(self) -> Result<OwnedLoadableConstant> {
+        self.owned(0)
+    }
+
+    fn owned(self, depth: u32) -> Result<OwnedLoadableConstant> {
+        Ok(match self {
+            Self::Int(x) => OwnedLoadableConstant::Int(x),
+            Self::Float(x) => OwnedLoadableConstant::Float(x),
+            Self::Long(x) => OwnedLoadableConstant::Long(x),
+            Self::Double(x) => OwnedLoadableConstant::Double(x),
+            Self::String(x) => OwnedLoadableConstant::String(x.to_string()),
+            Self::Class(x) => OwnedLoadableConstant::Class(x.to_string()),
+            Self::MethodType(x) => OwnedLoadableConstant::MethodType(x.to_string()),
+            Self::MethodHandle(x) => OwnedLoadableConstant::MethodHandle(x.into_owned()),
+            Self::Dynamic(x) => OwnedLoadableConstant::Dynamic(x.owned(depth)?),
+        })
+    }
+}
+
+impl Annotation<'_> {
+    pub fn into_owned(self) -> OwnedAnnotation {
+        OwnedAnnotation {
+            type_name: self.type_name.to_string(),
+            elements: self
+                .elements
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.into_owned()))
+                .collect(),
+        }
+    }
+}
+
+impl ElementValue<'_> {
+    pub fn into_owned(self) -> OwnedElementValue {
+        match self {
+            Self::Byte(x) => OwnedElementValue::Byte(x),
+            Self::Char(x) => OwnedElementValue::Char(x),
+            Self::Double(x) => OwnedElementValue::Double(x),
+            Self::Float(x) => OwnedElementValue::Float(x),
+            Self::Int(x) => OwnedElementValue::Int(x),
+            Self::Long(x) => OwnedElementValue::Long(x),
+            Self::Short(x) => OwnedElementValue::Short(x),
+            Self::Boolean(x) => OwnedElementValue::Boolean(x),
+            Self::String(x) => OwnedElementValue::String(x.to_string()),
+            Self::Enum { type_name, const_name } => OwnedElementValue::Enum {
+                type_name: type_name.to_string(),
+                const_name: const_name.to_string(),
+            },
+            Self::Class(x) => OwnedElementValue::Class(x.to_string()),
+            Self::Annotation(x) => OwnedElementValue::Annotation(x.into_owned()),
+            Self::Array(x) => OwnedElementValue::Array(x.into_iter().map(ElementValue::into_owned).collect()),
+        }
+    }
+}
+
+impl Instruction<'_> {
+    /// Copies out every string and descriptor, resolving bootstrap method
+    /// arguments on the way.
+    pub fn into_owned(self) -> Result<OwnedInstruction> {
+        Ok(match self {
+            Instruction::Aaload => OwnedInstruction::Aaload,
+            Instruction::Aastore => OwnedInstruction::Aastore,
+            Instruction::AconstNull => OwnedInstruction::AconstNull,
+            Instruction::Aload { index } => OwnedInstruction::Aload { index },
+            Instruction::Aload0 => OwnedInstruction::Aload0,
+            Instruction::Aload1 => OwnedInstruction::Aload1,
+            Instruction::Aload2 => OwnedInstruction::Aload2,
+            Instruction::Aload3 => OwnedInstruction::Aload3,
+            Instruction::Anewarray { class } => OwnedInstruction::Anewarray {
+                class: class.to_string(),
+            },
+            Instruction::Areturn => OwnedInstruction::Areturn,
+            Instruction::Arraylength => OwnedInstruction::Arraylength,
+            Instruction::Astore { index } => OwnedInstruction::Astore { index },
+            Instruction::Astore0 => OwnedInstruction::Astore0,
+            Instruction::Astore1 => OwnedInstruction::Astore1,
+            Instruction::Astore2 => OwnedInstruction::Astore2,
+            Instruction::Astore3 => OwnedInstruction::Astore3,
+            Instruction::Athrow => OwnedInstruction::Athrow,
+            Instruction::Baload => OwnedInstruction::Baload,
+            Instruction::Bastore => OwnedInstruction::Bastore,
+            Instruction::Bipush { value } => OwnedInstruction::Bipush { value },
+            Instruction::Caload => OwnedInstruction::Caload,
+            Instruction::Castore => OwnedInstruction::Castore,
+            Instruction::Checkcast { class } => OwnedInstruction::Checkcast {
+                class: class.to_string(),
+            },
+            Instruction::D2f => OwnedInstruction::D2f,
+            Instruction::D2i => OwnedInstruction::D2i,
+            Instruction::D2l => OwnedInstruction::D2l,
+            Instruction::Dadd => OwnedInstruction::Dadd,
+            Instruction::Daload => OwnedInstruction::Daload,
+            Instruction::Dastore => OwnedInstruction::Dastore,
+            Instruction::Dcmpg => OwnedInstruction::Dcmpg,
+            Instruction::Dcmpl => OwnedInstruction::Dcmpl,
+            Instruction::Dconst0 => OwnedInstruction::Dconst0,
+            Instruction::Dconst1 => OwnedInstruction::Dconst1,
+            Instruction::Ddiv => OwnedInstruction::Ddiv,
+            Instruction::Dload { index } => OwnedInstruction::Dload { index },
+            Instruction::Dload0 => OwnedInstruction::Dload0,
+            Instruction::Dload1 => OwnedInstruction::Dload1,
+            Instruction::Dload2 => OwnedInstruction::Dload2,
+            Instruction::Dload3 => OwnedInstruction::Dload3,
+            Instruction::Dmul => OwnedInstruction::Dmul,
+            Instruction::Dneg => OwnedInstruction::Dneg,
+            Instruction::Drem => OwnedInstruction::Drem,
+            Instruction::Dreturn => OwnedInstruction::Dreturn,
+            Instruction::Dstore { index } => OwnedInstruction::Dstore { index },
+            Instruction::Dstore0 => OwnedInstruction::Dstore0,
+            Instruction::Dstore1 => OwnedInstruction::Dstore1,
+            Instruction::Dstore2 => OwnedInstruction::Dstore2,
+            Instruction::Dstore3 => OwnedInstruction::Dstore3,
+            Instruction::Dsub => OwnedInstruction::Dsub,
+            Instruction::Dup => OwnedInstruction::Dup,
+            Instruction::DupX1 => OwnedInstruction::DupX1,
+            Instruction::DupX2 => OwnedInstruction::DupX2,
+            Instruction::Dup2 => OwnedInstruction::Dup2,
+            Instruction::Dup2X1 => OwnedInstruction::Dup2X1,
+            Instruction::Dup2X2 => OwnedInstruction::Dup2X2,
+            Instruction::F2d => OwnedInstruction::F2d,
+            Instruction::F2i => OwnedInstruction::F2i,
+            Instruction::F2l => OwnedInstruction::F2l,
+            Instruction::Fadd => OwnedInstruction::Fadd,
+            Instruction::Faload => OwnedInstruction::Faload,
+            Instruction::Fastore => OwnedInstruction::Fastore,
+            Instruction::Fcmpg => OwnedInstruction::Fcmpg,
+            Instruction::Fcmpl => OwnedInstruction::Fcmpl,
+            Instruction::Fconst0 => OwnedInstruction::Fconst0,
+            Instruction::Fconst1 => OwnedInstruction::Fconst1,
+            Instruction::Fconst2 => OwnedInstruction::Fconst2,
+            Instruction::Fdiv => OwnedInstruction::Fdiv,
+            Instruction::Fload { index } => OwnedInstruction::Fload { index },
+            Instruction::Fload0 => OwnedInstruction::Fload0,
+            Instruction::Fload1 => OwnedInstruction::Fload1,
+            Instruction::Fload2 => OwnedInstruction::Fload2,
+            Instruction::Fload3 => OwnedInstruction::Fload3,
+            Instruction::Fmul => OwnedInstruction::Fmul,
+            Instruction::Fneg => OwnedInstruction::Fneg,
+            Instruction::Frem => OwnedInstruction::Frem,
+            Instruction::Freturn => OwnedInstruction::Freturn,
+            Instruction::Fstore { index } => OwnedInstruction::Fstore { index },
+            Instruction::Fstore0 => OwnedInstruction::Fstore0,
+            Instruction::Fstore1 => OwnedInstruction::Fstore1,
+            Instruction::Fstore2 => OwnedInstruction::Fstore2,
+            Instruction::Fstore3 => OwnedInstruction::Fstore3,
+            Instruction::Fsub => OwnedInstruction::Fsub,
+            Instruction::Getfield { field } => OwnedInstruction::Getfield {
+                field: field.into_owned(),
+            },
+            Instruction::Getstatic { field } => OwnedInstruction::Getstatic {
+                field: field.into_owned(),
+            },
+            Instruction::Goto { offset } => OwnedInstruction::Goto { offset },
+            Instruction::GotoW { offset } => OwnedInstruction::GotoW { offset },
+            Instruction::I2b => OwnedInstruction::I2b,
+            Instruction::I2c => OwnedInstruction::I2c,
+            Instruction::I2d => OwnedInstruction::I2d,
+            Instruction::I2f => OwnedInstruction::I2f,
+            Instruction::I2l => OwnedInstruction::I2l,
+            Instruction::I2s => OwnedInstruction::I2s,
+            Instruction::Iadd => OwnedInstruction::Iadd,
+            Instruction::Iaload => OwnedInstruction::Iaload,
+            Instruction::Iand => OwnedInstruction::Iand,
+            Instruction::Iastore => OwnedInstruction::Iastore,
+            Instruction::IconstM1 => OwnedInstruction::IconstM1,
+            Instruction::Iconst0 => OwnedInstruction::Iconst0,
+            Instruction::Iconst1 => OwnedInstruction::Iconst1,
+            Instruction::Iconst2 => OwnedInstruction::Iconst2,
+            Instruction::Iconst3 => OwnedInstruction::Iconst3,
+            Instruction::Iconst4 => OwnedInstruction::Iconst4,
+            Instruction::Iconst5 => OwnedInstruction::Iconst5,
+            Instruction::Idiv => OwnedInstruction::Idiv,
+            Instruction::IfAcmpeq { offset } => OwnedInstruction::IfAcmpeq { offset },
+            Instruction::IfAcmpne { offset } => OwnedInstruction::IfAcmpne { offset },
+            Instruction::IfIcmpeq { offset } => OwnedInstruction::IfIcmpeq { offset },
+            Instruction::IfIcmpne { offset } => OwnedInstruction::IfIcmpne { offset },
+            Instruction::IfIcmplt { offset } => OwnedInstruction::IfIcmplt { offset },
+            Instruction::IfIcmpge { offset } => OwnedInstruction::IfIcmpge { offset },
+            Instruction::IfIcmpgt { offset } => OwnedInstruction::IfIcmpgt { offset },
+            Instruction::IfIcmple { offset } => OwnedInstruction::IfIcmple { offset },
+            Instruction::Ifeq { offset } => OwnedInstruction::Ifeq { offset },
+            Instruction::Ifne { offset } => OwnedInstruction::Ifne { offset },
+            Instruction::Iflt { offset } => OwnedInstruction::Iflt { offset },
+            Instruction::Ifge { offset } => OwnedInstruction::Ifge { offset },
+            Instruction::Ifgt { offset } => OwnedInstruction::Ifgt { offset },
+            Instruction::Ifle { offset } => OwnedInstruction::Ifle { offset },
+            Instruction::Ifnonnull { offset } => OwnedInstruction::Ifnonnull { offset },
+            Instruction::Ifnull { offset } => OwnedInstruction::Ifnull { offset },
+            Instruction::Iinc { index, constant } => OwnedInstruction::Iinc { index, constant },
+            Instruction::Iload { index } => OwnedInstruction::Iload { index },
+            Instruction::Iload0 => OwnedInstruction::Iload0,
+            Instruction::Iload1 => OwnedInstruction::Iload1,
+            Instruction::Iload2 => OwnedInstruction::Iload2,
+            Instruction::Iload3 => OwnedInstruction::Iload3,
+            Instruction::Imul => OwnedInstruction::Imul,
+            Instruction::Ineg => OwnedInstruction::Ineg,
+            Instruction::Instanceof { class } => OwnedInstruction::Instanceof {
+                class: class.to_string(),
+            },
+            Instruction::Invokedynamic { index, .. } => OwnedInstruction::Invokedynamic {
+                index: index.into_owned()?,
+            },
+            Instruction::Invokeinterface { index, count, .. } => OwnedInstruction::Invokeinterface {
+                index: index.into_owned(),
+                count,
+            },
+            Instruction::Invokespecial { index } => OwnedInstruction::Invokespecial {
+                index: index.into_owned(),
+            },
+            Instruction::Invokestatic { index } => OwnedInstruction::Invokestatic {
+                index: index.into_owned(),
+            },
+            Instruction::Invokevirtual { index } => OwnedInstruction::Invokevirtual {
+                index: index.into_owned(),
+            },
+            Instruction::Ior => OwnedInstruction::Ior,
+            Instruction::Irem => OwnedInstruction::Irem,
+            Instruction::Ireturn => OwnedInstruction::Ireturn,
+            Instruction::Ishl => OwnedInstruction::Ishl,
+            Instruction::Ishr => OwnedInstruction::Ishr,
+            Instruction::Istore { index } => OwnedInstruction::Istore { index },
+            Instruction::Istore0 => OwnedInstruction::Istore0,
+            Instruction::Istore1 => OwnedInstruction::Istore1,
+            Instruction::Istore2 => OwnedInstruction::Istore2,
+            Instruction::Istore3 => OwnedInstruction::Istore3,
+            Instruction::Isub => OwnedInstruction::Isub,
+            Instruction::Iushr => OwnedInstruction::Iushr,
+            Instruction::Ixor => OwnedInstruction::Ixor,
+            Instruction::Jsr { offset } => OwnedInstruction::Jsr { offset },
+            Instruction::JsrW { offset } => OwnedInstruction::JsrW { offset },
+            Instruction::L2d => OwnedInstruction::L2d,
+            Instruction::L2f => OwnedInstruction::L2f,
+            Instruction::L2i => OwnedInstruction::L2i,
+            Instruction::Ladd => OwnedInstruction::Ladd,
+            Instruction::Laload => OwnedInstruction::Laload,
+            Instruction::Land => OwnedInstruction::Land,
+            Instruction::Lastore => OwnedInstruction::Lastore,
+            Instruction::Lcmp => OwnedInstruction::Lcmp,
+            Instruction::Lconst0 => OwnedInstruction::Lconst0,
+            Instruction::Lconst1 => OwnedInstruction::Lconst1,
+            Instruction::Ldc { index, constant } => OwnedInstruction::Ldc {
+                index,
+                constant: constant.into_owned()?,
+            },
+            Instruction::LdcW { index, constant } => OwnedInstruction::LdcW {
+                index,
+                constant: constant.into_owned()?,
+            },
+            Instruction::Ldc2W { index, constant } => OwnedInstruction::Ldc2W {
+                index,
+                constant: constant.into_owned()?,
+            },
+            Instruction::Ldiv => OwnedInstruction::Ldiv,
+            Instruction::Lload { index } => OwnedInstruction::Lload { index },
+            Instruction::Lload0 => OwnedInstruction::Lload0,
+            Instruction::Lload1 => OwnedInstruction::Lload1,
+            Instruction::Lload2 => OwnedInstruction::Lload2,
+            Instruction::Lload3 => OwnedInstruction::Lload3,
+            Instruction::Lmul => OwnedInstruction::Lmul,
+            Instruction::Lneg => OwnedInstruction::Lneg,
+            Instruction::Lookupswitch { default, pairs, .. } => OwnedInstruction::Lookupswitch { default, pairs },
+            Instruction::Lor => OwnedInstruction::Lor,
+            Instruction::Lrem => OwnedInstruction::Lrem,
+            Instruction::Lreturn => OwnedInstruction::Lreturn,
+            Instruction::Lshl => OwnedInstruction::Lshl,
+            Instruction::Lshr => OwnedInstruction::Lshr,
+            Instruction::Lstore { index } => OwnedInstruction::Lstore { index },
+            Instruction::Lstore0 => OwnedInstruction::Lstore0,
+            Instruction::Lstore1 => OwnedInstruction::Lstore1,
+            Instruction::Lstore2 => OwnedInstruction::Lstore2,
+            Instruction::Lstore3 => OwnedInstruction::Lstore3,
+            Instruction::Lsub => OwnedInstruction::Lsub,
+            Instruction::Lushr => OwnedInstruction::Lushr,
+            Instruction::Lxor => OwnedInstruction::Lxor,
+            Instruction::Monitorenter => OwnedInstruction::Monitorenter,
+            Instruction::Monitorexit => OwnedInstruction::Monitorexit,
+            Instruction::Multianewarray { class, dimensions } => OwnedInstruction::Multianewarray {
+                class: class.to_string(),
+                dimensions,
+            },
+            Instruction::New { class } => OwnedInstruction::New {
+                class: class.to_string(),
+            },
+            Instruction::Newarray { atype } => OwnedInstruction::Newarray { atype },
+            Instruction::Nop => OwnedInstruction::Nop,
+            Instruction::Pop => OwnedInstruction::Pop,
+            Instruction::Pop2 => OwnedInstruction::Pop2,
+            Instruction::Putfield { field } => OwnedInstruction::Putfield {
+                field: field.into_owned(),
+            },
+            Instruction::Putstatic { field } => OwnedInstruction::Putstatic {
+                field: field.into_owned(),
+            },
+            Instruction::Ret { index } => OwnedInstruction::Ret { index },
+            Instruction::Return => OwnedInstruction::Return,
+            Instruction::Saload => OwnedInstruction::Saload,
+            Instruction::Sastore => OwnedInstruction::Sastore,
+            Instruction::Sipush { value } => OwnedInstruction::Sipush { value },
+            Instruction::Swap => OwnedInstruction::Swap,
+            Instruction::Tableswitch { default, low, high, jump_offsets, .. } => OwnedInstruction::Tableswitch { default, low, high, jump_offsets },
+            Instruction::WideAload { index } => OwnedInstruction::WideAload { index },
+            Instruction::WideAstore { index } => OwnedInstruction::WideAstore { index },
+            Instruction::WideDload { index } => OwnedInstruction::WideDload { index },
+            Instruction::WideDstore { index } => OwnedInstruction::WideDstore { index },
+            Instruction::WideFload { index } => OwnedInstruction::WideFload { index },
+            Instruction::WideFstore { index } => OwnedInstruction::WideFstore { index },
+            Instruction::WideIinc { index, constant } => OwnedInstruction::WideIinc { index, constant },
+            Instruction::WideIload { index } => OwnedInstruction::WideIload { index },
+            Instruction::WideIstore { index } => OwnedInstruction::WideIstore { index },
+            Instruction::WideLload { index } => OwnedInstruction::WideLload { index },
+            Instruction::WideLstore { index } => OwnedInstruction::WideLstore { index },
+            Instruction::WideRet { index } => OwnedInstruction::WideRet { index },
+            Instruction::Unknown { opcode, pc } => OwnedInstruction::Unknown { opcode, pc },
+        })
+    }
+}
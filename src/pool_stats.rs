@@ -0,0 +1,71 @@
+//! Constant pool composition and unused entries, see
+//! [`ClassFile::constant_pool_stats`].
+
+use std::collections::BTreeMap;
+
+use crate::{
+    canonical::{item, references, Walker},
+    raw::ConstantPoolItem,
+    ClassFile, Result,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct ConstantPoolStats {
+    /// The number of entries, not counting the unusable slot after each
+    /// `Long` and `Double`.
+    pub entries: usize,
+    /// The number of entries of each kind, keyed by [`ConstantPoolItem::kind`].
+    pub counts: BTreeMap<&'static str, usize>,
+    /// The size of all `Utf8` contents, as stored in the class file.
+    pub utf8_bytes: usize,
+    /// Entries referenced by neither the class structure, its attributes and
+    /// code, nor other entries in use.
+    pub unused: Vec<u16>,
+    /// Attributes the crate cannot look into. Entries only they refer to are
+    /// reported as unused.
+    pub unknown_attributes: Vec<String>,
+}
+
+impl ClassFile {
+    /// Counts the constant pool entries by kind and finds those nothing
+    /// refers to, such as leftovers from shrinking or padding added by
+    /// obfuscators.
+    pub fn constant_pool_stats(&self) -> Result<ConstantPoolStats> {
+        let cpool = &self.constant_pool;
+        let mut used = vec![false; cpool.0.len()];
+        let mut walker = Walker {
+            cpool,
+            map: |index: u16, _| {
+                let mut pending = vec![index];
+                while let Some(index) = pending.pop() {
+                    let item = item(cpool, index)?;
+                    let slot = &mut used[index as usize - 1];
+                    if !*slot {
+                        *slot = true;
+                        pending.extend(references(item)?);
+                    }
+                }
+                Ok(index)
+            },
+            unknown: Vec::new(),
+        };
+        walker.class(self)?;
+        let unknown_attributes = walker.unknown;
+
+        let mut stats = ConstantPoolStats {
+            unknown_attributes,
+            ..Default::default()
+        };
+        for (index, x) in self.constant_pool() {
+            stats.entries += 1;
+            *stats.counts.entry(x.kind()).or_default() += 1;
+            if let ConstantPoolItem::Utf8 { bytes, .. } = x {
+                stats.utf8_bytes += bytes.len();
+            }
+            if !used[index as usize - 1] {
+                stats.unused.push(index);
+            }
+        }
+        Ok(stats)
+    }
+}
@@ -1,296 +1,691 @@
-use std::{collections::HashMap, fmt::Debug};
-
-use binrw::{binread, BinRead};
-
-bitflags::bitflags! {
-    #[derive(Debug)]
-    pub struct ClassAccessFlags: u16 {
-        const PUBLIC     = 0x0001;
-        const FINAL      = 0x0010;
-        const SUPER      = 0x0020;
-        const INTERFACE  = 0x0200;
-        const ABSTRACT   = 0x0400;
-        const SYNTHETIC  = 0x1000;
-        const ANNOTATION = 0x2000;
-        const ENUM       = 0x4000;
-        const MODULE     = 0x8000;
-    }
-}
-
-#[derive(Debug)]
-pub struct ConstantPool(pub Vec<ConstantPoolItem>);
-
-impl BinRead for ConstantPool {
-    type Args<'a> = ();
-
-    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
-        reader: &mut R,
-        _endian: binrw::Endian,
-        _args: Self::Args<'_>,
-    ) -> binrw::prelude::BinResult<Self> {
-        let cpool_count = u16::read_be(reader)?;
-        let mut cpool = Vec::new();
-        let mut i = 1;
-        loop {
-            if i >= cpool_count {
-                break;
-            }
-            let pos = reader.stream_position()?;
-            let item = ConstantPoolItem::read_be(reader)?;
-            let bump = match &item {
-                ConstantPoolItem::Long { .. } | ConstantPoolItem::Double { .. } => 2,
-                ConstantPoolItem::Skip => {
-                    return Err(binrw::Error::AssertFail {
-                        pos,
-                        message: format!("Invalid Constant Pool Item."),
-                    })
-                }
-                _ => 1,
-            };
-            cpool.push(item);
-            for _ in 1..bump {
-                cpool.push(ConstantPoolItem::Skip);
-            }
-
-            i += bump;
-        }
-        Ok(Self(cpool))
-    }
-}
-
-#[binread]
-#[derive(Debug)]
-pub enum ConstantPoolItem {
-    #[doc = "CONSTANT_Class as defined in §4.4.1"]
-    #[br(magic = 7u8)]
-    Class { name_index: Utf8Index },
-    #[doc = "CONSTANT_Fieldref as defined in §4.4.2"]
-    #[br(magic = 9u8)]
-    Fieldref {
-        class_index: ClassIndex,
-        name_and_type_index: NameAndTypeIndex,
-    },
-    #[doc = "CONSTANT_Methodref as defined in §4.4.2"]
-    #[br(magic = 10u8)]
-    Methodref {
-        class_index: ClassIndex,
-        name_and_type_index: NameAndTypeIndex,
-    },
-    #[doc = "CONSTANT_InterfaceMethodref as defined in §4.4.2"]
-    #[br(magic = 11u8)]
-    InterfaceMethodref {
-        class_index: ClassIndex,
-        name_and_type_index: NameAndTypeIndex,
-    },
-    #[doc = "CONSTANT_String as defined in §4.4.3"]
-    #[br(magic = 8u8)]
-    String { string_index: Utf8Index },
-    #[doc = "CONSTANT_Integer as defined in §4.4.4"]
-    #[br(magic = 3u8)]
-    Integer { value: i32 },
-    #[doc = "CONSTANT_Float as defined in §4.4.4"]
-    #[br(magic = 4u8)]
-    Float { value: f32 },
-    #[doc = "CONSTANT_Long as defined in §4.4.5"]
-    #[br(magic = 5u8)]
-    Long { value: i64 },
-    #[doc = "CONSTANT_Double as defined in §4.4.5"]
-    #[br(magic = 6u8)]
-    Double { value: f64 },
-    #[doc = "CONSTANT_NameAndType as defined in §4.4.6"]
-    #[br(magic = 12u8)]
-    NameAndType {
-        name_index: Utf8Index,
-        descriptor_index: Utf8Index,
-    },
-    #[doc = "CONSTANT_Utf8 as defined in §4.4.7"]
-    #[br(magic = 1u8)]
-    Utf8 {
-        #[br(temp)]
-        length: u16,
-        #[br(count = length, try_map = |x: Vec<u8>| String::from_utf8(x))]
-        value: String,
-    },
-    #[doc = "CONSTANT_MethodHandle as defined in §4.4.8"]
-    #[br(magic = 15u8)]
-    MethodHandle { reference: Reference },
-    #[doc = "CONSTANT_MethodType as defined in §4.4.9"]
-    #[br(magic = 16u8)]
-    MethodType { descriptor_index: Utf8Index },
-    #[doc = "CONSTANT_Dynamic as defined in §4.4.10"]
-    #[br(magic = 17u8)]
-    Dynamic {
-        bootstrap_method_attr_index: BootstrapMethodAttrInfo,
-        name_and_type_index: NameAndTypeIndex,
-    },
-    #[doc = "CONSTANT_InvokeDynamic as defined in §4.4.10"]
-    #[br(magic = 18u8)]
-    InvokeDynamic {
-        bootstrap_method_attr_index: BootstrapMethodAttrInfo,
-        name_and_type_index: NameAndTypeIndex,
-    },
-    #[doc = "CONSTANT_Module as defined in §4.4.11"]
-    #[br(magic = 19u8)]
-    Module { name_index: Utf8Index },
-    #[doc = "CONSTANT_Package as defined in §4.4.12"]
-    #[br(magic = 20u8)]
-    Package { name_index: Utf8Index },
-
-    #[doc = "Catch all. Used after Double and Long to give a constant off-by-one for constant pool indexing."]
-    Skip,
-}
-
-macro_rules! index_ty {
-    ($name:ident { $cpool:ident, $($inner:ident),* } => { $($t:tt)* }) => {
-        paste::paste! {
-            #[binread]
-            #[derive(Debug)]
-            pub struct [<$name Index>] (pub u16);
-
-            impl [<$name Index>] {
-                pub fn get_as_string<'a>(&self, class: &'a super::ClassFile) -> super::Result<&'a str> {
-                    self.get_as_string_impl(&class.constant_pool)
-                }
-
-                pub(crate) fn get_as_string_impl<'a>(&self, $cpool: &'a ConstantPool) -> super::Result<&'a str> {
-                    match &$cpool.0[self.0 as usize - 1] {
-                        ConstantPoolItem::$name { $($inner),* } => {
-                            Ok($($t)*)
-                        }
-                        x => Err(super::Error::ConstantPoolError(format!("expected {}, found {:?}", stringify!($name), x)))
-                    }
-                }
-            }
-        }
-    };
-}
-
-index_ty!(Utf8 { cpool, value } => { value.as_str() });
-index_ty!(Class { cpool, name_index } => { name_index.get_as_string_impl(cpool)? });
-index_ty!(NameAndType { cpool, name_index, descriptor_index } => { name_index.get_as_string_impl(cpool)? });
-index_ty!(MethodHandle { cpool, reference } => { "" });
-index_ty!(InvokeDynamic { cpool, bootstrap_method_attr_index, name_and_type_index } => { "" });
-
-impl NameAndTypeIndex {
-    pub fn get_name<'a>(&self, class: &'a super::ClassFile) -> super::Result<&'a str> {
-        match &class.constant_pool.0[self.0 as usize - 1] {
-            ConstantPoolItem::NameAndType { name_index, .. } => {
-                name_index.get_as_string(class)
-            }
-            x => Err(super::Error::ConstantPoolError(format!("expected NameAndType, found {:?}", x)))
-        }
-    }
-
-    pub fn get_descriptor<'a>(&self, class: &'a super::ClassFile) -> super::Result<&'a str> {
-        match &class.constant_pool.0[self.0 as usize - 1] {
-            ConstantPoolItem::NameAndType { descriptor_index, .. } => {
-                descriptor_index.get_as_string(class)
-            }
-            x => Err(super::Error::ConstantPoolError(format!("expected NameAndType, found {:?}", x)))
-        }
-    }
-}
-
-impl InvokeDynamicIndex {
-    
-}
-
-#[binread]
-#[derive(Debug)]
-pub struct BootstrapMethodAttrInfo(pub(crate) u16);
-
-#[binread]
-#[derive(Debug)]
-pub struct Reference {
-    pub kind: u8,
-    pub index: u16,
-}
-
-bitflags::bitflags! {
-    #[derive(Debug)]
-    pub struct FieldAccessFlags: u16 {
-        const PUBLIC    = 0x0001;
-        const PRIVATE   = 0x0002;
-        const PROTECTED = 0x0004;
-        const STATIC    = 0x0008;
-        const FINAL     = 0x0010;
-        const VOLATILE  = 0x0040;
-        const TRANSIENT = 0x0080;
-        const SYNTHETIC = 0x1000;
-        const ENUM      = 0x4000;
-    }
-}
-
-#[binread]
-#[br(import(cpool: &ConstantPool))]
-#[derive(Debug)]
-pub struct FieldRaw {
-    #[br(map = |x: u16| FieldAccessFlags::from_bits_truncate(x))]
-    pub access_flags: FieldAccessFlags,
-    pub name_index: Utf8Index,
-    pub descriptor_index: Utf8Index,
-    #[br(args(cpool,))]
-    pub attributes: Attributes,
-}
-
-bitflags::bitflags! {
-    #[derive(Debug)]
-    pub struct MethodAccessFlags: u16 {
-        const PUBLIC       = 0x0001;
-        const PRIVATE      = 0x0002;
-        const PROTECTED    = 0x0004;
-        const STATIC       = 0x0008;
-        const FINAL        = 0x0010;
-        const SYNCHRONIZED = 0x0020;
-        const BRIDGE       = 0x0040;
-        const VARARGS      = 0x0080;
-        const NATIVE       = 0x0100;
-        const ABSTRACT     = 0x4000;
-        const STRICT       = 0x8000;
-        const SYNTHETIC    = 0x1000;
-    }
-}
-
-#[binread]
-#[br(import(cpool: &ConstantPool))]
-#[derive(Debug)]
-pub struct MethodRaw {
-    #[br(map = |x: u16| MethodAccessFlags::from_bits_truncate(x))]
-    pub access_flags: MethodAccessFlags,
-    pub name_index: Utf8Index,
-    pub descriptor_index: Utf8Index,
-    #[br(args(cpool,))]
-    pub attributes: Attributes,
-}
-
-pub struct Attributes(pub(crate) HashMap<String, Vec<u8>>);
-
-impl Debug for Attributes {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("Attributes").field(&self.0.keys().collect::<Vec<_>>()).finish()
-    }
-}
-
-impl BinRead for Attributes {
-    type Args<'a> = (&'a ConstantPool,);
-
-    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
-        reader: &mut R,
-        _endian: binrw::Endian,
-        (cpool,): Self::Args<'_>,
-    ) -> binrw::prelude::BinResult<Self> {
-        let attributes_count = u16::read_be(reader)?;
-        let attributes = (0..attributes_count)
-            .map(|_| {
-                let attribute_name = Utf8Index::read_be(reader)?
-                    .get_as_string_impl(cpool)
-                    .unwrap_or("");
-                let attribute_len = u32::read_be(reader)?;
-                let mut info = vec![0u8; attribute_len as usize];
-                reader.read_exact(&mut info)?;
-                Ok::<(String, Vec<u8>), binrw::Error>((attribute_name.to_string(), info))
-            })
-            .collect::<std::result::Result<HashMap<_, _>, _>>()?;
-        Ok(Self(attributes))
-    }
-}
\ No newline at end of file
+use std::{
+    cell::RefCell,
+    fmt::Debug,
+    io::{Read, Seek, SeekFrom},
+    time::Instant,
+};
+
+use binrw::{binread, BinRead};
+
+use crate::{
+    hexdump::{RawAttribute, DECODED_ATTRIBUTES},
+    metrics::Phase,
+    mutf8,
+    span::Span,
+    trace,
+    options::{AttributeFilter, Leniency, ParseOptions, ParseWarning},
+};
+
+bitflags::bitflags! {
+    #[derive(Debug)]
+    pub struct ClassAccessFlags: u16 {
+        const PUBLIC     = 0x0001;
+        const FINAL      = 0x0010;
+        const SUPER      = 0x0020;
+        const INTERFACE  = 0x0200;
+        const ABSTRACT   = 0x0400;
+        const SYNTHETIC  = 0x1000;
+        const ANNOTATION = 0x2000;
+        const ENUM       = 0x4000;
+        const MODULE     = 0x8000;
+    }
+}
+
+/// The pool entries, and the bytes each was read from.
+#[derive(Debug)]
+pub struct ConstantPool(pub Vec<ConstantPoolItem>, pub(crate) Vec<Span>);
+
+impl ConstantPool {
+    /// The entry at `index`, failing on 0 and indices past the end of the pool.
+    pub(crate) fn get(&self, index: u16) -> super::Result<&ConstantPoolItem> {
+        (index as usize)
+            .checked_sub(1)
+            .and_then(|x| self.0.get(x))
+            .ok_or_else(|| super::Error::ConstantPoolError(format!("index {} out of range", index)))
+    }
+}
+
+impl BinRead for ConstantPool {
+    type Args<'a> = (&'a ParseOptions, Option<&'a RefCell<Vec<ParseWarning>>>);
+
+    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
+        reader: &mut R,
+        _endian: binrw::Endian,
+        (options, warnings): Self::Args<'_>,
+    ) -> binrw::prelude::BinResult<Self> {
+        let cpool_count = u16::read_be(reader)?;
+        trace::span!(DEBUG, "constant_pool", count = cpool_count);
+        let start = Instant::now();
+        let mut cpool = Vec::new();
+        let mut spans = Vec::new();
+        let mut i = 1;
+        loop {
+            if i >= cpool_count {
+                break;
+            }
+            let pos = reader.stream_position()?;
+            let item = ConstantPoolItem::read_be(reader)?;
+            let (item, bump) = match item {
+                ConstantPoolItem::Long { .. } | ConstantPoolItem::Double { .. } => (item, 2),
+                ConstantPoolItem::Skip => match (u8::read_be(reader)?, &options.constant_pool_hook) {
+                    (1, _) if options.leniency.lossy_utf8 => {
+                        let length = u16::read_be(reader)?;
+                        let bytes = read_bytes(reader, length as u64)?;
+                        if let Some(warnings) = warnings {
+                            warnings
+                                .borrow_mut()
+                                .push(ParseWarning::InvalidUtf8 { index: i });
+                        }
+                        let value = mutf8::decode_lossy(&bytes);
+                        (ConstantPoolItem::Utf8 { bytes, value }, 1)
+                    }
+                    (1, _) => {
+                        let length = u16::read_be(reader)?;
+                        let bytes = read_bytes(reader, length as u64)?;
+                        let offset = pos + 3 + mutf8::invalid_at(&bytes).unwrap_or(0) as u64;
+                        return Err(binrw::Error::Custom {
+                            pos,
+                            err: Box::new(crate::Error::InvalidUtf8 { index: i, offset }),
+                        });
+                    }
+                    (tag, Some(hook)) => {
+                        let custom = hook(tag, reader).map_err(|x| binrw::Error::Custom {
+                            pos,
+                            err: Box::new(x),
+                        })?;
+                        let bump = custom.slots.max(1) as u16;
+                        (ConstantPoolItem::Custom { tag, data: custom.data }, bump)
+                    }
+                    (_, None) => {
+                        return Err(binrw::Error::AssertFail {
+                            pos,
+                            message: format!("Invalid Constant Pool Item."),
+                        })
+                    }
+                },
+                _ => (item, 1),
+            };
+            trace::event!(TRACE, index = i, offset = pos, kind = item.kind());
+            let end = reader.stream_position()?;
+            cpool.push(item);
+            spans.push(Span::between(pos, end));
+            for _ in 1..bump {
+                cpool.push(ConstantPoolItem::Skip);
+                spans.push(Span::new(end, 0));
+            }
+
+            // Entries are numbered by a u16, so a wide entry or one of a hook's
+            // can claim slots that do not exist.
+            i = i.checked_add(bump).ok_or_else(|| binrw::Error::AssertFail {
+                pos,
+                message: format!("constant pool entry {} takes {} slots, past index 65535", i, bump),
+            })?;
+        }
+        if let Some(metrics) = &options.metrics {
+            metrics.phase_finished(Phase::ConstantPool, start.elapsed());
+        }
+        Ok(Self(cpool, spans))
+    }
+}
+
+#[binread]
+#[derive(Debug, Clone)]
+pub enum ConstantPoolItem {
+    #[doc = "CONSTANT_Class as defined in §4.4.1"]
+    #[br(magic = 7u8)]
+    Class { name_index: Utf8Index },
+    #[doc = "CONSTANT_Fieldref as defined in §4.4.2"]
+    #[br(magic = 9u8)]
+    Fieldref {
+        class_index: ClassIndex,
+        name_and_type_index: NameAndTypeIndex,
+    },
+    #[doc = "CONSTANT_Methodref as defined in §4.4.2"]
+    #[br(magic = 10u8)]
+    Methodref {
+        class_index: ClassIndex,
+        name_and_type_index: NameAndTypeIndex,
+    },
+    #[doc = "CONSTANT_InterfaceMethodref as defined in §4.4.2"]
+    #[br(magic = 11u8)]
+    InterfaceMethodref {
+        class_index: ClassIndex,
+        name_and_type_index: NameAndTypeIndex,
+    },
+    #[doc = "CONSTANT_String as defined in §4.4.3"]
+    #[br(magic = 8u8)]
+    String { string_index: Utf8Index },
+    #[doc = "CONSTANT_Integer as defined in §4.4.4"]
+    #[br(magic = 3u8)]
+    Integer { value: i32 },
+    #[doc = "CONSTANT_Float as defined in §4.4.4"]
+    #[br(magic = 4u8)]
+    Float { value: f32 },
+    #[doc = "CONSTANT_Long as defined in §4.4.5"]
+    #[br(magic = 5u8)]
+    Long { value: i64 },
+    #[doc = "CONSTANT_Double as defined in §4.4.5"]
+    #[br(magic = 6u8)]
+    Double { value: f64 },
+    #[doc = "CONSTANT_NameAndType as defined in §4.4.6"]
+    #[br(magic = 12u8)]
+    NameAndType {
+        name_index: Utf8Index,
+        descriptor_index: Utf8Index,
+    },
+    #[doc = "CONSTANT_Utf8 as defined in §4.4.7"]
+    #[br(magic = 1u8)]
+    Utf8 {
+        #[br(temp)]
+        length: u16,
+        #[doc = "The modified UTF-8 bytes as stored in the class file."]
+        #[br(count = length)]
+        bytes: Vec<u8>,
+        #[br(try_calc = mutf8::decode(&bytes).ok_or("invalid modified UTF-8"))]
+        value: String,
+    },
+    #[doc = "CONSTANT_MethodHandle as defined in §4.4.8"]
+    #[br(magic = 15u8)]
+    MethodHandle { reference: Reference },
+    #[doc = "CONSTANT_MethodType as defined in §4.4.9"]
+    #[br(magic = 16u8)]
+    MethodType { descriptor_index: Utf8Index },
+    #[doc = "CONSTANT_Dynamic as defined in §4.4.10"]
+    #[br(magic = 17u8)]
+    Dynamic {
+        bootstrap_method_attr_index: BootstrapMethodAttrInfo,
+        name_and_type_index: NameAndTypeIndex,
+    },
+    #[doc = "CONSTANT_InvokeDynamic as defined in §4.4.10"]
+    #[br(magic = 18u8)]
+    InvokeDynamic {
+        bootstrap_method_attr_index: BootstrapMethodAttrInfo,
+        name_and_type_index: NameAndTypeIndex,
+    },
+    #[doc = "CONSTANT_Module as defined in §4.4.11"]
+    #[br(magic = 19u8)]
+    Module { name_index: Utf8Index },
+    #[doc = "CONSTANT_Package as defined in §4.4.12"]
+    #[br(magic = 20u8)]
+    Package { name_index: Utf8Index },
+
+    #[doc = "A non-standard entry decoded by a user supplied ConstantPoolHook."]
+    #[br(pre_assert(false))]
+    Custom {
+        #[br(ignore)]
+        tag: u8,
+        #[br(ignore)]
+        data: Vec<u8>,
+    },
+
+    #[doc = "Catch all. Used after Double and Long to give a constant off-by-one for constant pool indexing."]
+    Skip,
+}
+
+impl ConstantPoolItem {
+    /// The name of the entry's kind, e.g. `"Integer"` for CONSTANT_Integer.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Class { .. } => "Class",
+            Self::Fieldref { .. } => "Fieldref",
+            Self::Methodref { .. } => "Methodref",
+            Self::InterfaceMethodref { .. } => "InterfaceMethodref",
+            Self::String { .. } => "String",
+            Self::Integer { .. } => "Integer",
+            Self::Float { .. } => "Float",
+            Self::Long { .. } => "Long",
+            Self::Double { .. } => "Double",
+            Self::NameAndType { .. } => "NameAndType",
+            Self::Utf8 { .. } => "Utf8",
+            Self::MethodHandle { .. } => "MethodHandle",
+            Self::MethodType { .. } => "MethodType",
+            Self::Dynamic { .. } => "Dynamic",
+            Self::InvokeDynamic { .. } => "InvokeDynamic",
+            Self::Module { .. } => "Module",
+            Self::Package { .. } => "Package",
+            Self::Custom { .. } => "Custom",
+            Self::Skip => "Skip",
+        }
+    }
+}
+
+macro_rules! index_ty {
+    ($name:ident { $cpool:ident, $($inner:ident),* } => { $($t:tt)* }) => {
+        paste::paste! {
+            #[binread]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct [<$name Index>] (pub u16);
+
+            impl [<$name Index>] {
+                pub fn get_as_string<'a>(&self, class: &'a super::ClassFile) -> super::Result<&'a str> {
+                    self.get_as_string_impl(&class.constant_pool)
+                }
+
+                pub(crate) fn get_as_string_impl<'a>(&self, $cpool: &'a ConstantPool) -> super::Result<&'a str> {
+                    match $cpool.get(self.0)? {
+                        ConstantPoolItem::$name { $($inner),*, .. } => {
+                            Ok($($t)*)
+                        }
+                        x => Err(super::Error::ConstantPoolError(format!("expected {}, found {:?}", stringify!($name), x)))
+                    }
+                }
+            }
+        }
+    };
+}
+
+index_ty!(Utf8 { cpool, value } => { value.as_str() });
+index_ty!(Class { cpool, name_index } => { name_index.get_as_string_impl(cpool)? });
+index_ty!(NameAndType { cpool, name_index, descriptor_index } => { name_index.get_as_string_impl(cpool)? });
+index_ty!(MethodHandle { cpool, reference } => { "" });
+index_ty!(InvokeDynamic { cpool, bootstrap_method_attr_index, name_and_type_index } => { "" });
+index_ty!(Module { cpool, name_index } => { name_index.get_as_string_impl(cpool)? });
+index_ty!(Package { cpool, name_index } => { name_index.get_as_string_impl(cpool)? });
+
+macro_rules! plain_index_ty {
+    ($($name:ident),*) => {
+        paste::paste! {
+            $(
+                #[doc = "The index of a CONSTANT_" $name " entry."]
+                #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+                pub struct [<$name Index>] (pub u16);
+            )*
+        }
+    };
+}
+
+plain_index_ty!(
+    Fieldref,
+    Methodref,
+    InterfaceMethodref,
+    String,
+    Integer,
+    Float,
+    Long,
+    Double,
+    MethodType,
+    Dynamic
+);
+
+impl NameAndTypeIndex {
+    pub fn get_name<'a>(&self, class: &'a super::ClassFile) -> super::Result<&'a str> {
+        match class.constant_pool.get(self.0)? {
+            ConstantPoolItem::NameAndType { name_index, .. } => {
+                name_index.get_as_string(class)
+            }
+            x => Err(super::Error::ConstantPoolError(format!("expected NameAndType, found {:?}", x)))
+        }
+    }
+
+    pub fn get_descriptor<'a>(&self, class: &'a super::ClassFile) -> super::Result<&'a str> {
+        self.get_descriptor_index(class)?.get_as_string(class)
+    }
+
+    pub(crate) fn get_descriptor_index(&self, class: &super::ClassFile) -> super::Result<Utf8Index> {
+        match class.constant_pool.get(self.0)? {
+            ConstantPoolItem::NameAndType { descriptor_index, .. } => Ok(*descriptor_index),
+            x => Err(super::Error::ConstantPoolError(format!("expected NameAndType, found {:?}", x)))
+        }
+    }
+}
+
+impl MethodTypeIndex {
+    /// The parsed descriptor of the method type.
+    pub fn get_descriptor<'a>(&self, class: &'a super::ClassFile) -> super::Result<crate::method::MethodDescriptor<'a>> {
+        match class.constant(self.0) {
+            Some(ConstantPoolItem::MethodType { descriptor_index }) => {
+                class.method_descriptor_at(*descriptor_index)
+            }
+            x => Err(super::Error::ConstantPoolError(format!("expected MethodType, found {:?}", x)))
+        }
+    }
+}
+
+impl Utf8Index {
+    /// The constant as UTF-16 code units, keeping unpaired surrogates.
+    pub(crate) fn get_as_utf16_impl(&self, cpool: &ConstantPool) -> super::Result<Vec<u16>> {
+        match cpool.get(self.0)? {
+            ConstantPoolItem::Utf8 { bytes, .. } => mutf8::to_utf16(bytes)
+                .ok_or_else(|| super::Error::ConstantPoolError(format!("invalid modified UTF-8 at {}", self.0))),
+            x => Err(super::Error::ConstantPoolError(format!("expected Utf8, found {:?}", x)))
+        }
+    }
+}
+
+impl InvokeDynamicIndex {
+    
+}
+
+/// An index into the `BootstrapMethods` attribute, rather than the pool.
+#[binread]
+#[derive(Debug, Clone)]
+pub struct BootstrapMethodAttrInfo(pub u16);
+
+#[binread]
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub kind: u8,
+    pub index: u16,
+}
+
+bitflags::bitflags! {
+    #[derive(Debug)]
+    pub struct FieldAccessFlags: u16 {
+        const PUBLIC    = 0x0001;
+        const PRIVATE   = 0x0002;
+        const PROTECTED = 0x0004;
+        const STATIC    = 0x0008;
+        const FINAL     = 0x0010;
+        const VOLATILE  = 0x0040;
+        const TRANSIENT = 0x0080;
+        const SYNTHETIC = 0x1000;
+        const ENUM      = 0x4000;
+    }
+}
+
+#[binread]
+#[br(import(cpool: &ConstantPool, leniency: Leniency, warnings: Option<&RefCell<Vec<ParseWarning>>>, filter: Option<&AttributeFilter>))]
+#[derive(Debug)]
+pub struct FieldRaw {
+    #[br(map = |x: u16| FieldAccessFlags::from_bits_retain(x))]
+    pub access_flags: FieldAccessFlags,
+    pub name_index: Utf8Index,
+    pub descriptor_index: Utf8Index,
+    #[br(args(cpool, leniency, warnings, filter))]
+    pub attributes: Attributes,
+    #[br(ignore)]
+    pub span: Span,
+}
+
+bitflags::bitflags! {
+    #[derive(Debug)]
+    pub struct MethodAccessFlags: u16 {
+        const PUBLIC       = 0x0001;
+        const PRIVATE      = 0x0002;
+        const PROTECTED    = 0x0004;
+        const STATIC       = 0x0008;
+        const FINAL        = 0x0010;
+        const SYNCHRONIZED = 0x0020;
+        const BRIDGE       = 0x0040;
+        const VARARGS      = 0x0080;
+        const NATIVE       = 0x0100;
+        const ABSTRACT     = 0x0400;
+        const STRICT       = 0x0800;
+        const SYNTHETIC    = 0x1000;
+    }
+}
+
+#[binread]
+#[br(import(cpool: &ConstantPool, leniency: Leniency, warnings: Option<&RefCell<Vec<ParseWarning>>>, filter: Option<&AttributeFilter>))]
+#[derive(Debug)]
+pub struct MethodRaw {
+    #[br(map = |x: u16| MethodAccessFlags::from_bits_retain(x))]
+    pub access_flags: MethodAccessFlags,
+    pub name_index: Utf8Index,
+    pub descriptor_index: Utf8Index,
+    #[br(args(cpool, leniency, warnings, filter))]
+    pub attributes: Attributes,
+    #[br(ignore)]
+    pub span: Span,
+}
+
+/// Implemented by [`FieldRaw`] and [`MethodRaw`] so both can be read by [`read_members`].
+pub(crate) trait Member {
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    const KIND: &'static str;
+
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    fn name_index(&self) -> &Utf8Index;
+
+    fn set_span(&mut self, span: Span);
+}
+
+impl Member for FieldRaw {
+    const KIND: &'static str = "field";
+
+    fn name_index(&self) -> &Utf8Index {
+        &self.name_index
+    }
+
+    fn set_span(&mut self, span: Span) {
+        self.span = span;
+    }
+}
+
+impl Member for MethodRaw {
+    const KIND: &'static str = "method";
+
+    fn name_index(&self) -> &Utf8Index {
+        &self.name_index
+    }
+
+    fn set_span(&mut self, span: Span) {
+        self.span = span;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn read_members<'a, R, T>(
+    reader: &mut R,
+    endian: binrw::Endian,
+    (count, cpool, leniency, warnings, filter): (
+        u16,
+        &'a ConstantPool,
+        Leniency,
+        Option<&'a RefCell<Vec<ParseWarning>>>,
+        Option<&'a AttributeFilter>,
+    ),
+) -> binrw::BinResult<Vec<T>>
+where
+    R: Read + Seek,
+    T: Member + for<'b> BinRead<Args<'b> = AttributeArgs<'b>>,
+{
+    let mut members = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let start = reader.stream_position()?;
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "member",
+            kind = T::KIND,
+            offset = start,
+            name = tracing::field::Empty
+        )
+        .entered();
+        let mut member = T::read_options(reader, endian, (cpool, leniency, warnings, filter))?;
+        #[cfg(feature = "tracing")]
+        if let Ok(name) = member.name_index().get_as_string_impl(cpool) {
+            span.record("name", name);
+        }
+        member.set_span(Span::between(start, reader.stream_position()?));
+        members.push(member);
+    }
+    Ok(members)
+}
+
+pub(crate) struct AttributeEntry {
+    pub(crate) name_index: u16,
+    pub(crate) name: String,
+    pub(crate) data: Vec<u8>,
+    /// The span of the whole attribute, including its name and length.
+    pub(crate) span: Span,
+}
+
+impl AttributeEntry {
+    pub(crate) fn raw<'a>(&'a self, class_file: &'a crate::ClassFile) -> RawAttribute<'a> {
+        RawAttribute {
+            class_file,
+            name_index: self.name_index,
+            name: &self.name,
+            data: &self.data,
+            span: self.span,
+        }
+    }
+}
+
+/// Raw attributes in the order they appear in the file. Duplicates are kept;
+/// lookups by name see the last one, as a map keyed by name would have.
+pub struct Attributes(pub(crate) Vec<AttributeEntry>);
+
+impl Attributes {
+    /// The last attribute called `name`, as a map keyed by name would have kept.
+    fn entry(&self, name: &str) -> Option<&AttributeEntry> {
+        self.0.iter().rev().find(|x| x.name == name)
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&[u8]> {
+        self.entry(name).map(|x| x.data.as_slice())
+    }
+
+    pub(crate) fn span(&self, name: &str) -> Option<Span> {
+        self.entry(name).map(|x| x.span)
+    }
+
+    /// The offset of the attribute's data, just past its six byte header.
+    pub(crate) fn data_offset(&self, name: &str) -> u64 {
+        self.span(name).map_or(0, |x| x.start + 6)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &AttributeEntry> {
+        self.0.iter()
+    }
+
+    /// Every attribute, duplicates included, in the order they appear in the file.
+    pub(crate) fn raw<'a>(&'a self, class_file: &'a crate::ClassFile) -> Vec<RawAttribute<'a>> {
+        self.iter().map(|x| x.raw(class_file)).collect()
+    }
+
+    /// Attributes without a typed accessor, in the order they appear in the file.
+    pub(crate) fn undecoded<'a>(&'a self, class_file: &'a crate::ClassFile) -> Vec<RawAttribute<'a>> {
+        self.iter()
+            .filter(|x| !DECODED_ATTRIBUTES.contains(&x.name.as_str()))
+            .map(|x| x.raw(class_file))
+            .collect()
+    }
+}
+
+/// Reads nothing, returning the current offset. Used to record spans in derived readers.
+pub(crate) fn stream_position<R: Read + Seek>(
+    reader: &mut R,
+    _endian: binrw::Endian,
+    _args: (),
+) -> binrw::BinResult<u64> {
+    Ok(reader.stream_position()?)
+}
+
+impl Debug for Attributes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Attributes")
+            .field(&self.iter().map(|x| &x.name).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+fn remaining<R: Read + Seek>(reader: &mut R) -> binrw::BinResult<u64> {
+    let pos = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(pos))?;
+    Ok(end.saturating_sub(pos))
+}
+
+/// Fails with [`crate::Error::LengthExceedsInput`] if fewer than `length` bytes
+/// remain, so that a forged length cannot make us allocate more than the input.
+pub(crate) fn ensure_remaining<R: Read + Seek>(reader: &mut R, length: u64) -> binrw::BinResult<()> {
+    let remaining = remaining(reader)?;
+    if length > remaining {
+        let offset = reader.stream_position()?;
+        return Err(binrw::Error::Custom {
+            pos: offset,
+            err: Box::new(crate::Error::LengthExceedsInput {
+                offset,
+                length,
+                remaining,
+            }),
+        });
+    }
+    Ok(())
+}
+
+/// Reads `length` bytes, checking them against the remaining input first.
+pub(crate) fn read_bytes<R: Read + Seek>(reader: &mut R, length: u64) -> binrw::BinResult<Vec<u8>> {
+    ensure_remaining(reader, length)?;
+    let mut bytes = vec![0u8; length as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Reads the `length` bytes of an attribute. With [`Leniency::truncated_attributes`]
+/// a length running past the end of the input yields whatever is left, flagged by
+/// the returned `bool`. Nothing bounds the length by the member or attribute
+/// around it, so only an attribute at the very end of the input can be recovered.
+pub(crate) fn read_attribute_info<R: Read + Seek>(
+    reader: &mut R,
+    length: u32,
+    leniency: Leniency,
+) -> binrw::BinResult<(Vec<u8>, bool)> {
+    let mut length = length as u64;
+    let mut truncated = false;
+    if leniency.truncated_attributes {
+        let remaining = remaining(reader)?;
+        if length > remaining {
+            length = remaining;
+            truncated = true;
+        }
+    }
+    Ok((read_bytes(reader, length)?, truncated))
+}
+
+/// The pool, leniency, warning sink and filter attributes are read with.
+pub(crate) type AttributeArgs<'a> = (
+    &'a ConstantPool,
+    Leniency,
+    Option<&'a RefCell<Vec<ParseWarning>>>,
+    Option<&'a AttributeFilter>,
+);
+
+impl BinRead for Attributes {
+    type Args<'a> = AttributeArgs<'a>;
+
+    fn read_options<R: std::io::prelude::Read + std::io::prelude::Seek>(
+        reader: &mut R,
+        _endian: binrw::Endian,
+        (cpool, leniency, warnings, filter): Self::Args<'_>,
+    ) -> binrw::prelude::BinResult<Self> {
+        let attributes_count = u16::read_be(reader)?;
+        let mut attributes = Vec::new();
+        for _ in 0..attributes_count {
+            let start = reader.stream_position()?;
+            let name_index = Utf8Index::read_be(reader)?;
+            let attribute_name = name_index.get_as_string_impl(cpool).unwrap_or("");
+            let attribute_len = u32::read_be(reader)?;
+            trace::event!(
+                TRACE,
+                name = attribute_name,
+                offset = reader.stream_position()?,
+                length = attribute_len,
+                "attribute"
+            );
+            let (info, truncated) = read_attribute_info(reader, attribute_len, leniency)?;
+            if truncated {
+                if let Some(warnings) = warnings {
+                    warnings.borrow_mut().push(ParseWarning::TruncatedAttribute {
+                        name: attribute_name.to_string(),
+                        length: attribute_len,
+                        available: info.len() as u32,
+                    });
+                }
+            }
+            if filter.is_none_or(|x| x(attribute_name)) {
+                attributes.push(AttributeEntry {
+                    name_index: name_index.0,
+                    name: attribute_name.to_string(),
+                    data: info,
+                    span: Span::between(start, reader.stream_position()?),
+                });
+            }
+            if truncated {
+                break;
+            }
+        }
+        Ok(Self(attributes))
+    }
+}
@@ -0,0 +1,269 @@
+//! Renaming classes and members through the constant pool, see
+//! [`ClassFile::remap`].
+
+use std::collections::HashMap;
+
+use crate::{
+    raw::{ConstantPool, ConstantPoolItem, Utf8Index},
+    ClassFile, Error, NameAndTypeIndex, Result,
+};
+
+/// New member names keyed by owner, name and descriptor, all before renaming.
+/// Nested so that lookups can borrow their keys.
+type MemberRenames = HashMap<String, HashMap<String, HashMap<String, String>>>;
+
+/// The renames applied by [`ClassFile::remap`]. Classes are given by internal
+/// name, e.g. `java/lang/Object`.
+#[derive(Debug, Clone, Default)]
+pub struct Remapper {
+    classes: HashMap<String, String>,
+    packages: Vec<(String, String)>,
+    fields: MemberRenames,
+    methods: MemberRenames,
+}
+
+impl Remapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_class(mut self, from: &str, to: &str) -> Self {
+        self.classes.insert(from.to_string(), to.to_string());
+        self
+    }
+
+    /// Moves every class in package `from` or its subpackages to `to`, e.g.
+    /// `com/example` to `shaded/com/example`. Classes renamed by
+    /// [`Self::with_class`] are left to that.
+    pub fn with_package(mut self, from: &str, to: &str) -> Self {
+        self.packages
+            .push((format!("{}/", from.trim_end_matches('/')), format!("{}/", to.trim_end_matches('/'))));
+        self
+    }
+
+    /// Renames field `name` of type `descriptor` when referenced through
+    /// `owner`. References through a subclass need their own entry.
+    pub fn with_field(mut self, owner: &str, name: &str, descriptor: &str, to: &str) -> Self {
+        insert_member(&mut self.fields, owner, name, descriptor, to);
+        self
+    }
+
+    /// Like [`Self::with_field`], for methods.
+    pub fn with_method(mut self, owner: &str, name: &str, descriptor: &str, to: &str) -> Self {
+        insert_member(&mut self.methods, owner, name, descriptor, to);
+        self
+    }
+
+    /// The new name of class `name`, which may also be an array descriptor as
+    /// found in `CONSTANT_Class`.
+    pub fn class_name(&self, name: &str) -> String {
+        if name.starts_with('[') {
+            return self.descriptor(name);
+        }
+        if let Some(x) = self.classes.get(name) {
+            return x.clone();
+        }
+        for (from, to) in &self.packages {
+            if let Some(rest) = name.strip_prefix(from.as_str()) {
+                return format!("{}{}", to, rest);
+            }
+        }
+        name.to_string()
+    }
+
+    /// `descriptor`, a field or method descriptor, with its class names renamed.
+    pub fn descriptor(&self, descriptor: &str) -> String {
+        let mut res = String::with_capacity(descriptor.len());
+        let mut rest = descriptor;
+        // Outside class names, `L` only ever starts one.
+        while let Some(start) = rest.find('L') {
+            let end = match rest[start..].find(';') {
+                Some(x) => start + x,
+                None => break,
+            };
+            res.push_str(&rest[..=start]);
+            res.push_str(&self.class_name(&rest[start + 1..end]));
+            rest = &rest[end..];
+        }
+        res.push_str(rest);
+        res
+    }
+
+    fn member(&self, renames: &MemberRenames, owner: &str, name: &str, descriptor: &str) -> String {
+        renames
+            .get(owner)
+            .and_then(|x| x.get(name))
+            .and_then(|x| x.get(descriptor))
+            .map_or_else(|| name.to_string(), |x| x.clone())
+    }
+
+    pub fn field_name(&self, owner: &str, name: &str, descriptor: &str) -> String {
+        self.member(&self.fields, owner, name, descriptor)
+    }
+
+    pub fn method_name(&self, owner: &str, name: &str, descriptor: &str) -> String {
+        self.member(&self.methods, owner, name, descriptor)
+    }
+}
+
+fn insert_member(renames: &mut MemberRenames, owner: &str, name: &str, descriptor: &str, to: &str) {
+    renames
+        .entry(owner.to_string())
+        .or_default()
+        .entry(name.to_string())
+        .or_default()
+        .insert(descriptor.to_string(), to.to_string());
+}
+
+fn name_and_type(cpool: &ConstantPool, index: NameAndTypeIndex) -> Result<(&str, &str)> {
+    match (index.0 as usize).checked_sub(1).and_then(|x| cpool.0.get(x)) {
+        Some(ConstantPoolItem::NameAndType {
+            name_index,
+            descriptor_index,
+        }) => Ok((
+            name_index.get_as_string_impl(cpool)?,
+            descriptor_index.get_as_string_impl(cpool)?,
+        )),
+        x => Err(Error::ConstantPoolError(format!("expected NameAndType, found {:?}", x))),
+    }
+}
+
+impl ClassFile {
+    /// The index of the `NameAndType` constant for `name` and `descriptor`,
+    /// adding one if there is none.
+    fn name_and_type_index(&mut self, name: &str, descriptor: &str) -> Result<NameAndTypeIndex> {
+        let name_index = Utf8Index(self.utf8_index(name)?);
+        let descriptor_index = Utf8Index(self.utf8_index(descriptor)?);
+        let existing = self.constant_pool.0.iter().position(|x| match x {
+            ConstantPoolItem::NameAndType {
+                name_index: n,
+                descriptor_index: d,
+            } => *n == name_index && *d == descriptor_index,
+            _ => false,
+        });
+        Ok(NameAndTypeIndex(match existing {
+            Some(x) => x as u16 + 1,
+            None => self.add_constant(ConstantPoolItem::NameAndType {
+                name_index,
+                descriptor_index,
+            })?,
+        }))
+    }
+
+    /// Renames classes, fields and methods throughout the constant pool and
+    /// the class's own members, keeping every index valid.
+    ///
+    /// Entries are never edited where they may be shared, so renaming method
+    /// `a` leaves a string literal `"a"` alone: new `Utf8` and `NameAndType`
+    /// constants are added instead, and those no longer used stay in the pool.
+    /// Strings, and names inside attributes such as `Signature`,
+    /// `LocalVariableTable` and annotations, are not rewritten.
+    pub fn remap(&mut self, remapper: &Remapper) -> Result<()> {
+        let original = ConstantPool(self.constant_pool.0.clone(), Vec::new());
+        for (i, item) in original.0.iter().enumerate() {
+            let new = match item {
+                ConstantPoolItem::Class { name_index } => {
+                    let name = name_index.get_as_string_impl(&original)?;
+                    let new = remapper.class_name(name);
+                    if new == name {
+                        continue;
+                    }
+                    ConstantPoolItem::Class {
+                        name_index: Utf8Index(self.utf8_index(&new)?),
+                    }
+                }
+                ConstantPoolItem::MethodType { descriptor_index } => {
+                    let descriptor = descriptor_index.get_as_string_impl(&original)?;
+                    let new = remapper.descriptor(descriptor);
+                    if new == descriptor {
+                        continue;
+                    }
+                    ConstantPoolItem::MethodType {
+                        descriptor_index: Utf8Index(self.utf8_index(&new)?),
+                    }
+                }
+                ConstantPoolItem::Fieldref {
+                    class_index,
+                    name_and_type_index,
+                }
+                | ConstantPoolItem::Methodref {
+                    class_index,
+                    name_and_type_index,
+                }
+                | ConstantPoolItem::InterfaceMethodref {
+                    class_index,
+                    name_and_type_index,
+                } => {
+                    let owner = class_index.get_as_string_impl(&original)?;
+                    let (name, descriptor) = name_and_type(&original, *name_and_type_index)?;
+                    let new_name = match item {
+                        ConstantPoolItem::Fieldref { .. } => remapper.field_name(owner, name, descriptor),
+                        _ => remapper.method_name(owner, name, descriptor),
+                    };
+                    let new_descriptor = remapper.descriptor(descriptor);
+                    if new_name == name && new_descriptor == descriptor {
+                        continue;
+                    }
+                    let name_and_type_index = self.name_and_type_index(&new_name, &new_descriptor)?;
+                    match item {
+                        ConstantPoolItem::Fieldref { .. } => ConstantPoolItem::Fieldref {
+                            class_index: *class_index,
+                            name_and_type_index,
+                        },
+                        ConstantPoolItem::Methodref { .. } => ConstantPoolItem::Methodref {
+                            class_index: *class_index,
+                            name_and_type_index,
+                        },
+                        _ => ConstantPoolItem::InterfaceMethodref {
+                            class_index: *class_index,
+                            name_and_type_index,
+                        },
+                    }
+                }
+                ConstantPoolItem::Dynamic {
+                    bootstrap_method_attr_index,
+                    name_and_type_index,
+                }
+                | ConstantPoolItem::InvokeDynamic {
+                    bootstrap_method_attr_index,
+                    name_and_type_index,
+                } => {
+                    let (name, descriptor) = name_and_type(&original, *name_and_type_index)?;
+                    let new_descriptor = remapper.descriptor(descriptor);
+                    if new_descriptor == descriptor {
+                        continue;
+                    }
+                    let bootstrap_method_attr_index = bootstrap_method_attr_index.clone();
+                    let name_and_type_index = self.name_and_type_index(name, &new_descriptor)?;
+                    match item {
+                        ConstantPoolItem::Dynamic { .. } => ConstantPoolItem::Dynamic {
+                            bootstrap_method_attr_index,
+                            name_and_type_index,
+                        },
+                        _ => ConstantPoolItem::InvokeDynamic {
+                            bootstrap_method_attr_index,
+                            name_and_type_index,
+                        },
+                    }
+                }
+                _ => continue,
+            };
+            self.constant_pool.0[i] = new;
+        }
+
+        let this_class = self.this_class.get_as_string_impl(&original)?;
+        for i in 0..self.fields.len() {
+            let name = self.fields[i].name_index.get_as_string_impl(&original)?;
+            let descriptor = self.fields[i].descriptor_index.get_as_string_impl(&original)?;
+            self.fields[i].name_index = Utf8Index(self.utf8_index(&remapper.field_name(this_class, name, descriptor))?);
+            self.fields[i].descriptor_index = Utf8Index(self.utf8_index(&remapper.descriptor(descriptor))?);
+        }
+        for i in 0..self.methods.len() {
+            let name = self.methods[i].name_index.get_as_string_impl(&original)?;
+            let descriptor = self.methods[i].descriptor_index.get_as_string_impl(&original)?;
+            self.methods[i].name_index = Utf8Index(self.utf8_index(&remapper.method_name(this_class, name, descriptor))?);
+            self.methods[i].descriptor_index = Utf8Index(self.utf8_index(&remapper.descriptor(descriptor))?);
+        }
+        Ok(())
+    }
+}
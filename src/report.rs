@@ -0,0 +1,505 @@
+//! Browsable documents describing classes, in HTML or Markdown, to read
+//! through a library or attach to a review, see [`ReportGenerator`].
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use crate::{
+    attributes::Annotation,
+    dependencies::DependencyInventory,
+    disassemble::{modifier_words, CLASS_MODIFIERS, FIELD_MODIFIERS, METHOD_MODIFIERS},
+    field::{Field, TypeDescriptor},
+    java_stub::constant_value,
+    method::Method,
+    ClassAccessFlags, ClassFile, MethodAccessFlags, Result,
+};
+
+/// The markup a [`ReportGenerator`] writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// A standalone HTML page.
+    #[default]
+    Html,
+    /// CommonMark, with HTML anchors to link to classes, as GitHub renders
+    /// it.
+    Markdown,
+}
+
+impl ReportFormat {
+    /// The extension of a file holding a document in this format, without
+    /// the dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+/// Writes a document describing a set of classes, such as those of a JAR:
+/// an index of the classes by package, then for each class its declaration,
+/// annotations, fields and methods, the classes it uses and those in the set
+/// that use it, and optionally its disassembly.
+///
+/// Members are written as `javap` writes them, with erased types, each
+/// followed by its generic signature if it has one. Synthetic members are
+/// left out. Every class name in a declaration, an annotation or a
+/// dependency list links to the description of that class if it is in the
+/// set.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReportGenerator {
+    format: ReportFormat,
+    disassembly: bool,
+}
+
+/// A document being written.
+struct Report<'g> {
+    generator: &'g ReportGenerator,
+    /// The classes described, by internal name.
+    defined: BTreeSet<String>,
+    out: String,
+}
+
+/// The anchor of the description of a class, its binary name, e.g.
+/// `java.util.Map$Entry`.
+fn anchor(internal_name: &str) -> String {
+    internal_name.replace('/', ".")
+}
+
+fn html_escaped(text: &str) -> String {
+    let mut res = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// `text` with a backslash before every character Markdown could take as
+/// markup, `$` included as GitHub reads it as the start of math.
+fn markdown_escaped(text: &str) -> String {
+    let mut res = String::new();
+    for c in text.chars() {
+        if "\\`*_{}[]<>()#+-!|~$&".contains(c) {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// The kind of declaration `class_file` is, as the keyword declaring it.
+fn kind_word(class_file: &ClassFile) -> Result<&'static str> {
+    let flags = class_file.access_flags();
+    Ok(if flags.contains(ClassAccessFlags::MODULE) {
+        "module"
+    } else if flags.contains(ClassAccessFlags::ANNOTATION) {
+        "@interface"
+    } else if flags.contains(ClassAccessFlags::INTERFACE) {
+        "interface"
+    } else if flags.contains(ClassAccessFlags::ENUM) {
+        "enum"
+    } else if class_file.record_components()?.is_some() {
+        "record"
+    } else {
+        "class"
+    })
+}
+
+impl ReportGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes `format`, HTML by default.
+    pub fn with_format(mut self, format: ReportFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Whether to end the description of each class with its listing as
+    /// [`ClassFile::disassemble`] writes it. Listings are left out by
+    /// default, as they make documents long.
+    pub fn with_disassembly(mut self, disassembly: bool) -> Self {
+        self.disassembly = disassembly;
+        self
+    }
+
+    pub fn format(&self) -> ReportFormat {
+        self.format
+    }
+
+    /// A document called `title` describing `classes`, in order of their
+    /// names.
+    pub fn report<'c>(&self, title: &str, classes: impl IntoIterator<Item = &'c ClassFile>) -> Result<String> {
+        let mut classes = classes
+            .into_iter()
+            .map(|x| Ok((x.this_class()?, x)))
+            .collect::<Result<Vec<_>>>()?;
+        classes.sort_by_key(|x| x.0);
+
+        // Who uses whom, among the classes described and beyond.
+        let mut uses = BTreeMap::new();
+        let mut used_by: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (name, class_file) in &classes {
+            let inventory = DependencyInventory::from_classes([*class_file])?;
+            let used = inventory.external_classes().map(|x| x.0.to_string()).collect::<BTreeSet<_>>();
+            uses.insert(*name, used);
+        }
+        for (name, used) in &uses {
+            for x in used {
+                if let Some((user, _)) = classes.iter().find(|(y, _)| y == x) {
+                    used_by.entry(user).or_default().insert(name);
+                }
+            }
+        }
+
+        let mut report = Report {
+            generator: self,
+            defined: classes.iter().map(|x| x.0.to_string()).collect(),
+            out: String::new(),
+        };
+        report.start(title)?;
+        let mut packages: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (name, _) in &classes {
+            let package = name.rsplit_once('/').map_or("", |x| x.0);
+            packages.entry(package).or_default().push(name);
+        }
+        report.heading(2, "Classes", None)?;
+        for (package, names) in packages {
+            let package = match package {
+                "" => "(default package)".to_string(),
+                x => anchor(x),
+            };
+            report.heading(3, &package, None)?;
+            let links = names.iter().map(|x| report.class_name(x)).collect::<Vec<_>>();
+            report.list(&links)?;
+        }
+
+        for (name, class_file) in &classes {
+            report.class(class_file, &uses[name], used_by.get(name))?;
+        }
+        report.end()?;
+        Ok(report.out)
+    }
+}
+
+impl Report<'_> {
+    fn format(&self) -> ReportFormat {
+        self.generator.format
+    }
+
+    fn escaped(&self, text: &str) -> String {
+        match self.format() {
+            ReportFormat::Html => html_escaped(text),
+            ReportFormat::Markdown => markdown_escaped(text),
+        }
+    }
+
+    /// The binary name of a class, linking to its description if there is
+    /// one.
+    fn class_name(&self, internal_name: &str) -> String {
+        let text = self.escaped(&anchor(internal_name));
+        if !self.defined.contains(internal_name) {
+            return text;
+        }
+        match self.format() {
+            ReportFormat::Html => format!("<a href=\"#{}\">{}</a>", html_escaped(&anchor(internal_name)), text),
+            ReportFormat::Markdown => format!("[{}](#{})", text, anchor(internal_name)),
+        }
+    }
+
+    /// The text `write` writes with each class name it is given as
+    /// [`Self::class_name`] writes it, and the rest escaped.
+    fn linked(&self, write: impl FnOnce(&mut dyn FnMut(&str) -> String) -> String) -> String {
+        // Names are swapped for markers that survive escaping, then for links.
+        let mut names = Vec::new();
+        let text = write(&mut |x| {
+            names.push(x.to_string());
+            format!("\u{1}{}\u{2}", names.len() - 1)
+        });
+        let mut res = String::new();
+        let mut rest = self.escaped(&text);
+        while let Some((before, after)) = rest.split_once('\u{1}') {
+            res.push_str(before);
+            let (index, after) = after.split_once('\u{2}').unwrap_or((after, ""));
+            if let Some(name) = index.parse::<usize>().ok().and_then(|x| names.get(x)) {
+                res.push_str(&self.class_name(name));
+            }
+            rest = after.to_string();
+        }
+        res.push_str(&rest);
+        res
+    }
+
+    fn java_type(&self, ty: &TypeDescriptor) -> String {
+        self.linked(|class_name| ty.to_java_type_with(class_name))
+    }
+
+    /// The annotations, visible and invisible, each followed by a space.
+    fn annotations(&self, annotations: Vec<Annotation>) -> String {
+        let mut res = String::new();
+        for x in annotations {
+            res.push_str(&self.linked(|class_name| x.to_java_with(class_name)));
+            res.push(' ');
+        }
+        res
+    }
+
+    /// Text in a monospaced font, `text` being markup already.
+    fn code(&self, text: &str) -> String {
+        match self.format() {
+            ReportFormat::Html => format!("<code>{}</code>", text),
+            // Code spans cannot hold links.
+            ReportFormat::Markdown => text.to_string(),
+        }
+    }
+
+    fn start(&mut self, title: &str) -> Result<()> {
+        if self.format() == ReportFormat::Html {
+            writeln!(self.out, "<!DOCTYPE html>")?;
+            writeln!(self.out, "<html>")?;
+            writeln!(self.out, "<head>")?;
+            writeln!(self.out, "<meta charset=\"utf-8\">")?;
+            writeln!(self.out, "<title>{}</title>", html_escaped(title))?;
+            writeln!(self.out, "<style>")?;
+            writeln!(self.out, "body {{ font-family: sans-serif; max-width: 60em; margin: auto; }}")?;
+            writeln!(self.out, "li code {{ white-space: pre-wrap; }}")?;
+            writeln!(self.out, "pre {{ background: #f4f4f4; padding: 1em; overflow-x: auto; }}")?;
+            writeln!(self.out, "</style>")?;
+            writeln!(self.out, "</head>")?;
+            writeln!(self.out, "<body>")?;
+        }
+        let title = self.escaped(title);
+        self.heading(1, &title, None)
+    }
+
+    fn end(&mut self) -> Result<()> {
+        if self.format() == ReportFormat::Html {
+            writeln!(self.out, "</body>")?;
+            writeln!(self.out, "</html>")?;
+        }
+        Ok(())
+    }
+
+    /// A heading of `level`, `text` being markup already, with the anchor
+    /// `id` if given.
+    fn heading(&mut self, level: usize, text: &str, id: Option<&str>) -> Result<()> {
+        match self.format() {
+            ReportFormat::Html => match id {
+                Some(id) => writeln!(self.out, "<h{0} id=\"{1}\">{2}</h{0}>", level, html_escaped(id), text)?,
+                None => writeln!(self.out, "<h{0}>{1}</h{0}>", level, text)?,
+            },
+            ReportFormat::Markdown => {
+                if let Some(id) = id {
+                    writeln!(self.out, "<a id=\"{}\"></a>\n", html_escaped(id))?;
+                }
+                writeln!(self.out, "{} {}\n", "#".repeat(level), text)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn paragraph(&mut self, text: &str) -> Result<()> {
+        match self.format() {
+            ReportFormat::Html => writeln!(self.out, "<p>{}</p>", text)?,
+            ReportFormat::Markdown => writeln!(self.out, "{}\n", text)?,
+        }
+        Ok(())
+    }
+
+    fn list(&mut self, items: &[String]) -> Result<()> {
+        match self.format() {
+            ReportFormat::Html => {
+                writeln!(self.out, "<ul>")?;
+                for x in items {
+                    writeln!(self.out, "<li>{}</li>", x)?;
+                }
+                writeln!(self.out, "</ul>")?;
+            }
+            ReportFormat::Markdown => {
+                for x in items {
+                    writeln!(self.out, "- {}", x)?;
+                }
+                writeln!(self.out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A block of preformatted plain text.
+    fn preformatted(&mut self, text: &str) -> Result<()> {
+        match self.format() {
+            ReportFormat::Html => writeln!(self.out, "<pre>{}</pre>", html_escaped(text.trim_end()))?,
+            ReportFormat::Markdown => {
+                // The fence has to be longer than any run of backticks inside.
+                let mut longest = 0;
+                let mut run = 0;
+                for c in text.chars() {
+                    run = if c == '`' { run + 1 } else { 0 };
+                    longest = longest.max(run);
+                }
+                let fence = "`".repeat((longest + 1).max(3));
+                writeln!(self.out, "{}text\n{}\n{}\n", fence, text.trim_end(), fence)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn class(&mut self, class_file: &ClassFile, uses: &BTreeSet<String>, used_by: Option<&BTreeSet<&str>>) -> Result<()> {
+        let name = class_file.this_class()?;
+        let kind = kind_word(class_file)?;
+        let interface = class_file.access_flags().contains(ClassAccessFlags::INTERFACE);
+        let heading = format!("{} {}", kind, self.escaped(&anchor(name)));
+        self.heading(2, &heading, Some(&anchor(name)))?;
+
+        let mut annotations = class_file.annotations()?;
+        annotations.extend(class_file.invisible_annotations()?);
+        let mut flags = ClassAccessFlags::from_bits_retain(class_file.access_flags().bits());
+        if interface {
+            flags.remove(ClassAccessFlags::ABSTRACT);
+        }
+        let mut declaration = format!("{}{}{} {}", self.annotations(annotations), modifier_words(&flags, &CLASS_MODIFIERS), kind, self.escaped(&anchor(name)));
+        if !interface && class_file.super_class.0 != 0 && class_file.super_class()? != "java/lang/Object" {
+            write!(declaration, " extends {}", self.class_name(class_file.super_class()?))?;
+        }
+        let interfaces = class_file.interfaces()?;
+        if !interfaces.is_empty() {
+            let names = interfaces.iter().map(|x| self.class_name(x)).collect::<Vec<_>>();
+            let keyword = if interface { "extends" } else { "implements" };
+            write!(declaration, " {} {}", keyword, names.join(", "))?;
+        }
+        if let Some(permitted) = class_file.permitted_subclasses()? {
+            let names = permitted.iter().map(|x| self.class_name(x)).collect::<Vec<_>>();
+            write!(declaration, " permits {}", names.join(", "))?;
+        }
+        let declaration = self.code(&declaration);
+        self.paragraph(&declaration)?;
+        if let Some(signature) = class_file.signature()? {
+            let simple_name = name.rsplit(['/', '$']).next().unwrap_or(name);
+            let generic = format!("Generic: {}", self.code(&self.escaped(&signature.to_java(simple_name, interface))));
+            self.paragraph(&generic)?;
+        }
+        if class_file.is_deprecated() {
+            self.paragraph("Deprecated.")?;
+        }
+
+        let fields = class_file
+            .fields()
+            .iter()
+            .filter(|x| !x.is_synthetic())
+            .map(|x| self.field(x))
+            .collect::<Result<Vec<_>>>()?;
+        if !fields.is_empty() {
+            self.heading(3, "Fields", None)?;
+            self.list(&fields)?;
+        }
+        let methods = class_file
+            .methods()
+            .iter()
+            .filter(|x| !x.is_synthetic())
+            .map(|x| self.method(class_file, x))
+            .collect::<Result<Vec<_>>>()?;
+        if !methods.is_empty() {
+            self.heading(3, "Methods", None)?;
+            self.list(&methods)?;
+        }
+
+        self.heading(3, "Dependencies", None)?;
+        let uses = uses.iter().map(|x| self.class_name(x)).collect::<Vec<_>>();
+        let uses = match uses.is_empty() {
+            true => "Uses no other classes.".to_string(),
+            false => format!("Uses {}.", uses.join(", ")),
+        };
+        self.paragraph(&uses)?;
+        if let Some(used_by) = used_by {
+            let used_by = used_by.iter().map(|x| self.class_name(x)).collect::<Vec<_>>();
+            self.paragraph(&format!("Used by {}.", used_by.join(", ")))?;
+        }
+
+        if self.generator.disassembly {
+            self.heading(3, "Disassembly", None)?;
+            self.preformatted(&class_file.disassemble()?)?;
+        }
+        Ok(())
+    }
+
+    fn field(&self, field: &Field) -> Result<String> {
+        let mut annotations = field.annotations()?;
+        annotations.extend(field.invisible_annotations()?);
+        let descriptor = field.descriptor()?;
+        let mut res = format!(
+            "{}{}{} {}",
+            self.annotations(annotations),
+            modifier_words(&field.field_inner.access_flags, &FIELD_MODIFIERS),
+            self.java_type(&descriptor),
+            self.escaped(field.identifier()?)
+        );
+        if let Some(value) = field.initial_value()? {
+            if let Some(value) = constant_value(&descriptor, &value)? {
+                write!(res, " = {}", self.escaped(&value.to_string()))?;
+            }
+        }
+        let mut res = self.code(&res);
+        if let Some(signature) = field.signature()? {
+            write!(res, " (generic: {})", self.code(&self.escaped(&signature.to_string())))?;
+        }
+        Ok(res)
+    }
+
+    fn method(&self, class_file: &ClassFile, method: &Method) -> Result<String> {
+        let mut annotations = method.annotations()?;
+        annotations.extend(method.invisible_annotations()?);
+        let flags = &method.method_inner.access_flags;
+        let name = method.identifier()?;
+        let descriptor = method.descriptor()?;
+        let mut res = format!("{}{}", self.annotations(annotations), modifier_words(flags, &METHOD_MODIFIERS));
+        let mut parameters = descriptor.parameter_types().iter().map(|x| self.java_type(x)).collect::<Vec<_>>();
+        if flags.contains(MethodAccessFlags::VARARGS) {
+            if let Some(last) = parameters.last_mut().filter(|x| x.ends_with("[]")) {
+                last.truncate(last.len() - 2);
+                last.push_str("...");
+            }
+        }
+        let parameters = parameters.join(", ");
+        match name {
+            "<init>" => write!(res, "{}({})", self.escaped(&anchor(class_file.this_class()?)), parameters)?,
+            "<clinit>" => write!(res, "{{}}")?,
+            _ => {
+                let return_type = descriptor.return_type().map_or_else(|| "void".to_string(), |x| self.java_type(x));
+                write!(res, "{} {}({})", return_type, self.escaped(name), parameters)?;
+            }
+        }
+        if let Some(exceptions) = method.exceptions()? {
+            let names = exceptions.class_names()?;
+            if !names.is_empty() {
+                let names = names.iter().map(|x| self.class_name(x)).collect::<Vec<_>>();
+                write!(res, " throws {}", names.join(", "))?;
+            }
+        }
+        if let Some(value) = method.annotation_default()? {
+            write!(res, " default {}", self.linked(|class_name| value.to_java_with(class_name)))?;
+        }
+        let mut res = self.code(&res);
+        if let Some(signature) = method.signature()? {
+            let generic = match name {
+                // A constructor is written without a result, named as its class.
+                "<init>" => {
+                    let simple_name = class_file.this_class()?.rsplit(['/', '$']).next().unwrap_or(name);
+                    signature.to_java(simple_name).replacen("void ", "", 1)
+                }
+                x => signature.to_java(x),
+            };
+            write!(res, " (generic: {})", self.code(&self.escaped(&generic)))?;
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet, HashMap},
+    hash::{Hash, Hasher},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{resolver::ClassResolver, ClassFile, ParseOptions, Result};
+
+const SHARDS: usize = 16;
+
+#[derive(Default)]
+struct Shard {
+    classes: HashMap<String, Arc<ClassFile>>,
+    /// Direct subclasses and implementors by supertype, including supertypes
+    /// that have not been added themselves.
+    subtypes: HashMap<String, BTreeSet<String>>,
+}
+
+/// Parsed classes by internal name, with hierarchy queries.
+///
+/// Every method takes `&self`, and the repository is `Send + Sync`, so one
+/// repository can be filled by parallel parsers and queried from other threads
+/// through an `Arc`. Classes are split across independently locked shards, so
+/// readers only contend with writers adding classes to the same shard.
+pub struct ClassRepository {
+    shards: Vec<RwLock<Shard>>,
+}
+
+impl Default for ClassRepository {
+    fn default() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| RwLock::default()).collect(),
+        }
+    }
+}
+
+impl std::fmt::Debug for ClassRepository {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClassRepository")
+            .field("classes", &self.len())
+            .finish()
+    }
+}
+
+pub(crate) fn supertypes(class_file: &ClassFile) -> Result<Vec<&str>> {
+    let mut res = class_file.interfaces()?;
+    if class_file.super_class.0 != 0 {
+        res.insert(0, class_file.super_class()?);
+    }
+    Ok(res)
+}
+
+impl ClassRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn shard(&self, name: &str) -> &RwLock<Shard> {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+
+    // A panic while holding a lock cannot leave a shard half-updated, so
+    // poisoning is ignored.
+    fn read(&self, name: &str) -> RwLockReadGuard<'_, Shard> {
+        self.shard(name).read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self, name: &str) -> RwLockWriteGuard<'_, Shard> {
+        self.shard(name).write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds `class_file` under its own name, returning the class it replaced.
+    pub fn insert(&self, class_file: ClassFile) -> Result<Option<Arc<ClassFile>>> {
+        let name = class_file.this_class()?.to_string();
+        let added = supertypes(&class_file)?
+            .into_iter()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        let previous = self
+            .write(&name)
+            .classes
+            .insert(name.clone(), Arc::new(class_file));
+        // Lock one shard at a time so concurrent inserts cannot deadlock.
+        if let Some(previous) = &previous {
+            for supertype in supertypes(previous)? {
+                if let Some(x) = self.write(supertype).subtypes.get_mut(supertype) {
+                    x.remove(&name);
+                }
+            }
+        }
+        for supertype in added {
+            self.write(&supertype)
+                .subtypes
+                .entry(supertype)
+                .or_default()
+                .insert(name.clone());
+        }
+        Ok(previous)
+    }
+
+    /// Parses `data` and adds the class, returning it.
+    pub fn parse(&self, data: Vec<u8>, options: &ParseOptions) -> Result<Arc<ClassFile>> {
+        let class_file = ClassFile::parse_with_options(data, options)?;
+        let name = class_file.this_class()?.to_string();
+        self.insert(class_file)?;
+        self.get(&name).ok_or_else(|| {
+            crate::Error::ConstantPoolError(format!("class {} was removed while being added", name))
+        })
+    }
+
+    pub fn remove(&self, name: &str) -> Result<Option<Arc<ClassFile>>> {
+        let removed = self.write(name).classes.remove(name);
+        if let Some(removed) = &removed {
+            for supertype in supertypes(removed)? {
+                if let Some(x) = self.write(supertype).subtypes.get_mut(supertype) {
+                    x.remove(name);
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<ClassFile>> {
+        self.read(name).classes.get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.read(name).classes.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|x| x.read().unwrap_or_else(|e| e.into_inner()).classes.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The names of every class, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self
+            .shards
+            .iter()
+            .flat_map(|x| {
+                x.read()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .classes
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// `name`'s superclass, its superclass and so on, stopping at the first
+    /// class missing from the repository.
+    pub fn super_classes(&self, name: &str) -> Result<Vec<String>> {
+        ClassResolver::super_classes(self, name)
+    }
+
+    /// Classes directly extending or implementing `name`, sorted.
+    pub fn direct_subtypes(&self, name: &str) -> Vec<String> {
+        self.read(name)
+            .subtypes
+            .get(name)
+            .map(|x| x.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether `sub` is `sup` or reaches it through superclasses and interfaces
+    /// present in the repository.
+    pub fn is_subtype_of(&self, sub: &str, sup: &str) -> Result<bool> {
+        ClassResolver::is_subtype_of(self, sub, sup)
+    }
+}
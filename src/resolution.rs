@@ -0,0 +1,402 @@
+//! Resolution of symbolic references to methods and fields, finding the
+//! member a reference reaches as the JVM does (JVMS §5.4.3.2 to §5.4.3.4),
+//! see [`resolve_class_method`], [`resolve_interface_method`] and
+//! [`resolve_field`], or `resolve` on [`MethodRef`] and the other references.
+//!
+//! The class a reference names must resolve, or resolution fails with
+//! [`Error::ClassNotFound`]. Its supertypes that do not resolve are skipped,
+//! so a partial class path finds what it can rather than failing. Access
+//! control (§5.4.4) is not checked.
+
+use std::{collections::BTreeSet, sync::Arc};
+
+use crate::{
+    field::Field,
+    instruction::{FieldRef, InterfaceMethodRef, MaybeInterfaceMethodRef, MethodRef},
+    method::Method,
+    raw::{ClassAccessFlags, MethodAccessFlags},
+    resolver::ClassResolver,
+    ClassFile, Error, Result,
+};
+
+const OBJECT: &str = "java/lang/Object";
+
+/// The member a reference resolved to.
+#[derive(Debug, Clone)]
+pub struct ResolvedMember {
+    /// The class declaring the member.
+    pub class_file: Arc<ClassFile>,
+    pub name: String,
+    /// The descriptor the member is declared with. For a signature
+    /// polymorphic method, such as `MethodHandle.invoke`, it is not the one
+    /// the reference gives.
+    pub descriptor: String,
+}
+
+impl ResolvedMember {
+    fn new(class_file: &Arc<ClassFile>, name: &str, descriptor: &str) -> Self {
+        Self {
+            class_file: class_file.clone(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+        }
+    }
+
+    /// The name of the class declaring the member.
+    pub fn class(&self) -> Result<&str> {
+        self.class_file.this_class()
+    }
+
+    /// The method resolved to, or `None` if the member is a field.
+    pub fn method(&self) -> Result<Option<Method<'_>>> {
+        for x in self.class_file.methods() {
+            if x.identifier()? == self.name
+                && x.method_inner.descriptor_index.get_as_string(&self.class_file)? == self.descriptor
+            {
+                return Ok(Some(x));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The field resolved to, or `None` if the member is a method.
+    pub fn field(&self) -> Result<Option<Field<'_>>> {
+        for x in self.class_file.fields() {
+            if x.identifier()? == self.name
+                && x.field_inner.descriptor_index.get_as_string(&self.class_file)? == self.descriptor
+            {
+                return Ok(Some(x));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn no_such_member(class: &str, name: &str, descriptor: &str) -> Error {
+    Error::NoSuchMember {
+        class: class.to_string(),
+        name: name.to_string(),
+        descriptor: descriptor.to_string(),
+    }
+}
+
+fn is_interface(class_file: &ClassFile) -> bool {
+    class_file.access_flags.contains(ClassAccessFlags::INTERFACE)
+}
+
+fn super_class(class_file: &ClassFile) -> Result<Option<&str>> {
+    match class_file.super_class.0 {
+        0 => Ok(None),
+        _ => Ok(Some(class_file.super_class()?)),
+    }
+}
+
+/// The access flags of the method `class_file` declares with `name` and
+/// `descriptor`.
+fn declared_method(class_file: &ClassFile, name: &str, descriptor: &str) -> Result<Option<MethodAccessFlags>> {
+    for x in class_file.methods() {
+        if x.identifier()? == name && x.method_inner.descriptor_index.get_as_string(class_file)? == descriptor {
+            return Ok(Some(MethodAccessFlags::from_bits_retain(x.method_inner.access_flags.bits())));
+        }
+    }
+    Ok(None)
+}
+
+/// The descriptor of the signature polymorphic method `name` of
+/// `class_file` (JVMS §2.9.3), if it has one: the only method of that name
+/// of `MethodHandle` or `VarHandle`, native and varargs, taking just an
+/// `Object[]`.
+fn signature_polymorphic(class_file: &ClassFile, name: &str) -> Result<Option<String>> {
+    if !matches!(class_file.this_class()?, "java/lang/invoke/MethodHandle" | "java/lang/invoke/VarHandle") {
+        return Ok(None);
+    }
+    let mut res = None;
+    for x in class_file.methods() {
+        if x.identifier()? != name {
+            continue;
+        }
+        if res.is_some() {
+            return Ok(None);
+        }
+        let descriptor = x.method_inner.descriptor_index.get_as_string(class_file)?;
+        let flags = &x.method_inner.access_flags;
+        res = Some(descriptor.to_string()).filter(|x| {
+            x.starts_with("([Ljava/lang/Object;)")
+                && flags.contains(MethodAccessFlags::VARARGS | MethodAccessFlags::NATIVE)
+        });
+    }
+    Ok(res)
+}
+
+fn load(resolver: &(impl ClassResolver + ?Sized), class: &str) -> Result<Arc<ClassFile>> {
+    resolver.resolve(class)?.ok_or_else(|| Error::ClassNotFound(class.to_string()))
+}
+
+/// Every superinterface of `class_file`, direct or not, including those of
+/// its superclasses, breadth first.
+fn superinterfaces(resolver: &(impl ClassResolver + ?Sized), class_file: &ClassFile) -> Result<Vec<Arc<ClassFile>>> {
+    let mut res = Vec::new();
+    let mut seen = BTreeSet::new();
+    let mut queue = std::collections::VecDeque::new();
+    let mut current = Some(class_file.this_class()?.to_string());
+    // The superclasses first, so their interfaces are reached.
+    while let Some(class) = current.take() {
+        if !seen.insert(class.clone()) {
+            break;
+        }
+        let class_file = match resolver.resolve(&class)? {
+            Some(x) => x,
+            None => break,
+        };
+        queue.extend(class_file.interfaces()?.into_iter().map(str::to_string));
+        current = super_class(&class_file)?.map(str::to_string);
+    }
+    while let Some(interface) = queue.pop_front() {
+        if !seen.insert(interface.clone()) {
+            continue;
+        }
+        if let Some(class_file) = resolver.resolve(&interface)? {
+            queue.extend(class_file.interfaces()?.into_iter().map(str::to_string));
+            res.push(class_file);
+        }
+    }
+    Ok(res)
+}
+
+/// A superinterface method that is neither private nor static.
+struct Candidate {
+    interface: Arc<ClassFile>,
+    is_abstract: bool,
+    /// Whether no other candidate's interface is a subinterface of this one's
+    /// (JVMS §5.4.3.3).
+    maximally_specific: bool,
+}
+
+/// The superinterface methods of `class_file` with `name` and `descriptor`
+/// that are neither private nor static, in the order of [`superinterfaces`].
+fn superinterface_methods(
+    resolver: &(impl ClassResolver + ?Sized),
+    class_file: &ClassFile,
+    name: &str,
+    descriptor: &str,
+) -> Result<Vec<Candidate>> {
+    let mut res = Vec::new();
+    for interface in superinterfaces(resolver, class_file)? {
+        if let Some(flags) = declared_method(&interface, name, descriptor)? {
+            if !flags.intersects(MethodAccessFlags::PRIVATE | MethodAccessFlags::STATIC) {
+                res.push(Candidate {
+                    interface,
+                    is_abstract: flags.contains(MethodAccessFlags::ABSTRACT),
+                    maximally_specific: true,
+                });
+            }
+        }
+    }
+    for i in 0..res.len() {
+        for j in 0..res.len() {
+            if i != j && resolver.is_subtype_of(res[j].interface.this_class()?, res[i].interface.this_class()?)? {
+                res[i].maximally_specific = false;
+                break;
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Picks from the superinterface methods as the last steps of method and
+/// interface method resolution do: the only maximally specific one that is
+/// not abstract, or failing that any of them.
+fn choose_superinterface_method(
+    resolver: &(impl ClassResolver + ?Sized),
+    class_file: &ClassFile,
+    name: &str,
+    descriptor: &str,
+) -> Result<Option<ResolvedMember>> {
+    let candidates = superinterface_methods(resolver, class_file, name, descriptor)?;
+    let mut concrete = candidates.iter().filter(|x| x.maximally_specific && !x.is_abstract);
+    if let (Some(x), None) = (concrete.next(), concrete.next()) {
+        return Ok(Some(ResolvedMember::new(&x.interface, name, descriptor)));
+    }
+    // Prefer a maximally specific method, as HotSpot does.
+    Ok(candidates
+        .iter()
+        .find(|x| x.maximally_specific)
+        .or(candidates.first())
+        .map(|x| ResolvedMember::new(&x.interface, name, descriptor)))
+}
+
+/// The methods with `name` and `descriptor` that are maximally specific
+/// among the superinterfaces of `class`, those a call through `class` could
+/// reach when no class declares the method (JVMS §5.4.3.3). Two or more of
+/// them that are not abstract make such a call ambiguous.
+pub fn maximally_specific_methods(
+    resolver: &(impl ClassResolver + ?Sized),
+    class: &str,
+    name: &str,
+    descriptor: &str,
+) -> Result<Vec<ResolvedMember>> {
+    let class_file = load(resolver, class)?;
+    Ok(superinterface_methods(resolver, &class_file, name, descriptor)?
+        .iter()
+        .filter(|x| x.maximally_specific)
+        .map(|x| ResolvedMember::new(&x.interface, name, descriptor))
+        .collect())
+}
+
+/// Resolves a `Methodref` to `name` and `descriptor` in `class` (JVMS
+/// §5.4.3.3): the method of `class` or its nearest superclass declaring it,
+/// including a signature polymorphic one, then the only maximally specific
+/// superinterface method that is not abstract, then any superinterface
+/// method. Fails with [`Error::IncompatibleClassChange`] if `class` is an
+/// interface, and [`Error::NoSuchMember`] if nothing declares the method.
+pub fn resolve_class_method(
+    resolver: &(impl ClassResolver + ?Sized),
+    class: &str,
+    name: &str,
+    descriptor: &str,
+) -> Result<ResolvedMember> {
+    let class_file = load(resolver, class)?;
+    if is_interface(&class_file) {
+        return Err(Error::IncompatibleClassChange(format!(
+            "method reference to {}.{}{} names an interface",
+            class, name, descriptor
+        )));
+    }
+    let mut seen = BTreeSet::new();
+    let mut current = Some(class_file.clone());
+    while let Some(x) = current.take() {
+        if let Some(polymorphic) = signature_polymorphic(&x, name)? {
+            return Ok(ResolvedMember::new(&x, name, &polymorphic));
+        }
+        if declared_method(&x, name, descriptor)?.is_some() {
+            return Ok(ResolvedMember::new(&x, name, descriptor));
+        }
+        if !seen.insert(x.this_class()?.to_string()) {
+            break;
+        }
+        if let Some(super_class) = super_class(&x)? {
+            current = resolver.resolve(super_class)?;
+        }
+    }
+    choose_superinterface_method(resolver, &class_file, name, descriptor)?
+        .ok_or_else(|| no_such_member(class, name, descriptor))
+}
+
+/// Resolves an `InterfaceMethodref` to `name` and `descriptor` in `class`
+/// (JVMS §5.4.3.4): the method `class` declares, then a public method of
+/// `java/lang/Object` that is not static, then the only maximally specific
+/// superinterface method that is not abstract, then any superinterface
+/// method. Fails with [`Error::IncompatibleClassChange`] if `class` is not
+/// an interface, and [`Error::NoSuchMember`] if nothing declares the method.
+pub fn resolve_interface_method(
+    resolver: &(impl ClassResolver + ?Sized),
+    class: &str,
+    name: &str,
+    descriptor: &str,
+) -> Result<ResolvedMember> {
+    let class_file = load(resolver, class)?;
+    if !is_interface(&class_file) {
+        return Err(Error::IncompatibleClassChange(format!(
+            "interface method reference to {}.{}{} names a class",
+            class, name, descriptor
+        )));
+    }
+    if declared_method(&class_file, name, descriptor)?.is_some() {
+        return Ok(ResolvedMember::new(&class_file, name, descriptor));
+    }
+    if let Some(object) = resolver.resolve(OBJECT)? {
+        if declared_method(&object, name, descriptor)?
+            .is_some_and(|x| x.contains(MethodAccessFlags::PUBLIC) && !x.contains(MethodAccessFlags::STATIC))
+        {
+            return Ok(ResolvedMember::new(&object, name, descriptor));
+        }
+    }
+    choose_superinterface_method(resolver, &class_file, name, descriptor)?
+        .ok_or_else(|| no_such_member(class, name, descriptor))
+}
+
+/// Resolves a method reference to `name` and `descriptor` in `class` as
+/// [`resolve_interface_method`] if `class` is an interface and
+/// [`resolve_class_method`] otherwise, for when the kind of reference is not
+/// known.
+pub fn resolve_method(
+    resolver: &(impl ClassResolver + ?Sized),
+    class: &str,
+    name: &str,
+    descriptor: &str,
+) -> Result<ResolvedMember> {
+    let class_file = load(resolver, class)?;
+    if is_interface(&class_file) {
+        resolve_interface_method(resolver, class, name, descriptor)
+    } else {
+        resolve_class_method(resolver, class, name, descriptor)
+    }
+}
+
+/// Resolves a `Fieldref` to `name` and `descriptor` in `class` (JVMS
+/// §5.4.3.2): the field `class` declares, then one of its superinterfaces,
+/// depth first, then one of its superclass found the same way. Fails with
+/// [`Error::NoSuchMember`] if nothing declares the field.
+pub fn resolve_field(
+    resolver: &(impl ClassResolver + ?Sized),
+    class: &str,
+    name: &str,
+    descriptor: &str,
+) -> Result<ResolvedMember> {
+    load(resolver, class)?;
+    let mut seen = BTreeSet::new();
+    // A stack of classes still to visit, in reverse order.
+    let mut pending = vec![class.to_string()];
+    while let Some(current) = pending.pop() {
+        if !seen.insert(current.clone()) {
+            continue;
+        }
+        let class_file = match resolver.resolve(&current)? {
+            Some(x) => x,
+            None => continue,
+        };
+        for field in class_file.fields() {
+            if field.identifier()? == name && field.field_inner.descriptor_index.get_as_string(&class_file)? == descriptor
+            {
+                return Ok(ResolvedMember::new(&class_file, name, descriptor));
+            }
+        }
+        if let Some(x) = super_class(&class_file)?.filter(|_| !is_interface(&class_file)) {
+            pending.push(x.to_string());
+        }
+        pending.extend(class_file.interfaces()?.into_iter().rev().map(str::to_string));
+    }
+    Err(no_such_member(class, name, descriptor))
+}
+
+impl MethodRef<'_> {
+    /// The method this reference resolves to, see [`resolve_class_method`].
+    pub fn resolve(&self, resolver: &(impl ClassResolver + ?Sized)) -> Result<ResolvedMember> {
+        resolve_class_method(resolver, self.class, self.name, &self.descriptor.to_string())
+    }
+}
+
+impl InterfaceMethodRef<'_> {
+    /// The method this reference resolves to, see [`resolve_interface_method`].
+    pub fn resolve(&self, resolver: &(impl ClassResolver + ?Sized)) -> Result<ResolvedMember> {
+        resolve_interface_method(resolver, self.class, self.name, &self.descriptor.to_string())
+    }
+}
+
+impl MaybeInterfaceMethodRef<'_> {
+    /// The method this reference resolves to, as whichever kind of reference
+    /// it is.
+    pub fn resolve(&self, resolver: &(impl ClassResolver + ?Sized)) -> Result<ResolvedMember> {
+        match self {
+            Self::RegularMethod(x) => x.resolve(resolver),
+            Self::InterfaceMethod(x) => x.resolve(resolver),
+        }
+    }
+}
+
+impl FieldRef<'_> {
+    /// The field this reference resolves to, see [`resolve_field`].
+    pub fn resolve(&self, resolver: &(impl ClassResolver + ?Sized)) -> Result<ResolvedMember> {
+        resolve_field(resolver, self.class, self.name, &self.descriptor.to_string())
+    }
+}
@@ -0,0 +1,454 @@
+//! Finding classes by name wherever they are kept, see [`ClassResolver`].
+//!
+//! Features that follow references from one class to another, such as the
+//! hierarchy queries, method and field resolution and
+//! [`Verifier`](crate::verifier::Verifier), look classes up through this trait,
+//! so the same code works over a [`ClassRepository`], a directory of class
+//! files, a JAR or JMOD, a JDK's image, or a chain of them.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    archive::Archive,
+    jimage::JImage,
+    repository::{supertypes, ClassRepository},
+    resolution::{self, ResolvedMember},
+    ClassFile, Error, ParseOptions, Result,
+};
+
+/// A source of classes by internal name.
+pub trait ClassResolver {
+    /// The class called `internal_name`, e.g. `java/lang/Object`, or `None`
+    /// if this resolver does not have it. Fails if the class is there but
+    /// cannot be read.
+    fn resolve(&self, internal_name: &str) -> Result<Option<Arc<ClassFile>>>;
+
+    /// `name`'s superclass, its superclass and so on, stopping at the first
+    /// class that cannot be resolved.
+    fn super_classes(&self, name: &str) -> Result<Vec<String>> {
+        let mut res = Vec::new();
+        let mut current = self.resolve(name)?;
+        while let Some(class_file) = current {
+            if class_file.super_class.0 == 0 {
+                break;
+            }
+            let super_class = class_file.super_class()?.to_string();
+            // Guard against cycles in malformed input.
+            if res.contains(&super_class) {
+                break;
+            }
+            current = self.resolve(&super_class)?;
+            res.push(super_class);
+        }
+        Ok(res)
+    }
+
+    /// Whether `sub` is `sup` or reaches it through superclasses and
+    /// interfaces that can be resolved.
+    fn is_subtype_of(&self, sub: &str, sup: &str) -> Result<bool> {
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::from([sub.to_string()]);
+        while let Some(name) = queue.pop_front() {
+            if name == sup {
+                return Ok(true);
+            }
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            if let Some(class_file) = self.resolve(&name)? {
+                queue.extend(supertypes(&class_file)?.into_iter().map(str::to_string));
+            }
+        }
+        Ok(false)
+    }
+
+    /// The class declaring the method a reference to `name` and
+    /// `descriptor` in `class` resolves to, see
+    /// [`resolution::resolve_method`]. `None` if `class` does not resolve or
+    /// no class declares the method.
+    fn resolve_method(&self, class: &str, name: &str, descriptor: &str) -> Result<Option<Arc<ClassFile>>> {
+        declaring_class(resolution::resolve_method(self, class, name, descriptor))
+    }
+
+    /// The class declaring the field a reference to `name` and `descriptor`
+    /// in `class` resolves to, see [`resolution::resolve_field`]. `None` if
+    /// `class` does not resolve or no class declares the field.
+    fn resolve_field(&self, class: &str, name: &str, descriptor: &str) -> Result<Option<Arc<ClassFile>>> {
+        declaring_class(resolution::resolve_field(self, class, name, descriptor))
+    }
+}
+
+fn declaring_class(member: Result<ResolvedMember>) -> Result<Option<Arc<ClassFile>>> {
+    match member {
+        Ok(x) => Ok(Some(x.class_file)),
+        Err(Error::ClassNotFound(_) | Error::NoSuchMember { .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl<R: ClassResolver + ?Sized> ClassResolver for &R {
+    fn resolve(&self, internal_name: &str) -> Result<Option<Arc<ClassFile>>> {
+        (**self).resolve(internal_name)
+    }
+}
+
+impl<R: ClassResolver + ?Sized> ClassResolver for Box<R> {
+    fn resolve(&self, internal_name: &str) -> Result<Option<Arc<ClassFile>>> {
+        (**self).resolve(internal_name)
+    }
+}
+
+impl<R: ClassResolver + ?Sized> ClassResolver for Arc<R> {
+    fn resolve(&self, internal_name: &str) -> Result<Option<Arc<ClassFile>>> {
+        (**self).resolve(internal_name)
+    }
+}
+
+impl ClassResolver for ClassRepository {
+    fn resolve(&self, internal_name: &str) -> Result<Option<Arc<ClassFile>>> {
+        Ok(self.get(internal_name))
+    }
+}
+
+/// Classes stored as `.class` files under a directory, in a subdirectory per
+/// package as a class path lays them out. Every class is read and parsed
+/// again each time it is resolved, see [`CachingResolver`].
+#[derive(Debug, Clone)]
+pub struct DirectoryResolver {
+    root: PathBuf,
+    options: ParseOptions,
+}
+
+impl DirectoryResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// Parses classes with `options` rather than the default.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl ClassResolver for DirectoryResolver {
+    fn resolve(&self, internal_name: &str) -> Result<Option<Arc<ClassFile>>> {
+        // Names are paths relative to the root, and must stay under it.
+        if internal_name.split('/').any(|x| x.is_empty() || x == "." || x == "..") {
+            return Ok(None);
+        }
+        let data = match std::fs::read(self.root.join(format!("{}.class", internal_name))) {
+            Ok(x) => x,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(Arc::new(ClassFile::parse_with_options(data, &self.options)?)))
+    }
+}
+
+/// The classes of a JAR or JMOD, see [`Archive::class_bytes`]. Every class is
+/// decompressed and parsed again each time it is resolved, see
+/// [`CachingResolver`].
+#[derive(Debug)]
+pub struct ArchiveResolver {
+    archive: Archive,
+    options: ParseOptions,
+}
+
+impl ArchiveResolver {
+    pub fn new(archive: Archive) -> Self {
+        Self {
+            archive,
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// Parses classes with `options` rather than the default.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn archive(&self) -> &Archive {
+        &self.archive
+    }
+}
+
+impl ClassResolver for ArchiveResolver {
+    fn resolve(&self, internal_name: &str) -> Result<Option<Arc<ClassFile>>> {
+        Ok(self.archive.read_class(internal_name, &self.options)?.map(Arc::new))
+    }
+}
+
+/// The classes of a JDK's image, see [`JImage::class_bytes`]. Every class is
+/// read and parsed again each time it is resolved, see [`CachingResolver`].
+#[derive(Debug)]
+pub struct JImageResolver {
+    image: JImage,
+    options: ParseOptions,
+}
+
+impl JImageResolver {
+    pub fn new(image: JImage) -> Self {
+        Self {
+            image,
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// Parses classes with `options` rather than the default.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn image(&self) -> &JImage {
+        &self.image
+    }
+}
+
+impl ClassResolver for JImageResolver {
+    fn resolve(&self, internal_name: &str) -> Result<Option<Arc<ClassFile>>> {
+        Ok(self.image.read_class(internal_name, &self.options)?.map(Arc::new))
+    }
+}
+
+/// Resolvers tried in turn, the class coming from the first that has it, as
+/// the entries of a class path are.
+#[derive(Default)]
+pub struct ChainResolver {
+    resolvers: Vec<Box<dyn ClassResolver + Send + Sync>>,
+}
+
+impl std::fmt::Debug for ChainResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChainResolver")
+            .field("resolvers", &self.resolvers.len())
+            .finish()
+    }
+}
+
+impl ChainResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tries `resolver` after those already in the chain.
+    pub fn with_resolver(mut self, resolver: impl ClassResolver + Send + Sync + 'static) -> Self {
+        self.push(resolver);
+        self
+    }
+
+    /// Tries `resolver` after those already in the chain.
+    pub fn push(&mut self, resolver: impl ClassResolver + Send + Sync + 'static) {
+        self.resolvers.push(Box::new(resolver));
+    }
+
+    pub fn len(&self) -> usize {
+        self.resolvers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resolvers.is_empty()
+    }
+}
+
+impl ClassResolver for ChainResolver {
+    fn resolve(&self, internal_name: &str) -> Result<Option<Arc<ClassFile>>> {
+        for resolver in &self.resolvers {
+            if let Some(x) = resolver.resolve(internal_name)? {
+                return Ok(Some(x));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// How a [`CachingResolver`] has done since it was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups answered from the cache.
+    pub hits: u64,
+    /// Lookups passed on to the inner resolver.
+    pub misses: u64,
+    /// Entries dropped to stay within the limits.
+    pub evictions: u64,
+    /// Entries held now, including names that did not resolve.
+    pub entries: usize,
+    /// The size of the classes held now, in bytes of class file.
+    pub bytes: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    class_file: Option<Arc<ClassFile>>,
+    size: u64,
+    /// When the entry was last used, see [`Cache::tick`].
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Cache {
+    entries: HashMap<String, CacheEntry>,
+    /// Names by when they were last used, least recently first.
+    recency: BTreeMap<u64, String>,
+    /// Counts lookups, giving each use of an entry a later time than the one
+    /// before.
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl Cache {
+    fn get(&mut self, internal_name: &str) -> Option<Option<Arc<ClassFile>>> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(internal_name)?;
+        let name = self.recency.remove(&entry.last_used)?;
+        entry.last_used = self.tick;
+        self.recency.insert(self.tick, name);
+        Some(entry.class_file.clone())
+    }
+
+    fn insert(&mut self, internal_name: &str, class_file: Option<Arc<ClassFile>>, size: u64) {
+        self.tick += 1;
+        let entry = CacheEntry {
+            class_file,
+            size,
+            last_used: self.tick,
+        };
+        if let Some(old) = self.entries.insert(internal_name.to_string(), entry) {
+            // Another thread resolved the same name meanwhile.
+            self.recency.remove(&old.last_used);
+            self.stats.bytes -= old.size;
+        }
+        self.recency.insert(self.tick, internal_name.to_string());
+        self.stats.bytes += size;
+    }
+
+    /// Drops the least recently used entries until both limits are met.
+    fn evict(&mut self, max_bytes: Option<u64>, max_entries: Option<usize>) {
+        while max_bytes.is_some_and(|x| self.stats.bytes > x) || max_entries.is_some_and(|x| self.entries.len() > x) {
+            let Some((_, name)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&name) {
+                self.stats.bytes -= entry.size;
+                self.stats.evictions += 1;
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.stats.bytes = 0;
+    }
+}
+
+/// Remembers what another resolver returns for each name, including that it
+/// has no such class, so classes read from disk are only parsed once. Errors
+/// are not remembered.
+///
+/// By default every class is kept. With [`with_max_bytes`](Self::with_max_bytes)
+/// or [`with_max_entries`](Self::with_max_entries) the least recently used are
+/// dropped once the cache outgrows the limit, so walking the hierarchy of a
+/// whole JDK stays within a memory budget while the classes every walk passes
+/// through, such as `java/lang/Object`, stay cached. A class is counted as
+/// the size of its class file, see [`ClassFile::span`], which is roughly
+/// proportional to the memory it takes parsed.
+pub struct CachingResolver<R> {
+    inner: R,
+    max_bytes: Option<u64>,
+    max_entries: Option<usize>,
+    cache: Mutex<Cache>,
+}
+
+impl<R: std::fmt::Debug> std::fmt::Debug for CachingResolver<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachingResolver")
+            .field("inner", &self.inner)
+            .field("max_bytes", &self.max_bytes)
+            .field("max_entries", &self.max_entries)
+            .field("stats", &self.lock().stats)
+            .finish()
+    }
+}
+
+impl<R> CachingResolver<R> {
+    // A panic while holding the lock cannot leave the cache half-updated, so
+    // poisoning is ignored.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<R: ClassResolver> CachingResolver<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            max_bytes: None,
+            max_entries: None,
+            cache: Mutex::default(),
+        }
+    }
+
+    /// Keeps classes whose class files total at most `bytes`. A class bigger
+    /// than that on its own is returned but not kept.
+    pub fn with_max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Keeps at most `entries` names, whether they resolved or not.
+    pub fn with_max_entries(mut self, entries: usize) -> Self {
+        self.max_entries = Some(entries);
+        self
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let cache = self.lock();
+        CacheStats {
+            entries: cache.entries.len(),
+            ..cache.stats
+        }
+    }
+
+    /// Forgets every class resolved so far. The counts of hits, misses and
+    /// evictions are kept.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+impl<R: ClassResolver> ClassResolver for CachingResolver<R> {
+    fn resolve(&self, internal_name: &str) -> Result<Option<Arc<ClassFile>>> {
+        {
+            let mut cache = self.lock();
+            if let Some(x) = cache.get(internal_name) {
+                cache.stats.hits += 1;
+                return Ok(x);
+            }
+            cache.stats.misses += 1;
+        }
+        // Not holding the lock, so other threads can use the cache while the
+        // class is read.
+        let res = self.inner.resolve(internal_name)?;
+        let size = res.as_ref().map_or(0, |x| x.span().len);
+        if self.max_bytes.is_none_or(|x| size <= x) && self.max_entries != Some(0) {
+            let mut cache = self.lock();
+            cache.insert(internal_name, res.clone(), size);
+            cache.evict(self.max_bytes, self.max_entries);
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,128 @@
+//! Randomized round-trip checks over [`ClassGenerator`] output. Enabled by the
+//! `generator` feature.
+
+use crate::{generator::ClassGenerator, ClassFile, Result};
+
+/// The parts of a class compared across a round trip, rendered to strings so
+/// that two parses of different buffers can be compared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub version: (u16, u16),
+    pub access_flags: u16,
+    pub this_class: String,
+    pub super_class: Option<String>,
+    pub interfaces: Vec<String>,
+    /// Name, descriptor and access flags of each field.
+    pub fields: Vec<(String, String, u16)>,
+    /// Name, descriptor, access flags and instructions of each method.
+    pub methods: Vec<(String, String, u16, Vec<String>)>,
+    pub attributes: Vec<String>,
+}
+
+impl Summary {
+    pub fn new(class_file: &ClassFile) -> Result<Self> {
+        let fields = class_file
+            .fields()
+            .iter()
+            .map(|x| {
+                Ok((
+                    x.identifier()?.to_string(),
+                    x.field_inner.descriptor_index.get_as_string(class_file)?.to_string(),
+                    x.field_inner.access_flags.bits(),
+                ))
+            })
+            .collect::<Result<_>>()?;
+        let methods = class_file
+            .methods()
+            .iter()
+            .map(|x| {
+                let instructions = match x.code()? {
+                    Some(code) => code
+                        .instructions_with_pcs()?
+                        .iter()
+                        .map(|(pc, instruction)| format!("{}: {:?}", pc, instruction))
+                        .collect(),
+                    None => Vec::new(),
+                };
+                Ok((
+                    x.identifier()?.to_string(),
+                    x.method_inner.descriptor_index.get_as_string(class_file)?.to_string(),
+                    x.method_inner.access_flags.bits(),
+                    instructions,
+                ))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            version: (class_file.major_version(), class_file.minor_version()),
+            access_flags: class_file.access_flags.bits(),
+            this_class: class_file.this_class()?.to_string(),
+            super_class: match class_file.super_class.0 {
+                0 => None,
+                _ => Some(class_file.super_class()?.to_string()),
+            },
+            interfaces: class_file
+                .interfaces()?
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            fields,
+            methods,
+            attributes: class_file.attributes.iter().map(|x| x.name.clone()).collect(),
+        })
+    }
+}
+
+/// A generated class that failed a check.
+#[derive(Debug, Clone)]
+pub struct Counterexample {
+    /// Passing this to [`ClassGenerator::random`] reproduces the class.
+    pub seed: u64,
+    pub generator: ClassGenerator,
+    pub bytes: Vec<u8>,
+    pub problem: String,
+}
+
+fn check(generator: &ClassGenerator, bytes: Vec<u8>, write: &dyn Fn(&ClassFile) -> Result<Vec<u8>>) -> Result<Option<String>> {
+    let class_file = ClassFile::parse(bytes)?;
+    if let Some(problem) = generator.mismatch(&class_file)? {
+        return Ok(Some(problem));
+    }
+    let summary = Summary::new(&class_file)?;
+    let rewritten = match ClassFile::parse(write(&class_file)?) {
+        Ok(x) => x,
+        Err(e) => return Ok(Some(format!("rewritten class fails to parse: {}", e))),
+    };
+    let rewritten = Summary::new(&rewritten)?;
+    if rewritten != summary {
+        return Ok(Some(format!("rewritten class differs: {:?} became {:?}", summary, rewritten)));
+    }
+    Ok(None)
+}
+
+/// Generates `count` classes from consecutive seeds starting at `seed`, parses
+/// each, checks it against its generator, passes it to `write`, and compares
+/// the [`Summary`] of the re-parsed output with the original.
+///
+/// Returns every class that failed to parse, differed from what was generated,
+/// or changed across `write`.
+pub fn check_round_trips<F>(seed: u64, count: u64, write: F) -> Vec<Counterexample>
+where
+    F: Fn(&ClassFile) -> Result<Vec<u8>>,
+{
+    (seed..seed.saturating_add(count))
+        .filter_map(|seed| {
+            let generator = ClassGenerator::random(seed);
+            let bytes = generator.build();
+            let problem = match check(&generator, bytes.clone(), &write) {
+                Ok(x) => x?,
+                Err(e) => e.to_string(),
+            };
+            Some(Counterexample {
+                seed,
+                generator,
+                bytes,
+                problem,
+            })
+        })
+        .collect()
+}
@@ -1,241 +1,429 @@
-use nom::{
-    branch::alt,
-    bytes::complete::{is_a, tag},
-    character::complete::char,
-    combinator::{map, opt, value},
-    multi::{many0, many1},
-    sequence::{delimited, preceded, terminated, tuple},
-    IResult,
-};
-
-#[derive(Clone)]
-pub enum JavaType<'a> {
-    Base(BaseType),
-    Reference(ReferenceType<'a>),
-}
-
-impl<'a> JavaType<'a> {
-    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        alt((
-            map(ReferenceType::parse, |x| Self::Reference(x)),
-            map(BaseType::parse, |x| Self::Base(x)),
-        ))(input)
-    }
-}
-
-#[derive(Clone)]
-pub enum BaseType {
-    Byte,
-    Char,
-    Double,
-    Float,
-    Int,
-    Long,
-    Short,
-    Boolean,
-}
-
-impl BaseType {
-    fn parse<'a>(input: &'a str) -> IResult<&'a str, Self> {
-        alt((
-            value(Self::Byte, char('B')),
-            value(Self::Char, char('C')),
-            value(Self::Double, char('D')),
-            value(Self::Float, char('F')),
-            value(Self::Int, char('I')),
-            value(Self::Long, char('J')),
-            value(Self::Short, char('S')),
-            value(Self::Boolean, char('Z')),
-        ))(input)
-    }
-}
-
-#[derive(Clone)]
-pub enum ReferenceType<'a> {
-    JavaString,
-    JavaClass,
-    ClassType(ClassType<'a>),
-    TypeVariable(&'a str),
-    ArrayType(Box<JavaType<'a>>),
-}
-
-impl<'a> ReferenceType<'a> {
-    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        alt((
-            value(Self::JavaString, tag("Ljava/lang/String;")),
-            value(Self::JavaClass, tag("Ljava/lang/Class;")),
-            map(ClassType::parse, |x| Self::ClassType(x)),
-            map(delimited(char('T'), identifier, char(';')), |x| {
-                Self::TypeVariable(x)
-            }),
-            map(preceded(char('['), JavaType::parse), |x| {
-                Self::ArrayType(Box::new(x))
-            }),
-        ))(input)
-    }
-}
-
-#[derive(Clone)]
-pub enum TypeArgument<'a> {
-    Plus(ReferenceType<'a>),
-    Minus(ReferenceType<'a>),
-    Star,
-}
-
-impl<'a> TypeArgument<'a> {
-    fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        alt((
-            map(preceded(char('+'), ReferenceType::parse), |x| Self::Plus(x)),
-            map(preceded(char('-'), ReferenceType::parse), |x| {
-                Self::Minus(x)
-            }),
-            value(Self::Star, char('*')),
-        ))(input)
-    }
-}
-
-#[derive(Clone)]
-pub struct SimpleClassType<'a> {
-    pub name: &'a str,
-    pub type_arguments: Vec<TypeArgument<'a>>,
-}
-
-impl<'a> SimpleClassType<'a> {
-    fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        let (input, (name, type_arguments)) = tuple((
-            identifier,
-            opt(delimited(char('<'), many1(TypeArgument::parse), char('>'))),
-        ))(input)?;
-        let type_arguments = type_arguments.unwrap_or_default();
-        Ok((
-            input,
-            Self {
-                name,
-                type_arguments,
-            },
-        ))
-    }
-}
-
-#[derive(Clone)]
-pub struct ClassType<'a> {
-    pub package: Vec<&'a str>,
-    pub base: SimpleClassType<'a>,
-    pub sub: Vec<SimpleClassType<'a>>,
-}
-
-impl<'a> ClassType<'a> {
-    fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        let (input, (package, base, sub)) = delimited(
-            char('L'),
-            tuple((
-                many0(terminated(identifier, char('/'))),
-                SimpleClassType::parse,
-                many0(preceded(char('.'), SimpleClassType::parse)),
-            )),
-            char(';'),
-        )(input)?;
-        Ok((input, Self { package, base, sub }))
-    }
-}
-
-fn identifier<'a>(input: &'a str) -> IResult<&'a str, &'a str> {
-    is_a("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_")(input)
-}
-
-pub struct TypeParameter<'a> {
-    pub name: &'a str,
-    pub class_bound: Option<ReferenceType<'a>>,
-    pub interface_bounds: Vec<ReferenceType<'a>>,
-}
-
-impl<'a> TypeParameter<'a> {
-    fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        let (input, (name, class_bound, interface_bounds)) = tuple((
-            identifier,
-            preceded(char(':'), opt(ReferenceType::parse)),
-            many0(preceded(char(':'), ReferenceType::parse)),
-        ))(input)?;
-        Ok((
-            input,
-            Self {
-                name,
-                class_bound,
-                interface_bounds,
-            },
-        ))
-    }
-}
-
-pub struct ClassSignature<'a> {
-    pub type_parameters: Vec<TypeParameter<'a>>,
-    pub superclass_signature: ClassType<'a>,
-    pub superinterface_signatures: Vec<ClassType<'a>>,
-}
-
-impl<'a> ClassSignature<'a> {
-    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        let (input, (type_parameters, superclass_signature, superinterface_signatures)) =
-            tuple((
-                opt(delimited(char('<'), many1(TypeParameter::parse), char('>'))),
-                ClassType::parse,
-                many0(ClassType::parse),
-            ))(input)?;
-        let type_parameters = type_parameters.unwrap_or_default();
-        Ok((
-            input,
-            Self {
-                type_parameters,
-                superclass_signature,
-                superinterface_signatures,
-            },
-        ))
-    }
-}
-
-pub enum ThrowsSignature<'a> {
-    ClassType(ClassType<'a>),
-    TypeVariable(&'a str),
-}
-
-impl<'a> ThrowsSignature<'a> {
-    fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        preceded(
-            char('^'),
-            alt((
-                map(ClassType::parse, |x| Self::ClassType(x)),
-                map(delimited(char('T'), identifier, char(';')), |x| {
-                    Self::TypeVariable(x)
-                }),
-            )),
-        )(input)
-    }
-}
-
-pub struct MethodSignature<'a> {
-    pub type_parameters: Vec<TypeParameter<'a>>,
-    pub parameters: Vec<JavaType<'a>>,
-    pub result: Option<JavaType<'a>>,
-    pub throws: Vec<ThrowsSignature<'a>>,
-}
-
-impl<'a> MethodSignature<'a> {
-    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
-        let (input, (type_parameters, parameters, result, throws)) = tuple((
-            opt(delimited(char('<'), many1(TypeParameter::parse), char('>'))),
-            delimited(char('('), many0(JavaType::parse), char(')')),
-            alt((value(None, char('V')), map(JavaType::parse, |x| Some(x)))),
-            many0(ThrowsSignature::parse),
-        ))(input)?;
-        let type_parameters = type_parameters.unwrap_or_default();
-        Ok((
-            input,
-            Self {
-                type_parameters,
-                parameters,
-                result,
-                throws,
-            },
-        ))
-    }
-}
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag},
+    character::complete::char,
+    combinator::{map, opt, value},
+    multi::{many0, many1},
+    sequence::{delimited, preceded, terminated, tuple},
+    IResult,
+};
+
+#[derive(Clone)]
+pub enum JavaType<'a> {
+    Base(BaseType),
+    Reference(ReferenceType<'a>),
+}
+
+impl<'a> JavaType<'a> {
+    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        alt((
+            map(ReferenceType::parse, |x| Self::Reference(x)),
+            map(BaseType::parse, |x| Self::Base(x)),
+        ))(input)
+    }
+}
+
+/// Writes the type as Java source does, with simple class names, e.g.
+/// `List<String>[]`.
+impl std::fmt::Display for JavaType<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Base(x) => x.fmt(f),
+            Self::Reference(x) => x.fmt(f),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum BaseType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+}
+
+impl BaseType {
+    fn parse<'a>(input: &'a str) -> IResult<&'a str, Self> {
+        alt((
+            value(Self::Byte, char('B')),
+            value(Self::Char, char('C')),
+            value(Self::Double, char('D')),
+            value(Self::Float, char('F')),
+            value(Self::Int, char('I')),
+            value(Self::Long, char('J')),
+            value(Self::Short, char('S')),
+            value(Self::Boolean, char('Z')),
+        ))(input)
+    }
+}
+
+/// Writes the Java keyword, e.g. `int`.
+impl std::fmt::Display for BaseType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Byte => "byte",
+            Self::Char => "char",
+            Self::Double => "double",
+            Self::Float => "float",
+            Self::Int => "int",
+            Self::Long => "long",
+            Self::Short => "short",
+            Self::Boolean => "boolean",
+        })
+    }
+}
+
+#[derive(Clone)]
+pub enum ReferenceType<'a> {
+    JavaString,
+    JavaClass,
+    ClassType(ClassType<'a>),
+    TypeVariable(&'a str),
+    ArrayType(Box<JavaType<'a>>),
+}
+
+impl<'a> ReferenceType<'a> {
+    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        alt((
+            value(Self::JavaString, tag("Ljava/lang/String;")),
+            value(Self::JavaClass, tag("Ljava/lang/Class;")),
+            map(ClassType::parse, |x| Self::ClassType(x)),
+            map(delimited(char('T'), identifier, char(';')), |x| {
+                Self::TypeVariable(x)
+            }),
+            map(preceded(char('['), JavaType::parse), |x| {
+                Self::ArrayType(Box::new(x))
+            }),
+        ))(input)
+    }
+}
+
+/// Writes the type as Java source does, with simple class names, e.g.
+/// `Map.Entry<K, V>` or `T[]`.
+impl std::fmt::Display for ReferenceType<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::JavaString => f.write_str("String"),
+            Self::JavaClass => f.write_str("Class"),
+            Self::ClassType(x) => x.fmt(f),
+            Self::TypeVariable(x) => f.write_str(x),
+            Self::ArrayType(x) => write!(f, "{}[]", x),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum TypeArgument<'a> {
+    /// A type argument without a wildcard, e.g. the `String` of
+    /// `List<String>`.
+    Exact(ReferenceType<'a>),
+    Plus(ReferenceType<'a>),
+    Minus(ReferenceType<'a>),
+    Star,
+}
+
+impl<'a> TypeArgument<'a> {
+    fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        alt((
+            map(preceded(char('+'), ReferenceType::parse), |x| Self::Plus(x)),
+            map(preceded(char('-'), ReferenceType::parse), |x| {
+                Self::Minus(x)
+            }),
+            value(Self::Star, char('*')),
+            map(ReferenceType::parse, Self::Exact),
+        ))(input)
+    }
+}
+
+/// Writes the argument as Java source does, e.g. `? extends Number`.
+impl std::fmt::Display for TypeArgument<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact(x) => x.fmt(f),
+            Self::Plus(x) => write!(f, "? extends {}", x),
+            Self::Minus(x) => write!(f, "? super {}", x),
+            Self::Star => f.write_str("?"),
+        }
+    }
+}
+
+/// Writes `<A, B>` for a non-empty list of type arguments.
+fn write_type_arguments(f: &mut std::fmt::Formatter<'_>, arguments: &[TypeArgument]) -> std::fmt::Result {
+    if arguments.is_empty() {
+        return Ok(());
+    }
+    f.write_str("<")?;
+    for (i, x) in arguments.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", x)?;
+    }
+    f.write_str(">")
+}
+
+#[derive(Clone)]
+pub struct SimpleClassType<'a> {
+    pub name: &'a str,
+    pub type_arguments: Vec<TypeArgument<'a>>,
+}
+
+impl<'a> SimpleClassType<'a> {
+    fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let (input, (name, type_arguments)) = tuple((
+            identifier,
+            opt(delimited(char('<'), many1(TypeArgument::parse), char('>'))),
+        ))(input)?;
+        let type_arguments = type_arguments.unwrap_or_default();
+        Ok((
+            input,
+            Self {
+                name,
+                type_arguments,
+            },
+        ))
+    }
+}
+
+/// Writes the name and type arguments, e.g. `Entry<K, V>`. The `$` of a
+/// binary name, as in `Map$Entry`, becomes a `.`.
+impl std::fmt::Display for SimpleClassType<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name.replace('$', "."))?;
+        write_type_arguments(f, &self.type_arguments)
+    }
+}
+
+#[derive(Clone)]
+pub struct ClassType<'a> {
+    pub package: Vec<&'a str>,
+    pub base: SimpleClassType<'a>,
+    pub sub: Vec<SimpleClassType<'a>>,
+}
+
+impl<'a> ClassType<'a> {
+    /// A class type without type arguments, from an internal name like `java/io/IOException`.
+    pub(crate) fn from_internal_name(name: &'a str) -> Self {
+        let mut package = name.split('/').collect::<Vec<_>>();
+        let base = package.pop().unwrap_or_default();
+        Self {
+            package,
+            base: SimpleClassType {
+                name: base,
+                type_arguments: Vec::new(),
+            },
+            sub: Vec::new(),
+        }
+    }
+
+    fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let (input, (package, base, sub)) = delimited(
+            char('L'),
+            tuple((
+                many0(terminated(identifier, char('/'))),
+                SimpleClassType::parse,
+                many0(preceded(char('.'), SimpleClassType::parse)),
+            )),
+            char(';'),
+        )(input)?;
+        Ok((input, Self { package, base, sub }))
+    }
+}
+
+/// Writes the type as Java source does, without its package, e.g.
+/// `Outer<T>.Inner<U>`.
+impl std::fmt::Display for ClassType<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.base)?;
+        for x in &self.sub {
+            write!(f, ".{}", x)?;
+        }
+        Ok(())
+    }
+}
+
+/// A name in a signature, which may hold any character but those that
+/// delimit names (§4.7.9.1).
+fn identifier<'a>(input: &'a str) -> IResult<&'a str, &'a str> {
+    is_not(".;[/<>:")(input)
+}
+
+pub struct TypeParameter<'a> {
+    pub name: &'a str,
+    pub class_bound: Option<ReferenceType<'a>>,
+    pub interface_bounds: Vec<ReferenceType<'a>>,
+}
+
+impl<'a> TypeParameter<'a> {
+    fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let (input, (name, class_bound, interface_bounds)) = tuple((
+            identifier,
+            preceded(char(':'), opt(ReferenceType::parse)),
+            many0(preceded(char(':'), ReferenceType::parse)),
+        ))(input)?;
+        Ok((
+            input,
+            Self {
+                name,
+                class_bound,
+                interface_bounds,
+            },
+        ))
+    }
+}
+
+/// Writes the parameter as Java source declares it, e.g.
+/// `T extends Comparable<? super T>`. A lone `Object` bound is left out, but
+/// not one before interface bounds, which sets the erasure.
+impl std::fmt::Display for TypeParameter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)?;
+        let object = matches!(
+            &self.class_bound,
+            Some(ReferenceType::ClassType(x)) if x.package == ["java", "lang"] && x.base.name == "Object"
+        );
+        if object && self.interface_bounds.is_empty() {
+            return Ok(());
+        }
+        for (i, x) in self.class_bound.iter().chain(&self.interface_bounds).enumerate() {
+            f.write_str(if i == 0 { " extends " } else { " & " })?;
+            write!(f, "{}", x)?;
+        }
+        Ok(())
+    }
+}
+
+/// Type parameters as Java source declares them, e.g. `<K, V>`, or nothing.
+pub(crate) fn type_parameters_to_java(parameters: &[TypeParameter]) -> String {
+    match parameters.is_empty() {
+        true => String::new(),
+        false => format!("<{}>", parameters.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+pub struct ClassSignature<'a> {
+    pub type_parameters: Vec<TypeParameter<'a>>,
+    pub superclass_signature: ClassType<'a>,
+    pub superinterface_signatures: Vec<ClassType<'a>>,
+}
+
+impl<'a> ClassSignature<'a> {
+    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let (input, (type_parameters, superclass_signature, superinterface_signatures)) =
+            tuple((
+                opt(delimited(char('<'), many1(TypeParameter::parse), char('>'))),
+                ClassType::parse,
+                many0(ClassType::parse),
+            ))(input)?;
+        let type_parameters = type_parameters.unwrap_or_default();
+        Ok((
+            input,
+            Self {
+                type_parameters,
+                superclass_signature,
+                superinterface_signatures,
+            },
+        ))
+    }
+
+    /// The head of a declaration of a class called `name` with this
+    /// signature, as Java source writes it with simple class names, e.g.
+    /// `Foo<T> extends Bar<T> implements Comparable<Foo<T>>`. An interface
+    /// extends its superinterfaces, and an `Object` superclass is left out.
+    pub fn to_java(&self, name: &str, is_interface: bool) -> String {
+        let mut res = format!("{}{}", name, type_parameters_to_java(&self.type_parameters));
+        let superclass = &self.superclass_signature;
+        let object = superclass.package == ["java", "lang"] && superclass.base.name == "Object";
+        if !is_interface && !object {
+            res.push_str(&format!(" extends {}", superclass));
+        }
+        if !self.superinterface_signatures.is_empty() {
+            let interfaces = self.superinterface_signatures.iter().map(ToString::to_string).collect::<Vec<_>>();
+            let keyword = if is_interface { "extends" } else { "implements" };
+            res.push_str(&format!(" {} {}", keyword, interfaces.join(", ")));
+        }
+        res
+    }
+}
+
+pub enum ThrowsSignature<'a> {
+    ClassType(ClassType<'a>),
+    TypeVariable(&'a str),
+}
+
+impl<'a> ThrowsSignature<'a> {
+    fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        preceded(
+            char('^'),
+            alt((
+                map(ClassType::parse, |x| Self::ClassType(x)),
+                map(delimited(char('T'), identifier, char(';')), |x| {
+                    Self::TypeVariable(x)
+                }),
+            )),
+        )(input)
+    }
+}
+
+/// Writes the exception type as Java source does, e.g. `IOException` or `X`.
+impl std::fmt::Display for ThrowsSignature<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ClassType(x) => x.fmt(f),
+            Self::TypeVariable(x) => f.write_str(x),
+        }
+    }
+}
+
+pub struct MethodSignature<'a> {
+    pub type_parameters: Vec<TypeParameter<'a>>,
+    pub parameters: Vec<JavaType<'a>>,
+    pub result: Option<JavaType<'a>>,
+    pub throws: Vec<ThrowsSignature<'a>>,
+}
+
+impl<'a> MethodSignature<'a> {
+    pub(crate) fn parse(input: &'a str) -> IResult<&'a str, Self> {
+        let (input, (type_parameters, parameters, result, throws)) = tuple((
+            opt(delimited(char('<'), many1(TypeParameter::parse), char('>'))),
+            delimited(char('('), many0(JavaType::parse), char(')')),
+            alt((value(None, char('V')), map(JavaType::parse, |x| Some(x)))),
+            many0(ThrowsSignature::parse),
+        ))(input)?;
+        let type_parameters = type_parameters.unwrap_or_default();
+        Ok((
+            input,
+            Self {
+                type_parameters,
+                parameters,
+                result,
+                throws,
+            },
+        ))
+    }
+
+    /// A declaration of a method called `name` with this signature, as Java
+    /// source writes it with simple class names and without parameter names,
+    /// e.g. `<T> List<T> f(T) throws IOException`.
+    pub fn to_java(&self, name: &str) -> String {
+        let mut res = type_parameters_to_java(&self.type_parameters);
+        if !res.is_empty() {
+            res.push(' ');
+        }
+        match &self.result {
+            Some(x) => res.push_str(&x.to_string()),
+            None => res.push_str("void"),
+        }
+        let parameters = self.parameters.iter().map(ToString::to_string).collect::<Vec<_>>();
+        res.push_str(&format!(" {}({})", name, parameters.join(", ")));
+        if !self.throws.is_empty() {
+            let throws = self.throws.iter().map(ToString::to_string).collect::<Vec<_>>();
+            res.push_str(&format!(" throws {}", throws.join(", ")));
+        }
+        res
+    }
+}
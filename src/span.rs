@@ -0,0 +1,28 @@
+/// A range of bytes in the class file an element was parsed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Span {
+    pub start: u64,
+    pub len: u64,
+}
+
+impl Span {
+    pub fn new(start: u64, len: u64) -> Self {
+        Self { start, len }
+    }
+
+    pub(crate) fn between(start: u64, end: u64) -> Self {
+        Self {
+            start,
+            len: end.saturating_sub(start),
+        }
+    }
+
+    /// The offset just past the last byte.
+    pub fn end(&self) -> u64 {
+        self.start + self.len
+    }
+
+    pub fn contains(&self, offset: u64) -> bool {
+        (self.start..self.end()).contains(&offset)
+    }
+}
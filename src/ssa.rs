@@ -0,0 +1,472 @@
+//! Static single assignment form of method bodies, see [`Method::ssa`].
+
+use std::collections::BTreeMap;
+
+use crate::{
+    analyzer::{Frame, VerificationType},
+    cfg::{ControlFlowGraph, EdgeKind},
+    instruction::{Instruction, InstructionAt},
+    method::Method,
+    Error, Result,
+};
+
+/// A value in SSA form, defined exactly once. The number is its position in
+/// [`Ssa::definitions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Value(pub usize);
+
+/// Where a [`Value`] comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueDef {
+    /// The local at `index` on entry to the method: `this` or a parameter.
+    Parameter { index: u16 },
+    /// A phi at the start of `block`.
+    Phi { block: usize },
+    /// The result of the statement at `pc`.
+    Instruction { pc: u32 },
+    /// The exception caught on entering the handler `block`.
+    CaughtException { block: usize },
+}
+
+/// A local variable slot or operand stack slot, counted from the bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Variable {
+    Local(u16),
+    Stack(u16),
+}
+
+/// Picks between the values `variable` has on the paths into a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Phi {
+    pub result: Value,
+    pub variable: Variable,
+    /// The value from each predecessor block, or `None` for the method entry
+    /// when the entry block is also a loop header. A handler can be entered
+    /// from any instruction of a block it covers, so it may have several
+    /// operands from the same predecessor.
+    pub operands: Vec<(Option<usize>, Value)>,
+}
+
+/// An instruction with the values it takes from the stack or locals and the
+/// value it pushes, if any. The second slot of a `long` or `double` is not a
+/// separate value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub pc: u32,
+    /// In the order the instruction's operands are pushed.
+    pub arguments: Vec<Value>,
+    pub result: Option<Value>,
+}
+
+/// The SSA form of one block of [`Ssa::graph`]. Unreachable blocks are empty.
+#[derive(Debug, Clone, Default)]
+pub struct SsaBlock {
+    pub phis: Vec<Phi>,
+    pub statements: Vec<Statement>,
+}
+
+/// A method body in SSA form, see [`Method::ssa`].
+#[derive(Debug)]
+pub struct Ssa<'a> {
+    graph: ControlFlowGraph<'a>,
+    definitions: Vec<ValueDef>,
+    blocks: Vec<SsaBlock>,
+}
+
+impl<'a> Ssa<'a> {
+    /// The control-flow graph the form is built on. Its blocks line up with
+    /// [`Self::blocks`].
+    pub fn graph(&self) -> &ControlFlowGraph<'a> {
+        &self.graph
+    }
+
+    pub fn blocks(&self) -> &[SsaBlock] {
+        &self.blocks
+    }
+
+    /// Where every value comes from, indexed by [`Value`].
+    pub fn definitions(&self) -> &[ValueDef] {
+        &self.definitions
+    }
+
+    pub fn definition(&self, value: Value) -> ValueDef {
+        self.definitions[value.0]
+    }
+
+    /// The statement for the instruction at `pc`, or `None` if there is no
+    /// reachable instruction there or it only moves values around.
+    pub fn statement_at(&self, pc: u32) -> Option<&Statement> {
+        let statements = &self.blocks[self.graph.block_at(pc)?].statements;
+        let i = statements.binary_search_by_key(&pc, |x| x.pc).ok()?;
+        Some(&statements[i])
+    }
+
+    /// The instruction a statement was made from.
+    pub fn instruction(&self, statement: &Statement) -> &InstructionAt<'a> {
+        let instructions = self.graph.instructions();
+        let i = instructions
+            .binary_search_by_key(&statement.pc, |x| x.pc)
+            .expect("statements are made from instructions");
+        &instructions[i]
+    }
+}
+
+/// What a slot holds while renaming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Value(Value),
+    /// The second slot of a `long` or `double`.
+    High,
+    Undefined,
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    locals: Vec<Slot>,
+    stack: Vec<Slot>,
+}
+
+impl State {
+    fn get(&self, variable: Variable) -> Slot {
+        let slot = match variable {
+            Variable::Local(i) => self.locals.get(i as usize),
+            Variable::Stack(i) => self.stack.get(i as usize),
+        };
+        slot.copied().unwrap_or(Slot::Undefined)
+    }
+
+    fn set(&mut self, variable: Variable, value: Value) {
+        match variable {
+            Variable::Local(i) => self.locals[i as usize] = Slot::Value(value),
+            Variable::Stack(i) => self.stack[i as usize] = Slot::Value(value),
+        }
+    }
+
+    /// Forgets what `frame` has no type for, leaving a `High` after each
+    /// `long` or `double`.
+    fn normalize(&mut self, frame: &Frame) {
+        for (slots, types) in [(&mut self.locals, &frame.locals), (&mut self.stack, &frame.stack)] {
+            for (i, ty) in types.iter().enumerate() {
+                if *ty == VerificationType::Top {
+                    slots[i] = if i > 0 && types[i - 1].is_wide() {
+                        Slot::High
+                    } else {
+                        Slot::Undefined
+                    };
+                }
+            }
+        }
+    }
+}
+
+fn has_type(frame: &Frame, variable: Variable) -> bool {
+    let ty = match variable {
+        Variable::Local(i) => frame.locals.get(i as usize),
+        Variable::Stack(i) => frame.stack.get(i as usize),
+    };
+    ty.is_some_and(|x| *x != VerificationType::Top)
+}
+
+struct Builder {
+    definitions: Vec<ValueDef>,
+    statements: Vec<Statement>,
+}
+
+impl Builder {
+    fn value(&mut self, definition: ValueDef) -> Value {
+        self.definitions.push(definition);
+        Value(self.definitions.len() - 1)
+    }
+
+    /// Renames the slots `at` touches in `state`, making a statement unless
+    /// the instruction only loads, stores or shuffles values.
+    fn execute(&mut self, state: &mut State, at: &InstructionAt) -> Result<()> {
+        let error = |message: String| Error::AnalysisError { pc: at.pc, message };
+        let effect = at.instruction.stack_effect();
+        let (pops, pushes) = (effect.pops as usize, effect.pushes as usize);
+        if state.stack.len() < pops {
+            return Err(error(format!("{} pops {} slots from a stack of {}", at.instruction.mnemonic(), pops, state.stack.len())));
+        }
+        let mut popped = state.stack.split_off(state.stack.len() - pops);
+
+        let access = at.instruction.local_access();
+        let max_locals = state.locals.len();
+        let locals = |index: u16, size: usize| {
+            let index = index as usize;
+            if index + size > max_locals {
+                return Err(error(format!("{} uses local {}, past max_locals", at.instruction.mnemonic(), index + size - 1)));
+            }
+            Ok(index..index + size)
+        };
+        let mut arguments = Vec::new();
+        match (&at.instruction, access) {
+            (Instruction::Swap, _) => {
+                popped.swap(0, 1);
+                state.stack.extend(popped);
+                return Ok(());
+            }
+            (
+                Instruction::Dup
+                | Instruction::DupX1
+                | Instruction::DupX2
+                | Instruction::Dup2
+                | Instruction::Dup2X1
+                | Instruction::Dup2X2,
+                _,
+            ) => {
+                state.stack.extend_from_slice(&popped[pops - (pushes - pops)..]);
+                state.stack.extend(popped);
+                return Ok(());
+            }
+            (Instruction::Pop | Instruction::Pop2, _) => return Ok(()),
+            (_, Some(x)) if x.writes && !x.reads => {
+                state.locals[locals(x.index, popped.len())?].copy_from_slice(&popped);
+                return Ok(());
+            }
+            (_, Some(x)) if x.reads && !x.writes && pushes > 0 => {
+                state.stack.extend_from_slice(&state.locals[locals(x.index, pushes)?]);
+                return Ok(());
+            }
+            // `iinc` and `ret`
+            (_, Some(x)) => popped.push(state.locals[locals(x.index, 1)?.start]),
+            _ => {}
+        }
+        for slot in popped {
+            match slot {
+                Slot::Value(x) => arguments.push(x),
+                Slot::High => {}
+                Slot::Undefined => {
+                    return Err(error(format!("{} reads an undefined value", at.instruction.mnemonic())));
+                }
+            }
+        }
+
+        let result = match (pushes, access) {
+            (0, Some(x)) if x.writes => {
+                let result = self.value(ValueDef::Instruction { pc: at.pc });
+                state.locals[x.index as usize] = Slot::Value(result);
+                Some(result)
+            }
+            (0, _) => None,
+            _ => {
+                let result = self.value(ValueDef::Instruction { pc: at.pc });
+                state.stack.push(Slot::Value(result));
+                if pushes == 2 {
+                    state.stack.push(Slot::High);
+                }
+                Some(result)
+            }
+        };
+        self.statements.push(Statement {
+            pc: at.pc,
+            arguments,
+            result,
+        });
+        Ok(())
+    }
+}
+
+impl<'a> Method<'a> {
+    /// Converts the code to SSA form over its control-flow graph, or returns
+    /// `None` for a method without code.
+    ///
+    /// Every local and stack slot becomes a variable, and phis are placed on
+    /// the iterated dominance frontiers of their writes, leaving out slots
+    /// with no single type where paths meet. Handlers get a phi for every
+    /// typed local, as they may be entered from any instruction they cover.
+    /// Loads, stores, `pop`, `dup` and `swap` only move values, so make no
+    /// statement; every other instruction makes one with its pc, so results
+    /// can be traced back to the code.
+    ///
+    /// Fails with [`Error::AnalysisError`] where [`Self::frames`] does, or if
+    /// an instruction reads a local or stack slot with no value or uses a
+    /// local past `max_locals`.
+    pub fn ssa(&self) -> Result<Option<Ssa<'a>>> {
+        let code = match self.code()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let frames = match self.frames()? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let graph = code.control_flow_graph()?;
+        let count = graph.blocks().len();
+        let dominators = graph.dominators();
+        let frontiers = graph.dominance_frontiers(&dominators);
+        let entry_frames: Vec<Option<&Frame>> = graph.blocks().iter().map(|x| frames.at(x.start_pc)).collect();
+        let handlers: Vec<bool> = (0..count)
+            .map(|x| graph.predecessors(x).any(|x| matches!(x.kind, EdgeKind::Exception { .. })))
+            .collect();
+
+        // The blocks writing each variable, other than the entry.
+        let mut writes: BTreeMap<Variable, Vec<usize>> = BTreeMap::new();
+        for (block, x) in graph.blocks().iter().enumerate() {
+            for at in graph.block_instructions(block) {
+                let frame = match frames.at(at.pc) {
+                    Some(x) => x,
+                    None => break,
+                };
+                let mut written = Vec::new();
+                if let Some(access) = at.instruction.local_access().filter(|x| x.writes) {
+                    written.extend((access.index..access.index + access.slots).map(Variable::Local));
+                }
+                let effect = at.instruction.stack_effect();
+                let base = frame.stack.len().saturating_sub(effect.pops as usize) as u16;
+                written.extend((base..base + effect.pushes).map(Variable::Stack));
+                for variable in written {
+                    let sites = writes.entry(variable).or_default();
+                    if sites.last() != Some(&block) {
+                        sites.push(block);
+                    }
+                }
+            }
+            if let (true, Some(frame)) = (handlers[block], frames.at(x.start_pc)) {
+                for i in 0..frame.locals.len() as u16 {
+                    writes.entry(Variable::Local(i)).or_default();
+                }
+            }
+        }
+
+        let mut builder = Builder {
+            definitions: Vec::new(),
+            statements: Vec::new(),
+        };
+        let mut blocks = vec![SsaBlock::default(); count];
+        for (&variable, written) in &writes {
+            let live = |block: usize| entry_frames[block].is_some_and(|x| has_type(x, variable));
+            let local = matches!(variable, Variable::Local(_));
+            let mut placed = vec![false; count];
+            let mut queued = vec![false; count];
+            for &block in written {
+                queued[block] = true;
+            }
+            let mut worklist = written.clone();
+            let mut candidates: Vec<usize> = (0..count).filter(|&x| local && handlers[x]).collect();
+            loop {
+                for block in candidates.drain(..) {
+                    if placed[block] || !live(block) || (!local && handlers[block]) {
+                        continue;
+                    }
+                    placed[block] = true;
+                    let result = builder.value(ValueDef::Phi { block });
+                    blocks[block].phis.push(Phi {
+                        result,
+                        variable,
+                        operands: Vec::new(),
+                    });
+                    if !queued[block] {
+                        queued[block] = true;
+                        worklist.push(block);
+                    }
+                }
+                match worklist.pop() {
+                    Some(x) => candidates.extend(&frontiers[x]),
+                    None => break,
+                }
+            }
+        }
+
+        // Rename in a preorder walk of the dominator tree, so each block
+        // starts from the state its immediate dominator ends in.
+        let mut children = vec![Vec::new(); count];
+        for block in 0..count {
+            if let Some(x) = dominators.immediate_dominator(block) {
+                children[x].push(block);
+            }
+        }
+        let entry = match entry_frames.first().copied().flatten() {
+            Some(frame) => {
+                let mut state = State {
+                    locals: (0..frame.locals.len() as u16)
+                        .map(|index| Slot::Value(builder.value(ValueDef::Parameter { index })))
+                        .collect(),
+                    stack: Vec::new(),
+                };
+                state.normalize(frame);
+                state
+            }
+            None => {
+                return Ok(Some(Ssa {
+                    graph,
+                    definitions: builder.definitions,
+                    blocks,
+                }))
+            }
+        };
+        let mut exits: Vec<Option<State>> = vec![None; count];
+        let mut thrown: Vec<Vec<State>> = vec![Vec::new(); count];
+        let mut pending = vec![0];
+        while let Some(block) = pending.pop() {
+            pending.extend(children[block].iter().rev());
+            let error = |message: &str| Error::AnalysisError {
+                pc: graph.blocks()[block].start_pc,
+                message: message.to_string(),
+            };
+            let frame = entry_frames[block].ok_or_else(|| error("block in the dominator tree has no frame"))?;
+            let mut state = match dominators.immediate_dominator(block) {
+                Some(x) => exits[x].clone().ok_or_else(|| error("block's dominator was not renamed first"))?,
+                None => entry.clone(),
+            };
+            state.locals.resize(frame.locals.len(), Slot::Undefined);
+            if handlers[block] {
+                state.stack = vec![Slot::Value(builder.value(ValueDef::CaughtException { block }))];
+            } else {
+                state.stack.resize(frame.stack.len(), Slot::Undefined);
+            }
+            for phi in &blocks[block].phis {
+                state.set(phi.variable, phi.result);
+            }
+            state.normalize(frame);
+
+            let throws = graph
+                .successors(block)
+                .any(|x| matches!(x.kind, EdgeKind::Exception { .. }));
+            for at in graph.block_instructions(block) {
+                if throws {
+                    thrown[block].push(state.clone());
+                }
+                builder.execute(&mut state, at)?;
+            }
+            blocks[block].statements = std::mem::take(&mut builder.statements);
+            exits[block] = Some(state);
+        }
+
+        for (block, x) in blocks.iter_mut().enumerate() {
+            for phi in &mut x.phis {
+                let mut operands = Vec::new();
+                if block == 0 {
+                    operands.push((None, entry.get(phi.variable)));
+                }
+                for edge in graph.predecessors(block) {
+                    let states = match (&edge.kind, &exits[edge.from]) {
+                        (_, None) => continue,
+                        (EdgeKind::Exception { .. }, _) => &thrown[edge.from][..],
+                        (_, Some(x)) => std::slice::from_ref(x),
+                    };
+                    operands.extend(states.iter().map(|x| (Some(edge.from), x.get(phi.variable))));
+                }
+                for (from, slot) in operands {
+                    let value = match slot {
+                        Slot::Value(x) => x,
+                        _ => {
+                            return Err(Error::AnalysisError {
+                                pc: graph.blocks()[block].start_pc,
+                                message: format!("{:?} has no value on some path into the block", phi.variable),
+                            })
+                        }
+                    };
+                    if !phi.operands.contains(&(from, value)) {
+                        phi.operands.push((from, value));
+                    }
+                }
+            }
+        }
+
+        Ok(Some(Ssa {
+            graph,
+            definitions: builder.definitions,
+            blocks,
+        }))
+    }
+}
@@ -0,0 +1,321 @@
+//! Switches as the source wrote them, with the case labels javac lowers
+//! away for strings and enums, see [`Code::switches`].
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{
+    attributes::Code,
+    field::TypeDescriptor,
+    instruction::{Instruction, InstructionAt, LoadableConstant},
+    resolver::ClassResolver,
+    ClassFile, Result,
+};
+
+/// What a [`Switch`] switches on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SwitchKind<'a> {
+    /// An `int`, `char`, `short` or `byte`, or a switch that is not one of
+    /// the patterns below.
+    Int,
+    /// A `String`. javac switches on its `hashCode()` at `hash_pc` first,
+    /// where each case compares the string with `equals` and keeps the
+    /// position of the label that matched, then switches on that position.
+    String { hash_pc: u32 },
+    /// A constant of the enum `class`. javac switches on
+    /// `map_class.map_field[value.ordinal()]`, an array filled in by the
+    /// static initializer of `map_class`, a synthetic class usually named
+    /// like `Outer$1`, so that adding constants to the enum does not break
+    /// the switch.
+    Enum {
+        class: &'a str,
+        map_class: &'a str,
+        map_field: &'a str,
+    },
+}
+
+/// The label of a case, written as in the source by its
+/// [`Display`](std::fmt::Display), e.g. `3`, `"foo"` or `RED`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaseLabel<'a> {
+    /// An integer key, or for an enum whose switch map could not be read,
+    /// the number the map gives the constant.
+    Int(i32),
+    String(&'a str),
+    /// The name of an enum constant.
+    Enum(String),
+}
+
+impl std::fmt::Display for CaseLabel<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(x) => write!(f, "{}", x),
+            Self::String(x) => f.write_str(&crate::text::quote(x)),
+            Self::Enum(x) => f.write_str(x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchCase<'a> {
+    pub label: CaseLabel<'a>,
+    /// The pc of the code for the case.
+    pub target: u32,
+}
+
+/// Writes `case <label>: <target>`.
+impl std::fmt::Display for SwitchCase<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "case {}: {}", self.label, self.target)
+    }
+}
+
+/// A `switch` statement or expression, see [`Code::switches`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Switch<'a> {
+    /// The pc of the `tableswitch` or `lookupswitch` that jumps to the cases.
+    pub pc: u32,
+    pub kind: SwitchKind<'a>,
+    /// The cases in the order of their labels in the source, which javac
+    /// numbers strings and enum constants by, or by key for an `int`.
+    /// Labels sharing code each have a case with the same target.
+    pub cases: Vec<SwitchCase<'a>>,
+    /// The pc of the `default` case, or of the code after the switch if
+    /// there is none.
+    pub default: u32,
+}
+
+/// The keys and targets of a `tableswitch` or `lookupswitch`, leaving out
+/// the keys of a `tableswitch` that go to the default, and the default.
+fn switch_table(at: &InstructionAt) -> Option<(Vec<(i32, u32)>, u32)> {
+    let target = |x: &crate::instruction::BranchOffset| x.target(at.pc);
+    match &at.instruction {
+        Instruction::Tableswitch {
+            default,
+            low,
+            jump_offsets,
+            ..
+        } => {
+            let default = target(default)?;
+            let mut keys = Vec::new();
+            for (key, offset) in (*low..).zip(jump_offsets) {
+                let pc = target(offset)?;
+                if pc != default {
+                    keys.push((key, pc));
+                }
+            }
+            Some((keys, default))
+        }
+        Instruction::Lookupswitch { default, pairs, .. } => {
+            let keys = pairs.iter().map(|(key, offset)| Some((*key, target(offset)?))).collect::<Option<_>>()?;
+            Some((keys, target(default)?))
+        }
+        _ => None,
+    }
+}
+
+/// The value an instruction pushing an `int` constant pushes.
+fn int_constant(instruction: &Instruction) -> Option<i32> {
+    match instruction {
+        Instruction::IconstM1 => Some(-1),
+        Instruction::Iconst0 => Some(0),
+        Instruction::Iconst1 => Some(1),
+        Instruction::Iconst2 => Some(2),
+        Instruction::Iconst3 => Some(3),
+        Instruction::Iconst4 => Some(4),
+        Instruction::Iconst5 => Some(5),
+        Instruction::Bipush { value } => Some(*value as i32),
+        Instruction::Sipush { value } => Some(*value as i32),
+        Instruction::Ldc {
+            constant: LoadableConstant::Int(x),
+            ..
+        }
+        | Instruction::LdcW {
+            constant: LoadableConstant::Int(x),
+            ..
+        } => Some(*x),
+        _ => None,
+    }
+}
+
+/// The local an `iload` reads.
+fn int_load(instruction: &Instruction) -> Option<u16> {
+    match instruction {
+        Instruction::Iload { .. } | Instruction::WideIload { .. } | Instruction::Iload0 | Instruction::Iload1 | Instruction::Iload2 | Instruction::Iload3 => {
+            instruction.local_access().map(|x| x.index)
+        }
+        _ => None,
+    }
+}
+
+/// The local an `istore` writes.
+fn int_store(instruction: &Instruction) -> Option<u16> {
+    match instruction {
+        Instruction::Istore { .. } | Instruction::WideIstore { .. } | Instruction::Istore0 | Instruction::Istore1 | Instruction::Istore2 | Instruction::Istore3 => {
+            instruction.local_access().map(|x| x.index)
+        }
+        _ => None,
+    }
+}
+
+fn is_string_method(instruction: &Instruction, name: &str) -> bool {
+    matches!(instruction, Instruction::Invokevirtual { index } if index.class == "java/lang/String" && index.name == name)
+}
+
+/// The enum constants by the numbers `map_field` of `map_class` gives them,
+/// read off stores like `map_field[Enum.CONST.ordinal()] = 1` in its static
+/// initializer.
+fn switch_map(map_class: &ClassFile, map_field: &str) -> Result<BTreeMap<i32, String>> {
+    let mut res = BTreeMap::new();
+    let clinit = match map_class.methods().into_iter().find(|x| x.identifier().is_ok_and(|x| x == "<clinit>")) {
+        Some(x) => x,
+        None => return Ok(res),
+    };
+    let code = match clinit.code()? {
+        Some(x) => x,
+        None => return Ok(res),
+    };
+    let instructions = code.instructions()?;
+    let mut array = None;
+    for (i, instruction) in instructions.iter().enumerate() {
+        if let Instruction::Getstatic { field } = instruction {
+            if field.descriptor == TypeDescriptor::Array(Box::new(TypeDescriptor::Int)) {
+                array = Some(field.name);
+                continue;
+            }
+        }
+        let window = match instructions.get(i..i + 4) {
+            Some(x) => x,
+            None => continue,
+        };
+        if let [Instruction::Getstatic { field: constant }, Instruction::Invokevirtual { index }, value, Instruction::Iastore] = window {
+            if array == Some(map_field) && index.name == "ordinal" && index.class == constant.class {
+                if let Some(value) = int_constant(value) {
+                    res.insert(value, constant.name.to_string());
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// The position of the instruction at `pc`.
+fn position(instructions: &[InstructionAt], pc: u32) -> Option<usize> {
+    instructions.binary_search_by_key(&pc, |x| x.pc).ok()
+}
+
+/// If the instruction at `load`, just before a switch, loads the label
+/// position a string switch lowered by javac keeps, the position of the
+/// `hashCode()` switch and the strings by their label positions.
+fn string_labels<'a>(instructions: &[InstructionAt<'a>], load: usize) -> Option<(usize, BTreeMap<i32, &'a str>)> {
+    let join = instructions[load].pc;
+    let local = int_load(&instructions[load].instruction)?;
+    let hash = instructions[..load]
+        .iter()
+        .rposition(|x| matches!(switch_table(x), Some((_, default)) if default == join))?;
+    if !is_string_method(&instructions[hash.checked_sub(1)?].instruction, "hashCode") {
+        return None;
+    }
+
+    let mut labels = BTreeMap::new();
+    let (keys, _) = switch_table(&instructions[hash])?;
+    for (_, target) in keys {
+        let mut pc = target;
+        // Strings with the same hash code are compared one after another.
+        while pc != join {
+            let i = position(instructions, pc)?;
+            let [_, constant, equals, test, value, store, ..] = instructions.get(i..i + 6)? else {
+                return None;
+            };
+            let string = match &constant.instruction {
+                Instruction::Ldc {
+                    constant: LoadableConstant::String(x),
+                    ..
+                }
+                | Instruction::LdcW {
+                    constant: LoadableConstant::String(x),
+                    ..
+                } => *x,
+                _ => return None,
+            };
+            let next = match &test.instruction {
+                Instruction::Ifeq { offset } => offset.target(test.pc)?,
+                _ => return None,
+            };
+            if !is_string_method(&equals.instruction, "equals") || int_store(&store.instruction) != Some(local) {
+                return None;
+            }
+            labels.insert(int_constant(&value.instruction)?, string);
+            pc = next;
+        }
+    }
+    Some((hash, labels))
+}
+
+impl<'a> Code<'a> {
+    /// Every `tableswitch` and `lookupswitch` as the switch in the source it
+    /// came from, in code order. Switches javac lowered from a `String` or an
+    /// enum get the labels of the source: the `hashCode()` switch of a
+    /// string is folded into the switch on the matching label, and the
+    /// constants of an enum are read from the synthetic class holding its
+    /// switch map, found through `resolver`.
+    ///
+    /// A switch that looks only partly like one of these is left as a
+    /// [`SwitchKind::Int`] with its keys, as is every switch from other
+    /// compilers. An enum switch whose map cannot be read keeps the numbers
+    /// the map gives its constants.
+    pub fn switches(&self, resolver: &(impl ClassResolver + ?Sized)) -> Result<Vec<Switch<'a>>> {
+        let instructions = self.instructions_at()?;
+        let mut res = Vec::new();
+        let mut hash_switches = HashSet::new();
+        for (i, at) in instructions.iter().enumerate() {
+            let (keys, default) = match switch_table(at) {
+                Some(x) => x,
+                None => continue,
+            };
+            let mut kind = SwitchKind::Int;
+            let mut labels: Option<Vec<CaseLabel<'a>>> = None;
+            let previous = |n: usize| i.checked_sub(n).map(|x| &instructions[x].instruction);
+
+            if let Some((hash, strings)) = i.checked_sub(1).and_then(|x| string_labels(&instructions, x)) {
+                labels = keys.iter().map(|(key, _)| strings.get(key).map(|x| CaseLabel::String(x))).collect();
+                if labels.is_some() {
+                    hash_switches.insert(instructions[hash].pc);
+                    kind = SwitchKind::String {
+                        hash_pc: instructions[hash].pc,
+                    };
+                }
+            } else if let (Some(Instruction::Iaload), Some(Instruction::Invokevirtual { index })) = (previous(1), previous(2)) {
+                let map = instructions[..i - 2].iter().rev().find_map(|x| match &x.instruction {
+                    Instruction::Getstatic { field } if field.name.starts_with("$SwitchMap$") => Some(field),
+                    _ => None,
+                });
+                if let Some(map) = map.filter(|_| index.name == "ordinal" && index.descriptor.parameter_types().is_empty()) {
+                    kind = SwitchKind::Enum {
+                        class: index.class,
+                        map_class: map.class,
+                        map_field: map.name,
+                    };
+                    let constants = match resolver.resolve(map.class)? {
+                        Some(map_class) => switch_map(&map_class, map.name)?,
+                        None => BTreeMap::new(),
+                    };
+                    labels = keys.iter().map(|(key, _)| constants.get(key).map(|x| CaseLabel::Enum(x.clone()))).collect();
+                }
+            }
+
+            let labels = labels.unwrap_or_else(|| keys.iter().map(|(key, _)| CaseLabel::Int(*key)).collect());
+            res.push(Switch {
+                pc: at.pc,
+                kind,
+                cases: labels
+                    .into_iter()
+                    .zip(&keys)
+                    .map(|(label, (_, target))| SwitchCase { label, target: *target })
+                    .collect(),
+                default,
+            });
+        }
+        res.retain(|x| !hash_switches.contains(&x.pc));
+        Ok(res)
+    }
+}
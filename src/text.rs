@@ -0,0 +1,1411 @@
+//! A textual assembly format for whole classes, in the style of Jasmin, read
+//! by [`parse_assembly`] and written by [`ClassFile::to_assembly`].
+//!
+//! ```text
+//! .version 49 0
+//! .class public super Hello
+//! .super java/lang/Object
+//!
+//! .method public static main([Ljava/lang/String;)V
+//!     .limit stack 2
+//!     getstatic java/lang/System/out Ljava/io/PrintStream;
+//!     ldc "Hello"
+//!     invokevirtual java/io/PrintStream/println(Ljava/lang/String;)V
+//!     return
+//! .end method
+//! ```
+//!
+//! Each line holds a directive, a label definition `name:` or an instruction,
+//! and `;` at the start of a word comments out the rest of the line. Class
+//! level directives are:
+//!
+//! - `.version <major> <minor>`, 50 0 by default,
+//! - `.constant <index> <kind> <operands>`, giving the constant pool entry at
+//!   the next index before anything else adds to the pool. The kind is the
+//!   entry's lowercase name, e.g. `utf8 "Hello"`, `integer 1`,
+//!   `methodref 5 12` or `methodhandle 6 20`, with references to other
+//!   entries given as their indices and a `utf8` that is not valid Unicode
+//!   given in hexadecimal,
+//! - `.class <flags> <name>`, with flags written as their lowercase names,
+//!   e.g. `public`, or as hexadecimal numbers,
+//! - `.super <name>`, `java/lang/Object` by default, or `.nosuper`,
+//! - `.implements <name>`,
+//! - `.source "<file name>"`,
+//! - `.bootstrap <kind> <reference> <argument>...`, defining bootstrap
+//!   methods 0, 1, ... in order,
+//! - `.field <flags> <name> <descriptor> [= <constant>]`, optionally
+//!   followed by its `.attribute`s and `.end field`,
+//! - `.method <flags> <name><descriptor>`, up to `.end method`, containing
+//!   code and the method level directives `.limit stack <n>`,
+//!   `.limit locals <n>`, `.throws <class>`,
+//!   `.catch <class> from <label> to <label> using <label>`, where the class
+//!   may be `all`, and `.codeattribute <name> [<hex contents>]` for
+//!   attributes of the code,
+//! - `.attribute <name> [<hex contents>]`, for the class, field or method.
+//!
+//! Instructions are written as their mnemonic followed by operands:
+//!
+//! - locals as numbers, with `wide` implied by the operands,
+//! - branch targets as labels,
+//! - `tableswitch <low>` followed by its targets and `default : <label>`,
+//!   and `lookupswitch` followed by `<key> : <label>` pairs and
+//!   `default : <label>`, on as many lines as needed,
+//! - fields as `<class>/<name> <descriptor>` and methods as
+//!   `<class>/<name><descriptor>`, preceded by `interface` for interface
+//!   methods outside `invokeinterface`,
+//! - `invokedynamic <bootstrap method> <name><descriptor>`,
+//! - constants as `1`, `1L`, `1.0f`, `1.0d`, `"string"`, `class <name>` or
+//!   `methodtype <descriptor>`, plus `handle <kind> <reference>` among
+//!   bootstrap arguments, where the kind is one of `getfield`, `getstatic`,
+//!   `putfield`, `putstatic`, `invokevirtual`, `invokestatic`,
+//!   `invokespecial`, `newinvokespecial` and `invokeinterface`.
+//!
+//! Pool indices and pcs in hex contents are taken as they are. Instructions
+//! and directives refer to constants by value, reusing the first matching
+//! `.constant`, so giving the pool of the original class keeps such indices
+//! valid, and code assembled as it was written keeps its pcs.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Write,
+    iter::Peekable,
+    str::{Chars, FromStr},
+};
+
+use bitflags::Flags;
+
+use crate::{
+    asm::{Constant, Label, Op},
+    attributes::Code,
+    builder::{
+        ClassFileBuilder, ConstantPoolBuilder, ExceptionHandler, FieldBuilder, MethodBuilder,
+    },
+    canonical::Reader,
+    field::TypeDescriptor,
+    instruction::{BranchOffset, Instruction, OperandFormat, OPCODE_TABLE},
+    method::MethodDescriptor,
+    mutf8,
+    raw::{BootstrapMethodAttrInfo, ConstantPoolItem, Reference},
+    ClassAccessFlags, ClassFile, ClassIndex, Error, MethodAccessFlags, NameAndTypeIndex, Result,
+    Utf8Index,
+};
+
+/// Method handle kinds, from `REF_getField` = 1.
+const HANDLE_KINDS: [&str; 9] = [
+    "getfield",
+    "getstatic",
+    "putfield",
+    "putstatic",
+    "invokevirtual",
+    "invokestatic",
+    "invokespecial",
+    "newinvokespecial",
+    "invokeinterface",
+];
+
+/// `newarray` element types, from `T_BOOLEAN` = 4.
+pub(crate) const ARRAY_TYPES: [&str; 8] = [
+    "boolean", "char", "float", "double", "byte", "short", "int", "long",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    String(String),
+    Colon,
+    Newline,
+}
+
+fn syntax_error(line: usize, message: impl std::fmt::Display) -> Error {
+    Error::AssemblyError(format!("line {}: {}", line, message))
+}
+
+fn unescape(chars: &mut Peekable<Chars>, line: usize) -> Result<String> {
+    let mut res = String::new();
+    loop {
+        let c = match chars.next() {
+            None => return Err(syntax_error(line, "unterminated string")),
+            Some('"') => return Ok(res),
+            Some('\\') => match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some(c @ ('\\' | '"')) => c,
+                Some('u') => {
+                    let hex = chars.by_ref().take(4).collect::<String>();
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| syntax_error(line, format!("invalid escape \\u{}", hex)))?
+                }
+                c => return Err(syntax_error(line, format!("invalid escape {:?}", c))),
+            },
+            Some(c) => c,
+        };
+        res.push(c);
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    for (line, text) in text.lines().enumerate() {
+        let line = line + 1;
+        let mut chars = text.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            match c {
+                ';' => break,
+                ':' => {
+                    chars.next();
+                    tokens.push((line, Token::Colon));
+                }
+                '"' => {
+                    chars.next();
+                    tokens.push((line, Token::String(unescape(&mut chars, line)?)));
+                }
+                _ if c.is_whitespace() => {
+                    chars.next();
+                }
+                _ => {
+                    let mut word = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || c == ':' || c == '"' {
+                            break;
+                        }
+                        word.push(c);
+                        chars.next();
+                    }
+                    tokens.push((line, Token::Word(word)));
+                }
+            }
+        }
+        tokens.push((line, Token::Newline));
+    }
+    Ok(tokens)
+}
+
+fn literal(word: &str) -> Option<Constant> {
+    if let Some(x) = word.strip_suffix('L') {
+        return x.parse().ok().map(Constant::Long);
+    }
+    if let Some(x) = word.strip_suffix(['f', 'F']) {
+        return x.parse().ok().map(Constant::Float);
+    }
+    if let Ok(x) = word.parse() {
+        return Some(Constant::Integer(x));
+    }
+    word.strip_suffix(['d', 'D'])
+        .unwrap_or(word)
+        .parse()
+        .ok()
+        .map(Constant::Double)
+}
+
+/// Splits `<class>/<name><descriptor>` after the name.
+fn split_method(word: &str) -> Option<(&str, &str)> {
+    word.find('(').map(|x| word.split_at(x))
+}
+
+/// Splits `<class>/<name>` at the last `/`.
+fn split_member(word: &str) -> Option<(String, String)> {
+    word.rsplit_once('/')
+        .map(|(class, name)| (class.to_string(), name.to_string()))
+}
+
+/// The local variable slots taken by the parameters of a method.
+fn parameter_slots(descriptor: &MethodDescriptor, is_static: bool) -> u16 {
+    descriptor.parameter_slots() + !is_static as u16
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    pool: ConstantPoolBuilder,
+}
+
+impl Parser {
+    fn error(&self, message: impl std::fmt::Display) -> Error {
+        let line = self
+            .tokens
+            .get(self.pos.saturating_sub(1))
+            .map_or(0, |x| x.0);
+        syntax_error(line, message)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|x| &x.1)
+    }
+
+    fn peek_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(x)) if x == word)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let res = self.tokens.get(self.pos).map(|x| x.1.clone());
+        self.pos += 1;
+        res
+    }
+
+    fn word(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Word(x)) => Ok(x),
+            x => Err(self.error(format!("expected a word, found {:?}", x))),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<()> {
+        match self.word()? {
+            x if x == keyword => Ok(()),
+            x => Err(self.error(format!("expected {}, found {}", keyword, x))),
+        }
+    }
+
+    fn number<T: FromStr>(&mut self) -> Result<T> {
+        let word = self.word()?;
+        word.parse()
+            .map_err(|_| self.error(format!("invalid number {}", word)))
+    }
+
+    fn colon(&mut self) -> Result<()> {
+        match self.next() {
+            Some(Token::Colon) => Ok(()),
+            x => Err(self.error(format!("expected :, found {:?}", x))),
+        }
+    }
+
+    fn at_end_of_line(&self) -> bool {
+        matches!(self.peek(), None | Some(Token::Newline))
+    }
+
+    fn end_of_line(&mut self) -> Result<()> {
+        match self.next() {
+            None | Some(Token::Newline) => Ok(()),
+            x => Err(self.error(format!("expected the end of the line, found {:?}", x))),
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    /// The words up to the end of the line or `=`.
+    fn words(&mut self) -> Vec<String> {
+        let mut res = Vec::new();
+        while let Some(Token::Word(x)) = self.peek() {
+            if x == "=" {
+                break;
+            }
+            res.push(x.clone());
+            self.pos += 1;
+        }
+        res
+    }
+
+    fn flags<F: Flags<Bits = u16>>(&self, words: &[String]) -> Result<F> {
+        let mut bits = 0;
+        for word in words {
+            bits |= match word.strip_prefix("0x") {
+                Some(x) => u16::from_str_radix(x, 16).ok(),
+                None => F::from_name(&word.to_uppercase()).map(|x| x.bits()),
+            }
+            .ok_or_else(|| self.error(format!("unknown flag {}", word)))?;
+        }
+        Ok(F::from_bits_retain(bits))
+    }
+
+    fn constant(&mut self) -> Result<Constant> {
+        match self.next() {
+            Some(Token::String(x)) => Ok(Constant::String(x)),
+            Some(Token::Word(x)) => match x.as_str() {
+                "class" => Ok(Constant::Class(self.word()?)),
+                "methodtype" => Ok(Constant::MethodType(self.word()?)),
+                _ => literal(&x).ok_or_else(|| self.error(format!("invalid constant {}", x))),
+            },
+            x => Err(self.error(format!("expected a constant, found {:?}", x))),
+        }
+    }
+
+    fn interface(&mut self) -> bool {
+        let res = self.peek_word("interface");
+        self.pos += res as usize;
+        res
+    }
+
+    fn method_ref(&mut self) -> Result<(String, String, String)> {
+        let word = self.word()?;
+        split_method(&word)
+            .and_then(|(member, descriptor)| {
+                let (class, name) = split_member(member)?;
+                Some((class, name, descriptor.to_string()))
+            })
+            .ok_or_else(|| {
+                self.error(format!(
+                    "expected <class>/<name><descriptor>, found {}",
+                    word
+                ))
+            })
+    }
+
+    fn field_ref(&mut self) -> Result<(String, String, String)> {
+        let word = self.word()?;
+        let (class, name) = split_member(&word)
+            .ok_or_else(|| self.error(format!("expected <class>/<name>, found {}", word)))?;
+        Ok((class, name, self.word()?))
+    }
+
+    /// A method handle by kind and reference, added to the pool.
+    fn handle(&mut self) -> Result<u16> {
+        let word = self.word()?;
+        let kind = HANDLE_KINDS
+            .iter()
+            .position(|x| *x == word)
+            .ok_or_else(|| self.error(format!("unknown method handle kind {}", word)))?
+            as u8
+            + 1;
+        let interface = self.interface() || kind == 9;
+        let reference = match kind {
+            1..=4 => {
+                let (class, name, descriptor) = self.field_ref()?;
+                self.pool.field_ref(&class, &name, &descriptor)?.0
+            }
+            _ => {
+                let (class, name, descriptor) = self.method_ref()?;
+                match interface {
+                    true => {
+                        self.pool
+                            .interface_method_ref(&class, &name, &descriptor)?
+                            .0
+                    }
+                    false => self.pool.method_ref(&class, &name, &descriptor)?.0,
+                }
+            }
+        };
+        Ok(self.pool.method_handle(kind, reference)?.0)
+    }
+
+    fn bootstrap_argument(&mut self) -> Result<u16> {
+        if self.peek_word("handle") {
+            self.pos += 1;
+            return self.handle();
+        }
+        let constant = self.constant()?;
+        constant.add_to(&mut self.pool)
+    }
+
+    fn hex(&self, hex: &str) -> Result<Vec<u8>> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|x| {
+                hex.get(x..x + 2)
+                    .and_then(|x| u8::from_str_radix(x, 16).ok())
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| self.error(format!("invalid hex contents {}", hex)))
+    }
+
+    fn attribute(&mut self) -> Result<(String, Vec<u8>)> {
+        let name = self.word()?;
+        if self.at_end_of_line() {
+            return Ok((name, Vec::new()));
+        }
+        let hex = self.word()?;
+        Ok((name, self.hex(&hex)?))
+    }
+
+    /// A `.constant` entry, which must take the next index of the pool.
+    fn pool_entry(&mut self) -> Result<()> {
+        let index: u16 = self.number()?;
+        if index != self.pool.next_index() {
+            return Err(self.error(format!(
+                "constant {} out of order, the next index is {}",
+                index,
+                self.pool.next_index()
+            )));
+        }
+        let kind = self.word()?;
+        let item = match kind.as_str() {
+            "utf8" => match self.next() {
+                Some(Token::String(value)) => ConstantPoolItem::Utf8 {
+                    bytes: mutf8::encode(&value),
+                    value,
+                },
+                Some(Token::Word(x)) => {
+                    let bytes = self.hex(&x)?;
+                    let value = mutf8::decode(&bytes)
+                        .ok_or_else(|| self.error(format!("invalid modified UTF-8 {}", x)))?;
+                    ConstantPoolItem::Utf8 { bytes, value }
+                }
+                x => return Err(self.error(format!("expected a string, found {:?}", x))),
+            },
+            "integer" => ConstantPoolItem::Integer {
+                value: self.number()?,
+            },
+            "float" => ConstantPoolItem::Float {
+                value: self.number()?,
+            },
+            "long" => ConstantPoolItem::Long {
+                value: self.number()?,
+            },
+            "double" => ConstantPoolItem::Double {
+                value: self.number()?,
+            },
+            "class" => ConstantPoolItem::Class {
+                name_index: Utf8Index(self.number()?),
+            },
+            "string" => ConstantPoolItem::String {
+                string_index: Utf8Index(self.number()?),
+            },
+            "methodtype" => ConstantPoolItem::MethodType {
+                descriptor_index: Utf8Index(self.number()?),
+            },
+            "module" => ConstantPoolItem::Module {
+                name_index: Utf8Index(self.number()?),
+            },
+            "package" => ConstantPoolItem::Package {
+                name_index: Utf8Index(self.number()?),
+            },
+            "fieldref" => ConstantPoolItem::Fieldref {
+                class_index: ClassIndex(self.number()?),
+                name_and_type_index: NameAndTypeIndex(self.number()?),
+            },
+            "methodref" => ConstantPoolItem::Methodref {
+                class_index: ClassIndex(self.number()?),
+                name_and_type_index: NameAndTypeIndex(self.number()?),
+            },
+            "interfacemethodref" => ConstantPoolItem::InterfaceMethodref {
+                class_index: ClassIndex(self.number()?),
+                name_and_type_index: NameAndTypeIndex(self.number()?),
+            },
+            "nameandtype" => ConstantPoolItem::NameAndType {
+                name_index: Utf8Index(self.number()?),
+                descriptor_index: Utf8Index(self.number()?),
+            },
+            "methodhandle" => ConstantPoolItem::MethodHandle {
+                reference: Reference {
+                    kind: self.number()?,
+                    index: self.number()?,
+                },
+            },
+            "dynamic" => ConstantPoolItem::Dynamic {
+                bootstrap_method_attr_index: BootstrapMethodAttrInfo(self.number()?),
+                name_and_type_index: NameAndTypeIndex(self.number()?),
+            },
+            "invokedynamic" => ConstantPoolItem::InvokeDynamic {
+                bootstrap_method_attr_index: BootstrapMethodAttrInfo(self.number()?),
+                name_and_type_index: NameAndTypeIndex(self.number()?),
+            },
+            x => return Err(self.error(format!("unknown constant kind {}", x))),
+        };
+        self.pool.push(item)?;
+        Ok(())
+    }
+
+    fn label(&mut self, labels: &mut HashMap<String, Label>) -> Result<Label> {
+        let name = self.word()?;
+        let next = Label(labels.len() as u32);
+        Ok(*labels.entry(name).or_insert(next))
+    }
+
+    fn field(&mut self) -> Result<FieldBuilder> {
+        let mut words = self.words();
+        let (descriptor, name) = match (words.pop(), words.pop()) {
+            (Some(descriptor), Some(name)) => (descriptor, name),
+            _ => return Err(self.error("expected a field name and descriptor")),
+        };
+        let parsed = match TypeDescriptor::parse(&descriptor) {
+            Ok(("", x)) => x,
+            _ => return Err(self.error(format!("invalid field descriptor {}", descriptor))),
+        };
+        let mut field = FieldBuilder::new(self.flags(&words)?, &name, &parsed);
+        if self.peek_word("=") {
+            self.pos += 1;
+            field = field.with_constant_value(self.constant()?);
+        }
+
+        // Attributes on the lines below belong to the field only if they are
+        // closed by `.end field`, and otherwise to the class.
+        let end = self.pos;
+        let mut attributes = Vec::new();
+        self.end_of_line()?;
+        loop {
+            self.skip_newlines();
+            if !self.peek_word(".attribute") {
+                break;
+            }
+            self.pos += 1;
+            attributes.push(self.attribute()?);
+            self.end_of_line()?;
+        }
+        if self.peek_word(".end")
+            && matches!(self.tokens.get(self.pos + 1), Some((_, Token::Word(x))) if x == "field")
+        {
+            self.pos += 2;
+            field = attributes.into_iter().fold(field, |field, (name, data)| {
+                field.with_attribute(&name, data)
+            });
+        } else {
+            self.pos = end;
+        }
+        Ok(field)
+    }
+
+    fn method(&mut self) -> Result<MethodBuilder> {
+        let mut words = self.words();
+        let signature = words.pop().unwrap_or_default();
+        let (name, descriptor) = split_method(&signature).ok_or_else(|| {
+            self.error(format!("expected <name><descriptor>, found {}", signature))
+        })?;
+        let parsed = match MethodDescriptor::parse(descriptor) {
+            Ok(("", x)) => x,
+            _ => return Err(self.error(format!("invalid method descriptor {}", descriptor))),
+        };
+        let access_flags = self.flags::<MethodAccessFlags>(&words)?;
+        let is_static = access_flags.contains(MethodAccessFlags::STATIC);
+        let mut method = MethodBuilder::new(access_flags, name, &parsed);
+        self.end_of_line()?;
+
+        let mut labels = HashMap::new();
+        let mut bound = HashSet::new();
+        let mut ops = Vec::new();
+        let (mut max_stack, mut max_locals) = (None, None);
+        let mut exceptions = Vec::new();
+        let mut attributes = Vec::new();
+        let mut exceptions_position = None;
+        let mut code_attributes = Vec::new();
+        loop {
+            self.skip_newlines();
+            let word = self.word()?;
+            match word.as_str() {
+                ".end" => {
+                    self.keyword("method")?;
+                    break;
+                }
+                ".limit" => match self.word()?.as_str() {
+                    "stack" => max_stack = Some(self.number()?),
+                    "locals" => max_locals = Some(self.number()?),
+                    x => return Err(self.error(format!("unknown limit {}", x))),
+                },
+                ".throws" => {
+                    // The attribute goes where its first entry is.
+                    if exceptions.is_empty() {
+                        exceptions_position = Some(attributes.len());
+                        attributes.push(("Exceptions".to_string(), Vec::new()));
+                    }
+                    let class = self.word()?;
+                    exceptions.push(self.pool.class(&class)?.0);
+                }
+                ".catch" => {
+                    let class = self.word()?;
+                    self.keyword("from")?;
+                    let start = self.label(&mut labels)?;
+                    self.keyword("to")?;
+                    let end = self.label(&mut labels)?;
+                    self.keyword("using")?;
+                    let handler = self.label(&mut labels)?;
+                    method = method.with_exception_handler(ExceptionHandler {
+                        start,
+                        end,
+                        handler,
+                        catch_type: (class != "all").then_some(class),
+                    });
+                }
+                ".attribute" => attributes.push(self.attribute()?),
+                ".codeattribute" => code_attributes.push(self.attribute()?),
+                _ if self.peek() == Some(&Token::Colon) => {
+                    // An instruction may follow on the same line.
+                    self.pos += 1;
+                    if !bound.insert(word.clone()) {
+                        return Err(self.error(format!("label {} is bound twice", word)));
+                    }
+                    let next = Label(labels.len() as u32);
+                    ops.push(Op::Label(*labels.entry(word).or_insert(next)));
+                    continue;
+                }
+                _ => ops.push(self.instruction(&word, &mut labels)?),
+            }
+            self.end_of_line()?;
+        }
+
+        if let Some(x) = labels.keys().find(|x| !bound.contains(*x)) {
+            return Err(self.error(format!("label {} in {} is never bound", x, name)));
+        }
+        if let Some(x) = exceptions_position {
+            let mut data = (exceptions.len() as u16).to_be_bytes().to_vec();
+            data.extend(exceptions.iter().flat_map(|x| x.to_be_bytes()));
+            attributes[x].1 = data;
+        }
+        method = attributes.into_iter().fold(method, |method, (name, data)| {
+            method.with_attribute(&name, data)
+        });
+        method = code_attributes
+            .into_iter()
+            .fold(method, |method, (name, data)| {
+                method.with_code_attribute(&name, data)
+            });
+        if !ops.is_empty() {
+            let max_stack = max_stack
+                .ok_or_else(|| self.error(format!("{} has code but no .limit stack", name)))?;
+            let max_locals = max_locals.unwrap_or_else(|| parameter_slots(&parsed, is_static));
+            method = method.with_code(max_stack, max_locals, ops);
+        }
+        Ok(method)
+    }
+
+    fn instruction(&mut self, mnemonic: &str, labels: &mut HashMap<String, Label>) -> Result<Op> {
+        let opcode = OPCODE_TABLE
+            .iter()
+            .position(|x| x.mnemonic == mnemonic)
+            .ok_or_else(|| self.error(format!("unknown instruction {}", mnemonic)))?
+            as u8;
+        Ok(match opcode {
+            0x10 => Op::Bipush(self.number()?),
+            0x11 => Op::Sipush(self.number()?),
+            0x12..=0x14 => Op::Ldc(self.constant()?),
+            0x15..=0x19 | 0x36..=0x3a | 0xa9 => Op::Local {
+                opcode,
+                index: self.number()?,
+            },
+            0x84 => Op::Iinc {
+                index: self.number()?,
+                constant: self.number()?,
+            },
+            0x99..=0xa8 | 0xc6..=0xc9 => Op::Branch {
+                opcode,
+                target: self.label(labels)?,
+            },
+            0xaa => {
+                let low = self.number()?;
+                let mut targets = Vec::new();
+                loop {
+                    self.skip_newlines();
+                    if self.peek_word("default") {
+                        self.pos += 1;
+                        self.colon()?;
+                        let default = self.label(labels)?;
+                        break Op::Tableswitch {
+                            default,
+                            low,
+                            targets,
+                        };
+                    }
+                    targets.push(self.label(labels)?);
+                }
+            }
+            0xab => {
+                let mut pairs = Vec::new();
+                loop {
+                    self.skip_newlines();
+                    if self.peek_word("default") {
+                        self.pos += 1;
+                        self.colon()?;
+                        let default = self.label(labels)?;
+                        break Op::Lookupswitch { default, pairs };
+                    }
+                    let key = self.number()?;
+                    self.colon()?;
+                    pairs.push((key, self.label(labels)?));
+                }
+            }
+            0xb2..=0xb5 => {
+                let (class, name, descriptor) = self.field_ref()?;
+                Op::Field {
+                    opcode,
+                    class,
+                    name,
+                    descriptor,
+                }
+            }
+            0xb6..=0xb9 => {
+                let interface = self.interface() || opcode == 0xb9;
+                let (class, name, descriptor) = self.method_ref()?;
+                Op::Invoke {
+                    opcode,
+                    class,
+                    name,
+                    descriptor,
+                    interface,
+                }
+            }
+            0xba => {
+                let bootstrap_method = self.number()?;
+                let word = self.word()?;
+                let (name, descriptor) = split_method(&word).ok_or_else(|| {
+                    self.error(format!("expected <name><descriptor>, found {}", word))
+                })?;
+                Op::Invokedynamic {
+                    bootstrap_method,
+                    name: name.to_string(),
+                    descriptor: descriptor.to_string(),
+                }
+            }
+            0xbb | 0xbd | 0xc0 | 0xc1 => Op::Type {
+                opcode,
+                class: self.word()?,
+            },
+            0xbc => {
+                let word = self.word()?;
+                let atype = ARRAY_TYPES
+                    .iter()
+                    .position(|x| *x == word)
+                    .ok_or_else(|| self.error(format!("unknown array type {}", word)))?;
+                Op::Newarray(atype as u8 + 4)
+            }
+            0xc5 => Op::Multianewarray {
+                class: self.word()?,
+                dimensions: self.number()?,
+            },
+            0xc4 => return Err(self.error("wide is implied by the operands")),
+            _ => Op::Simple(opcode),
+        })
+    }
+
+    fn class(mut self) -> Result<ClassFileBuilder> {
+        let mut version = None;
+        let mut header = None;
+        let mut super_class = Some("java/lang/Object".to_string());
+        let mut interfaces = Vec::new();
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+        let mut attributes = Vec::new();
+        let mut bootstrap_methods = Vec::new();
+        let mut bootstrap_position = None;
+        loop {
+            self.skip_newlines();
+            let directive = match self.next() {
+                None => break,
+                Some(Token::Word(x)) => x,
+                x => return Err(self.error(format!("expected a directive, found {:?}", x))),
+            };
+            match directive.as_str() {
+                ".version" => version = Some((self.number()?, self.number()?)),
+                ".constant" => self.pool_entry()?,
+                ".class" => {
+                    let mut words = self.words();
+                    let name = words
+                        .pop()
+                        .ok_or_else(|| self.error("expected a class name"))?;
+                    header = Some((self.flags::<ClassAccessFlags>(&words)?, name));
+                }
+                ".super" => super_class = Some(self.word()?),
+                ".nosuper" => super_class = None,
+                ".implements" => interfaces.push(self.word()?),
+                ".source" => {
+                    let name = match self.next() {
+                        Some(Token::String(x)) => x,
+                        x => return Err(self.error(format!("expected a string, found {:?}", x))),
+                    };
+                    let index = self.pool.utf8(&name)?.0;
+                    attributes.push(("SourceFile".to_string(), index.to_be_bytes().to_vec()));
+                }
+                ".bootstrap" => {
+                    // The attribute goes where its first entry is.
+                    if bootstrap_methods.is_empty() {
+                        bootstrap_position = Some(attributes.len());
+                        attributes.push(("BootstrapMethods".to_string(), Vec::new()));
+                    }
+                    let mut entry = self.handle()?.to_be_bytes().to_vec();
+                    let mut arguments = Vec::new();
+                    while !self.at_end_of_line() {
+                        arguments.push(self.bootstrap_argument()?);
+                    }
+                    entry.extend((arguments.len() as u16).to_be_bytes());
+                    entry.extend(arguments.iter().flat_map(|x| x.to_be_bytes()));
+                    bootstrap_methods.push(entry);
+                }
+                ".field" => fields.push(self.field()?),
+                ".method" => methods.push(self.method()?),
+                ".attribute" => attributes.push(self.attribute()?),
+                x => return Err(self.error(format!("unknown directive {}", x))),
+            }
+            self.end_of_line()?;
+        }
+
+        let (access_flags, name) = header.ok_or_else(|| syntax_error(1, "missing .class"))?;
+        if let Some(x) = bootstrap_position {
+            let mut data = (bootstrap_methods.len() as u16).to_be_bytes().to_vec();
+            data.extend(bootstrap_methods.concat());
+            attributes[x].1 = data;
+        }
+        let mut class = ClassFileBuilder::new(&name)
+            .with_constant_pool(self.pool)
+            .with_access_flags(access_flags);
+        if let Some((major, minor)) = version {
+            class = class.with_version(major, minor);
+        }
+        class = match super_class {
+            Some(x) => class.with_super_class(&x),
+            None => class.without_super_class(),
+        };
+        class = interfaces
+            .iter()
+            .fold(class, |class, x| class.with_interface(x));
+        class = fields.into_iter().fold(class, ClassFileBuilder::with_field);
+        class = methods
+            .into_iter()
+            .fold(class, ClassFileBuilder::with_method);
+        Ok(attributes.iter().fold(class, |class, (name, data)| {
+            class.with_attribute(name, data.clone())
+        }))
+    }
+}
+
+/// Assembles a class from the [text format](self).
+pub fn parse_assembly(text: &str) -> Result<ClassFile> {
+    let parser = Parser {
+        tokens: tokenize(text)?,
+        pos: 0,
+        pool: ConstantPoolBuilder::new(),
+    };
+    ClassFile::parse(parser.class()?.build()?)
+}
+
+pub(crate) fn quote(value: &str) -> String {
+    let mut res = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            '\\' | '"' => {
+                res.push('\\');
+                res.push(c);
+            }
+            _ if c.is_control() => {
+                let _ = write!(res, "\\u{:04x}", c as u32);
+            }
+            _ => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// `value` as written by `{:?}`, with `Infinity` in place of `inf`.
+fn float_text(value: impl std::fmt::Debug) -> String {
+    format!("{:?}", value).replace("inf", "Infinity")
+}
+
+fn flag_words<F: Flags<Bits = u16>>(flags: &F) -> String {
+    let mut res = String::new();
+    for (name, _) in flags.iter_names() {
+        res.push_str(&name.to_lowercase());
+        res.push(' ');
+    }
+    let unknown = flags.bits() & !F::all().bits();
+    if unknown != 0 {
+        let _ = write!(res, "{:#06x} ", unknown);
+    }
+    res
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().fold(String::new(), |mut res, x| {
+        let _ = write!(res, "{:02x}", x);
+        res
+    })
+}
+
+fn unsupported(message: impl std::fmt::Display) -> Error {
+    Error::WriteError(format!("cannot write {} as assembly", message))
+}
+
+impl ClassFile {
+    fn constant_item(&self, index: u16) -> Result<&ConstantPoolItem> {
+        (index as usize)
+            .checked_sub(1)
+            .and_then(|x| self.constant_pool.0.get(x))
+            .ok_or_else(|| {
+                Error::ConstantPoolError(format!("invalid constant pool index {}", index))
+            })
+    }
+
+    /// A field or method reference, preceded by `interface` for interface
+    /// methods unless `implied`.
+    fn reference_text(&self, index: u16, implied: bool) -> Result<String> {
+        let (class_index, name_and_type_index, prefix, separator) =
+            match self.constant_item(index)? {
+                ConstantPoolItem::Fieldref {
+                    class_index,
+                    name_and_type_index,
+                } => (class_index, name_and_type_index, "", " "),
+                ConstantPoolItem::Methodref {
+                    class_index,
+                    name_and_type_index,
+                } => (class_index, name_and_type_index, "", ""),
+                ConstantPoolItem::InterfaceMethodref {
+                    class_index,
+                    name_and_type_index,
+                } => (
+                    class_index,
+                    name_and_type_index,
+                    if implied { "" } else { "interface " },
+                    "",
+                ),
+                x => return Err(unsupported(format_args!("{:?} as a member reference", x))),
+            };
+        Ok(format!(
+            "{}{}/{}{}{}",
+            prefix,
+            class_index.get_as_string(self)?,
+            name_and_type_index.get_name(self)?,
+            separator,
+            name_and_type_index.get_descriptor(self)?
+        ))
+    }
+
+    fn handle_text(&self, reference: &Reference) -> Result<String> {
+        let kind = (reference.kind as usize)
+            .checked_sub(1)
+            .and_then(|x| HANDLE_KINDS.get(x))
+            .ok_or_else(|| unsupported(format_args!("method handle kind {}", reference.kind)))?;
+        Ok(format!(
+            "{} {}",
+            kind,
+            self.reference_text(reference.index, reference.kind == 9)?
+        ))
+    }
+
+    fn constant_text(&self, index: u16) -> Result<String> {
+        Ok(match self.constant_item(index)? {
+            ConstantPoolItem::Integer { value } => value.to_string(),
+            ConstantPoolItem::Float { value } => format!("{}f", float_text(value)),
+            ConstantPoolItem::Long { value } => format!("{}L", value),
+            ConstantPoolItem::Double { value } => format!("{}d", float_text(value)),
+            ConstantPoolItem::String { string_index } => quote(string_index.get_as_string(self)?),
+            ConstantPoolItem::Class { name_index } => {
+                format!("class {}", name_index.get_as_string(self)?)
+            }
+            ConstantPoolItem::MethodType { descriptor_index } => {
+                format!("methodtype {}", descriptor_index.get_as_string(self)?)
+            }
+            ConstantPoolItem::MethodHandle { reference } => {
+                format!("handle {}", self.handle_text(reference)?)
+            }
+            x => return Err(unsupported(format_args!("constant {:?}", x))),
+        })
+    }
+
+    /// A pool entry as written after `.constant <index>`, with references to
+    /// other entries as their indices.
+    fn pool_entry_text(&self, item: &ConstantPoolItem) -> Result<String> {
+        let operands = match item {
+            ConstantPoolItem::Utf8 { bytes, value } if mutf8::encode(value) == *bytes => {
+                quote(value)
+            }
+            ConstantPoolItem::Utf8 { bytes, .. } => hex(bytes),
+            ConstantPoolItem::Integer { value } => value.to_string(),
+            ConstantPoolItem::Float { value } => float_text(value),
+            ConstantPoolItem::Long { value } => value.to_string(),
+            ConstantPoolItem::Double { value } => float_text(value),
+            ConstantPoolItem::Class { name_index: x }
+            | ConstantPoolItem::String { string_index: x }
+            | ConstantPoolItem::MethodType {
+                descriptor_index: x,
+            }
+            | ConstantPoolItem::Module { name_index: x }
+            | ConstantPoolItem::Package { name_index: x } => x.0.to_string(),
+            ConstantPoolItem::Fieldref {
+                class_index,
+                name_and_type_index,
+            }
+            | ConstantPoolItem::Methodref {
+                class_index,
+                name_and_type_index,
+            }
+            | ConstantPoolItem::InterfaceMethodref {
+                class_index,
+                name_and_type_index,
+            } => format!("{} {}", class_index.0, name_and_type_index.0),
+            ConstantPoolItem::NameAndType {
+                name_index,
+                descriptor_index,
+            } => format!("{} {}", name_index.0, descriptor_index.0),
+            ConstantPoolItem::MethodHandle { reference } => {
+                format!("{} {}", reference.kind, reference.index)
+            }
+            ConstantPoolItem::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            }
+            | ConstantPoolItem::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => format!(
+                "{} {}",
+                bootstrap_method_attr_index.0, name_and_type_index.0
+            ),
+            ConstantPoolItem::Custom { tag, .. } => {
+                return Err(unsupported(format_args!(
+                    "constant pool entry of tag {}",
+                    tag
+                )))
+            }
+            ConstantPoolItem::Skip => unreachable!("Skip entries are not written"),
+        };
+        Ok(format!("{} {}", item.kind().to_lowercase(), operands))
+    }
+
+    fn attribute_text(
+        name: &str,
+        data: &[u8],
+        directive: &str,
+        indent: &str,
+        out: &mut String,
+    ) -> Result<()> {
+        if data.is_empty() {
+            writeln!(out, "{}{} {}", indent, directive, name)?;
+        } else {
+            writeln!(out, "{}{} {} {}", indent, directive, name, hex(data))?;
+        }
+        Ok(())
+    }
+
+    fn bootstrap_methods_text(&self, data: &[u8], out: &mut String) -> Result<()> {
+        let r = &mut Reader::new(data, "BootstrapMethods");
+        for _ in 0..r.u16()? {
+            let reference = match self.constant_item(r.u16()?)? {
+                ConstantPoolItem::MethodHandle { reference } => reference,
+                x => return Err(unsupported(format_args!("bootstrap method {:?}", x))),
+            };
+            write!(out, ".bootstrap {}", self.handle_text(reference)?)?;
+            for _ in 0..r.u16()? {
+                write!(out, " {}", self.constant_text(r.u16()?)?)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// The instructions of `code` by pc, adding the pcs they branch to to
+    /// `targets`.
+    pub(crate) fn instructions_text(
+        &self,
+        code: &Code,
+        targets: &mut BTreeSet<u32>,
+    ) -> Result<Vec<(u32, String)>> {
+        let mut res = Vec::new();
+        for (pc, instruction) in code.instructions_with_pcs()? {
+            let mut label = |offset: &BranchOffset| match offset.target(pc) {
+                Some(x) => {
+                    targets.insert(x);
+                    Ok(format!("L{}", x))
+                }
+                None => Err(unsupported(format_args!(
+                    "branch from pc {} to before the code",
+                    pc
+                ))),
+            };
+            // Decoding keeps the constants but not which entries they are.
+            let index = || {
+                code.constant_index(pc)
+                    .ok_or_else(|| unsupported(format_args!("operands at pc {}", pc)))
+            };
+            let operands = match &instruction {
+                Instruction::Bipush { value } => value.to_string(),
+                Instruction::Sipush { value } => value.to_string(),
+                Instruction::Ldc { .. } | Instruction::LdcW { .. } | Instruction::Ldc2W { .. } => {
+                    let index = index()?;
+                    if let ConstantPoolItem::MethodHandle { .. }
+                    | ConstantPoolItem::Dynamic { .. } = self.constant_item(index)?
+                    {
+                        return Err(unsupported(format_args!("ldc of constant {}", index)));
+                    }
+                    self.constant_text(index)?
+                }
+                Instruction::Iinc { index, constant }
+                | Instruction::WideIinc { index, constant } => format!("{} {}", index.0, constant),
+                Instruction::Tableswitch {
+                    default,
+                    low,
+                    jump_offsets,
+                    ..
+                } => {
+                    let mut res = low.to_string();
+                    for x in jump_offsets {
+                        res.push_str("\n        ");
+                        res.push_str(&label(x)?);
+                    }
+                    res.push_str("\n        default : ");
+                    res.push_str(&label(default)?);
+                    res
+                }
+                Instruction::Lookupswitch { default, pairs, .. } => {
+                    let mut res = String::new();
+                    for (key, x) in pairs {
+                        let _ = write!(res, "\n        {} : {}", key, label(x)?);
+                    }
+                    res.push_str("\n        default : ");
+                    res.push_str(&label(default)?);
+                    res
+                }
+                Instruction::Getfield { .. }
+                | Instruction::Getstatic { .. }
+                | Instruction::Putfield { .. }
+                | Instruction::Putstatic { .. }
+                | Instruction::Invokevirtual { .. }
+                | Instruction::Invokespecial { .. }
+                | Instruction::Invokestatic { .. } => self.reference_text(index()?, false)?,
+                Instruction::Invokeinterface { .. } => self.reference_text(index()?, true)?,
+                Instruction::Invokedynamic { .. } => match self.constant_item(index()?)? {
+                    ConstantPoolItem::InvokeDynamic {
+                        bootstrap_method_attr_index,
+                        name_and_type_index,
+                    } => format!(
+                        "{} {}{}",
+                        bootstrap_method_attr_index.0,
+                        name_and_type_index.get_name(self)?,
+                        name_and_type_index.get_descriptor(self)?
+                    ),
+                    x => return Err(unsupported(format_args!("invokedynamic of {:?}", x))),
+                },
+                Instruction::Anewarray { class }
+                | Instruction::Checkcast { class }
+                | Instruction::Instanceof { class }
+                | Instruction::New { class } => class.to_string(),
+                Instruction::Multianewarray { class, dimensions } => {
+                    format!("{} {}", class, dimensions)
+                }
+                Instruction::Newarray { atype } => ARRAY_TYPES[*atype as usize - 4].to_string(),
+                Instruction::Unknown { opcode, .. } => {
+                    return Err(unsupported(format_args!(
+                        "opcode {:#04x} at pc {}",
+                        opcode, pc
+                    )))
+                }
+                x => match (x.local_access(), x.branch_offsets().first()) {
+                    // Not the forms such as `iload_0` that imply their index.
+                    (Some(access), _) if x.operand_format() == Some(OperandFormat::LocalIndex) => {
+                        access.index.to_string()
+                    }
+                    (_, Some(offset)) => label(offset)?,
+                    _ => String::new(),
+                },
+            };
+            let mnemonic = instruction.mnemonic();
+            res.push((
+                pc,
+                if operands.is_empty() {
+                    mnemonic.to_string()
+                } else {
+                    format!("{} {}", mnemonic, operands)
+                },
+            ));
+        }
+        Ok(res)
+    }
+
+    fn code_text(&self, data: &[u8], out: &mut String) -> Result<()> {
+        let r = &mut Reader::new(data, "Code");
+        let (max_stack, max_locals) = (r.u16()?, r.u16()?);
+        let length = r.u32()?;
+        let code = r.bytes(length as usize)?;
+        writeln!(out, "    .limit stack {}", max_stack)?;
+        writeln!(out, "    .limit locals {}", max_locals)?;
+
+        let mut targets = BTreeSet::new();
+        for _ in 0..r.u16()? {
+            let (start, end, handler) = (r.u16()? as u32, r.u16()? as u32, r.u16()? as u32);
+            let catch_type = match r.u16()? {
+                0 => "all",
+                x => ClassIndex(x).get_as_string(self)?,
+            };
+            writeln!(
+                out,
+                "    .catch {} from L{} to L{} using L{}",
+                catch_type, start, end, handler
+            )?;
+            targets.extend([start, end, handler]);
+        }
+        for _ in 0..r.u16()? {
+            let name = self.utf8_constant(r.u16()?)?;
+            let length = r.u32()?;
+            Self::attribute_text(
+                name,
+                r.bytes(length as usize)?,
+                ".codeattribute",
+                "    ",
+                out,
+            )?;
+        }
+
+        let decoded = crate::attributes::decode::<Code>("Code", data, 0, self)?;
+        let instructions = self.instructions_text(&decoded, &mut targets)?;
+        let pcs = instructions
+            .iter()
+            .map(|x| x.0)
+            .chain([code.len() as u32])
+            .collect::<BTreeSet<_>>();
+        if let Some(x) = targets.difference(&pcs).next() {
+            return Err(unsupported(format_args!("code referring to pc {}", x)));
+        }
+        for (pc, text) in instructions {
+            if targets.contains(&pc) {
+                writeln!(out, "L{}:", pc)?;
+            }
+            writeln!(out, "    {}", text)?;
+        }
+        if targets.contains(&(code.len() as u32)) {
+            writeln!(out, "L{}:", code.len())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the class in the [text format](crate::text), which
+    /// [`parse_assembly`] reads back.
+    ///
+    /// The whole constant pool is written out first, and attributes the
+    /// format has no syntax for, such as `LineNumberTable` and
+    /// `StackMapTable`, as hex contents, so that reading the text back gives
+    /// the same class. Instructions using a duplicate pool entry, or an
+    /// `ldc` of a string that is not valid Unicode, come back using an
+    /// equivalent entry. Fails on constants the format cannot express, such
+    /// as `ldc` of a method handle.
+    pub fn to_assembly(&self) -> Result<String> {
+        let mut out = String::new();
+        writeln!(
+            out,
+            ".version {} {}",
+            self._major_version, self._minor_version
+        )?;
+        for (i, item) in self.constant_pool.0.iter().enumerate() {
+            if !matches!(item, ConstantPoolItem::Skip) {
+                writeln!(out, ".constant {} {}", i + 1, self.pool_entry_text(item)?)?;
+            }
+        }
+        writeln!(
+            out,
+            ".class {}{}",
+            flag_words(&self.access_flags),
+            self.this_class()?
+        )?;
+        match self.super_class.0 {
+            0 => writeln!(out, ".nosuper")?,
+            _ => writeln!(out, ".super {}", self.super_class()?)?,
+        }
+        for interface in self.interfaces()? {
+            writeln!(out, ".implements {}", interface)?;
+        }
+        for attribute in self.attributes.iter() {
+            match attribute.name.as_str() {
+                "SourceFile" => {
+                    let index = Reader::new(&attribute.data, "SourceFile").u16()?;
+                    writeln!(out, ".source {}", quote(self.utf8_constant(index)?))?;
+                }
+                "BootstrapMethods" => self.bootstrap_methods_text(&attribute.data, &mut out)?,
+                name => Self::attribute_text(name, &attribute.data, ".attribute", "", &mut out)?,
+            }
+        }
+
+        for field in &self.fields {
+            writeln!(out)?;
+            write!(
+                out,
+                ".field {}{} {}",
+                flag_words(&field.access_flags),
+                field.name_index.get_as_string(self)?,
+                field.descriptor_index.get_as_string(self)?
+            )?;
+            let mut others = String::new();
+            for attribute in field.attributes.iter() {
+                match attribute.name.as_str() {
+                    "ConstantValue" => {
+                        let index = Reader::new(&attribute.data, "ConstantValue").u16()?;
+                        write!(out, " = {}", self.constant_text(index)?)?;
+                    }
+                    name => Self::attribute_text(
+                        name,
+                        &attribute.data,
+                        ".attribute",
+                        "    ",
+                        &mut others,
+                    )?,
+                }
+            }
+            writeln!(out)?;
+            if !others.is_empty() {
+                out.push_str(&others);
+                writeln!(out, ".end field")?;
+            }
+        }
+
+        for method in &self.methods {
+            writeln!(out)?;
+            writeln!(
+                out,
+                ".method {}{}{}",
+                flag_words(&method.access_flags),
+                method.name_index.get_as_string(self)?,
+                method.descriptor_index.get_as_string(self)?
+            )?;
+            let mut code = None;
+            for attribute in method.attributes.iter() {
+                match attribute.name.as_str() {
+                    "Code" => code = Some(&attribute.data),
+                    "Exceptions" => {
+                        let r = &mut Reader::new(&attribute.data, "Exceptions");
+                        for _ in 0..r.u16()? {
+                            writeln!(
+                                out,
+                                "    .throws {}",
+                                ClassIndex(r.u16()?).get_as_string(self)?
+                            )?;
+                        }
+                    }
+                    name => {
+                        Self::attribute_text(name, &attribute.data, ".attribute", "    ", &mut out)?
+                    }
+                }
+            }
+            if let Some(code) = code {
+                self.code_text(code, &mut out)?;
+            }
+            writeln!(out, ".end method")?;
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_class_round_trips() {
+        let bytes = include_bytes!("../example.class");
+        let class_file = ClassFile::parse(&bytes[..]).unwrap();
+        let text = class_file.to_assembly().unwrap();
+        assert!(text.contains(".codeattribute StackMapTable "));
+        let assembled = parse_assembly(&text).unwrap();
+        assert_eq!(assembled.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn field_attributes_end_with_end_field() {
+        let attribute_names = |text: &str| {
+            let class_file = parse_assembly(text).unwrap();
+            let names = |x: Vec<crate::hexdump::RawAttribute>| {
+                x.iter().map(|x| x.name.to_string()).collect::<Vec<_>>()
+            };
+            let field = names(class_file.fields()[0].raw_attributes());
+            (field, names(class_file.raw_attributes()))
+        };
+        let class = ".class public Fields\n.field public x I\n.attribute Synthetic\n";
+        assert_eq!(
+            attribute_names(class),
+            (vec![], vec!["Synthetic".to_string()])
+        );
+        assert_eq!(
+            attribute_names(&format!("{}.end field\n", class)),
+            (vec!["Synthetic".to_string()], vec![])
+        );
+    }
+
+    #[test]
+    fn constants_come_before_the_pool_is_used() {
+        let text = ".class public Late\n.source \"Late.java\"\n.constant 1 utf8 \"Late\"\n";
+        assert!(parse_assembly(text).is_err());
+        let text = ".constant 1 utf8 \"Late\"\n.constant 2 class 1\n.class public Late\n";
+        assert_eq!(parse_assembly(text).unwrap().this_class().unwrap(), "Late");
+    }
+}
@@ -0,0 +1,18 @@
+//! No-op unless the `tracing` feature is enabled.
+
+/// Enters a span for the rest of the enclosing block.
+macro_rules! span {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(tracing::Level::$level, $($arg)*).entered();
+    };
+}
+
+macro_rules! event {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!(tracing::Level::$level, $($arg)*);
+    };
+}
+
+pub(crate) use {event, span};
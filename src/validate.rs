@@ -0,0 +1,807 @@
+//! Structural checks on a whole class file (JVMS §4.8), see
+//! [`ClassFile::validate`].
+
+use std::collections::HashSet;
+
+use crate::{
+    canonical::Reader,
+    field::TypeDescriptor,
+    method::MethodDescriptor,
+    raw::{AttributeEntry, ClassAccessFlags, ConstantPoolItem, FieldAccessFlags, MethodAccessFlags, Reference},
+    ClassFile, Error, Result,
+};
+
+/// Where in the class file a [`Problem`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// The constant pool entry at this index.
+    ConstantPool(u16),
+    /// The class's own flags, names, interfaces or attributes.
+    Class,
+    /// The field at this position in [`ClassFile::fields`].
+    Field(usize),
+    /// The method at this position in [`ClassFile::methods`].
+    Method(usize),
+}
+
+/// A rule of the class file format the class breaks, see
+/// [`ClassFile::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub location: Location,
+    pub message: String,
+}
+
+/// The kinds of entries `ldc` and bootstrap arguments may refer to.
+const LOADABLE: &[&str] = &[
+    "Integer",
+    "Float",
+    "Long",
+    "Double",
+    "Class",
+    "String",
+    "MethodHandle",
+    "MethodType",
+    "Dynamic",
+];
+
+/// An unqualified name (§4.2.2), as of a field or one part of a class name.
+fn is_unqualified_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['.', ';', '[', '/'])
+}
+
+fn is_method_name(name: &str) -> bool {
+    name == "<init>" || name == "<clinit>" || (is_unqualified_name(name) && !name.contains(['<', '>']))
+}
+
+/// A class or interface name in internal form, e.g. `java/lang/Object`.
+fn is_binary_name(name: &str) -> bool {
+    name.split('/').all(is_unqualified_name)
+}
+
+/// Whether the classes named in `ty` are well-formed and it has at most 255
+/// array dimensions.
+fn is_valid_type(mut ty: &TypeDescriptor) -> bool {
+    let mut dimensions = 0;
+    while let TypeDescriptor::Array(x) = ty {
+        ty = x;
+        dimensions += 1;
+    }
+    dimensions <= 255
+        && match ty {
+            TypeDescriptor::ClassName(x) => is_binary_name(x),
+            _ => true,
+        }
+}
+
+/// What a CONSTANT_Class may name: a class, an interface or an array type.
+fn is_class_name(name: &str) -> bool {
+    if name.starts_with('[') {
+        matches!(TypeDescriptor::parse(name), Ok(("", ty)) if is_valid_type(&ty))
+    } else {
+        is_binary_name(name)
+    }
+}
+
+struct Validator<'a> {
+    class_file: &'a ClassFile,
+    location: Location,
+    problems: Vec<Problem>,
+}
+
+impl<'a> Validator<'a> {
+    fn report(&mut self, message: String) {
+        self.problems.push(Problem {
+            location: self.location,
+            message,
+        });
+    }
+
+    /// Reports every message whose rule is broken.
+    fn rules(&mut self, rules: &[(bool, &str)]) {
+        for (broken, message) in rules {
+            if *broken {
+                self.report(message.to_string());
+            }
+        }
+    }
+
+    /// The entry at `index`, reporting `what` refers to it unless it is one
+    /// of `kinds`.
+    fn expect(&mut self, index: u16, kinds: &[&str], what: &str) -> Option<&'a ConstantPoolItem> {
+        match self.class_file.constant(index) {
+            None | Some(ConstantPoolItem::Skip) => {
+                self.report(format!("{} has invalid constant pool index {}", what, index));
+                None
+            }
+            Some(x) if kinds.contains(&x.kind()) => Some(x),
+            Some(x) => {
+                self.report(format!("{} {} is a {}, not a {}", what, index, x.kind(), kinds.join(" or ")));
+                None
+            }
+        }
+    }
+
+    fn utf8(&mut self, index: u16, what: &str) -> Option<&'a str> {
+        match self.expect(index, &["Utf8"], what)? {
+            ConstantPoolItem::Utf8 { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The name of the class entry at `index`.
+    fn class_name(&mut self, index: u16, what: &str) -> Option<&'a str> {
+        match self.expect(index, &["Class"], what)? {
+            ConstantPoolItem::Class { name_index } => name_index.get_as_string(self.class_file).ok(),
+            _ => None,
+        }
+    }
+
+    /// The name and descriptor of the NameAndType entry at `index`.
+    fn name_and_type(&mut self, index: u16, what: &str) -> Option<(&'a str, &'a str)> {
+        match self.expect(index, &["NameAndType"], what)? {
+            ConstantPoolItem::NameAndType {
+                name_index,
+                descriptor_index,
+            } => Some((
+                name_index.get_as_string(self.class_file).ok()?,
+                descriptor_index.get_as_string(self.class_file).ok()?,
+            )),
+            _ => None,
+        }
+    }
+
+    fn field_descriptor(&mut self, descriptor: &'a str) -> Option<TypeDescriptor<'a>> {
+        match TypeDescriptor::parse(descriptor) {
+            Ok(("", ty)) if is_valid_type(&ty) => Some(ty),
+            _ => {
+                self.report(format!("invalid field descriptor {:?}", descriptor));
+                None
+            }
+        }
+    }
+
+    fn method_descriptor(&mut self, descriptor: &'a str) -> Option<MethodDescriptor<'a>> {
+        match MethodDescriptor::parse(descriptor) {
+            Ok(("", x)) if x.parameter_types().iter().chain(x.return_type()).all(is_valid_type) => Some(x),
+            _ => {
+                self.report(format!("invalid method descriptor {:?}", descriptor));
+                None
+            }
+        }
+    }
+
+    fn constant_pool(&mut self) {
+        let version = self.class_file.major_version();
+        let bootstrap_methods = self
+            .class_file
+            .attributes
+            .get("BootstrapMethods")
+            .and_then(|x| x.get(..2))
+            .map_or(0, |x| u16::from_be_bytes([x[0], x[1]]));
+        for (i, item) in self.class_file.constant_pool.0.iter().enumerate() {
+            self.location = Location::ConstantPool(i as u16 + 1);
+            let since = match item {
+                ConstantPoolItem::MethodHandle { .. }
+                | ConstantPoolItem::MethodType { .. }
+                | ConstantPoolItem::InvokeDynamic { .. } => 51,
+                ConstantPoolItem::Module { .. } | ConstantPoolItem::Package { .. } => 53,
+                ConstantPoolItem::Dynamic { .. } => 55,
+                _ => 0,
+            };
+            if version < since {
+                self.report(format!("{} entries need class file version {}", item.kind(), since));
+            }
+            match item {
+                ConstantPoolItem::Class { name_index } => {
+                    if let Some(name) = self.utf8(name_index.0, "class name") {
+                        if !is_class_name(name) {
+                            self.report(format!("invalid class name {:?}", name));
+                        }
+                    }
+                }
+                ConstantPoolItem::Fieldref {
+                    class_index,
+                    name_and_type_index,
+                } => {
+                    self.class_name(class_index.0, "class");
+                    if let Some((name, descriptor)) = self.name_and_type(name_and_type_index.0, "name and type") {
+                        if !is_unqualified_name(name) {
+                            self.report(format!("invalid field name {:?}", name));
+                        }
+                        self.field_descriptor(descriptor);
+                    }
+                }
+                ConstantPoolItem::Methodref {
+                    class_index,
+                    name_and_type_index,
+                }
+                | ConstantPoolItem::InterfaceMethodref {
+                    class_index,
+                    name_and_type_index,
+                } => {
+                    self.class_name(class_index.0, "class");
+                    if let Some((name, descriptor)) = self.name_and_type(name_and_type_index.0, "name and type") {
+                        // Only a Methodref may name a constructor.
+                        let constructor = name == "<init>" && matches!(item, ConstantPoolItem::Methodref { .. });
+                        if name == "<clinit>" || (!constructor && !is_method_name(name)) {
+                            self.report(format!("invalid method name {:?}", name));
+                        }
+                        let descriptor = self.method_descriptor(descriptor);
+                        if constructor && descriptor.is_some_and(|x| x.return_type().is_some()) {
+                            self.report("<init> must return void".to_string());
+                        }
+                    }
+                }
+                ConstantPoolItem::String { string_index } => {
+                    self.utf8(string_index.0, "string");
+                }
+                ConstantPoolItem::NameAndType {
+                    name_index,
+                    descriptor_index,
+                } => {
+                    self.utf8(name_index.0, "name");
+                    self.utf8(descriptor_index.0, "descriptor");
+                }
+                ConstantPoolItem::MethodHandle { reference } => self.method_handle(reference, version),
+                ConstantPoolItem::MethodType { descriptor_index } => {
+                    if let Some(descriptor) = self.utf8(descriptor_index.0, "descriptor") {
+                        self.method_descriptor(descriptor);
+                    }
+                }
+                ConstantPoolItem::Dynamic {
+                    bootstrap_method_attr_index,
+                    name_and_type_index,
+                }
+                | ConstantPoolItem::InvokeDynamic {
+                    bootstrap_method_attr_index,
+                    name_and_type_index,
+                } => {
+                    if bootstrap_method_attr_index.0 >= bootstrap_methods {
+                        self.report(format!(
+                            "bootstrap method {} is not in the BootstrapMethods attribute",
+                            bootstrap_method_attr_index.0
+                        ));
+                    }
+                    if let Some((name, descriptor)) = self.name_and_type(name_and_type_index.0, "name and type") {
+                        if matches!(item, ConstantPoolItem::Dynamic { .. }) {
+                            if !is_unqualified_name(name) {
+                                self.report(format!("invalid field name {:?}", name));
+                            }
+                            self.field_descriptor(descriptor);
+                        } else {
+                            if !is_method_name(name) || name.starts_with('<') {
+                                self.report(format!("invalid method name {:?}", name));
+                            }
+                            self.method_descriptor(descriptor);
+                        }
+                    }
+                }
+                ConstantPoolItem::Module { name_index } | ConstantPoolItem::Package { name_index } => {
+                    self.utf8(name_index.0, "name");
+                }
+                ConstantPoolItem::Integer { .. }
+                | ConstantPoolItem::Float { .. }
+                | ConstantPoolItem::Long { .. }
+                | ConstantPoolItem::Double { .. }
+                | ConstantPoolItem::Utf8 { .. }
+                | ConstantPoolItem::Custom { .. }
+                | ConstantPoolItem::Skip => {}
+            }
+        }
+    }
+
+    fn method_handle(&mut self, reference: &Reference, version: u16) {
+        let kinds: &[&str] = match reference.kind {
+            1..=4 => &["Fieldref"],
+            5 | 8 => &["Methodref"],
+            6 | 7 if version >= 52 => &["Methodref", "InterfaceMethodref"],
+            6 | 7 => &["Methodref"],
+            9 => &["InterfaceMethodref"],
+            x => return self.report(format!("invalid reference kind {}", x)),
+        };
+        let name = match self.expect(reference.index, kinds, "reference") {
+            Some(ConstantPoolItem::Methodref {
+                name_and_type_index, ..
+            })
+            | Some(ConstantPoolItem::InterfaceMethodref {
+                name_and_type_index, ..
+            }) => name_and_type_index.get_name(self.class_file).ok(),
+            _ => None,
+        };
+        match name {
+            Some(x) if reference.kind == 8 && x != "<init>" => {
+                self.report(format!("newInvokeSpecial of {}, not <init>", x))
+            }
+            Some(x) if reference.kind != 8 && (x == "<init>" || x == "<clinit>") => {
+                self.report(format!("reference kind {} to {}", reference.kind, x))
+            }
+            _ => {}
+        }
+    }
+
+    fn header(&mut self) {
+        self.location = Location::Class;
+        let class_file = self.class_file;
+        let flags = &class_file.access_flags;
+        let interface = flags.contains(ClassAccessFlags::INTERFACE);
+        self.rules(&[
+            (
+                interface && !flags.contains(ClassAccessFlags::ABSTRACT),
+                "an interface must be ACC_ABSTRACT",
+            ),
+            (
+                interface && flags.intersects(ClassAccessFlags::FINAL | ClassAccessFlags::SUPER | ClassAccessFlags::ENUM),
+                "an interface cannot be ACC_FINAL, ACC_SUPER or ACC_ENUM",
+            ),
+            (
+                !interface && flags.contains(ClassAccessFlags::ANNOTATION),
+                "only an interface can be ACC_ANNOTATION",
+            ),
+            (
+                !interface && flags.contains(ClassAccessFlags::FINAL | ClassAccessFlags::ABSTRACT),
+                "a class cannot be both ACC_FINAL and ACC_ABSTRACT",
+            ),
+            (
+                flags.contains(ClassAccessFlags::MODULE) && flags.bits() != ClassAccessFlags::MODULE.bits(),
+                "a module cannot have any other flags",
+            ),
+        ]);
+
+        let this_class = self.class_name(class_file.this_class.0, "this_class");
+        if this_class.is_some_and(|x| x.starts_with('[')) {
+            self.report("this_class is an array type".to_string());
+        }
+        match class_file.super_class.0 {
+            0 if this_class != Some("java/lang/Object") && !flags.contains(ClassAccessFlags::MODULE) => {
+                self.report("only java/lang/Object has no superclass".to_string())
+            }
+            0 => {}
+            x => match self.class_name(x, "super_class") {
+                Some(x) if x.starts_with('[') => self.report("super_class is an array type".to_string()),
+                Some(x) if interface && x != "java/lang/Object" => {
+                    self.report(format!("an interface extends {}, not java/lang/Object", x))
+                }
+                _ => {}
+            },
+        }
+        for x in &class_file.interfaces {
+            if self.class_name(x.0, "interface").is_some_and(|x| x.starts_with('[')) {
+                self.report("an interface is an array type".to_string());
+            }
+        }
+        self.attributes(&class_file.attributes.0);
+    }
+
+    fn fields(&mut self) {
+        let class_file = self.class_file;
+        let interface = class_file.access_flags.contains(ClassAccessFlags::INTERFACE);
+        let mut seen = HashSet::new();
+        for (i, field) in class_file.fields.iter().enumerate() {
+            self.location = Location::Field(i);
+            let flags = &field.access_flags;
+            let access = FieldAccessFlags::PUBLIC | FieldAccessFlags::PRIVATE | FieldAccessFlags::PROTECTED;
+            let constant = FieldAccessFlags::PUBLIC | FieldAccessFlags::STATIC | FieldAccessFlags::FINAL;
+            self.rules(&[
+                (
+                    flags.bits() & access.bits() != 0 && !(flags.bits() & access.bits()).is_power_of_two(),
+                    "a field can only be one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED",
+                ),
+                (
+                    flags.contains(FieldAccessFlags::FINAL | FieldAccessFlags::VOLATILE),
+                    "a field cannot be both ACC_FINAL and ACC_VOLATILE",
+                ),
+                (
+                    interface && !flags.contains(constant),
+                    "an interface field must be ACC_PUBLIC, ACC_STATIC and ACC_FINAL",
+                ),
+                (
+                    interface
+                        && flags.intersects(
+                            FieldAccessFlags::PRIVATE
+                                | FieldAccessFlags::PROTECTED
+                                | FieldAccessFlags::VOLATILE
+                                | FieldAccessFlags::TRANSIENT
+                                | FieldAccessFlags::ENUM,
+                        ),
+                    "an interface field cannot be ACC_PRIVATE, ACC_PROTECTED, ACC_VOLATILE, ACC_TRANSIENT or ACC_ENUM",
+                ),
+            ]);
+
+            let name = self.utf8(field.name_index.0, "name");
+            if let Some(x) = name.filter(|x| !is_unqualified_name(x)) {
+                self.report(format!("invalid field name {:?}", x));
+            }
+            let descriptor = self.utf8(field.descriptor_index.0, "descriptor");
+            if let Some(x) = descriptor {
+                self.field_descriptor(x);
+            }
+            if let (Some(name), Some(descriptor)) = (name, descriptor) {
+                if !seen.insert((name, descriptor)) {
+                    self.report(format!("duplicate field {} {}", name, descriptor));
+                }
+            }
+            self.attributes(&field.attributes.0);
+        }
+    }
+
+    fn methods(&mut self) {
+        let class_file = self.class_file;
+        let version = class_file.major_version();
+        let interface = class_file.access_flags.contains(ClassAccessFlags::INTERFACE);
+        let mut seen = HashSet::new();
+        for (i, method) in class_file.methods.iter().enumerate() {
+            self.location = Location::Method(i);
+            let name = self.utf8(method.name_index.0, "name");
+            if let Some(x) = name.filter(|x| !is_method_name(x)) {
+                self.report(format!("invalid method name {:?}", x));
+            }
+            if let Some(name) = name {
+                self.method_flags(&method.access_flags, name, interface, version);
+            }
+
+            let descriptor = self.utf8(method.descriptor_index.0, "descriptor");
+            if let Some(parsed) = descriptor.and_then(|x| self.method_descriptor(x)) {
+                let this = u16::from(!method.access_flags.contains(MethodAccessFlags::STATIC));
+                let slots = parsed.parameter_slots() + this;
+                if slots > 255 {
+                    self.report(format!("the parameters take {} slots, more than 255", slots));
+                }
+                if name == Some("<init>") && parsed.return_type().is_some() {
+                    self.report("<init> must return void".to_string());
+                }
+            }
+            if let (Some(name), Some(descriptor)) = (name, descriptor) {
+                if !seen.insert((name, descriptor)) {
+                    self.report(format!("duplicate method {}{}", name, descriptor));
+                }
+            }
+            self.attributes(&method.attributes.0);
+        }
+    }
+
+    fn method_flags(&mut self, flags: &MethodAccessFlags, name: &str, interface: bool, version: u16) {
+        if name == "<clinit>" {
+            // Other flags are ignored on a class initializer.
+            if version >= 51 && !flags.contains(MethodAccessFlags::STATIC) {
+                self.report("<clinit> must be ACC_STATIC".to_string());
+            }
+            return;
+        }
+        let access = (MethodAccessFlags::PUBLIC | MethodAccessFlags::PRIVATE | MethodAccessFlags::PROTECTED).bits();
+        let abstract_forbidden = MethodAccessFlags::PRIVATE
+            | MethodAccessFlags::STATIC
+            | MethodAccessFlags::FINAL
+            | MethodAccessFlags::SYNCHRONIZED
+            | MethodAccessFlags::NATIVE;
+        let is_abstract = flags.contains(MethodAccessFlags::ABSTRACT);
+        self.rules(&[
+            (
+                flags.bits() & access != 0 && !(flags.bits() & access).is_power_of_two(),
+                "a method can only be one of ACC_PUBLIC, ACC_PRIVATE and ACC_PROTECTED",
+            ),
+            (
+                interface && version < 52 && !flags.contains(MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT),
+                "an interface method must be ACC_PUBLIC and ACC_ABSTRACT before class file version 52",
+            ),
+            (
+                interface
+                    && version >= 52
+                    && !flags.intersects(MethodAccessFlags::PUBLIC | MethodAccessFlags::PRIVATE),
+                "an interface method must be ACC_PUBLIC or ACC_PRIVATE",
+            ),
+            (
+                interface
+                    && flags.intersects(
+                        MethodAccessFlags::PROTECTED
+                            | MethodAccessFlags::FINAL
+                            | MethodAccessFlags::SYNCHRONIZED
+                            | MethodAccessFlags::NATIVE,
+                    ),
+                "an interface method cannot be ACC_PROTECTED, ACC_FINAL, ACC_SYNCHRONIZED or ACC_NATIVE",
+            ),
+            (
+                is_abstract && flags.intersects(abstract_forbidden),
+                "an abstract method cannot be ACC_PRIVATE, ACC_STATIC, ACC_FINAL, ACC_SYNCHRONIZED or ACC_NATIVE",
+            ),
+            (
+                is_abstract && flags.contains(MethodAccessFlags::STRICT) && (46..61).contains(&version),
+                "an abstract method cannot be ACC_STRICT",
+            ),
+            (interface && name == "<init>", "an interface cannot have an <init> method"),
+            (
+                name == "<init>"
+                    && flags.intersects(
+                        MethodAccessFlags::STATIC
+                            | MethodAccessFlags::FINAL
+                            | MethodAccessFlags::SYNCHRONIZED
+                            | MethodAccessFlags::BRIDGE
+                            | MethodAccessFlags::NATIVE
+                            | MethodAccessFlags::ABSTRACT,
+                    ),
+                "<init> cannot be ACC_STATIC, ACC_FINAL, ACC_SYNCHRONIZED, ACC_BRIDGE, ACC_NATIVE or ACC_ABSTRACT",
+            ),
+        ]);
+    }
+
+    fn attributes(&mut self, attributes: &'a [AttributeEntry]) {
+        for x in attributes {
+            if self.utf8(x.name_index, "attribute name").is_some() {
+                self.attribute(&x.name, &x.data);
+            }
+        }
+    }
+
+    /// Checks the contents of attribute `name` against its layout, if it is
+    /// one the specification defines.
+    fn attribute(&mut self, name: &str, data: &[u8]) {
+        let label = format!("attribute {}", name);
+        let r = &mut Reader::new(data, &label);
+        if self.layout(name, r).is_some() && r.pos != data.len() {
+            self.report(format!(
+                "attribute {} has {} bytes past its contents",
+                name,
+                data.len() - r.pos
+            ));
+        }
+    }
+
+    /// The value of a read, reporting why it failed if it did.
+    fn read<T>(&mut self, x: Result<T>) -> Option<T> {
+        match x {
+            Ok(x) => Some(x),
+            Err(Error::WriteError(x)) => {
+                self.report(x);
+                None
+            }
+            Err(x) => {
+                self.report(x.to_string());
+                None
+            }
+        }
+    }
+
+    /// Reports a problem that stops the rest of an attribute being read.
+    fn malformed(&mut self, message: String) -> Option<()> {
+        self.report(message);
+        None
+    }
+
+    fn skip(&mut self, r: &mut Reader, n: usize) -> Option<()> {
+        self.read(r.bytes(n)).map(|_| ())
+    }
+
+    fn index(&mut self, r: &mut Reader, kinds: &[&str], what: &str) -> Option<()> {
+        let index = self.read(r.u16())?;
+        self.expect(index, kinds, what);
+        Some(())
+    }
+
+    /// An index that may be 0 for "none".
+    fn optional_index(&mut self, r: &mut Reader, kinds: &[&str], what: &str) -> Option<()> {
+        let index = self.read(r.u16())?;
+        if index != 0 {
+            self.expect(index, kinds, what);
+        }
+        Some(())
+    }
+
+    /// A `u16` count followed by that many items.
+    fn table<G>(&mut self, r: &mut Reader, mut item: G) -> Option<()>
+    where
+        G: FnMut(&mut Self, &mut Reader) -> Option<()>,
+    {
+        let count = self.read(r.u16())?;
+        (0..count).try_for_each(|_| item(self, r))
+    }
+
+    /// An attribute table nested in another attribute.
+    fn attribute_table(&mut self, r: &mut Reader) -> Option<()> {
+        for _ in 0..self.read(r.u16())? {
+            let name_index = self.read(r.u16())?;
+            let length = self.read(r.u32())?;
+            let data = self.read(r.bytes(length as usize))?;
+            if let Some(name) = self.utf8(name_index, "attribute name") {
+                self.attribute(name, data);
+            }
+        }
+        Some(())
+    }
+
+    fn layout(&mut self, name: &str, r: &mut Reader) -> Option<()> {
+        match name {
+            "ConstantValue" => self.index(r, &["Integer", "Float", "Long", "Double", "String"], name),
+            "SourceFile" | "Signature" => self.index(r, &["Utf8"], name),
+            "NestHost" | "ModuleMainClass" => self.index(r, &["Class"], name),
+            "Exceptions" | "NestMembers" | "PermittedSubclasses" => {
+                self.table(r, |v, r| v.index(r, &["Class"], name))
+            }
+            "ModulePackages" => self.table(r, |v, r| v.index(r, &["Package"], name)),
+            "Synthetic" | "Deprecated" => Some(()),
+            "LineNumberTable" => self.table(r, |v, r| v.skip(r, 4)),
+            "LocalVariableTable" | "LocalVariableTypeTable" => self.table(r, |v, r| {
+                v.skip(r, 4)?;
+                v.index(r, &["Utf8"], "local variable name")?;
+                v.index(r, &["Utf8"], "local variable type")?;
+                v.skip(r, 2)
+            }),
+            "InnerClasses" => self.table(r, |v, r| {
+                v.index(r, &["Class"], "inner class")?;
+                v.optional_index(r, &["Class"], "outer class")?;
+                v.optional_index(r, &["Utf8"], "inner name")?;
+                v.skip(r, 2)
+            }),
+            "EnclosingMethod" => {
+                self.index(r, &["Class"], "enclosing class")?;
+                self.optional_index(r, &["NameAndType"], "enclosing method")
+            }
+            "BootstrapMethods" => self.table(r, |v, r| {
+                v.index(r, &["MethodHandle"], "bootstrap method")?;
+                v.table(r, |v, r| v.index(r, LOADABLE, "bootstrap argument"))
+            }),
+            "MethodParameters" => {
+                for _ in 0..self.read(r.u8())? {
+                    self.optional_index(r, &["Utf8"], "parameter name")?;
+                    self.skip(r, 2)?;
+                }
+                Some(())
+            }
+            "Code" => self.code(r),
+            "StackMapTable" => self.stack_map_table(r),
+            "Record" => self.table(r, |v, r| {
+                v.index(r, &["Utf8"], "record component name")?;
+                v.index(r, &["Utf8"], "record component descriptor")?;
+                v.attribute_table(r)
+            }),
+            "RuntimeVisibleAnnotations" | "RuntimeInvisibleAnnotations" => {
+                self.table(r, |v, r| v.annotation(r))
+            }
+            "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+                for _ in 0..self.read(r.u8())? {
+                    self.table(r, |v, r| v.annotation(r))?;
+                }
+                Some(())
+            }
+            "RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => {
+                self.table(r, |v, r| v.type_annotation(r))
+            }
+            "AnnotationDefault" => self.element_value(r),
+            "Module" => self.module(r),
+            // SourceDebugExtension and unknown attributes are opaque.
+            _ => {
+                r.pos = r.data.len();
+                Some(())
+            }
+        }
+    }
+
+    fn code(&mut self, r: &mut Reader) -> Option<()> {
+        self.skip(r, 4)?;
+        let length = self.read(r.u32())?;
+        if !(1..65536).contains(&length) {
+            self.report(format!("code length {} is not between 1 and 65535", length));
+        }
+        self.skip(r, length as usize)?;
+        self.table(r, |v, r| {
+            v.skip(r, 6)?;
+            v.optional_index(r, &["Class"], "catch type")
+        })?;
+        self.attribute_table(r)
+    }
+
+    fn verification_type(&mut self, r: &mut Reader) -> Option<()> {
+        match self.read(r.u8())? {
+            0..=6 => Some(()),
+            7 => self.index(r, &["Class"], "stack map type"),
+            8 => self.skip(r, 2),
+            x => self.malformed(format!("unknown verification type {}", x)),
+        }
+    }
+
+    fn stack_map_table(&mut self, r: &mut Reader) -> Option<()> {
+        self.table(r, |v, r| match v.read(r.u8())? {
+            0..=63 => Some(()),
+            64..=127 => v.verification_type(r),
+            247 => {
+                v.skip(r, 2)?;
+                v.verification_type(r)
+            }
+            248..=251 => v.skip(r, 2),
+            x @ 252..=254 => {
+                v.skip(r, 2)?;
+                (251..x).try_for_each(|_| v.verification_type(r))
+            }
+            255 => {
+                v.skip(r, 2)?;
+                v.table(r, |v, r| v.verification_type(r))?;
+                v.table(r, |v, r| v.verification_type(r))
+            }
+            x => v.malformed(format!("unknown stack map frame type {}", x)),
+        })
+    }
+
+    fn annotation(&mut self, r: &mut Reader) -> Option<()> {
+        self.index(r, &["Utf8"], "annotation type")?;
+        self.table(r, |v, r| {
+            v.index(r, &["Utf8"], "element name")?;
+            v.element_value(r)
+        })
+    }
+
+    fn element_value(&mut self, r: &mut Reader) -> Option<()> {
+        match self.read(r.u8())? {
+            b'B' | b'C' | b'I' | b'S' | b'Z' => self.index(r, &["Integer"], "element value"),
+            b'D' => self.index(r, &["Double"], "element value"),
+            b'F' => self.index(r, &["Float"], "element value"),
+            b'J' => self.index(r, &["Long"], "element value"),
+            b's' => self.index(r, &["Utf8"], "element value"),
+            b'c' => self.index(r, &["Utf8"], "element class"),
+            b'e' => {
+                self.index(r, &["Utf8"], "enum type")?;
+                self.index(r, &["Utf8"], "enum constant")
+            }
+            b'@' => self.annotation(r),
+            b'[' => self.table(r, |v, r| v.element_value(r)),
+            x => self.malformed(format!("unknown element value tag {}", x)),
+        }
+    }
+
+    fn type_annotation(&mut self, r: &mut Reader) -> Option<()> {
+        match self.read(r.u8())? {
+            0x00 | 0x01 | 0x16 => self.skip(r, 1)?,
+            0x10..=0x12 | 0x17 | 0x42..=0x46 => self.skip(r, 2)?,
+            0x13..=0x15 => {}
+            0x40 | 0x41 => self.table(r, |v, r| v.skip(r, 6))?,
+            0x47..=0x4b => self.skip(r, 3)?,
+            x => return self.malformed(format!("unknown type annotation target {:#04x}", x)),
+        }
+        let path_length = self.read(r.u8())?;
+        self.skip(r, path_length as usize * 2)?;
+        self.annotation(r)
+    }
+
+    fn module(&mut self, r: &mut Reader) -> Option<()> {
+        self.index(r, &["Module"], "module")?;
+        self.skip(r, 2)?;
+        self.optional_index(r, &["Utf8"], "module version")?;
+        self.table(r, |v, r| {
+            v.index(r, &["Module"], "required module")?;
+            v.skip(r, 2)?;
+            v.optional_index(r, &["Utf8"], "required module version")
+        })?;
+        // exports and opens
+        for _ in 0..2 {
+            self.table(r, |v, r| {
+                v.index(r, &["Package"], "package")?;
+                v.skip(r, 2)?;
+                v.table(r, |v, r| v.index(r, &["Module"], "target module"))
+            })?;
+        }
+        self.table(r, |v, r| v.index(r, &["Class"], "used service"))?;
+        self.table(r, |v, r| {
+            v.index(r, &["Class"], "provided service")?;
+            v.table(r, |v, r| v.index(r, &["Class"], "service implementation"))
+        })
+    }
+}
+
+impl ClassFile {
+    /// Checks the structure of the class file outside method code, which
+    /// [`Verifier`](crate::verifier::Verifier) checks: that every constant
+    /// pool index refers to an entry of the right kind, that the standard
+    /// attributes fill exactly their declared lengths, that access flags are
+    /// combined legally, and that names and descriptors are well-formed.
+    /// Returns the problems found, none for a well-formed class.
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut validator = Validator {
+            class_file: self,
+            location: Location::Class,
+            problems: Vec::new(),
+        };
+        validator.constant_pool();
+        validator.header();
+        validator.fields();
+        validator.methods();
+        validator.problems
+    }
+}
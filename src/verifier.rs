@@ -0,0 +1,950 @@
+//! Checks method bodies against the rules the JVM enforces when it loads a
+//! class (JVMS §4.9, §4.10), see [`Verifier`].
+
+use std::collections::BTreeMap;
+
+use crate::{
+    analyzer::{Analyzer, Frame, VerificationType},
+    attributes::{Code, ExceptionHandler},
+    field::TypeDescriptor,
+    instruction::{BranchTarget, Instruction, InstructionAt, LoadableConstant, MaybeInterfaceMethodRef},
+    method::{Method, MethodDescriptor},
+    raw::{ClassAccessFlags, MethodAccessFlags},
+    repository::ClassRepository,
+    resolver::ClassResolver,
+    ClassFile, Error, Result,
+};
+
+/// A rule a method breaks, see [`Verifier::verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation<'a> {
+    pub method: &'a str,
+    pub descriptor: &'a str,
+    /// The pc of the offending instruction, or `None` for a problem with the
+    /// method as a whole.
+    pub pc: Option<u32>,
+    pub message: String,
+}
+
+/// A bytecode verifier, checking the code of every method for the
+/// structural constraints on instructions and their operands (§4.9) and
+/// then type checking it (§4.10.1).
+///
+/// Code of class file version 50 with a `StackMapTable`, or any later
+/// version, is checked against its stack map frames as the JVM does. Older
+/// code has its frames inferred instead, as by [`Method::frames`], and since
+/// inference merges different classes to `java/lang/Object`, only the kinds of
+/// references are checked there, not their classes.
+///
+/// Whether one class is assignable to another takes the class hierarchy, so
+/// without a [`ClassResolver`] any class is taken to be assignable to any
+/// other. With one, a class is only found not to be assignable when all of
+/// its superclasses resolve. Interfaces take anything, as for the JVM.
+#[derive(Default, Clone, Copy)]
+pub struct Verifier<'r> {
+    resolver: Option<&'r dyn ClassResolver>,
+}
+
+impl std::fmt::Debug for Verifier<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Verifier")
+            .field("resolver", &self.resolver.is_some())
+            .finish()
+    }
+}
+
+impl<'r> Verifier<'r> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks up classes in `repository` to check assignability.
+    pub fn with_repository(self, repository: &'r ClassRepository) -> Self {
+        self.with_resolver(repository)
+    }
+
+    /// Looks up classes through `resolver` to check assignability.
+    pub fn with_resolver(mut self, resolver: &'r dyn ClassResolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Checks every method of `class_file`, returning the violations found,
+    /// none if the class passes. Structural problems are all reported, but
+    /// type checking is skipped for code that has any, and stops at the first
+    /// type error in a method, as the JVM does. Fails only if the names or
+    /// descriptors of methods cannot be read.
+    pub fn verify<'a>(&self, class_file: &'a ClassFile) -> Result<Vec<Violation<'a>>> {
+        let mut res = Vec::new();
+        for method in class_file.methods() {
+            let name = method.identifier()?;
+            let descriptor = method.method_inner.descriptor_index.get_as_string(class_file)?;
+            let mut checker = Checker {
+                verifier: self,
+                method: &method,
+                descriptor: method.descriptor()?,
+                this_class: class_file.this_class()?,
+                strict: false,
+                max_stack: 0,
+                violations: Vec::new(),
+            };
+            checker.method();
+            res.extend(checker.violations.into_iter().map(|(pc, message)| Violation {
+                method: name,
+                descriptor,
+                pc,
+                message,
+            }));
+        }
+        Ok(res)
+    }
+
+    /// Whether an instance of class `from` is one of class `to`, unless the
+    /// resolver shows otherwise. Classes that fail to resolve count as
+    /// missing.
+    fn class_assignable(&self, from: &str, to: &str) -> bool {
+        if from == to || to == "java/lang/Object" {
+            return true;
+        }
+        let resolver = match self.resolver {
+            Some(x) => x,
+            None => return true,
+        };
+        match resolver.resolve(to) {
+            Ok(Some(x)) if !x.access_flags.contains(ClassAccessFlags::INTERFACE) => {}
+            _ => return true,
+        }
+        match resolver.super_classes(from) {
+            Ok(chain) if !chain.iter().any(|x| x == to) => {
+                // The chain stops early at a class that does not resolve.
+                let last = chain.last().map_or(from, |x| x);
+                !matches!(resolver.resolve(last), Ok(Some(_)))
+            }
+            _ => true,
+        }
+    }
+}
+
+/// The name of a class type, or `None` for an array or primitive type.
+fn class_name<'a>(ty: &TypeDescriptor<'a>) -> Option<&'a str> {
+    match ty {
+        TypeDescriptor::String => Some("java/lang/String"),
+        TypeDescriptor::Class => Some("java/lang/Class"),
+        TypeDescriptor::ClassName(x) => Some(x),
+        _ => None,
+    }
+}
+
+fn is_reference(ty: &VerificationType) -> bool {
+    matches!(
+        ty,
+        VerificationType::Null
+            | VerificationType::Object(_)
+            | VerificationType::UninitializedThis
+            | VerificationType::Uninitialized { .. }
+    )
+}
+
+fn is_primitive(ty: &TypeDescriptor) -> bool {
+    !matches!(
+        ty,
+        TypeDescriptor::String | TypeDescriptor::Class | TypeDescriptor::ClassName(_) | TypeDescriptor::Array(_)
+    )
+}
+
+/// The class, name and descriptor of a method reference, and whether it is
+/// to an interface method.
+fn method_ref<'a, 'b>(x: &'b MaybeInterfaceMethodRef<'a>) -> (&'a str, &'a str, &'b MethodDescriptor<'a>, bool) {
+    match x {
+        MaybeInterfaceMethodRef::RegularMethod(x) => (x.class, x.name, &x.descriptor, false),
+        MaybeInterfaceMethodRef::InterfaceMethod(x) => (x.class, x.name, &x.descriptor, true),
+    }
+}
+
+/// What an instruction needs of a value it pops.
+enum Operand<'a> {
+    /// A value assignable to the type.
+    Type(VerificationType<'a>),
+    /// Any reference, even one not yet initialized.
+    Reference,
+    /// An object whose constructor has not been called.
+    Uninitialized,
+    /// Any one slot value, including a return address.
+    Any,
+    /// `null` or an array whose element type passes the check, described by
+    /// the string.
+    Array(fn(&TypeDescriptor) -> bool, &'static str),
+}
+
+impl std::fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Type(x) => write!(f, "{}", x),
+            Self::Reference => f.write_str("a reference"),
+            Self::Uninitialized => f.write_str("an uninitialized object"),
+            Self::Any => f.write_str("a one slot value"),
+            Self::Array(_, x) => write!(f, "{}", x),
+        }
+    }
+}
+
+fn int<'a>() -> Operand<'a> {
+    Operand::Type(VerificationType::Integer)
+}
+
+fn long<'a>() -> Operand<'a> {
+    Operand::Type(VerificationType::Long)
+}
+
+fn float<'a>() -> Operand<'a> {
+    Operand::Type(VerificationType::Float)
+}
+
+fn double<'a>() -> Operand<'a> {
+    Operand::Type(VerificationType::Double)
+}
+
+fn object(name: &str) -> Operand<'_> {
+    Operand::Type(VerificationType::Object(TypeDescriptor::ClassName(name)))
+}
+
+fn arguments<'a>(descriptor: &MethodDescriptor<'a>) -> Vec<Operand<'a>> {
+    descriptor
+        .parameter_types()
+        .iter()
+        .map(|x| Operand::Type(VerificationType::from_descriptor(x)))
+        .collect()
+}
+
+/// The checks of one method, collecting its violations.
+struct Checker<'a, 'v> {
+    verifier: &'v Verifier<'v>,
+    method: &'v Method<'a>,
+    descriptor: MethodDescriptor<'a>,
+    this_class: &'a str,
+    /// Whether references are checked by class, not only by kind.
+    strict: bool,
+    max_stack: usize,
+    violations: Vec<(Option<u32>, String)>,
+}
+
+impl<'a> Checker<'a, '_> {
+    fn report(&mut self, pc: Option<u32>, message: impl Into<String>) {
+        self.violations.push((pc, message.into()));
+    }
+
+    fn report_error(&mut self, error: Error) {
+        match error {
+            Error::AnalysisError { pc, message } => self.report(Some(pc), message),
+            Error::InvalidBranchTarget { pc, offset } => {
+                self.report(Some(pc), format!("branches by {} to the middle of an instruction", offset))
+            }
+            x => self.report(None, x.to_string()),
+        }
+    }
+
+    fn method(&mut self) {
+        let flags = &self.method.method_inner.access_flags;
+        let code = match self.method.code() {
+            Ok(x) => x,
+            Err(x) => return self.report_error(x),
+        };
+        match (code, flags.intersects(MethodAccessFlags::ABSTRACT | MethodAccessFlags::NATIVE)) {
+            (Some(_), true) => self.report(None, "abstract or native method has code"),
+            (None, false) => self.report(None, "method is neither abstract nor native but has no code"),
+            (None, true) => {}
+            (Some(code), false) => {
+                if let Err(x) = self.code(&code) {
+                    self.report_error(x);
+                }
+            }
+        }
+    }
+
+    fn code(&mut self, code: &Code<'a>) -> Result<()> {
+        let length = code.code_span().len;
+        if length == 0 || length > u16::MAX as u64 {
+            self.report(None, format!("code is {} bytes long, not between 1 and 65535", length));
+            if length == 0 {
+                return Ok(());
+            }
+        }
+        let instructions = code.instructions_at()?;
+        let targets = code.branch_targets()?;
+        let handlers = code.exception_handlers()?;
+        self.max_stack = code.max_stack as usize;
+
+        let before = self.violations.len();
+        let major_version = self.method.class_file.major_version();
+        for at in &instructions {
+            self.structure(at, major_version, code.max_locals);
+        }
+        if let Some(last) = instructions.last().filter(|x| x.instruction.falls_through()) {
+            self.report(Some(last.pc), "execution can run off the end of the code");
+        }
+        for handler in &handlers {
+            if handler.start.pc >= handler.end.pc {
+                self.report(
+                    Some(handler.handler.pc),
+                    format!("exception handler covers the empty range {}..{}", handler.start.pc, handler.end.pc),
+                );
+            }
+            if let Some(x) = handler.catch_type.filter(|x| !self.verifier.class_assignable(x, "java/lang/Throwable")) {
+                self.report(Some(handler.handler.pc), format!("exception handler catches {}, not a Throwable", x));
+            }
+        }
+        if self.violations.len() > before {
+            return Ok(());
+        }
+
+        let analyzer = Analyzer::new(self.method, code)?;
+        let stack_map = self.method.stack_map_frames()?;
+        if major_version >= 51 || (major_version == 50 && stack_map.is_some()) {
+            self.strict = true;
+            self.type_check(&analyzer, &instructions, &targets, &handlers, stack_map.unwrap_or_default());
+        } else {
+            self.infer(&analyzer, &instructions)?;
+        }
+        Ok(())
+    }
+
+    /// The static constraints on an instruction and its operands (§4.9.1).
+    fn structure(&mut self, at: &InstructionAt<'a>, major_version: u16, max_locals: u16) {
+        let pc = Some(at.pc);
+        let mnemonic = at.instruction.mnemonic();
+        if let Some(x) = at.instruction.local_access() {
+            if x.index as u32 + x.slots as u32 > max_locals as u32 {
+                self.report(pc, format!("{} uses local {}, past max_locals {}", mnemonic, x.index, max_locals));
+            }
+        }
+        let method_name = |name: &str| if name.starts_with('<') { Some(format!("{} calls {}", mnemonic, name)) } else { None };
+        let problem = match &at.instruction {
+            Instruction::Unknown { opcode, .. } => Some(format!("unknown opcode {:#04x}", opcode)),
+            Instruction::Jsr { .. } | Instruction::JsrW { .. } | Instruction::Ret { .. } | Instruction::WideRet { .. }
+                if major_version >= 51 =>
+            {
+                Some(format!("{} is not allowed from class file version 51", mnemonic))
+            }
+            Instruction::Ldc { constant, .. } | Instruction::LdcW { constant, .. } if constant.slots() == 2 => {
+                Some(format!("{} loads a two slot constant", mnemonic))
+            }
+            Instruction::Ldc2W { constant, .. } if constant.slots() == 1 => {
+                Some(format!("{} loads a one slot constant", mnemonic))
+            }
+            Instruction::Ldc { constant, .. } | Instruction::LdcW { constant, .. }
+                if matches!(constant, LoadableConstant::Dynamic(_)) && major_version < 55 =>
+            {
+                Some(format!("{} of a dynamic constant needs class file version 55", mnemonic))
+            }
+            Instruction::Invokevirtual { index } => method_name(index.name),
+            Instruction::Invokeinterface { index, count, .. } => method_name(index.name).or_else(|| {
+                let slots = index.descriptor.parameter_slots() + 1;
+                (*count as u16 != slots)
+                    .then(|| format!("invokeinterface count is {} but the arguments take {} slots", count, slots))
+            }),
+            Instruction::Invokestatic { index } | Instruction::Invokespecial { index } => {
+                let (_, name, descriptor, interface) = method_ref(index);
+                let is_special = matches!(at.instruction, Instruction::Invokespecial { .. });
+                if is_special && name == "<init>" {
+                    descriptor
+                        .return_type()
+                        .map(|_| "invokespecial calls an <init> that does not return void".to_string())
+                } else if let Some(x) = method_name(name) {
+                    Some(x)
+                } else if interface && major_version < 52 {
+                    Some(format!("{} of an interface method needs class file version 52", mnemonic))
+                } else {
+                    None
+                }
+            }
+            Instruction::Invokedynamic { .. } if major_version < 51 => {
+                Some("invokedynamic needs class file version 51".to_string())
+            }
+            Instruction::New { class } if class.starts_with('[') => Some(format!("new of array class {}", class)),
+            Instruction::Anewarray { class } if class.bytes().take_while(|&x| x == b'[').count() >= 255 => {
+                Some(format!("anewarray of {} makes an array of more than 255 dimensions", class))
+            }
+            Instruction::Multianewarray { class, dimensions } => {
+                let depth = class.bytes().take_while(|&x| x == b'[').count();
+                if *dimensions == 0 || depth < *dimensions as usize {
+                    Some(format!("multianewarray of {} dimensions of {}", dimensions, class))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some(x) = problem {
+            self.report(pc, x);
+        }
+    }
+
+    /// Type checks the code against its stack map frames (§4.10.1), going
+    /// through the instructions in order and stopping at the first problem.
+    fn type_check(
+        &mut self,
+        analyzer: &Analyzer<'a>,
+        instructions: &[InstructionAt<'a>],
+        targets: &[Vec<BranchTarget>],
+        handlers: &[ExceptionHandler<'a>],
+        stack_map: Vec<(u32, Frame<'a>)>,
+    ) {
+        for (pc, _) in &stack_map {
+            if instructions.binary_search_by_key(pc, |x| x.pc).is_err() {
+                return self.report(Some(*pc), "stack map frame is not at the start of an instruction");
+            }
+        }
+        let stack_map: BTreeMap<u32, Frame<'a>> = stack_map.into_iter().collect();
+        let target_frame = |pc: u32| {
+            stack_map
+                .get(&pc)
+                .ok_or_else(|| format!("no stack map frame at the target {}", pc))
+        };
+
+        let mut current = Some(analyzer.entry().clone());
+        for (at, targets) in instructions.iter().zip(targets) {
+            let pc = Some(at.pc);
+            let before = match (current.take(), stack_map.get(&at.pc)) {
+                (Some(x), Some(recorded)) => {
+                    if let Err(x) = self.frame_assignable(&x, recorded) {
+                        return self.report(pc, x);
+                    }
+                    recorded.clone()
+                }
+                (None, Some(recorded)) => recorded.clone(),
+                (Some(x), None) => x,
+                (None, None) => {
+                    return self.report(pc, "no stack map frame after an unconditional branch");
+                }
+            };
+            if let Err(x) = self.instruction(&before, at) {
+                return self.report(pc, x);
+            }
+
+            for handler in handlers.iter().filter(|x| x.covers(at.pc)) {
+                let thrown = match analyzer.object(handler.catch_type.unwrap_or("java/lang/Throwable")) {
+                    Ok(x) => x,
+                    Err(x) => return self.report(pc, x),
+                };
+                let frame = Frame {
+                    locals: before.locals.clone(),
+                    stack: vec![thrown],
+                };
+                if let Err(x) = target_frame(handler.handler.pc).and_then(|x| self.frame_assignable(&frame, x)) {
+                    return self.report(pc, format!("exception handler at {}: {}", handler.handler.pc, x));
+                }
+            }
+
+            let mut after = before;
+            if let Err(x) = analyzer.execute(&mut after, at) {
+                return self.report(pc, x);
+            }
+            if after.stack.len() > self.max_stack {
+                return self.report(pc, format!("stack grows to {} slots, past max_stack {}", after.stack.len(), self.max_stack));
+            }
+            for target in targets {
+                if let Err(x) = target_frame(target.pc).and_then(|x| self.frame_assignable(&after, x)) {
+                    return self.report(pc, x);
+                }
+            }
+            if at.instruction.falls_through() {
+                current = Some(after);
+            }
+        }
+    }
+
+    /// Checks each instruction against the frames inferred for it, reporting
+    /// every problem.
+    fn infer(&mut self, analyzer: &Analyzer<'a>, instructions: &[InstructionAt<'a>]) -> Result<()> {
+        let frames = match self.method.frames()? {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        for at in instructions {
+            let before = match frames.at(at.pc) {
+                Some(x) => x,
+                None => continue,
+            };
+            if let Err(x) = self.instruction(before, at) {
+                self.report(Some(at.pc), x);
+                continue;
+            }
+            let mut after = before.clone();
+            if let Err(x) = analyzer.execute(&mut after, at) {
+                self.report(Some(at.pc), x);
+            } else if after.stack.len() > self.max_stack {
+                self.report(
+                    Some(at.pc),
+                    format!("stack grows to {} slots, past max_stack {}", after.stack.len(), self.max_stack),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a frame may flow into one recorded in the stack map.
+    fn frame_assignable(&self, from: &Frame<'a>, to: &Frame<'a>) -> std::result::Result<(), String> {
+        if from.stack.len() != to.stack.len() {
+            return Err(format!(
+                "the stack has {} slots where the stack map frame has {}",
+                from.stack.len(),
+                to.stack.len()
+            ));
+        }
+        for (i, (x, y)) in from.locals.iter().zip(&to.locals).enumerate() {
+            if !self.assignable(x, y) {
+                return Err(format!("local {} is {} where the stack map frame has {}", i, x, y));
+            }
+        }
+        for (i, (x, y)) in from.stack.iter().zip(&to.stack).enumerate() {
+            if !self.assignable(x, y) {
+                return Err(format!("stack slot {} is {} where the stack map frame has {}", i, x, y));
+            }
+        }
+        Ok(())
+    }
+
+    fn assignable(&self, from: &VerificationType<'a>, to: &VerificationType<'a>) -> bool {
+        match (from, to) {
+            (x, y) if x == y => true,
+            (_, VerificationType::Top) => true,
+            (VerificationType::Null, VerificationType::Object(_)) => true,
+            (VerificationType::Object(x), VerificationType::Object(y)) => self.reference_assignable(x, y),
+            _ => false,
+        }
+    }
+
+    fn reference_assignable(&self, from: &TypeDescriptor<'a>, to: &TypeDescriptor<'a>) -> bool {
+        match (from, to) {
+            (TypeDescriptor::Array(x), TypeDescriptor::Array(y)) => {
+                if is_primitive(x) || is_primitive(y) {
+                    x == y
+                } else {
+                    self.reference_assignable(x, y)
+                }
+            }
+            (TypeDescriptor::Array(_), y) => {
+                matches!(class_name(y), Some("java/lang/Object" | "java/lang/Cloneable" | "java/io/Serializable"))
+            }
+            (x, TypeDescriptor::Array(_)) => !self.strict && class_name(x) == Some("java/lang/Object"),
+            (x, y) => match (class_name(x), class_name(y)) {
+                (Some(x), Some(y)) => !self.strict || self.verifier.class_assignable(x, y),
+                _ => false,
+            },
+        }
+    }
+
+    /// Checks that the locals and stack in `frame` are what `at` needs.
+    fn instruction(&self, frame: &Frame<'a>, at: &InstructionAt<'a>) -> std::result::Result<(), String> {
+        let mnemonic = at.instruction.mnemonic();
+        if let Some((index, expected)) = self.load(&at.instruction) {
+            let found = frame
+                .locals
+                .get(index as usize)
+                .ok_or_else(|| format!("local {} is past max_locals", index))?;
+            let valid = match &expected {
+                Operand::Reference => is_reference(found),
+                Operand::Type(x) => self.assignable(found, x),
+                _ => true,
+            };
+            if !valid {
+                return Err(format!("{} of local {} needs {} but it holds {}", mnemonic, index, expected, found));
+            }
+        }
+
+        let stack = &frame.stack;
+        // Whether the slot `depth` from the top is the second half of a `long`
+        // or `double`, which the instruction would split.
+        let split = |depth: usize| stack.len().checked_sub(depth).is_some_and(|i| i > 0 && stack[i - 1].is_wide());
+        let depths: &[usize] = match at.instruction {
+            Instruction::Pop | Instruction::Dup => &[1],
+            Instruction::DupX1 | Instruction::Swap => &[1, 2],
+            Instruction::DupX2 => &[1, 3],
+            Instruction::Pop2 | Instruction::Dup2 => &[2],
+            Instruction::Dup2X1 => &[2, 3],
+            Instruction::Dup2X2 => &[2, 4],
+            _ => &[],
+        };
+        if depths.iter().any(|&x| split(x)) {
+            return Err(format!("{} splits a long or double on the stack", mnemonic));
+        }
+
+        let operands = self.operands(frame, at)?;
+        let slots: usize = operands
+            .iter()
+            .map(|x| match x {
+                Operand::Type(x) if x.is_wide() => 2,
+                _ => 1,
+            })
+            .sum();
+        if stack.len() < slots {
+            return Err(format!("{} pops {} slots from a stack of {}", mnemonic, slots, stack.len()));
+        }
+        let mut i = stack.len() - slots;
+        for operand in &operands {
+            let found = &stack[i];
+            let valid = match operand {
+                Operand::Type(x) => {
+                    i += usize::from(x.is_wide());
+                    self.assignable(found, x)
+                }
+                Operand::Reference => is_reference(found),
+                Operand::Uninitialized => {
+                    matches!(found, VerificationType::UninitializedThis | VerificationType::Uninitialized { .. })
+                }
+                Operand::Any => !(found.is_wide() || i > 0 && stack[i - 1].is_wide()),
+                Operand::Array(element, _) => match found {
+                    VerificationType::Null => true,
+                    VerificationType::Object(TypeDescriptor::Array(x)) => element(x),
+                    _ => false,
+                },
+            };
+            if !valid {
+                return Err(format!("{} needs {} but the stack holds {}", mnemonic, operand, found));
+            }
+            i += 1;
+        }
+
+        if let Instruction::Return = at.instruction {
+            if self.method.identifier().ok() == Some("<init>")
+                && self.this_class != "java/lang/Object"
+                && frame.locals.contains(&VerificationType::UninitializedThis)
+            {
+                return Err("return before the superclass constructor is called".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// The local a load reads and what it needs there.
+    fn load(&self, instruction: &Instruction) -> Option<(u16, Operand<'a>)> {
+        Some(match instruction {
+            Instruction::Iload { index } | Instruction::WideIload { index } => (index.0, int()),
+            Instruction::Iinc { index, .. } | Instruction::WideIinc { index, .. } => (index.0, int()),
+            Instruction::Iload0 => (0, int()),
+            Instruction::Iload1 => (1, int()),
+            Instruction::Iload2 => (2, int()),
+            Instruction::Iload3 => (3, int()),
+            Instruction::Lload { index } | Instruction::WideLload { index } => (index.0, long()),
+            Instruction::Lload0 => (0, long()),
+            Instruction::Lload1 => (1, long()),
+            Instruction::Lload2 => (2, long()),
+            Instruction::Lload3 => (3, long()),
+            Instruction::Fload { index } | Instruction::WideFload { index } => (index.0, float()),
+            Instruction::Fload0 => (0, float()),
+            Instruction::Fload1 => (1, float()),
+            Instruction::Fload2 => (2, float()),
+            Instruction::Fload3 => (3, float()),
+            Instruction::Dload { index } | Instruction::WideDload { index } => (index.0, double()),
+            Instruction::Dload0 => (0, double()),
+            Instruction::Dload1 => (1, double()),
+            Instruction::Dload2 => (2, double()),
+            Instruction::Dload3 => (3, double()),
+            Instruction::Aload { index } | Instruction::WideAload { index } => (index.0, Operand::Reference),
+            Instruction::Aload0 => (0, Operand::Reference),
+            Instruction::Aload1 => (1, Operand::Reference),
+            Instruction::Aload2 => (2, Operand::Reference),
+            Instruction::Aload3 => (3, Operand::Reference),
+            _ => return None,
+        })
+    }
+
+    /// What `at` needs of the values it pops, in the order they were pushed.
+    fn operands(&self, frame: &Frame<'a>, at: &InstructionAt<'a>) -> std::result::Result<Vec<Operand<'a>>, String> {
+        let returns = |expected: fn(&TypeDescriptor) -> bool| match self.descriptor.return_type() {
+            Some(x) if expected(x) => Ok(vec![Operand::Type(VerificationType::from_descriptor(x))]),
+            x => Err(format!(
+                "{} in a method returning {}",
+                at.instruction.mnemonic(),
+                x.map_or("void".to_string(), ToString::to_string)
+            )),
+        };
+        Ok(match &at.instruction {
+            Instruction::Iadd
+            | Instruction::Isub
+            | Instruction::Imul
+            | Instruction::Idiv
+            | Instruction::Irem
+            | Instruction::Ishl
+            | Instruction::Ishr
+            | Instruction::Iushr
+            | Instruction::Iand
+            | Instruction::Ior
+            | Instruction::Ixor
+            | Instruction::IfIcmpeq { .. }
+            | Instruction::IfIcmpne { .. }
+            | Instruction::IfIcmplt { .. }
+            | Instruction::IfIcmpge { .. }
+            | Instruction::IfIcmpgt { .. }
+            | Instruction::IfIcmple { .. } => vec![int(), int()],
+            Instruction::Ineg
+            | Instruction::I2l
+            | Instruction::I2f
+            | Instruction::I2d
+            | Instruction::I2b
+            | Instruction::I2c
+            | Instruction::I2s
+            | Instruction::Ifeq { .. }
+            | Instruction::Ifne { .. }
+            | Instruction::Iflt { .. }
+            | Instruction::Ifge { .. }
+            | Instruction::Ifgt { .. }
+            | Instruction::Ifle { .. }
+            | Instruction::Tableswitch { .. }
+            | Instruction::Lookupswitch { .. }
+            | Instruction::Istore { .. }
+            | Instruction::WideIstore { .. }
+            | Instruction::Istore0
+            | Instruction::Istore1
+            | Instruction::Istore2
+            | Instruction::Istore3
+            | Instruction::Newarray { .. }
+            | Instruction::Anewarray { .. } => vec![int()],
+            Instruction::Ladd
+            | Instruction::Lsub
+            | Instruction::Lmul
+            | Instruction::Ldiv
+            | Instruction::Lrem
+            | Instruction::Land
+            | Instruction::Lor
+            | Instruction::Lxor
+            | Instruction::Lcmp => vec![long(), long()],
+            Instruction::Lshl | Instruction::Lshr | Instruction::Lushr => vec![long(), int()],
+            Instruction::Lneg
+            | Instruction::L2i
+            | Instruction::L2f
+            | Instruction::L2d
+            | Instruction::Lstore { .. }
+            | Instruction::WideLstore { .. }
+            | Instruction::Lstore0
+            | Instruction::Lstore1
+            | Instruction::Lstore2
+            | Instruction::Lstore3 => vec![long()],
+            Instruction::Fadd
+            | Instruction::Fsub
+            | Instruction::Fmul
+            | Instruction::Fdiv
+            | Instruction::Frem
+            | Instruction::Fcmpl
+            | Instruction::Fcmpg => vec![float(), float()],
+            Instruction::Fneg
+            | Instruction::F2i
+            | Instruction::F2l
+            | Instruction::F2d
+            | Instruction::Fstore { .. }
+            | Instruction::WideFstore { .. }
+            | Instruction::Fstore0
+            | Instruction::Fstore1
+            | Instruction::Fstore2
+            | Instruction::Fstore3 => vec![float()],
+            Instruction::Dadd
+            | Instruction::Dsub
+            | Instruction::Dmul
+            | Instruction::Ddiv
+            | Instruction::Drem
+            | Instruction::Dcmpl
+            | Instruction::Dcmpg => vec![double(), double()],
+            Instruction::Dneg
+            | Instruction::D2i
+            | Instruction::D2l
+            | Instruction::D2f
+            | Instruction::Dstore { .. }
+            | Instruction::WideDstore { .. }
+            | Instruction::Dstore0
+            | Instruction::Dstore1
+            | Instruction::Dstore2
+            | Instruction::Dstore3 => vec![double()],
+            // A return address from `jsr` can only be stored, and only in old code.
+            Instruction::Astore { .. }
+            | Instruction::WideAstore { .. }
+            | Instruction::Astore0
+            | Instruction::Astore1
+            | Instruction::Astore2
+            | Instruction::Astore3 => vec![if self.strict { Operand::Reference } else { Operand::Any }],
+            Instruction::IfAcmpeq { .. } | Instruction::IfAcmpne { .. } => {
+                vec![Operand::Reference, Operand::Reference]
+            }
+            Instruction::Ifnull { .. }
+            | Instruction::Ifnonnull { .. }
+            | Instruction::Monitorenter
+            | Instruction::Monitorexit
+            | Instruction::Checkcast { .. }
+            | Instruction::Instanceof { .. } => vec![Operand::Reference],
+
+            Instruction::Iaload => vec![int_array(), int()],
+            Instruction::Baload => vec![byte_array(), int()],
+            Instruction::Caload => vec![char_array(), int()],
+            Instruction::Saload => vec![short_array(), int()],
+            Instruction::Laload => vec![long_array(), int()],
+            Instruction::Faload => vec![float_array(), int()],
+            Instruction::Daload => vec![double_array(), int()],
+            Instruction::Aaload => vec![reference_array(), int()],
+            Instruction::Iastore => vec![int_array(), int(), int()],
+            Instruction::Bastore => vec![byte_array(), int(), int()],
+            Instruction::Castore => vec![char_array(), int(), int()],
+            Instruction::Sastore => vec![short_array(), int(), int()],
+            Instruction::Lastore => vec![long_array(), int(), long()],
+            Instruction::Fastore => vec![float_array(), int(), float()],
+            Instruction::Dastore => vec![double_array(), int(), double()],
+            Instruction::Aastore => vec![reference_array(), int(), Operand::Reference],
+            Instruction::Arraylength => vec![Operand::Array(|_| true, "an array")],
+            Instruction::Multianewarray { dimensions, .. } => (0..*dimensions).map(|_| int()).collect(),
+
+            Instruction::Ireturn => returns(|x| VerificationType::from_descriptor(x) == VerificationType::Integer)?,
+            Instruction::Lreturn => returns(|x| *x == TypeDescriptor::Long)?,
+            Instruction::Freturn => returns(|x| *x == TypeDescriptor::Float)?,
+            Instruction::Dreturn => returns(|x| *x == TypeDescriptor::Double)?,
+            Instruction::Areturn => returns(|x| !is_primitive(x))?,
+            Instruction::Return => match self.descriptor.return_type() {
+                None => Vec::new(),
+                Some(x) => return Err(format!("return in a method returning {}", x)),
+            },
+            Instruction::Athrow => vec![object("java/lang/Throwable")],
+
+            Instruction::Putstatic { field } => vec![Operand::Type(VerificationType::from_descriptor(&field.descriptor))],
+            Instruction::Getfield { field } => vec![object(field.class)],
+            Instruction::Putfield { field } => {
+                // A constructor may set its own class's fields before calling
+                // the superclass constructor.
+                let receiver = frame.stack.len().checked_sub(field.descriptor.slots() as usize + 1);
+                let receiver = match receiver.map(|x| &frame.stack[x]) {
+                    Some(VerificationType::UninitializedThis) if field.class == self.this_class => Operand::Uninitialized,
+                    _ => object(field.class),
+                };
+                vec![receiver, Operand::Type(VerificationType::from_descriptor(&field.descriptor))]
+            }
+            Instruction::Invokevirtual { index } => {
+                std::iter::once(self.receiver(index.class)?).chain(arguments(&index.descriptor)).collect()
+            }
+            Instruction::Invokeinterface { index, .. } => {
+                std::iter::once(self.receiver(index.class)?).chain(arguments(&index.descriptor)).collect()
+            }
+            Instruction::Invokespecial { index } => {
+                let (_, name, descriptor, _) = method_ref(index);
+                let receiver = if name == "<init>" {
+                    Operand::Uninitialized
+                } else {
+                    object(self.this_class)
+                };
+                std::iter::once(receiver).chain(arguments(descriptor)).collect()
+            }
+            Instruction::Invokestatic { index } => arguments(index.descriptor()),
+            Instruction::Invokedynamic { index, .. } => arguments(&index.descriptor),
+            _ => Vec::new(),
+        })
+    }
+
+    /// What a call needs of the object it is made on, which may be an array
+    /// for the methods arrays inherit from `Object`.
+    fn receiver(&self, class: &'a str) -> std::result::Result<Operand<'a>, String> {
+        if class.starts_with('[') {
+            let ty = TypeDescriptor::parse(class).map_err(|x| crate::Error::from(x).to_string())?.1;
+            return Ok(Operand::Type(VerificationType::Object(ty)));
+        }
+        Ok(object(class))
+    }
+}
+
+fn int_array<'a>() -> Operand<'a> {
+    Operand::Array(|x| *x == TypeDescriptor::Int, "an int[]")
+}
+
+fn byte_array<'a>() -> Operand<'a> {
+    Operand::Array(
+        |x| matches!(x, TypeDescriptor::Byte | TypeDescriptor::Boolean),
+        "a byte[] or boolean[]",
+    )
+}
+
+fn char_array<'a>() -> Operand<'a> {
+    Operand::Array(|x| *x == TypeDescriptor::Char, "a char[]")
+}
+
+fn short_array<'a>() -> Operand<'a> {
+    Operand::Array(|x| *x == TypeDescriptor::Short, "a short[]")
+}
+
+fn long_array<'a>() -> Operand<'a> {
+    Operand::Array(|x| *x == TypeDescriptor::Long, "a long[]")
+}
+
+fn float_array<'a>() -> Operand<'a> {
+    Operand::Array(|x| *x == TypeDescriptor::Float, "a float[]")
+}
+
+fn double_array<'a>() -> Operand<'a> {
+    Operand::Array(|x| *x == TypeDescriptor::Double, "a double[]")
+}
+
+fn reference_array<'a>() -> Operand<'a> {
+    Operand::Array(|x| !is_primitive(x), "an array of references")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::Op,
+        builder::{ClassFileBuilder, MethodBuilder},
+    };
+
+    const EXAMPLE: &[u8] = include_bytes!("../example.class");
+
+    /// The violations in a class with `static int run()` made of `ops`.
+    fn violations(ops: Vec<Op>) -> Vec<(String, Option<u32>)> {
+        let descriptor = MethodDescriptor::new(Vec::new(), Some(TypeDescriptor::Int));
+        let method =
+            MethodBuilder::new(MethodAccessFlags::STATIC, "run", &descriptor).with_code(2, 0, ops);
+        let bytes = ClassFileBuilder::new("Run")
+            .with_method(method)
+            .build()
+            .unwrap();
+        let class_file = ClassFile::parse(&bytes[..]).unwrap();
+        let violations = Verifier::new().verify(&class_file).unwrap();
+        violations
+            .into_iter()
+            .map(|x| (x.method.to_string(), x.pc))
+            .collect()
+    }
+
+    #[test]
+    fn example_class_verifies_before_and_after_writing() {
+        let class_file = ClassFile::parse(EXAMPLE).unwrap();
+        assert_eq!(Verifier::new().verify(&class_file).unwrap(), Vec::new());
+
+        let written = ClassFile::parse(&class_file.to_bytes().unwrap()[..]).unwrap();
+        assert_eq!(Verifier::new().verify(&written).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn valid_code_verifies() {
+        assert_eq!(
+            violations(vec![Op::Simple(0x04), Op::Simple(0xac)]),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn stack_underflow_is_a_violation() {
+        assert_eq!(
+            violations(vec![Op::Simple(0xac)]),
+            vec![("run".to_string(), Some(0))]
+        );
+    }
+
+    #[test]
+    fn wrong_return_type_is_a_violation() {
+        // `fconst_1`, `ireturn`
+        assert_eq!(
+            violations(vec![Op::Simple(0x0c), Op::Simple(0xac)]),
+            vec![("run".to_string(), Some(1))]
+        );
+    }
+
+    #[test]
+    fn falling_off_the_end_is_a_violation() {
+        let found = violations(vec![Op::Simple(0x04)]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "run");
+    }
+}
@@ -0,0 +1,854 @@
+//! Reading and rewriting classes through visitors, in the style of ASM. A
+//! class is read as a sequence of events passed down a chain of
+//! [`ClassVisitor`]s, [`MethodVisitor`]s and [`CodeVisitor`]s, which pass
+//! each event on unchanged unless they override it, usually ending in a
+//! [`ClassWriter`] that encodes the result. See [`ClassFile::accept`].
+//!
+//! Code is given as [`Op`]s naming their constants, so instructions can be
+//! added, removed or changed without handling pool indices, and the writer
+//! keeps branches, exception handlers and the pc tables of the code pointing
+//! at the same instructions.
+
+use crate::{
+    asm::{assemble, Constant, Label, Op},
+    attributes::Code,
+    builder::{attribute_entries, code_attribute, ClassFileBuilder, ConstantPoolBuilder, ExceptionHandler, FieldBuilder, MethodBuilder},
+    canonical::Reader,
+    instruction::{BranchOffset, Instruction},
+    raw::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags, NameAndTypeIndex},
+    ClassFile, ClassIndex, ConstantPoolItem, Error, Result, Utf8Index,
+};
+
+/// The first label [`ClassFile::accept`] does not bind, as code is at most
+/// 65535 bytes. Labels for new positions in the code can be numbered from
+/// here.
+pub const FIRST_NEW_LABEL: u32 = 0x10000;
+
+/// The parts of a class before its members, see [`ClassVisitor::visit`].
+#[derive(Debug)]
+pub struct ClassHeader {
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub access_flags: ClassAccessFlags,
+    /// The internal name, e.g. `com/example/Foo`.
+    pub name: String,
+    /// `None` for `java/lang/Object` and module descriptors.
+    pub super_class: Option<String>,
+    pub interfaces: Vec<String>,
+}
+
+/// A field with its attributes, see [`ClassVisitor::visit_field`].
+#[derive(Debug)]
+pub struct FieldInfo {
+    pub access_flags: FieldAccessFlags,
+    pub name: String,
+    pub descriptor: String,
+    /// The attributes by name with their contents, in the order they appear
+    /// in the file.
+    pub attributes: Vec<(String, Vec<u8>)>,
+}
+
+/// The declaration of a method, see [`ClassVisitor::visit_method`].
+#[derive(Debug)]
+pub struct MethodHeader {
+    pub access_flags: MethodAccessFlags,
+    pub name: String,
+    pub descriptor: String,
+}
+
+/// Receives a class as [`Self::visit`], then its fields, methods and
+/// attributes in the order of the file, then [`Self::visit_end`].
+///
+/// Every event is passed on to [`Self::delegate`] by default, so a visitor
+/// only overrides the events it changes: it can pass them on with other
+/// arguments, leave them out by not passing them on, or add events of its
+/// own. Attribute contents are passed as they are, with pool indices
+/// referring to the pool of the class read.
+pub trait ClassVisitor {
+    /// The visitor events are passed on to. With `None`, the default, they
+    /// are dropped.
+    fn delegate(&mut self) -> Option<&mut dyn ClassVisitor> {
+        None
+    }
+
+    fn visit(&mut self, header: ClassHeader) -> Result<()> {
+        match self.delegate() {
+            Some(x) => x.visit(header),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_field(&mut self, field: FieldInfo) -> Result<()> {
+        match self.delegate() {
+            Some(x) => x.visit_field(field),
+            None => Ok(()),
+        }
+    }
+
+    /// Starts a method, returning the visitor for its attributes and code,
+    /// or `None` to leave the method out.
+    fn visit_method(&mut self, method: MethodHeader) -> Result<Option<Box<dyn MethodVisitor + '_>>> {
+        match self.delegate() {
+            Some(x) => x.visit_method(method),
+            None => Ok(None),
+        }
+    }
+
+    fn visit_attribute(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        match self.delegate() {
+            Some(x) => x.visit_attribute(name, data),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_end(&mut self) -> Result<()> {
+        match self.delegate() {
+            Some(x) => x.visit_end(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Receives the attributes of a method in the order of the file, with
+/// [`Self::visit_code`] in place of the `Code` attribute, then
+/// [`Self::visit_end`]. Events are passed on as for a [`ClassVisitor`].
+pub trait MethodVisitor {
+    /// The visitor events are passed on to. With `None`, the default, they
+    /// are dropped.
+    fn delegate(&mut self) -> Option<&mut dyn MethodVisitor> {
+        None
+    }
+
+    /// An attribute other than `Code`.
+    fn visit_attribute(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        match self.delegate() {
+            Some(x) => x.visit_attribute(name, data),
+            None => Ok(()),
+        }
+    }
+
+    /// Starts the code of the method, returning the visitor for it, or
+    /// `None` to leave the code out.
+    fn visit_code(&mut self) -> Result<Option<Box<dyn CodeVisitor + '_>>> {
+        match self.delegate() {
+            Some(x) => x.visit_code(),
+            None => Ok(None),
+        }
+    }
+
+    fn visit_end(&mut self) -> Result<()> {
+        match self.delegate() {
+            Some(x) => x.visit_end(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Receives the code of a method as its instructions, then its exception
+/// handlers, the attributes of the `Code` attribute, [`Self::visit_maxs`] and
+/// [`Self::visit_end`]. Events are passed on as for a [`ClassVisitor`].
+///
+/// Code read by [`ClassFile::accept`] binds `Label(pc)` before the
+/// instruction at every pc, and once more at the end of the code, which its
+/// branches and exception handlers refer to. The pcs in attributes such as
+/// `LineNumberTable` are those of the code as read.
+///
+/// Ops visited after a label are part of what a branch to it runs, so code
+/// meant to run only when falling through goes before the label, and code
+/// for the start of a method before the first one. Stack map frames refer to
+/// a `new` by its label, which it must stay right after.
+pub trait CodeVisitor {
+    /// The visitor events are passed on to. With `None`, the default, they
+    /// are dropped.
+    fn delegate(&mut self) -> Option<&mut dyn CodeVisitor> {
+        None
+    }
+
+    /// An instruction, or with [`Op::Label`] a position in the code.
+    fn visit_op(&mut self, op: Op) -> Result<()> {
+        match self.delegate() {
+            Some(x) => x.visit_op(op),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_exception_handler(&mut self, handler: ExceptionHandler) -> Result<()> {
+        match self.delegate() {
+            Some(x) => x.visit_exception_handler(handler),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_attribute(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        match self.delegate() {
+            Some(x) => x.visit_attribute(name, data),
+            None => Ok(()),
+        }
+    }
+
+    /// The operand stack and local variable sizes, which are not recomputed
+    /// when code changes.
+    fn visit_maxs(&mut self, max_stack: u16, max_locals: u16) -> Result<()> {
+        match self.delegate() {
+            Some(x) => x.visit_maxs(max_stack, max_locals),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_end(&mut self) -> Result<()> {
+        match self.delegate() {
+            Some(x) => x.visit_end(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A [`ClassVisitor`] encoding the class it is given, see [`Self::to_bytes`].
+///
+/// Code is assembled from its [`Op`]s. When that leaves every instruction
+/// read at its pc, the attributes of the code are kept as they are.
+/// Otherwise the pcs in `LineNumberTable`, `LocalVariableTable`,
+/// `LocalVariableTypeTable` and `StackMapTable` follow their instructions,
+/// entries for removed instructions are dropped, and other code attributes,
+/// such as type annotations, are dropped too. Stack map frames are not
+/// computed, so new branch targets need frames of their own.
+#[derive(Debug, Default)]
+pub struct ClassWriter {
+    pool: ConstantPoolBuilder,
+    header: Option<ClassHeader>,
+    fields: Vec<FieldBuilder>,
+    methods: Vec<MethodBuilder>,
+    attributes: Vec<(String, Vec<u8>)>,
+}
+
+impl ClassWriter {
+    /// A writer starting from an empty constant pool, for classes whose
+    /// attributes refer to no pool entries.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A writer starting from the constant pool of `class_file`, so that the
+    /// attributes read from it keep their meaning. Entries no longer used are
+    /// kept too.
+    pub fn from_class(class_file: &ClassFile) -> Result<Self> {
+        Ok(Self {
+            pool: ConstantPoolBuilder::from_items(&class_file.constant_pool.0)?,
+            ..Self::default()
+        })
+    }
+
+    /// Encodes the class visited. A class read by [`ClassFile::accept`] and
+    /// passed on unchanged to a writer from [`Self::from_class`] comes out
+    /// the same, as long as its code is encoded the way the assembler would
+    /// and it has no duplicate pool entries, of which the first is used.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let header = self
+            .header
+            .as_ref()
+            .ok_or_else(|| Error::WriteError("no class was visited".to_string()))?;
+        let mut builder = ClassFileBuilder::new(&header.name)
+            .with_constant_pool(self.pool.clone())
+            .with_version(header.major_version, header.minor_version)
+            .with_access_flags(ClassAccessFlags::from_bits_retain(header.access_flags.bits()));
+        builder = match &header.super_class {
+            Some(x) => builder.with_super_class(x),
+            None => builder.without_super_class(),
+        };
+        for interface in &header.interfaces {
+            builder = builder.with_interface(interface);
+        }
+        for field in &self.fields {
+            builder = builder.with_field(field.clone());
+        }
+        for method in &self.methods {
+            builder = builder.with_method(method.clone());
+        }
+        for (name, data) in &self.attributes {
+            builder = builder.with_attribute(name, data.clone());
+        }
+        builder.build()
+    }
+}
+
+impl ClassVisitor for ClassWriter {
+    fn visit(&mut self, header: ClassHeader) -> Result<()> {
+        self.header = Some(header);
+        Ok(())
+    }
+
+    fn visit_field(&mut self, field: FieldInfo) -> Result<()> {
+        let mut builder = FieldBuilder::from_raw(field.access_flags.bits(), &field.name, field.descriptor);
+        for (name, data) in field.attributes {
+            builder = builder.with_attribute(&name, data);
+        }
+        self.fields.push(builder);
+        Ok(())
+    }
+
+    fn visit_method(&mut self, method: MethodHeader) -> Result<Option<Box<dyn MethodVisitor + '_>>> {
+        Ok(Some(Box::new(MethodWriter {
+            class: self,
+            header: method,
+            attributes: Vec::new(),
+        })))
+    }
+
+    fn visit_attribute(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        self.attributes.push((name.to_string(), data.to_vec()));
+        Ok(())
+    }
+
+    fn visit_end(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct MethodWriter<'w> {
+    class: &'w mut ClassWriter,
+    header: MethodHeader,
+    attributes: Vec<(String, Vec<u8>)>,
+}
+
+impl MethodVisitor for MethodWriter<'_> {
+    fn visit_attribute(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        self.attributes.push((name.to_string(), data.to_vec()));
+        Ok(())
+    }
+
+    fn visit_code(&mut self) -> Result<Option<Box<dyn CodeVisitor + '_>>> {
+        Ok(Some(Box::new(CodeWriter {
+            pool: &mut self.class.pool,
+            method_attributes: &mut self.attributes,
+            ops: Vec::new(),
+            exception_handlers: Vec::new(),
+            attributes: Vec::new(),
+            max_stack: 0,
+            max_locals: 0,
+        })))
+    }
+
+    fn visit_end(&mut self) -> Result<()> {
+        let mut method = MethodBuilder::from_raw(self.header.access_flags.bits(), &self.header.name, self.header.descriptor.clone());
+        for (name, data) in self.attributes.drain(..) {
+            method = method.with_attribute(&name, data);
+        }
+        self.class.methods.push(method);
+        Ok(())
+    }
+}
+
+struct CodeWriter<'m> {
+    pool: &'m mut ConstantPoolBuilder,
+    /// Where the `Code` attribute goes, in the place of the one read.
+    method_attributes: &'m mut Vec<(String, Vec<u8>)>,
+    ops: Vec<Op>,
+    exception_handlers: Vec<ExceptionHandler>,
+    attributes: Vec<(String, Vec<u8>)>,
+    max_stack: u16,
+    max_locals: u16,
+}
+
+impl CodeVisitor for CodeWriter<'_> {
+    fn visit_op(&mut self, op: Op) -> Result<()> {
+        self.ops.push(op);
+        Ok(())
+    }
+
+    fn visit_exception_handler(&mut self, handler: ExceptionHandler) -> Result<()> {
+        self.exception_handlers.push(handler);
+        Ok(())
+    }
+
+    fn visit_attribute(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        self.attributes.push((name.to_string(), data.to_vec()));
+        Ok(())
+    }
+
+    fn visit_maxs(&mut self, max_stack: u16, max_locals: u16) -> Result<()> {
+        self.max_stack = max_stack;
+        self.max_locals = max_locals;
+        Ok(())
+    }
+
+    fn visit_end(&mut self) -> Result<()> {
+        let assembled = assemble(&self.ops, self.pool)?;
+        let label_pc = |label: Label| {
+            assembled
+                .pc(label)
+                .map(|x| x as u16)
+                .ok_or_else(|| Error::AssemblyError(format!("label {} is never bound", label.0)))
+        };
+        let exception_table = self
+            .exception_handlers
+            .iter()
+            .map(|x| {
+                let catch_type = match &x.catch_type {
+                    Some(name) => self.pool.class(name)?.0,
+                    None => 0,
+                };
+                Ok([label_pc(x.start)?, label_pc(x.end)?, label_pc(x.handler)?, catch_type])
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let pc = |pc: u32| assembled.pc(Label(pc));
+        let moved = self
+            .ops
+            .iter()
+            .any(|x| matches!(x, Op::Label(label) if label.0 < FIRST_NEW_LABEL && pc(label.0) != Some(label.0)));
+        let mut attributes = Vec::new();
+        for (name, data) in self.attributes.drain(..) {
+            if !moved {
+                attributes.push((name, data));
+                continue;
+            }
+            let data = match name.as_str() {
+                "LineNumberTable" => line_numbers(&data, &pc)?,
+                "LocalVariableTable" | "LocalVariableTypeTable" => local_variables(&data, &name, &pc)?,
+                "StackMapTable" => stack_map_table(&data, &pc)?,
+                _ => continue,
+            };
+            attributes.push((name, data));
+        }
+        let attributes = attribute_entries(self.pool, attributes)?;
+        let code = code_attribute(self.max_stack, self.max_locals, &assembled.code, &exception_table, &attributes)?;
+        self.method_attributes.push(("Code".to_string(), code));
+        Ok(())
+    }
+}
+
+/// A table of `u16` entries, preceded by their count.
+fn table<const N: usize>(entries: &[[u16; N]]) -> Vec<u8> {
+    let mut out = (entries.len() as u16).to_be_bytes().to_vec();
+    for x in entries.iter().flatten() {
+        out.extend_from_slice(&x.to_be_bytes());
+    }
+    out
+}
+
+/// A `LineNumberTable` with the pcs of moved code, leaving out removed code.
+fn line_numbers(data: &[u8], pc: &dyn Fn(u32) -> Option<u32>) -> Result<Vec<u8>> {
+    let mut r = Reader::new(data, "LineNumberTable");
+    let mut entries = Vec::new();
+    for _ in 0..r.u16()? {
+        let (start, line) = (r.u16()?, r.u16()?);
+        if let Some(start) = pc(start.into()) {
+            entries.push([start as u16, line]);
+        }
+    }
+    Ok(table(&entries))
+}
+
+/// A `LocalVariableTable` or `LocalVariableTypeTable` with the pcs of moved
+/// code, leaving out variables whose range no longer exists.
+fn local_variables(data: &[u8], name: &str, pc: &dyn Fn(u32) -> Option<u32>) -> Result<Vec<u8>> {
+    let mut r = Reader::new(data, name);
+    let mut entries = Vec::new();
+    for _ in 0..r.u16()? {
+        let [start, length, name, descriptor, index] = [r.u16()?, r.u16()?, r.u16()?, r.u16()?, r.u16()?];
+        let range = pc(start.into()).zip(pc(start as u32 + length as u32));
+        if let Some((start, length)) = range.and_then(|(start, end)| Some((start, end.checked_sub(start)?))) {
+            entries.push([start as u16, length as u16, name, descriptor, index]);
+        }
+    }
+    Ok(table(&entries))
+}
+
+fn removed_frame(pc: u32) -> Error {
+    Error::WriteError(format!("the stack map frame at pc {} is for removed code", pc))
+}
+
+fn verification_type(r: &mut Reader, out: &mut Vec<u8>, pc: &dyn Fn(u32) -> Option<u32>) -> Result<()> {
+    let tag = r.u8()?;
+    out.push(tag);
+    match tag {
+        0..=6 => {}
+        7 => out.extend_from_slice(r.bytes(2)?),
+        // An object from the `new` at this pc, not yet initialized.
+        8 => {
+            let offset = r.u16()?;
+            let offset = pc(offset.into()).ok_or_else(|| removed_frame(offset.into()))?;
+            out.extend_from_slice(&(offset as u16).to_be_bytes());
+        }
+        _ => return Err(Error::WriteError(format!("unknown verification type {}", tag))),
+    }
+    Ok(())
+}
+
+/// A `StackMapTable` with the pcs of moved code. Frames of the compact forms
+/// are widened where their offset delta no longer fits.
+fn stack_map_table(data: &[u8], pc: &dyn Fn(u32) -> Option<u32>) -> Result<Vec<u8>> {
+    let mut r = Reader::new(data, "StackMapTable");
+    let count = r.u16()?;
+    let mut out = count.to_be_bytes().to_vec();
+    let mut last: Option<(u32, u32)> = None;
+    for _ in 0..count {
+        let frame_type = r.u8()?;
+        let delta = match frame_type {
+            0..=63 => frame_type as u32,
+            64..=127 => frame_type as u32 - 64,
+            247..=255 => r.u16()? as u32,
+            _ => return Err(Error::WriteError(format!("unknown stack map frame type {}", frame_type))),
+        };
+        // Each frame after the first is one past the previous pc plus its delta.
+        let old_pc = last.map_or(delta, |(old, _)| old + delta + 1);
+        let new_pc = pc(old_pc).ok_or_else(|| removed_frame(old_pc))?;
+        let delta = match last {
+            Some((_, new)) => new_pc.checked_sub(new + 1),
+            None => Some(new_pc),
+        };
+        let delta = delta
+            .and_then(|x| u16::try_from(x).ok())
+            .ok_or_else(|| Error::WriteError(format!("the stack map frame at pc {} is out of order", old_pc)))?;
+        last = Some((old_pc, new_pc));
+
+        let extended = |out: &mut Vec<u8>, frame_type: u8| {
+            out.push(frame_type);
+            out.extend_from_slice(&delta.to_be_bytes());
+        };
+        match frame_type {
+            0..=63 if delta < 64 => out.push(delta as u8),
+            0..=63 => extended(&mut out, 251),
+            64..=127 => {
+                match delta {
+                    0..=63 => out.push(64 + delta as u8),
+                    _ => extended(&mut out, 247),
+                }
+                verification_type(&mut r, &mut out, pc)?;
+            }
+            247 => {
+                extended(&mut out, frame_type);
+                verification_type(&mut r, &mut out, pc)?;
+            }
+            252..=254 => {
+                extended(&mut out, frame_type);
+                for _ in 251..frame_type {
+                    verification_type(&mut r, &mut out, pc)?;
+                }
+            }
+            255 => {
+                extended(&mut out, frame_type);
+                for _ in 0..2 {
+                    let count = r.u16()?;
+                    out.extend_from_slice(&count.to_be_bytes());
+                    for _ in 0..count {
+                        verification_type(&mut r, &mut out, pc)?;
+                    }
+                }
+            }
+            _ => extended(&mut out, frame_type),
+        }
+    }
+    Ok(out)
+}
+
+/// The class, name and descriptor of a field or method reference, and
+/// whether it is an `InterfaceMethodref`.
+struct Member {
+    class: String,
+    name: String,
+    descriptor: String,
+    interface: bool,
+}
+
+fn name_and_type(class_file: &ClassFile, index: &NameAndTypeIndex) -> Result<(String, String)> {
+    Ok((index.get_name(class_file)?.to_string(), index.get_descriptor(class_file)?.to_string()))
+}
+
+fn member(class_file: &ClassFile, index: u16) -> Result<Member> {
+    let (class_index, name_and_type_index, interface) = match class_file.constant(index) {
+        Some(ConstantPoolItem::Fieldref {
+            class_index,
+            name_and_type_index,
+        })
+        | Some(ConstantPoolItem::Methodref {
+            class_index,
+            name_and_type_index,
+        }) => (class_index, name_and_type_index, false),
+        Some(ConstantPoolItem::InterfaceMethodref {
+            class_index,
+            name_and_type_index,
+        }) => (class_index, name_and_type_index, true),
+        x => {
+            return Err(Error::ConstantPoolError(format!(
+                "expected a field or method reference at {}, found {:?}",
+                index, x
+            )))
+        }
+    };
+    let (name, descriptor) = name_and_type(class_file, name_and_type_index)?;
+    Ok(Member {
+        class: class_index.get_as_string(class_file)?.to_string(),
+        name,
+        descriptor,
+        interface,
+    })
+}
+
+/// The constant an `ldc`, `ldc_w` or `ldc2_w` loads.
+fn constant(class_file: &ClassFile, index: u16) -> Result<Constant> {
+    let utf8 = |x: &Utf8Index| x.get_as_string(class_file).map(str::to_string);
+    Ok(match class_file.constant(index) {
+        Some(ConstantPoolItem::Integer { value }) => Constant::Integer(*value),
+        Some(ConstantPoolItem::Float { value }) => Constant::Float(*value),
+        Some(ConstantPoolItem::Long { value }) => Constant::Long(*value),
+        Some(ConstantPoolItem::Double { value }) => Constant::Double(*value),
+        Some(ConstantPoolItem::String { string_index }) => {
+            let units = class_file.utf16_constant(string_index.0)?;
+            match String::from_utf16(&units) {
+                Ok(x) => Constant::String(x),
+                Err(_) => Constant::Utf16String(units),
+            }
+        }
+        Some(ConstantPoolItem::Class { name_index }) => Constant::Class(utf8(name_index)?),
+        Some(ConstantPoolItem::MethodType { descriptor_index }) => Constant::MethodType(utf8(descriptor_index)?),
+        Some(ConstantPoolItem::MethodHandle { reference }) => {
+            let member = member(class_file, reference.index)?;
+            Constant::MethodHandle {
+                kind: reference.kind,
+                class: member.class,
+                name: member.name,
+                descriptor: member.descriptor,
+                interface: member.interface,
+            }
+        }
+        Some(ConstantPoolItem::Dynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        }) => {
+            let (name, descriptor) = name_and_type(class_file, name_and_type_index)?;
+            Constant::Dynamic {
+                bootstrap_method: bootstrap_method_attr_index.0,
+                name,
+                descriptor,
+            }
+        }
+        x => {
+            return Err(Error::ConstantPoolError(format!(
+                "expected a loadable constant at {}, found {:?}",
+                index, x
+            )))
+        }
+    })
+}
+
+/// The general form of a load or store, e.g. `iload` for `iload_2`.
+fn general_form(opcode: u8) -> u8 {
+    match opcode {
+        0x1a..=0x2d => 0x15 + (opcode - 0x1a) / 4,
+        0x3b..=0x4e => 0x36 + (opcode - 0x3b) / 4,
+        _ => opcode,
+    }
+}
+
+/// Converts the decoded instructions of `code` into ops, binding
+/// `Label(pc)` before every instruction and at the end.
+fn ops(class_file: &ClassFile, code: &Code) -> Result<Vec<Op>> {
+    let mut res = Vec::new();
+    for (pc, instruction) in code.instructions_with_pcs()? {
+        let target = |offset: &BranchOffset| {
+            offset
+                .target(pc)
+                .map(Label)
+                .ok_or_else(|| Error::WriteError(format!("branch out of the code at pc {}", pc)))
+        };
+        // Decoding keeps the constants but not which entries they are.
+        let index = || {
+            code.constant_index(pc)
+                .ok_or_else(|| Error::WriteError(format!("no constant pool index at pc {}", pc)))
+        };
+        res.push(Op::Label(Label(pc)));
+        let opcode = instruction.opcode();
+        let op = match &instruction {
+            Instruction::Bipush { value } => Op::Bipush(*value),
+            Instruction::Sipush { value } => Op::Sipush(*value),
+            Instruction::Ldc { index, .. } => Op::Ldc(constant(class_file, *index as u16)?),
+            Instruction::LdcW { index, .. } | Instruction::Ldc2W { index, .. } => {
+                Op::Ldc(constant(class_file, *index)?)
+            }
+            Instruction::Iinc { index, constant } | Instruction::WideIinc { index, constant } => Op::Iinc {
+                index: index.0,
+                constant: *constant,
+            },
+            Instruction::Tableswitch {
+                default,
+                low,
+                jump_offsets,
+                ..
+            } => Op::Tableswitch {
+                default: target(default)?,
+                low: *low,
+                targets: jump_offsets.iter().map(target).collect::<Result<_>>()?,
+            },
+            Instruction::Lookupswitch { default, pairs, .. } => Op::Lookupswitch {
+                default: target(default)?,
+                pairs: pairs.iter().map(|(key, x)| Ok((*key, target(x)?))).collect::<Result<_>>()?,
+            },
+            Instruction::Getfield { .. }
+            | Instruction::Getstatic { .. }
+            | Instruction::Putfield { .. }
+            | Instruction::Putstatic { .. } => {
+                let member = member(class_file, index()?)?;
+                Op::Field {
+                    opcode,
+                    class: member.class,
+                    name: member.name,
+                    descriptor: member.descriptor,
+                }
+            }
+            // The argument count of `invokeinterface` is recomputed by the assembler.
+            Instruction::Invokevirtual { .. }
+            | Instruction::Invokespecial { .. }
+            | Instruction::Invokestatic { .. }
+            | Instruction::Invokeinterface { .. } => {
+                let member = member(class_file, index()?)?;
+                Op::Invoke {
+                    opcode,
+                    class: member.class,
+                    name: member.name,
+                    descriptor: member.descriptor,
+                    interface: member.interface,
+                }
+            }
+            Instruction::Invokedynamic { .. } => {
+                let index = index()?;
+                match class_file.constant(index) {
+                    Some(ConstantPoolItem::InvokeDynamic {
+                        bootstrap_method_attr_index,
+                        name_and_type_index,
+                    }) => {
+                        let (name, descriptor) = name_and_type(class_file, name_and_type_index)?;
+                        Op::Invokedynamic {
+                            bootstrap_method: bootstrap_method_attr_index.0,
+                            name,
+                            descriptor,
+                        }
+                    }
+                    x => {
+                        return Err(Error::ConstantPoolError(format!(
+                            "expected InvokeDynamic at {}, found {:?}",
+                            index, x
+                        )))
+                    }
+                }
+            }
+            Instruction::Anewarray { class }
+            | Instruction::Checkcast { class }
+            | Instruction::Instanceof { class }
+            | Instruction::New { class } => Op::Type {
+                opcode,
+                class: class.to_string(),
+            },
+            Instruction::Multianewarray { class, dimensions } => Op::Multianewarray {
+                class: class.to_string(),
+                dimensions: *dimensions,
+            },
+            Instruction::Newarray { atype } => Op::Newarray(*atype as u8),
+            Instruction::Unknown { opcode, .. } => {
+                return Err(Error::WriteError(format!("unknown opcode {:#04x} at pc {}", opcode, pc)))
+            }
+            x => match (x.local_access(), x.branch_offsets().first()) {
+                (Some(access), _) => Op::Local {
+                    opcode: general_form(opcode),
+                    index: access.index,
+                },
+                (_, Some(offset)) => Op::Branch {
+                    opcode,
+                    target: target(offset)?,
+                },
+                _ => Op::Simple(opcode),
+            },
+        };
+        res.push(op);
+    }
+    res.push(Op::Label(Label(code.code_span().len as u32)));
+    Ok(res)
+}
+
+fn accept_code(class_file: &ClassFile, data: &[u8], visitor: &mut dyn CodeVisitor) -> Result<()> {
+    let mut r = Reader::new(data, "Code");
+    let (max_stack, max_locals) = (r.u16()?, r.u16()?);
+    let length = r.u32()?;
+    r.bytes(length as usize)?;
+    let code = crate::attributes::decode::<Code>("Code", data, 0, class_file)?;
+    for op in ops(class_file, &code)? {
+        visitor.visit_op(op)?;
+    }
+    for _ in 0..r.u16()? {
+        let [start, end, handler, catch_type] = [r.u16()?, r.u16()?, r.u16()?, r.u16()?];
+        visitor.visit_exception_handler(ExceptionHandler {
+            start: Label(start.into()),
+            end: Label(end.into()),
+            handler: Label(handler.into()),
+            catch_type: match catch_type {
+                0 => None,
+                x => Some(ClassIndex(x).get_as_string(class_file)?.to_string()),
+            },
+        })?;
+    }
+    for _ in 0..r.u16()? {
+        let name = Utf8Index(r.u16()?).get_as_string(class_file)?;
+        let length = r.u32()?;
+        visitor.visit_attribute(name, r.bytes(length as usize)?)?;
+    }
+    visitor.visit_maxs(max_stack, max_locals)?;
+    visitor.visit_end()
+}
+
+impl ClassFile {
+    /// Passes the class to `visitor` as the events of a [`ClassVisitor`],
+    /// with the code of each method decoded into [`Op`]s as described for
+    /// [`CodeVisitor`]. Loads and stores come as [`Op::Local`] whichever
+    /// form they were encoded in.
+    ///
+    /// To rewrite a class, pass it through visitors that make the changes to
+    /// a [`ClassWriter::from_class`].
+    pub fn accept(&self, visitor: &mut dyn ClassVisitor) -> Result<()> {
+        visitor.visit(ClassHeader {
+            major_version: self.major_version(),
+            minor_version: self.minor_version(),
+            access_flags: ClassAccessFlags::from_bits_retain(self.access_flags.bits()),
+            name: self.this_class()?.to_string(),
+            super_class: match self.super_class.0 {
+                0 => None,
+                _ => Some(self.super_class()?.to_string()),
+            },
+            interfaces: self.interfaces()?.into_iter().map(str::to_string).collect(),
+        })?;
+        for field in self.fields() {
+            visitor.visit_field(FieldInfo {
+                access_flags: FieldAccessFlags::from_bits_retain(field.field_inner.access_flags.bits()),
+                name: field.identifier()?.to_string(),
+                descriptor: field.field_inner.descriptor_index.get_as_string(self)?.to_string(),
+                attributes: field
+                    .raw_attributes()
+                    .into_iter()
+                    .map(|x| (x.name.to_string(), x.data.to_vec()))
+                    .collect(),
+            })?;
+        }
+        for method in self.methods() {
+            let header = MethodHeader {
+                access_flags: MethodAccessFlags::from_bits_retain(method.method_inner.access_flags.bits()),
+                name: method.identifier()?.to_string(),
+                descriptor: method.method_inner.descriptor_index.get_as_string(self)?.to_string(),
+            };
+            let mut method_visitor = match visitor.visit_method(header)? {
+                Some(x) => x,
+                None => continue,
+            };
+            for attribute in method.raw_attributes() {
+                if attribute.name != "Code" {
+                    method_visitor.visit_attribute(attribute.name, attribute.data)?;
+                } else if let Some(mut code_visitor) = method_visitor.visit_code()? {
+                    accept_code(self, attribute.data, &mut *code_visitor)?;
+                }
+            }
+            method_visitor.visit_end()?;
+        }
+        for attribute in self.raw_attributes() {
+            visitor.visit_attribute(attribute.name, attribute.data)?;
+        }
+        visitor.visit_end()
+    }
+}
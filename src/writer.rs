@@ -0,0 +1,349 @@
+use std::io::Write;
+
+use crate::{
+    mutf8,
+    raw::{AttributeEntry, Attributes, ConstantPool, ConstantPoolItem, FieldRaw, MethodRaw},
+    ClassAccessFlags, ClassFile, Error, Result, Span,
+};
+
+pub(crate) fn count<T>(items: &[T], what: &str) -> Result<u16> {
+    u16::try_from(items.len())
+        .map_err(|_| Error::WriteError(format!("{} {} do not fit a u16 count", items.len(), what)))
+}
+
+pub(crate) fn write_u16<W: Write>(writer: &mut W, value: u16) -> Result<()> {
+    Ok(writer.write_all(&value.to_be_bytes())?)
+}
+
+impl ConstantPoolItem {
+    fn tag(&self) -> Option<u8> {
+        Some(match self {
+            Self::Class { .. } => 7,
+            Self::Fieldref { .. } => 9,
+            Self::Methodref { .. } => 10,
+            Self::InterfaceMethodref { .. } => 11,
+            Self::String { .. } => 8,
+            Self::Integer { .. } => 3,
+            Self::Float { .. } => 4,
+            Self::Long { .. } => 5,
+            Self::Double { .. } => 6,
+            Self::NameAndType { .. } => 12,
+            Self::Utf8 { .. } => 1,
+            Self::MethodHandle { .. } => 15,
+            Self::MethodType { .. } => 16,
+            Self::Dynamic { .. } => 17,
+            Self::InvokeDynamic { .. } => 18,
+            Self::Module { .. } => 19,
+            Self::Package { .. } => 20,
+            Self::Custom { tag, .. } => *tag,
+            Self::Skip => return None,
+        })
+    }
+
+    pub(crate) fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let tag = match self.tag() {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        writer.write_all(&[tag])?;
+        match self {
+            Self::Class { name_index: a }
+            | Self::String { string_index: a }
+            | Self::MethodType { descriptor_index: a }
+            | Self::Module { name_index: a }
+            | Self::Package { name_index: a } => write_u16(writer, a.0)?,
+            Self::Fieldref {
+                class_index,
+                name_and_type_index,
+            }
+            | Self::Methodref {
+                class_index,
+                name_and_type_index,
+            }
+            | Self::InterfaceMethodref {
+                class_index,
+                name_and_type_index,
+            } => {
+                write_u16(writer, class_index.0)?;
+                write_u16(writer, name_and_type_index.0)?;
+            }
+            Self::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            }
+            | Self::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                write_u16(writer, bootstrap_method_attr_index.0)?;
+                write_u16(writer, name_and_type_index.0)?;
+            }
+            Self::NameAndType {
+                name_index,
+                descriptor_index,
+            } => {
+                write_u16(writer, name_index.0)?;
+                write_u16(writer, descriptor_index.0)?;
+            }
+            Self::Integer { value } => writer.write_all(&value.to_be_bytes())?,
+            Self::Float { value } => writer.write_all(&value.to_bits().to_be_bytes())?,
+            Self::Long { value } => writer.write_all(&value.to_be_bytes())?,
+            Self::Double { value } => writer.write_all(&value.to_bits().to_be_bytes())?,
+            Self::Utf8 { bytes, value } => {
+                // Keep the original bytes, even invalid ones read leniently,
+                // unless `value` was changed since.
+                let encoded;
+                let bytes = if mutf8::decode_lossy(bytes) == *value {
+                    bytes
+                } else {
+                    encoded = mutf8::encode(value);
+                    &encoded
+                };
+                let length = u16::try_from(bytes.len()).map_err(|_| {
+                    Error::WriteError(format!("Utf8 constant of {} bytes is too long", bytes.len()))
+                })?;
+                write_u16(writer, length)?;
+                writer.write_all(bytes)?;
+            }
+            Self::MethodHandle { reference } => {
+                writer.write_all(&[reference.kind])?;
+                write_u16(writer, reference.index)?;
+            }
+            Self::Custom { data, .. } => writer.write_all(data)?,
+            Self::Skip => {}
+        }
+        Ok(())
+    }
+}
+
+impl ConstantPool {
+    pub(crate) fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let count = u16::try_from(self.0.len() + 1)
+            .map_err(|_| Error::WriteError(format!("{} constants do not fit a u16 count", self.0.len())))?;
+        write_u16(writer, count)?;
+        for item in &self.0 {
+            item.write_to(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl Attributes {
+    /// Replaces the data of the last attribute called `name`, keeping its
+    /// name index, or appends one named by `name_index`.
+    fn set(&mut self, name_index: u16, name: &str, data: Vec<u8>) {
+        match self.0.iter_mut().rev().find(|x| x.name == name) {
+            Some(x) => x.data = data,
+            None => self.0.push(AttributeEntry {
+                name_index,
+                name: name.to_string(),
+                data,
+                span: Span::default(),
+            }),
+        }
+    }
+
+    fn remove(&mut self, name: &str) -> bool {
+        let len = self.0.len();
+        self.0.retain(|x| x.name != name);
+        self.0.len() != len
+    }
+
+    pub(crate) fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_u16(writer, count(&self.0, "attributes")?)?;
+        for x in self.iter() {
+            let length = u32::try_from(x.data.len()).map_err(|_| {
+                Error::WriteError(format!("attribute {} of {} bytes is too long", x.name, x.data.len()))
+            })?;
+            write_u16(writer, x.name_index)?;
+            writer.write_all(&length.to_be_bytes())?;
+            writer.write_all(&x.data)?;
+        }
+        Ok(())
+    }
+}
+
+impl FieldRaw {
+    pub(crate) fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_u16(writer, self.access_flags.bits())?;
+        write_u16(writer, self.name_index.0)?;
+        write_u16(writer, self.descriptor_index.0)?;
+        self.attributes.write_to(writer)
+    }
+}
+
+impl MethodRaw {
+    pub(crate) fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_u16(writer, self.access_flags.bits())?;
+        write_u16(writer, self.name_index.0)?;
+        write_u16(writer, self.descriptor_index.0)?;
+        self.attributes.write_to(writer)
+    }
+}
+
+impl ClassFile {
+    /// Serializes the class. Every attribute, whether or not this crate decodes
+    /// it, is written with its original name index and payload, so an
+    /// unmodified class is written back byte for byte. The exceptions are
+    /// attributes dropped by [`crate::ParseOptions::with_attribute_filter`] and
+    /// anything read leniently, such as truncated attributes. Entries from a
+    /// [`crate::options::ConstantPoolHook`] are written as their tag followed by
+    /// [`crate::CustomConstant::data`].
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(b"\xca\xfe\xba\xbe")?;
+        write_u16(writer, self._minor_version)?;
+        write_u16(writer, self._major_version)?;
+        self.constant_pool.write_to(writer)?;
+        write_u16(writer, self.access_flags.bits())?;
+        write_u16(writer, self.this_class.0)?;
+        write_u16(writer, self.super_class.0)?;
+        write_u16(writer, count(&self.interfaces, "interfaces")?)?;
+        for interface in &self.interfaces {
+            write_u16(writer, interface.0)?;
+        }
+        write_u16(writer, count(&self.fields, "fields")?)?;
+        for field in &self.fields {
+            field.write_to(writer)?;
+        }
+        write_u16(writer, count(&self.methods, "methods")?)?;
+        for method in &self.methods {
+            method.write_to(writer)?;
+        }
+        self.attributes.write_to(writer)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut res = Vec::new();
+        self.write_to(&mut res)?;
+        Ok(res)
+    }
+
+    pub fn access_flags(&self) -> &ClassAccessFlags {
+        &self.access_flags
+    }
+
+    pub fn set_access_flags(&mut self, access_flags: ClassAccessFlags) {
+        self.access_flags = access_flags;
+    }
+
+    pub fn set_version(&mut self, major: u16, minor: u16) {
+        self._major_version = major;
+        self._minor_version = minor;
+    }
+
+    /// The constant pool entry at `index`, for editing in place. Changing the
+    /// `value` of a `Utf8` entry is enough for the writer to re-encode it.
+    pub fn constant_mut(&mut self, index: u16) -> Option<&mut ConstantPoolItem> {
+        (index as usize)
+            .checked_sub(1)
+            .and_then(|x| self.constant_pool.0.get_mut(x))
+    }
+
+    /// Appends `item` to the constant pool and returns its index.
+    pub fn add_constant(&mut self, item: ConstantPoolItem) -> Result<u16> {
+        let wide = matches!(item, ConstantPoolItem::Long { .. } | ConstantPoolItem::Double { .. });
+        let index = self.constant_pool.0.len() + 1;
+        if index + wide as usize >= u16::MAX as usize {
+            return Err(Error::WriteError("the constant pool is full".to_string()));
+        }
+        self.constant_pool.0.push(item);
+        self.constant_pool.1.push(Span::default());
+        if wide {
+            self.constant_pool.0.push(ConstantPoolItem::Skip);
+            self.constant_pool.1.push(Span::default());
+        }
+        Ok(index as u16)
+    }
+
+    /// The index of the `Utf8` constant `value`, adding one if there is none.
+    pub(crate) fn utf8_index(&mut self, value: &str) -> Result<u16> {
+        let existing = self.constant_pool.0.iter().position(|x| match x {
+            ConstantPoolItem::Utf8 { value: x, .. } => x == value,
+            _ => false,
+        });
+        match existing {
+            Some(x) => Ok(x as u16 + 1),
+            None => self.add_constant(ConstantPoolItem::Utf8 {
+                bytes: mutf8::encode(value),
+                value: value.to_string(),
+            }),
+        }
+    }
+
+    /// Replaces the contents of the class attribute `name`, or appends it if
+    /// the class has none.
+    pub fn set_attribute(&mut self, name: &str, data: Vec<u8>) -> Result<()> {
+        let name_index = self.utf8_index(name)?;
+        self.attributes.set(name_index, name, data);
+        Ok(())
+    }
+
+    /// Removes every class attribute called `name`, returning whether there
+    /// was one.
+    pub fn remove_attribute(&mut self, name: &str) -> bool {
+        self.attributes.remove(name)
+    }
+
+    /// Like [`Self::set_attribute`], for the attributes of field `field`.
+    pub fn set_field_attribute(&mut self, field: usize, name: &str, data: Vec<u8>) -> Result<()> {
+        if field >= self.fields.len() {
+            return Err(Error::WriteError(format!("no field {}", field)));
+        }
+        let name_index = self.utf8_index(name)?;
+        self.fields[field].attributes.set(name_index, name, data);
+        Ok(())
+    }
+
+    pub fn remove_field_attribute(&mut self, field: usize, name: &str) -> bool {
+        self.fields.get_mut(field).is_some_and(|x| x.attributes.remove(name))
+    }
+
+    /// Like [`Self::set_attribute`], for the attributes of method `method`.
+    pub fn set_method_attribute(&mut self, method: usize, name: &str, data: Vec<u8>) -> Result<()> {
+        if method >= self.methods.len() {
+            return Err(Error::WriteError(format!("no method {}", method)));
+        }
+        let name_index = self.utf8_index(name)?;
+        self.methods[method].attributes.set(name_index, name, data);
+        Ok(())
+    }
+
+    pub fn remove_method_attribute(&mut self, method: usize, name: &str) -> bool {
+        self.methods.get_mut(method).is_some_and(|x| x.attributes.remove(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ClassFile;
+
+    const EXAMPLE: &[u8] = include_bytes!("../example.class");
+
+    #[test]
+    fn unmodified_class_round_trips() {
+        let class_file = ClassFile::parse(EXAMPLE).unwrap();
+        assert_eq!(class_file.to_bytes().unwrap(), EXAMPLE);
+    }
+
+    #[test]
+    fn edited_class_round_trips() {
+        let mut class_file = ClassFile::parse(EXAMPLE).unwrap();
+        class_file.set_version(52, 0);
+        class_file.set_method_attribute(0, "Deprecated", Vec::new()).unwrap();
+        let bytes = class_file.to_bytes().unwrap();
+
+        let written = ClassFile::parse(&bytes[..]).unwrap();
+        assert_eq!(written.major_version(), 52);
+        assert!(written.methods()[0].raw_attributes().iter().any(|x| x.name == "Deprecated"));
+        assert_eq!(written.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn attribute_of_a_missing_member_adds_nothing_to_the_pool() {
+        let mut class_file = ClassFile::parse(EXAMPLE).unwrap();
+        let before = class_file.constant_pool.0.len();
+        assert!(class_file.set_field_attribute(usize::MAX, "Unused", Vec::new()).is_err());
+        assert!(class_file.set_method_attribute(usize::MAX, "Unused", Vec::new()).is_err());
+        assert_eq!(class_file.constant_pool.0.len(), before);
+    }
+}